@@ -0,0 +1,88 @@
+//! Abbreviations expanded when a space is typed after them or when the line
+//! is accepted (like fish `abbr`)
+use std::collections::HashMap;
+
+/// Dynamic expansion of abbreviations, registered with
+/// [`Editor::add_abbreviation_expander`](crate::Editor::add_abbreviation_expander)
+/// and consulted for the words which are not in the abbreviation table.
+///
+/// ```
+/// let mut rl = rustyline::Editor::<()>::new();
+/// rl.add_abbreviation("gco", "git checkout");
+/// // `!n` expanded to `n` dashes
+/// rl.add_abbreviation_expander(|word: &str| {
+///     let n = word.strip_prefix('!')?.parse().ok()?;
+///     Some("-".repeat(n))
+/// });
+/// ```
+pub trait Abbreviator {
+    /// Return the expansion of `word` (the whitespace-delimited word before
+    /// the cursor), if it is an abbreviation.
+    fn expand(&self, word: &str) -> Option<String>;
+}
+
+impl<F: Fn(&str) -> Option<String>> Abbreviator for F {
+    fn expand(&self, word: &str) -> Option<String> {
+        self(word)
+    }
+}
+
+/// Abbreviation table and expanders of an editor
+#[derive(Default)]
+pub(crate) struct Abbreviations {
+    table: HashMap<String, String>,
+    expanders: Vec<Box<dyn Abbreviator + Send + Sync>>,
+}
+
+impl Abbreviations {
+    pub fn insert(&mut self, abbreviation: &str, expansion: &str) -> Option<String> {
+        self.table
+            .insert(abbreviation.to_owned(), expansion.to_owned())
+    }
+
+    pub fn remove(&mut self, abbreviation: &str) -> Option<String> {
+        self.table.remove(abbreviation)
+    }
+
+    pub fn add_expander(&mut self, expander: Box<dyn Abbreviator + Send + Sync>) {
+        self.expanders.push(expander);
+    }
+
+    pub fn clear_expanders(&mut self) {
+        self.expanders.clear();
+    }
+
+    /// Expansion of `word`: from the table or else from the first expander
+    /// which knows it
+    pub fn expand(&self, word: &str) -> Option<String> {
+        if word.is_empty() {
+            return None;
+        }
+        self.table
+            .get(word)
+            .cloned()
+            .or_else(|| self.expanders.iter().find_map(|e| e.expand(word)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Abbreviations;
+
+    #[test]
+    fn expand() {
+        let mut abbreviations = Abbreviations::default();
+        assert_eq!(None, abbreviations.expand("gco"));
+        abbreviations.insert("gco", "git checkout");
+        abbreviations.add_expander(Box::new(|word: &str| {
+            word.strip_prefix('@').map(str::to_uppercase)
+        }));
+        assert_eq!(Some("git checkout".to_owned()), abbreviations.expand("gco"));
+        assert_eq!(Some("HOME".to_owned()), abbreviations.expand("@home"));
+        assert_eq!(None, abbreviations.expand("ls"));
+        assert_eq!(Some("git checkout".to_owned()), abbreviations.remove("gco"));
+        assert_eq!(None, abbreviations.expand("gco"));
+        abbreviations.clear_expanders();
+        assert_eq!(None, abbreviations.expand("@home"));
+    }
+}
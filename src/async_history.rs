@@ -0,0 +1,355 @@
+//! History shared through an asynchronous provider (e.g. a network service)
+//!
+//! An [`AsyncHistory`] keeps the entries in a local [`History`] cache: the
+//! editor only ever reads the cache, which is refreshed from the
+//! [`HistoryProvider`] by [`refresh`](AsyncHistory::refresh) (or
+//! [`sync`](HistoryStore::sync)), and added entries are pushed to the
+//! provider as they are added to the cache.
+//!
+//! The provider futures are polled by the calling thread for at most
+//! [`timeout`](AsyncHistory::set_timeout): a slow provider does not block the
+//! editor, which goes on with the cache while the requests are still pending
+//! (they are polled again by the next operations). As there is no runtime,
+//! a provider relying on one (e.g. for its network I/O) should spawn its
+//! requests on it and return futures of their outcome (e.g. a oneshot
+//! channel receiver).
+//!
+//! The cache can be persisted with `load` / `save` / `append`, for example to
+//! start with the entries of the last session while the provider is
+//! unreachable.
+use std::borrow::Cow;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant, SystemTime};
+
+use log::warn;
+
+use super::Result;
+use crate::config::Config;
+use crate::history::{Direction, EntryMetadata, History, HistoryStore};
+
+/// Outcome of a [`HistoryProvider`] request
+pub type ProviderFuture<T> = Pin<Box<dyn Future<Output = Result<T>> + Send>>;
+
+/// Asynchronous access to a shared history.
+///
+/// ```
+/// use std::future::ready;
+/// use std::sync::{Arc, Mutex};
+///
+/// use rustyline::async_history::{AsyncHistory, HistoryProvider, ProviderFuture};
+/// use rustyline::history::HistoryStore;
+///
+/// #[derive(Clone, Default)]
+/// struct InMemory(Arc<Mutex<Vec<String>>>);
+///
+/// impl HistoryProvider for InMemory {
+///     fn fetch(&self) -> ProviderFuture<Vec<String>> {
+///         Box::pin(ready(Ok(self.0.lock().unwrap().clone())))
+///     }
+///
+///     fn push(&self, line: String) -> ProviderFuture<()> {
+///         self.0.lock().unwrap().push(line);
+///         Box::pin(ready(Ok(())))
+///     }
+/// }
+///
+/// let provider = InMemory::default();
+/// let mut one = AsyncHistory::new(provider.clone());
+/// let mut two = AsyncHistory::new(provider);
+/// one.add("cargo build");
+/// assert!(two.refresh()?);
+/// assert_eq!(Some("cargo build".into()), two.entry(0));
+/// # Ok::<(), rustyline::error::ReadlineError>(())
+/// ```
+pub trait HistoryProvider {
+    /// Return all the entries of the shared history, oldest first.
+    fn fetch(&self) -> ProviderFuture<Vec<String>>;
+
+    /// Add `line` to the shared history.
+    fn push(&self, line: String) -> ProviderFuture<()>;
+}
+
+/// Local cache of a history shared through a [`HistoryProvider`]
+pub struct AsyncHistory<P: HistoryProvider> {
+    provider: P,
+    cache: History,
+    timeout: Duration,
+    /// Entries whose push is still pending
+    pushes: Vec<(String, ProviderFuture<()>)>,
+    /// Pending fetch (started after the last completed push)
+    fetch: Option<ProviderFuture<Vec<String>>>,
+}
+
+impl<P: HistoryProvider> AsyncHistory<P> {
+    /// Empty cache of the history shared through `provider` (see
+    /// [`refresh`](Self::refresh))
+    pub fn new(provider: P) -> Self {
+        Self {
+            provider,
+            cache: History::new(),
+            timeout: Duration::from_millis(50),
+            pushes: Vec::new(),
+            fetch: None,
+        }
+    }
+
+    /// The shared history provider
+    pub fn provider(&self) -> &P {
+        &self.provider
+    }
+
+    /// Set how long the provider requests are waited for before the cache is
+    /// used without them.
+    ///
+    /// By default, 50 ms.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Return the number of entries not pushed to the provider yet.
+    pub fn pending(&self) -> usize {
+        self.pushes.len()
+    }
+
+    /// Replace the cache content by the entries of the provider (the ones
+    /// not pushed yet being kept last): return `false` if the provider did
+    /// not answer in time, the cache being refreshed once it does by a later
+    /// call.
+    pub fn refresh(&mut self) -> Result<bool> {
+        self.poll_pushes(Duration::from_secs(0));
+        let provider = &self.provider;
+        let fetch = self.fetch.get_or_insert_with(|| provider.fetch());
+        let entries = match poll_until(fetch, self.timeout) {
+            Some(result) => {
+                self.fetch = None;
+                result?
+            }
+            None => return Ok(false),
+        };
+        self.cache.clear();
+        for entry in entries {
+            self.cache.add(entry);
+        }
+        for (line, _) in &self.pushes {
+            self.cache.add(line.as_str());
+        }
+        Ok(true)
+    }
+
+    /// Poll the pending pushes for at most `timeout`
+    fn poll_pushes(&mut self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        let pending = self.pushes.len();
+        self.pushes.retain_mut(|(line, push)| {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match poll_until(push, timeout) {
+                Some(Ok(())) => false,
+                Some(Err(err)) => {
+                    warn!(target: "rustyline", "cannot push history entry {:?}: {}", line, err);
+                    false
+                }
+                None => true,
+            }
+        });
+        if self.pushes.len() < pending {
+            // the pending fetch may miss the pushed entries
+            self.fetch = None;
+        }
+    }
+}
+
+impl<P: HistoryProvider> HistoryStore for AsyncHistory<P> {
+    fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    fn entry(&self, index: usize) -> Option<Cow<'_, str>> {
+        self.cache.get(index).map(Cow::from)
+    }
+
+    fn timestamp(&self, index: usize) -> Option<SystemTime> {
+        self.cache.timestamp(index)
+    }
+
+    fn metadata(&self, index: usize) -> Option<Cow<'_, EntryMetadata>> {
+        self.cache.metadata(index).map(Cow::Borrowed)
+    }
+
+    fn set_metadata(&mut self, index: usize, key: &str, value: &str) -> bool {
+        HistoryStore::set_metadata(&mut self.cache, index, key, value)
+    }
+
+    /// Add `line` to the cache and push it to the provider.
+    fn add(&mut self, line: &str) -> bool {
+        if !self.cache.add(line) {
+            return false;
+        }
+        let push = self.provider.push(line.to_owned());
+        self.pushes.push((line.to_owned(), push));
+        self.poll_pushes(self.timeout);
+        true
+    }
+
+    /// Clear the cache: the shared history is left untouched.
+    fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    fn search(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
+        self.cache.search(term, start, dir)
+    }
+
+    fn starts_with(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
+        self.cache.starts_with(term, start, dir)
+    }
+
+    fn load(&mut self, path: &Path) -> Result<()> {
+        self.cache.load(path)
+    }
+
+    fn save(&mut self, path: &Path) -> Result<()> {
+        self.cache.save(path)
+    }
+
+    fn append(&mut self, path: &Path) -> Result<()> {
+        self.cache.append(path)
+    }
+
+    /// Refresh the cache and save it at `path`.
+    fn sync(&mut self, path: &Path) -> Result<()> {
+        if self.refresh()? {
+            self.cache.save(path)
+        } else {
+            self.cache.append(path)
+        }
+    }
+
+    fn configure(&mut self, config: &Config) {
+        HistoryStore::configure(&mut self.cache, config);
+    }
+}
+
+/// Wake up the thread polling a future
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Poll `future` until it completes or `timeout` expires
+fn poll_until<T>(future: &mut ProviderFuture<T>, timeout: Duration) -> Option<Result<T>> {
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Poll::Ready(result) = future.as_mut().poll(&mut cx) {
+            return Some(result);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return None;
+        }
+        thread::park_timeout(deadline - now);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::{ready, Future};
+    use std::pin::Pin;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use super::{AsyncHistory, HistoryProvider, ProviderFuture};
+    use crate::history::HistoryStore;
+
+    /// Provider answering once `ready` is set
+    #[derive(Clone, Default)]
+    struct Slow {
+        entries: Arc<Mutex<Vec<String>>>,
+        ready: Arc<AtomicBool>,
+    }
+
+    struct Wait<T>(Arc<AtomicBool>, Option<T>);
+
+    impl<T: Unpin> Future for Wait<T> {
+        type Output = crate::Result<T>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            if self.0.load(Ordering::SeqCst) {
+                Poll::Ready(Ok(self.1.take().unwrap()))
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    impl HistoryProvider for Slow {
+        fn fetch(&self) -> ProviderFuture<Vec<String>> {
+            let entries = self.entries.lock().unwrap().clone();
+            Box::pin(Wait(self.ready.clone(), Some(entries)))
+        }
+
+        fn push(&self, line: String) -> ProviderFuture<()> {
+            let entries = self.entries.clone();
+            let push = Wait(self.ready.clone(), Some(()));
+            Box::pin(async move {
+                push.await?;
+                entries.lock().unwrap().push(line);
+                Ok(())
+            })
+        }
+    }
+
+    fn entries<S: HistoryStore>(history: &S) -> Vec<String> {
+        history.entries().map(|entry| entry.into_owned()).collect()
+    }
+
+    #[test]
+    fn slow_provider() -> crate::Result<()> {
+        let provider = Slow::default();
+        provider.entries.lock().unwrap().push("ls".to_owned());
+        let mut history = AsyncHistory::new(provider.clone());
+        history.set_timeout(Duration::from_millis(10));
+        assert!(!history.refresh()?);
+        assert!(history.add("make"));
+        assert_eq!(1, history.pending());
+        assert_eq!(vec!["make"], entries(&history));
+
+        provider.ready.store(true, Ordering::SeqCst);
+        assert!(history.refresh()?);
+        assert_eq!(0, history.pending());
+        assert_eq!(vec!["ls", "make"], entries(&history));
+        assert_eq!(vec!["ls", "make"], *provider.entries.lock().unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn failing_provider() {
+        struct Failing;
+        impl HistoryProvider for Failing {
+            fn fetch(&self) -> ProviderFuture<Vec<String>> {
+                Box::pin(ready(Err(std::io::Error::other("unreachable").into())))
+            }
+
+            fn push(&self, _: String) -> ProviderFuture<()> {
+                Box::pin(ready(Err(std::io::Error::other("unreachable").into())))
+            }
+        }
+        let mut history = AsyncHistory::new(Failing);
+        assert!(history.add("ls"));
+        assert_eq!(0, history.pending());
+        assert!(history.refresh().is_err());
+        assert_eq!(vec!["ls"], entries(&history));
+    }
+}
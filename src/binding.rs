@@ -1,9 +1,14 @@
 /// Custom event handlers
+use crate::keys::{MouseEvent, MouseEventKind};
 use crate::{
     Cmd, EditMode, InputMode, InputState, KeyCode, KeyEvent, Modifiers, Refresher, RepeatCount,
 };
 
-use radix_trie::TrieKey;
+use std::collections::{HashMap, VecDeque};
+use std::iter;
+use std::sync::Arc;
+
+use radix_trie::{Trie, TrieCommon, TrieKey};
 use smallvec::{smallvec, SmallVec};
 
 /// Input event
@@ -15,17 +20,24 @@ pub enum Event {
     /// Key sequence
     // TODO Validate 2 ?
     KeySeq(SmallVec<[KeyEvent; 2]>),
-    /// TODO Mouse event
-    Mouse(),
+    /// Mouse event: bindings match the kind and modifiers only, the handler
+    /// receives the position.
+    Mouse(MouseEvent),
 }
 
 impl Event {
     /// See [`KeyEvent::normalize`]
     pub(crate) fn normalize(mut self) -> Self {
-        if let Event::KeySeq(ref mut keys) = self {
-            for key in keys.iter_mut() {
-                *key = KeyEvent::normalize(*key);
+        match self {
+            Event::KeySeq(ref mut keys) => {
+                for key in keys.iter_mut() {
+                    *key = KeyEvent::normalize(*key);
+                }
             }
+            Event::Mouse(ref mut mouse) => {
+                *mouse = MouseEvent::new(mouse.kind, mouse.modifiers);
+            }
+            Event::Any => {}
         }
         self
     }
@@ -38,11 +50,42 @@ impl Event {
             None
         }
     }
+
+    /// Append `key` to a key sequence
+    pub(crate) fn push(&mut self, key: KeyEvent) {
+        if let Event::KeySeq(ref mut ks) = self {
+            ks.push(key);
+        }
+    }
+
+    /// Remove the last key of a key sequence
+    pub(crate) fn pop(&mut self) -> Option<KeyEvent> {
+        if let Event::KeySeq(ref mut ks) = self {
+            ks.pop()
+        } else {
+            None
+        }
+    }
 }
 
 impl From<KeyEvent> for Event {
     fn from(k: KeyEvent) -> Event {
-        Event::KeySeq(smallvec![k])
+        match k {
+            KeyEvent(KeyCode::Mouse(mouse), _) => Event::Mouse(mouse),
+            k => Event::KeySeq(smallvec![k]),
+        }
+    }
+}
+
+impl From<MouseEvent> for Event {
+    fn from(mouse: MouseEvent) -> Event {
+        Event::Mouse(mouse)
+    }
+}
+
+impl From<MouseEventKind> for Event {
+    fn from(kind: MouseEventKind) -> Event {
+        Event::Mouse(MouseEvent::from(kind))
     }
 }
 
@@ -50,6 +93,7 @@ const BASE: u32 = 0x0010ffff + 1;
 const BASE_CONTROL: u32 = 0x02000000;
 const BASE_META: u32 = 0x04000000;
 const BASE_SHIFT: u32 = 0x01000000;
+const BASE_KEYPAD: u32 = 0x08000000;
 const ESCAPE: u32 = 27;
 const PAGE_UP: u32 = BASE + 1;
 const PAGE_DOWN: u32 = PAGE_UP + 1;
@@ -65,7 +109,9 @@ const INSERT: u32 = DELETE + 1;
 const MOUSE: u32 = /* F24 + 1 */ INSERT + 25;
 const PASTE_START: u32 = MOUSE + 1;
 const PASTE_FINISH: u32 = PASTE_START + 1;
-const ANY: u32 = PASTE_FINISH + 1;
+const FOCUS_GAINED: u32 = PASTE_FINISH + 1;
+const FOCUS_LOST: u32 = FOCUS_GAINED + 1;
+const ANY: u32 = FOCUS_LOST + 1;
 
 impl KeyEvent {
     fn encode(&self) -> u32 {
@@ -81,10 +127,13 @@ impl KeyEvent {
             KeyCode::End => END,
             KeyCode::Enter => u32::from('\r'),
             KeyCode::F(i) => INSERT + i as u32,
+            KeyCode::FocusGained => FOCUS_GAINED,
+            KeyCode::FocusLost => FOCUS_LOST,
             KeyCode::Esc => ESCAPE,
             KeyCode::Home => HOME,
             KeyCode::Insert => INSERT,
             KeyCode::Left => LEFT,
+            KeyCode::Mouse(_) => MOUSE,
             KeyCode::Null => 0,
             KeyCode::PageDown => PAGE_DOWN,
             KeyCode::PageUp => PAGE_UP,
@@ -101,10 +150,29 @@ impl KeyEvent {
         if self.1.contains(Modifiers::SHIFT) {
             u |= BASE_SHIFT;
         }
+        if self.1.contains(Modifiers::KEYPAD) {
+            u |= BASE_KEYPAD;
+        }
         u
     }
 }
 
+fn mouse_kind(kind: MouseEventKind) -> [u8; 2] {
+    use crate::keys::MouseButton;
+    let button = |button| match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+    };
+    match kind {
+        MouseEventKind::Down(b) => [0, button(b)],
+        MouseEventKind::Up => [1, 0],
+        MouseEventKind::Drag(b) => [2, button(b)],
+        MouseEventKind::ScrollUp => [3, 0],
+        MouseEventKind::ScrollDown => [4, 0],
+    }
+}
+
 impl TrieKey for Event {
     fn encode_bytes(&self) -> Vec<u8> {
         match self {
@@ -116,7 +184,12 @@ impl TrieKey for Event {
                 }
                 dst
             }
-            Event::Mouse() => MOUSE.to_be_bytes().to_vec(),
+            Event::Mouse(mouse) => {
+                let mut dst = MOUSE.to_be_bytes().to_vec();
+                dst.extend_from_slice(&mouse_kind(mouse.kind));
+                dst.push(mouse.modifiers.bits());
+                dst
+            }
         }
     }
 }
@@ -127,8 +200,8 @@ pub enum EventHandler {
     Simple(Cmd),
     /// handler behaviour depends on input state
     Conditional(Box<dyn ConditionalEventHandler>),
-    /* invoke multiple actions
-     * TODO Macro(), */
+    /// invoke multiple actions, one after the other
+    Macro(Vec<Cmd>),
 }
 
 impl From<Cmd> for EventHandler {
@@ -137,18 +210,344 @@ impl From<Cmd> for EventHandler {
     }
 }
 
+/// Description of a custom binding, as returned by
+/// [`Editor::bindings`](crate::Editor::bindings) and
+/// [`Editor::lookup`](crate::Editor::lookup)
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Binding {
+    /// unconditional command
+    Command(Cmd),
+    /// handler behaviour depends on input state
+    Conditional,
+    /// multiple actions, one after the other
+    Macro(Vec<Cmd>),
+}
+
+impl From<&EventHandler> for Binding {
+    fn from(handler: &EventHandler) -> Binding {
+        match handler {
+            EventHandler::Simple(cmd) => Binding::Command(cmd.clone()),
+            EventHandler::Conditional(_) => Binding::Conditional,
+            EventHandler::Macro(cmds) => Binding::Macro(cmds.clone()),
+        }
+    }
+}
+
+/// Keymap: a set of key bindings that is active only in some editing
+/// mode(s).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Keymap {
+    /// emacs
+    Emacs,
+    /// vi-command (or vi-move): vi command mode
+    ViCommand,
+    /// vi-insert: vi insert (and replace) mode
+    ViInsert,
+    /// Mode registered by the application under this name (see
+    /// [`CustomMode`](crate::CustomMode))
+    Custom(&'static str),
+}
+
+/// Layer of key bindings.
+///
+/// Bindings of a layer with a higher precedence (see
+/// [`Editor::set_layer_order`](crate::Editor::set_layer_order)) shadow the
+/// ones of lower layers without replacing them: removing the former reveals
+/// the latter.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Layer {
+    /// Bindings made by the application ([`Editor::bind_sequence`](crate::Editor::bind_sequence))
+    Application,
+    /// Bindings read from inputrc files
+    User,
+    /// Built-in emacs / vi bindings, plus the bindings the application
+    /// registers as defaults
+    Defaults,
+}
+
+/// Custom bindings of one layer.
+///
+/// Global bindings are active in every keymap but keymap specific bindings
+/// take precedence.
+#[derive(Default)]
+struct LayerBindings {
+    global: Trie<Event, EventHandler>,
+    keymaps: HashMap<Keymap, Trie<Event, EventHandler>>,
+}
+
+impl LayerBindings {
+    /// Keymap specific trie then global one, with their keymap (`None` when
+    /// global)
+    fn sourced_tries(
+        &self,
+        keymap: Keymap,
+    ) -> impl Iterator<Item = (Option<Keymap>, &Trie<Event, EventHandler>)> {
+        self.keymaps
+            .get(&keymap)
+            .map(|trie| (Some(keymap), trie))
+            .into_iter()
+            .chain(iter::once((None, &self.global)))
+    }
+}
+
+/// Where a custom binding is defined
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BindingSource {
+    /// Layer of the binding
+    pub layer: Layer,
+    /// Keymap of the binding, `None` for a global binding
+    pub keymap: Option<Keymap>,
+}
+
+/// Conflict between custom bindings (see
+/// [`Editor::binding_conflicts`](crate::Editor::binding_conflicts)).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BindingConflict {
+    /// `event` is bound more than once: only the binding from `active` is
+    /// used, the ones from `shadowed` (by decreasing precedence) are hidden.
+    Shadowed {
+        /// Bound event
+        event: Event,
+        /// Binding which is used
+        active: BindingSource,
+        /// Bindings which are hidden
+        shadowed: Vec<BindingSource>,
+    },
+    /// `prefix` is bound and is also the beginning of the longer `sequences`:
+    /// after `prefix` is typed, its command only runs once `keyseq-timeout`
+    /// expires (or never if there is no timeout).
+    Prefix {
+        /// Bound key sequence
+        prefix: Event,
+        /// Bound key sequences starting with `prefix`
+        sequences: Vec<Event>,
+    },
+}
+
+/// Custom bindings, by layer.
+pub(crate) struct Bindings {
+    layers: HashMap<Layer, LayerBindings>,
+    /// Enabled layers, highest precedence first
+    order: Vec<Layer>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            layers: HashMap::new(),
+            order: vec![Layer::Application, Layer::User, Layer::Defaults],
+        }
+    }
+}
+
+impl Bindings {
+    pub fn insert(
+        &mut self,
+        layer: Layer,
+        keymap: Option<Keymap>,
+        evt: Event,
+        handler: EventHandler,
+    ) -> Option<EventHandler> {
+        let layer = self.layers.entry(layer).or_default();
+        match keymap {
+            Some(keymap) => layer
+                .keymaps
+                .entry(keymap)
+                .or_default()
+                .insert(evt, handler),
+            None => layer.global.insert(evt, handler),
+        }
+    }
+
+    pub fn remove(
+        &mut self,
+        layer: Layer,
+        keymap: Option<Keymap>,
+        evt: &Event,
+    ) -> Option<EventHandler> {
+        let layer = self.layers.get_mut(&layer)?;
+        match keymap {
+            Some(keymap) => layer.keymaps.get_mut(&keymap)?.remove(evt),
+            None => layer.global.remove(evt),
+        }
+    }
+
+    /// Remove all the bindings of `layer`
+    pub fn clear(&mut self, layer: Layer) {
+        self.layers.remove(&layer);
+    }
+
+    /// Enabled layers, highest precedence first
+    pub fn order(&self) -> &[Layer] {
+        &self.order
+    }
+
+    pub fn set_order(&mut self, layers: &[Layer]) {
+        self.order.clear();
+        for layer in layers {
+            if !self.order.contains(layer) {
+                self.order.push(*layer);
+            }
+        }
+    }
+
+    /// Tell if the built-in bindings are enabled
+    pub fn has_defaults(&self) -> bool {
+        self.order.contains(&Layer::Defaults)
+    }
+
+    /// Handler bound to `evt` in `keymap` or globally
+    pub fn get(&self, keymap: Keymap, evt: &Event) -> Option<&EventHandler> {
+        self.tries(keymap).find_map(|trie| trie.get(evt))
+    }
+
+    /// Tell if `evt` is (the prefix of) a sequence bound in `keymap` or
+    /// globally
+    #[cfg(test)]
+    pub fn has_descendant(&self, keymap: Keymap, evt: &Event) -> bool {
+        self.tries(keymap)
+            .any(|trie| trie.get_raw_descendant(evt).is_some())
+    }
+
+    /// Tell if `evt` is the prefix of a longer sequence bound in `keymap` or
+    /// globally
+    pub fn is_prefix(&self, keymap: Keymap, evt: &Event) -> bool {
+        self.tries(keymap)
+            .any(|trie| match trie.get_raw_descendant(evt) {
+                Some(descendants) => (&descendants).len() > usize::from(trie.get(evt).is_some()),
+                None => false,
+            })
+    }
+
+    /// Clear the [`Modifiers::KEYPAD`] flag of the last key of `evt` unless
+    /// `evt` is bound (or is the prefix of a bound sequence) with it: keys
+    /// sent in application keypad mode fall back to the bindings of the
+    /// normal keys.
+    pub fn keypad_fallback(&self, keymap: Keymap, evt: &mut Event) {
+        let keypad = match evt {
+            Event::KeySeq(keys) => {
+                matches!(keys.last(), Some(KeyEvent(_, mods)) if mods.contains(Modifiers::KEYPAD))
+            }
+            _ => false,
+        };
+        if !keypad || self.get(keymap, evt).is_some() || self.is_prefix(keymap, evt) {
+            return;
+        }
+        if let Event::KeySeq(keys) = evt {
+            if let Some(KeyEvent(_, mods)) = keys.last_mut() {
+                mods.remove(Modifiers::KEYPAD);
+            }
+        }
+    }
+
+    /// Bindings active in `keymap`: keymap specific ones then the global ones
+    /// which are not overridden, by layer
+    pub fn iter(&self, keymap: Keymap) -> Vec<(&Event, &EventHandler)> {
+        let tries: Vec<_> = self.tries(keymap).collect();
+        let mut bindings = Vec::new();
+        for (i, trie) in tries.iter().enumerate() {
+            let shadowing = &tries[..i];
+            bindings.extend(
+                trie.iter()
+                    .filter(|(evt, _)| shadowing.iter().all(|trie| trie.get(*evt).is_none())),
+            );
+        }
+        bindings
+    }
+
+    /// Shadowed bindings and prefix conflicts in `keymap`
+    pub fn conflicts(&self, keymap: Keymap) -> Vec<BindingConflict> {
+        // active events, in precedence order, with all their sources
+        let mut events: Vec<(&Event, Vec<BindingSource>)> = Vec::new();
+        let mut index: HashMap<&Event, usize> = HashMap::new();
+        for (source, trie) in self.sourced_tries(keymap) {
+            for (evt, _) in trie.iter() {
+                match index.get(evt) {
+                    Some(&i) => events[i].1.push(source),
+                    None => {
+                        index.insert(evt, events.len());
+                        events.push((evt, vec![source]));
+                    }
+                }
+            }
+        }
+        let mut conflicts = Vec::new();
+        for (evt, sources) in &events {
+            if sources.len() > 1 {
+                conflicts.push(BindingConflict::Shadowed {
+                    event: (*evt).clone(),
+                    active: sources[0],
+                    shadowed: sources[1..].to_vec(),
+                });
+            }
+        }
+        for (prefix, _) in &events {
+            let prefix_keys = match prefix {
+                Event::KeySeq(keys) => keys,
+                _ => continue,
+            };
+            let sequences: Vec<Event> = events
+                .iter()
+                .filter(|(evt, _)| match evt {
+                    Event::KeySeq(keys) => {
+                        keys.len() > prefix_keys.len() && keys.starts_with(prefix_keys)
+                    }
+                    _ => false,
+                })
+                .map(|(evt, _)| (*evt).clone())
+                .collect();
+            if !sequences.is_empty() {
+                conflicts.push(BindingConflict::Prefix {
+                    prefix: (*prefix).clone(),
+                    sequences,
+                });
+            }
+        }
+        conflicts
+    }
+
+    fn tries(&self, keymap: Keymap) -> impl Iterator<Item = &Trie<Event, EventHandler>> {
+        self.sourced_tries(keymap).map(|(_, trie)| trie)
+    }
+
+    /// Enabled tries in precedence order, with their source
+    fn sourced_tries(
+        &self,
+        keymap: Keymap,
+    ) -> impl Iterator<Item = (BindingSource, &Trie<Event, EventHandler>)> {
+        self.order
+            .iter()
+            .filter_map(move |layer| self.layers.get(layer).map(|bindings| (*layer, bindings)))
+            .flat_map(move |(layer, bindings)| {
+                bindings
+                    .sourced_tries(keymap)
+                    .map(move |(keymap, trie)| (BindingSource { layer, keymap }, trie))
+            })
+    }
+}
+
 /// Give access to user input.
 pub struct EventContext<'r> {
     mode: EditMode,
     input_mode: InputMode,
+    completing: bool,
+    recent_cmds: &'r VecDeque<Cmd>,
+    pending_keys: &'r VecDeque<KeyEvent>,
     wrt: &'r dyn Refresher,
 }
 
 impl<'r> EventContext<'r> {
-    pub(crate) fn new(is: &InputState, wrt: &'r dyn Refresher) -> Self {
+    pub(crate) fn new(is: &'r InputState, wrt: &'r dyn Refresher) -> Self {
         EventContext {
             mode: is.mode,
             input_mode: is.input_mode,
+            completing: is.completing,
+            recent_cmds: &is.recent_cmds,
+            pending_keys: &is.pending_keys,
             wrt,
         }
     }
@@ -163,6 +562,12 @@ impl<'r> EventContext<'r> {
         self.input_mode
     }
 
+    /// Returns `true` while completion candidates are being cycled through
+    /// or listed (i.e. the key was pressed after `Tab`).
+    pub fn is_completing(&self) -> bool {
+        self.completing
+    }
+
     /// Returns `true` if there is a hint displayed.
     pub fn has_hint(&self) -> bool {
         self.wrt.has_hint()
@@ -182,6 +587,38 @@ impl<'r> EventContext<'r> {
     pub fn pos(&self) -> usize {
         self.wrt.pos()
     }
+
+    /// Previously executed command on the current line, if any.
+    ///
+    /// ```
+    /// use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+    ///
+    /// /// `yank-pop` only after a yank, like readline
+    /// struct YankPop;
+    /// impl ConditionalEventHandler for YankPop {
+    ///     fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+    ///         match ctx.last_command() {
+    ///             Some(Cmd::Yank(..)) | Some(Cmd::YankPop) => Some(Cmd::YankPop),
+    ///             _ => Some(Cmd::Noop),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn last_command(&self) -> Option<&Cmd> {
+        self.recent_cmds.front()
+    }
+
+    /// Last executed commands on the current line, most recent first (only
+    /// the last sixteen are kept).
+    pub fn command_history(&self) -> impl Iterator<Item = &Cmd> {
+        self.recent_cmds.iter()
+    }
+
+    /// Keys already typed but not handled yet (e.g. keys read while
+    /// resolving a key sequence but not part of it).
+    pub fn pending_keys(&self) -> impl Iterator<Item = &KeyEvent> {
+        self.pending_keys.iter()
+    }
 }
 
 /// May behave differently depending on:
@@ -192,7 +629,26 @@ impl<'r> EventContext<'r> {
 ///  * repeat count
 ///  * original key pressed (when same command is bound to different key)
 ///  * hint
+///  * completion in progress
 ///  * ...
+///
+/// For example, `Tab` can complete only when the cursor follows a word:
+/// ```
+/// use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+///
+/// struct TabAfterWord;
+/// impl ConditionalEventHandler for TabAfterWord {
+///     fn handle(&self, _: &Event, n: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+///         if ctx.is_completing() {
+///             return None; // next candidate
+///         }
+///         match ctx.line()[..ctx.pos()].chars().next_back() {
+///             Some(c) if !c.is_whitespace() => None, // complete
+///             _ => Some(Cmd::SelfInsert(n, '\t')),
+///         }
+///     }
+/// }
+/// ```
 pub trait ConditionalEventHandler: Send + Sync {
     /// Takes the current input state and
     /// returns the command to be performed or `None` to perform the default
@@ -206,9 +662,96 @@ pub trait ConditionalEventHandler: Send + Sync {
     ) -> Option<Cmd>;
 }
 
+/// Application specific named functions.
+///
+/// Registered functions can be bound from inputrc files exactly like
+/// readline's functions:
+/// ```
+/// use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, Movement, RepeatCount};
+///
+/// struct ExpandAlias;
+/// impl ConditionalEventHandler for ExpandAlias {
+///     fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+///         if ctx.line() == "ll" {
+///             Some(Cmd::Replace(Movement::WholeLine, Some("ls -l".to_owned())))
+///         } else {
+///             None
+///         }
+///     }
+/// }
+///
+/// let mut rl = rustyline::Editor::<()>::new();
+/// rl.functions_mut().register("my-app-expand-alias", ExpandAlias);
+/// rl.apply_inputrc(&rustyline::inputrc::parse("\"\\C-xa\": my-app-expand-alias"));
+/// ```
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Arc<dyn ConditionalEventHandler>>,
+}
+
+impl FunctionRegistry {
+    /// Empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `handler` under `name` (case insensitive), replacing any
+    /// previous function with the same name.
+    ///
+    /// Readline's functions with the same name are shadowed.
+    pub fn register<S: AsRef<str>, H: ConditionalEventHandler + 'static>(
+        &mut self,
+        name: S,
+        handler: H,
+    ) {
+        self.functions
+            .insert(name.as_ref().to_ascii_lowercase(), Arc::new(handler));
+    }
+
+    /// Remove the function registered under `name`.
+    ///
+    /// Returns `true` if there was such a function. Keys already bound to it
+    /// are not unbound.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.functions.remove(&name.to_ascii_lowercase()).is_some()
+    }
+
+    /// Tell if a function is registered under `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(&name.to_ascii_lowercase())
+    }
+
+    /// Registered function names (lowercase), in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.functions.keys().map(String::as_str)
+    }
+
+    /// Handler to bind to a key sequence
+    pub(crate) fn handler(&self, name: &str) -> Option<EventHandler> {
+        self.functions
+            .get(&name.to_ascii_lowercase())
+            .map(|function| EventHandler::Conditional(Box::new(Function(Arc::clone(function)))))
+    }
+}
+
+/// Registered function, shared by all the key sequences bound to it
+struct Function(Arc<dyn ConditionalEventHandler>);
+
+impl ConditionalEventHandler for Function {
+    fn handle(
+        &self,
+        evt: &Event,
+        n: RepeatCount,
+        positive: bool,
+        ctx: &EventContext,
+    ) -> Option<Cmd> {
+        self.0.handle(evt, n, positive, ctx)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Event, EventHandler};
+    use super::{BindingConflict, BindingSource, Bindings, Event, EventHandler, Keymap, Layer};
     use crate::{Cmd, KeyCode, KeyEvent, Modifiers};
     use radix_trie::Trie;
     use smallvec::smallvec;
@@ -241,4 +784,146 @@ mod test {
         trie.insert(E::from(K(C::Enter, M::CTRL)), H::from(Cmd::Noop));
         trie.insert(E::from(K(C::Tab, M::CTRL)), H::from(Cmd::Noop));
     }
+
+    #[test]
+    fn keymaps() {
+        let mut bindings = Bindings::default();
+        let evt = Event::from(KeyEvent::ctrl('X'));
+        bindings.insert(
+            Layer::Application,
+            None,
+            evt.clone(),
+            EventHandler::from(Cmd::Noop),
+        );
+        bindings.insert(
+            Layer::Application,
+            Some(Keymap::ViCommand),
+            evt.clone(),
+            EventHandler::from(Cmd::Abort),
+        );
+        assert!(matches!(
+            bindings.get(Keymap::ViCommand, &evt),
+            Some(EventHandler::Simple(Cmd::Abort))
+        ));
+        assert!(matches!(
+            bindings.get(Keymap::ViInsert, &evt),
+            Some(EventHandler::Simple(Cmd::Noop))
+        ));
+        let other = Event::from(KeyEvent::ctrl('Y'));
+        bindings.insert(
+            Layer::Application,
+            None,
+            other.clone(),
+            EventHandler::from(Cmd::Undo(1)),
+        );
+        let active = bindings.iter(Keymap::ViCommand);
+        assert_eq!(2, active.len());
+        assert!(matches!(active[0], (e, EventHandler::Simple(Cmd::Abort)) if *e == evt));
+        assert!(matches!(active[1], (e, EventHandler::Simple(Cmd::Undo(1))) if *e == other));
+        assert_eq!(2, bindings.iter(Keymap::Emacs).len());
+        assert!(bindings.remove(Layer::Application, None, &other).is_some());
+        assert!(bindings
+            .remove(Layer::Application, Some(Keymap::Emacs), &evt)
+            .is_none());
+        assert!(bindings.remove(Layer::Application, None, &evt).is_some());
+        assert!(bindings.get(Keymap::Emacs, &evt).is_none());
+        assert!(bindings.has_descendant(Keymap::ViCommand, &evt));
+        assert!(!bindings.is_prefix(Keymap::ViCommand, &evt));
+        let seq = Event::KeySeq(smallvec![KeyEvent::ctrl('X'), KeyEvent::from('a')]);
+        bindings.insert(Layer::Application, None, seq, EventHandler::from(Cmd::Noop));
+        assert!(bindings.is_prefix(Keymap::ViCommand, &evt));
+    }
+
+    #[test]
+    fn layers() {
+        let mut bindings = Bindings::default();
+        let evt = Event::from(KeyEvent::ctrl('X'));
+        bindings.insert(
+            Layer::User,
+            None,
+            evt.clone(),
+            EventHandler::from(Cmd::Abort),
+        );
+        bindings.insert(
+            Layer::Application,
+            Some(Keymap::Emacs),
+            evt.clone(),
+            EventHandler::from(Cmd::Noop),
+        );
+        assert!(matches!(
+            bindings.get(Keymap::Emacs, &evt),
+            Some(EventHandler::Simple(Cmd::Noop))
+        ));
+        assert_eq!(1, bindings.iter(Keymap::Emacs).len());
+        bindings.set_order(&[Layer::User, Layer::Application, Layer::User]);
+        assert_eq!(&[Layer::User, Layer::Application], bindings.order());
+        assert!(!bindings.has_defaults());
+        assert!(matches!(
+            bindings.get(Keymap::Emacs, &evt),
+            Some(EventHandler::Simple(Cmd::Abort))
+        ));
+        bindings.clear(Layer::User);
+        assert!(matches!(
+            bindings.get(Keymap::Emacs, &evt),
+            Some(EventHandler::Simple(Cmd::Noop))
+        ));
+        assert!(bindings.get(Keymap::ViInsert, &evt).is_none());
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut bindings = Bindings::default();
+        let ctrl_x = Event::from(KeyEvent::ctrl('X'));
+        let ctrl_x_ctrl_f = Event::KeySeq(smallvec![KeyEvent::ctrl('X'), KeyEvent::ctrl('F')]);
+        assert!(bindings.conflicts(Keymap::Emacs).is_empty());
+        bindings.insert(
+            Layer::User,
+            None,
+            ctrl_x.clone(),
+            EventHandler::from(Cmd::Abort),
+        );
+        bindings.insert(
+            Layer::Application,
+            Some(Keymap::Emacs),
+            ctrl_x.clone(),
+            EventHandler::from(Cmd::Noop),
+        );
+        bindings.insert(
+            Layer::Defaults,
+            None,
+            ctrl_x_ctrl_f.clone(),
+            EventHandler::from(Cmd::Noop),
+        );
+        assert_eq!(
+            vec![
+                BindingConflict::Shadowed {
+                    event: ctrl_x.clone(),
+                    active: BindingSource {
+                        layer: Layer::Application,
+                        keymap: Some(Keymap::Emacs),
+                    },
+                    shadowed: vec![BindingSource {
+                        layer: Layer::User,
+                        keymap: None,
+                    }],
+                },
+                BindingConflict::Prefix {
+                    prefix: ctrl_x.clone(),
+                    sequences: vec![ctrl_x_ctrl_f.clone()],
+                },
+            ],
+            bindings.conflicts(Keymap::Emacs)
+        );
+        // only the global binding is active in vi insert mode
+        assert_eq!(
+            vec![BindingConflict::Prefix {
+                prefix: ctrl_x.clone(),
+                sequences: vec![ctrl_x_ctrl_f],
+            }],
+            bindings.conflicts(Keymap::ViInsert)
+        );
+        // disabled layers are ignored
+        bindings.set_order(&[Layer::Application]);
+        assert!(bindings.conflicts(Keymap::Emacs).is_empty());
+    }
 }
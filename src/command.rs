@@ -1,12 +1,12 @@
 use std::sync::{Arc, Mutex};
 
 use crate::complete_hint_line;
-use crate::config::Config;
+use crate::config::{Config, HistorySearchMode};
 use crate::edit::State;
 use crate::error;
 use crate::history::Direction;
 use crate::keymap::{Anchor, At, Cmd, Movement, Word};
-use crate::keymap::{InputState, Refresher};
+use crate::keymap::{InputMode, InputState, Refresher};
 use crate::kill_ring::{KillRing, Mode};
 use crate::line_buffer::WordAction;
 use crate::{Helper, Result};
@@ -25,12 +25,34 @@ pub fn execute<H: Helper>(
 ) -> Result<Status> {
     use Status::*;
 
+    {
+        let mut kill_ring = kill_ring.lock().unwrap();
+        kill_ring.select_register(input_state.register);
+        if let Some(ref text) = input_state.inserted {
+            kill_ring.set_last_insert(text);
+        }
+    }
     match cmd {
         Cmd::CompleteHint => {
-            complete_hint_line(s)?;
+            complete_hint_line(s, false)?;
+        }
+        Cmd::CompleteHintWord => {
+            complete_hint_line(s, true)?;
         }
         Cmd::SelfInsert(n, c) => {
-            s.edit_insert(c, n)?;
+            if n > 1 || !s.edit_auto_pair(c, config)? {
+                s.edit_insert(c, n)?;
+            }
+        }
+        Cmd::Kill(Movement::BackwardChar(n))
+            if input_state.is_emacs_mode() && input_state.input_mode == InputMode::Replace =>
+        {
+            s.edit_overwrite_backspace(n)?;
+        }
+        Cmd::Kill(Movement::BackwardChar(1)) => {
+            if !s.edit_delete_pair(config)? {
+                s.edit_kill(&Movement::BackwardChar(1))?;
+            }
         }
         Cmd::Insert(n, text) => {
             s.edit_yank(&input_state, &text, Anchor::Before, n)?;
@@ -96,19 +118,25 @@ pub fn execute<H: Helper>(
         }
         Cmd::LineUpOrPreviousHistory(n) => {
             if !s.edit_move_line_up(n)? {
-                s.edit_history_next(true)?
+                edit_history_up_down(s, Direction::Reverse, config)?
             }
         }
         Cmd::LineDownOrNextHistory(n) => {
             if !s.edit_move_line_down(n)? {
-                s.edit_history_next(false)?
+                edit_history_up_down(s, Direction::Forward, config)?
             }
         }
         Cmd::HistorySearchBackward => s.edit_history_search(Direction::Reverse)?,
         Cmd::HistorySearchForward => s.edit_history_search(Direction::Forward)?,
-        Cmd::TransposeChars => {
+        Cmd::HistorySubstringSearchBackward => {
+            s.edit_history_substring_search(Direction::Reverse)?
+        }
+        Cmd::HistorySubstringSearchForward => {
+            s.edit_history_substring_search(Direction::Forward)?
+        }
+        Cmd::TransposeChars(n) => {
             // Exchange the char before cursor with the character at cursor.
-            s.edit_transpose_chars()?
+            s.edit_transpose_chars(n)?
         }
         Cmd::Yank(n, anchor) => {
             // retrieve (yank) last item killed
@@ -123,7 +151,10 @@ pub fn execute<H: Helper>(
                 kill_ring.kill(&text, Mode::Append)
             }
         }
-        Cmd::AcceptLine | Cmd::AcceptOrInsertLine { .. } | Cmd::Newline => {
+        Cmd::AcceptLine
+        | Cmd::AcceptOrInsertLine { .. }
+        | Cmd::Newline
+        | Cmd::OperateAndGetNext => {
             if s.has_hint() || !s.is_default_prompt() {
                 // Force a refresh without hints to leave the previous
                 // line as the user typed it after a newline.
@@ -134,6 +165,7 @@ pub fn execute<H: Helper>(
             let end = s.line.is_end_of_input();
             match (cmd, valid, end) {
                 (Cmd::AcceptLine, ..)
+                | (Cmd::OperateAndGetNext, ..)
                 | (Cmd::AcceptOrInsertLine { .. }, true, true)
                 | (
                     Cmd::AcceptOrInsertLine {
@@ -154,6 +186,14 @@ pub fn execute<H: Helper>(
                 _ => unreachable!(),
             }
         }
+        Cmd::InsertComment { toggle } => {
+            s.edit_insert_comment(config.comment_begin(), toggle)?;
+            // like readline, the line is accepted without validation
+            if s.has_hint() || !s.is_default_prompt() {
+                s.refresh_line_with_msg(None)?;
+            }
+            return Ok(Submit);
+        }
         Cmd::BeginningOfHistory => {
             // move to first entry in history
             s.edit_history(true)?
@@ -166,13 +206,17 @@ pub fn execute<H: Helper>(
             // move backwards one word
             s.edit_move_to_prev_word(word_def, n)?
         }
-        Cmd::CapitalizeWord => {
+        Cmd::CapitalizeWord(n) => {
             // capitalize word after point
-            s.edit_word(WordAction::Capitalize)?
+            s.edit_word(WordAction::Capitalize, n)?
         }
         Cmd::Kill(ref mvt) => {
             s.edit_kill(mvt)?;
         }
+        Cmd::DeleteCharOrList => {
+            // the possible completions are listed before at the end of the line
+            s.edit_delete(1)?
+        }
         Cmd::Move(Movement::ForwardWord(n, at, word_def)) => {
             // move forwards one word
             s.edit_move_to_next_word(at, word_def, n)?
@@ -191,17 +235,17 @@ pub fn execute<H: Helper>(
             // Move to the end of the buffer.
             s.edit_move_buffer_end()?
         }
-        Cmd::DowncaseWord => {
+        Cmd::DowncaseWord(n) => {
             // lowercase word after point
-            s.edit_word(WordAction::Lowercase)?
+            s.edit_word(WordAction::Lowercase, n)?
         }
         Cmd::TransposeWords(n) => {
             // transpose words
             s.edit_transpose_words(n)?
         }
-        Cmd::UpcaseWord => {
+        Cmd::UpcaseWord(n) => {
             // uppercase word after point
-            s.edit_word(WordAction::Uppercase)?
+            s.edit_word(WordAction::Uppercase, n)?
         }
         Cmd::YankPop => {
             // yank-pop
@@ -210,12 +254,31 @@ pub fn execute<H: Helper>(
                 s.edit_yank_pop(yank_size, text)?
             }
         }
+        Cmd::YankLastArg(n) => s.edit_yank_arg(n, true, config.word_boundary())?,
+        Cmd::YankNthArg(n) => s.edit_yank_arg(n, false, config.word_boundary())?,
+        Cmd::ViOpenLine(anchor) => s.edit_open_line(anchor)?,
+        Cmd::DabbrevExpand => s.edit_dabbrev_expand(config.word_boundary())?,
+        Cmd::HistoryExpandLine => s.edit_history_expand(config.hist_chars())?,
+        Cmd::MagicSpace => {
+            if config.history_expansion() {
+                s.edit_history_expand(config.hist_chars())?;
+            }
+            s.edit_insert(' ', 1)?
+        }
         Cmd::Move(Movement::ViCharSearch(n, cs)) => s.edit_move_to(cs, n)?,
         Cmd::Undo(n) => {
             if s.changes.borrow_mut().undo(&mut s.line, n) {
                 s.refresh_line()?;
             }
         }
+        Cmd::Redo(n) => {
+            if s.changes.borrow_mut().redo(&mut s.line, n) {
+                s.refresh_line()?;
+            }
+        }
+        Cmd::RevertLine => s.edit_revert_line()?,
+        Cmd::SetMark => s.edit_set_mark()?,
+        Cmd::ExchangePointAndMark => s.edit_exchange_point_and_mark()?,
         Cmd::Dedent(mvt) => {
             s.edit_indent(&mvt, config.indent_size(), true)?;
         }
@@ -235,3 +298,16 @@ pub fn execute<H: Helper>(
     }
     Ok(Proceed)
 }
+
+/// Up / Down once the cursor is on the first / last line
+fn edit_history_up_down<H: Helper>(
+    s: &mut State<'_, '_, H>,
+    dir: Direction,
+    config: &Config,
+) -> Result<()> {
+    match config.history_search_mode() {
+        HistorySearchMode::Prefix => s.edit_history_search(dir),
+        HistorySearchMode::Substring => s.edit_history_substring_search(dir),
+        _ => s.edit_history_next(dir == Direction::Reverse),
+    }
+}
@@ -1,8 +1,20 @@
 //! Completion API
 use std::borrow::Cow::{self, Borrowed, Owned};
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::fs;
-use std::path::{self, Path};
+use std::future::Future;
+use std::io;
+use std::iter;
+use std::ops::Range;
+use std::path::{self, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::{Context as TaskContext, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::config::Config;
 use crate::line_buffer::LineBuffer;
 use crate::{Context, Result};
 use memchr::memchr;
@@ -18,6 +30,32 @@ pub trait Candidate {
     fn display(&self) -> &str;
     /// Text to insert in line.
     fn replacement(&self) -> &str;
+    /// Description displayed next to the candidate when listing
+    /// alternatives.
+    fn description(&self) -> Option<&str> {
+        None
+    }
+    /// Text inserted after the replacement once the candidate is elected
+    /// (e.g. a space, or a `/` after a directory name).
+    fn suffix(&self) -> Option<&str> {
+        None
+    }
+    /// Byte ranges of the display matched by the completed word, highlighted
+    /// when listing alternatives (see [`MatchStyle`]).
+    fn matches(&self) -> &[Range<usize>] {
+        &[]
+    }
+    /// Name of the group of the candidate (e.g. "files", "branches"),
+    /// displayed as a header above the candidates of the group when listing
+    /// alternatives.
+    fn group(&self) -> Option<&str> {
+        None
+    }
+    /// Tell if the candidate is a spelling correction of the word rather
+    /// than a completion (see [`Correction`]).
+    fn is_correction(&self) -> bool {
+        false
+    }
 }
 
 impl Candidate for String {
@@ -69,6 +107,473 @@ impl Candidate for Pair {
     }
 }
 
+/// Completion candidate, possibly displayed differently from what is
+/// inserted and with a description
+///
+/// ```
+/// use rustyline::completion::Completion;
+///
+/// let candidate = Completion {
+///     description: Some("Compile the current package".to_owned()),
+///     suffix: Some(" ".to_owned()),
+///     ..Completion::new("build")
+/// };
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Completion {
+    /// Text to insert in line.
+    pub replacement: String,
+    /// Text to display when listing alternatives.
+    pub display: String,
+    /// Description displayed next to the candidate.
+    pub description: Option<String>,
+    /// Text inserted after the replacement once the candidate is elected.
+    pub suffix: Option<String>,
+    /// Group of the candidate, displayed as a header.
+    pub group: Option<String>,
+}
+
+impl Completion {
+    /// Candidate displayed as it is inserted, without description nor suffix
+    pub fn new<S: Into<String>>(replacement: S) -> Self {
+        let replacement = replacement.into();
+        Self {
+            display: replacement.clone(),
+            replacement,
+            description: None,
+            suffix: None,
+            group: None,
+        }
+    }
+}
+
+impl Candidate for Completion {
+    fn display(&self) -> &str {
+        self.display.as_str()
+    }
+
+    fn replacement(&self) -> &str {
+        self.replacement.as_str()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn suffix(&self) -> Option<&str> {
+        self.suffix.as_deref()
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+}
+
+/// Text inserted for the elected `candidate`: its replacement followed by
+/// its suffix
+pub(crate) fn elected<C: Candidate + ?Sized>(candidate: &C) -> Cow<'_, str> {
+    match candidate.suffix() {
+        Some(suffix) => Owned(format!("{}{}", candidate.replacement(), suffix)),
+        None => Borrowed(candidate.replacement()),
+    }
+}
+
+/// Spelling correction offered when no candidate completes the word: a word
+/// of the completer [vocabulary](Completer::vocabulary) close to it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Correction {
+    /// Corrected word
+    pub word: String,
+    /// Edit distance between the word and the correction
+    pub distance: usize,
+}
+
+impl Candidate for Correction {
+    fn display(&self) -> &str {
+        self.word.as_str()
+    }
+
+    fn replacement(&self) -> &str {
+        self.word.as_str()
+    }
+
+    fn is_correction(&self) -> bool {
+        true
+    }
+}
+
+/// Words of `vocabulary` within `max_distance` edits of `word` (but not
+/// `word` itself), the closest first.
+///
+/// ```
+/// use rustyline::completion::corrections;
+///
+/// let corrections = corrections("cagro", vec!["cargo", "cat", "rustc"], 2);
+/// assert_eq!("cargo", corrections[0].word);
+/// assert_eq!(1, corrections.len());
+/// ```
+pub fn corrections<I, S>(word: &str, vocabulary: I, max_distance: usize) -> Vec<Correction>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let mut corrections: Vec<Correction> = Vec::new();
+    for candidate in vocabulary {
+        let candidate = candidate.into();
+        if corrections.iter().any(|c| c.word == candidate) {
+            continue;
+        }
+        match edit_distance(word, &candidate) {
+            distance @ 1.. if distance <= max_distance => corrections.push(Correction {
+                word: candidate,
+                distance,
+            }),
+            _ => {}
+        }
+    }
+    // stable sort
+    corrections.sort_by_key(|c| c.distance);
+    corrections
+}
+
+/// Number of insertions, deletions, substitutions and transpositions of
+/// characters needed to change `a` into `b` (optimal string alignment)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances between the prefixes of `a` and the ones of `b`, the last
+    // three rows
+    let mut previous2 = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(previous2[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut previous2, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Gathers the candidates of each group (see [`Candidate::group`]), groups
+/// being ordered by their first candidate and ungrouped candidates first.
+pub(crate) fn sort_by_group<C: Candidate>(candidates: &mut [C]) {
+    let mut groups: Vec<String> = Vec::new();
+    for group in candidates.iter().filter_map(Candidate::group) {
+        if !groups.iter().any(|g| g == group) {
+            groups.push(group.to_owned());
+        }
+    }
+    if !groups.is_empty() {
+        // stable sort
+        candidates
+            .sort_by_cached_key(|c| c.group().and_then(|g| groups.iter().position(|n| n == g)));
+    }
+}
+
+/// Runs of consecutive candidates of the same group
+pub(crate) fn groups<C: Candidate>(candidates: &[C]) -> Vec<(Option<&str>, Range<usize>)> {
+    let mut groups: Vec<(Option<&str>, Range<usize>)> = Vec::new();
+    for (i, candidate) in candidates.iter().enumerate() {
+        match groups.last_mut() {
+            Some((group, range)) if *group == candidate.group() => range.end = i + 1,
+            _ => groups.push((candidate.group(), i..i + 1)),
+        }
+    }
+    groups
+}
+
+/// How candidates are matched against the word being completed
+///
+/// ```
+/// use rustyline::completion::{Candidate, MatchStyle};
+///
+/// let commands = ["rebuild", "build", "clean", "doc"];
+/// let matched = MatchStyle::Fuzzy.filter("bd", commands.iter().copied());
+/// assert_eq!(vec!["build", "rebuild"], matched.iter().map(|m| m.replacement()).collect::<Vec<_>>());
+/// assert_eq!([0..1, 4..5], matched[0].matches());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MatchStyle {
+    /// Candidates starting with the word
+    #[default]
+    Prefix,
+    /// Candidates containing the word
+    Substring,
+    /// Candidates containing the characters of the word in order (like fzf),
+    /// the best matches first: consecutive characters and characters at the
+    /// start of words score higher. Case is ignored unless the word contains
+    /// an upper case character.
+    Fuzzy,
+}
+
+impl MatchStyle {
+    /// Match the `display` of a candidate against `word`: return the score
+    /// of the match (the higher, the better) and the matched byte ranges.
+    pub fn matches(self, word: &str, display: &str) -> Option<(i64, Vec<Range<usize>>)> {
+        match self {
+            MatchStyle::Prefix => display
+                .starts_with(word)
+                .then(|| (0, iter::once(0..word.len()).collect())),
+            MatchStyle::Substring => display.find(word).map(|start| {
+                (
+                    -(start as i64),
+                    iter::once(start..start + word.len()).collect(),
+                )
+            }),
+            MatchStyle::Fuzzy => fuzzy_match(word, display),
+        }
+    }
+
+    /// Keep the `candidates` whose display matches `word`, the best matches
+    /// first.
+    pub fn filter<C: Candidate, I: IntoIterator<Item = C>>(
+        self,
+        word: &str,
+        candidates: I,
+    ) -> Vec<Matched<C>> {
+        let mut matched: Vec<_> = candidates
+            .into_iter()
+            .filter_map(|candidate| {
+                let (score, ranges) = self.matches(word, candidate.display())?;
+                Some(Matched {
+                    candidate,
+                    score,
+                    ranges,
+                })
+            })
+            .collect();
+        matched.sort_by_key(|m| Reverse(m.score)); // stable
+        matched
+    }
+}
+
+/// Candidate matched by a [`MatchStyle`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Matched<C> {
+    /// The matched candidate
+    pub candidate: C,
+    /// Score of the match (the higher, the better)
+    pub score: i64,
+    /// Byte ranges of the candidate display matched by the word
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl<C: Candidate> Candidate for Matched<C> {
+    fn display(&self) -> &str {
+        self.candidate.display()
+    }
+
+    fn replacement(&self) -> &str {
+        self.candidate.replacement()
+    }
+
+    fn description(&self) -> Option<&str> {
+        self.candidate.description()
+    }
+
+    fn suffix(&self) -> Option<&str> {
+        self.candidate.suffix()
+    }
+
+    fn matches(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.candidate.group()
+    }
+
+    fn is_correction(&self) -> bool {
+        self.candidate.is_correction()
+    }
+}
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_FIRST_CHAR: i64 = 8;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+/// Subsequence match of `pattern` in `text`, scored like fzf (v1): the
+/// shortest window ending at the first complete match is scored
+fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<Range<usize>>)> {
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let ignore_case = !pattern.chars().any(char::is_uppercase);
+    let eq = |p: char, t: char| {
+        if ignore_case {
+            t.to_lowercase().eq(p.to_lowercase())
+        } else {
+            p == t
+        }
+    };
+    let pattern: Vec<char> = pattern.chars().collect();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    // forward: end of the first complete match
+    let mut p = 0;
+    let mut end = None;
+    for (i, &(_, c)) in chars.iter().enumerate() {
+        if eq(pattern[p], c) {
+            p += 1;
+            if p == pattern.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+    // backward: start of the shortest window
+    let mut p = pattern.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if eq(pattern[p - 1], chars[i].1) {
+            p -= 1;
+            if p == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+    // score the greedy match within the window
+    let mut score = 0;
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut p = 0;
+    let mut previous: Option<usize> = None;
+    for i in start..=end {
+        if p == pattern.len() {
+            break;
+        }
+        let (offset, c) = chars[i];
+        if !eq(pattern[p], c) {
+            continue;
+        }
+        score += SCORE_MATCH;
+        let prev = if i == 0 { None } else { Some(chars[i - 1].1) };
+        let boundary = match prev {
+            None => true,
+            Some(prev) => !prev.is_alphanumeric() || (prev.is_lowercase() && c.is_uppercase()),
+        };
+        if boundary {
+            score += BONUS_BOUNDARY;
+        }
+        if i == 0 {
+            score += BONUS_FIRST_CHAR;
+        }
+        match previous {
+            Some(j) if j + 1 == i => score += BONUS_CONSECUTIVE,
+            Some(j) => {
+                score -= PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (i - j - 2) as i64;
+            }
+            None => {}
+        }
+        let next = offset + c.len_utf8();
+        match ranges.last_mut() {
+            Some(range) if range.end == offset => range.end = next,
+            _ => ranges.push(offset..next),
+        }
+        previous = Some(i);
+        p += 1;
+    }
+    Some((score, ranges))
+}
+
+/// Start position and candidates of an asynchronous completion (see
+/// [`Completer::complete_async`])
+pub type CompletionFuture<C> = Pin<Box<dyn Future<Output = Result<(usize, Vec<C>)>> + Send>>;
+
+/// Run `complete` on a background thread and return the future of its
+/// outcome.
+///
+/// ```
+/// use rustyline::completion::{spawn_completion, Completer, CompletionFuture};
+/// use rustyline::Context;
+///
+/// struct Remote;
+///
+/// impl Completer for Remote {
+///     type Candidate = String;
+///
+///     fn complete_async(
+///         &self,
+///         line: &str,
+///         pos: usize,
+///         _: &Context<'_>,
+///     ) -> Option<CompletionFuture<String>> {
+///         let word = line[..pos].to_owned();
+///         Some(spawn_completion(move || {
+///             // e.g. ask a server for the candidates
+///             Ok((0, vec![word + "_remote"]))
+///         }))
+///     }
+/// }
+/// ```
+pub fn spawn_completion<C, F>(complete: F) -> CompletionFuture<C>
+where
+    C: Send + 'static,
+    F: FnOnce() -> Result<(usize, Vec<C>)> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel();
+    let waker = Arc::new(Mutex::new(None::<Waker>));
+    let spawned = Spawned {
+        rx,
+        waker: waker.clone(),
+    };
+    thread::spawn(move || {
+        let _wake = WakeOnDrop(waker); // even if `complete` panics
+        let tx = tx; // dropped before `_wake`
+        let _ = tx.send(complete());
+    });
+    Box::pin(spawned)
+}
+
+/// Wake up the task polling a [`Spawned`] future
+struct WakeOnDrop(Arc<Mutex<Option<Waker>>>);
+
+impl Drop for WakeOnDrop {
+    fn drop(&mut self) {
+        let waker = match self.0.lock() {
+            Ok(mut waker) => waker.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Outcome of a completion running on a background thread
+struct Spawned<T> {
+    rx: mpsc::Receiver<Result<T>>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl<T> Future for Spawned<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Self::Output> {
+        *self.waker.lock().unwrap_or_else(|e| e.into_inner()) = Some(cx.waker().clone());
+        match self.rx.try_recv() {
+            Ok(result) => Poll::Ready(result),
+            Err(mpsc::TryRecvError::Empty) => Poll::Pending,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Poll::Ready(Err(io::Error::other("completion thread panicked").into()))
+            }
+        }
+    }
+}
+
 /// To be called for tab-completion.
 pub trait Completer {
     /// Specific completion candidate.
@@ -88,90 +593,831 @@ pub trait Completer {
         let _ = (line, pos, ctx);
         Ok((0, Vec::with_capacity(0)))
     }
+    /// Starts the completion of the partial word without waiting for the
+    /// candidates: slow completers (network, large file systems) return
+    /// their future (see [`spawn_completion`]) while the prompt stays
+    /// responsive, the completion being cancelled if the user goes on typing.
+    ///
+    /// By default, `None`: the candidates are returned by
+    /// [`complete`](Self::complete).
+    fn complete_async(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Option<CompletionFuture<Self::Candidate>> {
+        let _ = (line, pos, ctx);
+        None
+    }
+    /// Words which the partial word at the cursor `pos`ition may be a
+    /// misspelling of: when no candidate completes it, the ones close to it
+    /// are offered as [`Correction`]s (see
+    /// [`Config::completion_correction_distance`]).
+    ///
+    /// By default, none.
+    fn vocabulary(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<Vec<String>> {
+        let _ = (line, pos, ctx);
+        Ok(Vec::new())
+    }
     /// Updates the edited `line` with the `elected` candidate.
+    ///
+    /// By default, the word is replaced from `start` to the cursor, and
+    /// further when the cursor is in the middle of the word and the
+    /// candidate completes the text after it (e.g. `car|go` becomes `cargo|`
+    /// rather than `cargo|go`): the rest of the line is preserved and the
+    /// cursor is moved after the candidate.
     fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
-        let end = line.pos();
+        let pos = line.pos();
+        let end = pos + completed_tail(&line[pos..], elected.get(pos - start..).unwrap_or(""));
         line.replace(start..end, elected)
     }
 }
 
+/// Length of the start of the word following the cursor (`tail`) which is
+/// also in the completed part of the candidate (`completed`), the following
+/// whitespace included if the candidate ends with it
+fn completed_tail(tail: &str, completed: &str) -> usize {
+    let word = tail.find(char::is_whitespace).map_or(tail, |i| &tail[..i]);
+    let len = word
+        .char_indices()
+        .map(|(i, c)| i + c.len_utf8())
+        .rev()
+        .find(|&end| completed.contains(&word[..end]))
+        .unwrap_or(0);
+    match completed.chars().last() {
+        Some(c) if len == word.len() && c.is_whitespace() && tail[len..].starts_with(c) => {
+            len + c.len_utf8()
+        }
+        _ => len,
+    }
+}
+
 impl Completer for () {
     type Candidate = String;
 
     fn update(&self, _line: &mut LineBuffer, _start: usize, _elected: &str) {
         unreachable!()
     }
-}
+}
+
+impl<'c, C: ?Sized + Completer> Completer for &'c C {
+    type Candidate = C::Candidate;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Self::Candidate>)> {
+        (**self).complete(line, pos, ctx)
+    }
+
+    fn complete_async(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Option<CompletionFuture<Self::Candidate>> {
+        (**self).complete_async(line, pos, ctx)
+    }
+
+    fn vocabulary(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<Vec<String>> {
+        (**self).vocabulary(line, pos, ctx)
+    }
+
+    fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
+        (**self).update(line, start, elected)
+    }
+}
+macro_rules! box_completer {
+    ($($id: ident)*) => {
+        $(
+            impl<C: ?Sized + Completer> Completer for $id<C> {
+                type Candidate = C::Candidate;
+
+                fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<(usize, Vec<Self::Candidate>)> {
+                    (**self).complete(line, pos, ctx)
+                }
+                fn complete_async(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<CompletionFuture<Self::Candidate>> {
+                    (**self).complete_async(line, pos, ctx)
+                }
+                fn vocabulary(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<Vec<String>> {
+                    (**self).vocabulary(line, pos, ctx)
+                }
+                fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
+                    (**self).update(line, start, elected)
+                }
+            }
+        )*
+    }
+}
+
+use std::rc::Rc;
+box_completer! { Box Rc Arc }
+
+/// A `Completer` querying several completers (e.g. commands, paths,
+/// application-specific words): their candidates are merged, the ones of the
+/// completers with the highest priority first, and deduplicated.
+///
+/// ```
+/// use rustyline::completion::{CompleterSet, FilenameCompleter};
+///
+/// struct Commands;
+/// # impl rustyline::completion::Completer for Commands { type Candidate = String; }
+///
+/// let mut completer = CompleterSet::new();
+/// completer.add(10, Commands);
+/// completer.add(0, FilenameCompleter::new());
+/// ```
+///
+/// Asynchronous completers are queried through
+/// [`complete`](Completer::complete), and the completers
+/// [`update`](Completer::update) is not used.
+#[derive(Default)]
+pub struct CompleterSet {
+    /// Completers sorted by priority (highest first)
+    completers: Vec<(i32, Box<dyn AnyCompleter>)>,
+}
+
+/// `Completer` whose candidates are converted to [`Completion`]s
+pub(crate) trait AnyCompleter {
+    fn complete_any(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Completion>)>;
+
+    fn vocabulary_any(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<Vec<String>>;
+
+    fn update_any(&self, line: &mut LineBuffer, start: usize, elected: &str);
+}
+
+impl<C: Completer> AnyCompleter for C {
+    fn complete_any(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Completion>)> {
+        let (start, candidates) = self.complete(line, pos, ctx)?;
+        let completions = candidates
+            .iter()
+            .map(|candidate| Completion {
+                replacement: candidate.replacement().to_owned(),
+                display: candidate.display().to_owned(),
+                description: candidate.description().map(str::to_owned),
+                suffix: candidate.suffix().map(str::to_owned),
+                group: candidate.group().map(str::to_owned),
+            })
+            .collect();
+        Ok((start, completions))
+    }
+
+    fn vocabulary_any(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<Vec<String>> {
+        self.vocabulary(line, pos, ctx)
+    }
+
+    fn update_any(&self, line: &mut LineBuffer, start: usize, elected: &str) {
+        self.update(line, start, elected)
+    }
+}
+
+/// Completion of the word at the cursor for frontends rendering the
+/// candidates themselves (e.g. in a dropdown): the candidates are those of
+/// the completer, and the accepted one is inserted as by `complete`.
+///
+/// ```
+/// use rustyline::completion::{Completer, CompletionState};
+/// use rustyline::history::History;
+/// use rustyline::line_buffer::LineBuffer;
+/// use rustyline::{Context, Result};
+///
+/// struct Commands;
+/// impl Completer for Commands {
+///     type Candidate = String;
+///
+///     fn complete(&self, line: &str, pos: usize, _: &Context<'_>) -> Result<(usize, Vec<String>)> {
+///         let commands = ["build", "bench", "clean"];
+///         Ok((0, commands.iter().filter(|c| c.starts_with(&line[..pos])).map(|c| c.to_string()).collect()))
+///     }
+/// }
+///
+/// let history = History::new();
+/// let mut line = LineBuffer::with_capacity(80);
+/// line.update("b", 1);
+/// let mut state = CompletionState::new(&Commands, &line, &Context::new(&history))?;
+/// assert_eq!(2, state.candidates().len());
+/// state.select_next();
+/// state.select_next();
+/// assert_eq!(Some("bench"), state.accepted_text().as_deref());
+/// assert!(state.accept(&Commands, &mut line));
+/// assert_eq!("bench", line.as_str());
+/// # Ok::<(), rustyline::error::ReadlineError>(())
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompletionState {
+    start: usize,
+    word: String,
+    candidates: Vec<Completion>,
+    selected: Option<usize>,
+}
+
+impl CompletionState {
+    /// Candidates of `completer` for the word at the cursor of `line`, none
+    /// being selected
+    pub fn new<C: Completer>(completer: &C, line: &LineBuffer, ctx: &Context<'_>) -> Result<Self> {
+        Self::from_any(completer, line, ctx)
+    }
+
+    pub(crate) fn from_any(
+        completer: &dyn AnyCompleter,
+        line: &LineBuffer,
+        ctx: &Context<'_>,
+    ) -> Result<Self> {
+        let pos = line.pos();
+        let (start, mut candidates) = completer.complete_any(line, pos, ctx)?;
+        sort_by_group(&mut candidates);
+        Ok(Self {
+            start,
+            word: line[start..pos].to_owned(),
+            candidates,
+            selected: None,
+        })
+    }
+
+    /// Start of the completed word in the line
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Completed word (up to the cursor)
+    pub fn word(&self) -> &str {
+        &self.word
+    }
+
+    /// Candidates, the ones of a group together
+    pub fn candidates(&self) -> &[Completion] {
+        &self.candidates
+    }
+
+    /// Index of the selected candidate (`None` for the original word)
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Selects the candidate at `index` (the original word if `None` or out
+    /// of range)
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected = index.filter(|&i| i < self.candidates.len());
+    }
+
+    /// Selects the next candidate, the original word after the last one
+    /// (like `menu-complete`)
+    pub fn select_next(&mut self) {
+        self.selected = match self.selected {
+            None if !self.candidates.is_empty() => Some(0),
+            Some(i) if i + 1 < self.candidates.len() => Some(i + 1),
+            _ => None,
+        };
+    }
+
+    /// Selects the previous candidate, the original word before the first
+    /// one (like `menu-complete-backward`)
+    pub fn select_previous(&mut self) {
+        self.selected = match self.selected {
+            None => self.candidates.len().checked_sub(1),
+            Some(i) => i.checked_sub(1),
+        };
+    }
+
+    /// Text inserted if the selected candidate is accepted: its replacement
+    /// followed by its suffix
+    pub fn accepted_text(&self) -> Option<Cow<'_, str>> {
+        self.selected.map(|i| elected(&self.candidates[i]))
+    }
+
+    /// Inserts the selected candidate in `line` with the `update` of
+    /// `completer` (nothing if no candidate is selected or if the word has
+    /// been edited since)
+    pub fn accept<C: Completer>(&self, completer: &C, line: &mut LineBuffer) -> bool {
+        self.accept_any(completer, line)
+    }
+
+    pub(crate) fn accept_any(&self, completer: &dyn AnyCompleter, line: &mut LineBuffer) -> bool {
+        let text = match self.accepted_text() {
+            Some(text) => text,
+            None => return false,
+        };
+        if line.get(self.start..line.pos()) != Some(self.word.as_str()) {
+            return false;
+        }
+        completer.update_any(line, self.start, &text);
+        true
+    }
+}
+
+impl CompleterSet {
+    /// Empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `completer`: its candidates are listed after the ones of the
+    /// completers with a higher `priority` (and of the ones with the same
+    /// priority registered before).
+    pub fn add<C: Completer + 'static>(&mut self, priority: i32, completer: C) {
+        let index = self
+            .completers
+            .iter()
+            .position(|(p, _)| *p < priority)
+            .unwrap_or(self.completers.len());
+        self.completers
+            .insert(index, (priority, Box::new(completer)));
+    }
+
+    /// Number of registered completers
+    pub fn len(&self) -> usize {
+        self.completers.len()
+    }
+
+    /// Tell if no completer is registered
+    pub fn is_empty(&self) -> bool {
+        self.completers.is_empty()
+    }
+}
+
+impl Completer for CompleterSet {
+    type Candidate = Completion;
+
+    /// Candidates of all the completers: the ones completing a shorter word
+    /// (starting after the others) are extended to start at the same
+    /// position, and only the first candidate with a given replacement is
+    /// kept.
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Completion>)> {
+        let mut results = Vec::with_capacity(self.completers.len());
+        for (_, completer) in &self.completers {
+            let (start, completions) = completer.complete_any(line, pos, ctx)?;
+            if !completions.is_empty() {
+                results.push((start, completions));
+            }
+        }
+        let start = results.iter().map(|(start, _)| *start).min().unwrap_or(pos);
+        let mut merged: Vec<Completion> = Vec::new();
+        for (from, completions) in results {
+            for mut completion in completions {
+                if from > start {
+                    completion.replacement.insert_str(0, &line[start..from]);
+                }
+                if !merged
+                    .iter()
+                    .any(|c| c.replacement == completion.replacement)
+                {
+                    merged.push(completion);
+                }
+            }
+        }
+        Ok((start, merged))
+    }
+
+    /// Vocabularies of all the completers
+    fn vocabulary(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<Vec<String>> {
+        let mut vocabulary = Vec::new();
+        for (_, completer) in &self.completers {
+            vocabulary.extend(completer.vocabulary_any(line, pos, ctx)?);
+        }
+        Ok(vocabulary)
+    }
+}
+
+/// A `Completer` remembering the candidates of an expensive completer (e.g.
+/// listing cloud resources) for some time: it is not queried again while the
+/// text before the cursor (the completed word and its context) is the same,
+/// unless the cached candidates are invalidated.
+///
+/// ```
+/// use std::time::Duration;
+/// use rustyline::completion::{CachedCompleter, FilenameCompleter};
+///
+/// let completer = CachedCompleter::new(FilenameCompleter::new(), Duration::from_secs(5));
+/// // given to the code knowing when the candidates are stale
+/// let invalidator = completer.invalidator();
+/// invalidator.invalidate_matching(|line| line.starts_with("cd "));
+/// ```
+pub struct CachedCompleter<C: Completer> {
+    completer: C,
+    ttl: Duration,
+    cache: CacheInvalidator<C::Candidate>,
+}
+
+/// Candidates (with their start position and the time they were returned)
+/// by text before the cursor
+type Cache<T> = HashMap<String, (Instant, usize, Vec<T>)>;
+
+impl<C: Completer> CachedCompleter<C> {
+    /// Caches the candidates of `completer` for `ttl`
+    pub fn new(completer: C, ttl: Duration) -> Self {
+        Self {
+            completer,
+            ttl,
+            cache: CacheInvalidator(Arc::new(Mutex::new(HashMap::new()))),
+        }
+    }
+
+    /// The cached completer
+    pub fn get_ref(&self) -> &C {
+        &self.completer
+    }
+
+    /// Forgets all the cached candidates
+    pub fn invalidate(&self) {
+        self.cache.invalidate()
+    }
+
+    /// Handle invalidating the cached candidates, e.g. from another thread
+    pub fn invalidator(&self) -> CacheInvalidator<C::Candidate> {
+        self.cache.clone()
+    }
+}
+
+impl<C: Completer> Completer for CachedCompleter<C>
+where
+    C::Candidate: Clone + Send + 'static,
+{
+    type Candidate = C::Candidate;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Self::Candidate>)> {
+        if let Some(cached) = self.cache.get(&line[..pos], self.ttl) {
+            return Ok(cached);
+        }
+        let (start, candidates) = self.completer.complete(line, pos, ctx)?;
+        self.cache
+            .insert(line[..pos].to_owned(), start, &candidates, self.ttl);
+        Ok((start, candidates))
+    }
+
+    /// The future of the cached completer, its candidates being cached once
+    /// ready (`None` if they already are)
+    fn complete_async(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &Context<'_>,
+    ) -> Option<CompletionFuture<Self::Candidate>> {
+        if self.cache.get(&line[..pos], self.ttl).is_some() {
+            return None;
+        }
+        let future = self.completer.complete_async(line, pos, ctx)?;
+        let cache = self.cache.clone();
+        let key = line[..pos].to_owned();
+        let ttl = self.ttl;
+        Some(Box::pin(async move {
+            let (start, candidates) = future.await?;
+            cache.insert(key, start, &candidates, ttl);
+            Ok((start, candidates))
+        }))
+    }
+
+    fn vocabulary(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<Vec<String>> {
+        self.completer.vocabulary(line, pos, ctx)
+    }
+
+    fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
+        self.completer.update(line, start, elected)
+    }
+}
+
+/// Handle invalidating the candidates cached by a [`CachedCompleter`]
+pub struct CacheInvalidator<T>(Arc<Mutex<Cache<T>>>);
+
+impl<T> Clone for CacheInvalidator<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> CacheInvalidator<T> {
+    /// Forgets all the cached candidates
+    pub fn invalidate(&self) {
+        self.0.lock().unwrap().clear();
+    }
+
+    /// Forgets the candidates cached for the text before the cursor matching
+    /// `predicate`
+    pub fn invalidate_matching<F: FnMut(&str) -> bool>(&self, mut predicate: F) {
+        self.0.lock().unwrap().retain(|line, _| !predicate(line));
+    }
+}
+
+impl<T: Clone> CacheInvalidator<T> {
+    /// Candidates cached for `line` (before the cursor) less than `ttl` ago
+    fn get(&self, line: &str, ttl: Duration) -> Option<(usize, Vec<T>)> {
+        match self.0.lock().unwrap().get(line) {
+            Some((time, start, candidates)) if time.elapsed() < ttl => {
+                Some((*start, candidates.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Caches `candidates` for `line`, forgetting the expired ones
+    fn insert(&self, line: String, start: usize, candidates: &[T], ttl: Duration) {
+        let mut cache = self.0.lock().unwrap();
+        cache.retain(|_, (time, ..)| time.elapsed() < ttl);
+        cache.insert(line, (Instant::now(), start, candidates.to_vec()));
+    }
+}
+
+/// A `Completer` for file and folder names.
+///
+/// Former name of [`PathCompleter`].
+pub type FilenameCompleter = PathCompleter;
+
+/// A `Completer` for file and folder names: `~` and `~user` are expanded to
+/// home directories, completed directory names are suffixed with a path
+/// separator ([`Config::mark_directories`]) and the completed paths are
+/// quoted or escaped following the rules of a [`QuotingDialect`].
+///
+/// ```
+/// use rustyline::completion::{HiddenFiles, PathCompleter, QuotingDialect};
+///
+/// let completer = PathCompleter::new()
+///     .quoting(QuotingDialect::Posix)
+///     .hidden_files(HiddenFiles::WhenDotTyped);
+/// ```
+#[derive(Clone, Debug)]
+pub struct PathCompleter {
+    quoting: QuotingDialect,
+    hidden_files: HiddenFiles,
+}
+
+const DOUBLE_QUOTES_ESCAPE_CHAR: Option<char> = Some('\\');
+
+// rl_basic_word_break_characters, rl_completer_word_break_characters
+const POSIX_BREAK_CHARS: [u8; 18] = [
+    b' ', b'\t', b'\n', b'"', b'\\', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&',
+    b'{', b'(', b'\0',
+];
+// In double quotes, not all break_chars need to be escaped
+// https://www.gnu.org/software/bash/manual/html_node/Double-Quotes.html
+const POSIX_DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 4] = [b'"', b'$', b'\\', b'`'];
+// Without \ to make file completion work on windows
+const WINDOWS_BREAK_CHARS: [u8; 17] = [
+    b' ', b'\t', b'\n', b'"', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&', b'{',
+    b'(', b'\0',
+];
+const RAW_BREAK_CHARS: [u8; 3] = [b' ', b'\t', b'\n'];
+
+/// Quoting and escaping rules of the completed paths
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QuotingDialect {
+    /// POSIX shells: special characters are escaped with a backslash, except
+    /// in single quotes (and only `"$\`` in double quotes)
+    Posix,
+    /// Windows shells: there is no escape character, paths with special
+    /// characters are double quoted
+    Windows,
+    /// Neither quoting nor escaping: paths end at the first whitespace
+    Raw,
+}
+
+impl Default for QuotingDialect {
+    /// Dialect of the platform shell
+    fn default() -> Self {
+        if cfg!(windows) {
+            QuotingDialect::Windows
+        } else if cfg!(unix) {
+            QuotingDialect::Posix
+        } else {
+            QuotingDialect::Raw
+        }
+    }
+}
+
+impl QuotingDialect {
+    fn break_chars(self) -> &'static [u8] {
+        match self {
+            QuotingDialect::Posix => &POSIX_BREAK_CHARS,
+            QuotingDialect::Windows => &WINDOWS_BREAK_CHARS,
+            QuotingDialect::Raw => &RAW_BREAK_CHARS,
+        }
+    }
+
+    fn escape_char(self) -> Option<char> {
+        match self {
+            QuotingDialect::Posix => Some('\\'),
+            _ => None,
+        }
+    }
+
+    /// Quote or escape the completed `path` (following an unclosed `quote`)
+    fn quote(self, mut path: String, quote: Quote) -> String {
+        match self {
+            QuotingDialect::Posix => {
+                let special_chars: &[u8] = if quote == Quote::Double {
+                    &POSIX_DOUBLE_QUOTES_SPECIAL_CHARS
+                } else {
+                    &POSIX_BREAK_CHARS
+                };
+                escape(path, Some('\\'), special_chars, quote)
+            }
+            QuotingDialect::Windows => {
+                if quote == Quote::None
+                    && path
+                        .bytes()
+                        .any(|b| memchr(b, &WINDOWS_BREAK_CHARS).is_some())
+                {
+                    path.insert(0, '"'); // force double quote
+                }
+                path
+            }
+            QuotingDialect::Raw => path,
+        }
+    }
+
+    /// Split `line` into words at unquoted whitespaces
+    fn split(self, line: &str) -> Vec<Word> {
+        let mut words = Vec::new();
+        let mut word: Option<Word> = None;
+        let mut quote = Quote::None;
+        let mut chars = line.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if quote == Quote::None && c.is_whitespace() {
+                words.extend(word.take());
+                continue;
+            }
+            let w = word.get_or_insert_with(|| Word {
+                range: i..i,
+                text: String::new(),
+            });
+            match (self, quote, c) {
+                (QuotingDialect::Posix | QuotingDialect::Windows, Quote::None, '"') => {
+                    quote = Quote::Double
+                }
+                (QuotingDialect::Posix, Quote::None, '\'') => quote = Quote::Single,
+                (_, Quote::Double, '"') | (_, Quote::Single, '\'') => quote = Quote::None,
+                (QuotingDialect::Posix, Quote::None, '\\') => {
+                    if let Some((_, c)) = chars.next() {
+                        w.text.push(c);
+                    }
+                }
+                (QuotingDialect::Posix, Quote::Double, '\\') => match chars.peek() {
+                    Some(&(_, c))
+                        if memchr(c as u8, &POSIX_DOUBLE_QUOTES_SPECIAL_CHARS).is_some() =>
+                    {
+                        w.text.push(c);
+                        chars.next();
+                    }
+                    _ => w.text.push(c),
+                },
+                _ => w.text.push(c),
+            }
+            w.range.end = chars.peek().map_or(line.len(), |&(j, _)| j);
+        }
+        words.extend(word);
+        words
+    }
+}
+
+/// Word of the line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Word {
+    /// Position in the line (quotes and escape characters included)
+    pub range: Range<usize>,
+    /// Text without quotes nor escape characters
+    pub text: String,
+}
+
+/// The line being completed, split into words following the rules of a
+/// [`QuotingDialect`], and the partial word to be completed.
+///
+/// ```
+/// use rustyline::completion::{CompletionContext, Quote, QuotingDialect};
+///
+/// let line = "git commit -m 'fix typo";
+/// let ctx = CompletionContext::with_dialect(line, line.len(), QuotingDialect::Posix);
+/// assert_eq!(15, ctx.start());
+/// assert_eq!("fix typo", ctx.word());
+/// assert_eq!(Quote::Single, ctx.quote());
+/// assert_eq!(3, ctx.word_index());
+/// assert_eq!("fix typo", ctx.words()[3].text);
+/// ```
+#[derive(Clone, Debug)]
+pub struct CompletionContext<'l> {
+    line: &'l str,
+    pos: usize,
+    start: usize,
+    word: Cow<'l, str>,
+    quote: Quote,
+    words: Vec<Word>,
+    word_index: usize,
+}
+
+impl<'l> CompletionContext<'l> {
+    /// Context of the `line` completed at `pos`, following the rules of the
+    /// platform shell
+    pub fn new(line: &'l str, pos: usize) -> Self {
+        Self::with_dialect(line, pos, QuotingDialect::default())
+    }
+
+    /// Context of the `line` completed at `pos`, following the rules of
+    /// `dialect`
+    pub fn with_dialect(line: &'l str, pos: usize, dialect: QuotingDialect) -> Self {
+        let (start, word, quote) = match find_unclosed_quote(&line[..pos]) {
+            Some((idx, quote)) if dialect != QuotingDialect::Raw => {
+                let start = idx + 1;
+                let word = if quote == Quote::Double {
+                    unescape(&line[start..pos], DOUBLE_QUOTES_ESCAPE_CHAR)
+                } else {
+                    Borrowed(&line[start..pos])
+                };
+                (start, word, quote)
+            }
+            _ => {
+                let esc_char = dialect.escape_char();
+                let (start, word) = extract_word(line, pos, esc_char, dialect.break_chars());
+                (start, unescape(word, esc_char), Quote::None)
+            }
+        };
+        let words = dialect.split(line);
+        let word_index = words
+            .iter()
+            .position(|w| pos <= w.range.end)
+            .unwrap_or(words.len());
+        Self {
+            line,
+            pos,
+            start,
+            word,
+            quote,
+            words,
+            word_index,
+        }
+    }
+
+    /// The whole line
+    pub fn line(&self) -> &'l str {
+        self.line
+    }
 
-impl<'c, C: ?Sized + Completer> Completer for &'c C {
-    type Candidate = C::Candidate;
+    /// Cursor position
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
 
-    fn complete(
-        &self,
-        line: &str,
-        pos: usize,
-        ctx: &Context<'_>,
-    ) -> Result<(usize, Vec<Self::Candidate>)> {
-        (**self).complete(line, pos, ctx)
+    /// Start of the partial word to be completed (after the opening quote if
+    /// any), to be returned by [`Completer::complete`]
+    pub fn start(&self) -> usize {
+        self.start
     }
 
-    fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
-        (**self).update(line, start, elected)
+    /// Partial word to be completed (from [`start`](Self::start) to the
+    /// cursor), without escape characters: it starts after the last unquoted
+    /// word break character (e.g. `=` or `;` for POSIX shells)
+    pub fn word(&self) -> &str {
+        &self.word
     }
-}
-macro_rules! box_completer {
-    ($($id: ident)*) => {
-        $(
-            impl<C: ?Sized + Completer> Completer for $id<C> {
-                type Candidate = C::Candidate;
 
-                fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<(usize, Vec<Self::Candidate>)> {
-                    (**self).complete(line, pos, ctx)
-                }
-                fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
-                    (**self).update(line, start, elected)
-                }
-            }
-        )*
+    /// Quote the cursor is in
+    pub fn quote(&self) -> Quote {
+        self.quote
     }
-}
 
-use std::rc::Rc;
-use std::sync::Arc;
-box_completer! { Box Rc Arc }
+    /// Words of the line, split at unquoted whitespaces
+    pub fn words(&self) -> &[Word] {
+        &self.words
+    }
 
-/// A `Completer` for file and folder names.
-pub struct FilenameCompleter {
-    break_chars: &'static [u8],
-    double_quotes_special_chars: &'static [u8],
+    /// Index in [`words`](Self::words) of the word at the cursor (of the next
+    /// word if the cursor is between words, a new word being typed)
+    pub fn word_index(&self) -> usize {
+        self.word_index
+    }
 }
 
-const DOUBLE_QUOTES_ESCAPE_CHAR: Option<char> = Some('\\');
-
-cfg_if::cfg_if! {
-    if #[cfg(unix)] {
-        // rl_basic_word_break_characters, rl_completer_word_break_characters
-        const DEFAULT_BREAK_CHARS: [u8; 18] = [
-            b' ', b'\t', b'\n', b'"', b'\\', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&',
-            b'{', b'(', b'\0',
-        ];
-        const ESCAPE_CHAR: Option<char> = Some('\\');
-        // In double quotes, not all break_chars need to be escaped
-        // https://www.gnu.org/software/bash/manual/html_node/Double-Quotes.html
-        const DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 4] = [b'"', b'$', b'\\', b'`'];
-    } else if #[cfg(windows)] {
-        // Remove \ to make file completion works on windows
-        const DEFAULT_BREAK_CHARS: [u8; 17] = [
-            b' ', b'\t', b'\n', b'"', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&', b'{',
-            b'(', b'\0',
-        ];
-        const ESCAPE_CHAR: Option<char> = None;
-        const DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 1] = [b'"']; // TODO Validate: only '"' ?
-    } else if #[cfg(target_arch = "wasm32")] {
-        const DEFAULT_BREAK_CHARS: [u8; 0] = [];
-        const ESCAPE_CHAR: Option<char> = None;
-        const DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 0] = [];
-    }
+/// Which hidden files (whose name starts with a `.`) are completed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HiddenFiles {
+    /// All of them
+    #[default]
+    Show,
+    /// Only when the completed name starts with a `.` (like readline with
+    /// `match-hidden-files` off)
+    WhenDotTyped,
+    /// None of them
+    Hide,
 }
 
 /// Kind of quote.
@@ -185,65 +1431,180 @@ pub enum Quote {
     None,
 }
 
-impl FilenameCompleter {
-    /// Constructor
+impl PathCompleter {
+    /// Constructor: paths quoted following the platform shell rules, hidden
+    /// files shown
     pub fn new() -> Self {
         Self {
-            break_chars: &DEFAULT_BREAK_CHARS,
-            double_quotes_special_chars: &DOUBLE_QUOTES_SPECIAL_CHARS,
+            quoting: QuotingDialect::default(),
+            hidden_files: HiddenFiles::default(),
         }
     }
 
+    /// Set the quoting and escaping rules of the completed paths.
+    ///
+    /// By default, the ones of the platform shell.
+    pub fn quoting(mut self, quoting: QuotingDialect) -> Self {
+        self.quoting = quoting;
+        self
+    }
+
+    /// Set which hidden files are completed.
+    ///
+    /// By default, all of them.
+    pub fn hidden_files(mut self, hidden_files: HiddenFiles) -> Self {
+        self.hidden_files = hidden_files;
+        self
+    }
+
     /// Takes the currently edited `line` with the cursor `pos`ition and
     /// returns the start position and the completion candidates for the
     /// partial path to be completed.
     pub fn complete_path(&self, line: &str, pos: usize) -> Result<(usize, Vec<Pair>)> {
-        let (start, path, esc_char, break_chars, quote) =
-            if let Some((idx, quote)) = find_unclosed_quote(&line[..pos]) {
-                let start = idx + 1;
-                if quote == Quote::Double {
-                    (
-                        start,
-                        unescape(&line[start..pos], DOUBLE_QUOTES_ESCAPE_CHAR),
-                        DOUBLE_QUOTES_ESCAPE_CHAR,
-                        &self.double_quotes_special_chars,
-                        quote,
-                    )
-                } else {
-                    (
-                        start,
-                        Borrowed(&line[start..pos]),
-                        None,
-                        &self.break_chars,
-                        quote,
-                    )
-                }
-            } else {
-                let (start, path) = extract_word(line, pos, ESCAPE_CHAR, &self.break_chars);
-                let path = unescape(path, ESCAPE_CHAR);
-                (start, path, ESCAPE_CHAR, &self.break_chars, Quote::None)
-            };
-        let mut matches = filename_complete(&path, esc_char, break_chars, quote);
+        self.complete_path_with(line, pos, &Config::default())
+    }
+
+    fn complete_path_with(
+        &self,
+        line: &str,
+        pos: usize,
+        config: &Config,
+    ) -> Result<(usize, Vec<Pair>)> {
+        let ctx = CompletionContext::with_dialect(line, pos, self.quoting);
+        let mut matches = self.complete_file_names(ctx.word(), ctx.quote(), config);
         #[allow(clippy::unnecessary_sort_by)]
         matches.sort_by(|a, b| a.display().cmp(b.display()));
-        Ok((start, matches))
+        Ok((ctx.start(), matches))
+    }
+
+    /// Candidates for the unquoted `path`
+    fn complete_file_names(&self, path: &str, quote: Quote, config: &Config) -> Vec<Pair> {
+        use std::env::current_dir;
+
+        let sep = path::MAIN_SEPARATOR;
+        let (dir_name, file_name) = match path.rfind(sep) {
+            Some(idx) => path.split_at(idx + sep.len_utf8()),
+            None => ("", path),
+        };
+
+        let mut entries: Vec<Pair> = Vec::new();
+
+        if dir_name.is_empty() && file_name.starts_with('~') {
+            // ~[user] => ~[user]/
+            if home_dir(&file_name[1..]).is_some() {
+                let path = format!("{}{}", file_name, sep);
+                entries.push(Pair {
+                    display: path.clone(),
+                    replacement: self.quoting.quote(path, quote),
+                });
+                return entries;
+            }
+        }
+
+        let dir_path = Path::new(dir_name);
+        let dir = if let Some(dir) = expand_tilde(dir_name) {
+            dir
+        } else if dir_path.is_relative() {
+            if let Ok(cwd) = current_dir() {
+                cwd.join(dir_path)
+            } else {
+                dir_path.to_path_buf()
+            }
+        } else {
+            dir_path.to_path_buf()
+        };
+
+        // if dir doesn't exist, then don't offer any completions
+        if !dir.exists() {
+            return entries;
+        }
+
+        let show_hidden = match self.hidden_files {
+            HiddenFiles::Show => true,
+            HiddenFiles::WhenDotTyped => file_name.starts_with('.'),
+            HiddenFiles::Hide => false,
+        };
+        // if any of the below IO operations have errors, just ignore them
+        if let Ok(read_dir) = dir.read_dir() {
+            let ignore_case = config.completion_ignore_case();
+            let file_name = normalize(file_name, ignore_case);
+            for entry in read_dir.flatten() {
+                if let Some(s) = entry.file_name().to_str() {
+                    if s.starts_with('.') && !show_hidden {
+                        continue;
+                    }
+                    let ns = normalize(s, ignore_case);
+                    if ns.starts_with(file_name.as_ref()) {
+                        if let Ok(metadata) = fs::metadata(entry.path()) {
+                            let mut path = String::from(dir_name) + s;
+                            if metadata.is_dir() && config.mark_directories() {
+                                path.push(sep);
+                            }
+                            entries.push(Pair {
+                                display: String::from(s),
+                                replacement: self.quoting.quote(path, quote),
+                            });
+                        } // else ignore PermissionDenied
+                    }
+                }
+            }
+        }
+        entries
     }
 }
 
-impl Default for FilenameCompleter {
+impl Default for PathCompleter {
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl Completer for FilenameCompleter {
+impl Completer for PathCompleter {
     type Candidate = Pair;
 
-    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Result<(usize, Vec<Pair>)> {
-        self.complete_path(line, pos)
+    fn complete(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Result<(usize, Vec<Pair>)> {
+        self.complete_path_with(line, pos, ctx.config())
+    }
+}
+
+/// Home directory of `user` (of the current user if empty)
+fn home_dir(user: &str) -> Option<PathBuf> {
+    if user.is_empty() {
+        #[cfg(feature = "with-dirs")]
+        {
+            dirs_next::home_dir()
+        }
+        #[cfg(not(feature = "with-dirs"))]
+        {
+            std::env::var_os("HOME").map(PathBuf::from)
+        }
+    } else {
+        #[cfg(unix)]
+        {
+            nix::unistd::User::from_name(user)
+                .ok()
+                .flatten()
+                .map(|user| user.dir)
+        }
+        #[cfg(not(unix))]
+        {
+            None
+        }
     }
 }
 
+/// `dir_name` with a leading `~[user]` replaced by the home directory (of
+/// `user`)
+fn expand_tilde(dir_name: &str) -> Option<PathBuf> {
+    let tilde = dir_name.strip_prefix('~')?;
+    let sep = path::MAIN_SEPARATOR;
+    let (user, rel_path) = match tilde.find(sep) {
+        Some(idx) => (&tilde[..idx], &tilde[idx + sep.len_utf8()..]),
+        None => (tilde, ""),
+    };
+    home_dir(user).map(|home| home.join(rel_path))
+}
+
 /// Remove escape char
 pub fn unescape(input: &str, esc_char: Option<char>) -> Cow<'_, str> {
     let esc_char = if let Some(c) = esc_char {
@@ -313,91 +1674,19 @@ pub fn escape(
     result
 }
 
-fn filename_complete(
-    path: &str,
-    esc_char: Option<char>,
-    break_chars: &[u8],
-    quote: Quote,
-) -> Vec<Pair> {
-    #[cfg(feature = "with-dirs")]
-    use dirs_next::home_dir;
-    use std::env::current_dir;
-
-    let sep = path::MAIN_SEPARATOR;
-    let (dir_name, file_name) = match path.rfind(sep) {
-        Some(idx) => path.split_at(idx + sep.len_utf8()),
-        None => ("", path),
-    };
-
-    let dir_path = Path::new(dir_name);
-    let dir = if dir_path.starts_with("~") {
-        // ~[/...]
-        #[cfg(feature = "with-dirs")]
-        {
-            if let Some(home) = home_dir() {
-                match dir_path.strip_prefix("~") {
-                    Ok(rel_path) => home.join(rel_path),
-                    _ => home,
-                }
-            } else {
-                dir_path.to_path_buf()
-            }
-        }
-        #[cfg(not(feature = "with-dirs"))]
-        {
-            dir_path.to_path_buf()
-        }
-    } else if dir_path.is_relative() {
-        // TODO ~user[/...] (https://crates.io/crates/users)
-        if let Ok(cwd) = current_dir() {
-            cwd.join(dir_path)
-        } else {
-            dir_path.to_path_buf()
-        }
-    } else {
-        dir_path.to_path_buf()
-    };
-
-    let mut entries: Vec<Pair> = Vec::new();
-
-    // if dir doesn't exist, then don't offer any completions
-    if !dir.exists() {
-        return entries;
-    }
-
-    // if any of the below IO operations have errors, just ignore them
-    if let Ok(read_dir) = dir.read_dir() {
-        let file_name = normalize(file_name);
-        for entry in read_dir.flatten() {
-            if let Some(s) = entry.file_name().to_str() {
-                let ns = normalize(s);
-                if ns.starts_with(file_name.as_ref()) {
-                    if let Ok(metadata) = fs::metadata(entry.path()) {
-                        let mut path = String::from(dir_name) + s;
-                        if metadata.is_dir() {
-                            path.push(sep);
-                        }
-                        entries.push(Pair {
-                            display: String::from(s),
-                            replacement: escape(path, esc_char, break_chars, quote),
-                        });
-                    } // else ignore PermissionDenied
-                }
-            }
-        }
-    }
-    entries
-}
-
 #[cfg(any(windows, target_os = "macos"))]
-fn normalize(s: &str) -> Cow<str> {
+fn normalize(s: &str, _ignore_case: bool) -> Cow<'_, str> {
     // case insensitive
     Cow::Owned(s.to_lowercase())
 }
 
 #[cfg(not(any(windows, target_os = "macos")))]
-fn normalize(s: &str) -> Cow<str> {
-    Cow::Borrowed(s)
+fn normalize(s: &str, ignore_case: bool) -> Cow<'_, str> {
+    if ignore_case {
+        Cow::Owned(s.to_lowercase())
+    } else {
+        Cow::Borrowed(s)
+    }
 }
 
 /// Given a `line` and a cursor `pos`ition,
@@ -441,6 +1730,18 @@ pub fn extract_word<'l>(
 
 /// Returns the longest common prefix among all `Candidate::replacement()`s.
 pub fn longest_common_prefix<C: Candidate>(candidates: &[C]) -> Option<&str> {
+    common_prefix(candidates, false)
+}
+
+/// Returns the longest common prefix among all `Candidate::replacement()`s,
+/// ignoring ASCII case differences.
+///
+/// The prefix is taken from the first candidate.
+pub fn longest_common_prefix_ignore_case<C: Candidate>(candidates: &[C]) -> Option<&str> {
+    common_prefix(candidates, true)
+}
+
+fn common_prefix<C: Candidate>(candidates: &[C], ignore_case: bool) -> Option<&str> {
     if candidates.is_empty() {
         return None;
     } else if candidates.len() == 1 {
@@ -453,7 +1754,11 @@ pub fn longest_common_prefix<C: Candidate>(candidates: &[C]) -> Option<&str> {
             let b2 = candidates[i + 1].replacement().as_bytes();
             if b1.len() <= longest_common_prefix
                 || b2.len() <= longest_common_prefix
-                || b1[longest_common_prefix] != b2[longest_common_prefix]
+                || !eq_byte(
+                    b1[longest_common_prefix],
+                    b2[longest_common_prefix],
+                    ignore_case,
+                )
             {
                 break 'o;
             }
@@ -470,6 +1775,14 @@ pub fn longest_common_prefix<C: Candidate>(candidates: &[C]) -> Option<&str> {
     Some(&candidate[0..longest_common_prefix])
 }
 
+fn eq_byte(b1: u8, b2: u8, ignore_case: bool) -> bool {
+    if ignore_case {
+        b1.eq_ignore_ascii_case(&b2)
+    } else {
+        b1 == b2
+    }
+}
+
 #[derive(PartialEq)]
 enum ScanMode {
     DoubleQuote,
@@ -482,7 +1795,7 @@ enum ScanMode {
 /// try to find an unclosed single/double quote in `s`.
 /// Return `None` if no unclosed quote is found.
 /// Return the unclosed quote position and if it is a double quote.
-fn find_unclosed_quote(s: &str) -> Option<(usize, Quote)> {
+pub(crate) fn find_unclosed_quote(s: &str) -> Option<(usize, Quote)> {
     let char_indices = s.char_indices();
     let mut mode = ScanMode::Normal;
     let mut quote_index = 0;
@@ -532,7 +1845,7 @@ fn find_unclosed_quote(s: &str) -> Option<(usize, Quote)> {
 mod tests {
     #[test]
     pub fn extract_word() {
-        let break_chars: &[u8] = &super::DEFAULT_BREAK_CHARS;
+        let break_chars: &[u8] = &super::POSIX_BREAK_CHARS;
         let line = "ls '/usr/local/b";
         assert_eq!(
             (4, "/usr/local/b"),
@@ -563,7 +1876,7 @@ mod tests {
 
     #[test]
     pub fn escape() {
-        let break_chars: &[u8] = &super::DEFAULT_BREAK_CHARS;
+        let break_chars: &[u8] = &super::POSIX_BREAK_CHARS;
         let input = String::from("/usr/local/b");
         assert_eq!(
             input.clone(),
@@ -612,6 +1925,260 @@ mod tests {
         assert_eq!(Some("f"), lcp);
     }
 
+    #[test]
+    pub fn longest_common_prefix_ignore_case() {
+        let candidates = vec![String::from("Makefile"), String::from("makedepend")];
+        assert_eq!(None, super::longest_common_prefix(&candidates));
+        let lcp = super::longest_common_prefix_ignore_case(&candidates);
+        assert_eq!(Some("Make"), lcp);
+    }
+
+    #[test]
+    pub fn filename_complete() {
+        use crate::config::Config;
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("Docs")).unwrap();
+        fs::write(dir.path().join("data.txt"), "").unwrap();
+        let mut prefix = dir.path().to_str().unwrap().to_owned();
+        prefix.push(std::path::MAIN_SEPARATOR);
+        let sep = std::path::MAIN_SEPARATOR.to_string();
+        let complete = |word: &str, config: &Config| {
+            let path = prefix.clone() + word;
+            let mut pairs = super::PathCompleter::new()
+                .quoting(super::QuotingDialect::Raw)
+                .complete_file_names(&path, super::Quote::None, config);
+            pairs.sort_by(|a, b| a.display.cmp(&b.display));
+            pairs
+                .into_iter()
+                .map(|p| p.replacement[prefix.len()..].to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        let config = Config::default();
+        assert_eq!(vec!["Docs".to_owned() + &sep], complete("Do", &config));
+        if cfg!(not(any(windows, target_os = "macos"))) {
+            assert_eq!(vec!["data.txt"], complete("d", &config));
+        }
+        let config = Config::builder().completion_ignore_case(true).build();
+        assert_eq!(
+            vec!["Docs".to_owned() + &sep, "data.txt".to_owned()],
+            complete("d", &config)
+        );
+        let config = Config::builder()
+            .completion_ignore_case(true)
+            .mark_directories(false)
+            .build();
+        assert_eq!(vec!["Docs"], complete("do", &config));
+    }
+
+    #[test]
+    pub fn path_completer() {
+        use super::{Candidate, HiddenFiles, PathCompleter, QuotingDialect};
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("my file.txt"), "").unwrap();
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+        let mut prefix = dir.path().to_str().unwrap().to_owned();
+        prefix.push(std::path::MAIN_SEPARATOR);
+        let complete = |completer: &PathCompleter, line: &str| {
+            let (_, pairs) = completer
+                .complete_path(
+                    &line.replace("DIR/", &prefix),
+                    line.len() + prefix.len() - 4,
+                )
+                .unwrap();
+            pairs
+                .iter()
+                .map(|p| p.replacement().replace(&prefix, "DIR/"))
+                .collect::<Vec<_>>()
+        };
+
+        let posix = PathCompleter::new().quoting(QuotingDialect::Posix);
+        assert_eq!(vec!["DIR/my\\ file.txt"], complete(&posix, "cat DIR/my"));
+        assert_eq!(vec!["DIR/my file.txt"], complete(&posix, "cat 'DIR/my"));
+        let windows = PathCompleter::new().quoting(QuotingDialect::Windows);
+        assert_eq!(vec!["\"DIR/my file.txt"], complete(&windows, "cat DIR/my"));
+        let raw = PathCompleter::new().quoting(QuotingDialect::Raw);
+        assert_eq!(vec!["DIR/my file.txt"], complete(&raw, "cat DIR/my"));
+
+        let posix = posix.hidden_files(HiddenFiles::WhenDotTyped);
+        assert_eq!(vec!["DIR/my\\ file.txt"], complete(&posix, "cat DIR/"));
+        assert_eq!(vec!["DIR/.hidden"], complete(&posix, "cat DIR/."));
+        let posix = posix.hidden_files(HiddenFiles::Hide);
+        assert!(complete(&posix, "cat DIR/.").is_empty());
+    }
+
+    #[test]
+    pub fn completion_context() {
+        use super::{CompletionContext, Quote, QuotingDialect, Word};
+
+        let line = r#"echo a\ b "c \"d\"" 'e\' f"#;
+        let ctx = CompletionContext::with_dialect(line, line.len(), QuotingDialect::Posix);
+        assert_eq!(
+            vec!["echo", "a b", "c \"d\"", "e\\", "f"],
+            ctx.words()
+                .iter()
+                .map(|w| w.text.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(5..9, ctx.words()[1].range);
+        assert_eq!(4, ctx.word_index());
+        assert_eq!(("f", Quote::None), (ctx.word(), ctx.quote()));
+
+        let line = "cc -o=out  main.c";
+        let ctx = CompletionContext::with_dialect(line, 10, QuotingDialect::Posix);
+        assert_eq!(2, ctx.word_index()); // new word
+        assert_eq!("", ctx.word());
+        let ctx = CompletionContext::with_dialect(line, 7, QuotingDialect::Posix);
+        assert_eq!(1, ctx.word_index());
+        assert_eq!((6, "o"), (ctx.start(), ctx.word()));
+
+        let line = r#"type "C:\Program Files\a"#;
+        let ctx = CompletionContext::with_dialect(line, line.len(), QuotingDialect::Windows);
+        assert_eq!(
+            &Word {
+                range: 5..line.len(),
+                text: "C:\\Program Files\\a".to_owned()
+            },
+            &ctx.words()[1]
+        );
+        assert_eq!(Quote::Double, ctx.quote());
+        let ctx = CompletionContext::with_dialect(line, line.len(), QuotingDialect::Raw);
+        assert_eq!(3, ctx.words().len());
+        assert_eq!(("Files\\a", Quote::None), (ctx.word(), ctx.quote()));
+    }
+
+    #[test]
+    pub fn update() {
+        use super::Completer;
+        use crate::line_buffer::LineBuffer;
+
+        struct Default;
+        impl Completer for Default {
+            type Candidate = String;
+        }
+        let update = |line: &str, pos: usize, start: usize, elected: &str| {
+            let mut lb = LineBuffer::init(line, pos, None);
+            Default.update(&mut lb, start, elected);
+            (lb.as_str().to_owned(), lb.pos())
+        };
+        assert_eq!(("cargo".to_owned(), 5), update("car", 3, 0, "cargo"));
+        assert_eq!(
+            ("cargo --all".to_owned(), 5),
+            update("cargo --all", 3, 0, "cargo")
+        );
+        assert_eq!(
+            ("cargo --all".to_owned(), 6),
+            update("cargo --all", 2, 0, "cargo ")
+        );
+        assert_eq!(("(cargo)".to_owned(), 6), update("(cargo)", 3, 1, "cargo"));
+        assert_eq!(
+            ("cargo --all".to_owned(), 6),
+            update("car --all", 3, 0, "cargo ")
+        );
+        // common prefix
+        assert_eq!(("cargo".to_owned(), 2), update("cargo", 1, 0, "ca"));
+        // unrelated text after the cursor
+        assert_eq!(("cargoxyz".to_owned(), 5), update("caxyz", 2, 0, "cargo"));
+    }
+
+    #[test]
+    pub fn tilde() {
+        let sep = std::path::MAIN_SEPARATOR;
+        if let Some(home) = super::home_dir("") {
+            assert_eq!(
+                Some(home.join("src")),
+                super::expand_tilde(&format!("~{}src{}", sep, sep))
+            );
+            let (_, pairs) = super::PathCompleter::new()
+                .complete_path("cd ~", 4)
+                .unwrap();
+            assert_eq!(format!("~{}", sep), pairs[0].replacement);
+        }
+        #[cfg(unix)]
+        assert!(super::expand_tilde("~root/").is_some());
+        assert_eq!(None, super::expand_tilde("/tmp/"));
+    }
+
+    #[test]
+    pub fn completer_set() {
+        use super::{Candidate, Completer, CompleterSet, Completion, Pair};
+        use crate::history::History;
+        use crate::{Context, Result};
+
+        struct Words(&'static [&'static str]);
+        impl Completer for Words {
+            type Candidate = Pair;
+
+            fn complete(
+                &self,
+                line: &str,
+                pos: usize,
+                _: &Context<'_>,
+            ) -> Result<(usize, Vec<Pair>)> {
+                let start = line[..pos].rfind(' ').map_or(0, |i| i + 1);
+                let word = &line[start..pos];
+                let pairs = self
+                    .0
+                    .iter()
+                    .filter(|w| w.starts_with(word))
+                    .map(|w| Pair {
+                        display: w.to_string(),
+                        replacement: w.to_string(),
+                    })
+                    .collect();
+                Ok((start, pairs))
+            }
+        }
+        /// Completes the last path component
+        struct Components;
+        impl Completer for Components {
+            type Candidate = Completion;
+
+            fn complete(
+                &self,
+                line: &str,
+                pos: usize,
+                _: &Context<'_>,
+            ) -> Result<(usize, Vec<Completion>)> {
+                Ok(match line[..pos].rfind('/') {
+                    Some(i) => (i + 1, vec![Completion::new("bin")]),
+                    None => (pos, vec![]),
+                })
+            }
+        }
+
+        let history = History::new();
+        let ctx = Context::new(&history);
+        let mut completer = CompleterSet::new();
+        completer.add(0, Words(&["cargo", "cat"]));
+        completer.add(10, Words(&["cat", "cd"]));
+        completer.add(0, Components);
+        completer.add(5, Words(&["/usr/lib"]));
+        assert_eq!(4, completer.len());
+        let (start, completions) = completer.complete("c", 1, &ctx).unwrap();
+        assert_eq!(0, start);
+        assert_eq!(
+            vec!["cat", "cd", "cargo"],
+            completions
+                .iter()
+                .map(|c| c.replacement())
+                .collect::<Vec<_>>()
+        );
+        let (start, completions) = completer.complete("ls /usr/", 8, &ctx).unwrap();
+        assert_eq!(3, start);
+        assert_eq!(
+            vec!["/usr/lib", "/usr/bin"],
+            completions
+                .iter()
+                .map(|c| c.replacement())
+                .collect::<Vec<_>>()
+        );
+    }
+
     #[test]
     pub fn find_unclosed_quote() {
         assert_eq!(None, super::find_unclosed_quote("ls /etc"));
@@ -632,6 +2199,208 @@ mod tests {
     #[cfg(windows)]
     #[test]
     pub fn normalize() {
-        assert_eq!(super::normalize("Windows"), "windows")
+        assert_eq!(super::normalize("Windows", false), "windows")
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    pub fn match_style() {
+        use super::MatchStyle;
+        assert_eq!(
+            Some((0, vec![0..2])),
+            MatchStyle::Prefix.matches("ca", "cargo")
+        );
+        assert_eq!(None, MatchStyle::Prefix.matches("go", "cargo"));
+        assert_eq!(
+            Some((-3, vec![3..5])),
+            MatchStyle::Substring.matches("go", "cargo")
+        );
+        assert_eq!(None, MatchStyle::Fuzzy.matches("gc", "cargo"));
+        // shortest window
+        let (_, ranges) = MatchStyle::Fuzzy.matches("ab", "a_xab").unwrap();
+        assert_eq!(vec![3..5], ranges);
+        // smart case
+        assert!(MatchStyle::Fuzzy.matches("cb", "CargoBuild").is_some());
+        assert!(MatchStyle::Fuzzy.matches("Cb", "CargoBuild").is_none());
+        let (_, ranges) = MatchStyle::Fuzzy.matches("éc", "été_cas").unwrap();
+        assert_eq!(vec![3..5, 6..7], ranges);
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    pub fn fuzzy_order() {
+        use super::{Candidate, MatchStyle};
+        let candidates = ["xfxoxo", "afoo", "foo_bar", "fbar", "f_oo"];
+        let matched = MatchStyle::Fuzzy.filter("fo", candidates);
+        assert_eq!(
+            vec!["foo_bar", "f_oo", "afoo", "xfxoxo"],
+            matched.iter().map(|m| m.display()).collect::<Vec<_>>()
+        );
+        assert!(matched.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!([0..2], matched[0].matches());
+        assert_eq!([0..1, 2..3], matched[1].matches());
+    }
+
+    #[test]
+    pub fn spawn_completion() {
+        use std::sync::Arc;
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct Unpark(std::thread::Thread);
+        impl Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = super::spawn_completion(|| Ok((1, vec!["rustc".to_owned()])));
+        let completions = loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(completions) => break completions.unwrap(),
+                Poll::Pending => std::thread::park(),
+            }
+        };
+        assert_eq!((1, vec!["rustc".to_owned()]), completions);
+
+        let mut future = super::spawn_completion::<String, _>(|| panic!("unreachable server"));
+        let result = loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => break result,
+                Poll::Pending => std::thread::park(),
+            }
+        };
+        assert!(result.is_err());
+    }
+
+    #[test]
+    pub fn sort_by_group() {
+        use super::{Candidate, Completion};
+        let mut candidates: Vec<Completion> = [
+            ("a", Some("files")),
+            ("b", Some("branches")),
+            ("c", None),
+            ("d", Some("files")),
+        ]
+        .iter()
+        .map(|(name, group)| Completion {
+            group: group.map(str::to_owned),
+            ..Completion::new(*name)
+        })
+        .collect();
+        super::sort_by_group(&mut candidates);
+        assert_eq!(
+            vec!["c", "a", "d", "b"],
+            candidates
+                .iter()
+                .map(Candidate::replacement)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                (None, 0..1),
+                (Some("files"), 1..3),
+                (Some("branches"), 3..4)
+            ],
+            super::groups(&candidates)
+        );
+    }
+
+    #[test]
+    pub fn cached_completer() {
+        use super::{CachedCompleter, Completer};
+        use crate::history::History;
+        use crate::{Context, Result};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        #[derive(Default)]
+        struct Counting(AtomicUsize);
+        impl Completer for Counting {
+            type Candidate = String;
+
+            fn complete(
+                &self,
+                _line: &str,
+                pos: usize,
+                _ctx: &Context<'_>,
+            ) -> Result<(usize, Vec<String>)> {
+                let n = self.0.fetch_add(1, Ordering::Relaxed);
+                Ok((pos, vec![n.to_string()]))
+            }
+        }
+
+        let history = History::new();
+        let ctx = Context::new(&history);
+        let completer = CachedCompleter::new(Counting::default(), Duration::from_secs(60));
+        assert_eq!(
+            (2, vec!["0".to_owned()]),
+            completer.complete("ls", 2, &ctx).unwrap()
+        );
+        // the text after the cursor is not part of the key
+        assert_eq!(
+            (2, vec!["0".to_owned()]),
+            completer.complete("ls -l", 2, &ctx).unwrap()
+        );
+        assert_eq!(
+            (1, vec!["1".to_owned()]),
+            completer.complete("ls", 1, &ctx).unwrap()
+        );
+        completer
+            .invalidator()
+            .invalidate_matching(|line| line == "l");
+        assert_eq!(
+            (2, vec!["0".to_owned()]),
+            completer.complete("ls", 2, &ctx).unwrap()
+        );
+        assert_eq!(
+            (1, vec!["2".to_owned()]),
+            completer.complete("ls", 1, &ctx).unwrap()
+        );
+        completer.invalidate();
+        assert_eq!(
+            (2, vec!["3".to_owned()]),
+            completer.complete("ls", 2, &ctx).unwrap()
+        );
+        // expired
+        let completer = CachedCompleter::new(Counting::default(), Duration::from_secs(0));
+        assert_eq!(
+            (2, vec!["0".to_owned()]),
+            completer.complete("ls", 2, &ctx).unwrap()
+        );
+        assert_eq!(
+            (2, vec!["1".to_owned()]),
+            completer.complete("ls", 2, &ctx).unwrap()
+        );
+        assert_eq!(2, completer.get_ref().0.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    pub fn edit_distance() {
+        use super::edit_distance;
+        assert_eq!(0, edit_distance("cargo", "cargo"));
+        assert_eq!(1, edit_distance("carg", "cargo"));
+        assert_eq!(1, edit_distance("cargoo", "cargo"));
+        assert_eq!(1, edit_distance("carqo", "cargo"));
+        assert_eq!(1, edit_distance("cagro", "cargo"));
+        assert_eq!(3, edit_distance("", "cat"));
+        assert_eq!(2, edit_distance("été", "ete"));
+    }
+
+    #[test]
+    pub fn corrections() {
+        use super::{corrections, Candidate};
+        let words = vec!["rustc", "rustup", "rust", "rustc"];
+        // the closest first, then in the vocabulary order
+        let found = corrections("rustpu", words, 2);
+        assert_eq!(
+            vec![("rustup", 1), ("rustc", 2), ("rust", 2)],
+            found
+                .iter()
+                .map(|c| (c.word.as_str(), c.distance))
+                .collect::<Vec<_>>()
+        );
+        assert!(found[0].is_correction());
+        assert!(corrections("rust", vec!["rust"], 2).is_empty());
     }
 }
@@ -2,16 +2,47 @@
 use std::default::Default;
 
 /// User preferences
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Config {
     /// Maximum number of entries in History.
     max_history_size: usize, // history_max_entries
+    /// Maximum total size (in bytes) of the History entries.
+    history_max_bytes: usize,
+    history_eviction: HistoryEviction,
     history_duplicates: HistoryDuplicates,
     history_ignore_space: bool,
+    history_format: HistoryFormat,
+    history_search_mode: HistorySearchMode,
+    /// Expand `!` history references in accepted lines
+    history_expansion: bool,
+    hist_chars: HistChars,
     completion_type: CompletionType,
     /// When listing completion alternatives, only display
     /// one screen of possibilities at a time.
     completion_prompt_limit: usize,
+    /// Whether filename matching and completion ignore case
+    completion_ignore_case: bool,
+    /// List ambiguous completions immediately instead of ringing the bell
+    completion_show_all_if_ambiguous: bool,
+    /// List completions without common prefix immediately
+    completion_show_all_if_unmodified: bool,
+    /// Display one screen of completions at a time
+    page_completions: bool,
+    /// Screen columns used to list completions
+    completion_display_width: Option<usize>,
+    /// List completions sorted horizontally
+    print_completions_horizontally: bool,
+    /// Display the common prefix before cycling through candidates
+    menu_complete_display_prefix: bool,
+    /// Display the candidates below the line while cycling through them
+    completion_show_menu: bool,
+    /// Shown while the candidates of an asynchronous completer are pending
+    completion_pending_indicator: String,
+    /// Maximum edit distance of the corrections offered when no candidate
+    /// completes the word (0 to offer none)
+    completion_correction_distance: usize,
+    /// Append a path separator to completed directory names
+    mark_directories: bool,
     /// Duration (milliseconds) Rustyline will wait for a character when
     /// reading an ambiguous key sequence.
     keyseq_timeout: i32,
@@ -30,10 +61,34 @@ pub struct Config {
     tab_stop: usize,
     /// Indentation size for indent/dedent commands
     indent_size: usize,
+    /// Characters which make up emacs words
+    word_boundary: WordBoundary,
+    /// Inserted by `insert-comment`
+    comment_begin: String,
+    /// Show the editing mode in the prompt
+    show_mode_in_prompt: bool,
+    /// Mode indicators: emacs, vi insert and vi command mode
+    emacs_mode_string: String,
+    vi_ins_mode_string: String,
+    vi_cmd_mode_string: String,
+    /// Change the cursor shape with the vi input mode
+    cursor_shape: bool,
+    /// Copy the selection copied or cut to the system clipboard too
+    selection_clipboard: bool,
+    /// Characters inserted with their closing character
+    auto_pairs: Vec<(char, char)>,
+    /// Whether brackets are paired inside quoted strings too
+    auto_pairs_in_strings: bool,
     /// Check if cursor position is at leftmost before displaying prompt
     check_cursor_position: bool,
     /// Bracketed paste on unix platform
     enable_bracketed_paste: bool,
+    /// Convert bytes with the eighth bit set to ESC-prefixed sequences
+    convert_meta: bool,
+    /// Keep the eighth bit of input bytes
+    input_meta: bool,
+    /// Display characters with the eighth bit set directly
+    output_meta: bool,
 }
 
 impl Config {
@@ -51,6 +106,28 @@ impl Config {
         self.max_history_size = max_size;
     }
 
+    /// Tell the maximum total size (in bytes) of the history entries.
+    ///
+    /// By default, there is no limit (`usize::MAX`).
+    pub fn history_max_bytes(&self) -> usize {
+        self.history_max_bytes
+    }
+
+    pub(crate) fn set_history_max_bytes(&mut self, max_bytes: usize) {
+        self.history_max_bytes = max_bytes;
+    }
+
+    /// Tell which entries are dropped when the history is full.
+    ///
+    /// By default, `HistoryEviction::DropOldest`.
+    pub fn history_eviction(&self) -> HistoryEviction {
+        self.history_eviction
+    }
+
+    pub(crate) fn set_history_eviction(&mut self, eviction: HistoryEviction) {
+        self.history_eviction = eviction;
+    }
+
     /// Tell if lines which match the previous history entry are saved or not
     /// in the history list.
     ///
@@ -67,6 +144,31 @@ impl Config {
         };
     }
 
+    pub(crate) fn set_history_duplicates(&mut self, duplicates: HistoryDuplicates) {
+        self.history_duplicates = duplicates;
+    }
+
+    /// Like bash `HISTCONTROL`: a colon-separated list of `ignorespace`,
+    /// `ignoredups`, `ignoreboth` and `erasedups` (other values are ignored)
+    pub(crate) fn set_history_control(&mut self, value: &str) {
+        let mut duplicates = HistoryDuplicates::AlwaysAdd;
+        let mut ignore_space = false;
+        for control in value.split(':') {
+            match control {
+                "ignorespace" => ignore_space = true,
+                "ignoredups" => duplicates = HistoryDuplicates::IgnoreConsecutive,
+                "ignoreboth" => {
+                    ignore_space = true;
+                    duplicates = HistoryDuplicates::IgnoreConsecutive;
+                }
+                "erasedups" => duplicates = HistoryDuplicates::EraseAll,
+                _ => {}
+            }
+        }
+        self.history_duplicates = duplicates;
+        self.history_ignore_space = ignore_space;
+    }
+
     /// Tell if lines which begin with a space character are saved or not in
     /// the history list.
     ///
@@ -79,6 +181,51 @@ impl Config {
         self.history_ignore_space = yes;
     }
 
+    /// Format of the history files written by `save` / `append`.
+    ///
+    /// By default, `HistoryFormat::Escaped`.
+    pub fn history_format(&self) -> HistoryFormat {
+        self.history_format
+    }
+
+    pub(crate) fn set_history_format(&mut self, format: HistoryFormat) {
+        self.history_format = format;
+    }
+
+    /// What Up / Down do once the cursor is on the first / last line.
+    ///
+    /// By default, `HistorySearchMode::Navigate`.
+    pub fn history_search_mode(&self) -> HistorySearchMode {
+        self.history_search_mode
+    }
+
+    pub(crate) fn set_history_search_mode(&mut self, mode: HistorySearchMode) {
+        self.history_search_mode = mode;
+    }
+
+    /// Tell if `!` history references (like `!!` or `!$`) are expanded in
+    /// the lines returned by `readline` (see [`crate::expansion`]).
+    ///
+    /// By default, they are not.
+    pub fn history_expansion(&self) -> bool {
+        self.history_expansion
+    }
+
+    pub(crate) fn set_history_expansion(&mut self, yes: bool) {
+        self.history_expansion = yes;
+    }
+
+    /// Characters used by history expansion (like bash `histchars`).
+    ///
+    /// By default, `!`, `^` and `#`.
+    pub fn hist_chars(&self) -> HistChars {
+        self.hist_chars
+    }
+
+    pub(crate) fn set_hist_chars(&mut self, hist_chars: HistChars) {
+        self.hist_chars = hist_chars;
+    }
+
     /// Completion behaviour.
     ///
     /// By default, `CompletionType::Circular`.
@@ -93,11 +240,112 @@ impl Config {
         self.completion_prompt_limit
     }
 
+    /// Tell if filename matching and completion are performed in a
+    /// case-insensitive fashion.
+    ///
+    /// By default, they are not.
+    pub fn completion_ignore_case(&self) -> bool {
+        self.completion_ignore_case
+    }
+
+    /// Tell if words which have more than one possible completion cause the
+    /// matches to be listed immediately instead of ringing the bell (used for
+    /// `CompletionType::List` mode).
+    ///
+    /// By default, they don't.
+    pub fn completion_show_all_if_ambiguous(&self) -> bool {
+        self.completion_show_all_if_ambiguous
+    }
+
+    /// Tell if words which have more than one possible completion without
+    /// any common prefix to insert cause the matches to be listed immediately
+    /// instead of ringing the bell (used for `CompletionType::List` mode).
+    ///
+    /// By default, they don't.
+    pub fn completion_show_all_if_unmodified(&self) -> bool {
+        self.completion_show_all_if_unmodified
+    }
+
+    /// Tell if the list of possible completions is displayed one screen at
+    /// a time, with a `--More--` prompt (used for `CompletionType::List`
+    /// mode).
+    ///
+    /// By default, it is.
+    pub fn page_completions(&self) -> bool {
+        self.page_completions
+    }
+
+    /// Number of screen columns used to list the possible completions (one
+    /// per line if 0), the whole screen width being used if `None` or if it
+    /// is larger.
+    ///
+    /// By default, `None`.
+    pub fn completion_display_width(&self) -> Option<usize> {
+        self.completion_display_width
+    }
+
+    /// Tell if the possible completions are listed sorted horizontally (in
+    /// rows) rather than down the screen (in columns).
+    ///
+    /// By default, they are sorted down the screen.
+    pub fn print_completions_horizontally(&self) -> bool {
+        self.print_completions_horizontally
+    }
+
+    /// Tell if the common prefix of the list of possible completions is
+    /// inserted before cycling through the list (used for
+    /// `CompletionType::Circular` mode).
+    ///
+    /// By default, it is not.
+    pub fn menu_complete_display_prefix(&self) -> bool {
+        self.menu_complete_display_prefix
+    }
+
+    /// Tell if the possible completions are displayed below the line, the
+    /// current one highlighted, while cycling through them (used by
+    /// `menu-complete` and `CompletionType::Circular` mode).
+    ///
+    /// By default, they are not.
+    pub fn completion_show_menu(&self) -> bool {
+        self.completion_show_menu
+    }
+
+    /// Message shown below the line while the candidates of an asynchronous
+    /// completer are pending (see
+    /// [`Completer::complete_async`](crate::completion::Completer::complete_async)).
+    ///
+    /// By default, `…`.
+    pub fn completion_pending_indicator(&self) -> &str {
+        &self.completion_pending_indicator
+    }
+
+    pub(crate) fn set_completion_pending_indicator(&mut self, indicator: &str) {
+        self.completion_pending_indicator = indicator.to_owned();
+    }
+
+    /// Maximum edit distance (insertions, deletions, substitutions and
+    /// transpositions of characters) between the word and the spelling
+    /// corrections offered when no candidate completes it (see
+    /// [`Completer::vocabulary`](crate::completion::Completer::vocabulary)).
+    ///
+    /// By default, 0: no correction is offered.
+    pub fn completion_correction_distance(&self) -> usize {
+        self.completion_correction_distance
+    }
+
+    /// Tell if completed directory names have a path separator appended.
+    ///
+    /// By default, they do.
+    pub fn mark_directories(&self) -> bool {
+        self.mark_directories
+    }
+
     /// Duration (milliseconds) Rustyline will wait for a character when
     /// reading an ambiguous key sequence (used for `EditMode::Vi` mode on unix
     /// platform).
     ///
-    /// By default, no timeout (-1) or 500ms if `EditMode::Vi` is activated.
+    /// By default, no timeout (-1) or 500ms if `EditMode::Vi` (or a
+    /// `EditMode::Custom` mode) is activated.
     pub fn keyseq_timeout(&self) -> i32 {
         self.keyseq_timeout
     }
@@ -107,6 +355,14 @@ impl Config {
         self.edit_mode
     }
 
+    pub(crate) fn set_edit_mode(&mut self, edit_mode: EditMode) {
+        self.edit_mode = edit_mode;
+        self.keyseq_timeout = match edit_mode {
+            EditMode::Emacs => -1, // no timeout
+            EditMode::Vi | EditMode::Custom(_) => 500,
+        };
+    }
+
     /// Tell if lines are automatically added to the history.
     ///
     /// By default, they are not.
@@ -119,6 +375,10 @@ impl Config {
         self.bell_style
     }
 
+    pub(crate) fn set_bell_style(&mut self, bell_style: BellStyle) {
+        self.bell_style = bell_style;
+    }
+
     /// Tell if colors should be enabled.
     ///
     /// By default, they are except if stdout is not a TTY.
@@ -170,22 +430,191 @@ impl Config {
         self.indent_size = indent_size;
     }
 
+    /// Characters which make up words for the emacs word commands
+    ///
+    /// By default, alphanumeric characters.
+    pub fn word_boundary(&self) -> WordBoundary {
+        self.word_boundary
+    }
+
+    pub(crate) fn set_word_boundary(&mut self, word_boundary: WordBoundary) {
+        self.word_boundary = word_boundary;
+    }
+
+    /// String inserted at the beginning of the line by `insert-comment`
+    ///
+    /// By default, `#`.
+    pub fn comment_begin(&self) -> &str {
+        &self.comment_begin
+    }
+
+    pub(crate) fn set_comment_begin(&mut self, comment_begin: &str) {
+        self.comment_begin = comment_begin.to_owned();
+    }
+
+    /// Whether the editing mode is shown at the start of the (last line of
+    /// the) prompt, with [`emacs_mode_string`](Self::emacs_mode_string),
+    /// [`vi_ins_mode_string`](Self::vi_ins_mode_string) or
+    /// [`vi_cmd_mode_string`](Self::vi_cmd_mode_string)
+    ///
+    /// By default, it's disabled.
+    pub fn show_mode_in_prompt(&self) -> bool {
+        self.show_mode_in_prompt
+    }
+
+    pub(crate) fn set_show_mode_in_prompt(&mut self, yes: bool) {
+        self.show_mode_in_prompt = yes;
+    }
+
+    /// Mode indicator in emacs mode
+    ///
+    /// By default, `@`.
+    pub fn emacs_mode_string(&self) -> &str {
+        &self.emacs_mode_string
+    }
+
+    pub(crate) fn set_emacs_mode_string(&mut self, mode_string: &str) {
+        self.emacs_mode_string = mode_string.to_owned();
+    }
+
+    /// Mode indicator in vi insert mode
+    ///
+    /// By default, `(ins)`.
+    pub fn vi_ins_mode_string(&self) -> &str {
+        &self.vi_ins_mode_string
+    }
+
+    pub(crate) fn set_vi_ins_mode_string(&mut self, mode_string: &str) {
+        self.vi_ins_mode_string = mode_string.to_owned();
+    }
+
+    /// Mode indicator in vi command mode
+    ///
+    /// By default, `(cmd)`.
+    pub fn vi_cmd_mode_string(&self) -> &str {
+        &self.vi_cmd_mode_string
+    }
+
+    pub(crate) fn set_vi_cmd_mode_string(&mut self, mode_string: &str) {
+        self.vi_cmd_mode_string = mode_string.to_owned();
+    }
+
+    /// Whether the cursor shape is changed (with DECSCUSR escape sequences)
+    /// with the vi input mode: a block in command mode, a bar in insert mode
+    /// and an underline in replace mode. It is reset when the line is
+    /// accepted and when the terminal is given back (suspend, external
+    /// editor).
+    ///
+    /// By default, it's disabled.
+    pub fn cursor_shape(&self) -> bool {
+        self.cursor_shape
+    }
+
+    pub(crate) fn set_cursor_shape(&mut self, yes: bool) {
+        self.cursor_shape = yes;
+    }
+
+    /// Whether the text selected with shift + movement keys and copied
+    /// (Ctrl-Insert) or cut (Shift-Del) is also copied to the system
+    /// clipboard (with an OSC 52 sequence on Unix). Failures are ignored.
+    ///
+    /// By default, it's disabled.
+    pub fn selection_clipboard(&self) -> bool {
+        self.selection_clipboard
+    }
+
+    pub(crate) fn set_selection_clipboard(&mut self, yes: bool) {
+        self.selection_clipboard = yes;
+    }
+
+    /// Pairs of opening / closing characters: an opening character typed
+    /// before a blank, a closing character or the end of the line is
+    /// followed by its closing character, a closing character typed before
+    /// the same one is skipped over, and backspace between the two
+    /// characters of a pair deletes both (see [`AUTO_PAIRS`]).
+    ///
+    /// By default, there is none: auto-pairing is disabled.
+    pub fn auto_pairs(&self) -> &[(char, char)] {
+        &self.auto_pairs
+    }
+
+    pub(crate) fn set_auto_pairs(&mut self, pairs: &[(char, char)]) {
+        self.auto_pairs = pairs.to_vec();
+    }
+
+    /// Whether brackets are paired inside a quoted string too (quotes never
+    /// are)
+    ///
+    /// By default, they are not.
+    pub fn auto_pairs_in_strings(&self) -> bool {
+        self.auto_pairs_in_strings
+    }
+
+    pub(crate) fn set_auto_pairs_in_strings(&mut self, yes: bool) {
+        self.auto_pairs_in_strings = yes;
+    }
+
     /// Bracketed paste on unix platform
     ///
     /// By default, it's enabled.
     pub fn enable_bracketed_paste(&self) -> bool {
         self.enable_bracketed_paste
     }
+
+    pub(crate) fn set_enable_bracketed_paste(&mut self, enabled: bool) {
+        self.enable_bracketed_paste = enabled;
+    }
+
+    /// Tell if input bytes with the eighth bit set are converted to
+    /// ESC-prefixed sequences (i.e. Meta / Alt keys) on unix platform.
+    ///
+    /// By default, they are not.
+    pub fn convert_meta(&self) -> bool {
+        self.convert_meta
+    }
+
+    /// Tell if the eighth bit of input bytes is kept (i.e. UTF-8 input is
+    /// decoded) on unix platform. Otherwise, it is stripped.
+    ///
+    /// By default, it is kept.
+    pub fn input_meta(&self) -> bool {
+        self.input_meta
+    }
+
+    /// Tell if non-ASCII characters are displayed directly on unix platform.
+    /// Otherwise, their bytes are displayed as octal escapes (e.g. `\303\251`).
+    ///
+    /// By default, they are displayed directly.
+    pub fn output_meta(&self) -> bool {
+        self.output_meta
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             max_history_size: 100,
+            history_max_bytes: usize::MAX,
+            history_eviction: HistoryEviction::default(),
             history_duplicates: HistoryDuplicates::IgnoreConsecutive,
             history_ignore_space: false,
+            history_format: HistoryFormat::default(),
+            history_search_mode: HistorySearchMode::default(),
+            history_expansion: false,
+            hist_chars: HistChars::default(),
             completion_type: CompletionType::Circular, // TODO Validate
             completion_prompt_limit: 100,
+            completion_ignore_case: false,
+            completion_show_all_if_ambiguous: false,
+            completion_show_all_if_unmodified: false,
+            page_completions: true,
+            completion_display_width: None,
+            print_completions_horizontally: false,
+            menu_complete_display_prefix: false,
+            completion_show_menu: false,
+            completion_pending_indicator: "…".to_owned(),
+            completion_correction_distance: 0,
+            mark_directories: true,
             keyseq_timeout: -1,
             edit_mode: EditMode::Emacs,
             auto_add_history: false,
@@ -194,8 +623,21 @@ impl Default for Config {
             output_stream: OutputStreamType::Stdout,
             tab_stop: 8,
             indent_size: 2,
+            word_boundary: WordBoundary::Alphanumeric,
+            comment_begin: "#".to_owned(),
+            show_mode_in_prompt: false,
+            emacs_mode_string: "@".to_owned(),
+            vi_ins_mode_string: "(ins)".to_owned(),
+            vi_cmd_mode_string: "(cmd)".to_owned(),
+            cursor_shape: false,
+            selection_clipboard: false,
+            auto_pairs: Vec::new(),
+            auto_pairs_in_strings: false,
             check_cursor_position: false,
             enable_bracketed_paste: true,
+            convert_meta: false,
+            input_meta: true,
+            output_meta: true,
         }
     }
 }
@@ -207,7 +649,7 @@ pub enum BellStyle {
     Audible,
     /// Silent
     None,
-    /// Flash screen (not supported)
+    /// Flash screen (reverse video)
     Visible,
 }
 
@@ -226,14 +668,95 @@ impl Default for BellStyle {
 }
 
 /// History filter
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum HistoryDuplicates {
     /// No filter
     AlwaysAdd,
     /// a line will not be added to the history if it matches the previous entry
+    #[default]
     IgnoreConsecutive,
+    /// all the previous entries matching a line are removed before it is
+    /// added
+    EraseAll,
+}
+
+/// Entries dropped when the history is full (see
+/// [`Config::max_history_size`] and [`Config::history_max_bytes`])
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HistoryEviction {
+    /// The oldest entries
+    #[default]
+    DropOldest,
+    /// The oldest entries which are repeated later first, then the oldest
+    /// ones
+    DropOldestDuplicateFirst,
+}
+
+/// On-disk format of history files (see [`History`](crate::history::History)
+/// for details). All are read whatever the configured one is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HistoryFormat {
+    /// `#V2` header then one entry per line, with line feeds and backslashes
+    /// escaped as `\n` and `\\`
+    #[default]
+    Escaped,
+    /// Like bash `HISTFILE`: each entry is preceded by a `#<seconds since the
+    /// epoch>` timestamp line and spans as many lines as it contains
+    Bash,
+    /// Like zsh `HISTFILE` with `EXTENDED_HISTORY`: `: <seconds since the
+    /// epoch>:0;<entry>` lines, line feeds being preceded by a backslash
+    Zsh,
+    /// Like fish history files: `- cmd: <entry>` records followed by their
+    /// `when: <seconds since the epoch>` field, with line feeds and
+    /// backslashes escaped as `\n` and `\\`
+    Fish,
+}
+
+/// What Up / Down (and `k` / `j` in vi command mode) do once the cursor is
+/// on the first / last line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HistorySearchMode {
+    /// Previous / next history entry
+    #[default]
+    Navigate,
+    /// Previous / next history entry starting with the text before the cursor
+    /// (like `history-search-backward` / `history-search-forward`)
+    Prefix,
+    /// Previous / next history entry containing the line as typed, with the
+    /// matched text highlighted (like fish, or
+    /// `history-substring-search-backward` / `history-substring-search-forward`)
+    Substring,
 }
 
+/// Characters used by history expansion (like bash `histchars`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistChars {
+    /// Start of a history reference (`!!`, `!$`, ...)
+    pub expansion: char,
+    /// Quick substitution at the start of a line (`^old^new`)
+    pub substitution: char,
+    /// Start of a word which ends expansion (`None` to expand comments too)
+    pub comment: Option<char>,
+}
+
+impl Default for HistChars {
+    fn default() -> Self {
+        Self {
+            expansion: '!',
+            substitution: '^',
+            comment: Some('#'),
+        }
+    }
+}
+
+/// Usual pairs for [`Builder::auto_pairs`]: brackets, braces, parentheses and
+/// quotes
+pub const AUTO_PAIRS: &[(char, char)] =
+    &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
 /// Tab completion style
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[non_exhaustive]
@@ -261,6 +784,10 @@ pub enum EditMode {
     Emacs,
     /// Vi keymap
     Vi,
+    /// Mode registered by the application under this name (see
+    /// [`Editor::register_mode`](crate::Editor::register_mode)): emacs
+    /// keymap if there is no such mode
+    Custom(&'static str),
 }
 
 /// Colorization mode
@@ -275,6 +802,46 @@ pub enum ColorMode {
     Disabled,
 }
 
+/// Characters which make up words for the emacs word commands
+/// (`forward-word`, `backward-word`, `kill-word`, `backward-kill-word`, ...)
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub enum WordBoundary {
+    /// Words are separated by whitespaces only (`foo.bar::baz` is one word)
+    Whitespace,
+    /// Words are made of alphanumeric characters (like readline)
+    Alphanumeric,
+    /// Words are shell tokens: separated by whitespaces, quotes and shell
+    /// metacharacters (`|&;()<>`)
+    ShellToken,
+    /// Words are made of the characters matching the predicate
+    Custom(fn(char) -> bool),
+}
+
+impl WordBoundary {
+    /// Tell if `c` is part of a word
+    pub fn is_word_char(&self, c: char) -> bool {
+        match self {
+            WordBoundary::Whitespace => !c.is_whitespace(),
+            WordBoundary::Alphanumeric => c.is_alphanumeric(),
+            WordBoundary::ShellToken => !(c.is_whitespace() || "|&;()<>'\"`".contains(c)),
+            WordBoundary::Custom(f) => f(c),
+        }
+    }
+}
+
+/// Custom predicates are compared by address.
+impl PartialEq for WordBoundary {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (WordBoundary::Custom(f), WordBoundary::Custom(g)) => *f as usize == *g as usize,
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl Eq for WordBoundary {}
+
 /// Should the editor use stdout or stderr
 // TODO console term::TermTarget
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -306,6 +873,22 @@ impl Builder {
         self
     }
 
+    /// Set the maximum total size (in bytes) of the history entries.
+    ///
+    /// By default, there is no limit.
+    pub fn history_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.set_history_max_bytes(max_bytes);
+        self
+    }
+
+    /// Choose which entries are dropped when the history is full.
+    ///
+    /// By default, `HistoryEviction::DropOldest`.
+    pub fn history_eviction(mut self, eviction: HistoryEviction) -> Self {
+        self.set_history_eviction(eviction);
+        self
+    }
+
     /// Tell if lines which match the previous history entry are saved or not
     /// in the history list.
     ///
@@ -315,6 +898,21 @@ impl Builder {
         self
     }
 
+    /// Choose how lines which match history entries are saved.
+    ///
+    /// By default, `HistoryDuplicates::IgnoreConsecutive`.
+    pub fn history_duplicates(mut self, duplicates: HistoryDuplicates) -> Self {
+        self.set_history_duplicates(duplicates);
+        self
+    }
+
+    /// Set both `history_duplicates` and `history_ignore_space` from a
+    /// bash `HISTCONTROL` like value (e.g. `ignorespace:erasedups`).
+    pub fn history_control(mut self, value: &str) -> Self {
+        self.set_history_control(value);
+        self
+    }
+
     /// Tell if lines which begin with a space character are saved or not in
     /// the history list.
     ///
@@ -324,6 +922,38 @@ impl Builder {
         self
     }
 
+    /// Format of the history files written by `save` / `append`.
+    ///
+    /// By default, `HistoryFormat::Escaped`.
+    pub fn history_format(mut self, format: HistoryFormat) -> Self {
+        self.set_history_format(format);
+        self
+    }
+
+    /// What Up / Down do once the cursor is on the first / last line.
+    ///
+    /// By default, `HistorySearchMode::Navigate`.
+    pub fn history_search_mode(mut self, mode: HistorySearchMode) -> Self {
+        self.set_history_search_mode(mode);
+        self
+    }
+
+    /// Expand `!` history references in the lines returned by `readline`.
+    ///
+    /// By default, they are not expanded.
+    pub fn history_expansion(mut self, yes: bool) -> Self {
+        self.set_history_expansion(yes);
+        self
+    }
+
+    /// Characters used by history expansion (like bash `histchars`).
+    ///
+    /// By default, `!`, `^` and `#`.
+    pub fn hist_chars(mut self, hist_chars: HistChars) -> Self {
+        self.set_hist_chars(hist_chars);
+        self
+    }
+
     /// Set `completion_type`.
     pub fn completion_type(mut self, completion_type: CompletionType) -> Self {
         self.set_completion_type(completion_type);
@@ -337,11 +967,107 @@ impl Builder {
         self
     }
 
+    /// Perform filename matching and completion in a case-insensitive
+    /// fashion.
+    ///
+    /// By default, case is significant.
+    pub fn completion_ignore_case(mut self, yes: bool) -> Self {
+        self.set_completion_ignore_case(yes);
+        self
+    }
+
+    /// List ambiguous completions immediately instead of ringing the bell.
+    ///
+    /// By default, a second tab is needed.
+    pub fn completion_show_all_if_ambiguous(mut self, yes: bool) -> Self {
+        self.set_completion_show_all_if_ambiguous(yes);
+        self
+    }
+
+    /// List ambiguous completions immediately instead of ringing the bell
+    /// when they have no common prefix to insert.
+    ///
+    /// By default, a second tab is needed.
+    pub fn completion_show_all_if_unmodified(mut self, yes: bool) -> Self {
+        self.set_completion_show_all_if_unmodified(yes);
+        self
+    }
+
+    /// Display the list of possible completions one screen at a time.
+    ///
+    /// By default, they are paged.
+    pub fn page_completions(mut self, yes: bool) -> Self {
+        self.set_page_completions(yes);
+        self
+    }
+
+    /// Number of screen columns used to list the possible completions (one
+    /// per line if 0).
+    ///
+    /// By default, the whole screen width.
+    pub fn completion_display_width(mut self, width: Option<usize>) -> Self {
+        self.set_completion_display_width(width);
+        self
+    }
+
+    /// List the possible completions sorted horizontally (in rows).
+    ///
+    /// By default, they are sorted down the screen.
+    pub fn print_completions_horizontally(mut self, yes: bool) -> Self {
+        self.set_print_completions_horizontally(yes);
+        self
+    }
+
+    /// Insert the common prefix of the possible completions before cycling
+    /// through them.
+    ///
+    /// By default, cycling starts with the first completion.
+    pub fn menu_complete_display_prefix(mut self, yes: bool) -> Self {
+        self.set_menu_complete_display_prefix(yes);
+        self
+    }
+
+    /// Display the possible completions below the line while cycling through
+    /// them.
+    ///
+    /// By default, only the current one is shown, in the line.
+    pub fn completion_show_menu(mut self, yes: bool) -> Self {
+        self.set_completion_show_menu(yes);
+        self
+    }
+
+    /// Message shown below the line while the candidates of an asynchronous
+    /// completer are pending (empty to show nothing).
+    ///
+    /// By default, `…`.
+    pub fn completion_pending_indicator(mut self, indicator: &str) -> Self {
+        self.set_completion_pending_indicator(indicator);
+        self
+    }
+
+    /// Offer the words of the completer vocabulary within `distance` edits
+    /// of the word as corrections when no candidate completes it.
+    ///
+    /// By default, 0: no correction is offered.
+    pub fn completion_correction_distance(mut self, distance: usize) -> Self {
+        self.set_completion_correction_distance(distance);
+        self
+    }
+
+    /// Append a path separator to completed directory names.
+    ///
+    /// By default, they are marked.
+    pub fn mark_directories(mut self, yes: bool) -> Self {
+        self.set_mark_directories(yes);
+        self
+    }
+
     /// Timeout for ambiguous key sequences in milliseconds.
-    /// Currently, it is used only to distinguish a single ESC from an ESC
-    /// sequence.
-    /// After seeing an ESC key, wait at most `keyseq_timeout_ms` for another
-    /// byte.
+    /// It is used to distinguish a single ESC from an ESC sequence: after
+    /// seeing an ESC key, wait at most `keyseq_timeout_ms` for another byte.
+    /// And when a custom key sequence is bound and is also the beginning of a
+    /// longer bound sequence, wait at most `keyseq_timeout_ms` for the next
+    /// key (a negative value means: wait indefinitely).
     pub fn keyseq_timeout(mut self, keyseq_timeout_ms: i32) -> Self {
         self.set_keyseq_timeout(keyseq_timeout_ms);
         self
@@ -407,6 +1133,88 @@ impl Builder {
         self
     }
 
+    /// Characters which make up words for the emacs word commands
+    ///
+    /// By default, alphanumeric characters.
+    pub fn word_boundary(mut self, word_boundary: WordBoundary) -> Self {
+        self.set_word_boundary(word_boundary);
+        self
+    }
+
+    /// String inserted at the beginning of the line by `insert-comment`
+    ///
+    /// By default, `#`.
+    pub fn comment_begin(mut self, comment_begin: &str) -> Self {
+        self.set_comment_begin(comment_begin);
+        self
+    }
+
+    /// Show the editing mode at the start of the (last line of the) prompt
+    ///
+    /// By default, it's disabled.
+    pub fn show_mode_in_prompt(mut self, yes: bool) -> Self {
+        self.set_show_mode_in_prompt(yes);
+        self
+    }
+
+    /// Mode indicator in emacs mode
+    ///
+    /// By default, `@`.
+    pub fn emacs_mode_string(mut self, mode_string: &str) -> Self {
+        self.set_emacs_mode_string(mode_string);
+        self
+    }
+
+    /// Mode indicator in vi insert mode
+    ///
+    /// By default, `(ins)`.
+    pub fn vi_ins_mode_string(mut self, mode_string: &str) -> Self {
+        self.set_vi_ins_mode_string(mode_string);
+        self
+    }
+
+    /// Mode indicator in vi command mode
+    ///
+    /// By default, `(cmd)`.
+    pub fn vi_cmd_mode_string(mut self, mode_string: &str) -> Self {
+        self.set_vi_cmd_mode_string(mode_string);
+        self
+    }
+
+    /// Change the cursor shape with the vi input mode (block in command
+    /// mode, bar in insert mode)
+    ///
+    /// By default, it's disabled.
+    pub fn cursor_shape(mut self, yes: bool) -> Self {
+        self.set_cursor_shape(yes);
+        self
+    }
+
+    /// Copy the selection copied or cut to the system clipboard too
+    ///
+    /// By default, it's disabled.
+    pub fn selection_clipboard(mut self, yes: bool) -> Self {
+        self.set_selection_clipboard(yes);
+        self
+    }
+
+    /// Pairs of opening / closing characters inserted together, e.g.
+    /// [`AUTO_PAIRS`]
+    ///
+    /// By default, there is none.
+    pub fn auto_pairs(mut self, pairs: &[(char, char)]) -> Self {
+        self.set_auto_pairs(pairs);
+        self
+    }
+
+    /// Whether brackets are paired inside quoted strings too
+    ///
+    /// By default, they are not.
+    pub fn auto_pairs_in_strings(mut self, yes: bool) -> Self {
+        self.set_auto_pairs_in_strings(yes);
+        self
+    }
+
     /// Enable or disable bracketed paste on unix platform
     ///
     /// By default, it's enabled.
@@ -415,6 +1223,32 @@ impl Builder {
         self
     }
 
+    /// Convert input bytes with the eighth bit set to ESC-prefixed sequences
+    /// on unix platform.
+    ///
+    /// By default, they are not.
+    pub fn convert_meta(mut self, yes: bool) -> Self {
+        self.set_convert_meta(yes);
+        self
+    }
+
+    /// Keep (or strip) the eighth bit of input bytes on unix platform.
+    ///
+    /// By default, it is kept.
+    pub fn input_meta(mut self, yes: bool) -> Self {
+        self.set_input_meta(yes);
+        self
+    }
+
+    /// Display non-ASCII characters directly (or as octal escapes) on unix
+    /// platform.
+    ///
+    /// By default, they are displayed directly.
+    pub fn output_meta(mut self, yes: bool) -> Self {
+        self.set_output_meta(yes);
+        self
+    }
+
     /// Builds a `Config` with the settings specified so far.
     pub fn build(self) -> Config {
         self.p
@@ -437,6 +1271,20 @@ pub trait Configurer {
         self.config_mut().set_max_history_size(max_size);
     }
 
+    /// Set the maximum total size (in bytes) of the history entries.
+    ///
+    /// By default, there is no limit.
+    fn set_history_max_bytes(&mut self, max_bytes: usize) {
+        self.config_mut().set_history_max_bytes(max_bytes);
+    }
+
+    /// Choose which entries are dropped when the history is full.
+    ///
+    /// By default, `HistoryEviction::DropOldest`.
+    fn set_history_eviction(&mut self, eviction: HistoryEviction) {
+        self.config_mut().set_history_eviction(eviction);
+    }
+
     /// Tell if lines which match the previous history entry are saved or not
     /// in the history list.
     ///
@@ -445,6 +1293,29 @@ pub trait Configurer {
         self.config_mut().set_history_ignore_dups(yes);
     }
 
+    /// Choose how lines which match history entries are saved.
+    ///
+    /// By default, `HistoryDuplicates::IgnoreConsecutive`.
+    fn set_history_duplicates(&mut self, duplicates: HistoryDuplicates) {
+        self.config_mut().set_history_duplicates(duplicates);
+    }
+
+    /// Set both `history_duplicates` and `history_ignore_space` from a
+    /// bash `HISTCONTROL` like value (e.g. `ignorespace:erasedups`).
+    ///
+    /// ```
+    /// use rustyline::config::Configurer;
+    /// use rustyline::Editor;
+    ///
+    /// let mut rl = Editor::<()>::new();
+    /// if let Ok(value) = std::env::var("HISTCONTROL") {
+    ///     rl.set_history_control(&value);
+    /// }
+    /// ```
+    fn set_history_control(&mut self, value: &str) {
+        self.config_mut().set_history_control(value);
+    }
+
     /// Tell if lines which begin with a space character are saved or not in
     /// the history list.
     ///
@@ -452,6 +1323,35 @@ pub trait Configurer {
     fn set_history_ignore_space(&mut self, yes: bool) {
         self.config_mut().set_history_ignore_space(yes);
     }
+
+    /// Format of the history files written by `save` / `append`.
+    ///
+    /// By default, `HistoryFormat::Escaped`.
+    fn set_history_format(&mut self, format: HistoryFormat) {
+        self.config_mut().set_history_format(format);
+    }
+
+    /// What Up / Down do once the cursor is on the first / last line.
+    ///
+    /// By default, `HistorySearchMode::Navigate`.
+    fn set_history_search_mode(&mut self, mode: HistorySearchMode) {
+        self.config_mut().set_history_search_mode(mode);
+    }
+
+    /// Expand `!` history references in the lines returned by `readline`.
+    ///
+    /// By default, they are not expanded.
+    fn set_history_expansion(&mut self, yes: bool) {
+        self.config_mut().set_history_expansion(yes);
+    }
+
+    /// Characters used by history expansion (like bash `histchars`).
+    ///
+    /// By default, `!`, `^` and `#`.
+    fn set_hist_chars(&mut self, hist_chars: HistChars) {
+        self.config_mut().set_hist_chars(hist_chars);
+    }
+
     /// Set `completion_type`.
     fn set_completion_type(&mut self, completion_type: CompletionType) {
         self.config_mut().completion_type = completion_type;
@@ -463,6 +1363,91 @@ pub trait Configurer {
         self.config_mut().completion_prompt_limit = completion_prompt_limit;
     }
 
+    /// Perform filename matching and completion in a case-insensitive
+    /// fashion.
+    ///
+    /// By default, case is significant.
+    fn set_completion_ignore_case(&mut self, yes: bool) {
+        self.config_mut().completion_ignore_case = yes;
+    }
+
+    /// List ambiguous completions immediately instead of ringing the bell.
+    ///
+    /// By default, a second tab is needed.
+    fn set_completion_show_all_if_ambiguous(&mut self, yes: bool) {
+        self.config_mut().completion_show_all_if_ambiguous = yes;
+    }
+
+    /// List ambiguous completions immediately instead of ringing the bell
+    /// when they have no common prefix to insert.
+    ///
+    /// By default, a second tab is needed.
+    fn set_completion_show_all_if_unmodified(&mut self, yes: bool) {
+        self.config_mut().completion_show_all_if_unmodified = yes;
+    }
+
+    /// Display the list of possible completions one screen at a time.
+    ///
+    /// By default, they are paged.
+    fn set_page_completions(&mut self, yes: bool) {
+        self.config_mut().page_completions = yes;
+    }
+
+    /// Number of screen columns used to list the possible completions (one
+    /// per line if 0).
+    ///
+    /// By default, the whole screen width.
+    fn set_completion_display_width(&mut self, width: Option<usize>) {
+        self.config_mut().completion_display_width = width;
+    }
+
+    /// List the possible completions sorted horizontally (in rows).
+    ///
+    /// By default, they are sorted down the screen.
+    fn set_print_completions_horizontally(&mut self, yes: bool) {
+        self.config_mut().print_completions_horizontally = yes;
+    }
+
+    /// Insert the common prefix of the possible completions before cycling
+    /// through them.
+    ///
+    /// By default, cycling starts with the first completion.
+    fn set_menu_complete_display_prefix(&mut self, yes: bool) {
+        self.config_mut().menu_complete_display_prefix = yes;
+    }
+
+    /// Display the possible completions below the line while cycling through
+    /// them.
+    ///
+    /// By default, only the current one is shown, in the line.
+    fn set_completion_show_menu(&mut self, yes: bool) {
+        self.config_mut().completion_show_menu = yes;
+    }
+
+    /// Message shown below the line while the candidates of an asynchronous
+    /// completer are pending (empty to show nothing).
+    ///
+    /// By default, `…`.
+    fn set_completion_pending_indicator(&mut self, indicator: &str) {
+        self.config_mut()
+            .set_completion_pending_indicator(indicator);
+    }
+
+    /// Offer the words of the completer vocabulary within `distance` edits
+    /// of the word as corrections when no candidate completes it.
+    ///
+    /// By default, 0: no correction is offered.
+    fn set_completion_correction_distance(&mut self, distance: usize) {
+        self.config_mut().completion_correction_distance = distance;
+    }
+
+    /// Append a path separator to completed directory names.
+    ///
+    /// By default, they are marked.
+    fn set_mark_directories(&mut self, yes: bool) {
+        self.config_mut().mark_directories = yes;
+    }
+
     /// Timeout for ambiguous key sequences in milliseconds.
     fn set_keyseq_timeout(&mut self, keyseq_timeout_ms: i32) {
         self.config_mut().keyseq_timeout = keyseq_timeout_ms;
@@ -470,11 +1455,7 @@ pub trait Configurer {
 
     /// Choose between Emacs or Vi mode.
     fn set_edit_mode(&mut self, edit_mode: EditMode) {
-        self.config_mut().edit_mode = edit_mode;
-        match edit_mode {
-            EditMode::Emacs => self.set_keyseq_timeout(-1), // no timeout
-            EditMode::Vi => self.set_keyseq_timeout(500),
-        }
+        self.config_mut().set_edit_mode(edit_mode);
     }
 
     /// Tell if lines are automatically added to the history.
@@ -486,7 +1467,7 @@ pub trait Configurer {
 
     /// Set bell style: beep, flash or nothing.
     fn set_bell_style(&mut self, bell_style: BellStyle) {
-        self.config_mut().bell_style = bell_style;
+        self.config_mut().set_bell_style(bell_style);
     }
 
     /// Forces colorization on or off.
@@ -523,10 +1504,108 @@ pub trait Configurer {
         self.config_mut().set_indent_size(size);
     }
 
+    /// Characters which make up words for the emacs word commands
+    /// (`forward-word`, `backward-word`, `kill-word`, ...)
+    ///
+    /// By default, alphanumeric characters.
+    fn set_word_boundary(&mut self, word_boundary: WordBoundary) {
+        self.config_mut().set_word_boundary(word_boundary);
+    }
+
+    /// String inserted at the beginning of the line by `insert-comment`
+    /// (`comment-begin` in inputrc)
+    ///
+    /// By default, `#`.
+    fn set_comment_begin(&mut self, comment_begin: &str) {
+        self.config_mut().set_comment_begin(comment_begin);
+    }
+
+    /// Show the editing mode at the start of the (last line of the) prompt
+    /// (`show-mode-in-prompt` in inputrc)
+    ///
+    /// By default, it's disabled.
+    fn set_show_mode_in_prompt(&mut self, yes: bool) {
+        self.config_mut().set_show_mode_in_prompt(yes);
+    }
+
+    /// Mode indicator in emacs mode (`emacs-mode-string` in inputrc)
+    ///
+    /// By default, `@`.
+    fn set_emacs_mode_string(&mut self, mode_string: &str) {
+        self.config_mut().set_emacs_mode_string(mode_string);
+    }
+
+    /// Mode indicator in vi insert mode (`vi-ins-mode-string` in inputrc)
+    ///
+    /// By default, `(ins)`.
+    fn set_vi_ins_mode_string(&mut self, mode_string: &str) {
+        self.config_mut().set_vi_ins_mode_string(mode_string);
+    }
+
+    /// Mode indicator in vi command mode (`vi-cmd-mode-string` in inputrc)
+    ///
+    /// By default, `(cmd)`.
+    fn set_vi_cmd_mode_string(&mut self, mode_string: &str) {
+        self.config_mut().set_vi_cmd_mode_string(mode_string);
+    }
+
+    /// Change the cursor shape with the vi input mode (block in command
+    /// mode, bar in insert mode)
+    ///
+    /// By default, it's disabled.
+    fn set_cursor_shape(&mut self, yes: bool) {
+        self.config_mut().set_cursor_shape(yes);
+    }
+
+    /// Copy the selection copied or cut to the system clipboard too
+    ///
+    /// By default, it's disabled.
+    fn set_selection_clipboard(&mut self, yes: bool) {
+        self.config_mut().set_selection_clipboard(yes);
+    }
+
+    /// Pairs of opening / closing characters inserted together, e.g.
+    /// [`AUTO_PAIRS`]
+    ///
+    /// By default, there is none.
+    fn set_auto_pairs(&mut self, pairs: &[(char, char)]) {
+        self.config_mut().set_auto_pairs(pairs);
+    }
+
+    /// Whether brackets are paired inside quoted strings too
+    ///
+    /// By default, they are not.
+    fn set_auto_pairs_in_strings(&mut self, yes: bool) {
+        self.config_mut().set_auto_pairs_in_strings(yes);
+    }
+
     /// Enable or disable bracketed paste on unix platform
     ///
     /// By default, it's enabled.
     fn enable_bracketed_paste(&mut self, enabled: bool) {
-        self.config_mut().enable_bracketed_paste = enabled;
+        self.config_mut().set_enable_bracketed_paste(enabled);
+    }
+
+    /// Convert input bytes with the eighth bit set to ESC-prefixed sequences
+    /// on unix platform.
+    ///
+    /// By default, they are not.
+    fn set_convert_meta(&mut self, yes: bool) {
+        self.config_mut().convert_meta = yes;
+    }
+
+    /// Keep (or strip) the eighth bit of input bytes on unix platform.
+    ///
+    /// By default, it is kept.
+    fn set_input_meta(&mut self, yes: bool) {
+        self.config_mut().input_meta = yes;
+    }
+
+    /// Display non-ASCII characters directly (or as octal escapes) on unix
+    /// platform.
+    ///
+    /// By default, they are displayed directly.
+    fn set_output_meta(&mut self, yes: bool) {
+        self.config_mut().output_meta = yes;
     }
 }
@@ -0,0 +1,68 @@
+//! Editing modes implemented by applications (see
+//! [`EditMode::Custom`](crate::EditMode::Custom))
+use std::collections::HashMap;
+
+use crate::binding::EventContext;
+use crate::keymap::Cmd;
+use crate::keys::KeyEvent;
+
+/// Editing mode implemented by the application, with its own state machine.
+///
+/// Once registered under a name with
+/// [`Editor::register_mode`](crate::Editor::register_mode), the mode is
+/// selected with `EditMode::Custom(name)` (e.g. with
+/// [`Configurer::set_edit_mode`](crate::config::Configurer::set_edit_mode) or
+/// [`Cmd::SetEditMode`]). Every key typed in this mode is given to the mode,
+/// except the ones bound in [`Keymap::Custom(name)`](crate::Keymap::Custom)
+/// or globally.
+///
+/// For example, a mode where letters are commands until `i` is typed:
+/// ```
+/// use rustyline::config::Configurer;
+/// use rustyline::{Cmd, CustomMode, EditMode, EventContext, KeyCode, KeyEvent, Modifiers, Movement};
+///
+/// #[derive(Default)]
+/// struct Modal {
+///     inserting: bool,
+/// }
+/// impl CustomMode for Modal {
+///     fn reset(&mut self) {
+///         self.inserting = true;
+///     }
+///
+///     fn handle(&mut self, key: KeyEvent, _: &EventContext) -> Option<Cmd> {
+///         Some(match key {
+///             KeyEvent(KeyCode::Esc, _) => {
+///                 self.inserting = false;
+///                 Cmd::Noop
+///             }
+///             KeyEvent(KeyCode::Char(c), Modifiers::NONE) if self.inserting => Cmd::SelfInsert(1, c),
+///             KeyEvent(KeyCode::Char('i'), Modifiers::NONE) => {
+///                 self.inserting = true;
+///                 Cmd::Noop
+///             }
+///             KeyEvent(KeyCode::Char('h'), Modifiers::NONE) => Cmd::Move(Movement::BackwardChar(1)),
+///             KeyEvent(KeyCode::Char('e'), Modifiers::NONE) => Cmd::EmacsEditingMode,
+///             KeyEvent(KeyCode::Enter, _) => Cmd::AcceptLine,
+///             _ => Cmd::Noop,
+///         })
+///     }
+/// }
+///
+/// let mut rl = rustyline::Editor::<()>::new();
+/// rl.register_mode("modal", Modal::default());
+/// rl.set_edit_mode(EditMode::Custom("modal"));
+/// ```
+pub trait CustomMode: Send {
+    /// Called when a line starts being edited in this mode and when the mode
+    /// is selected, e.g. to reset the state machine.
+    fn reset(&mut self) {}
+
+    /// Takes the `key` typed and the input state and
+    /// returns the command to be performed or `None` to wait for the next
+    /// key (e.g. after the first key of a multi-keys command).
+    fn handle(&mut self, key: KeyEvent, ctx: &EventContext) -> Option<Cmd>;
+}
+
+/// Registered modes, by name
+pub(crate) type CustomModes = HashMap<&'static str, Box<dyn CustomMode>>;
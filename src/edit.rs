@@ -1,21 +1,29 @@
 //! Command processor
 
-use log::debug;
+use log::{debug, warn};
+use std::borrow::Cow;
 use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
+use std::ops::Range;
 use std::rc::Rc;
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthChar;
 
 use super::{Context, Helper, Result};
-use crate::highlight::Highlighter;
+use crate::abbreviation::Abbreviations;
+use crate::completion::find_unclosed_quote;
+use crate::config::{Config, EditMode, HistChars, WordBoundary};
+use crate::expansion::expand;
+use crate::highlight::{HighlightedRange, Highlighter};
 use crate::hint::Hint;
-use crate::history::Direction;
+use crate::history::{Direction, HistoryStore};
 use crate::keymap::{Anchor, At, CharSearch, Cmd, Movement, RepeatCount, Word};
-use crate::keymap::{InputState, Invoke, Refresher};
+use crate::keymap::{InputMode, InputState, Invoke, ModeState, Refresher};
+use crate::keys::KeyEvent;
 use crate::layout::{Layout, Position};
 use crate::line_buffer::{LineBuffer, WordAction, MAX_LINE};
-use crate::tty::{Renderer, Term, Terminal};
+use crate::tty::{escape_control, CursorShape, Renderer, Term, Terminal};
 use crate::undo::Changeset;
 use crate::validate::{ValidationContext, ValidationResult};
 
@@ -34,6 +42,27 @@ pub struct State<'out, 'prompt, H: Helper> {
     pub ctx: Context<'out>,          // Give access to history for `hinter`
     pub hint: Option<Box<dyn Hint>>, // last hint displayed
     highlight_char: bool,            // `true` if a char has been highlighted
+    /// Line as typed when a substring history search started
+    history_search_term: Option<String>,
+    /// Text of the line matched by a substring history search
+    history_match: Option<Range<usize>>,
+    /// History entry, text of the line and word number of the last
+    /// `yank-last-arg`
+    pub yanked_arg: Option<(usize, Range<usize>, isize)>,
+    /// State of the last `dabbrev-expand`
+    pub dabbrev: Option<Dabbrev>,
+    /// Edited history entries, by index: the history itself is left
+    /// untouched, these edits being dropped once the line is accepted
+    history_edits: HashMap<usize, String>,
+    /// Prompt with the editing mode indicator, if different from `prompt`
+    mode_prompt: Option<String>,
+    /// `true` if the text between the mark and the cursor is highlighted
+    active_region: bool,
+    /// `true` if the active region is a selection made with shift + movement
+    /// keys (replaced by the text typed)
+    selection: bool,
+    /// Cursor shape last set
+    cursor_shape: CursorShape,
 }
 
 enum Info<'m> {
@@ -63,6 +92,15 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             ctx,
             hint: None,
             highlight_char: false,
+            history_search_term: None,
+            history_match: None,
+            yanked_arg: None,
+            dabbrev: None,
+            history_edits: HashMap::new(),
+            mode_prompt: None,
+            active_region: false,
+            selection: false,
+            cursor_shape: CursorShape::Default,
         }
     }
 
@@ -82,11 +120,16 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
     ) -> Result<Cmd> {
         loop {
             let rc = input_state.next_cmd(rdr, self, single_esc_abort);
+            #[cfg(unix)]
+            if rc.is_err() && self.out.sigcont() {
+                // stopped by a signal (SIGTSTP, SIGSTOP) and resumed: the
+                // new size is taken into account by `resume`
+                self.out.sigwinch();
+                self.resume()?;
+                continue;
+            }
             if rc.is_err() && self.out.sigwinch() {
-                self.out.update_size();
-                self.prompt_size = self
-                    .out
-                    .calculate_position(self.prompt, Position::default());
+                self.window_resized();
                 self.refresh_line()?;
                 continue;
             }
@@ -97,6 +140,50 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         }
     }
 
+    /// Take the new size of the terminal into account: the rows already
+    /// displayed have been rewrapped by the terminal so the current layout is
+    /// recomputed for the new width, letting the next refresh clear all of
+    /// them.
+    fn window_resized(&mut self) {
+        self.out.update_size();
+        self.prompt_size = self
+            .out
+            .calculate_position(self.displayed_prompt(), Position::default());
+        let prompt_size = if self.layout.default_prompt {
+            self.prompt_size
+        } else {
+            self.layout.prompt_size
+        };
+        let info = self.hint.as_ref().map(|h| h.display());
+        self.layout =
+            self.out
+                .compute_layout(prompt_size, self.layout.default_prompt, &self.line, info);
+    }
+
+    /// Move the cursor below the displayed rows before the process is
+    /// suspended, so that the messages of the shell don't overwrite them.
+    #[cfg(unix)]
+    pub fn suspend(&mut self) -> Result<()> {
+        self.out.move_cursor(self.layout.cursor, self.layout.end)?;
+        self.layout.cursor = self.layout.end;
+        self.out.write_and_flush(b"\n")
+    }
+
+    /// Redraw the prompt and the line, with the cursor where it was, once the
+    /// process is resumed: the rows displayed before being suspended are
+    /// left as is, the rendering restarts on the current row.
+    #[cfg(unix)]
+    pub fn resume(&mut self) -> Result<()> {
+        // the terminal may have been resized while the process was stopped
+        self.out.update_size();
+        self.prompt_size = self
+            .out
+            .calculate_position(self.displayed_prompt(), Position::default());
+        self.layout.cursor = Position::default();
+        self.layout.end = Position::default();
+        self.refresh_line()
+    }
+
     pub fn backup(&mut self) {
         self.saved_line_for_history
             .update(self.line.as_str(), self.line.pos());
@@ -111,15 +198,16 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
 
     pub fn move_cursor(&mut self) -> Result<()> {
         // calculate the desired position of the cursor
-        let cursor = self
-            .out
-            .calculate_position(&self.line[..self.line.pos()], self.prompt_size);
+        let cursor = self.out.calculate_position(
+            &escape_control(&self.line[..self.line.pos()]),
+            self.prompt_size,
+        );
         if self.layout.cursor == cursor {
             return Ok(());
         }
-        if self.highlight_char() {
-            let prompt_size = self.prompt_size;
-            self.refresh(self.prompt, prompt_size, true, Info::NoHint)?;
+        let highlight_char = self.highlight_char();
+        if highlight_char || self.active_region {
+            self.refresh_default(Info::NoHint)?;
         } else {
             self.out.move_cursor(self.layout.cursor, cursor)?;
             self.layout.prompt_size = self.prompt_size;
@@ -134,6 +222,81 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         self.out.move_cursor_at_leftmost(rdr)
     }
 
+    /// Prompt as displayed, with the editing mode indicator
+    fn displayed_prompt(&self) -> &str {
+        self.mode_prompt.as_deref().unwrap_or(self.prompt)
+    }
+
+    /// Render the editing `mode` in the prompt: `indicator` is inserted at
+    /// the start of the last line of the prompt and the result is given to
+    /// [`Highlighter::mode_prompt`]. Returns `true` if the prompt changed.
+    pub fn update_mode_prompt(&mut self, mode: ModeState, indicator: &str) -> bool {
+        let idx = self.prompt.rfind('\n').map_or(0, |i| i + 1);
+        let prompt = format!(
+            "{}{}{}",
+            &self.prompt[..idx],
+            indicator,
+            &self.prompt[idx..]
+        );
+        let prompt = match self.helper {
+            Some(helper) => helper.mode_prompt(&prompt, &mode).into_owned(),
+            None => prompt,
+        };
+        let mode_prompt = if prompt == self.prompt {
+            None
+        } else {
+            Some(prompt)
+        };
+        if mode_prompt == self.mode_prompt {
+            return false;
+        }
+        self.mode_prompt = mode_prompt;
+        self.prompt_size = self
+            .out
+            .calculate_position(self.displayed_prompt(), Position::default());
+        true
+    }
+
+    /// Change the cursor shape with the vi input `mode`, if enabled
+    pub fn update_cursor_shape(&mut self, mode: ModeState) -> Result<()> {
+        if !self.ctx.config().cursor_shape() {
+            return Ok(());
+        }
+        let shape = match (mode.edit_mode, mode.input_mode) {
+            (EditMode::Vi, InputMode::Command) => CursorShape::Block,
+            (EditMode::Vi, InputMode::Insert) => CursorShape::Bar,
+            (EditMode::Vi, InputMode::Replace) => CursorShape::Underline,
+            _ => CursorShape::Default,
+        };
+        if shape == self.cursor_shape {
+            return Ok(());
+        }
+        self.cursor_shape = shape;
+        self.out.set_cursor_shape(shape)
+    }
+
+    /// Restore the default cursor shape, before giving the terminal back
+    pub fn reset_cursor_shape(&mut self) -> Result<()> {
+        if self.cursor_shape == CursorShape::Default {
+            return Ok(());
+        }
+        self.cursor_shape = CursorShape::Default;
+        self.out.set_cursor_shape(CursorShape::Default)
+    }
+
+    /// Refresh with the default prompt (and its editing mode indicator)
+    fn refresh_default(&mut self, info: Info<'_>) -> Result<()> {
+        let prompt_size = self.prompt_size;
+        match self.mode_prompt.take() {
+            Some(prompt) => {
+                let rc = self.refresh(&prompt, prompt_size, true, info);
+                self.mode_prompt = Some(prompt);
+                rc
+            }
+            None => self.refresh(self.prompt, prompt_size, true, info),
+        }
+    }
+
     fn refresh(
         &mut self,
         prompt: &str,
@@ -151,6 +314,23 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         } else {
             None
         };
+        let (range, region) = match self.history_match {
+            Some(ref range) => (Some(range.clone()), false),
+            None if self.active_region => (self.line.region().filter(|r| !r.is_empty()), true),
+            None => (None, false),
+        };
+        let highlighted_range;
+        let highlighter = match range {
+            Some(range) if self.out.colors_enabled() => {
+                highlighted_range = HighlightedRange {
+                    highlighter: highlighter.unwrap_or(&()),
+                    range,
+                    region,
+                };
+                Some(&highlighted_range as &dyn Highlighter)
+            }
+            _ => highlighter,
+        };
 
         let new_layout = self
             .out
@@ -238,17 +418,15 @@ impl<'out, 'prompt, H: Helper> Invoke for State<'out, 'prompt, H> {
 
 impl<'out, 'prompt, H: Helper> Refresher for State<'out, 'prompt, H> {
     fn refresh_line(&mut self) -> Result<()> {
-        let prompt_size = self.prompt_size;
         self.hint();
         self.highlight_char();
-        self.refresh(self.prompt, prompt_size, true, Info::Hint)
+        self.refresh_default(Info::Hint)
     }
 
     fn refresh_line_with_msg(&mut self, msg: Option<&str>) -> Result<()> {
-        let prompt_size = self.prompt_size;
         self.hint = None;
         self.highlight_char();
-        self.refresh(self.prompt, prompt_size, true, Info::Msg(msg))
+        self.refresh_default(Info::Msg(msg))
     }
 
     fn refresh_prompt_and_line(&mut self, prompt: &str) -> Result<()> {
@@ -258,6 +436,26 @@ impl<'out, 'prompt, H: Helper> Refresher for State<'out, 'prompt, H> {
         self.refresh(prompt, prompt_size, false, Info::Hint)
     }
 
+    fn refresh_pending_prefix(&mut self, prefix: &[KeyEvent]) -> Result<()> {
+        let prompt = match self.helper {
+            Some(helper) => helper.pending_prefix_prompt(self.displayed_prompt(), prefix),
+            None => return Ok(()),
+        };
+        if prompt == self.displayed_prompt() {
+            return Ok(());
+        }
+        let prompt = prompt.into_owned();
+        self.refresh_prompt_and_line(&prompt)
+    }
+
+    fn set_mode(&mut self, mode: ModeState, indicator: &str) -> Result<()> {
+        self.update_cursor_shape(mode)?;
+        if self.update_mode_prompt(mode, indicator) {
+            self.refresh_line()?;
+        }
+        Ok(())
+    }
+
     fn doing_insert(&mut self) {
         self.changes.borrow_mut().begin();
     }
@@ -316,7 +514,6 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
     pub fn edit_insert(&mut self, ch: char, n: RepeatCount) -> Result<()> {
         if let Some(push) = self.line.insert(ch, n) {
             if push {
-                let prompt_size = self.prompt_size;
                 let no_previous_hint = self.hint.is_none();
                 self.hint();
                 let width = ch.width().unwrap_or(0);
@@ -335,7 +532,7 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
                     let bits = bits.as_bytes();
                     self.out.write_and_flush(bits)
                 } else {
-                    self.refresh(self.prompt, prompt_size, true, Info::Hint)
+                    self.refresh_default(Info::Hint)
                 }
             } else {
                 self.refresh_line()
@@ -345,6 +542,70 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         }
     }
 
+    /// Auto-pairing of `ch` typed (see [`Config::auto_pairs`]): a closing
+    /// character is skipped over when it is under the cursor, an opening one
+    /// is inserted with its closing one. Returns `false` when `ch` must be
+    /// inserted as usual.
+    pub fn edit_auto_pair(&mut self, ch: char, config: &Config) -> Result<bool> {
+        let pairs = config.auto_pairs();
+        if pairs.is_empty() {
+            return Ok(false);
+        }
+        let pos = self.line.pos();
+        let prev = self.line[..pos].chars().next_back();
+        let next = self.line[pos..].chars().next();
+        let is_close = |c: char| pairs.iter().any(|&(_, close)| close == c);
+        if next == Some(ch) && is_close(ch) {
+            self.line.move_forward(1);
+            self.move_cursor()?;
+            return Ok(true);
+        }
+        let close = match pairs.iter().find(|&&(open, _)| open == ch) {
+            Some(&(_, close)) => close,
+            None => return Ok(false),
+        };
+        if matches!(next, Some(c) if !c.is_whitespace() && !is_close(c)) {
+            return Ok(false);
+        }
+        let in_string = find_unclosed_quote(&self.line[..pos]).is_some();
+        if ch == close {
+            // a quote: not an apostrophe nor a closing one
+            if in_string || matches!(prev, Some(c) if c.is_alphanumeric() || c == ch) {
+                return Ok(false);
+            }
+        } else if in_string && !config.auto_pairs_in_strings() {
+            return Ok(false);
+        }
+        let mut pair = String::with_capacity(ch.len_utf8() + close.len_utf8());
+        pair.push(ch);
+        pair.push(close);
+        self.line.insert_str(pos, &pair);
+        self.line.set_pos(pos + ch.len_utf8());
+        self.refresh_line()?;
+        Ok(true)
+    }
+
+    /// Backspace between the two characters of an auto-pair (see
+    /// [`Config::auto_pairs`]) deletes both. Returns `false` when the cursor
+    /// is not between them.
+    pub fn edit_delete_pair(&mut self, config: &Config) -> Result<bool> {
+        let pos = self.line.pos();
+        let (prev, next) = match (
+            self.line[..pos].chars().next_back(),
+            self.line[pos..].chars().next(),
+        ) {
+            (Some(prev), Some(next)) => (prev, next),
+            _ => return Ok(false),
+        };
+        if !config.auto_pairs().contains(&(prev, next)) {
+            return Ok(false);
+        }
+        self.line
+            .delete_range(pos - prev.len_utf8()..pos + next.len_utf8());
+        self.refresh_line()?;
+        Ok(true)
+    }
+
     /// Replace a single (or n) character(s) under the cursor (Vi mode)
     pub fn edit_replace_char(&mut self, ch: char, n: RepeatCount) -> Result<()> {
         self.changes.borrow_mut().begin();
@@ -364,7 +625,7 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         }
     }
 
-    /// Overwrite the character under the cursor (Vi mode)
+    /// Overwrite the character under the cursor (vi replace and overwrite modes)
     pub fn edit_overwrite_char(&mut self, ch: char) -> Result<()> {
         if let Some(end) = self.line.next_pos(1) {
             {
@@ -373,6 +634,16 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
                 self.line.replace(start..end, text);
             }
             self.refresh_line()
+        } else {
+            // nothing to overwrite at the end of the line
+            self.edit_insert(ch, 1)
+        }
+    }
+
+    /// Backspace in overwrite mode
+    pub fn edit_overwrite_backspace(&mut self, n: RepeatCount) -> Result<()> {
+        if self.line.overwrite_backspace(n) {
+            self.refresh_line()
         } else {
             Ok(())
         }
@@ -399,6 +670,15 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         }
     }
 
+    /// Open a new line below or above the current one (vi `o` / `O`)
+    pub fn edit_open_line(&mut self, anchor: Anchor) -> Result<()> {
+        if self.line.open_line(anchor) {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
     // Delete previously yanked text and yank/paste `text` at current position.
     pub fn edit_yank_pop(&mut self, yank_size: usize, text: &str) -> Result<()> {
         self.changes.borrow_mut().begin();
@@ -447,6 +727,40 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         }
     }
 
+    /// Move cursor to the character displayed at `target`, `cursor` being the
+    /// current (absolute) cursor position on the screen.
+    pub fn edit_move_to_screen_position(
+        &mut self,
+        target: Position,
+        cursor: Position,
+    ) -> Result<()> {
+        // target relative to the start of the prompt
+        let target = match (target.row + self.layout.cursor.row).checked_sub(cursor.row) {
+            Some(row) => Position {
+                col: target.col,
+                row,
+            },
+            None => Position::default(),
+        };
+        let mut pos = self.prompt_size;
+        let mut offset = 0;
+        for (i, g) in self.line.as_str().grapheme_indices(true) {
+            if pos > target {
+                break;
+            }
+            offset = i;
+            pos = self.out.calculate_position(&escape_control(g), pos);
+        }
+        if pos <= target {
+            offset = self.line.len();
+        }
+        if offset == self.line.pos() {
+            return Ok(());
+        }
+        self.line.set_pos(offset);
+        self.move_cursor()
+    }
+
     /// Move cursor to the start of the buffer.
     pub fn edit_move_buffer_start(&mut self) -> Result<()> {
         if self.line.move_buffer_start() {
@@ -491,9 +805,9 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
     }
 
     /// Exchange the char before cursor with the character at cursor.
-    pub fn edit_transpose_chars(&mut self) -> Result<()> {
+    pub fn edit_transpose_chars(&mut self, n: RepeatCount) -> Result<()> {
         self.changes.borrow_mut().begin();
-        let succeed = self.line.transpose_chars();
+        let succeed = self.line.transpose_chars(n);
         self.changes.borrow_mut().end();
         if succeed {
             self.refresh_line()
@@ -546,9 +860,9 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         }
     }
 
-    pub fn edit_word(&mut self, a: WordAction) -> Result<()> {
+    pub fn edit_word(&mut self, a: WordAction, n: RepeatCount) -> Result<()> {
         self.changes.borrow_mut().begin();
-        let succeed = self.line.edit_word(a);
+        let succeed = self.line.edit_word(a, n);
         self.changes.borrow_mut().end();
         if succeed {
             self.refresh_line()
@@ -584,6 +898,8 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             }
         } else if self.ctx.history_index == 0 && prev {
             return Ok(());
+        } else {
+            self.save_history_edit();
         }
         if prev {
             self.ctx.history_index -= 1;
@@ -591,9 +907,9 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             self.ctx.history_index += 1;
         }
         if self.ctx.history_index < history.len() {
-            let buf = history.get(self.ctx.history_index).unwrap();
+            let buf = self.history_line(self.ctx.history_index);
             self.changes.borrow_mut().begin();
-            self.line.update(buf, buf.len());
+            self.line.update(&buf, buf.len());
             self.changes.borrow_mut().end();
         } else {
             // Restore current edited line
@@ -602,38 +918,445 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
         self.refresh_line()
     }
 
-    // Non-incremental, anchored search
+    /// Non-incremental, anchored search (`history-search-backward` /
+    /// `history-search-forward`): substitute the currently edited line with
+    /// the previous / next history entry starting with the text before the
+    /// cursor. The cursor does not move so that the search can be repeated,
+    /// and searching forward past the last match restores the edited line.
     pub fn edit_history_search(&mut self, dir: Direction) -> Result<()> {
         let history = self.ctx.history;
-        if history.is_empty() {
-            return self.out.beep();
+        let pos = self.line.pos();
+        let prefix = self.line.as_str()[..pos].to_owned();
+        let found = self.find_history_entry(dir, |start| {
+            if prefix.is_empty() {
+                Some(start).filter(|&index| index < history.len())
+            } else {
+                history.starts_with(&prefix, start, dir)
+            }
+        });
+        match found {
+            Some(Some((index, entry))) => {
+                self.history_entry(index, &entry, pos);
+                self.refresh_line()
+            }
+            Some(None) if dir == Direction::Forward => {
+                self.restore_edited_line();
+                if self.line.as_str().starts_with(&prefix) {
+                    self.line.set_pos(pos);
+                }
+                self.refresh_line()
+            }
+            _ => self.out.beep(),
+        }
+    }
+
+    /// Substring search (`history-substring-search-backward` /
+    /// `history-substring-search-forward`): substitute the currently edited
+    /// line with the previous / next history entry containing the line as it
+    /// was typed, and highlight the matched text. Searching forward past the
+    /// last match restores the edited line.
+    pub fn edit_history_substring_search(&mut self, dir: Direction) -> Result<()> {
+        let history = self.ctx.history;
+        // the search goes on while the line is the last match
+        let searching = self.history_search_term.is_some()
+            && self.ctx.history_index < history.len()
+            && history.entry(self.ctx.history_index).as_deref() == Some(self.line.as_str());
+        if !searching {
+            self.history_search_term = Some(self.line.as_str().to_owned());
         }
-        if self.ctx.history_index == history.len() && dir == Direction::Forward
+        let term = self.history_search_term.clone().unwrap_or_default();
+        let found = self.find_history_entry(dir, |start| {
+            if term.is_empty() {
+                Some(start).filter(|&index| index < history.len())
+            } else {
+                history.search(&term, start, dir)
+            }
+        });
+        match found {
+            Some(Some((index, entry))) => {
+                self.history_entry(index, &entry, entry.len());
+                self.history_match = entry
+                    .find(term.as_str())
+                    .filter(|_| !term.is_empty())
+                    .map(|start| start..start + term.len());
+                self.refresh_line()
+            }
+            Some(None) if dir == Direction::Forward => {
+                self.restore_edited_line();
+                self.history_match = None;
+                self.refresh_line()
+            }
+            _ => self.out.beep(),
+        }
+    }
+
+    /// Vi history search (`/`, `?`, `n` and `N`): substitute the currently
+    /// edited line with the `n`th previous / next history entry containing
+    /// `pattern` (or starting with it when it is anchored with `^`), with the
+    /// cursor at the start of the line.
+    pub fn edit_vi_search(&mut self, pattern: &str, dir: Direction, n: RepeatCount) -> Result<()> {
+        let history = self.ctx.history;
+        let (anchored, term) = match pattern.strip_prefix('^') {
+            Some(term) => (true, term),
+            None => (false, pattern),
+        };
+        let mut found = false;
+        for _ in 0..n.max(1) {
+            let entry = self.find_history_entry(dir, |start| {
+                if anchored {
+                    history.starts_with(term, start, dir)
+                } else {
+                    history.search(term, start, dir)
+                }
+            });
+            match entry {
+                Some(Some((index, entry))) => {
+                    self.history_entry(index, &entry, 0);
+                    found = true;
+                }
+                _ => break,
+            }
+        }
+        if found {
+            self.refresh_line()
+        } else {
+            self.out.beep()
+        }
+    }
+
+    /// Nearest history entry (index and content) from the current one in the
+    /// `dir` direction found by `search` (called with the index to start
+    /// from), skipping the entries identical to the current line: `None` if
+    /// there is no entry to search in this direction.
+    fn find_history_entry<F>(
+        &self,
+        dir: Direction,
+        search: F,
+    ) -> Option<Option<(usize, Cow<'out, str>)>>
+    where
+        F: Fn(usize) -> Option<usize>,
+    {
+        let history = self.ctx.history;
+        if history.is_empty()
+            || self.ctx.history_index >= history.len() && dir == Direction::Forward
             || self.ctx.history_index == 0 && dir == Direction::Reverse
         {
-            return self.out.beep();
+            return None;
         }
-        if dir == Direction::Reverse {
-            self.ctx.history_index -= 1;
+        let mut start = self.ctx.history_index;
+        Some(loop {
+            start = match dir {
+                Direction::Reverse if start == 0 => break None,
+                Direction::Reverse => start - 1,
+                Direction::Forward => start + 1,
+            };
+            match search(start) {
+                Some(index) => match history.entry(index) {
+                    Some(entry) if entry != self.line.as_str() => break Some((index, entry)),
+                    _ => start = index,
+                },
+                None => break None,
+            }
+        })
+    }
+
+    /// Substitute the currently edited line with the history entry at `index`
+    fn history_entry(&mut self, index: usize, entry: &str, pos: usize) {
+        if self.ctx.history_index == self.ctx.history.len() {
+            // Save the current edited line before overwriting it
+            self.backup();
         } else {
-            self.ctx.history_index += 1;
+            self.save_history_edit();
         }
-        if let Some(history_index) = history.starts_with(
-            &self.line.as_str()[..self.line.pos()],
-            self.ctx.history_index,
-            dir,
-        ) {
-            self.ctx.history_index = history_index;
-            let buf = history.get(history_index).unwrap();
+        self.ctx.history_index = index;
+        self.changes.borrow_mut().begin();
+        self.line.update(entry, pos);
+        self.changes.borrow_mut().end();
+    }
+
+    /// Remember the edits made to the current history entry, if any, until
+    /// the line is accepted
+    fn save_history_edit(&mut self) {
+        let index = self.ctx.history_index;
+        match self.ctx.history.entry(index) {
+            Some(entry) if entry != self.line.as_str() => {
+                self.history_edits
+                    .insert(index, self.line.as_str().to_owned());
+            }
+            _ => {
+                self.history_edits.remove(&index);
+            }
+        }
+    }
+
+    /// History entry at `index`, as edited during this `readline`
+    fn history_line(&self, index: usize) -> Cow<'out, str> {
+        match self.history_edits.get(&index) {
+            Some(edited) => Cow::Owned(edited.clone()),
+            None => self.ctx.history.entry(index).unwrap(),
+        }
+    }
+
+    /// Undo all the changes made to the line: restore the original history
+    /// entry when one is edited
+    pub fn edit_revert_line(&mut self) -> Result<()> {
+        let index = self.ctx.history_index;
+        if let Some(entry) = self.ctx.history.entry(index) {
+            self.history_edits.remove(&index);
+            if entry == self.line.as_str() {
+                return Ok(());
+            }
+            self.changes.borrow_mut().begin();
+            self.line.update(&entry, entry.len());
+            self.changes.borrow_mut().end();
+        } else {
+            let mut changes = self.changes.borrow_mut();
+            while changes.undo(&mut self.line, RepeatCount::MAX) {}
+        }
+        self.refresh_line()
+    }
+
+    /// Restore the line edited before browsing the history
+    fn restore_edited_line(&mut self) {
+        self.ctx.history_index = self.ctx.history.len();
+        self.restore();
+    }
+
+    /// Insert word `n` of the previous history entry at the cursor. With
+    /// `walk`, the word inserted by the previous `yank-last-arg` is replaced
+    /// by the same word (whatever `n`) of the entry before.
+    pub fn edit_yank_arg(
+        &mut self,
+        n: isize,
+        walk: bool,
+        word_boundary: WordBoundary,
+    ) -> Result<()> {
+        let pos = self.line.pos();
+        let (index, range, n) = match self.yanked_arg.clone() {
+            Some((index, range, n)) if walk => (index.checked_sub(1), range, n),
+            _ => (self.ctx.history_index.checked_sub(1), pos..pos, n),
+        };
+        let index = match index {
+            Some(index) => index,
+            None => {
+                self.out.beep()?;
+                return Ok(());
+            }
+        };
+        let word = self
+            .ctx
+            .history
+            .entry(index)
+            .and_then(|entry| nth_word(&entry, n, word_boundary).map(str::to_owned));
+        if let Some(word) = word {
             self.changes.borrow_mut().begin();
-            self.line.update(buf, buf.len());
+            self.line.replace(range.clone(), &word);
             self.changes.borrow_mut().end();
+            if walk {
+                self.yanked_arg = Some((index, range.start..range.start + word.len(), n));
+            }
             self.refresh_line()
         } else {
+            if walk {
+                // the next try goes on with older entries
+                self.yanked_arg = Some((index, range, n));
+            }
             self.out.beep()
         }
     }
 
+    /// Complete the word before the cursor with the next word starting with
+    /// it, searched in the line (nearest first) then in the history (newest
+    /// first), the original word being restored once they are all offered.
+    pub fn edit_dabbrev_expand(&mut self, word_boundary: WordBoundary) -> Result<()> {
+        let mut dabbrev = match self.dabbrev.take() {
+            Some(dabbrev) => dabbrev,
+            None => {
+                let pos = self.line.pos();
+                let start = self.line[..pos]
+                    .char_indices()
+                    .rev()
+                    .take_while(|&(_, c)| word_boundary.is_word_char(c))
+                    .last()
+                    .map_or(pos, |(i, _)| i);
+                if start == pos {
+                    return self.out.beep();
+                }
+                Dabbrev::new(
+                    &self.line,
+                    start,
+                    pos,
+                    self.ctx.history_index,
+                    word_boundary,
+                )
+            }
+        };
+        let range = dabbrev.range.clone();
+        self.changes.borrow_mut().begin();
+        match dabbrev.next(self.ctx.history, word_boundary) {
+            Some(word) => {
+                let expansion = &word[dabbrev.prefix.len()..];
+                self.line.replace(range.clone(), expansion);
+                dabbrev.range = range.start..range.start + expansion.len();
+                self.dabbrev = Some(dabbrev);
+            }
+            None => {
+                // the next try starts over
+                self.line.replace(range, "");
+                self.out.beep()?;
+            }
+        }
+        self.changes.borrow_mut().end();
+        self.refresh_line()
+    }
+
+    /// Replace the line by its history expansion, the cursor staying at the
+    /// same distance from the end of the line.
+    pub fn edit_history_expand(&mut self, chars: HistChars) -> Result<()> {
+        match expand(self.ctx.history, &self.line, chars) {
+            Ok(Some(expanded)) => {
+                let from_end = self.line.len() - self.line.pos();
+                let pos = expanded.len().saturating_sub(from_end);
+                let pos = if expanded.is_char_boundary(pos) {
+                    pos
+                } else {
+                    expanded.len()
+                };
+                self.changes.borrow_mut().begin();
+                self.line.update(&expanded, pos);
+                self.changes.borrow_mut().end();
+                self.refresh_line()
+            }
+            Ok(None) => Ok(()),
+            Err(err) => {
+                debug!(target: "rustyline", "history expansion: {}", err);
+                self.out.beep()
+            }
+        }
+    }
+
+    /// Replace the line by the `edited` text returned by an external editor
+    /// (`None` if it failed), the cursor at the end.
+    pub fn edit_externally_edited(&mut self, edited: Option<String>) -> Result<()> {
+        match edited {
+            Some(text) => {
+                if text != self.line.as_str() {
+                    self.changes.borrow_mut().begin();
+                    self.line.update(&text, text.len());
+                    self.changes.borrow_mut().end();
+                }
+                self.refresh_line()
+            }
+            None => {
+                self.refresh_line()?;
+                self.out.beep()
+            }
+        }
+    }
+
+    /// Expand the abbreviation (whitespace-delimited word) ending at `end`,
+    /// before the cursor or the spaces just typed. The expansion is undone in
+    /// one step.
+    pub fn edit_expand_abbreviation(
+        &mut self,
+        abbreviations: &Abbreviations,
+        end: usize,
+    ) -> Result<()> {
+        let line = self.line.as_str();
+        let pos = self.line.pos();
+        if matches!(line[end..].chars().next(), Some(c) if !c.is_whitespace()) {
+            return Ok(()); // in the middle of a word
+        }
+        let start = line[..end]
+            .char_indices()
+            .rev()
+            .find(|&(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let mut expansion = match abbreviations.expand(&line[start..end]) {
+            Some(expansion) => expansion,
+            None => return Ok(()),
+        };
+        // the spaces are replaced too so that the cursor is after them once
+        // the expansion is undone
+        expansion.push_str(&line[end..pos]);
+        self.changes.borrow_mut().begin();
+        self.line.replace(start..pos, &expansion);
+        self.changes.borrow_mut().end();
+        self.refresh_line()
+    }
+
+    /// Stop highlighting the text matched by a substring history search
+    pub fn clear_history_match(&mut self) -> Result<()> {
+        if self.history_match.take().is_some() {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set the mark at the cursor position and highlight the region
+    pub fn edit_set_mark(&mut self) -> Result<()> {
+        self.line.set_mark();
+        self.active_region = true;
+        Ok(())
+    }
+
+    /// Swap the cursor and the mark positions and highlight the region
+    pub fn edit_exchange_point_and_mark(&mut self) -> Result<()> {
+        if self.line.exchange_point_and_mark() {
+            self.active_region = true;
+            self.refresh_line()
+        } else {
+            self.out.beep()
+        }
+    }
+
+    /// Start a selection at the cursor position, unless the region is already
+    /// active
+    pub fn start_selection(&mut self) {
+        if !self.active_region {
+            self.line.set_mark();
+            self.active_region = true;
+        }
+        self.selection = true;
+    }
+
+    /// `true` if text is selected with shift + movement keys
+    pub fn has_selection(&self) -> bool {
+        self.selection
+    }
+
+    /// Copy the selected text to the system clipboard, if enabled
+    /// (`Config::selection_clipboard`): a failure is only logged.
+    pub fn copy_selection_to_clipboard(&mut self) {
+        if !self.selection || !self.ctx.config().selection_clipboard() {
+            return;
+        }
+        if let Some(text) = self.line.copy(&Movement::Region) {
+            if let Err(e) = self.out.copy_to_clipboard(&text) {
+                warn!(target: "rustyline", "cannot copy to the clipboard: {}", e);
+            }
+        }
+    }
+
+    /// Delete the selected text (without killing it)
+    pub fn delete_selection(&mut self) {
+        if self.selection {
+            self.line.delete_region();
+        }
+    }
+
+    /// Stop highlighting the region (the mark is kept)
+    pub fn deactivate_region(&mut self) -> Result<()> {
+        self.selection = false;
+        if self.active_region {
+            self.active_region = false;
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
     /// Substitute the currently edited line with the first/last history entry.
     pub fn edit_history(&mut self, first: bool) -> Result<()> {
         let history = self.ctx.history;
@@ -649,12 +1372,14 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
             }
         } else if self.ctx.history_index == 0 && first {
             return Ok(());
+        } else {
+            self.save_history_edit();
         }
         if first {
             self.ctx.history_index = 0;
-            let buf = history.get(self.ctx.history_index).unwrap();
+            let buf = self.history_line(self.ctx.history_index);
             self.changes.borrow_mut().begin();
-            self.line.update(buf, buf.len());
+            self.line.update(&buf, buf.len());
             self.changes.borrow_mut().end();
         } else {
             self.ctx.history_index = history.len();
@@ -665,6 +1390,17 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
     }
 
     /// Change the indentation of the lines covered by movement
+    pub fn edit_insert_comment(&mut self, prefix: &str, toggle: bool) -> Result<()> {
+        self.changes.borrow_mut().begin();
+        let succeed = self.line.insert_comment(prefix, toggle);
+        self.changes.borrow_mut().end();
+        if succeed {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn edit_indent(&mut self, mvt: &Movement, amount: usize, dedent: bool) -> Result<()> {
         if self.line.indent(mvt, amount, dedent) {
             self.refresh_line()
@@ -674,13 +1410,96 @@ impl<'out, 'prompt, H: Helper> State<'out, 'prompt, H> {
     }
 }
 
+/// Expansions of a word by `dabbrev-expand`
+pub struct Dabbrev {
+    /// Word expanded
+    prefix: String,
+    /// Text of the line replaced by the current expansion
+    range: Range<usize>,
+    /// Expansions already offered
+    offered: Vec<String>,
+    /// Expansions found but not offered yet
+    pending: VecDeque<String>,
+    /// History entries older than this one are searched next
+    history_index: usize,
+}
+
+impl Dabbrev {
+    /// Expansions of `line[start..pos]`, first the ones found in the line
+    fn new(
+        line: &str,
+        start: usize,
+        pos: usize,
+        history_index: usize,
+        word_boundary: WordBoundary,
+    ) -> Self {
+        let mut dabbrev = Self {
+            prefix: line[start..pos].to_owned(),
+            range: pos..pos,
+            offered: Vec::new(),
+            pending: VecDeque::new(),
+            history_index,
+        };
+        let before = words(&line[..start], word_boundary).rev();
+        let after = words(&line[pos..], word_boundary);
+        for word in before.chain(after) {
+            dabbrev.push(word);
+        }
+        dabbrev
+    }
+
+    /// Queues `word` if it expands the prefix and has not been found yet
+    fn push(&mut self, word: &str) {
+        if word.len() > self.prefix.len()
+            && word.starts_with(&self.prefix)
+            && !self.offered.iter().any(|w| w == word)
+            && !self.pending.iter().any(|w| w == word)
+        {
+            self.pending.push_back(word.to_owned());
+        }
+    }
+
+    /// Next expansion, searching older history entries if needed
+    fn next(&mut self, history: &dyn HistoryStore, word_boundary: WordBoundary) -> Option<String> {
+        while self.pending.is_empty() && self.history_index > 0 {
+            self.history_index -= 1;
+            if let Some(entry) = history.entry(self.history_index) {
+                for word in words(&entry, word_boundary).rev() {
+                    self.push(word);
+                }
+            }
+        }
+        let word = self.pending.pop_front()?;
+        self.offered.push(word.clone());
+        Some(word)
+    }
+}
+
+/// Words of `text`, made of the characters matching `word_boundary`
+fn words(text: &str, word_boundary: WordBoundary) -> impl DoubleEndedIterator<Item = &str> {
+    text.split(move |c| !word_boundary.is_word_char(c))
+        .filter(|word| !word.is_empty())
+}
+
+/// Word `n` of `line` (counted from the end if negative), words being made of
+/// the characters matching `word_boundary`
+fn nth_word(line: &str, n: isize, word_boundary: WordBoundary) -> Option<&str> {
+    let words: Vec<&str> = words(line, word_boundary).collect();
+    let idx = if n < 0 {
+        words.len().checked_sub(n.unsigned_abs())?
+    } else {
+        n.unsigned_abs()
+    };
+    words.get(idx).copied()
+}
+
 #[cfg(test)]
 pub fn init_state<'out, H: Helper>(
     out: &'out mut <Terminal as Term>::Writer,
     line: &str,
     pos: usize,
     helper: Option<&'out H>,
-    history: &'out crate::history::History,
+    history: &'out dyn crate::history::HistoryStore,
 ) -> State<'out, 'static, H> {
     State {
         out,
@@ -695,6 +1514,15 @@ pub fn init_state<'out, H: Helper>(
         ctx: Context::new(history),
         hint: Some(Box::new("hint".to_owned())),
         highlight_char: false,
+        history_search_term: None,
+        history_match: None,
+        yanked_arg: None,
+        dabbrev: None,
+        history_edits: HashMap::new(),
+        mode_prompt: None,
+        active_region: false,
+        selection: false,
+        cursor_shape: CursorShape::Default,
     }
 }
 
@@ -702,6 +1530,7 @@ pub fn init_state<'out, H: Helper>(
 mod test {
     use super::init_state;
     use crate::history::History;
+    use crate::layout::Position;
     use crate::tty::Sink;
 
     #[test]
@@ -742,4 +1571,80 @@ mod test {
         assert_eq!(2, s.ctx.history_index);
         assert_eq!(line, s.line.as_str());
     }
+
+    #[test]
+    fn update_cursor_shape() {
+        use crate::config::EditMode;
+        use crate::keymap::{InputMode, ModeState};
+        use crate::tty::CursorShape;
+        let mut out = Sink::new();
+        let history = History::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "", 0, helper.as_ref(), &history);
+        let mut mode = ModeState {
+            edit_mode: EditMode::Vi,
+            input_mode: InputMode::Command,
+            operator: None,
+            count: None,
+        };
+        // disabled by default
+        s.update_cursor_shape(mode).unwrap();
+        assert_eq!(CursorShape::Default, s.cursor_shape);
+
+        s.ctx.config.set_cursor_shape(true);
+        s.update_cursor_shape(mode).unwrap();
+        assert_eq!(CursorShape::Block, s.cursor_shape);
+        mode.input_mode = InputMode::Insert;
+        s.update_cursor_shape(mode).unwrap();
+        assert_eq!(CursorShape::Bar, s.cursor_shape);
+        s.reset_cursor_shape().unwrap();
+        assert_eq!(CursorShape::Default, s.cursor_shape);
+        mode.edit_mode = EditMode::Emacs;
+        s.update_cursor_shape(mode).unwrap();
+        assert_eq!(CursorShape::Default, s.cursor_shape);
+    }
+
+    #[test]
+    fn window_resized() {
+        let mut out = Sink::new();
+        let history = History::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "hello world", 5, helper.as_ref(), &history);
+        // layout of the line displayed on a 4 columns terminal
+        s.layout.default_prompt = true;
+        s.layout.cursor = Position { col: 1, row: 1 };
+        s.layout.end = Position { col: 3, row: 3 };
+
+        s.window_resized();
+        assert_eq!(Position { col: 5, row: 0 }, s.layout.cursor);
+        // with the hint
+        assert_eq!(Position { col: 15, row: 0 }, s.layout.end);
+    }
+
+    #[test]
+    fn edit_move_to_screen_position() {
+        let mut out = Sink::new();
+        let history = History::new();
+        let helper: Option<()> = None;
+        let mut s = init_state(&mut out, "hello world", 0, helper.as_ref(), &history);
+        s.layout.end = Position { col: 11, row: 0 };
+        // the prompt starts at the 6th row of the screen
+        let cursor = Position { col: 0, row: 5 };
+
+        s.edit_move_to_screen_position(Position { col: 3, row: 5 }, cursor)
+            .unwrap();
+        assert_eq!(3, s.line.pos());
+        // after the end of the line
+        s.edit_move_to_screen_position(Position { col: 40, row: 5 }, cursor)
+            .unwrap();
+        assert_eq!(11, s.line.pos());
+        // above the prompt
+        s.edit_move_to_screen_position(Position { col: 3, row: 4 }, cursor)
+            .unwrap();
+        assert_eq!(0, s.line.pos());
+        // below the line
+        s.edit_move_to_screen_position(Position { col: 0, row: 6 }, cursor)
+            .unwrap();
+        assert_eq!(11, s.line.pos());
+    }
 }
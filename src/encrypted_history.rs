@@ -0,0 +1,299 @@
+//! History persisted in an encrypted file (`history-encrypted` feature)
+//!
+//! Entries are kept in memory like with [`History`] but the history file is
+//! encrypted (XChaCha20-Poly1305) with a key supplied by the application:
+//! either random bytes stored somewhere safer than the history file (e.g. a
+//! keyring) or derived from a passphrase with [`Key::from_passphrase`].
+//!
+//! ```no_run
+//! use rustyline::encrypted_history::{EncryptedHistory, Key};
+//! use rustyline::{Config, Editor};
+//!
+//! let key = Key::from_passphrase("correct horse battery staple", b"alice@example");
+//! let history = EncryptedHistory::new(key);
+//! let mut rl = Editor::<(), EncryptedHistory>::with_history(Config::default(), history);
+//! let _ = rl.load_history("history.enc");
+//! let line = rl.readline(">> ")?;
+//! rl.add_history_entry(line);
+//! rl.append_history("history.enc")?;
+//! # Ok::<(), rustyline::error::ReadlineError>(())
+//! ```
+//!
+//! The whole file is rewritten each time it is saved (appending merges the new
+//! entries with the ones of the file), and a file which cannot be decrypted
+//! with the key is reported as an [`io::ErrorKind::InvalidData`] error.
+use std::borrow::Cow;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::SystemTime;
+
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use fd_lock::FdLock;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use super::Result;
+use crate::config::Config;
+use crate::error::ReadlineError;
+use crate::history::{fix_perm, open_for_update, EntryMetadata, History, HistoryStore};
+
+/// Start of the encrypted files (format version included)
+const MAGIC: &[u8] = b"RLHE\x01";
+/// Length of the random nonce following `MAGIC`
+const NONCE_LEN: usize = 24;
+/// PBKDF2-HMAC-SHA256 iterations of [`Key::from_passphrase`]
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+/// 256-bit encryption key, wiped from memory once dropped
+#[derive(Clone)]
+pub struct Key(Zeroizing<[u8; 32]>);
+
+impl Key {
+    /// Key made of `bytes`
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(Zeroizing::new(bytes))
+    }
+
+    /// Derive a key from `passphrase` (with PBKDF2-HMAC-SHA256).
+    ///
+    /// The `salt` does not need to be secret but should be specific to the
+    /// user (e.g. a random value stored along with the history file).
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Self {
+        let mut key = Zeroizing::new([0; 32]);
+        pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut *key);
+        Self(key)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new((&*self.0).into())
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+/// History entries saved encrypted
+pub struct EncryptedHistory {
+    history: History,
+    key: Key,
+    config: Config,
+    /// Number of entries added and not saved yet
+    new_entries: usize,
+}
+
+impl EncryptedHistory {
+    /// Empty history whose files are encrypted with `key`
+    pub fn new(key: Key) -> Self {
+        Self {
+            history: History::new(),
+            key,
+            config: Config::default(),
+            new_entries: 0,
+        }
+    }
+
+    /// Entries in `file` (none if it is empty)
+    fn read(&self, file: &mut File) -> Result<History> {
+        let mut history = History::with_config(self.config.clone());
+        let mut data = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_end(&mut data)?;
+        if data.is_empty() {
+            return Ok(history);
+        }
+        if data.len() < MAGIC.len() + NONCE_LEN || !data.starts_with(MAGIC) {
+            return Err(invalid_data("not an encrypted history file"));
+        }
+        let (nonce, ciphertext) = data[MAGIC.len()..].split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: MAGIC,
+        };
+        let plaintext = Zeroizing::new(
+            self.key
+                .cipher()
+                .decrypt(XNonce::from_slice(nonce), payload)
+                .map_err(|_| invalid_data("cannot decrypt the history file (wrong key?)"))?,
+        );
+        history.read_from(&plaintext[..])?;
+        Ok(history)
+    }
+
+    /// Replace the content of `file` by the encrypted entries of `history`
+    fn write(key: &Key, file: &mut File, history: &mut History) -> Result<()> {
+        let mut plaintext = Zeroizing::new(Vec::new());
+        history.write_to(&mut *plaintext, false)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: &plaintext[..],
+            aad: MAGIC,
+        };
+        let ciphertext = key
+            .cipher()
+            .encrypt(&nonce, payload)
+            .map_err(|_| io::Error::other("cannot encrypt the history"))?;
+        fix_perm(file);
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(MAGIC)?;
+        file.write_all(&nonce)?;
+        file.write_all(&ciphertext)?;
+        let len = file.stream_position()?;
+        file.set_len(len)?;
+        Ok(())
+    }
+
+    /// Entries of the file at `path` followed by the new ones
+    fn merge(&mut self, path: &Path) -> Result<History> {
+        let mut lock = FdLock::new(open_for_update(path)?);
+        let mut file = lock.lock()?;
+        let mut merged = self.read(&mut file)?;
+        let first_new_entry = self.history.len().saturating_sub(self.new_entries);
+        for index in first_new_entry..self.history.len() {
+            let entry = &self.history[index];
+            if merged.add_with_timestamp(entry.as_str(), self.history.timestamp(index)) {
+                let last = merged.len() - 1;
+                if let (Some(metadata), Some(merged)) =
+                    (self.history.metadata(index), merged.metadata_mut(last))
+                {
+                    *merged = metadata.clone();
+                }
+            }
+        }
+        Self::write(&self.key, &mut file, &mut merged)?;
+        self.new_entries = 0;
+        Ok(merged)
+    }
+}
+
+impl HistoryStore for EncryptedHistory {
+    fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    fn entry(&self, index: usize) -> Option<Cow<'_, str>> {
+        self.history.get(index).map(Cow::from)
+    }
+
+    fn timestamp(&self, index: usize) -> Option<SystemTime> {
+        self.history.timestamp(index)
+    }
+
+    fn metadata(&self, index: usize) -> Option<Cow<'_, EntryMetadata>> {
+        self.history.metadata(index).map(Cow::Borrowed)
+    }
+
+    fn set_metadata(&mut self, index: usize, key: &str, value: &str) -> bool {
+        HistoryStore::set_metadata(&mut self.history, index, key, value)
+    }
+
+    fn add(&mut self, line: &str) -> bool {
+        let added = self.history.add(line);
+        if added {
+            self.new_entries = (self.new_entries + 1).min(self.history.len());
+        }
+        added
+    }
+
+    fn clear(&mut self) {
+        self.history.clear();
+        self.new_entries = 0;
+    }
+
+    fn load(&mut self, path: &Path) -> Result<()> {
+        let mut lock = FdLock::new(File::open(path)?);
+        let mut file = lock.lock()?;
+        let loaded = self.read(&mut file)?;
+        for (entry, timestamp) in loaded.iter_with_timestamps() {
+            self.history.add_with_timestamp(entry.as_str(), timestamp);
+        }
+        self.new_entries = 0;
+        Ok(())
+    }
+
+    fn save(&mut self, path: &Path) -> Result<()> {
+        let mut lock = FdLock::new(open_for_update(path)?);
+        let mut file = lock.lock()?;
+        Self::write(&self.key, &mut file, &mut self.history)?;
+        self.new_entries = 0;
+        Ok(())
+    }
+
+    fn append(&mut self, path: &Path) -> Result<()> {
+        if self.new_entries == 0 {
+            return Ok(());
+        }
+        self.merge(path).map(drop)
+    }
+
+    fn sync(&mut self, path: &Path) -> Result<()> {
+        self.history = self.merge(path)?;
+        Ok(())
+    }
+
+    fn configure(&mut self, config: &Config) {
+        self.config = config.clone();
+        HistoryStore::configure(&mut self.history, config);
+    }
+}
+
+fn invalid_data(msg: &str) -> ReadlineError {
+    io::Error::new(io::ErrorKind::InvalidData, msg).into()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::{EncryptedHistory, Key};
+    use crate::history::HistoryStore;
+    use crate::Result;
+
+    fn entries<S: HistoryStore>(history: &S) -> Vec<String> {
+        history.entries().map(|entry| entry.into_owned()).collect()
+    }
+
+    #[test]
+    fn save_and_load() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        let mut history = EncryptedHistory::new(Key::new([1; 32]));
+        history.add("export TOKEN=secret");
+        history.add("line\nfeed");
+        history.save(tf.path())?;
+        let content = fs::read(tf.path())?;
+        assert!(!content.windows(6).any(|w| w == b"secret"));
+
+        let mut loaded = EncryptedHistory::new(Key::new([1; 32]));
+        loaded.load(tf.path())?;
+        assert_eq!(entries(&history), entries(&loaded));
+
+        let mut wrong = EncryptedHistory::new(Key::new([2; 32]));
+        assert!(wrong.load(tf.path()).is_err());
+        assert!(wrong.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn append_and_sync() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        let mut one = EncryptedHistory::new(Key::new([1; 32]));
+        let mut two = EncryptedHistory::new(Key::new([1; 32]));
+        one.add("one");
+        one.append(tf.path())?;
+        two.add("two");
+        two.append(tf.path())?;
+        let mut loaded = EncryptedHistory::new(Key::new([1; 32]));
+        loaded.load(tf.path())?;
+        assert_eq!(vec!["one", "two"], entries(&loaded));
+
+        one.add("three");
+        one.sync(tf.path())?;
+        assert_eq!(vec!["one", "two", "three"], entries(&one));
+        Ok(())
+    }
+}
@@ -5,6 +5,8 @@ use std::error;
 use std::fmt;
 use std::io;
 
+use crate::expansion::ExpansionError;
+
 /// The error type for Rustyline errors that can arise from
 /// I/O related errors or Errno when using the nix-rust library
 // #[non_exhaustive]
@@ -34,6 +36,12 @@ pub enum ReadlineError {
     /// Something went wrong calling a Windows API
     #[cfg(windows)]
     SystemError(error_code::SystemError),
+    /// A history reference cannot be expanded (see
+    /// [`Config::history_expansion`](crate::Config::history_expansion))
+    HistoryExpansion(ExpansionError),
+    /// Error from the SQLite history database
+    #[cfg(feature = "history-sqlite")]
+    SQLiteError(rusqlite::Error),
 }
 
 impl fmt::Display for ReadlineError {
@@ -52,6 +60,9 @@ impl fmt::Display for ReadlineError {
             ReadlineError::Decode(ref err) => err.fmt(f),
             #[cfg(windows)]
             ReadlineError::SystemError(ref err) => err.fmt(f),
+            ReadlineError::HistoryExpansion(ref err) => err.fmt(f),
+            #[cfg(feature = "history-sqlite")]
+            ReadlineError::SQLiteError(ref err) => err.fmt(f),
         }
     }
 }
@@ -90,3 +101,16 @@ impl From<error_code::SystemError> for ReadlineError {
         ReadlineError::SystemError(err)
     }
 }
+
+impl From<ExpansionError> for ReadlineError {
+    fn from(err: ExpansionError) -> Self {
+        ReadlineError::HistoryExpansion(err)
+    }
+}
+
+#[cfg(feature = "history-sqlite")]
+impl From<rusqlite::Error> for ReadlineError {
+    fn from(err: rusqlite::Error) -> Self {
+        ReadlineError::SQLiteError(err)
+    }
+}
@@ -0,0 +1,611 @@
+//! History expansion (`!!`, `!$`, `^old^new`, ...)
+//!
+//! References to history entries are replaced like in bash:
+//! - events: `!!` (previous entry), `!n` (entry `n`, starting at 1), `!-n`
+//!   (`n`th previous entry), `!string` (most recent entry starting with
+//!   `string`), `!?string?` (most recent entry containing `string`),
+//! - words, after a `:` which can be omitted before `^`, `$`, `*` or `-`:
+//!   `0`, `n`, `^` (first argument), `$` (last argument), `x-y`, `x-`
+//!   (`x-$` without the last word), `x*` (`x-$`), `*` (`1-$`),
+//! - modifiers, each after a `:`: `h` (remove the last path component), `t`
+//!   (keep only the last path component), `r` (remove the extension), `e`
+//!   (keep only the extension), `s/old/new/` (replace the first `old`, `&` in
+//!   `new` standing for `old`), `gs/old/new/` (replace all), `&` / `g&`
+//!   (repeat the last substitution),
+//! - `^old^new^` at the start of the line, for `!!:s^old^new^`.
+//!
+//! The expansion character is not special when followed by a blank, `=` or
+//! `(`, after a backslash or inside single quotes, and the rest of the line
+//! is left as is after a comment character starting a word.
+//!
+//! ```
+//! use rustyline::config::HistChars;
+//! use rustyline::expansion::expand;
+//! use rustyline::history::History;
+//!
+//! let mut history = History::new();
+//! history.add("cp src/lib.rs /tmp");
+//! let line = expand(&history, "ls -l !$ !!:1:t", HistChars::default());
+//! assert_eq!(Ok(Some("ls -l /tmp lib.rs".to_owned())), line);
+//! ```
+use std::error;
+use std::fmt;
+
+use crate::config::HistChars;
+use crate::history::{Direction, HistoryStore};
+
+/// Why a history reference cannot be expanded.
+///
+/// Each variant holds the text of the reference, as typed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExpansionError {
+    /// No history entry matches the event designator
+    EventNotFound(String),
+    /// The word designator selects no word of the entry
+    BadWordSpecifier(String),
+    /// Unknown modifier
+    BadModifier(String),
+    /// The text to replace is not found
+    SubstitutionFailed(String),
+    /// `&` or an empty text to replace without any previous substitution
+    NoPreviousSubstitution(String),
+}
+
+impl fmt::Display for ExpansionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpansionError::EventNotFound(s) => write!(f, "{}: event not found", s),
+            ExpansionError::BadWordSpecifier(s) => write!(f, "{}: bad word specifier", s),
+            ExpansionError::BadModifier(s) => write!(f, "{}: unrecognized history modifier", s),
+            ExpansionError::SubstitutionFailed(s) => write!(f, "{}: substitution failed", s),
+            ExpansionError::NoPreviousSubstitution(s) => {
+                write!(f, "{}: no previous substitution", s)
+            }
+        }
+    }
+}
+
+impl error::Error for ExpansionError {}
+
+/// Expand the history references in `line`.
+///
+/// Returns `None` when there is nothing to expand.
+pub fn expand(
+    history: &dyn HistoryStore,
+    line: &str,
+    chars: HistChars,
+) -> Result<Option<String>, ExpansionError> {
+    let mut expander = Expander {
+        history,
+        chars,
+        line,
+        pos: 0,
+        out: String::with_capacity(line.len()),
+        subst: None,
+        expanded: false,
+    };
+    expander.expand_line()?;
+    Ok(if expander.expanded {
+        Some(expander.out)
+    } else {
+        None
+    })
+}
+
+struct Expander<'a> {
+    history: &'a dyn HistoryStore,
+    chars: HistChars,
+    line: &'a str,
+    pos: usize,
+    /// expanded line
+    out: String,
+    /// last substitution (`old`, `new`), for `&` and an empty `old`
+    subst: Option<(String, String)>,
+    expanded: bool,
+}
+
+impl Expander<'_> {
+    fn peek(&self) -> Option<char> {
+        self.line[self.pos..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.line[self.pos..].chars().nth(1)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Text of the reference starting at `start`, for errors
+    fn reference(&self, start: usize) -> String {
+        self.line[start..self.pos].to_owned()
+    }
+
+    fn expand_line(&mut self) -> Result<(), ExpansionError> {
+        if self.eat(self.chars.substitution) {
+            let event = self.previous(0, 1)?;
+            let sep = self.chars.substitution;
+            let text = self.substitute(0, event, sep, false)?;
+            self.out.push_str(&text);
+            self.expanded = true;
+        }
+        let mut single_quoted = false;
+        let mut double_quoted = false;
+        let mut word_start = true;
+        while let Some(c) = self.peek() {
+            if c == '\\' && !single_quoted {
+                self.bump();
+                self.out.push(c);
+                if let Some(c) = self.bump() {
+                    self.out.push(c);
+                }
+                word_start = false;
+                continue;
+            }
+            if c == self.chars.expansion && !single_quoted {
+                if let Some(next) = self.peek_second() {
+                    if !(next.is_whitespace()
+                        || next == '='
+                        || next == '('
+                        || (next == '"' && double_quoted))
+                    {
+                        let start = self.pos;
+                        self.bump();
+                        let text = self.designator(start)?;
+                        self.out.push_str(&text);
+                        self.expanded = true;
+                        word_start = false;
+                        continue;
+                    }
+                }
+            } else if Some(c) == self.chars.comment
+                && word_start
+                && !single_quoted
+                && !double_quoted
+            {
+                self.out.push_str(&self.line[self.pos..]);
+                break;
+            } else if c == '\'' && !double_quoted {
+                single_quoted = !single_quoted;
+            } else if c == '"' && !single_quoted {
+                double_quoted = !double_quoted;
+            }
+            self.bump();
+            self.out.push(c);
+            word_start = c.is_whitespace();
+        }
+        Ok(())
+    }
+
+    /// Event, word and modifiers of the reference starting at `start`
+    fn designator(&mut self, start: usize) -> Result<String, ExpansionError> {
+        let event = match self.peek() {
+            Some(c) if c == self.chars.expansion => {
+                self.bump();
+                self.previous(start, 1)?
+            }
+            Some('-') if self.peek_second().is_some_and(|c| c.is_ascii_digit()) => {
+                self.bump();
+                let n = self.number().unwrap_or_default();
+                self.previous(start, n)?
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let n = self.number().unwrap_or_default();
+                n.checked_sub(1)
+                    .and_then(|idx| self.history.entry(idx))
+                    .map(|entry| entry.into_owned())
+                    .ok_or_else(|| ExpansionError::EventNotFound(self.reference(start)))?
+            }
+            Some('?') => {
+                self.bump();
+                let end = self.line[self.pos..]
+                    .find('?')
+                    .map_or(self.line.len(), |i| self.pos + i);
+                let term = &self.line[self.pos..end];
+                self.pos = (end + 1).min(self.line.len());
+                self.find(start, term, false)?
+            }
+            Some('#') => {
+                self.bump();
+                self.out.clone()
+            }
+            Some(':') | Some('^') | Some('$') | Some('*') => self.previous(start, 1)?,
+            _ => {
+                let end = self.line[self.pos..]
+                    .find(|c: char| c.is_whitespace() || c == ':')
+                    .map_or(self.line.len(), |i| self.pos + i);
+                let term = &self.line[self.pos..end];
+                self.pos = end;
+                self.find(start, term, true)?
+            }
+        };
+        let text = self.words(start, event)?;
+        self.modifiers(start, text)
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let len = self.line[self.pos..]
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.line.len() - self.pos);
+        let n = self.line[self.pos..self.pos + len].parse().ok()?;
+        self.pos += len;
+        Some(n)
+    }
+
+    /// `n`th previous entry
+    fn previous(&self, start: usize, n: usize) -> Result<String, ExpansionError> {
+        self.history
+            .len()
+            .checked_sub(n)
+            .and_then(|idx| self.history.entry(idx))
+            .map(|entry| entry.into_owned())
+            .ok_or_else(|| ExpansionError::EventNotFound(self.reference(start)))
+    }
+
+    /// Most recent entry starting with / containing `term`
+    fn find(&self, start: usize, term: &str, prefix: bool) -> Result<String, ExpansionError> {
+        let last = self.history.len().checked_sub(1);
+        last.and_then(|last| {
+            if prefix {
+                self.history.starts_with(term, last, Direction::Reverse)
+            } else {
+                self.history.search(term, last, Direction::Reverse)
+            }
+        })
+        .and_then(|idx| self.history.entry(idx))
+        .map(|entry| entry.into_owned())
+        .ok_or_else(|| ExpansionError::EventNotFound(self.reference(start)))
+    }
+
+    /// Words selected from `event`, if any word designator follows
+    fn words(&mut self, start: usize, event: String) -> Result<String, ExpansionError> {
+        let designated = match self.peek() {
+            Some(':') => self
+                .peek_second()
+                .is_some_and(|c| c.is_ascii_digit() || "^$*-".contains(c)),
+            Some(c) => "^$*-".contains(c),
+            None => false,
+        };
+        if !designated {
+            return Ok(event);
+        }
+        self.eat(':');
+        let words = split_words(&event);
+        let last = words.len() - 1;
+        let (first, end) = if self.eat('*') {
+            if last == 0 {
+                return Ok(String::new());
+            }
+            (1, last)
+        } else if self.eat('-') {
+            (0, self.word_index(last).unwrap_or(last.saturating_sub(1)))
+        } else {
+            let first = self
+                .word_index(last)
+                .ok_or_else(|| ExpansionError::BadWordSpecifier(self.reference(start)))?;
+            if self.eat('*') {
+                (first, last)
+            } else if self.eat('-') {
+                (
+                    first,
+                    self.word_index(last).unwrap_or(last.saturating_sub(1)),
+                )
+            } else {
+                (first, first)
+            }
+        };
+        if first > end || end > last {
+            return Err(ExpansionError::BadWordSpecifier(self.reference(start)));
+        }
+        Ok(words[first..=end].join(" "))
+    }
+
+    fn word_index(&mut self, last: usize) -> Option<usize> {
+        if self.eat('^') {
+            Some(1)
+        } else if self.eat('$') {
+            Some(last)
+        } else {
+            self.number()
+        }
+    }
+
+    fn modifiers(&mut self, start: usize, mut text: String) -> Result<String, ExpansionError> {
+        while self.peek() == Some(':')
+            && self
+                .peek_second()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '&')
+        {
+            self.bump();
+            let mut modifier = self.bump();
+            let global = modifier == Some('g');
+            if global {
+                modifier = self.bump();
+            }
+            text = match modifier {
+                Some('h') if !global => match text.rfind('/') {
+                    Some(0) => "/".to_owned(),
+                    Some(i) => text[..i].to_owned(),
+                    None => text,
+                },
+                Some('t') if !global => match text.rfind('/') {
+                    Some(i) => text[i + 1..].to_owned(),
+                    None => text,
+                },
+                Some('r') if !global => match extension(&text) {
+                    Some(i) => text[..i].to_owned(),
+                    None => text,
+                },
+                Some('e') if !global => match extension(&text) {
+                    Some(i) => text[i..].to_owned(),
+                    None => String::new(),
+                },
+                Some('s') => match self.bump() {
+                    Some(sep) => self.substitute(start, text, sep, global)?,
+                    None => return Err(ExpansionError::BadModifier(self.reference(start))),
+                },
+                Some('&') => {
+                    let (old, new) = self.subst.clone().ok_or_else(|| {
+                        ExpansionError::NoPreviousSubstitution(self.reference(start))
+                    })?;
+                    replace(&text, &old, &new, global)
+                        .ok_or_else(|| ExpansionError::SubstitutionFailed(self.reference(start)))?
+                }
+                _ => return Err(ExpansionError::BadModifier(self.reference(start))),
+            };
+        }
+        Ok(text)
+    }
+
+    /// Parse `old<sep>new<sep>` and replace `old` by `new` in `text`
+    fn substitute(
+        &mut self,
+        start: usize,
+        text: String,
+        sep: char,
+        global: bool,
+    ) -> Result<String, ExpansionError> {
+        let mut old = self.delimited(sep, false);
+        let new = self.delimited(sep, true);
+        if old.is_empty() {
+            old = match self.subst {
+                Some((ref old, _)) => old.clone(),
+                None => {
+                    return Err(ExpansionError::NoPreviousSubstitution(
+                        self.reference(start),
+                    ))
+                }
+            };
+        }
+        let new = new.replace('\0', &old);
+        let result = replace(&text, &old, &new, global)
+            .ok_or_else(|| ExpansionError::SubstitutionFailed(self.reference(start)));
+        self.subst = Some((old, new));
+        result
+    }
+
+    /// Text up to the next unescaped `sep` (or the end of the line), with
+    /// unescaped `&` replaced by `\0` if `ampersand`
+    fn delimited(&mut self, sep: char, ampersand: bool) -> String {
+        let mut text = String::new();
+        while let Some(c) = self.bump() {
+            if c == sep {
+                break;
+            } else if c == '\\' && (self.peek() == Some(sep) || self.peek() == Some('&')) {
+                text.push(self.bump().unwrap());
+            } else if c == '&' && ampersand {
+                text.push('\0');
+            } else {
+                text.push(c);
+            }
+        }
+        text
+    }
+}
+
+fn replace(text: &str, old: &str, new: &str, global: bool) -> Option<String> {
+    if !text.contains(old) {
+        None
+    } else if global {
+        Some(text.replace(old, new))
+    } else {
+        Some(text.replacen(old, new, 1))
+    }
+}
+
+/// Start of the extension of the last path component
+fn extension(text: &str) -> Option<usize> {
+    let name = text.rfind('/').map_or(0, |i| i + 1);
+    text[name..].rfind('.').map(|i| name + i)
+}
+
+/// Split `line` into blank separated words, quotes included
+fn split_words(line: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && quote != Some('\'') {
+            escaped = true;
+        } else if quote.is_some() {
+            if quote == Some(c) {
+                quote = None;
+            }
+        } else if c == '\'' || c == '"' {
+            quote = Some(c);
+        } else if c.is_whitespace() {
+            if let Some(start) = start.take() {
+                words.push(&line[start..i]);
+            }
+            continue;
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(start) = start {
+        words.push(&line[start..]);
+    }
+    if words.is_empty() {
+        words.push("");
+    }
+    words
+}
+
+#[cfg(test)]
+mod test {
+    use super::{expand, split_words, ExpansionError};
+    use crate::config::HistChars;
+    use crate::history::History;
+
+    fn history() -> History {
+        let mut history = History::new();
+        history.add("git commit -m 'first commit'");
+        history.add("cargo build --release");
+        history.add("cp src/lib.rs /tmp/backup.tar.gz");
+        history
+    }
+
+    fn assert_expand(line: &str, expected: &str) {
+        let history = history();
+        assert_eq!(
+            Ok(Some(expected.to_owned())),
+            expand(&history, line, HistChars::default()),
+            "{}",
+            line
+        );
+    }
+
+    #[test]
+    fn events() {
+        assert_expand("!!", "cp src/lib.rs /tmp/backup.tar.gz");
+        assert_expand("sudo !!", "sudo cp src/lib.rs /tmp/backup.tar.gz");
+        assert_expand("!1", "git commit -m 'first commit'");
+        assert_expand("!-2", "cargo build --release");
+        assert_expand("!car", "cargo build --release");
+        assert_expand("!?first?", "git commit -m 'first commit'");
+        assert_expand("!?build", "cargo build --release");
+        assert_expand("echo !#", "echo echo ");
+    }
+
+    #[test]
+    fn words() {
+        assert_expand("ls !$", "ls /tmp/backup.tar.gz");
+        assert_expand("ls !^", "ls src/lib.rs");
+        assert_expand("echo !*", "echo src/lib.rs /tmp/backup.tar.gz");
+        assert_expand("!!:0", "cp");
+        assert_expand("!git:3", "'first commit'");
+        assert_expand("!git:1-2", "commit -m");
+        assert_expand("!git:2*", "-m 'first commit'");
+        assert_expand("!git:1-", "commit -m");
+        assert_expand("!git:-1", "git commit");
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_expand("!$:h", "/tmp");
+        assert_expand("!$:t", "backup.tar.gz");
+        assert_expand("!$:r", "/tmp/backup.tar");
+        assert_expand("!$:e", ".gz");
+        assert_expand("!$:t:r:r", "backup");
+        assert_expand("!-2:s/build/test/", "cargo test --release");
+        assert_expand("!-2:s/build/&ing/", "cargo building --release");
+        assert_expand("!!:gs/c/C/", "Cp srC/lib.rs /tmp/baCkup.tar.gz");
+        assert_expand("^cp^mv^ -v", "mv src/lib.rs /tmp/backup.tar.gz -v");
+        assert_expand("^.gz", "cp src/lib.rs /tmp/backup.tar");
+    }
+
+    #[test]
+    fn not_expanded() {
+        let history = history();
+        for line in &[
+            "echo hello!",
+            "a != b",
+            "x=!(foo)",
+            "echo \\!!",
+            "echo '!!'",
+            "echo # !!",
+        ] {
+            assert_eq!(
+                Ok(None),
+                expand(&history, line, HistChars::default()),
+                "{}",
+                line
+            );
+        }
+        assert_expand("echo \"!!\"", "echo \"cp src/lib.rs /tmp/backup.tar.gz\"");
+        let chars = HistChars {
+            expansion: '%',
+            substitution: '^',
+            comment: None,
+        };
+        assert_eq!(Ok(None), expand(&history, "echo !!", chars));
+        assert_eq!(
+            Ok(Some("echo # cargo build --release".to_owned())),
+            expand(&history, "echo # %car", chars)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let history = history();
+        let expand = |line| expand(&history, line, HistChars::default());
+        assert_eq!(
+            Err(ExpansionError::EventNotFound("!rustc".to_owned())),
+            expand("!rustc --version")
+        );
+        assert_eq!(
+            Err(ExpansionError::EventNotFound("!9".to_owned())),
+            expand("!9")
+        );
+        assert_eq!(
+            Err(ExpansionError::BadWordSpecifier("!!:5".to_owned())),
+            expand("!!:5")
+        );
+        assert_eq!(
+            Err(ExpansionError::BadModifier("!!:z".to_owned())),
+            expand("!!:z")
+        );
+        assert_eq!(
+            Err(ExpansionError::SubstitutionFailed("^foo^bar".to_owned())),
+            expand("^foo^bar")
+        );
+        assert_eq!(
+            Err(ExpansionError::NoPreviousSubstitution("!!:&".to_owned())),
+            expand("!!:&")
+        );
+        assert_eq!(
+            "!rustc: event not found",
+            ExpansionError::EventNotFound("!rustc".to_owned()).to_string()
+        );
+        let empty = History::new();
+        assert_eq!(
+            Err(ExpansionError::EventNotFound("!!".to_owned())),
+            super::expand(&empty, "!!", HistChars::default())
+        );
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(vec![""], split_words(""));
+        assert_eq!(vec!["a", "b"], split_words(" a  b "));
+        assert_eq!(
+            vec!["echo", "'a b'", "\"c d\"", "e\\ f"],
+            split_words("echo 'a b' \"c d\" e\\ f")
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! Edition of the line with an external editor (`edit-and-execute-command`)
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+use log::debug;
+
+use crate::Result;
+
+/// Editor used when neither `VISUAL` nor `EDITOR` is set
+#[cfg(unix)]
+const DEFAULT_EDITOR: &str = "vi";
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad";
+
+/// External editor command: `$VISUAL`, `$EDITOR` or `vi` (`notepad` on
+/// windows)
+pub fn editor() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_owned())
+}
+
+/// Save `text` to a temporary file, open it with the `editor` command (its
+/// arguments separated by white spaces) and return the edited text, without
+/// its trailing newlines, or `None` if the editor failed.
+///
+/// The terminal must be in cooked mode.
+pub fn edit(editor: &str, text: &str) -> Result<Option<String>> {
+    let mut args = editor.split_whitespace();
+    let program = match args.next() {
+        Some(program) => program,
+        None => return Ok(None),
+    };
+    let path = temp_file(text)?;
+    let status = Command::new(program).args(args).arg(&path).status();
+    let edited = match status {
+        Ok(status) if status.success() => fs::read_to_string(&path).map(Some),
+        Ok(status) => {
+            debug!(target: "rustyline", "{} exited with {}", program, status);
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    };
+    let _ = fs::remove_file(&path);
+    Ok(edited?.map(|text| text.trim_end_matches(&['\n', '\r'][..]).to_owned()))
+}
+
+/// Create a new temporary file containing `text` (followed by a newline, as
+/// expected by most editors)
+fn temp_file(text: &str) -> io::Result<PathBuf> {
+    let dir = env::temp_dir();
+    let mut n = 0;
+    loop {
+        let path = dir.join(format!("rustyline-{}-{}.txt", process::id(), n));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                writeln!(file, "{}", text)?;
+                return Ok(path);
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use std::fs;
+
+    #[test]
+    fn edit() {
+        let src = super::temp_file("echo edited").unwrap();
+        let editor = format!("cp {}", src.display());
+        assert_eq!(
+            Some("echo edited".to_owned()),
+            super::edit(&editor, "echo original").unwrap()
+        );
+        fs::remove_file(src).unwrap();
+
+        assert_eq!(Some("ls".to_owned()), super::edit("true", "ls").unwrap());
+        assert_eq!(None, super::edit("false", "ls").unwrap());
+        assert!(super::edit("rustyline-no-such-editor", "ls").is_err());
+    }
+}
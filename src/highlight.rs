@@ -1,9 +1,12 @@
 //! Syntax highlighting
 
 use crate::config::CompletionType;
+use crate::keymap::ModeState;
+use crate::keys::KeyEvent;
 use memchr::memchr;
 use std::borrow::Cow::{self, Borrowed, Owned};
 use std::cell::Cell;
+use std::ops::Range;
 
 /// Syntax highlighter with [ANSI color](https://en.wikipedia.org/wiki/ANSI_escape_code#SGR_(Select_Graphic_Rendition)_parameters).
 /// Rustyline will try to handle escape sequence for ANSI color on windows
@@ -33,8 +36,25 @@ pub trait Highlighter {
     }
     /// Takes the `hint` and
     /// returns the highlighted version (with ANSI color).
+    ///
+    /// By default, the hint is dimmed.
     fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
-        Borrowed(hint)
+        Owned(format!("\x1b[2m{}\x1b[22m", hint))
+    }
+    /// Takes the `prompt` and the keys typed so far of a pending key
+    /// sequence (e.g. `C-x` while `C-x C-s` is bound) and
+    /// returns the prompt displayed until the sequence is complete.
+    fn pending_prefix_prompt<'p>(&self, prompt: &'p str, prefix: &[KeyEvent]) -> Cow<'p, str> {
+        let _ = prefix;
+        Borrowed(prompt)
+    }
+    /// Takes the `prompt`, with the mode string of `show-mode-in-prompt` if
+    /// enabled, and the current editing `mode` and
+    /// returns the prompt displayed in this mode (e.g. with a `[N]` / `[I]`
+    /// indicator).
+    fn mode_prompt<'p>(&self, prompt: &'p str, mode: &ModeState) -> Cow<'p, str> {
+        let _ = mode;
+        Borrowed(prompt)
     }
     /// Takes the completion `candidate` and
     /// returns the highlighted version (with ANSI color).
@@ -57,6 +77,84 @@ pub trait Highlighter {
         let _ = (line, pos);
         false
     }
+    /// Takes the currently edited `line` with the cursor `pos`ition and the
+    /// `range` matched by a substring history search and returns the
+    /// highlighted version (with ANSI color).
+    ///
+    /// By default, the matched text is underlined.
+    fn highlight_match<'l>(&self, line: &'l str, pos: usize, range: Range<usize>) -> Cow<'l, str> {
+        let _ = pos;
+        match line.get(range.clone()) {
+            Some(matched) => Owned(format!(
+                "{}\x1b[4m{}\x1b[24m{}",
+                &line[..range.start],
+                matched,
+                &line[range.end..]
+            )),
+            None => Borrowed(line),
+        }
+    }
+    /// Takes the currently edited `line` with the cursor `pos`ition and the
+    /// active `region` (the text between the mark and the cursor, see
+    /// [`LineBuffer::region`](crate::line_buffer::LineBuffer::region)) and
+    /// returns the highlighted version (with ANSI color).
+    ///
+    /// By default, the region is in reverse video.
+    fn highlight_region<'l>(
+        &self,
+        line: &'l str,
+        pos: usize,
+        region: Range<usize>,
+    ) -> Cow<'l, str> {
+        let _ = pos;
+        match line.get(region.clone()) {
+            Some(selected) => Owned(format!(
+                "{}\x1b[7m{}\x1b[27m{}",
+                &line[..region.start],
+                selected,
+                &line[region.end..]
+            )),
+            None => Borrowed(line),
+        }
+    }
+    /// Takes the completion `candidate` and the byte `ranges` matched by the
+    /// completed word (see [`MatchStyle`](crate::completion::MatchStyle)) and
+    /// returns the highlighted version (with ANSI color).
+    ///
+    /// By default, the matched characters are in bold.
+    fn highlight_candidate_matches<'c>(
+        &self,
+        candidate: &'c str,
+        ranges: &[Range<usize>],
+    ) -> Cow<'c, str> {
+        if ranges.is_empty() {
+            return Borrowed(candidate);
+        }
+        let mut highlighted = String::with_capacity(candidate.len() + 9 * ranges.len());
+        let mut last = 0;
+        for range in ranges {
+            match candidate.get(range.clone()) {
+                Some(matched) if range.start >= last => {
+                    highlighted.push_str(&candidate[last..range.start]);
+                    highlighted.push_str("\x1b[1m");
+                    highlighted.push_str(matched);
+                    highlighted.push_str("\x1b[22m");
+                    last = range.end;
+                }
+                _ => return Borrowed(candidate),
+            }
+        }
+        highlighted.push_str(&candidate[last..]);
+        Owned(highlighted)
+    }
+    /// Takes the name of a `group` of completion candidates (see
+    /// [`Candidate::group`](crate::completion::Candidate::group)) and returns
+    /// the highlighted header (with ANSI color).
+    ///
+    /// By default, the header is in bold.
+    fn highlight_candidate_group<'g>(&self, group: &'g str) -> Cow<'g, str> {
+        Owned(format!("\x1b[1m{}\x1b[22m", group))
+    }
 }
 
 impl Highlighter for () {}
@@ -78,6 +176,14 @@ impl<'r, H: ?Sized + Highlighter> Highlighter for &'r H {
         (**self).highlight_hint(hint)
     }
 
+    fn pending_prefix_prompt<'p>(&self, prompt: &'p str, prefix: &[KeyEvent]) -> Cow<'p, str> {
+        (**self).pending_prefix_prompt(prompt, prefix)
+    }
+
+    fn mode_prompt<'p>(&self, prompt: &'p str, mode: &ModeState) -> Cow<'p, str> {
+        (**self).mode_prompt(prompt, mode)
+    }
+
     fn highlight_candidate<'c>(
         &self,
         candidate: &'c str,
@@ -89,6 +195,93 @@ impl<'r, H: ?Sized + Highlighter> Highlighter for &'r H {
     fn highlight_char(&self, line: &str, pos: usize) -> bool {
         (**self).highlight_char(line, pos)
     }
+
+    fn highlight_match<'l>(&self, line: &'l str, pos: usize, range: Range<usize>) -> Cow<'l, str> {
+        (**self).highlight_match(line, pos, range)
+    }
+
+    fn highlight_region<'l>(
+        &self,
+        line: &'l str,
+        pos: usize,
+        region: Range<usize>,
+    ) -> Cow<'l, str> {
+        (**self).highlight_region(line, pos, region)
+    }
+
+    fn highlight_candidate_matches<'c>(
+        &self,
+        candidate: &'c str,
+        ranges: &[Range<usize>],
+    ) -> Cow<'c, str> {
+        (**self).highlight_candidate_matches(candidate, ranges)
+    }
+
+    fn highlight_candidate_group<'g>(&self, group: &'g str) -> Cow<'g, str> {
+        (**self).highlight_candidate_group(group)
+    }
+}
+
+/// Highlight the text matched by a substring history search or the active
+/// region
+pub(crate) struct HighlightedRange<'h> {
+    pub highlighter: &'h dyn Highlighter,
+    pub range: Range<usize>,
+    /// `true` for the region, `false` for a history match
+    pub region: bool,
+}
+
+impl Highlighter for HighlightedRange<'_> {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        if self.region {
+            self.highlighter
+                .highlight_region(line, pos, self.range.clone())
+        } else {
+            self.highlighter
+                .highlight_match(line, pos, self.range.clone())
+        }
+    }
+
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        default: bool,
+    ) -> Cow<'b, str> {
+        self.highlighter.highlight_prompt(prompt, default)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        self.highlighter.highlight_hint(hint)
+    }
+
+    fn pending_prefix_prompt<'p>(&self, prompt: &'p str, prefix: &[KeyEvent]) -> Cow<'p, str> {
+        self.highlighter.pending_prefix_prompt(prompt, prefix)
+    }
+
+    fn mode_prompt<'p>(&self, prompt: &'p str, mode: &ModeState) -> Cow<'p, str> {
+        self.highlighter.mode_prompt(prompt, mode)
+    }
+
+    fn highlight_candidate<'c>(
+        &self,
+        candidate: &'c str,
+        completion: CompletionType,
+    ) -> Cow<'c, str> {
+        self.highlighter.highlight_candidate(candidate, completion)
+    }
+
+    fn highlight_candidate_matches<'c>(
+        &self,
+        candidate: &'c str,
+        ranges: &[Range<usize>],
+    ) -> Cow<'c, str> {
+        self.highlighter
+            .highlight_candidate_matches(candidate, ranges)
+    }
+
+    fn highlight_candidate_group<'g>(&self, group: &'g str) -> Cow<'g, str> {
+        self.highlighter.highlight_candidate_group(group)
+    }
 }
 
 const OPENS: &[u8; 3] = b"{[(";
@@ -234,6 +427,39 @@ fn is_close_bracket(bracket: u8) -> bool {
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    pub fn highlight_match() {
+        use super::Highlighter;
+        assert_eq!(
+            "cargo \x1b[4mtest\x1b[24m --all",
+            ().highlight_match("cargo test --all", 0, 6..10)
+        );
+        assert_eq!("cargo", ().highlight_match("cargo", 0, 6..10));
+    }
+    #[test]
+    pub fn highlight_region() {
+        use super::Highlighter;
+        assert_eq!(
+            "ca\x1b[7mrgo\x1b[27m test",
+            ().highlight_region("cargo test", 2, 2..5)
+        );
+    }
+    #[test]
+    pub fn highlight_candidate_matches() {
+        use super::Highlighter;
+        assert_eq!(
+            "\x1b[1mb\x1b[22mui\x1b[1mld\x1b[22m",
+            ().highlight_candidate_matches("build", &[0..1, 3..5])
+        );
+        assert_eq!(
+            "build",
+            ().highlight_candidate_matches("build", &[3..5, 0..1])
+        );
+        assert_eq!(
+            "build",
+            ().highlight_candidate_matches("build", &[0..1, 3..6])
+        );
+    }
     #[test]
     pub fn find_matching_bracket() {
         use super::find_matching_bracket;
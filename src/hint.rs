@@ -48,6 +48,16 @@ impl<'r, H: ?Sized + Hinter> Hinter for &'r H {
     }
 }
 
+/// Start of `text` up to the end of its first word (the separators before
+/// it included)
+pub(crate) fn first_word(text: &str) -> &str {
+    let start = text.find(char::is_alphanumeric).unwrap_or(text.len());
+    let end = text[start..]
+        .find(|c: char| !c.is_alphanumeric())
+        .map_or(text.len(), |i| start + i);
+    &text[..end]
+}
+
 /// Add suggestion based on previous history entries matching current user
 /// input.
 pub struct HistoryHinter {}
@@ -68,8 +78,8 @@ impl Hinter for HistoryHinter {
             ctx.history
                 .starts_with(&line[..pos], start, Direction::Reverse)
         {
-            let entry = ctx.history.get(history_index);
-            if let Some(entry) = entry {
+            let entry = ctx.history.entry(history_index);
+            if let Some(ref entry) = entry {
                 if entry == line || entry == &line[..pos] {
                     return None;
                 }
@@ -86,6 +96,13 @@ mod test {
     use crate::history::History;
     use crate::Context;
 
+    #[test]
+    pub fn first_word() {
+        assert_eq!("build", super::first_word("build --release"));
+        assert_eq!(" --release", super::first_word(" --release"));
+        assert_eq!("", super::first_word(""));
+    }
+
     #[test]
     pub fn empty_history() {
         let history = History::new();
@@ -1,17 +1,68 @@
 //! History API
-
+//!
+//! # History files
+//!
+//! [`History::save`] and [`History::append`] write the
+//! [format](crate::config::Config::history_format) set in the configuration,
+//! [`History::load`] reads them all:
+//! - [`HistoryFormat::Escaped`] (the default): a `#V2` line then one entry per
+//!   line, line feeds being written as `\n` and backslashes as `\\`:
+//!   ```text
+//!   #V2
+//!   cargo build
+//!   for i in 1 2; do\n  echo $i\ndone
+//!   ```
+//!
+//!   Entries timestamps are not saved in this format.
+//! - [`HistoryFormat::Bash`]: compatible with bash `HISTFILE`, each entry is
+//!   preceded by its timestamp line (seconds since the epoch) so that entries
+//!   with line feeds are read back whole (by bash too, with `shopt -s
+//!   lithist`):
+//!   ```text
+//!   #1634567890
+//!   cargo build
+//!   #1634567890
+//!   for i in 1 2; do
+//!     echo $i
+//!   done
+//!   ```
+//!   Files without timestamps (one entry per line, as written by bash when
+//!   `HISTTIMEFORMAT` is unset) are read too.
+//! - [`HistoryFormat::Zsh`]: compatible with zsh `HISTFILE` (with
+//!   `EXTENDED_HISTORY`), line feeds being preceded by a backslash:
+//!   ```text
+//!   : 1634567890:0;cargo build
+//!   : 1634567890:0;for i in 1 2; do\
+//!     echo $i\
+//!   done
+//!   ```
+//!   Files without timestamps are read too.
+//! - [`HistoryFormat::Fish`]: compatible with fish history files:
+//!   ```text
+//!   - cmd: cargo build
+//!     when: 1634567890
+//!   - cmd: for i in 1 2; do\n  echo $i\ndone
+//!     when: 1634567890
+//!   ```
+//!
+//! [`History::import`] and [`History::export`] read and write the entries in
+//! a given format, e.g. to carry the history of a shell over.
+
+use fd_lock::FdLock;
 use log::{debug, warn};
+use std::borrow::Cow;
 use std::collections::vec_deque;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::convert::TryFrom;
 use std::fs::{File, OpenOptions};
-use std::io::SeekFrom;
-use std::iter::DoubleEndedIterator;
+use std::io::{BufRead, Seek, SeekFrom, Write};
+use std::iter::{DoubleEndedIterator, Zip};
 use std::ops::Index;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use super::Result;
-use crate::config::{Config, HistoryDuplicates};
+use crate::config::{Config, HistoryDuplicates, HistoryEviction, HistoryFormat};
 
 /// Search direction
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -22,21 +73,209 @@ pub enum Direction {
     Reverse,
 }
 
+impl Direction {
+    pub(crate) fn opposite(self) -> Self {
+        match self {
+            Direction::Forward => Direction::Reverse,
+            Direction::Reverse => Direction::Forward,
+        }
+    }
+}
+
+/// Decide whether an accepted line is recorded in the history (see
+/// [`Editor::add_history_filter`](crate::Editor::add_history_filter)).
+///
+/// Implemented by closures, e.g. to keep lines with secrets out of the
+/// history:
+///
+/// ```
+/// use rustyline::Editor;
+///
+/// let mut rl = Editor::<()>::new();
+/// rl.add_history_filter(|line: &str| !line.contains("password") && !line.contains("token"));
+/// assert!(!rl.add_history_entry("export token=1234"));
+/// assert!(rl.add_history_entry("ls"));
+/// ```
+pub trait HistoryFilter {
+    /// Return `false` to keep `line` out of the history.
+    fn accept(&self, line: &str) -> bool;
+}
+
+impl<F: Fn(&str) -> bool> HistoryFilter for F {
+    fn accept(&self, line: &str) -> bool {
+        self(line)
+    }
+}
+
+/// Storage of the history entries.
+///
+/// [`History`] (entries in memory, persisted in a file) is the default
+/// implementation: applications can provide their own (e.g. backed by a
+/// database or a network service) with
+/// [`Editor::with_history`](crate::Editor::with_history).
+///
+/// Entries are indexed from 0 (the oldest) to `len() - 1` (the most recent).
+pub trait HistoryStore {
+    /// Return the number of entries.
+    fn len(&self) -> usize;
+
+    /// Return true if there is no entry.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the entry at position `index`.
+    fn entry(&self, index: usize) -> Option<Cow<'_, str>>;
+
+    /// Return when the entry at position `index` was added, if known.
+    fn timestamp(&self, _index: usize) -> Option<SystemTime> {
+        None
+    }
+
+    /// Return the metadata of the entry at position `index` (`None` if the
+    /// store does not support metadata).
+    fn metadata(&self, _index: usize) -> Option<Cow<'_, EntryMetadata>> {
+        None
+    }
+
+    /// Set the `key` metadata of the entry at position `index`: return `false`
+    /// if there is no such entry or the store does not support metadata.
+    fn set_metadata(&mut self, _index: usize, _key: &str, _value: &str) -> bool {
+        false
+    }
+
+    /// Return the index of the nearest entry whose `key` metadata is `value`,
+    /// from `start` (inclusive) in the `dir` direction.
+    ///
+    /// For example, with the working directory recorded as `cwd`:
+    /// ```
+    /// use rustyline::history::{Direction, History, HistoryStore};
+    ///
+    /// let mut history = History::new();
+    /// history.add("make");
+    /// history.set_metadata(0, "cwd", "/src/rustyline");
+    /// history.add("ls");
+    /// history.set_metadata(1, "cwd", "/tmp");
+    /// let found = history.search_metadata("cwd", "/src/rustyline", 1, Direction::Reverse);
+    /// assert_eq!(Some(0), found);
+    /// ```
+    fn search_metadata(
+        &self,
+        key: &str,
+        value: &str,
+        start: usize,
+        dir: Direction,
+    ) -> Option<usize> {
+        let test = |index: usize| {
+            self.metadata(index)
+                .is_some_and(|metadata| metadata.get(key).map(String::as_str) == Some(value))
+        };
+        if start >= self.len() {
+            return None;
+        }
+        match dir {
+            Direction::Reverse => (0..=start).rev().find(|&index| test(index)),
+            Direction::Forward => (start..self.len()).find(|&index| test(index)),
+        }
+    }
+
+    /// Add a new entry (the store may ignore it, e.g. if it is a duplicate):
+    /// return `true` if it has been added.
+    fn add(&mut self, line: &str) -> bool;
+
+    /// Remove all the entries.
+    fn clear(&mut self);
+
+    /// Return a forward iterator over the entries.
+    fn entries(&self) -> Box<dyn DoubleEndedIterator<Item = Cow<'_, str>> + '_> {
+        Box::new((0..self.len()).filter_map(move |index| self.entry(index)))
+    }
+
+    /// Return the index of the nearest entry containing `term`, from `start`
+    /// (inclusive) in the `dir` direction (see [`History::search`]).
+    fn search(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
+        search_match(self, term, start, dir, |entry| entry.contains(term))
+    }
+
+    /// Return the index of the nearest entry starting with `term`, from
+    /// `start` (inclusive) in the `dir` direction.
+    fn starts_with(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
+        search_match(self, term, start, dir, |entry| entry.starts_with(term))
+    }
+
+    /// Load the entries persisted at `path`.
+    fn load(&mut self, path: &Path) -> Result<()>;
+
+    /// Persist all the entries at `path`.
+    fn save(&mut self, path: &Path) -> Result<()>;
+
+    /// Persist the entries added since the last `load` / `save` / `append` at
+    /// `path`.
+    fn append(&mut self, path: &Path) -> Result<()> {
+        self.save(path)
+    }
+
+    /// Persist the new entries at `path` and load the ones persisted by other
+    /// sessions.
+    fn sync(&mut self, path: &Path) -> Result<()> {
+        self.append(path)
+    }
+
+    /// Apply the history settings of `config` (`max_history_size`,
+    /// `history_duplicates`, ...) once they have been changed.
+    fn configure(&mut self, _config: &Config) {}
+}
+
+fn search_match<S, F>(store: &S, term: &str, start: usize, dir: Direction, test: F) -> Option<usize>
+where
+    S: HistoryStore + ?Sized,
+    F: Fn(&str) -> bool,
+{
+    if term.is_empty() || start >= store.len() {
+        return None;
+    }
+    match dir {
+        Direction::Reverse => (0..=start)
+            .rev()
+            .find(|&index| store.entry(index).is_some_and(|entry| test(&entry))),
+        Direction::Forward => {
+            (start..store.len()).find(|&index| store.entry(index).is_some_and(|entry| test(&entry)))
+        }
+    }
+}
+
+/// Key/value pairs attached to a history entry by the application (e.g. the
+/// session which added it or the working directory).
+pub type EntryMetadata = BTreeMap<String, String>;
+
 /// Current state of the history.
+///
+/// The entries [metadata](HistoryStore::metadata) are kept in memory only:
+/// none of the file formats persists them.
 #[derive(Default)]
 pub struct History {
     entries: VecDeque<String>,
+    /// When each entry was added, if known
+    timestamps: VecDeque<Option<SystemTime>>,
+    metadata: VecDeque<EntryMetadata>,
     max_len: usize,
+    /// Maximum total size of the entries
+    max_bytes: usize,
+    /// Total size of the entries
+    bytes: usize,
+    pub(crate) eviction: HistoryEviction,
     pub(crate) ignore_space: bool,
-    pub(crate) ignore_dups: bool,
+    pub(crate) duplicates: HistoryDuplicates,
+    pub(crate) format: HistoryFormat,
     /// Number of entries inputed by user and not saved yet
     new_entries: usize,
     /// last path used by either `load` or `save`
     path_info: Option<PathInfo>,
 }
 
-/// Last histo path, modified timestamp and size
-struct PathInfo(PathBuf, SystemTime, usize);
+/// Last histo path, modified timestamp, size (number of entries) and length
+/// (in bytes)
+struct PathInfo(PathBuf, SystemTime, usize, u64);
 
 impl History {
     // New multiline-aware history files start with `#V2\n` and have newlines
@@ -50,14 +289,23 @@ impl History {
 
     /// Customized constructor with:
     /// - `Config::max_history_size()`,
+    /// - `Config::history_max_bytes()`,
+    /// - `Config::history_eviction()`,
     /// - `Config::history_ignore_space()`,
-    /// - `Config::history_duplicates()`.
+    /// - `Config::history_duplicates()`,
+    /// - `Config::history_format()`.
     pub fn with_config(config: Config) -> Self {
         Self {
             entries: VecDeque::new(),
+            timestamps: VecDeque::new(),
+            metadata: VecDeque::new(),
             max_len: config.max_history_size(),
+            max_bytes: config.history_max_bytes(),
+            bytes: 0,
+            eviction: config.history_eviction(),
             ignore_space: config.history_ignore_space(),
-            ignore_dups: config.history_duplicates() == HistoryDuplicates::IgnoreConsecutive,
+            duplicates: config.history_duplicates(),
+            format: config.history_format(),
             new_entries: 0,
             path_info: None,
         }
@@ -68,14 +316,44 @@ impl History {
         self.entries.get(index)
     }
 
+    /// Return when the history entry at position `index` was added, if known
+    /// (entries loaded from a file without timestamps have none).
+    pub fn timestamp(&self, index: usize) -> Option<SystemTime> {
+        self.timestamps.get(index).copied().flatten()
+    }
+
+    /// Return the metadata of the history entry at position `index`.
+    pub fn metadata(&self, index: usize) -> Option<&EntryMetadata> {
+        self.metadata.get(index)
+    }
+
+    /// Return the metadata of the history entry at position `index` to update
+    /// it.
+    pub fn metadata_mut(&mut self, index: usize) -> Option<&mut EntryMetadata> {
+        self.metadata.get_mut(index)
+    }
+
     /// Return the last history entry (i.e. previous command)
     pub fn last(&self) -> Option<&String> {
         self.entries.back()
     }
 
-    /// Add a new entry in the history.
+    /// Add a new entry in the history, timestamped now.
     pub fn add<S: AsRef<str> + Into<String>>(&mut self, line: S) -> bool {
-        if self.max_len == 0 {
+        self.add_with_timestamp(line, Some(SystemTime::now()))
+    }
+
+    /// Add a new entry in the history with the given timestamp (`None` if
+    /// unknown).
+    ///
+    /// Entries are dropped according to `Config::history_eviction()` while
+    /// the history is full.
+    pub fn add_with_timestamp<S: AsRef<str> + Into<String>>(
+        &mut self,
+        line: S,
+        timestamp: Option<SystemTime>,
+    ) -> bool {
+        if self.max_len == 0 || line.as_ref().len() > self.max_bytes {
             return false;
         }
         if line.as_ref().is_empty()
@@ -88,21 +366,67 @@ impl History {
         {
             return false;
         }
-        if self.ignore_dups {
-            if let Some(s) = self.entries.back() {
-                if s == line.as_ref() {
-                    return false;
+        match self.duplicates {
+            HistoryDuplicates::IgnoreConsecutive => {
+                if let Some(s) = self.entries.back() {
+                    if s == line.as_ref() {
+                        return false;
+                    }
                 }
             }
+            HistoryDuplicates::EraseAll => {
+                while let Some(index) = self.entries.iter().rposition(|s| s == line.as_ref()) {
+                    self.remove(index);
+                }
+            }
+            HistoryDuplicates::AlwaysAdd => {}
         }
-        if self.entries.len() == self.max_len {
-            self.entries.pop_front();
-        }
-        self.entries.push_back(line.into());
+        let line = line.into();
+        self.bytes += line.len();
+        self.entries.push_back(line);
+        self.timestamps.push_back(timestamp);
+        self.metadata.push_back(EntryMetadata::new());
         self.new_entries = self.new_entries.saturating_add(1).min(self.len());
+        self.evict();
         true
     }
 
+    /// Drop entries while there are more than `max_len` or they take more than
+    /// `max_bytes`
+    fn evict(&mut self) {
+        while self.len() > self.max_len || self.bytes > self.max_bytes {
+            let index = match self.eviction {
+                HistoryEviction::DropOldestDuplicateFirst => self.oldest_duplicate().unwrap_or(0),
+                _ => 0,
+            };
+            self.remove(index);
+        }
+    }
+
+    /// Index of the oldest entry which is repeated later
+    fn oldest_duplicate(&self) -> Option<usize> {
+        let mut seen = HashSet::new();
+        let mut oldest = None;
+        for (i, entry) in self.entries.iter().enumerate().rev() {
+            if !seen.insert(entry.as_str()) {
+                oldest = Some(i);
+            }
+        }
+        oldest
+    }
+
+    fn remove(&mut self, index: usize) {
+        if let Some(entry) = self.entries.remove(index) {
+            self.timestamps.remove(index);
+            self.metadata.remove(index);
+            self.bytes -= entry.len();
+            if index >= self.len() + 1 - self.new_entries {
+                self.new_entries -= 1; // not saved yet
+            }
+        }
+        self.new_entries = self.new_entries.min(self.len());
+    }
+
     /// Return the number of entries in the history.
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -115,19 +439,27 @@ impl History {
 
     /// Set the maximum length for the history. This function can be called even
     /// if there is already some history, the function will make sure to retain
-    /// just the latest `len` elements if the new history length value is
-    /// smaller than the amount of items already inside the history.
+    /// just the latest `len` elements (or to drop the duplicates first,
+    /// depending on `Config::history_eviction()`) if the new history length
+    /// value is smaller than the amount of items already inside the history.
     ///
     /// Like [stifle_history](http://tiswww.case.edu/php/chet/readline/history.html#IDX11).
     pub fn set_max_len(&mut self, len: usize) {
         self.max_len = len;
-        if self.len() > len {
-            self.entries.drain(..self.len() - len);
-            self.new_entries = self.new_entries.min(len);
-        }
+        self.evict();
     }
 
-    /// Save the history in the specified file.
+    /// Set the maximum total size (in bytes) of the history entries, dropping
+    /// entries like [`History::set_max_len`] if needed.
+    pub fn set_max_bytes(&mut self, max_bytes: usize) {
+        self.max_bytes = max_bytes;
+        self.evict();
+    }
+
+    /// Save the history in the specified file (see the [module](self)
+    /// documentation for the format).
+    ///
+    /// The file is locked while it is written.
     // TODO history_truncate_file
     // https://tiswww.case.edu/php/chet/readline/history.html#IDX31
     pub fn save<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
@@ -135,61 +467,114 @@ impl History {
             return Ok(());
         }
         let path = path.as_ref();
-        let old_umask = umask();
-        let f = File::create(path);
-        restore_umask(old_umask);
-        let file = f?;
-        self.save_to(&file, false)?;
+        let mut lock = FdLock::new(open_for_update(path)?);
+        let mut file = lock.lock()?;
+        self.rewrite(&mut file)?;
         self.new_entries = 0;
-        self.update_path(path, self.len())
+        self.update_path(path, &file, self.len())
     }
 
-    fn save_to(&mut self, file: &File, append: bool) -> Result<()> {
-        use std::io::{BufWriter, Write};
+    /// Replace the content of `file` by the entries
+    fn rewrite(&mut self, file: &mut File) -> Result<()> {
+        file.seek(SeekFrom::Start(0))?;
+        self.save_to(file, false)?;
+        let len = file.stream_position()?;
+        file.set_len(len)?;
+        Ok(())
+    }
 
+    fn save_to(&mut self, file: &File, append: bool) -> Result<()> {
         fix_perm(file);
-        let mut wtr = BufWriter::new(file);
+        self.write_to(std::io::BufWriter::new(file), append)
+    }
+
+    /// Write the entries (only the new ones if `append`) in the configured
+    /// format
+    pub(crate) fn write_to<W: Write>(&mut self, mut wtr: W, append: bool) -> Result<()> {
         let first_new_entry = if append {
             self.entries.len().saturating_sub(self.new_entries)
         } else {
-            wtr.write_all(Self::FILE_VERSION_V2.as_bytes())?;
-            wtr.write_all(b"\n")?;
+            if self.format == HistoryFormat::Escaped {
+                wtr.write_all(Self::FILE_VERSION_V2.as_bytes())?;
+                wtr.write_all(b"\n")?;
+            }
             0
         };
-        for entry in self.entries.iter().skip(first_new_entry) {
-            let mut bytes = entry.as_bytes();
-            while let Some(i) = memchr::memchr2(b'\\', b'\n', bytes) {
-                wtr.write_all(&bytes[..i])?;
-                if bytes[i] == b'\n' {
-                    wtr.write_all(b"\\n")?; // escaped line feed
-                } else {
-                    debug_assert_eq!(bytes[i], b'\\');
-                    wtr.write_all(b"\\\\")?; // escaped backslash
+        self.write_entries(&mut wtr, self.format, first_new_entry)?;
+        // https://github.com/rust-lang/rust/issues/32677#issuecomment-204833485
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Write the entries from `first` in `format` (without the `#V2` header)
+    fn write_entries<W: Write>(
+        &self,
+        wtr: &mut W,
+        format: HistoryFormat,
+        first: usize,
+    ) -> Result<()> {
+        let now = SystemTime::now();
+        let secs = |timestamp: Option<SystemTime>| {
+            timestamp
+                .unwrap_or(now)
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        };
+        for (entry, timestamp) in self.iter_with_timestamps().skip(first) {
+            match format {
+                HistoryFormat::Escaped => {
+                    let mut bytes = entry.as_bytes();
+                    while let Some(i) = memchr::memchr2(b'\\', b'\n', bytes) {
+                        wtr.write_all(&bytes[..i])?;
+                        if bytes[i] == b'\n' {
+                            wtr.write_all(b"\\n")?; // escaped line feed
+                        } else {
+                            debug_assert_eq!(bytes[i], b'\\');
+                            wtr.write_all(b"\\\\")?; // escaped backslash
+                        }
+                        bytes = &bytes[i + 1..];
+                    }
+                    wtr.write_all(bytes)?; // remaining bytes with no \n or \
+                    wtr.write_all(b"\n")?;
+                }
+                HistoryFormat::Bash => {
+                    // entries with line feeds are delimited by timestamps
+                    writeln!(wtr, "#{}", secs(timestamp))?;
+                    wtr.write_all(entry.as_bytes())?;
+                    wtr.write_all(b"\n")?;
+                }
+                HistoryFormat::Zsh => {
+                    write!(wtr, ": {}:0;", secs(timestamp))?;
+                    // line feeds are preceded by a backslash
+                    wtr.write_all(&metafy(&entry.replace('\n', "\\\n")))?;
+                    wtr.write_all(b"\n")?;
+                }
+                HistoryFormat::Fish => {
+                    writeln!(wtr, "- cmd: {}", fish_escape(entry))?;
+                    writeln!(wtr, "  when: {}", secs(timestamp))?;
                 }
-                bytes = &bytes[i + 1..];
             }
-            wtr.write_all(bytes)?; // remaining bytes with no \n or \
-            wtr.write_all(b"\n")?;
         }
-        // https://github.com/rust-lang/rust/issues/32677#issuecomment-204833485
-        wtr.flush()?;
         Ok(())
     }
 
     /// Append new entries in the specified file.
+    ///
+    /// The file is locked while it is updated so that several processes can
+    /// append to the same file: it is rewritten with the entries of the
+    /// other processes then the new ones (instead of just appended) when it
+    /// has been modified since it was last loaded or saved and would grow
+    /// beyond the history size limits. See also [`History::sync`].
     // Like [append_history](http://tiswww.case.edu/php/chet/readline/history.html#IDX30).
     pub fn append<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
-        use fd_lock::FdLock;
-        use std::io::Seek;
-
         if self.is_empty() || self.new_entries == 0 {
             return Ok(());
         }
         let path = path.as_ref();
-        if !path.exists() || self.new_entries == self.max_len {
-            return self.save(path);
-        } else if self.can_just_append(path)? {
-            let file = OpenOptions::new().append(true).open(path)?;
+        let mut lock = FdLock::new(open_for_update(path)?);
+        let mut file = lock.lock()?;
+        if self.can_just_append(path, &file)? {
+            file.seek(SeekFrom::End(0))?;
             self.save_to(&file, true)?;
             let size = self
                 .path_info
@@ -198,117 +583,162 @@ impl History {
                 .2
                 .saturating_add(self.new_entries);
             self.new_entries = 0;
-            return self.update_path(path, size);
+            return self.update_path(path, &file, size);
         }
-        let file = OpenOptions::new().write(true).read(true).open(path)?;
-        let mut lock = FdLock::new(file);
-        let mut lock_guard = lock.lock()?;
         // we may need to truncate file before appending new entries
+        let mut merged = self.merge(&file)?;
+        merged.rewrite(&mut file)?;
+        self.new_entries = 0;
+        self.update_path(path, &file, merged.len())
+    }
+
+    /// Append new entries in the specified file like [`History::append`] and
+    /// load the entries appended by other processes since the file was last
+    /// loaded or saved: the history then matches the file content.
+    ///
+    /// Calling it after each line is accepted shares the history between
+    /// concurrent sessions (like zsh `SHARE_HISTORY`).
+    pub fn sync<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
+        let path = path.as_ref();
+        let mut lock = FdLock::new(open_for_update(path)?);
+        let mut file = lock.lock()?;
+        let mut merged = self.merge(&file)?;
+        if self.new_entries > 0 || !self.is_in_sync(path, &file)? {
+            merged.rewrite(&mut file)?;
+        }
+        self.entries = merged.entries;
+        self.timestamps = merged.timestamps;
+        self.metadata = merged.metadata;
+        self.bytes = merged.bytes;
+        self.new_entries = 0;
+        self.update_path(path, &file, self.len())
+    }
+
+    /// Entries of `file` followed by the new ones
+    fn merge(&self, file: &File) -> Result<Self> {
         let mut other = Self {
             entries: VecDeque::new(),
+            timestamps: VecDeque::new(),
+            metadata: VecDeque::new(),
             max_len: self.max_len,
+            max_bytes: self.max_bytes,
+            bytes: 0,
+            eviction: self.eviction,
             ignore_space: self.ignore_space,
-            ignore_dups: self.ignore_dups,
+            duplicates: self.duplicates,
+            format: self.format,
             new_entries: 0,
             path_info: None,
         };
-        other.load_from(&lock_guard)?;
+        other.load_from(file)?;
         let first_new_entry = self.entries.len().saturating_sub(self.new_entries);
-        for entry in self.entries.iter().skip(first_new_entry) {
-            other.add(entry);
+        for (index, (entry, timestamp)) in self.iter_with_timestamps().enumerate() {
+            if index >= first_new_entry && other.add_with_timestamp(entry, timestamp) {
+                *other.metadata.back_mut().unwrap() = self.metadata[index].clone();
+            }
         }
-        lock_guard.seek(SeekFrom::Start(0))?;
-        other.save_to(&lock_guard, false)?;
-        drop(lock_guard);
-        self.update_path(path, other.len())?;
-        self.new_entries = 0;
-        Ok(())
+        Ok(other)
     }
 
-    /// Load the history from the specified file.
+    /// Load the history from the specified file, whatever its
+    /// [format](self) is.
     ///
     /// # Errors
     /// Will return `Err` if path does not already exist or could not be read.
     pub fn load<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
         let path = path.as_ref();
-        let file = File::open(path)?;
+        let mut lock = FdLock::new(File::open(path)?);
+        let file = lock.lock()?;
         let len = self.len();
         if self.load_from(&file)? {
-            self.update_path(path, self.len() - len)
+            self.update_path(path, &file, self.len() - len)
         } else {
-            // discard old version on next save
+            // discard old version / other format on next save
             self.path_info = None;
             Ok(())
         }
     }
 
+    /// Return `true` if the file is in the configured format (new entries
+    /// can be appended to it)
     fn load_from(&mut self, file: &File) -> Result<bool> {
-        use std::io::{BufRead, BufReader};
-
-        let rdr = BufReader::new(file);
-        let mut lines = rdr.lines();
-        let mut v2 = false;
-        if let Some(first) = lines.next() {
-            let line = first?;
-            if line == Self::FILE_VERSION_V2 {
-                v2 = true;
-            } else {
-                self.add(line);
+        self.read_from(std::io::BufReader::new(file))
+    }
+
+    /// Add the entries read from `rdr`, whatever their format is: return
+    /// `true` if they are in the configured format
+    pub(crate) fn read_from<R: BufRead>(&mut self, mut rdr: R) -> Result<bool> {
+        let format = match detect_format(rdr.fill_buf()?) {
+            Some(format) => format,
+            // new entries can be appended unless a header is expected
+            None => return Ok(self.format != HistoryFormat::Escaped),
+        };
+        self.read_entries(rdr, format)?;
+        self.new_entries = 0; // TODO we may lost new entries if loaded lines < max_len
+        Ok(self.format == format)
+    }
+
+    /// Add the entries read from `rdr` in `format` and return how many have
+    /// been added
+    fn read_entries<R: BufRead>(&mut self, rdr: R, format: HistoryFormat) -> Result<usize> {
+        let mut added = 0;
+        let mut add = |entry: String, timestamp: Option<SystemTime>| {
+            if self.add_with_timestamp(entry, timestamp) {
+                added += 1;
             }
+        };
+        match format {
+            HistoryFormat::Escaped => read_escaped(rdr, &mut add)?,
+            HistoryFormat::Bash => read_bash(rdr, &mut add)?,
+            HistoryFormat::Zsh => read_zsh(rdr, &mut add)?,
+            HistoryFormat::Fish => read_fish(rdr, &mut add)?,
         }
-        for line in lines {
-            let mut line = line?;
-            if line.is_empty() {
-                continue;
-            }
-            if v2 {
-                let mut copy = None; // lazily copy line if unescaping is needed
-                let mut str = line.as_str();
-                while let Some(i) = str.find('\\') {
-                    if copy.is_none() {
-                        copy = Some(String::with_capacity(line.len()));
-                    }
-                    let s = copy.as_mut().unwrap();
-                    s.push_str(&str[..i]);
-                    let j = i + 1; // escaped char idx
-                    let b = if j < str.len() {
-                        str.as_bytes()[j]
-                    } else {
-                        0 // unexpected if History::save works properly
-                    };
-                    match b {
-                        b'n' => {
-                            s.push('\n'); // unescaped line feed
-                        }
-                        b'\\' => {
-                            s.push('\\'); // unescaped back slash
-                        }
-                        _ => {
-                            // only line feed and back slash should have been escaped
-                            warn!(target: "rustyline", "bad escaped line: {}", line);
-                            copy = None;
-                            break;
-                        }
-                    }
-                    str = &str[j + 1..];
-                }
-                if let Some(mut s) = copy {
-                    s.push_str(str); // remaining bytes with no escaped char
-                    line = s;
-                }
-            }
-            self.add(line); // TODO truncate to MAX_LINE
+        Ok(added)
+    }
+
+    /// Add the entries read from `rdr` in `format` (e.g. the history file of
+    /// a shell) and return how many have been added.
+    ///
+    /// Unlike [`History::load`], the entries are new ones: they are written
+    /// by the next [`History::save`] or [`History::append`].
+    ///
+    /// ```
+    /// use rustyline::history::History;
+    /// use rustyline::HistoryFormat;
+    ///
+    /// let zsh_history = ": 1634567890:0;cargo build\n: 1634567891:0;cargo test\n";
+    /// let mut history = History::new();
+    /// assert_eq!(2, history.import(zsh_history.as_bytes(), HistoryFormat::Zsh)?);
+    /// let mut fish_history = Vec::new();
+    /// history.export(&mut fish_history, HistoryFormat::Fish)?;
+    /// assert!(fish_history.starts_with(b"- cmd: cargo build\n  when: 1634567890\n"));
+    /// # Ok::<(), rustyline::error::ReadlineError>(())
+    /// ```
+    pub fn import<R: BufRead>(&mut self, rdr: R, format: HistoryFormat) -> Result<usize> {
+        self.read_entries(rdr, format)
+    }
+
+    /// Write all the entries to `wtr` in `format` (e.g. to carry them to a
+    /// shell history file).
+    pub fn export<W: Write>(&self, mut wtr: W, format: HistoryFormat) -> Result<()> {
+        if format == HistoryFormat::Escaped {
+            wtr.write_all(Self::FILE_VERSION_V2.as_bytes())?;
+            wtr.write_all(b"\n")?;
         }
-        self.new_entries = 0; // TODO we may lost new entries if loaded lines < max_len
-        Ok(v2)
+        self.write_entries(&mut wtr, format, 0)?;
+        wtr.flush()?;
+        Ok(())
     }
 
-    fn update_path(&mut self, path: &Path, size: usize) -> Result<()> {
-        let modified = File::open(&path)?.metadata()?.modified()?;
+    fn update_path(&mut self, path: &Path, file: &File, size: usize) -> Result<()> {
+        let metadata = file.metadata()?;
+        let modified = metadata.modified()?;
+        let len = metadata.len();
         if let Some(PathInfo(
             ref mut previous_path,
             ref mut previous_modified,
             ref mut previous_size,
+            ref mut previous_len,
         )) = self.path_info
         {
             if previous_path.as_path() != path {
@@ -316,40 +746,69 @@ impl History {
             }
             *previous_modified = modified;
             *previous_size = size;
+            *previous_len = len;
         } else {
-            self.path_info = Some(PathInfo(path.to_owned(), modified, size));
+            self.path_info = Some(PathInfo(path.to_owned(), modified, size, len));
         }
-        debug!(target: "rustyline", "PathInfo({:?}, {:?}, {})", path, modified, size);
+        debug!(target: "rustyline", "PathInfo({:?}, {:?}, {}, {})", path, modified, size, len);
         Ok(())
     }
 
-    fn can_just_append(&self, path: &Path) -> Result<bool> {
-        if let Some(PathInfo(ref previous_path, ref previous_modified, ref previous_size)) =
+    /// Tell if `file` has not been modified since it was last loaded or saved
+    fn is_in_sync(&self, path: &Path, file: &File) -> Result<bool> {
+        if let Some(PathInfo(ref previous_path, ref previous_modified, _, previous_len)) =
             self.path_info
         {
             if previous_path.as_path() != path {
-                debug!(target: "rustyline", "cannot append: {:?} <> {:?}", previous_path, path);
+                debug!(target: "rustyline", "not in sync: {:?} <> {:?}", previous_path, path);
                 return Ok(false);
             }
-            let modified = File::open(&path)?.metadata()?.modified()?;
-            if *previous_modified != modified
-                || self.max_len <= *previous_size
-                || self.max_len < (*previous_size).saturating_add(self.new_entries)
-            {
-                debug!(target: "rustyline", "cannot append: {:?} < {:?} or {} < {} + {}",
-                       previous_modified, modified, self.max_len, previous_size, self.new_entries);
-                Ok(false)
-            } else {
-                Ok(true)
+            let metadata = file.metadata()?;
+            let modified = metadata.modified()?;
+            if *previous_modified != modified || previous_len != metadata.len() {
+                debug!(target: "rustyline", "not in sync: {:?} < {:?} or {} <> {}",
+                       previous_modified, modified, previous_len, metadata.len());
+                return Ok(false);
             }
+            Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    fn can_just_append(&self, path: &Path, file: &File) -> Result<bool> {
+        if !self.is_in_sync(path, file)? {
+            return Ok(false);
+        }
+        let previous_size = self.path_info.as_ref().map_or(0, |info| info.2);
+        // the file is at least as big as its entries
+        let new_bytes: usize = self
+            .entries
+            .iter()
+            .skip(self.len() - self.new_entries)
+            .map(String::len)
+            .sum();
+        let bytes = usize::try_from(file.metadata()?.len())
+            .unwrap_or(usize::MAX)
+            .saturating_add(new_bytes);
+        if self.max_len <= previous_size
+            || self.max_len < previous_size.saturating_add(self.new_entries)
+            || self.max_bytes < bytes
+        {
+            debug!(target: "rustyline", "cannot append: {} < {} + {} or {} < {}",
+                   self.max_len, previous_size, self.new_entries, self.max_bytes, bytes);
+            Ok(false)
+        } else {
+            Ok(true)
+        }
+    }
+
     /// Clear history
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.timestamps.clear();
+        self.metadata.clear();
+        self.bytes = 0;
         self.new_entries = 0;
     }
 
@@ -400,6 +859,338 @@ impl History {
     pub fn iter(&self) -> Iter<'_> {
         Iter(self.entries.iter())
     }
+
+    /// Return a forward iterator over the entries and when they were added
+    /// (if known).
+    ///
+    /// ```
+    /// use rustyline::history::History;
+    /// use std::time::SystemTime;
+    ///
+    /// let mut history = History::new();
+    /// history.add("ls");
+    /// for (entry, timestamp) in history.iter_with_timestamps() {
+    ///     let ago = timestamp.and_then(|t| SystemTime::now().duration_since(t).ok());
+    ///     println!("{:?} {}", ago, entry);
+    /// }
+    /// ```
+    pub fn iter_with_timestamps(&self) -> TimestampedIter<'_> {
+        TimestampedIter(self.entries.iter().zip(self.timestamps.iter()))
+    }
+}
+
+impl HistoryStore for History {
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn entry(&self, index: usize) -> Option<Cow<'_, str>> {
+        self.get(index).map(|entry| Cow::Borrowed(entry.as_str()))
+    }
+
+    fn timestamp(&self, index: usize) -> Option<SystemTime> {
+        self.timestamp(index)
+    }
+
+    fn metadata(&self, index: usize) -> Option<Cow<'_, EntryMetadata>> {
+        self.metadata(index).map(Cow::Borrowed)
+    }
+
+    fn set_metadata(&mut self, index: usize, key: &str, value: &str) -> bool {
+        match self.metadata_mut(index) {
+            Some(metadata) => {
+                metadata.insert(key.to_owned(), value.to_owned());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn add(&mut self, line: &str) -> bool {
+        self.add(line)
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+
+    fn search(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
+        self.search(term, start, dir)
+    }
+
+    fn starts_with(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
+        self.starts_with(term, start, dir)
+    }
+
+    fn load(&mut self, path: &Path) -> Result<()> {
+        self.load(path)
+    }
+
+    fn save(&mut self, path: &Path) -> Result<()> {
+        self.save(path)
+    }
+
+    fn append(&mut self, path: &Path) -> Result<()> {
+        self.append(path)
+    }
+
+    fn sync(&mut self, path: &Path) -> Result<()> {
+        self.sync(path)
+    }
+
+    fn configure(&mut self, config: &Config) {
+        self.max_len = config.max_history_size();
+        self.max_bytes = config.history_max_bytes();
+        self.eviction = config.history_eviction();
+        self.ignore_space = config.history_ignore_space();
+        self.duplicates = config.history_duplicates();
+        self.format = config.history_format();
+        self.evict();
+    }
+}
+
+/// Format of the history file starting with `buf` (`None` if it is empty)
+fn detect_format(buf: &[u8]) -> Option<HistoryFormat> {
+    let first = buf.split(|&b| b == b'\n').next()?;
+    let first = first.strip_suffix(b"\r").unwrap_or(first);
+    Some(if buf.is_empty() {
+        return None;
+    } else if first == History::FILE_VERSION_V2.as_bytes() {
+        HistoryFormat::Escaped
+    } else if parse_zsh_header(first).is_some() {
+        HistoryFormat::Zsh
+    } else if first.starts_with(b"- cmd:") {
+        HistoryFormat::Fish
+    } else {
+        // with or without timestamps (old rustyline versions)
+        HistoryFormat::Bash
+    })
+}
+
+/// Read a `#V2` history file
+fn read_escaped<R: BufRead>(rdr: R, add: &mut dyn FnMut(String, Option<SystemTime>)) -> Result<()> {
+    for (i, line) in rdr.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || (i == 0 && line == History::FILE_VERSION_V2) {
+            continue;
+        }
+        add(unescape(line), None); // TODO truncate to MAX_LINE
+    }
+    Ok(())
+}
+
+/// Read a bash history file, with or without timestamps
+fn read_bash<R: BufRead>(rdr: R, add: &mut dyn FnMut(String, Option<SystemTime>)) -> Result<()> {
+    let mut entry: Option<(String, Option<SystemTime>)> = None;
+    // the current entry follows a timestamp: it spans until the next one
+    let mut multiline = false;
+    let mut timestamp = None;
+    for line in rdr.lines() {
+        let line = line?;
+        if let Some(secs) = parse_timestamp(&line) {
+            if let Some((entry, ts)) = entry.take() {
+                add(entry, ts);
+            }
+            timestamp = Some(UNIX_EPOCH + Duration::from_secs(secs));
+            continue;
+        }
+        match entry {
+            Some((ref mut entry, _)) if multiline && timestamp.is_none() => {
+                entry.push('\n');
+                entry.push_str(&line);
+            }
+            _ if line.is_empty() && timestamp.is_none() => {}
+            _ => {
+                multiline = timestamp.is_some();
+                if let Some((entry, ts)) = entry.replace((line, timestamp.take())) {
+                    add(entry, ts);
+                }
+            }
+        }
+    }
+    if let Some((entry, ts)) = entry {
+        add(entry, ts);
+    }
+    Ok(())
+}
+
+/// Read a zsh history file, extended (`: <start>:<elapsed>;<command>`
+/// lines) or not
+fn read_zsh<R: BufRead>(rdr: R, add: &mut dyn FnMut(String, Option<SystemTime>)) -> Result<()> {
+    let mut entry: Option<(Vec<u8>, Option<SystemTime>)> = None;
+    let mut push = |(bytes, ts): (Vec<u8>, Option<SystemTime>)| {
+        add(String::from_utf8_lossy(&bytes).into_owned(), ts);
+    };
+    for line in rdr.split(b'\n') {
+        let line = unmetafy(&line?);
+        // a line ending with a backslash goes on with the next one
+        if let Some((ref mut bytes, _)) = entry {
+            if bytes.last() == Some(&b'\\') {
+                bytes.pop();
+                bytes.push(b'\n');
+                bytes.extend_from_slice(&line);
+                continue;
+            }
+        }
+        if let Some(entry) = entry.take() {
+            push(entry);
+        }
+        entry = Some(match parse_zsh_header(&line) {
+            Some((secs, command)) => (
+                command.to_vec(),
+                Some(UNIX_EPOCH + Duration::from_secs(secs)),
+            ),
+            None => (line, None),
+        });
+    }
+    if let Some(entry) = entry {
+        push(entry);
+    }
+    Ok(())
+}
+
+/// Read a fish history file (`- cmd: <command>` records followed by their
+/// `when: <start>` field)
+fn read_fish<R: BufRead>(rdr: R, add: &mut dyn FnMut(String, Option<SystemTime>)) -> Result<()> {
+    let mut entry: Option<(String, Option<SystemTime>)> = None;
+    for line in rdr.lines() {
+        let line = line?;
+        if let Some(command) = line.strip_prefix("- cmd:") {
+            if let Some((entry, ts)) = entry.take() {
+                add(entry, ts);
+            }
+            entry = Some((fish_unescape(command.trim_start()), None));
+        } else if let Some(when) = line.trim_start().strip_prefix("when:") {
+            if let (Some((_, ts)), Ok(secs)) = (entry.as_mut(), when.trim().parse()) {
+                *ts = Some(UNIX_EPOCH + Duration::from_secs(secs));
+            }
+        } // `paths` are ignored
+    }
+    if let Some((entry, ts)) = entry {
+        add(entry, ts);
+    }
+    Ok(())
+}
+
+/// Start time and command of an extended zsh history line
+fn parse_zsh_header(line: &[u8]) -> Option<(u64, &[u8])> {
+    let line = line.strip_prefix(b": ")?;
+    let colon = line.iter().position(|&b| b == b':')?;
+    let semicolon = line.iter().position(|&b| b == b';')?;
+    let (start, elapsed) = (&line[..colon], line.get(colon + 1..semicolon)?);
+    if start.is_empty() || !start.iter().chain(elapsed).all(u8::is_ascii_digit) {
+        return None;
+    }
+    let secs = std::str::from_utf8(start).ok()?.parse().ok()?;
+    Some((secs, &line[semicolon + 1..]))
+}
+
+/// zsh history files escape some bytes (including UTF-8 ones) as `0x83`
+/// followed by the byte xored with 32
+const ZSH_META: u8 = 0x83;
+
+fn metafy(line: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(line.len());
+    for &b in line.as_bytes() {
+        if b == 0 || (ZSH_META..=0xa2).contains(&b) {
+            bytes.push(ZSH_META);
+            bytes.push(b ^ 32);
+        } else {
+            bytes.push(b);
+        }
+    }
+    bytes
+}
+
+fn unmetafy(line: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(line.len());
+    let mut iter = line.iter();
+    while let Some(&b) = iter.next() {
+        if b == ZSH_META {
+            if let Some(&b) = iter.next() {
+                bytes.push(b ^ 32);
+            }
+        } else {
+            bytes.push(b);
+        }
+    }
+    bytes
+}
+
+/// Escape backslashes and line feeds like fish
+fn fish_escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn fish_unescape(command: &str) -> String {
+    let mut unescaped = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('\\') => unescaped.push('\\'),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Seconds since the epoch of a bash timestamp line: `#` followed by digits
+fn parse_timestamp(line: &str) -> Option<u64> {
+    let digits = line.strip_prefix('#')?;
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+/// Unescape line feeds and backslashes of a `#V2` history file line
+fn unescape(line: String) -> String {
+    let mut copy = None; // lazily copy line if unescaping is needed
+    let mut str = line.as_str();
+    while let Some(i) = str.find('\\') {
+        if copy.is_none() {
+            copy = Some(String::with_capacity(line.len()));
+        }
+        let s = copy.as_mut().unwrap();
+        s.push_str(&str[..i]);
+        let j = i + 1; // escaped char idx
+        let b = if j < str.len() {
+            str.as_bytes()[j]
+        } else {
+            0 // unexpected if History::save works properly
+        };
+        match b {
+            b'n' => {
+                s.push('\n'); // unescaped line feed
+            }
+            b'\\' => {
+                s.push('\\'); // unescaped back slash
+            }
+            _ => {
+                // only line feed and back slash should have been escaped
+                warn!(target: "rustyline", "bad escaped line: {}", line);
+                copy = None;
+                break;
+            }
+        }
+        str = &str[j + 1..];
+    }
+    match copy {
+        Some(mut s) => {
+            s.push_str(str); // remaining bytes with no escaped char
+            s
+        }
+        None => line,
+    }
 }
 
 impl Index<usize> for History {
@@ -434,12 +1225,50 @@ impl<'a> Iterator for Iter<'a> {
     }
 }
 
+/// History iterator over the entries and their timestamps.
+pub struct TimestampedIter<'a>(
+    Zip<vec_deque::Iter<'a, String>, vec_deque::Iter<'a, Option<SystemTime>>>,
+);
+
+impl<'a> Iterator for TimestampedIter<'a> {
+    type Item = (&'a String, Option<SystemTime>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(entry, timestamp)| (entry, *timestamp))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for TimestampedIter<'a> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0
+            .next_back()
+            .map(|(entry, timestamp)| (entry, *timestamp))
+    }
+}
+
 impl<'a> DoubleEndedIterator for Iter<'a> {
     fn next_back(&mut self) -> Option<&'a String> {
         self.0.next_back()
     }
 }
 
+/// Open (or create, with user only permissions) `path` without truncating it
+pub(crate) fn open_for_update(path: &Path) -> Result<File> {
+    let old_umask = umask();
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path);
+    restore_umask(old_umask);
+    Ok(f?)
+}
+
 cfg_if::cfg_if! {
     if #[cfg(any(windows, target_arch = "wasm32"))] {
         fn umask() -> u16 {
@@ -448,7 +1277,7 @@ cfg_if::cfg_if! {
 
         fn restore_umask(_: u16) {}
 
-        fn fix_perm(_: &File) {}
+        pub(crate) fn fix_perm(_: &File) {}
     } else if #[cfg(unix)] {
         fn umask() -> libc::mode_t {
             unsafe { libc::umask(libc::S_IXUSR | libc::S_IRWXG | libc::S_IRWXO) }
@@ -460,7 +1289,7 @@ cfg_if::cfg_if! {
             }
         }
 
-        fn fix_perm(file: &File) {
+        pub(crate) fn fix_perm(file: &File) {
             use std::os::unix::io::AsRawFd;
             unsafe {
                 libc::fchmod(file.as_raw_fd(), libc::S_IRUSR | libc::S_IWUSR);
@@ -471,9 +1300,10 @@ cfg_if::cfg_if! {
 
 #[cfg(test)]
 mod tests {
-    use super::{Direction, History};
-    use crate::config::Config;
+    use super::{Direction, History, HistoryStore};
+    use crate::config::{Config, HistoryDuplicates, HistoryEviction, HistoryFormat};
     use crate::Result;
+    use std::time::{Duration, UNIX_EPOCH};
 
     fn init() -> History {
         let mut history = History::new();
@@ -492,7 +1322,7 @@ mod tests {
     #[test]
     fn add() {
         let config = Config::builder().history_ignore_space(true).build();
-        let mut history = History::with_config(config);
+        let mut history = History::with_config(config.clone());
         assert_eq!(config.max_history_size(), history.max_len);
         assert!(history.add("line1"));
         assert!(history.add("line2"));
@@ -501,14 +1331,123 @@ mod tests {
         assert!(!history.add(" line3"));
     }
 
+    #[test]
+    fn erase_dups() {
+        let config = Config::builder()
+            .history_duplicates(HistoryDuplicates::EraseAll)
+            .build();
+        let mut history = History::with_config(config);
+        assert!(history.add("ls"));
+        assert!(history.add("pwd"));
+        assert!(history.add("ls"));
+        assert!(history.add("ls"));
+        assert_eq!(history.entries, ["pwd", "ls"]);
+        assert_eq!(history.timestamps.len(), 2);
+        assert_eq!(5, history.bytes);
+    }
+
+    #[test]
+    fn history_control() {
+        let config = Config::builder()
+            .history_control("ignorespace:erasedups")
+            .build();
+        assert_eq!(HistoryDuplicates::EraseAll, config.history_duplicates());
+        assert!(config.history_ignore_space());
+        let config = Config::builder().history_control("ignoreboth").build();
+        assert_eq!(
+            HistoryDuplicates::IgnoreConsecutive,
+            config.history_duplicates()
+        );
+        assert!(config.history_ignore_space());
+        let config = Config::builder().history_control("").build();
+        assert_eq!(HistoryDuplicates::AlwaysAdd, config.history_duplicates());
+        assert!(!config.history_ignore_space());
+        let mut history = History::with_config(config);
+        assert!(history.add(" ls"));
+        assert!(history.add(" ls"));
+    }
+
     #[test]
     fn set_max_len() {
         let mut history = init();
         history.set_max_len(1);
         assert_eq!(1, history.entries.len());
+        assert_eq!(1, history.timestamps.len());
         assert_eq!(Some(&"line3".to_owned()), history.last());
     }
 
+    #[test]
+    fn max_bytes() {
+        let mut history = init();
+        assert_eq!(15, history.bytes);
+        history.set_max_bytes(12);
+        assert_eq!(history.entries, ["line2", "line3"]);
+        assert!(!history.add("line4 is too long"));
+        assert!(history.add("l4"));
+        assert_eq!(history.entries, ["line2", "line3", "l4"]);
+        assert!(history.add("line5"));
+        assert_eq!(history.entries, ["line3", "l4", "line5"]);
+        assert_eq!(12, history.bytes);
+        history.clear();
+        assert_eq!(0, history.bytes);
+    }
+
+    #[test]
+    fn evict_duplicates_first() {
+        let config = Config::builder()
+            .max_history_size(3)
+            .history_eviction(HistoryEviction::DropOldestDuplicateFirst)
+            .build();
+        let mut history = History::with_config(config);
+        assert!(history.add("ls"));
+        assert!(history.add("make"));
+        assert!(history.add("ls"));
+        assert!(history.add("pwd"));
+        assert_eq!(history.entries, ["make", "ls", "pwd"]);
+        assert_eq!(3, history.new_entries);
+        assert!(history.add("cd"));
+        assert_eq!(history.entries, ["ls", "pwd", "cd"]);
+        history.set_max_len(2);
+        assert_eq!(history.entries, ["pwd", "cd"]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn append_max_bytes() -> Result<()> {
+        let config = Config::builder().history_max_bytes(12).build();
+        let mut history = History::with_config(config.clone());
+        assert!(history.add("line1"));
+        let tf = tempfile::NamedTempFile::new()?;
+        history.append(tf.path())?;
+        assert!(history.add("line2"));
+        history.append(tf.path())?;
+        assert!(history.add("line3"));
+        // file rewritten without the oldest entry
+        history.append(tf.path())?;
+
+        let mut history2 = History::new();
+        history2.load(tf.path())?;
+        assert_eq!(history2.entries, ["line2", "line3"]);
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn timestamps() {
+        let mut history = History::new();
+        history.set_max_len(2);
+        let timestamp = UNIX_EPOCH + Duration::from_secs(1);
+        assert!(history.add_with_timestamp("line1", None));
+        assert!(history.add_with_timestamp("line2", Some(timestamp)));
+        assert!(history.add("line3"));
+        assert_eq!(Some(timestamp), history.timestamp(0));
+        assert!(history.timestamp(1).is_some());
+        assert_eq!(None, history.timestamp(2));
+        let entries: Vec<_> = history.iter_with_timestamps().rev().collect();
+        assert_eq!((&"line3".to_owned(), history.timestamp(1)), entries[0]);
+        assert_eq!((&"line2".to_owned(), Some(timestamp)), entries[1]);
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
     fn save() -> Result<()> {
@@ -563,6 +1502,163 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn save_bash() -> Result<()> {
+        let config = Config::builder()
+            .history_format(HistoryFormat::Bash)
+            .build();
+        let mut history = History::with_config(config.clone());
+        let timestamp = UNIX_EPOCH + Duration::from_secs(1634567890);
+        assert!(history.add_with_timestamp("line0", Some(timestamp)));
+        assert!(history.add("line1"));
+        assert!(history.add("for i in 1 2; do\n  echo $i\n\ndone"));
+        assert!(history.add("line3 \\n"));
+        let tf = tempfile::NamedTempFile::new()?;
+
+        history.save(tf.path())?;
+        let content = std::fs::read_to_string(tf.path())?;
+        assert_eq!(4, content.lines().filter(|l| l.starts_with('#')).count());
+        assert!(content.starts_with("#1634567890\nline0\n"));
+        let mut history2 = History::with_config(config);
+        history2.load(tf.path())?;
+        assert_eq!(history.entries, history2.entries);
+        // with a one second precision
+        let timestamps = |h: &History| -> Vec<_> {
+            h.iter_with_timestamps()
+                .map(|(_, t)| t.unwrap().duration_since(UNIX_EPOCH).unwrap().as_secs())
+                .collect()
+        };
+        assert_eq!(timestamps(&history), timestamps(&history2));
+        // readable whatever the configured format is
+        let mut history3 = History::new();
+        history3.load(tf.path())?;
+        assert_eq!(history.entries, history3.entries);
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn load_bash() -> Result<()> {
+        use std::io::Write;
+        let tf = tempfile::NamedTempFile::new()?;
+        {
+            let mut file = std::fs::File::create(tf.path())?;
+            // without timestamps, then with them (`HISTTIMEFORMAT` set later)
+            file.write_all(
+                b"ls\n\ncd /tmp\n#1634567890\nif true\nthen\n\n  pwd\nfi\n#1634567891\ntrue\n",
+            )?;
+            file.flush()?;
+        }
+        let mut history = History::new();
+        history.load(tf.path())?;
+        assert_eq!(
+            history.entries,
+            ["ls", "cd /tmp", "if true\nthen\n\n  pwd\nfi", "true"]
+        );
+        assert_eq!(None, history.timestamp(1));
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_secs(1634567890)),
+            history.timestamp(2)
+        );
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_secs(1634567891)),
+            history.timestamp(3)
+        );
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn import_zsh() -> Result<()> {
+        let mut history = History::new();
+        let content = b": 1634567890:0;ls\nmake\n: 1634567891:2;if true; then\\\n  pwd\\\nfi\n: 1634567892:0;echo \xc3\x83\x80\n";
+        assert_eq!(4, history.import(&content[..], HistoryFormat::Zsh)?);
+        assert_eq!(
+            history.entries,
+            ["ls", "make", "if true; then\n  pwd\nfi", "echo \u{e0}"]
+        );
+        assert_eq!(None, history.timestamp(1));
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_secs(1634567891)),
+            history.timestamp(2)
+        );
+        // imported entries are new ones
+        assert_eq!(4, history.new_entries);
+        Ok(())
+    }
+
+    #[test]
+    fn import_fish() -> Result<()> {
+        let mut history = History::new();
+        let content = "- cmd: ls\n  when: 1634567890\n- cmd: cat a\\\\b\\nc\n  when: 1634567891\n  paths:\n    - a\\b\n";
+        assert_eq!(2, history.import(content.as_bytes(), HistoryFormat::Fish)?);
+        assert_eq!(history.entries, ["ls", "cat a\\b\nc"]);
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_secs(1634567891)),
+            history.timestamp(1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn export() -> Result<()> {
+        let mut history = History::new();
+        let timestamp = Some(UNIX_EPOCH + Duration::from_secs(1634567890));
+        for entry in &["ls", "for i in 1 2; do\n  echo \\$i\ndone", "echo \u{e0}"] {
+            history.add_with_timestamp(*entry, timestamp);
+        }
+        for format in &[
+            HistoryFormat::Escaped,
+            HistoryFormat::Bash,
+            HistoryFormat::Zsh,
+            HistoryFormat::Fish,
+        ] {
+            let mut content = Vec::new();
+            history.export(&mut content, *format)?;
+            let mut imported = History::new();
+            assert_eq!(3, imported.import(&content[..], *format)?);
+            assert_eq!(history.entries, imported.entries, "{:?}", format);
+            if *format != HistoryFormat::Escaped {
+                assert_eq!(timestamp, imported.timestamp(2), "{:?}", format);
+            }
+            // files are read whatever their format is
+            let mut loaded = History::new();
+            assert_eq!(
+                *format == HistoryFormat::Escaped,
+                loaded.read_from(&content[..])?
+            );
+            assert_eq!(history.entries, loaded.entries, "{:?}", format);
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn append_other_format() -> Result<()> {
+        let mut history = init();
+        let tf = tempfile::NamedTempFile::new()?;
+        history.save(tf.path())?;
+
+        let config = Config::builder()
+            .history_format(HistoryFormat::Bash)
+            .build();
+        let mut history2 = History::with_config(config);
+        history2.load(tf.path())?;
+        assert!(history2.add("line\n4"));
+        // the file is rewritten in the configured format
+        history2.append(tf.path())?;
+        let content = std::fs::read_to_string(tf.path())?;
+        assert!(content.starts_with('#') && !content.starts_with("#V2"));
+
+        let mut history3 = History::new();
+        history3.load(tf.path())?;
+        assert_eq!(history3.entries, ["line1", "line2", "line3", "line\n4"]);
+        tf.close()?;
+        Ok(())
+    }
+
     #[test]
     #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
     fn append() -> Result<()> {
@@ -587,6 +1683,120 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn append_concurrently() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        let config = Config::builder().max_history_size(1000).build();
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let path = tf.path().to_owned();
+                let config = config.clone();
+                std::thread::spawn(move || -> Result<()> {
+                    let mut history = History::with_config(config);
+                    for j in 0..25 {
+                        assert!(history.add(format!("{} {}", i, j)));
+                        history.append(&path)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap()?;
+        }
+        let mut history = History::with_config(config);
+        history.load(tf.path())?;
+        assert_eq!(100, history.len());
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn sync() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        let mut history1 = History::new();
+        let mut history2 = History::new();
+        assert!(history1.add("line1"));
+        history1.sync(tf.path())?;
+        assert!(history2.add("line2"));
+        history2.sync(tf.path())?;
+        assert_eq!(history2.entries, ["line1", "line2"]);
+        assert!(history1.add("line3"));
+        history1.sync(tf.path())?;
+        assert_eq!(history1.entries, ["line1", "line2", "line3"]);
+        // nothing new
+        history2.sync(tf.path())?;
+        assert_eq!(history1.entries, history2.entries);
+
+        let mut history3 = History::new();
+        history3.load(tf.path())?;
+        assert_eq!(history1.entries, history3.entries);
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn metadata() {
+        let mut history = init();
+        assert!(history.set_metadata(0, "cwd", "/tmp"));
+        assert!(!history.set_metadata(3, "cwd", "/tmp"));
+        assert_eq!(
+            Some("/tmp"),
+            history.metadata(0).unwrap().get("cwd").map(String::as_str)
+        );
+        assert_eq!(
+            Some(0),
+            history.search_metadata("cwd", "/tmp", 2, Direction::Reverse)
+        );
+        assert_eq!(
+            None,
+            history.search_metadata("cwd", "/tmp", 1, Direction::Forward)
+        );
+        history.duplicates = HistoryDuplicates::EraseAll;
+        assert!(history.add("line1")); // metadata dropped with the entry
+        assert_eq!(
+            None,
+            history.search_metadata("cwd", "/tmp", 2, Direction::Reverse)
+        );
+        assert!(history.metadata(2).unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn sync_metadata() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        let mut history = History::new();
+        assert!(history.add("line1"));
+        assert!(history.set_metadata(0, "session", "1"));
+        history.sync(tf.path())?;
+        assert_eq!(
+            Some("1"),
+            history
+                .metadata(0)
+                .unwrap()
+                .get("session")
+                .map(String::as_str)
+        );
+        tf.close()?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // unsupported operation: `getcwd` not available when isolation is enabled
+    fn save_truncates() -> Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        let mut history = init();
+        history.save(tf.path())?;
+        let mut history2 = History::new();
+        assert!(history2.add("line"));
+        history2.save(tf.path())?;
+        assert_eq!("#V2\nline\n", std::fs::read_to_string(tf.path())?);
+        tf.close()?;
+        Ok(())
+    }
+
     #[test]
     fn search() {
         let history = init();
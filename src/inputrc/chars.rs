@@ -0,0 +1,718 @@
+//! Key names and key sequences used in inputrc files
+//!
+//! [`parse_char_name`] / [`key_name`] and [`unescape_sequence`] /
+//! [`escape_sequence`] are inverse of each other (modulo
+//! [`KeyEvent::normalize`]) for keys that can be represented in an inputrc
+//! file. So are [`unescape_macro`] / [`escape_macro`] for macro bodies.
+use std::iter::Peekable;
+
+use super::strip_prefix_ignore_case;
+use crate::keys::{KeyCode as K, KeyEvent as E, KeyEvent, Modifiers as M};
+
+/// Modifier prefixes of key names
+const MODIFIERS: &[(&str, M)] = &[
+    ("Control-", M::CTRL),
+    ("Ctrl-", M::CTRL),
+    ("C-", M::CTRL),
+    ("Meta-", M::ALT),
+    ("Alt-", M::ALT),
+    ("M-", M::ALT),
+    ("Shift-", M::SHIFT),
+    ("S-", M::SHIFT),
+    ("Keypad-", M::KEYPAD),
+];
+
+/// Parse a key name like `Control-u`, `M-DEL`, `Control-Meta-Left`, `F1` or
+/// `a` (as used by `keyname: function-name` bindings).
+pub fn parse_char_name(name: &str) -> Option<KeyEvent> {
+    let mut mods = M::NONE;
+    let mut name = name;
+    while let Some((rest, m)) = MODIFIERS
+        .iter()
+        .find_map(|(prefix, m)| strip_prefix_ignore_case(name, prefix).map(|rest| (rest, *m)))
+    {
+        mods |= m;
+        name = rest;
+    }
+    if name.starts_with('\\') {
+        // `\e[A`, `\C-a`, `\\`, ...
+        return match unescape_sequence(name)?.as_slice() {
+            [E(code, m)] => Some(E(*code, *m | mods)),
+            _ => None,
+        };
+    }
+    let key = match name.to_ascii_uppercase().as_str() {
+        "DEL" | "RUBOUT" => E(K::Backspace, mods),
+        "ESC" | "ESCAPE" => E(K::Esc, mods),
+        "LFD" | "NEWLINE" => E(K::Char('J'), mods | M::CTRL),
+        "RET" | "RETURN" => E(K::Enter, mods),
+        "SPC" | "SPACE" => E(K::Char(' '), mods),
+        "TAB" => E(K::Tab, mods),
+        "BACKTAB" => E(K::BackTab, mods),
+        "DELETE" => E(K::Delete, mods),
+        "DOWN" => E(K::Down, mods),
+        "END" => E(K::End, mods),
+        "FOCUSGAINED" => E(K::FocusGained, mods),
+        "FOCUSLOST" => E(K::FocusLost, mods),
+        "HOME" => E(K::Home, mods),
+        "INSERT" => E(K::Insert, mods),
+        "LEFT" => E(K::Left, mods),
+        "PAGEDOWN" => E(K::PageDown, mods),
+        "PAGEUP" => E(K::PageUp, mods),
+        "RIGHT" => E(K::Right, mods),
+        "UP" => E(K::Up, mods),
+        upper => match upper.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n @ 1..=20) => E(K::F(n), mods),
+            _ => {
+                let mut chars = name.chars();
+                let c = chars.next()?;
+                if chars.next().is_some() {
+                    return None;
+                }
+                E::new(c, mods)
+            }
+        },
+    };
+    Some(key)
+}
+
+/// Parse the content of a quoted key sequence like `\C-x\C-r` or `\ef`
+/// (as used by `"keyseq": function-name` bindings).
+pub fn unescape_sequence(seq: &str) -> Option<Vec<KeyEvent>> {
+    let mut keys = Vec::new();
+    let mut chars = seq.chars().peekable();
+    let mut mods = M::NONE;
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next()? {
+                'C' if chars.peek() == Some(&'-') => {
+                    chars.next();
+                    mods |= M::CTRL;
+                    continue;
+                }
+                'M' if chars.peek() == Some(&'-') => {
+                    chars.next();
+                    mods |= M::ALT;
+                    continue;
+                }
+                'e' => {
+                    if mods.is_empty() {
+                        // `\e[A`, `\eOP`, ... as sent by terminals
+                        let mut lookahead = chars.clone();
+                        if let Some(key) = escape_sequence_key(&mut lookahead) {
+                            chars = lookahead;
+                            keys.push(key);
+                            continue;
+                        }
+                        // ESC followed by a key is how terminals send Meta-key
+                        if chars.peek().is_some() {
+                            mods = M::ALT;
+                            continue;
+                        }
+                    }
+                    '\x1b'
+                }
+                c => unescape_char(c, &mut chars)?,
+            }
+        } else {
+            c
+        };
+        keys.push(E::new(c, mods));
+        mods = M::NONE;
+    }
+    if !mods.is_empty() {
+        // dangling `\C-` or `\M-`
+        return None;
+    }
+    Some(keys)
+}
+
+/// Decode the character escaped by `\\c` (`chars` being positioned after `c`)
+fn unescape_char<I>(c: char, chars: &mut Peekable<I>) -> Option<char>
+where
+    I: Iterator<Item = char>,
+{
+    let c = match c {
+        'a' => '\x07',
+        'b' => '\x08',
+        'd' => '\x7f',
+        'f' => '\x0c',
+        'n' => '\n',
+        'r' => '\r',
+        't' => '\t',
+        'v' => '\x0b',
+        'x' => {
+            let mut code = 0;
+            let mut digits = 0;
+            while let Some(d) = chars.peek().and_then(|c| c.to_digit(16)) {
+                chars.next();
+                code = code * 16 + d;
+                digits += 1;
+                if digits == 2 {
+                    break;
+                }
+            }
+            if digits == 0 {
+                return None;
+            }
+            std::char::from_u32(code)?
+        }
+        d @ '0'..='7' => {
+            let mut code = d.to_digit(8).unwrap();
+            for _ in 0..2 {
+                match chars.peek().and_then(|c| c.to_digit(8)) {
+                    Some(d) => {
+                        chars.next();
+                        code = code * 8 + d;
+                    }
+                    None => break,
+                }
+            }
+            std::char::from_u32(code)?
+        }
+        c => c, // `\\`, `\"`, `\'`, ...
+    };
+    Some(c)
+}
+
+/// Parse the content of a quoted macro like `reload-config\n` (as used by
+/// `"keyseq": "text"` bindings).
+///
+/// `\C-x` is decoded as the corresponding control character and `\M-x` as
+/// ESC followed by `x`.
+pub fn unescape_macro(text: &str) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut mods = M::NONE;
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next()? {
+                'C' if chars.peek() == Some(&'-') => {
+                    chars.next();
+                    mods |= M::CTRL;
+                    continue;
+                }
+                'M' if chars.peek() == Some(&'-') => {
+                    chars.next();
+                    mods |= M::ALT;
+                    continue;
+                }
+                'e' => '\x1b',
+                c => unescape_char(c, &mut chars)?,
+            }
+        } else {
+            c
+        };
+        if mods.contains(M::ALT) {
+            result.push('\x1b');
+        }
+        if mods.contains(M::CTRL) {
+            result.push(control(c)?);
+        } else {
+            result.push(c);
+        }
+        mods = M::NONE;
+    }
+    if !mods.is_empty() {
+        // dangling `\C-` or `\M-`
+        return None;
+    }
+    Some(result)
+}
+
+/// Control character corresponding to `c` (`?` being DEL)
+fn control(c: char) -> Option<char> {
+    match c {
+        '?' => Some('\x7f'),
+        '@'..='_' | 'a'..='z' => Some(((c as u8).to_ascii_uppercase() & 0x1f) as char),
+        _ => None,
+    }
+}
+
+/// Escape `text` so that it can be used as a quoted macro (inverse of
+/// [`unescape_macro`]).
+pub fn escape_macro(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\x1b' => result.push_str("\\e"),
+            '\x7f' => result.push_str("\\d"),
+            '\x01'..='\x1a' => {
+                result.push_str("\\C-");
+                result.push(((c as u8) | 0x60) as char);
+            }
+            '\0'..='\x1f' => result.push_str(&format!("\\x{:02x}", c as u8)),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Name of `key` like `Control-Meta-Left`, `F1` or `a`
+/// (inverse of [`parse_char_name`]).
+pub fn key_name(key: KeyEvent) -> Option<String> {
+    let E(code, mods) = KeyEvent::normalize(key);
+    let mut name = String::new();
+    if mods.contains(M::CTRL) {
+        name.push_str("Control-");
+    }
+    if mods.contains(M::ALT) {
+        name.push_str("Meta-");
+    }
+    if mods.contains(M::SHIFT) {
+        name.push_str("Shift-");
+    }
+    if mods.contains(M::KEYPAD) {
+        name.push_str("Keypad-");
+    }
+    match code {
+        K::Backspace => name.push_str("Rubout"),
+        K::BackTab => name.push_str("BackTab"),
+        K::Char(' ') => name.push_str("SPC"),
+        K::Char('\\') => name.push_str("\\\\"),
+        K::Char(c) if mods.contains(M::CTRL) => name.push(c.to_ascii_lowercase()),
+        K::Char(c) => name.push(c),
+        K::Delete => name.push_str("Delete"),
+        K::Down => name.push_str("Down"),
+        K::End => name.push_str("End"),
+        K::Enter => name.push_str("RET"),
+        K::Esc => name.push_str("ESC"),
+        K::F(n @ 1..=20) => name.push_str(&format!("F{}", n)),
+        K::FocusGained => name.push_str("FocusGained"),
+        K::FocusLost => name.push_str("FocusLost"),
+        K::Home => name.push_str("Home"),
+        K::Insert => name.push_str("Insert"),
+        K::Left => name.push_str("Left"),
+        K::PageDown => name.push_str("PageDown"),
+        K::PageUp => name.push_str("PageUp"),
+        K::Right => name.push_str("Right"),
+        K::Tab => name.push_str("TAB"),
+        K::Up => name.push_str("Up"),
+        _ => return None,
+    }
+    Some(name)
+}
+
+/// Format `keys` as the content of a quoted key sequence like `\C-x\C-r`
+/// (inverse of [`unescape_sequence`]).
+pub fn escape_sequence(keys: &[KeyEvent]) -> Option<String> {
+    let mut seq = String::new();
+    for (i, key) in keys.iter().enumerate() {
+        let E(code, mods) = KeyEvent::normalize(*key);
+        if mods.contains(M::KEYPAD) {
+            seq.push_str(&ss3_sequence(code, mods)?);
+            continue;
+        }
+        if let Some(csi) = csi_sequence(code, mods) {
+            seq.push_str(&csi);
+            continue;
+        }
+        if mods.contains(M::SHIFT) {
+            return None;
+        }
+        if mods.contains(M::CTRL) {
+            seq.push_str("\\C-");
+        }
+        if mods.contains(M::ALT) {
+            seq.push_str("\\M-");
+        }
+        match code {
+            K::Backspace => seq.push_str("\\d"),
+            K::Char('\\') => seq.push_str("\\\\"),
+            K::Char('"') => seq.push_str("\\\""),
+            K::Char(c) if mods.contains(M::CTRL) => seq.push(c.to_ascii_lowercase()),
+            K::Char(c) => seq.push(c),
+            K::Enter => seq.push_str("\\r"),
+            // `\e` followed by a key would be read as Meta-key
+            K::Esc if mods.is_empty() && i + 1 < keys.len() => seq.push_str("\\033"),
+            K::Esc => seq.push_str("\\e"),
+            K::Tab => seq.push_str("\\t"),
+            _ => return None,
+        }
+    }
+    Some(seq)
+}
+
+/// Sequence sent by (xterm compatible) terminals for keys which are not
+/// characters
+fn csi_sequence(code: K, mods: M) -> Option<String> {
+    let (param, last) = match code {
+        K::BackTab => ("1", 'Z'),
+        K::Delete => ("3", '~'),
+        K::Down => ("1", 'B'),
+        K::End => ("1", 'F'),
+        K::F(n @ 1..=4) => ("1", (b'P' + n - 1) as char),
+        K::F(n @ 5..=20) => {
+            let code = match n {
+                5 => 15,
+                6..=10 => n + 11,
+                11..=14 => n + 12,
+                15..=16 => n + 13,
+                _ => n + 14,
+            };
+            return Some(if mods.is_empty() {
+                format!("\\e[{}~", code)
+            } else {
+                format!("\\e[{};{}~", code, modifiers_param(mods))
+            });
+        }
+        K::FocusGained if mods.is_empty() => ("", 'I'),
+        K::FocusLost if mods.is_empty() => ("", 'O'),
+        K::Home => ("1", 'H'),
+        K::Insert => ("2", '~'),
+        K::Left => ("1", 'D'),
+        K::PageDown => ("6", '~'),
+        K::PageUp => ("5", '~'),
+        K::Right => ("1", 'C'),
+        K::Up => ("1", 'A'),
+        _ => return None,
+    };
+    Some(match (code, mods.is_empty(), last) {
+        (K::F(_), true, _) => format!("\\eO{}", last),
+        (_, true, '~') => format!("\\e[{}~", param),
+        (_, true, _) => format!("\\e[{}", last),
+        _ => format!("\\e[{};{}{}", param, modifiers_param(mods), last),
+    })
+}
+
+/// Sequence sent by (xterm compatible) terminals in application keypad mode
+fn ss3_sequence(code: K, mods: M) -> Option<String> {
+    if mods != M::KEYPAD {
+        return None;
+    }
+    let last = match code {
+        K::Enter => 'M',
+        K::Down => 'B',
+        K::End => 'F',
+        K::Home => 'H',
+        K::Left => 'D',
+        K::Right => 'C',
+        K::Up => 'A',
+        _ => return None,
+    };
+    Some(format!("\\eO{}", last))
+}
+
+/// xterm modifier parameter (inverse of [`modifiers`])
+fn modifiers_param(mods: M) -> u8 {
+    let mut bits = 0;
+    if mods.contains(M::SHIFT) {
+        bits |= 1;
+    }
+    if mods.contains(M::ALT) {
+        bits |= 2;
+    }
+    if mods.contains(M::CTRL) {
+        bits |= 4;
+    }
+    bits + 1
+}
+
+/// Decode the key sent by a terminal as a CSI (`ESC [`) or SS3 (`ESC O`)
+/// sequence (`ESC` excluded).
+fn escape_sequence_key<I: Iterator<Item = char>>(chars: &mut I) -> Option<E> {
+    match chars.next()? {
+        '[' => {
+            let mut params = String::new();
+            let last = loop {
+                match chars.next()? {
+                    c @ ('0'..='9' | ';') => params.push(c),
+                    c => break c,
+                }
+            };
+            let mut params = params.split(';');
+            let first = params.next().unwrap_or("");
+            let mods = match params.next() {
+                Some(m) => modifiers(m)?,
+                None => M::NONE,
+            };
+            if params.next().is_some() {
+                return None;
+            }
+            let code = if last == '~' {
+                match first.parse::<u8>().ok()? {
+                    1 | 7 => K::Home,
+                    2 => K::Insert,
+                    3 => K::Delete,
+                    4 | 8 => K::End,
+                    5 => K::PageUp,
+                    6 => K::PageDown,
+                    n @ 11..=15 => K::F(n - 10),
+                    n @ 17..=21 => K::F(n - 11),
+                    n @ 23..=26 => K::F(n - 12),
+                    n @ 28..=29 => K::F(n - 13),
+                    n @ 31..=34 => K::F(n - 14),
+                    _ => return None,
+                }
+            } else if first.is_empty() || first == "1" {
+                match last {
+                    // focus reports have no parameter
+                    'I' if first.is_empty() && mods.is_empty() => K::FocusGained,
+                    'O' if first.is_empty() && mods.is_empty() => K::FocusLost,
+                    'Z' => return Some(E(K::BackTab, mods)),
+                    c => cursor_key(c)?,
+                }
+            } else {
+                return None;
+            };
+            Some(E(code, mods))
+        }
+        'O' => match chars.next()? {
+            'M' => Some(E(K::Enter, M::KEYPAD)),
+            // F1-F4 are always sent this way
+            c @ 'P'..='S' => Some(E(cursor_key(c)?, M::NONE)),
+            // application keypad mode
+            c => Some(E(cursor_key(c)?, M::KEYPAD)),
+        },
+        _ => None,
+    }
+}
+
+/// Final character of cursor and F1-F4 keys sequences
+fn cursor_key(c: char) -> Option<K> {
+    Some(match c {
+        'A' => K::Up,
+        'B' => K::Down,
+        'C' => K::Right,
+        'D' => K::Left,
+        'F' => K::End,
+        'H' => K::Home,
+        'P' => K::F(1),
+        'Q' => K::F(2),
+        'R' => K::F(3),
+        'S' => K::F(4),
+        _ => return None,
+    })
+}
+
+/// xterm modifier parameter: 1 + (1 for Shift, 2 for Alt, 4 for Ctrl)
+fn modifiers(param: &str) -> Option<M> {
+    let bits = param.parse::<u8>().ok()?.checked_sub(1)?;
+    if bits > 7 {
+        return None;
+    }
+    let mut mods = M::NONE;
+    if bits & 1 != 0 {
+        mods |= M::SHIFT;
+    }
+    if bits & 2 != 0 {
+        mods |= M::ALT;
+    }
+    if bits & 4 != 0 {
+        mods |= M::CTRL;
+    }
+    Some(mods)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        escape_macro, escape_sequence, key_name, parse_char_name, unescape_macro, unescape_sequence,
+    };
+    use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+
+    #[test]
+    fn char_name() {
+        assert_eq!(Some(E::from('a')), parse_char_name("a"));
+        assert_eq!(Some(E::ctrl('u')), parse_char_name("Control-u"));
+        assert_eq!(Some(E::ctrl('u')), parse_char_name("C-u"));
+        assert_eq!(Some(E::alt('f')), parse_char_name("Meta-f"));
+        assert_eq!(Some(E(K::Backspace, M::ALT)), parse_char_name("M-DEL"));
+        assert_eq!(Some(E(K::Backspace, M::NONE)), parse_char_name("Rubout"));
+        assert_eq!(Some(E(K::Enter, M::NONE)), parse_char_name("RET"));
+        assert_eq!(Some(E(K::Tab, M::NONE)), parse_char_name("TAB"));
+        assert_eq!(Some(E::from(' ')), parse_char_name("SPC"));
+        assert_eq!(
+            Some(E(K::Left, M::CTRL_ALT)),
+            parse_char_name("Control-Meta-Left")
+        );
+        assert_eq!(Some(E(K::Up, M::SHIFT)), parse_char_name("S-Up"));
+        assert_eq!(Some(E(K::F(1), M::NONE)), parse_char_name("F1"));
+        assert_eq!(Some(E(K::F(12), M::CTRL)), parse_char_name("C-F12"));
+        assert_eq!(Some(E(K::PageUp, M::NONE)), parse_char_name("PageUp"));
+        assert_eq!(Some(E(K::Home, M::NONE)), parse_char_name("home"));
+        assert_eq!(Some(E(K::Up, M::NONE)), parse_char_name("\\e[A"));
+        assert_eq!(Some(E(K::Up, M::KEYPAD)), parse_char_name("Keypad-Up"));
+        assert_eq!(Some(E(K::Up, M::KEYPAD)), parse_char_name("\\eOA"));
+        assert_eq!(None, parse_char_name("\\C-a\\C-b"));
+        assert_eq!(None, parse_char_name("F21"));
+        assert_eq!(None, parse_char_name("Foo"));
+        assert_eq!(None, parse_char_name(""));
+    }
+
+    #[test]
+    fn key_seq() {
+        assert_eq!(
+            Some(vec![E::ctrl('x'), E::ctrl('r')]),
+            unescape_sequence("\\C-x\\C-r")
+        );
+        assert_eq!(Some(vec![E::alt('f')]), unescape_sequence("\\ef"));
+        assert_eq!(Some(vec![E::alt('f')]), unescape_sequence("\\M-f"));
+        assert_eq!(Some(vec![E::ESC]), unescape_sequence("\\e"));
+        assert_eq!(
+            Some(vec![E::from('"'), E::from('\\')]),
+            unescape_sequence("\\\"\\\\")
+        );
+        assert_eq!(Some(vec![E::ctrl('A')]), unescape_sequence("\\001"));
+        assert_eq!(Some(vec![E::ctrl('A')]), unescape_sequence("\\x01"));
+        assert_eq!(None, unescape_sequence("\\C-"));
+    }
+
+    #[test]
+    fn terminal_sequence() {
+        assert_eq!(Some(vec![E(K::Up, M::NONE)]), unescape_sequence("\\e[A"));
+        assert_eq!(Some(vec![E(K::Up, M::KEYPAD)]), unescape_sequence("\\eOA"));
+        assert_eq!(
+            Some(vec![E(K::Enter, M::KEYPAD)]),
+            unescape_sequence("\\eOM")
+        );
+        assert_eq!(Some(vec![E(K::F(1), M::NONE)]), unescape_sequence("\\eOP"));
+        assert_eq!(
+            Some(vec![E(K::Right, M::CTRL)]),
+            unescape_sequence("\\e[1;5C")
+        );
+        assert_eq!(
+            Some(vec![E(K::Delete, M::NONE)]),
+            unescape_sequence("\\e[3~")
+        );
+        assert_eq!(
+            Some(vec![E(K::PageUp, M::SHIFT)]),
+            unescape_sequence("\\e[5;2~")
+        );
+        assert_eq!(
+            Some(vec![E(K::F(5), M::NONE)]),
+            unescape_sequence("\\e[15~")
+        );
+        assert_eq!(
+            Some(vec![E(K::F(12), M::NONE)]),
+            unescape_sequence("\\e[24~")
+        );
+        assert_eq!(
+            Some(vec![E(K::BackTab, M::NONE)]),
+            unescape_sequence("\\e[Z")
+        );
+        assert_eq!(
+            Some(vec![E(K::FocusGained, M::NONE)]),
+            unescape_sequence("\\e[I")
+        );
+        assert_eq!(
+            Some(vec![E(K::FocusLost, M::NONE)]),
+            unescape_sequence("\\e[O")
+        );
+        // not a known sequence: Meta-[ followed by keys
+        assert_eq!(
+            Some(vec![E::alt('['), E::from('9'), E::from('9'), E::from('~')]),
+            unescape_sequence("\\e[99~")
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let keys = [
+            E::from('a'),
+            E::from('A'),
+            E::from(' '),
+            E::from('"'),
+            E::from('\\'),
+            E::from('é'),
+            E::ctrl('x'),
+            E::ctrl('\\'),
+            E::alt('f'),
+            E::alt('['),
+            E(K::Char('f'), M::CTRL_ALT),
+            E::BACKSPACE,
+            E(K::Backspace, M::ALT),
+            E::ENTER,
+            E::ESC,
+            E(K::Esc, M::ALT),
+            E(K::Tab, M::NONE),
+            E(K::BackTab, M::NONE),
+            E(K::Up, M::NONE),
+            E(K::Left, M::CTRL_ALT_SHIFT),
+            E(K::Home, M::SHIFT),
+            E(K::End, M::NONE),
+            E(K::Insert, M::NONE),
+            E(K::Delete, M::CTRL),
+            E(K::PageUp, M::NONE),
+            E(K::PageDown, M::ALT),
+            E(K::F(1), M::NONE),
+            E(K::F(4), M::CTRL),
+            E(K::F(5), M::NONE),
+            E(K::F(12), M::SHIFT),
+            E(K::F(20), M::NONE),
+            E(K::FocusGained, M::NONE),
+            E(K::FocusLost, M::NONE),
+            E(K::Up, M::KEYPAD),
+            E(K::Home, M::KEYPAD),
+            E(K::Enter, M::KEYPAD),
+        ];
+        for key in keys.iter() {
+            let expected = E::normalize(*key);
+            let name = key_name(*key).unwrap();
+            assert_eq!(
+                Some(expected),
+                parse_char_name(&name).map(E::normalize),
+                "{}",
+                name
+            );
+            let seq = escape_sequence(&[*key]).unwrap();
+            assert_eq!(
+                Some(vec![expected]),
+                unescape_sequence(&seq).map(|keys| keys.into_iter().map(E::normalize).collect()),
+                "{}",
+                seq
+            );
+        }
+        // ESC followed by a key is not Meta-key
+        let keys = vec![E::ESC, E::from('f'), E::ESC];
+        let seq = escape_sequence(&keys).unwrap();
+        assert_eq!("\\033f\\e", seq);
+        assert_eq!(Some(keys), unescape_sequence(&seq));
+
+        assert_eq!(
+            "\\C-x\\C-r",
+            escape_sequence(&[E::ctrl('X'), E::ctrl('R')]).unwrap()
+        );
+        assert_eq!(
+            "\\e[1;5C",
+            escape_sequence(&[E(K::Right, M::CTRL)]).unwrap()
+        );
+        assert_eq!(
+            "Control-Meta-Left",
+            key_name(E(K::Left, M::CTRL_ALT)).unwrap()
+        );
+        assert_eq!(None, escape_sequence(&[E(K::Null, M::NONE)]));
+        assert_eq!(None, key_name(E(K::UnknownEscSeq, M::NONE)));
+    }
+
+    #[test]
+    fn macro_text() {
+        assert_eq!(
+            Some("reload-config\n".to_owned()),
+            unescape_macro("reload-config\\n")
+        );
+        assert_eq!(
+            Some("\"\\'\x01\x1bf\x7fé".to_owned()),
+            unescape_macro("\\\"\\\\\\'\\C-a\\M-f\\C-?é")
+        );
+        assert_eq!(Some("\x1b[A\t".to_owned()), unescape_macro("\\e[A\\011"));
+        assert_eq!(None, unescape_macro("\\C-"));
+        assert_eq!(None, unescape_macro("\\C-é"));
+
+        for text in &["ls -l\n", "\"\\\t\x01\x00\x1c\x1b\x7f", "\x1ca"] {
+            let escaped = escape_macro(text);
+            assert_eq!(
+                Some(text.to_string()),
+                unescape_macro(&escaped),
+                "{}",
+                escaped
+            );
+        }
+        assert_eq!("say \\\"hi\\\"\\n", escape_macro("say \"hi\"\n"));
+    }
+}
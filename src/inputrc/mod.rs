@@ -0,0 +1,1755 @@
+//! Readline init file (inputrc) support
+//!
+//! Only a subset of GNU Readline's syntax is supported:
+//! - `set variable value`, including `set keymap name` which selects the
+//!   keymap where the following key bindings are stored
+//! - `keyname: function-name` and `"keyseq": function-name`
+//! - macros: `"keyseq": "text"` (a trailing newline accepts the line)
+//! - `$if` / `$else` / `$endif`
+//! - `$include`
+//!
+//! [`parse`] returns the directives without applying them so that they can be
+//! inspected or filtered before being given to
+//! [`Editor::apply_inputrc`](crate::Editor::apply_inputrc):
+//! ```
+//! use rustyline::inputrc::{self, Directive};
+//!
+//! let mut directives = inputrc::parse("set editing-mode vi\nC-u: kill-whole-line\n");
+//! directives.retain(|d| !matches!(d, Directive::SetVariable { .. }));
+//! let mut rl = rustyline::Editor::<()>::new();
+//! rl.apply_inputrc(&directives);
+//! ```
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use log::debug;
+
+use crate::binding::Bindings;
+use crate::config::{Config, EditMode};
+use crate::history::{Direction, HistoryStore};
+use crate::keymap::{Anchor, At, Cmd, Movement, Word};
+use crate::keys::{KeyEvent, Modifiers};
+use crate::{Editor, Event, EventHandler, Helper, Keymap, Layer, Result};
+
+pub mod chars;
+pub(crate) mod settings;
+mod source;
+mod variables;
+
+pub use self::settings::{KeymapSettings, Settings};
+pub use self::source::InputrcSource;
+pub use self::variables::{Variable, VariableError};
+
+/// Location of a directive in the parsed text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    /// Line number (1-based) where the directive starts
+    pub line: usize,
+    /// Column number (1-based, in characters) where the directive starts
+    pub column: usize,
+    /// Byte range of the directive in the parsed text
+    pub range: Range<usize>,
+}
+
+/// Test of an `$if` directive
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Test {
+    /// `$if mode=emacs` or `$if mode=vi`
+    Mode(String),
+    /// `$if term=xterm`: matches `$TERM` or its prefix before the first `-`
+    Term(String),
+    /// `$if name`: matches the application name
+    Application(String),
+}
+
+/// Inputrc directive
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Directive {
+    /// `set name value`
+    SetVariable {
+        /// Variable name
+        name: String,
+        /// Variable value
+        value: String,
+        /// Location
+        span: Span,
+    },
+    /// `keyname: function-name` or `"keyseq": function-name`
+    Bind {
+        /// Key sequence
+        keys: Vec<KeyEvent>,
+        /// Readline function name
+        function: String,
+        /// Location
+        span: Span,
+    },
+    /// `"keyseq": "text"`: the text is inserted
+    Macro {
+        /// Key sequence
+        keys: Vec<KeyEvent>,
+        /// Text (unescaped)
+        text: String,
+        /// Location
+        span: Span,
+    },
+    /// `$if test` ... [`$else` ...] `$endif`
+    Conditional {
+        /// `$if` test
+        test: Test,
+        /// Directives applied when `test` matches
+        then: Vec<Directive>,
+        /// Directives applied when `test` does not match (`$else` branch)
+        otherwise: Vec<Directive>,
+        /// Location (from `$if` to `$endif`)
+        span: Span,
+    },
+    /// `$include path`
+    Include {
+        /// File name (as written)
+        path: String,
+        /// Location
+        span: Span,
+    },
+}
+
+impl Directive {
+    /// Location of this directive in the parsed text
+    pub fn span(&self) -> &Span {
+        match self {
+            Directive::SetVariable { span, .. }
+            | Directive::Bind { span, .. }
+            | Directive::Macro { span, .. }
+            | Directive::Conditional { span, .. }
+            | Directive::Include { span, .. } => span,
+        }
+    }
+}
+
+/// Format the directive as an inputrc line (or block for conditionals).
+///
+/// Bindings are always written with a quoted key sequence. Those whose keys
+/// cannot be represented in an inputrc file are commented out.
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Directive::SetVariable { name, value, .. } => write!(f, "set {} {}", name, value),
+            Directive::Bind { keys, function, .. } => match chars::escape_sequence(keys) {
+                Some(seq) => write!(f, "\"{}\": {}", seq, function),
+                None => write!(f, "# {:?}: {}", keys, function),
+            },
+            Directive::Macro { keys, text, .. } => {
+                let text = chars::escape_macro(text);
+                match chars::escape_sequence(keys) {
+                    Some(seq) => write!(f, "\"{}\": \"{}\"", seq, text),
+                    None => write!(f, "# {:?}: \"{}\"", keys, text),
+                }
+            }
+            Directive::Conditional {
+                test,
+                then,
+                otherwise,
+                ..
+            } => {
+                writeln!(f, "$if {}", test)?;
+                for directive in then {
+                    writeln!(f, "{}", directive)?;
+                }
+                if !otherwise.is_empty() {
+                    writeln!(f, "$else")?;
+                    for directive in otherwise {
+                        writeln!(f, "{}", directive)?;
+                    }
+                }
+                write!(f, "$endif")
+            }
+            Directive::Include { path, .. } => write!(f, "$include {}", path),
+        }
+    }
+}
+
+impl fmt::Display for Test {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Test::Mode(mode) => write!(f, "mode={}", mode),
+            Test::Term(term) => write!(f, "term={}", term),
+            Test::Application(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Error found while parsing or applying an inputrc file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputrcError {
+    /// File where the error occurred (`None` when parsing text directly)
+    pub file: Option<PathBuf>,
+    /// Line number (1-based)
+    pub line: usize,
+    /// Column number (1-based, in characters)
+    pub column: usize,
+    /// Offending text
+    pub text: String,
+    /// Why the text was rejected
+    pub reason: String,
+}
+
+impl fmt::Display for InputrcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref file) = self.file {
+            write!(f, "{}: ", file.display())?;
+        }
+        write!(
+            f,
+            "line {}, column {}: {}: {}",
+            self.line, self.column, self.text, self.reason
+        )
+    }
+}
+
+impl Error for InputrcError {}
+
+/// Parse inputrc `text` without applying it.
+///
+/// Unsupported or malformed lines are ignored.
+pub fn parse(text: &str) -> Vec<Directive> {
+    parse_with_errors(text).0
+}
+
+/// Parse inputrc `text` without applying it, collecting errors for
+/// unsupported or malformed lines.
+pub fn parse_with_errors(text: &str) -> (Vec<Directive>, Vec<InputrcError>) {
+    let mut parser = Parser::default();
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        parser.parse_line(line.trim_end(), i + 1, offset);
+        offset += line.len();
+    }
+    parser.finish(offset)
+}
+
+/// `$if` being parsed
+struct Block {
+    test: Test,
+    /// Location of the `$if` line
+    span: Span,
+    then: Vec<Directive>,
+    otherwise: Option<Vec<Directive>>,
+}
+
+#[derive(Default)]
+struct Parser {
+    directives: Vec<Directive>,
+    blocks: Vec<Block>,
+    errors: Vec<InputrcError>,
+}
+
+/// Line being parsed
+struct Line<'t> {
+    text: &'t str,
+    number: usize,
+    /// Offset of `text` in the whole parsed text
+    offset: usize,
+}
+
+impl Line<'_> {
+    /// Location of `token` (which must be a slice of this line)
+    fn span(&self, token: &str) -> Span {
+        let start = token.as_ptr() as usize - self.text.as_ptr() as usize;
+        Span {
+            line: self.number,
+            column: self.text[..start].chars().count() + 1,
+            range: self.offset + start..self.offset + start + token.len(),
+        }
+    }
+}
+
+impl Parser {
+    /// Where new directives go
+    fn target(&mut self) -> &mut Vec<Directive> {
+        match self.blocks.last_mut() {
+            Some(Block {
+                otherwise: Some(otherwise),
+                ..
+            }) => otherwise,
+            Some(block) => &mut block.then,
+            None => &mut self.directives,
+        }
+    }
+
+    fn error(&mut self, line: &Line<'_>, token: &str, reason: &str) {
+        let span = line.span(token);
+        self.errors.push(InputrcError {
+            file: None,
+            line: span.line,
+            column: span.column,
+            text: token.to_owned(),
+            reason: reason.to_owned(),
+        });
+    }
+
+    fn parse_line(&mut self, text: &str, number: usize, offset: usize) {
+        let line = Line {
+            text,
+            number,
+            offset,
+        };
+        let text = text.trim_start();
+        if text.is_empty() || text.starts_with('#') {
+            return;
+        }
+        let span = line.span(text);
+        let directive = if let Some(directive) = text.strip_prefix('$') {
+            self.parse_directive(&line, directive, span)
+        } else if let Some(rest) = strip_keyword(text, "set") {
+            let (name, value) = split_word(rest);
+            if name.is_empty() {
+                self.error(&line, text, "missing variable name");
+                None
+            } else {
+                Some(Directive::SetVariable {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                    span,
+                })
+            }
+        } else {
+            self.parse_binding(&line, text, span)
+        };
+        if let Some(directive) = directive {
+            self.target().push(directive);
+        }
+    }
+
+    fn parse_directive(
+        &mut self,
+        line: &Line<'_>,
+        directive: &str,
+        span: Span,
+    ) -> Option<Directive> {
+        let (name, args) = split_word(directive);
+        match name.to_ascii_lowercase().as_str() {
+            "if" => {
+                self.blocks.push(Block {
+                    test: parse_test(args),
+                    span,
+                    then: Vec::new(),
+                    otherwise: None,
+                });
+                None
+            }
+            "else" => {
+                match self.blocks.last_mut() {
+                    Some(block) if block.otherwise.is_none() => {
+                        block.otherwise = Some(Vec::new());
+                    }
+                    Some(_) => self.error(line, name, "duplicate $else"),
+                    None => self.error(line, name, "$else without $if"),
+                }
+                None
+            }
+            "endif" => match self.blocks.pop() {
+                Some(block) => Some(block.close(span.range.end)),
+                None => {
+                    self.error(line, name, "$endif without $if");
+                    None
+                }
+            },
+            "include" => {
+                if args.is_empty() {
+                    self.error(line, name, "missing file name");
+                    None
+                } else {
+                    Some(Directive::Include {
+                        path: args.to_owned(),
+                        span,
+                    })
+                }
+            }
+            _ => {
+                self.error(line, name, "unknown parser directive");
+                None
+            }
+        }
+    }
+
+    fn parse_binding(&mut self, line: &Line<'_>, text: &str, span: Span) -> Option<Directive> {
+        let (keys, key_text, rest) = if let Some(quoted) = text.strip_prefix('"') {
+            let end = match find_closing_quote(quoted) {
+                Some(end) => end,
+                None => {
+                    self.error(line, text, "no closing `\"' in key binding");
+                    return None;
+                }
+            };
+            (
+                chars::unescape_sequence(&quoted[..end]),
+                &text[..end + 2],
+                &quoted[end + 1..],
+            )
+        } else {
+            match text.find(':') {
+                Some(end) => {
+                    let name = text[..end].trim_end();
+                    (
+                        chars::parse_char_name(name).map(|k| vec![k]),
+                        name,
+                        &text[end..],
+                    )
+                }
+                None => {
+                    self.error(line, text, "no `:' in key binding");
+                    return None;
+                }
+            }
+        };
+        let keys = match keys {
+            Some(keys) if !keys.is_empty() => keys,
+            _ => {
+                self.error(line, key_text, "invalid key");
+                return None;
+            }
+        };
+        let function = match rest.trim_start().strip_prefix(':') {
+            Some(function) => function.trim_start(),
+            None => {
+                self.error(line, rest, "no `:' in key binding");
+                return None;
+            }
+        };
+        if let Some(quoted) = function.strip_prefix('"') {
+            let text = match find_closing_quote(quoted) {
+                Some(end) => chars::unescape_macro(&quoted[..end]),
+                None => {
+                    self.error(line, function, "no closing `\"' in macro");
+                    return None;
+                }
+            };
+            return match text {
+                Some(text) => Some(Directive::Macro { keys, text, span }),
+                None => {
+                    self.error(line, function, "invalid macro");
+                    None
+                }
+            };
+        }
+        let function = split_word(function).0;
+        if function.is_empty() {
+            self.error(line, text, "missing function name");
+            return None;
+        }
+        Some(Directive::Bind {
+            keys,
+            function: function.to_owned(),
+            span,
+        })
+    }
+
+    fn finish(mut self, end: usize) -> (Vec<Directive>, Vec<InputrcError>) {
+        while let Some(block) = self.blocks.pop() {
+            self.errors.push(InputrcError {
+                file: None,
+                line: block.span.line,
+                column: block.span.column,
+                text: "$if".to_owned(),
+                reason: "missing $endif".to_owned(),
+            });
+            let directive = block.close(end);
+            self.target().push(directive);
+        }
+        self.errors.sort_by_key(|err| (err.line, err.column));
+        (self.directives, self.errors)
+    }
+}
+
+impl Block {
+    fn close(self, end: usize) -> Directive {
+        Directive::Conditional {
+            test: self.test,
+            then: self.then,
+            otherwise: self.otherwise.unwrap_or_default(),
+            span: Span {
+                range: self.span.range.start..end,
+                ..self.span
+            },
+        }
+    }
+}
+
+fn parse_test(args: &str) -> Test {
+    if let Some(mode) = strip_prefix_ignore_case(args, "mode=") {
+        Test::Mode(mode.to_owned())
+    } else if let Some(term) = strip_prefix_ignore_case(args, "term=") {
+        Test::Term(term.to_owned())
+    } else {
+        Test::Application(args.to_owned())
+    }
+}
+
+/// Load and apply the inputrc file at `path` to `editor`.
+pub(crate) fn load<H: Helper, S: HistoryStore>(
+    editor: &mut Editor<H, S>,
+    path: &Path,
+    on_error: &mut dyn FnMut(&InputrcError),
+) -> Result<()> {
+    let mut applier = Applier::new(editor, on_error);
+    let result = applier.load_file(path);
+    let Applier { read, bound, .. } = applier;
+    editor.inputrc = Loaded {
+        path: Some(path.to_path_buf()),
+        files: read,
+        bindings: bound,
+        auto_reload: editor.inputrc.auto_reload,
+    };
+    result
+}
+
+/// Remove the key bindings made by the last loaded file then load it again.
+pub(crate) fn reload<H: Helper, S: HistoryStore>(
+    editor: &mut Editor<H, S>,
+    on_error: &mut dyn FnMut(&InputrcError),
+) -> Result<()> {
+    let path = match editor.inputrc.path.clone() {
+        Some(path) => path,
+        None => return Ok(()),
+    };
+    for (keymap, evt) in mem::take(&mut editor.inputrc.bindings) {
+        editor.unbind_sequence_in_layer(Layer::User, Some(keymap), evt);
+    }
+    load(editor, &path, on_error)
+}
+
+/// Apply `directives` to `editor`.
+pub(crate) fn apply<H: Helper, S: HistoryStore>(
+    editor: &mut Editor<H, S>,
+    directives: &[Directive],
+    on_error: &mut dyn FnMut(&InputrcError),
+) {
+    Applier::new(editor, on_error).apply(directives)
+}
+
+/// Last loaded inputrc file
+#[derive(Debug, Default)]
+pub(crate) struct Loaded {
+    /// File given to [`load`]
+    path: Option<PathBuf>,
+    /// Files read, including the `$include`d ones, with their modification
+    /// time
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    /// Key bindings made by these files
+    bindings: Vec<(Keymap, Event)>,
+    /// Reload when one of the files is modified
+    pub auto_reload: bool,
+}
+
+impl Loaded {
+    /// Tell if one of the files has been modified (or created or deleted)
+    /// since it was loaded.
+    pub fn is_modified(&self) -> bool {
+        self.files
+            .iter()
+            .any(|(path, time)| modified(path) != *time)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Default error handler: errors are only logged.
+pub(crate) fn log_error(err: &InputrcError) {
+    debug!(target: "rustyline", "inputrc: {}", err);
+}
+
+/// Maximum nesting of `$include` directives
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+struct Applier<'e, H: Helper, S: HistoryStore> {
+    editor: &'e mut Editor<H, S>,
+    on_error: &'e mut dyn FnMut(&InputrcError),
+    /// `$TERM`
+    term: Option<String>,
+    /// Files being loaded (outermost first), used to detect `$include` cycles
+    files: Vec<PathBuf>,
+    /// Keymap where key bindings are stored
+    keymap: Keymap,
+    /// Key implicitly prepended to bound sequences (`emacs-meta` and
+    /// `emacs-ctlx` keymaps)
+    prefix: Option<KeyEvent>,
+    /// Files read (see [`Loaded`])
+    read: Vec<(PathBuf, Option<SystemTime>)>,
+    /// Key bindings made (see [`Loaded`])
+    bound: Vec<(Keymap, Event)>,
+}
+
+impl<'e, H: Helper, S: HistoryStore> Applier<'e, H, S> {
+    fn new(editor: &'e mut Editor<H, S>, on_error: &'e mut dyn FnMut(&InputrcError)) -> Self {
+        let keymap = default_keymap(editor.config.edit_mode());
+        Self {
+            editor,
+            on_error,
+            term: env::var("TERM").ok(),
+            files: Vec::new(),
+            keymap,
+            prefix: None,
+            read: Vec::new(),
+            bound: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, span: &Span, text: &str, reason: &str) {
+        let err = InputrcError {
+            file: self.files.last().cloned(),
+            line: span.line,
+            column: span.column,
+            text: text.to_owned(),
+            reason: reason.to_owned(),
+        };
+        (self.on_error)(&err);
+    }
+
+    fn load_file(&mut self, path: &Path) -> Result<()> {
+        self.read.push((path.to_path_buf(), modified(path)));
+        let text = fs::read_to_string(path)?;
+        let (directives, errors) = parse_with_errors(&text);
+        for mut err in errors {
+            err.file = Some(path.to_path_buf());
+            (self.on_error)(&err);
+        }
+        self.files
+            .push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+        self.apply(&directives);
+        self.files.pop();
+        Ok(())
+    }
+
+    fn apply(&mut self, directives: &[Directive]) {
+        for directive in directives {
+            match directive {
+                Directive::SetVariable { name, value, span } => {
+                    self.set_variable(name, value, span)
+                }
+                Directive::Bind {
+                    keys,
+                    function,
+                    span,
+                } => self.bind(keys, function, span),
+                Directive::Macro { keys, text, .. } => self.bind_macro(keys, text),
+                Directive::Conditional {
+                    test,
+                    then,
+                    otherwise,
+                    span,
+                } => {
+                    if self.test(test, span) {
+                        self.apply(then);
+                    } else {
+                        self.apply(otherwise);
+                    }
+                }
+                Directive::Include { path, span } => self.include(path, span),
+            }
+        }
+    }
+
+    /// Evaluate the test of an `$if` directive
+    fn test(&mut self, test: &Test, span: &Span) -> bool {
+        match test {
+            Test::Mode(mode) => {
+                let edit_mode = self.editor.config.edit_mode();
+                if mode.eq_ignore_ascii_case("emacs") {
+                    edit_mode == EditMode::Emacs
+                } else if mode.eq_ignore_ascii_case("vi") {
+                    edit_mode == EditMode::Vi
+                } else if let EditMode::Custom(name) = edit_mode {
+                    mode.eq_ignore_ascii_case(name)
+                } else {
+                    self.error(span, mode, "unknown editing mode");
+                    false
+                }
+            }
+            Test::Term(term) => self.term.as_ref().is_some_and(|t| {
+                t.eq_ignore_ascii_case(term)
+                    || t.split('-').next().unwrap().eq_ignore_ascii_case(term)
+            }),
+            Test::Application(name) => self
+                .editor
+                .application_name
+                .as_ref()
+                .is_some_and(|app| app.eq_ignore_ascii_case(name)),
+        }
+    }
+
+    fn set_variable(&mut self, name: &str, value: &str, span: &Span) {
+        if name.eq_ignore_ascii_case("keymap") {
+            match keymap(value) {
+                Some((keymap, prefix)) => {
+                    self.keymap = keymap;
+                    self.prefix = prefix;
+                }
+                None => self.error(span, value, "invalid value for keymap"),
+            }
+            return;
+        }
+        let variable = match Variable::from_name(name) {
+            Some(variable) => variable,
+            None => return self.error(span, name, "unknown variable name"),
+        };
+        match variable.set(self.editor, value) {
+            Ok(()) => {
+                if variable == Variable::EditingMode {
+                    self.keymap = default_keymap(self.editor.config.edit_mode());
+                    self.prefix = None;
+                }
+            }
+            Err(_) => self.error(span, value, &format!("invalid value for {}", variable)),
+        }
+    }
+
+    fn bind(&mut self, keys: &[KeyEvent], function: &str, span: &Span) {
+        match handler(self.editor, function) {
+            // numeric arguments of the vi and custom modes are given otherwise
+            Some(EventHandler::Simple(Cmd::UniversalArgument)) if self.keymap != Keymap::Emacs => {
+                self.error(span, function, "only supported in the emacs keymap")
+            }
+            Some(handler) => {
+                let evt = to_event(&prefixed(self.prefix, keys));
+                self.bound.push((self.keymap, evt.clone()));
+                self.editor
+                    .bind_sequence_in_layer(Layer::User, Some(self.keymap), evt, handler);
+            }
+            None => self.error(span, function, "unknown function name"),
+        }
+    }
+
+    fn bind_macro(&mut self, keys: &[KeyEvent], text: &str) {
+        let evt = to_event(&prefixed(self.prefix, keys));
+        self.bound.push((self.keymap, evt.clone()));
+        self.editor.bind_sequence_in_layer(
+            Layer::User,
+            Some(self.keymap),
+            evt,
+            macro_handler(text),
+        );
+    }
+
+    fn include(&mut self, file_name: &str, span: &Span) {
+        let path = self.resolve(file_name);
+        if self.files.len() >= MAX_INCLUDE_DEPTH {
+            self.error(span, file_name, "$include nested too deeply");
+            return;
+        }
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if self.files.contains(&canonical) {
+            self.error(span, file_name, "recursive $include");
+            return;
+        }
+        if let Err(err) = self.load_file(&path) {
+            self.error(span, file_name, &err.to_string());
+        }
+    }
+
+    /// Expand `~` and make relative paths relative to the including file.
+    fn resolve(&self, file_name: &str) -> PathBuf {
+        if let Some(home) = source::home_dir() {
+            if file_name == "~" {
+                return home;
+            } else if let Some(rel_path) = file_name.strip_prefix("~/") {
+                return home.join(rel_path);
+            }
+        }
+        let path = Path::new(file_name);
+        match self.files.last().and_then(|file| file.parent()) {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+fn to_event(keys: &[KeyEvent]) -> Event {
+    if keys.len() == 1 {
+        Event::from(keys[0])
+    } else {
+        Event::KeySeq(keys.iter().copied().collect())
+    }
+}
+
+/// Commands performed by a macro: `text` is inserted and, if it ends with a
+/// newline, the line is accepted.
+fn macro_handler(text: &str) -> EventHandler {
+    let (text, accept) = match text.strip_suffix(|c| c == '\n' || c == '\r') {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+    let mut cmds = Vec::new();
+    if !text.is_empty() {
+        cmds.push(Cmd::Insert(1, text.to_owned()));
+    }
+    if accept {
+        cmds.push(Cmd::AcceptLine);
+    }
+    EventHandler::Macro(cmds)
+}
+
+/// Keymap selected by `set editing-mode`
+fn default_keymap(edit_mode: EditMode) -> Keymap {
+    match edit_mode {
+        EditMode::Emacs => Keymap::Emacs,
+        EditMode::Vi => Keymap::ViInsert,
+        EditMode::Custom(name) => Keymap::Custom(name),
+    }
+}
+
+/// Name of `keymap`, as accepted by `set keymap` (except for custom modes)
+fn keymap_name(keymap: Keymap) -> &'static str {
+    match keymap {
+        Keymap::Emacs => "emacs",
+        Keymap::ViCommand => "vi-command",
+        Keymap::ViInsert => "vi-insert",
+        Keymap::Custom(name) => name,
+    }
+}
+
+/// Map a readline keymap name to the corresponding keymap and prefix key.
+fn keymap(name: &str) -> Option<(Keymap, Option<KeyEvent>)> {
+    let keymap = match name.to_ascii_lowercase().as_str() {
+        "emacs" | "emacs-standard" => (Keymap::Emacs, None),
+        "emacs-meta" => (Keymap::Emacs, Some(KeyEvent::ESC)),
+        "emacs-ctlx" => (Keymap::Emacs, Some(KeyEvent::ctrl('X'))),
+        "vi" | "vi-command" | "vi-move" => (Keymap::ViCommand, None),
+        "vi-insert" => (Keymap::ViInsert, None),
+        _ => return None,
+    };
+    Some(keymap)
+}
+
+/// Prepend `prefix` to `keys`: ESC followed by a key is the Meta version of
+/// this key.
+fn prefixed(prefix: Option<KeyEvent>, keys: &[KeyEvent]) -> Vec<KeyEvent> {
+    let mut seq = keys.to_vec();
+    match prefix {
+        Some(KeyEvent::ESC) if !seq.is_empty() => {
+            let KeyEvent(code, mods) = seq[0];
+            seq[0] = KeyEvent(code, mods | Modifiers::ALT);
+        }
+        Some(prefix) => seq.insert(0, prefix),
+        None => {}
+    }
+    seq
+}
+
+/// Readline function names and the corresponding commands.
+///
+/// Each command has a single name, used by `dump-functions`, except for the
+/// [`ALIASES`].
+const FUNCTIONS: &[(&str, Cmd)] = &[
+    ("abort", Cmd::Abort),
+    ("accept-hint", Cmd::CompleteHint),
+    ("accept-hint-word", Cmd::CompleteHintWord),
+    ("accept-line", Cmd::AcceptLine),
+    ("backward-char", Cmd::Move(Movement::BackwardChar(1))),
+    ("backward-delete-char", Cmd::Kill(Movement::BackwardChar(1))),
+    ("backward-kill-line", Cmd::Kill(Movement::BeginningOfLine)),
+    (
+        "backward-kill-word",
+        Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)),
+    ),
+    (
+        "backward-word",
+        Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+    ),
+    ("beginning-of-history", Cmd::BeginningOfHistory),
+    ("beginning-of-line", Cmd::Move(Movement::BeginningOfLine)),
+    ("call-last-kbd-macro", Cmd::CallLastKbdMacro(1)),
+    ("capitalize-word", Cmd::CapitalizeWord(1)),
+    ("character-search", Cmd::CharacterSearch(1)),
+    ("character-search-backward", Cmd::CharacterSearchBackward(1)),
+    ("clear-screen", Cmd::ClearScreen),
+    ("complete", Cmd::Complete),
+    ("copy-region-as-kill", Cmd::ViYankTo(Movement::Region)),
+    ("dabbrev-expand", Cmd::DabbrevExpand),
+    ("delete-char", Cmd::Kill(Movement::ForwardChar(1))),
+    ("delete-char-or-list", Cmd::DeleteCharOrList),
+    ("downcase-word", Cmd::DowncaseWord(1)),
+    ("dump-functions", Cmd::DumpFunctions),
+    ("dump-macros", Cmd::DumpMacros),
+    ("dump-variables", Cmd::DumpVariables),
+    (
+        "edit-and-execute-command",
+        Cmd::EditCommandLine { accept: true },
+    ),
+    ("edit-command-line", Cmd::EditCommandLine { accept: false }),
+    ("emacs-editing-mode", Cmd::EmacsEditingMode),
+    ("end-kbd-macro", Cmd::EndKbdMacro),
+    ("end-of-history", Cmd::EndOfHistory),
+    ("end-of-line", Cmd::Move(Movement::EndOfLine)),
+    ("exchange-point-and-mark", Cmd::ExchangePointAndMark),
+    ("forward-char", Cmd::Move(Movement::ForwardChar(1))),
+    ("forward-search-history", Cmd::ForwardSearchHistory),
+    (
+        "forward-word",
+        Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    ("history-expand-line", Cmd::HistoryExpandLine),
+    ("history-search-backward", Cmd::HistorySearchBackward),
+    ("history-search-forward", Cmd::HistorySearchForward),
+    (
+        "history-substring-search-backward",
+        Cmd::HistorySubstringSearchBackward,
+    ),
+    (
+        "history-substring-search-forward",
+        Cmd::HistorySubstringSearchForward,
+    ),
+    ("insert-comment", Cmd::InsertComment { toggle: false }),
+    ("insert-completions", Cmd::InsertCompletions),
+    ("kill-line", Cmd::Kill(Movement::EndOfLine)),
+    ("kill-region", Cmd::Kill(Movement::Region)),
+    ("kill-whole-line", Cmd::Kill(Movement::WholeLine)),
+    (
+        "kill-word",
+        Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    ),
+    ("magic-space", Cmd::MagicSpace),
+    ("menu-complete", Cmd::MenuComplete),
+    ("menu-complete-backward", Cmd::MenuCompleteBackward),
+    ("next-history", Cmd::NextHistory),
+    ("operate-and-get-next", Cmd::OperateAndGetNext),
+    ("overwrite-mode", Cmd::OverwriteMode),
+    ("possible-completions", Cmd::PossibleCompletions),
+    ("previous-history", Cmd::PreviousHistory),
+    ("quoted-insert", Cmd::QuotedInsert(1)),
+    ("redo", Cmd::Redo(1)),
+    ("reverse-search-history", Cmd::ReverseSearchHistory),
+    ("revert-line", Cmd::RevertLine),
+    ("set-mark", Cmd::SetMark),
+    ("start-kbd-macro", Cmd::StartKbdMacro),
+    ("tab-insert", Cmd::SelfInsert(1, '\t')),
+    ("transpose-chars", Cmd::TransposeChars(1)),
+    ("transpose-words", Cmd::TransposeWords(1)),
+    ("undo", Cmd::Undo(1)),
+    ("universal-argument", Cmd::UniversalArgument),
+    ("unix-line-discard", Cmd::Kill(Movement::BeginningOfLine)),
+    (
+        "unix-word-rubout",
+        Cmd::Kill(Movement::BackwardWord(1, Word::Big)),
+    ),
+    ("upcase-word", Cmd::UpcaseWord(1)),
+    ("vi-editing-mode", Cmd::ViEditingMode),
+    ("vi-search", Cmd::ViSearch(Direction::Reverse)),
+    ("vi-search-again", Cmd::ViSearchAgain(1, Direction::Reverse)),
+    ("yank", Cmd::Yank(1, Anchor::Before)),
+    ("yank-last-arg", Cmd::YankLastArg(-1)),
+    ("yank-nth-arg", Cmd::YankNthArg(1)),
+    ("yank-pop", Cmd::YankPop),
+];
+
+/// Function names mapped to the same command as another one, which is the
+/// name used by `dump-functions`: `backward-kill-line` is dumped as
+/// `unix-line-discard`, the function bound to C-u by default.
+const ALIASES: &[&str] = &["backward-kill-line"];
+
+/// Map a readline function name to the corresponding command.
+fn function(name: &str) -> Option<Cmd> {
+    FUNCTIONS
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, cmd)| cmd.clone())
+}
+
+/// Handler of the function registered by the application under `name` or of
+/// the readline function `name`
+fn handler<H: Helper, S: HistoryStore>(editor: &Editor<H, S>, name: &str) -> Option<EventHandler> {
+    editor
+        .functions
+        .handler(name)
+        .or_else(|| function(name).map(EventHandler::from))
+}
+
+/// Map a command to the corresponding readline function name.
+fn function_name(cmd: &Cmd) -> Option<&'static str> {
+    FUNCTIONS
+        .iter()
+        .find(|(n, c)| c == cmd && !ALIASES.contains(n))
+        .map(|(n, _)| *n)
+}
+
+/// Key sequences of `keymap` bound to readline functions, with the function
+/// names
+fn functions(bindings: &Bindings, keymap: Keymap) -> Vec<(String, &'static str)> {
+    bindings
+        .iter(keymap)
+        .into_iter()
+        .filter_map(|(evt, handler)| match handler {
+            EventHandler::Simple(cmd) => Some((key_sequence(evt)?, function_name(cmd)?)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Key sequences of `keymap` bound to macros, with the macro texts
+fn macros(bindings: &Bindings, keymap: Keymap) -> Vec<(String, String)> {
+    bindings
+        .iter(keymap)
+        .into_iter()
+        .filter_map(|(evt, handler)| match handler {
+            EventHandler::Macro(cmds) => Some((key_sequence(evt)?, macro_text(cmds)?)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `dump-functions`: bindings of `keymap` to readline functions, in inputrc
+/// format.
+pub(crate) fn dump_functions(bindings: &Bindings, keymap: Keymap) -> String {
+    let mut dump = String::new();
+    for (seq, name) in functions(bindings, keymap) {
+        dump.push_str(&format!("\"{}\": {}\n", seq, name));
+    }
+    dump
+}
+
+/// `dump-macros`: macros bound in `keymap`, in inputrc format.
+pub(crate) fn dump_macros(bindings: &Bindings, keymap: Keymap) -> String {
+    let mut dump = String::new();
+    for (seq, text) in macros(bindings, keymap) {
+        dump.push_str(&format!(
+            "\"{}\": \"{}\"\n",
+            seq,
+            chars::escape_macro(&text)
+        ));
+    }
+    dump
+}
+
+/// `dump-variables`: readline variables, in inputrc format.
+pub(crate) fn dump_variables(config: &Config) -> String {
+    let mut dump = String::new();
+    for variable in Variable::ALL {
+        dump.push_str(&format!("set {} {}\n", variable, variable.get(config)));
+    }
+    dump
+}
+
+fn key_sequence(evt: &Event) -> Option<String> {
+    match evt {
+        Event::KeySeq(keys) => chars::escape_sequence(keys),
+        _ => None,
+    }
+}
+
+/// Inverse of `macro_handler`
+fn macro_text(cmds: &[Cmd]) -> Option<String> {
+    let mut text = String::new();
+    for cmd in cmds {
+        match cmd {
+            Cmd::Insert(1, s) => text.push_str(s),
+            Cmd::AcceptLine => text.push('\n'),
+            _ => return None,
+        }
+    }
+    Some(text)
+}
+
+/// Find the index of the `"` ending a quoted key sequence
+fn find_closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Split `s` at the first whitespace
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    match s.find(char::is_whitespace) {
+        Some(i) => (&s[..i], s[i..].trim_start()),
+        None => (s, ""),
+    }
+}
+
+/// Strip `keyword` followed by a whitespace
+fn strip_keyword<'s>(line: &'s str, keyword: &str) -> Option<&'s str> {
+    let (word, rest) = split_word(line);
+    if word.eq_ignore_ascii_case(keyword) {
+        Some(rest)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn strip_prefix_ignore_case<'s>(s: &'s str, prefix: &str) -> Option<&'s str> {
+    if s.len() >= prefix.len()
+        && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+    {
+        Some(&s[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Applier, Directive, Span, Test};
+    use crate::config::{BellStyle, Configurer, EditMode};
+    use crate::keys::KeyEvent as E;
+    use crate::{Cmd, Editor, Event, EventHandler, Keymap, Layer};
+
+    fn parse(editor: &mut Editor<()>, term: Option<&str>, text: &str) {
+        let mut on_error = super::log_error;
+        let mut applier = Applier::new(editor, &mut on_error);
+        applier.term = term.map(String::from);
+        applier.apply(&super::parse(text));
+    }
+
+    /// Remove the binding of `key` from all keymaps
+    fn binding(editor: &mut Editor<()>, key: E) -> Option<Cmd> {
+        let mut cmd = None;
+        for keymap in &[Keymap::Emacs, Keymap::ViCommand, Keymap::ViInsert] {
+            if let Some(EventHandler::Simple(c)) =
+                editor.unbind_sequence_in_layer(Layer::User, Some(*keymap), key)
+            {
+                cmd = cmd.or(Some(c));
+            }
+        }
+        cmd
+    }
+
+    #[test]
+    fn ast() {
+        let text = "# comment\n  set bell-style none\n$if mode=vi\nC-a: abort\n$else\n\"\\ef\": undo\n$endif\n$include ~/.inputrc.local";
+        assert_eq!(
+            vec![
+                Directive::SetVariable {
+                    name: "bell-style".to_owned(),
+                    value: "none".to_owned(),
+                    span: Span {
+                        line: 2,
+                        column: 3,
+                        range: 12..31,
+                    },
+                },
+                Directive::Conditional {
+                    test: Test::Mode("vi".to_owned()),
+                    then: vec![Directive::Bind {
+                        keys: vec![E::ctrl('a')],
+                        function: "abort".to_owned(),
+                        span: Span {
+                            line: 4,
+                            column: 1,
+                            range: 44..54,
+                        },
+                    }],
+                    otherwise: vec![Directive::Bind {
+                        keys: vec![E::alt('f')],
+                        function: "undo".to_owned(),
+                        span: Span {
+                            line: 6,
+                            column: 1,
+                            range: 61..72,
+                        },
+                    }],
+                    span: Span {
+                        line: 3,
+                        column: 1,
+                        range: 32..79,
+                    },
+                },
+                Directive::Include {
+                    path: "~/.inputrc.local".to_owned(),
+                    span: Span {
+                        line: 8,
+                        column: 1,
+                        range: 80..105,
+                    },
+                },
+            ],
+            super::parse(text)
+        );
+        // missing `$endif`
+        assert_eq!(
+            vec![Directive::Conditional {
+                test: Test::Term("xterm".to_owned()),
+                then: vec![],
+                otherwise: vec![],
+                span: Span {
+                    line: 1,
+                    column: 1,
+                    range: 0..15,
+                },
+            }],
+            super::parse("$if term=xterm\n")
+        );
+    }
+
+    #[test]
+    fn macros() {
+        let text = "\"\\C-x\\C-r\": \"reload-config\\n\"\n\
+                    C-t: \"\\\"quoted\\\" \\C-a\"  # comment\n\
+                    C-u: \"unterminated\n\
+                    C-v: \"\\C-\"\n";
+        let (directives, errors) = super::parse_with_errors(text);
+        assert_eq!(2, directives.len());
+        assert!(matches!(
+            &directives[0],
+            Directive::Macro { keys, text, .. }
+                if *keys == [E::ctrl('x'), E::ctrl('r')] && text == "reload-config\n"
+        ));
+        assert!(matches!(
+            &directives[1],
+            Directive::Macro { text, .. } if text == "\"quoted\" \x01"
+        ));
+        assert_eq!(
+            "\"\\C-t\": \"\\\"quoted\\\" \\C-a\"",
+            directives[1].to_string()
+        );
+        let reasons: Vec<_> = errors.iter().map(|e| e.reason.as_str()).collect();
+        assert_eq!(vec!["no closing `\"' in macro", "invalid macro"], reasons);
+
+        let mut editor = Editor::<()>::new();
+        editor.apply_inputrc(&directives);
+        let handler = editor.unbind_sequence_in_layer(
+            Layer::User,
+            Some(Keymap::Emacs),
+            Event::KeySeq(vec![E::ctrl('X'), E::ctrl('R')].into()),
+        );
+        assert!(matches!(
+            handler,
+            Some(EventHandler::Macro(cmds))
+                if cmds == [Cmd::Insert(1, "reload-config".to_owned()), Cmd::AcceptLine]
+        ));
+    }
+
+    #[test]
+    fn dump() {
+        let text = "\"\\C-xa\": \"ls -l\\n\"\n\
+                    C-t: transpose-words\n\
+                    C-u: unix-line-discard\n\
+                    set keymap vi-insert\n\
+                    C-w: backward-kill-word\n";
+        let mut editor = Editor::<()>::new();
+        editor.apply_inputrc(&super::parse(text));
+        editor.bind_sequence(E::ctrl('Y'), Cmd::Noop);
+        let bindings = editor.custom_bindings.read().unwrap();
+
+        let functions = super::dump_functions(&bindings, Keymap::Emacs);
+        assert_eq!(
+            "\"\\C-t\": transpose-words\n\"\\C-u\": unix-line-discard\n",
+            functions
+        );
+        let functions = super::dump_functions(&bindings, Keymap::ViInsert);
+        assert_eq!("\"\\C-w\": backward-kill-word\n", functions);
+
+        let macros = super::dump_macros(&bindings, Keymap::Emacs);
+        assert_eq!("\"\\C-xa\": \"ls -l\\n\"\n", macros);
+        assert_eq!(super::parse(text)[..1], super::parse(&macros)[..]);
+        assert!(super::dump_macros(&bindings, Keymap::ViCommand).is_empty());
+
+        let variables = super::dump_variables(&editor.config);
+        assert!(variables.contains("set editing-mode emacs\n"));
+        assert!(variables.contains("set show-all-if-ambiguous off\n"));
+        let (_, errors) = super::parse_with_errors(&variables);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn function_names() {
+        for (name, cmd) in super::FUNCTIONS {
+            let canonical = super::function_name(cmd);
+            if super::ALIASES.contains(name) {
+                assert!(canonical.is_some_and(|n| n != *name), "{}", name);
+            } else {
+                assert_eq!(Some(*name), canonical);
+            }
+        }
+    }
+
+    #[test]
+    fn dump_round_trip() {
+        let text = "C-t: transpose-words\n\
+                    C-u: backward-kill-line\n\
+                    C-w: unix-line-discard\n";
+        let mut editor = Editor::<()>::new();
+        editor.apply_inputrc(&super::parse(text));
+        let dump = {
+            let bindings = editor.custom_bindings.read().unwrap();
+            super::dump_functions(&bindings, Keymap::Emacs)
+        };
+        assert_eq!(
+            "\"\\C-t\": transpose-words\n\
+             \"\\C-u\": unix-line-discard\n\
+             \"\\C-w\": unix-line-discard\n",
+            dump
+        );
+
+        let mut reread = Editor::<()>::new();
+        reread.apply_inputrc(&super::parse(&dump));
+        let bindings = editor.custom_bindings.read().unwrap();
+        let reread = reread.custom_bindings.read().unwrap();
+        assert_eq!(dump, super::dump_functions(&reread, Keymap::Emacs));
+        for key in [E::ctrl('T'), E::ctrl('U'), E::ctrl('W')] {
+            let evt = Event::from(key);
+            let cmd = match bindings.get(Keymap::Emacs, &evt) {
+                Some(EventHandler::Simple(cmd)) => cmd,
+                _ => panic!("{:?}", key),
+            };
+            assert!(matches!(
+                reread.get(Keymap::Emacs, &evt),
+                Some(EventHandler::Simple(c)) if c == cmd
+            ));
+        }
+    }
+
+    #[test]
+    fn display() {
+        let text = "set editing-mode vi\n\
+                    $if term=xterm\n\
+                    \"\\e[1;5C\": forward-word\n\
+                    $else\n\
+                    $if Bash\n\
+                    Meta-Rubout: backward-kill-word\n\
+                    $endif\n\
+                    $endif\n\
+                    \"\\C-x\\C-r\": reverse-search-history\n\
+                    Control-\\\\: undo\n\
+                    $include ~/.inputrc.local";
+        let dump = super::parse(text)
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            "set editing-mode vi\n\
+             $if term=xterm\n\
+             \"\\e[1;5C\": forward-word\n\
+             $else\n\
+             $if Bash\n\
+             \"\\M-\\d\": backward-kill-word\n\
+             $endif\n\
+             $endif\n\
+             \"\\C-x\\C-r\": reverse-search-history\n\
+             \"\\C-\\\\\": undo\n\
+             $include ~/.inputrc.local",
+            dump
+        );
+        let lines = |directives: Vec<Directive>| {
+            directives
+                .iter()
+                .map(|d| format!("{}", d))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(super::parse(text)), lines(super::parse(&dump)));
+    }
+
+    #[test]
+    fn errors() {
+        let (directives, errors) = super::parse_with_errors(
+            "$if mode=vi\n\
+             C-a abort\n\
+             \"\\C-b: undo\n\
+             $else\n\
+             Foo: abort\n\
+             $else\n\
+             $foo\n\
+             set\n\
+             \tC-c:\n",
+        );
+        assert_eq!(1, directives.len());
+        let errors: Vec<_> = errors
+            .iter()
+            .map(|e| (e.line, e.column, e.text.as_str(), e.reason.as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, 1, "$if", "missing $endif"),
+                (2, 1, "C-a abort", "no `:' in key binding"),
+                (3, 1, "\"\\C-b: undo", "no closing `\"' in key binding"),
+                (5, 1, "Foo", "invalid key"),
+                (6, 2, "else", "duplicate $else"),
+                (7, 2, "foo", "unknown parser directive"),
+                (8, 1, "set", "missing variable name"),
+                (9, 2, "C-c:", "missing function name"),
+            ],
+            errors
+        );
+
+        let mut editor = Editor::<()>::new();
+        let mut errors = Vec::new();
+        editor.apply_inputrc_with(
+            &super::parse("set foo on\nset editing-mode ed\nC-a: foo\n$if mode=ed\n$endif"),
+            |err| errors.push(err.to_string()),
+        );
+        assert_eq!(
+            vec![
+                "line 1, column 1: foo: unknown variable name",
+                "line 2, column 1: ed: invalid value for editing-mode",
+                "line 3, column 1: foo: unknown function name",
+                "line 4, column 1: ed: unknown editing mode",
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn universal_argument() {
+        let mut editor = Editor::<()>::new();
+        let mut errors = Vec::new();
+        editor.apply_inputrc_with(
+            &super::parse(
+                "C-u: universal-argument\n\
+                 set keymap vi-insert\n\
+                 C-u: universal-argument\n\
+                 set keymap vi-command\n\
+                 C-u: universal-argument\n",
+            ),
+            |err| errors.push(err.to_string()),
+        );
+        assert_eq!(
+            vec![
+                "line 3, column 1: universal-argument: only supported in the emacs keymap",
+                "line 5, column 1: universal-argument: only supported in the emacs keymap",
+            ],
+            errors
+        );
+        let bindings = editor.custom_bindings.read().unwrap();
+        let evt = Event::from(E::ctrl('U'));
+        assert!(matches!(
+            bindings.get(Keymap::Emacs, &evt),
+            Some(EventHandler::Simple(Cmd::UniversalArgument))
+        ));
+        assert!(bindings.get(Keymap::ViInsert, &evt).is_none());
+        assert!(bindings.get(Keymap::ViCommand, &evt).is_none());
+    }
+
+    #[test]
+    fn flat() {
+        let mut editor = Editor::<()>::new();
+        parse(
+            &mut editor,
+            None,
+            "# comment\n\
+             set editing-mode vi\n\
+             Control-u: kill-whole-line\n\
+             \"\\C-x\\C-r\": reverse-search-history\n",
+        );
+        assert_eq!(EditMode::Vi, editor.config.edit_mode());
+        assert_eq!(
+            Some(Cmd::Kill(crate::Movement::WholeLine)),
+            binding(&mut editor, E::ctrl('U'))
+        );
+        assert!(matches!(
+            editor.unbind_sequence_in_layer(
+                Layer::User,
+                Some(Keymap::ViInsert),
+                Event::KeySeq(vec![E::ctrl('X'), E::ctrl('R')].into())
+            ),
+            Some(EventHandler::Simple(Cmd::ReverseSearchHistory))
+        ));
+    }
+
+    #[test]
+    fn completion_variables() {
+        let mut editor = Editor::<()>::new();
+        parse(
+            &mut editor,
+            None,
+            "set completion-ignore-case on
+             set completion-query-items 50
+             set mark-directories off
+             set menu-complete-display-prefix On
+             set show-all-if-ambiguous 1
+",
+        );
+        let config = editor.config;
+        assert!(config.completion_ignore_case());
+        assert_eq!(50, config.completion_prompt_limit());
+        assert!(!config.mark_directories());
+        assert!(config.menu_complete_display_prefix());
+        assert!(config.completion_show_all_if_ambiguous());
+    }
+
+    #[test]
+    fn bell_style() {
+        let mut editor = Editor::<()>::new();
+        parse(
+            &mut editor,
+            None,
+            "set bell-style visible
+",
+        );
+        assert_eq!(BellStyle::Visible, editor.config.bell_style());
+        assert_eq!(BellStyle::Visible, editor.term.bell_style);
+        parse(
+            &mut editor,
+            None,
+            "set bell-style none
+",
+        );
+        assert_eq!(BellStyle::None, editor.config.bell_style());
+        parse(
+            &mut editor,
+            None,
+            "set bell-style flash
+",
+        );
+        assert_eq!(BellStyle::None, editor.config.bell_style());
+    }
+
+    #[test]
+    fn keymap() {
+        let mut editor = Editor::<()>::new();
+        parse(
+            &mut editor,
+            None,
+            "C-a: abort
+             set keymap vi-command
+             C-b: undo
+             set keymap vi-insert
+             C-c: kill-whole-line
+             set keymap emacs-ctlx
+             C-d: yank
+             set keymap emacs-meta
+             f: forward-word
+             set keymap vi-mode
+",
+        );
+        let cmd = |editor: &mut Editor<()>, keymap, key: E| match editor.unbind_sequence_in_layer(
+            Layer::User,
+            Some(keymap),
+            key,
+        ) {
+            Some(EventHandler::Simple(cmd)) => Some(cmd),
+            _ => None,
+        };
+        assert_eq!(
+            Some(Cmd::Abort),
+            cmd(&mut editor, Keymap::Emacs, E::ctrl('A'))
+        );
+        assert_eq!(None, cmd(&mut editor, Keymap::ViInsert, E::ctrl('A')));
+        assert_eq!(
+            Some(Cmd::Undo(1)),
+            cmd(&mut editor, Keymap::ViCommand, E::ctrl('B'))
+        );
+        assert_eq!(None, cmd(&mut editor, Keymap::Emacs, E::ctrl('B')));
+        assert_eq!(
+            Some(Cmd::Kill(crate::Movement::WholeLine)),
+            cmd(&mut editor, Keymap::ViInsert, E::ctrl('C'))
+        );
+        assert!(matches!(
+            editor.unbind_sequence_in_layer(
+                Layer::User,
+                Some(Keymap::Emacs),
+                Event::KeySeq(vec![E::ctrl('X'), E::ctrl('D')].into())
+            ),
+            Some(EventHandler::Simple(Cmd::Yank(..)))
+        ));
+        assert!(matches!(
+            cmd(&mut editor, Keymap::Emacs, E::alt('f')),
+            Some(Cmd::Move(_))
+        ));
+        // editing-mode resets the keymap
+        parse(
+            &mut editor,
+            None,
+            "set editing-mode vi
+C-e: abort
+",
+        );
+        assert_eq!(
+            Some(Cmd::Abort),
+            cmd(&mut editor, Keymap::ViInsert, E::ctrl('E'))
+        );
+    }
+
+    #[test]
+    fn mode() {
+        let mut editor = Editor::<()>::new();
+        parse(
+            &mut editor,
+            None,
+            "$if mode=vi\n\
+             C-a: abort\n\
+             $else\n\
+             C-a: undo\n\
+             $endif\n\
+             set editing-mode vi\n\
+             $if mode=vi\n\
+             C-b: abort\n\
+             $endif\n",
+        );
+        assert_eq!(Some(Cmd::Undo(1)), binding(&mut editor, E::ctrl('A')));
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('B')));
+    }
+
+    #[test]
+    fn term() {
+        let text = "$if term=xterm\nC-a: abort\n$endif\n";
+        let mut editor = Editor::<()>::new();
+        parse(&mut editor, Some("xterm-256color"), text);
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('A')));
+        parse(&mut editor, Some("xterm"), text);
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('A')));
+        parse(&mut editor, Some("rxvt"), text);
+        assert_eq!(None, binding(&mut editor, E::ctrl('A')));
+        parse(&mut editor, None, text);
+        assert_eq!(None, binding(&mut editor, E::ctrl('A')));
+    }
+
+    #[test]
+    fn application() {
+        let text = "$if Bash\nC-a: abort\n$else\nC-b: abort\n$endif\n";
+        let mut editor = Editor::<()>::new();
+        parse(&mut editor, None, text);
+        assert_eq!(None, binding(&mut editor, E::ctrl('A')));
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('B')));
+        editor.set_application_name("bash");
+        parse(&mut editor, None, text);
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('A')));
+        assert_eq!(None, binding(&mut editor, E::ctrl('B')));
+    }
+
+    #[test]
+    fn nested() {
+        let mut editor = Editor::<()>::new();
+        editor.set_edit_mode(EditMode::Emacs);
+        parse(
+            &mut editor,
+            Some("xterm"),
+            "$if mode=vi\n\
+             $if term=xterm\n\
+             C-a: abort\n\
+             $else\n\
+             C-b: abort\n\
+             $endif\n\
+             $else\n\
+             $if term=rxvt\n\
+             C-c: abort\n\
+             $else\n\
+             C-d: abort\n\
+             $endif\n\
+             $endif\n\
+             $endif\n\
+             C-e: abort\n",
+        );
+        assert_eq!(None, binding(&mut editor, E::ctrl('A')));
+        assert_eq!(None, binding(&mut editor, E::ctrl('B')));
+        assert_eq!(None, binding(&mut editor, E::ctrl('C')));
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('D')));
+        // unbalanced `$endif` is ignored
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('E')));
+    }
+
+    #[test]
+    fn include() {
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("inputrc"),
+            "$include bindings\n$include missing\nC-b: abort\n",
+        )
+        .unwrap();
+        // unbalanced `$if` in an included file doesn't leak
+        fs::write(dir.path().join("bindings"), "C-a: abort\n$if mode=vi\n").unwrap();
+        let mut editor = Editor::<()>::new();
+        let mut errors = Vec::new();
+        editor
+            .load_inputrc_with(&dir.path().join("inputrc"), |err| errors.push(err.clone()))
+            .unwrap();
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('A')));
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('B')));
+        assert_eq!(2, errors.len());
+        assert_eq!(Some(dir.path().join("bindings")), errors[0].file);
+        assert_eq!("missing $endif", errors[0].reason);
+        assert_eq!(Some(dir.path().join("inputrc")), errors[1].file);
+        assert_eq!((2, "missing"), (errors[1].line, errors[1].text.as_str()));
+        // but a missing top-level file is an error
+        assert!(Editor::<()>::new()
+            .load_inputrc(&dir.path().join("missing"))
+            .is_err());
+    }
+
+    #[test]
+    fn reload() {
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("inputrc");
+        fs::write(&path, "C-a: abort\n$include local\n").unwrap();
+        let mut editor = Editor::<()>::new();
+        editor.bind_sequence_in(Keymap::Emacs, E::ctrl('C'), Cmd::Interrupt);
+        editor.load_inputrc(&path).unwrap();
+        assert!(!editor.inputrc.is_modified());
+        let bound = |editor: &Editor<()>, key: E| {
+            let bindings = editor.custom_bindings.read().unwrap();
+            bindings.get(Keymap::Emacs, &Event::from(key)).is_some()
+        };
+        assert!(bound(&editor, E::ctrl('A')));
+
+        // creating an included file is a modification
+        fs::write(dir.path().join("local"), "C-b: undo\n").unwrap();
+        assert!(editor.inputrc.is_modified());
+        fs::write(&path, "$include local\n").unwrap();
+        editor.reload_inputrc().unwrap();
+        assert!(!bound(&editor, E::ctrl('A')));
+        assert!(bound(&editor, E::ctrl('B')));
+        // bindings not made by the inputrc file are kept
+        assert!(bound(&editor, E::ctrl('C')));
+        assert!(!editor.inputrc.is_modified());
+
+        fs::remove_file(dir.path().join("local")).unwrap();
+        assert!(editor.inputrc.is_modified());
+    }
+
+    #[test]
+    fn include_cycle() {
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "$include b\nC-a: abort\n").unwrap();
+        fs::write(dir.path().join("b"), "$include a\nC-b: abort\n").unwrap();
+        let mut editor = Editor::<()>::new();
+        editor.load_inputrc(&dir.path().join("a")).unwrap();
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('A')));
+        assert_eq!(Some(Cmd::Abort), binding(&mut editor, E::ctrl('B')));
+    }
+
+    #[test]
+    fn include_depth() {
+        use std::fs;
+
+        let dir = tempfile::tempdir().unwrap();
+        let keys = "ABCDEFGHIJKL";
+        for (i, key) in keys.chars().enumerate() {
+            fs::write(
+                dir.path().join(i.to_string()),
+                format!("$include {}\nC-{}: abort\n", i + 1, key),
+            )
+            .unwrap();
+        }
+        let mut editor = Editor::<()>::new();
+        editor.load_inputrc(&dir.path().join("0")).unwrap();
+        for (i, key) in keys.chars().enumerate() {
+            let expected = if i < super::MAX_INCLUDE_DEPTH {
+                Some(Cmd::Abort)
+            } else {
+                None
+            };
+            assert_eq!(expected, binding(&mut editor, E::ctrl(key)));
+        }
+    }
+}
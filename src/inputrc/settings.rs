@@ -0,0 +1,172 @@
+//! Structured representation of variables and custom key bindings
+use std::collections::BTreeMap;
+
+use log::debug;
+#[cfg(feature = "with-serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{chars, handler, keymap, keymap_name, macro_handler, prefixed, to_event};
+use crate::history::HistoryStore;
+use crate::{Editor, Helper, Keymap, Layer};
+
+/// Readline variables and custom key bindings, as exported by
+/// [`Editor::settings`](crate::Editor::settings) and imported by
+/// [`Editor::apply_settings`](crate::Editor::apply_settings).
+///
+/// With the `with-serde` feature, it can be (de)serialized so that
+/// applications can ship a TOML or JSON file instead of an inputrc file:
+/// ```toml
+/// [variables]
+/// editing-mode = "vi"
+///
+/// [keymaps.vi-insert.functions]
+/// "\\C-u" = "kill-whole-line"
+///
+/// [keymaps.vi-insert.macros]
+/// "\\C-xl" = "ls -l\n"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(default))]
+pub struct Settings {
+    /// Variable values, by name (`set name value`)
+    pub variables: BTreeMap<String, String>,
+    /// Key bindings, by keymap name (`emacs`, `vi-command` or `vi-insert`)
+    pub keymaps: BTreeMap<String, KeymapSettings>,
+}
+
+/// Key bindings of one keymap
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "with-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "with-serde", serde(default))]
+pub struct KeymapSettings {
+    /// Readline function names, by key sequence (`"\C-u": kill-whole-line`)
+    pub functions: BTreeMap<String, String>,
+    /// Macro texts, by key sequence (`"\C-xl": "ls -l\n"`)
+    pub macros: BTreeMap<String, String>,
+}
+
+impl KeymapSettings {
+    fn is_empty(&self) -> bool {
+        self.functions.is_empty() && self.macros.is_empty()
+    }
+}
+
+pub(crate) fn export<H: Helper, S: HistoryStore>(editor: &Editor<H, S>) -> Settings {
+    let variables = editor
+        .variables()
+        .map(|(name, value)| (name.to_owned(), value))
+        .collect();
+    let bindings = editor.custom_bindings.read().unwrap();
+    let keymaps = [Keymap::Emacs, Keymap::ViCommand, Keymap::ViInsert]
+        .iter()
+        .map(|&keymap| {
+            let functions = super::functions(&bindings, keymap)
+                .into_iter()
+                .map(|(seq, name)| (seq, name.to_owned()))
+                .collect();
+            let macros = super::macros(&bindings, keymap).into_iter().collect();
+            (
+                keymap_name(keymap).to_owned(),
+                KeymapSettings { functions, macros },
+            )
+        })
+        .filter(|(_, settings)| !settings.is_empty())
+        .collect();
+    Settings { variables, keymaps }
+}
+
+/// Invalid entries are ignored.
+pub(crate) fn import<H: Helper, S: HistoryStore>(editor: &mut Editor<H, S>, settings: &Settings) {
+    for (name, value) in &settings.variables {
+        if let Err(err) = editor.set_variable(name, value) {
+            debug!(target: "rustyline", "{} {}: {}", name, value, err);
+        }
+    }
+    for (name, keymap_settings) in &settings.keymaps {
+        let (keymap, prefix) = match keymap(name) {
+            Some(keymap) => keymap,
+            None => {
+                debug!(target: "rustyline", "{}: invalid keymap", name);
+                continue;
+            }
+        };
+        for (seq, name) in &keymap_settings.functions {
+            match (chars::unescape_sequence(seq), handler(editor, name)) {
+                (Some(keys), Some(handler)) => {
+                    editor.bind_sequence_in_layer(
+                        Layer::User,
+                        Some(keymap),
+                        to_event(&prefixed(prefix, &keys)),
+                        handler,
+                    );
+                }
+                _ => debug!(target: "rustyline", "{}: {}: invalid binding", seq, name),
+            }
+        }
+        for (seq, text) in &keymap_settings.macros {
+            match chars::unescape_sequence(seq) {
+                Some(keys) => {
+                    editor.bind_sequence_in_layer(
+                        Layer::User,
+                        Some(keymap),
+                        to_event(&prefixed(prefix, &keys)),
+                        macro_handler(text),
+                    );
+                }
+                None => debug!(target: "rustyline", "{}: invalid key sequence", seq),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{KeymapSettings, Settings};
+    use crate::config::EditMode;
+    use crate::Editor;
+
+    #[test]
+    fn round_trip() {
+        let mut editor = Editor::<()>::new();
+        editor.apply_inputrc(&crate::inputrc::parse(
+            "set editing-mode vi\n\
+             set keymap vi-insert\n\
+             C-u: kill-whole-line\n\
+             \"\\C-xl\": \"ls -l\\n\"\n",
+        ));
+        let settings = editor.settings();
+        assert_eq!("vi", settings.variables["editing-mode"]);
+        assert_eq!(1, settings.keymaps.len());
+        let vi_insert = &settings.keymaps["vi-insert"];
+        assert_eq!("kill-whole-line", vi_insert.functions["\\C-u"]);
+        assert_eq!("ls -l\n", vi_insert.macros["\\C-xl"]);
+
+        let mut other = Editor::<()>::new();
+        other.apply_settings(&settings);
+        assert_eq!(EditMode::Vi, other.config.edit_mode());
+        assert_eq!(settings, other.settings());
+    }
+
+    #[test]
+    fn invalid_entries() {
+        let mut settings = Settings::default();
+        settings
+            .variables
+            .insert("history-size".to_owned(), "x".to_owned());
+        let mut keymap = KeymapSettings::default();
+        keymap
+            .functions
+            .insert("\\C-u".to_owned(), "no-such-function".to_owned());
+        keymap
+            .functions
+            .insert("\\C-w".to_owned(), "unix-word-rubout".to_owned());
+        settings.keymaps.insert("emacs".to_owned(), keymap.clone());
+        settings.keymaps.insert("no-such-keymap".to_owned(), keymap);
+        let mut editor = Editor::<()>::new();
+        editor.apply_settings(&settings);
+        let exported = editor.settings();
+        assert_eq!("100", exported.variables["history-size"]);
+        assert_eq!(1, exported.keymaps["emacs"].functions.len());
+    }
+}
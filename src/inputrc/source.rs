@@ -0,0 +1,170 @@
+//! Inputrc file lookup
+use std::env;
+use std::path::PathBuf;
+
+/// Location of an inputrc file
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Location {
+    /// `$INPUTRC`
+    Env,
+    /// `~/.inputrc`
+    Home,
+    /// `/etc/inputrc`
+    System,
+    /// `$XDG_CONFIG_HOME/<name>/inputrc` or `~/.config/<name>/inputrc`
+    Application(String),
+    /// A specific file
+    File(PathBuf),
+}
+
+/// Inputrc search path.
+///
+/// Locations are tried in the order they have been added and the first
+/// existing file is used. By default, like readline: `$INPUTRC`,
+/// `~/.inputrc` then `/etc/inputrc`.
+/// ```
+/// use rustyline::inputrc::InputrcSource;
+///
+/// // application specific file first, then readline's locations
+/// let source = InputrcSource::new()
+///     .application("myapp")
+///     .env()
+///     .home()
+///     .system();
+/// let mut rl = rustyline::Editor::<()>::new();
+/// rl.load_inputrc_from(&source).unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputrcSource {
+    locations: Vec<Location>,
+}
+
+impl InputrcSource {
+    /// Empty search path.
+    pub fn new() -> Self {
+        Self {
+            locations: Vec::new(),
+        }
+    }
+
+    /// Add the file named by the `INPUTRC` environment variable (ignored if
+    /// unset or empty).
+    pub fn env(self) -> Self {
+        self.add(Location::Env)
+    }
+
+    /// Add `~/.inputrc`.
+    pub fn home(self) -> Self {
+        self.add(Location::Home)
+    }
+
+    /// Add `/etc/inputrc`.
+    pub fn system(self) -> Self {
+        self.add(Location::System)
+    }
+
+    /// Add the application specific file: `~/.config/<name>/inputrc` (or
+    /// `$XDG_CONFIG_HOME/<name>/inputrc`).
+    pub fn application<S: Into<String>>(self, name: S) -> Self {
+        self.add(Location::Application(name.into()))
+    }
+
+    /// Add a specific file.
+    pub fn file<P: Into<PathBuf>>(self, path: P) -> Self {
+        self.add(Location::File(path.into()))
+    }
+
+    fn add(mut self, location: Location) -> Self {
+        self.locations.push(location);
+        self
+    }
+
+    /// Return the candidate files, in order.
+    ///
+    /// Locations which cannot be determined (e.g. unknown home directory) are
+    /// skipped.
+    pub fn candidates(&self) -> Vec<PathBuf> {
+        self.locations
+            .iter()
+            .filter_map(|location| match location {
+                Location::Env => env::var_os("INPUTRC")
+                    .filter(|path| !path.is_empty())
+                    .map(PathBuf::from),
+                Location::Home => home_dir().map(|home| home.join(".inputrc")),
+                Location::System => Some(PathBuf::from("/etc/inputrc")),
+                Location::Application(name) => {
+                    config_dir().map(|config| config.join(name).join("inputrc"))
+                }
+                Location::File(path) => Some(path.clone()),
+            })
+            .collect()
+    }
+
+    /// Return the first existing candidate file.
+    pub fn resolve(&self) -> Option<PathBuf> {
+        self.candidates().into_iter().find(|path| path.is_file())
+    }
+}
+
+/// `$INPUTRC`, `~/.inputrc` then `/etc/inputrc`
+impl Default for InputrcSource {
+    fn default() -> Self {
+        Self::new().env().home().system()
+    }
+}
+
+#[cfg(feature = "with-dirs")]
+pub(super) fn home_dir() -> Option<PathBuf> {
+    dirs_next::home_dir()
+}
+
+#[cfg(not(feature = "with-dirs"))]
+pub(super) fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| home_dir().map(|home| home.join(".config")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::InputrcSource;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn candidates() {
+        let source = InputrcSource::new()
+            .file("/tmp/first")
+            .application("myapp")
+            .system();
+        let candidates = source.candidates();
+        assert_eq!(Path::new("/tmp/first"), candidates[0]);
+        if candidates.len() == 3 {
+            assert!(candidates[1].ends_with("myapp/inputrc"));
+        }
+        assert_eq!(Path::new("/etc/inputrc"), candidates[candidates.len() - 1]);
+        assert!(InputrcSource::new().candidates().is_empty());
+    }
+
+    #[test]
+    fn resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first");
+        let second = dir.path().join("second");
+        fs::write(&second, "").unwrap();
+        let source = InputrcSource::new().file(&first).file(&second);
+        assert_eq!(Some(second.clone()), source.resolve());
+        fs::write(&first, "").unwrap();
+        assert_eq!(Some(first), source.resolve());
+        // directories are skipped
+        let source = InputrcSource::new().file(dir.path());
+        assert_eq!(None, source.resolve());
+    }
+}
@@ -0,0 +1,443 @@
+//! Readline variables (`set name value`)
+use std::error::Error;
+use std::fmt;
+
+use super::chars::{escape_macro, unescape_macro};
+use crate::config::{BellStyle, Config, Configurer, EditMode};
+
+/// Readline variable supported by `set`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Variable {
+    /// `bell-style`: `none`, `audible` or `visible`
+    BellStyle,
+    /// `comment-begin`: inserted by `insert-comment`
+    CommentBegin,
+    /// `completion-display-width`: negative for the screen width
+    CompletionDisplayWidth,
+    /// `completion-ignore-case`
+    CompletionIgnoreCase,
+    /// `completion-query-items`
+    CompletionQueryItems,
+    /// `convert-meta`
+    ConvertMeta,
+    /// `editing-mode`: `emacs` or `vi`
+    EditingMode,
+    /// `emacs-mode-string`
+    EmacsModeString,
+    /// `enable-bracketed-paste`
+    EnableBracketedPaste,
+    /// `history-size`
+    HistorySize,
+    /// `input-meta` (or `meta-flag`)
+    InputMeta,
+    /// `keyseq-timeout`: milliseconds, negative to wait indefinitely
+    KeyseqTimeout,
+    /// `mark-directories`
+    MarkDirectories,
+    /// `menu-complete-display-prefix`
+    MenuCompleteDisplayPrefix,
+    /// `output-meta`
+    OutputMeta,
+    /// `page-completions`
+    PageCompletions,
+    /// `prefer-visible-bell`
+    PreferVisibleBell,
+    /// `print-completions-horizontally`
+    PrintCompletionsHorizontally,
+    /// `show-all-if-ambiguous`
+    ShowAllIfAmbiguous,
+    /// `show-all-if-unmodified`
+    ShowAllIfUnmodified,
+    /// `show-mode-in-prompt`
+    ShowModeInPrompt,
+    /// `vi-cmd-mode-string`
+    ViCmdModeString,
+    /// `vi-ins-mode-string`
+    ViInsModeString,
+}
+
+impl Variable {
+    /// All known variables, sorted by name
+    pub const ALL: &'static [Variable] = &[
+        Variable::BellStyle,
+        Variable::CommentBegin,
+        Variable::CompletionDisplayWidth,
+        Variable::CompletionIgnoreCase,
+        Variable::CompletionQueryItems,
+        Variable::ConvertMeta,
+        Variable::EditingMode,
+        Variable::EmacsModeString,
+        Variable::EnableBracketedPaste,
+        Variable::HistorySize,
+        Variable::InputMeta,
+        Variable::KeyseqTimeout,
+        Variable::MarkDirectories,
+        Variable::MenuCompleteDisplayPrefix,
+        Variable::OutputMeta,
+        Variable::PageCompletions,
+        Variable::PreferVisibleBell,
+        Variable::PrintCompletionsHorizontally,
+        Variable::ShowAllIfAmbiguous,
+        Variable::ShowAllIfUnmodified,
+        Variable::ShowModeInPrompt,
+        Variable::ViCmdModeString,
+        Variable::ViInsModeString,
+    ];
+
+    /// Readline name
+    pub fn name(self) -> &'static str {
+        match self {
+            Variable::BellStyle => "bell-style",
+            Variable::CommentBegin => "comment-begin",
+            Variable::CompletionDisplayWidth => "completion-display-width",
+            Variable::CompletionIgnoreCase => "completion-ignore-case",
+            Variable::CompletionQueryItems => "completion-query-items",
+            Variable::ConvertMeta => "convert-meta",
+            Variable::EditingMode => "editing-mode",
+            Variable::EmacsModeString => "emacs-mode-string",
+            Variable::EnableBracketedPaste => "enable-bracketed-paste",
+            Variable::HistorySize => "history-size",
+            Variable::InputMeta => "input-meta",
+            Variable::KeyseqTimeout => "keyseq-timeout",
+            Variable::MarkDirectories => "mark-directories",
+            Variable::MenuCompleteDisplayPrefix => "menu-complete-display-prefix",
+            Variable::OutputMeta => "output-meta",
+            Variable::PageCompletions => "page-completions",
+            Variable::PreferVisibleBell => "prefer-visible-bell",
+            Variable::PrintCompletionsHorizontally => "print-completions-horizontally",
+            Variable::ShowAllIfAmbiguous => "show-all-if-ambiguous",
+            Variable::ShowAllIfUnmodified => "show-all-if-unmodified",
+            Variable::ShowModeInPrompt => "show-mode-in-prompt",
+            Variable::ViCmdModeString => "vi-cmd-mode-string",
+            Variable::ViInsModeString => "vi-ins-mode-string",
+        }
+    }
+
+    /// Lookup a variable by its readline name (case insensitive).
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("meta-flag") {
+            return Some(Variable::InputMeta);
+        }
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|v| v.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Current value, formatted like readline does (`on` / `off` for
+    /// booleans).
+    pub fn get(self, config: &Config) -> String {
+        match self {
+            Variable::BellStyle => match config.bell_style() {
+                BellStyle::None => "none",
+                BellStyle::Audible => "audible",
+                BellStyle::Visible => "visible",
+            }
+            .to_owned(),
+            Variable::CommentBegin => config.comment_begin().to_owned(),
+            Variable::CompletionDisplayWidth => match config.completion_display_width() {
+                Some(width) => width.to_string(),
+                None => "-1".to_owned(),
+            },
+            Variable::CompletionIgnoreCase => bool(config.completion_ignore_case()),
+            Variable::CompletionQueryItems => match config.completion_prompt_limit() {
+                usize::MAX => "-1".to_owned(),
+                limit => limit.to_string(),
+            },
+            Variable::ConvertMeta => bool(config.convert_meta()),
+            Variable::EditingMode => match config.edit_mode() {
+                EditMode::Emacs => "emacs",
+                EditMode::Vi => "vi",
+                EditMode::Custom(name) => name,
+            }
+            .to_owned(),
+            Variable::EmacsModeString => escape_macro(config.emacs_mode_string()),
+            Variable::EnableBracketedPaste => bool(config.enable_bracketed_paste()),
+            Variable::HistorySize => config.max_history_size().to_string(),
+            Variable::InputMeta => bool(config.input_meta()),
+            Variable::KeyseqTimeout => config.keyseq_timeout().to_string(),
+            Variable::MarkDirectories => bool(config.mark_directories()),
+            Variable::MenuCompleteDisplayPrefix => bool(config.menu_complete_display_prefix()),
+            Variable::OutputMeta => bool(config.output_meta()),
+            Variable::PageCompletions => bool(config.page_completions()),
+            Variable::PreferVisibleBell => bool(config.bell_style() == BellStyle::Visible),
+            Variable::PrintCompletionsHorizontally => bool(config.print_completions_horizontally()),
+            Variable::ShowAllIfAmbiguous => bool(config.completion_show_all_if_ambiguous()),
+            Variable::ShowAllIfUnmodified => bool(config.completion_show_all_if_unmodified()),
+            Variable::ShowModeInPrompt => bool(config.show_mode_in_prompt()),
+            Variable::ViCmdModeString => escape_macro(config.vi_cmd_mode_string()),
+            Variable::ViInsModeString => escape_macro(config.vi_ins_mode_string()),
+        }
+    }
+
+    /// Parse `value` and update `configurer`.
+    pub fn set<C: Configurer + ?Sized>(
+        self,
+        configurer: &mut C,
+        value: &str,
+    ) -> Result<(), VariableError> {
+        match self {
+            Variable::BellStyle => {
+                let bell_style = match value.to_ascii_lowercase().as_str() {
+                    "none" | "off" => BellStyle::None,
+                    "" | "audible" | "on" => BellStyle::Audible,
+                    "visible" => BellStyle::Visible,
+                    _ => return Err(VariableError::InvalidValue),
+                };
+                configurer.set_bell_style(bell_style);
+            }
+            Variable::CommentBegin => {
+                // a quoted value may end with spaces
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                configurer.set_comment_begin(value)
+            }
+            Variable::CompletionDisplayWidth => {
+                let width = match value.parse::<i64>() {
+                    Ok(width) if width < 0 => None,
+                    _ => Some(number(value)?),
+                };
+                configurer.set_completion_display_width(width)
+            }
+            Variable::CompletionIgnoreCase => configurer.set_completion_ignore_case(on(value)),
+            Variable::CompletionQueryItems => {
+                // like readline, a negative value means never ask
+                let limit = if value.starts_with('-') && value[1..].parse::<usize>().is_ok() {
+                    usize::MAX
+                } else {
+                    number(value)?
+                };
+                configurer.set_completion_prompt_limit(limit)
+            }
+            Variable::EditingMode => {
+                let edit_mode = if value.eq_ignore_ascii_case("emacs") {
+                    EditMode::Emacs
+                } else if value.eq_ignore_ascii_case("vi") {
+                    EditMode::Vi
+                } else {
+                    return Err(VariableError::InvalidValue);
+                };
+                configurer.set_edit_mode(edit_mode);
+            }
+            Variable::EmacsModeString => configurer.set_emacs_mode_string(&mode_string(value)?),
+            Variable::ConvertMeta => configurer.set_convert_meta(on(value)),
+            Variable::EnableBracketedPaste => configurer.enable_bracketed_paste(on(value)),
+            Variable::HistorySize => configurer.set_max_history_size(number(value)?),
+            Variable::InputMeta => configurer.set_input_meta(on(value)),
+            Variable::KeyseqTimeout => {
+                // like readline, zero or a non-numeric value means no timeout
+                let timeout = value.parse().ok().filter(|&ms: &i32| ms > 0);
+                configurer.set_keyseq_timeout(timeout.unwrap_or(-1))
+            }
+            Variable::MarkDirectories => configurer.set_mark_directories(on(value)),
+            Variable::MenuCompleteDisplayPrefix => {
+                configurer.set_menu_complete_display_prefix(on(value))
+            }
+            Variable::OutputMeta => configurer.set_output_meta(on(value)),
+            Variable::PageCompletions => configurer.set_page_completions(on(value)),
+            Variable::PreferVisibleBell => {
+                if on(value) {
+                    configurer.set_bell_style(BellStyle::Visible);
+                } else if configurer.config_mut().bell_style() == BellStyle::Visible {
+                    configurer.set_bell_style(BellStyle::Audible);
+                }
+            }
+            Variable::PrintCompletionsHorizontally => {
+                configurer.set_print_completions_horizontally(on(value))
+            }
+            Variable::ShowAllIfAmbiguous => {
+                configurer.set_completion_show_all_if_ambiguous(on(value))
+            }
+            Variable::ShowAllIfUnmodified => {
+                configurer.set_completion_show_all_if_unmodified(on(value))
+            }
+            Variable::ShowModeInPrompt => configurer.set_show_mode_in_prompt(on(value)),
+            Variable::ViCmdModeString => configurer.set_vi_cmd_mode_string(&mode_string(value)?),
+            Variable::ViInsModeString => configurer.set_vi_ins_mode_string(&mode_string(value)?),
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Variable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when a variable cannot be set
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VariableError {
+    /// No such variable
+    UnknownName,
+    /// Value not accepted by the variable
+    InvalidValue,
+}
+
+impl fmt::Display for VariableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VariableError::UnknownName => f.write_str("unknown variable name"),
+            VariableError::InvalidValue => f.write_str("invalid value"),
+        }
+    }
+}
+
+impl Error for VariableError {}
+
+/// Boolean variable value: like readline, an empty value, `on` or `1` mean
+/// on, anything else means off.
+fn on(value: &str) -> bool {
+    value.is_empty() || value.eq_ignore_ascii_case("on") || value == "1"
+}
+
+fn bool(value: bool) -> String {
+    if value { "on" } else { "off" }.to_owned()
+}
+
+fn number(value: &str) -> Result<usize, VariableError> {
+    value.parse().map_err(|_| VariableError::InvalidValue)
+}
+
+/// Mode string value: possibly quoted, with escapes like a macro, and
+/// `\1` / `\2` around the invisible characters (ignored, escape sequences
+/// being measured as such).
+fn mode_string(value: &str) -> Result<String, VariableError> {
+    let value = value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value);
+    let mut value = unescape_macro(value).ok_or(VariableError::InvalidValue)?;
+    value.retain(|c| c != '\x01' && c != '\x02');
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Variable, VariableError};
+    use crate::config::{BellStyle, EditMode};
+    use crate::Editor;
+
+    #[test]
+    fn names() {
+        for v in Variable::ALL {
+            assert_eq!(Some(*v), Variable::from_name(v.name()));
+        }
+        assert_eq!(
+            Some(Variable::EditingMode),
+            Variable::from_name("Editing-Mode")
+        );
+        assert_eq!(None, Variable::from_name("keymap"));
+        assert_eq!(Some(Variable::InputMeta), Variable::from_name("meta-flag"));
+        let mut names: Vec<_> = Variable::ALL.iter().map(|v| v.name()).collect();
+        names.sort_unstable();
+        assert!(names.iter().zip(Variable::ALL).all(|(n, v)| *n == v.name()));
+    }
+
+    #[test]
+    fn get_set() {
+        let mut rl = Editor::<()>::new();
+        assert_eq!(Some("emacs".to_owned()), rl.get_variable("editing-mode"));
+        assert_eq!(Ok(()), rl.set_variable("editing-mode", "VI"));
+        assert_eq!(EditMode::Vi, rl.config.edit_mode());
+        assert_eq!(Some("vi".to_owned()), rl.get_variable("editing-mode"));
+        assert_eq!(
+            Err(VariableError::InvalidValue),
+            rl.set_variable("editing-mode", "ed")
+        );
+        assert_eq!(
+            Err(VariableError::UnknownName),
+            rl.set_variable("no-such-variable", "on")
+        );
+        assert_eq!(None, rl.get_variable("no-such-variable"));
+
+        assert_eq!(Ok(()), rl.set_variable("history-size", "10"));
+        assert_eq!(10, rl.config.max_history_size());
+        assert_eq!(
+            Err(VariableError::InvalidValue),
+            rl.set_variable("history-size", "-1")
+        );
+
+        assert_eq!(Ok(()), rl.set_variable("completion-ignore-case", ""));
+        assert!(rl.config.completion_ignore_case());
+        assert_eq!(Some("#".to_owned()), rl.get_variable("comment-begin"));
+        assert_eq!(Ok(()), rl.set_variable("comment-begin", "\"// \""));
+        assert_eq!("// ", rl.config.comment_begin());
+        assert_eq!(Ok(()), rl.set_variable("comment-begin", "--"));
+        assert_eq!(Some("--".to_owned()), rl.get_variable("comment-begin"));
+        assert_eq!(Ok(()), rl.set_variable("keyseq-timeout", "250"));
+        assert_eq!(250, rl.config.keyseq_timeout());
+        assert_eq!(Ok(()), rl.set_variable("keyseq-timeout", "0"));
+        assert_eq!(Some("-1".to_owned()), rl.get_variable("keyseq-timeout"));
+        assert_eq!(Ok(()), rl.set_variable("convert-meta", "on"));
+        assert!(rl.config.convert_meta());
+        assert_eq!(Ok(()), rl.set_variable("meta-flag", "off"));
+        assert_eq!(Some("off".to_owned()), rl.get_variable("input-meta"));
+        assert_eq!(Ok(()), rl.set_variable("enable-bracketed-paste", "off"));
+        assert!(!rl.config.enable_bracketed_paste());
+        assert!(!rl.term.enable_bracketed_paste);
+        assert_eq!(Ok(()), rl.set_variable("mark-directories", "off"));
+        assert_eq!(Some("off".to_owned()), rl.get_variable("mark-directories"));
+        assert_eq!(Ok(()), rl.set_variable("show-all-if-unmodified", "on"));
+        assert!(rl.config.completion_show_all_if_unmodified());
+        assert_eq!(Ok(()), rl.set_variable("completion-display-width", "0"));
+        assert_eq!(Some(0), rl.config.completion_display_width());
+        assert_eq!(Ok(()), rl.set_variable("completion-display-width", "-1"));
+        assert_eq!(None, rl.config.completion_display_width());
+        assert_eq!(
+            Ok(()),
+            rl.set_variable("print-completions-horizontally", "on")
+        );
+        assert!(rl.config.print_completions_horizontally());
+        assert_eq!(Ok(()), rl.set_variable("page-completions", "off"));
+        assert!(!rl.config.page_completions());
+        assert_eq!(Ok(()), rl.set_variable("completion-query-items", "-1"));
+        assert_eq!(usize::MAX, rl.config.completion_prompt_limit());
+        assert_eq!(
+            Some("-1".to_owned()),
+            rl.get_variable("completion-query-items")
+        );
+
+        assert_eq!(Ok(()), rl.set_variable("prefer-visible-bell", "on"));
+        assert_eq!(BellStyle::Visible, rl.config.bell_style());
+        assert_eq!(Some("visible".to_owned()), rl.get_variable("bell-style"));
+        assert_eq!(Ok(()), rl.set_variable("prefer-visible-bell", "off"));
+        assert_eq!(BellStyle::Audible, rl.config.bell_style());
+        assert_eq!(Ok(()), rl.set_variable("bell-style", "none"));
+        assert_eq!(
+            Some("off".to_owned()),
+            rl.get_variable("prefer-visible-bell")
+        );
+
+        assert_eq!(Ok(()), rl.set_variable("show-mode-in-prompt", "on"));
+        assert!(rl.config.show_mode_in_prompt());
+        assert_eq!(
+            Some("(cmd)".to_owned()),
+            rl.get_variable("vi-cmd-mode-string")
+        );
+        assert_eq!(
+            Ok(()),
+            rl.set_variable("vi-ins-mode-string", "\"\\1\\e[1m\\2[I]\\1\\e[0m\\2 \"")
+        );
+        assert_eq!("\x1b[1m[I]\x1b[0m ", rl.config.vi_ins_mode_string());
+        assert_eq!(
+            Some("\\e[1m[I]\\e[0m ".to_owned()),
+            rl.get_variable("vi-ins-mode-string")
+        );
+        assert_eq!(Ok(()), rl.set_variable("emacs-mode-string", "[E]"));
+        assert_eq!("[E]", rl.config.emacs_mode_string());
+    }
+
+    #[test]
+    fn variables() {
+        let rl = Editor::<()>::new();
+        let variables: Vec<_> = rl.variables().collect();
+        assert_eq!(Variable::ALL.len(), variables.len());
+        assert!(variables.contains(&("editing-mode", "emacs".to_owned())));
+        assert!(variables.contains(&("show-all-if-ambiguous", "off".to_owned())));
+    }
+}
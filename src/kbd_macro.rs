@@ -0,0 +1,171 @@
+//! Keyboard macros (`C-x (`, `C-x )` and `C-x e`)
+use std::collections::VecDeque;
+use std::fmt;
+
+use crate::inputrc::chars;
+use crate::keys::KeyEvent;
+
+/// Recorded keyboard macro: the keys typed between `start-kbd-macro` and
+/// `end-kbd-macro`.
+///
+/// It can be persisted as an inputrc key sequence:
+/// ```
+/// use rustyline::{KeyEvent, Macro};
+///
+/// let kbd_macro = Macro::new(vec![KeyEvent::ctrl('A'), KeyEvent::from('#')]);
+/// let seq = kbd_macro.to_sequence().unwrap();
+/// assert_eq!("\\C-a#", seq);
+/// assert_eq!(Some(kbd_macro), Macro::from_sequence(&seq));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Macro {
+    keys: Vec<KeyEvent>,
+}
+
+impl Macro {
+    /// Macro replaying `keys`
+    pub fn new(keys: Vec<KeyEvent>) -> Self {
+        Self { keys }
+    }
+
+    /// Parse the content of an inputrc key sequence like `\C-xls\r`.
+    pub fn from_sequence(seq: &str) -> Option<Self> {
+        let keys = chars::unescape_sequence(seq)?;
+        Some(Self::new(
+            keys.into_iter().map(KeyEvent::normalize).collect(),
+        ))
+    }
+
+    /// Format the keys as the content of an inputrc key sequence, or `None`
+    /// if some keys cannot be represented.
+    pub fn to_sequence(&self) -> Option<String> {
+        chars::escape_sequence(&self.keys)
+    }
+
+    /// Recorded keys
+    pub fn keys(&self) -> &[KeyEvent] {
+        &self.keys
+    }
+
+    /// Tell if no key has been recorded
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl From<Vec<KeyEvent>> for Macro {
+    fn from(keys: Vec<KeyEvent>) -> Self {
+        Self::new(keys)
+    }
+}
+
+impl fmt::Display for Macro {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_sequence() {
+            Some(seq) => write!(f, "\"{}\"", seq),
+            None => write!(f, "{:?}", self.keys),
+        }
+    }
+}
+
+/// Macro being recorded, last recorded one and keys being replayed.
+///
+/// Shared by successive `readline` calls so that a macro can span several
+/// lines.
+#[derive(Default)]
+pub(crate) struct MacroRecorder {
+    recording: Option<Vec<KeyEvent>>,
+    last: Option<Macro>,
+    playback: VecDeque<KeyEvent>,
+}
+
+impl MacroRecorder {
+    pub fn last(&self) -> Option<&Macro> {
+        self.last.as_ref()
+    }
+
+    pub fn set_last(&mut self, kbd_macro: Option<Macro>) {
+        self.last = kbd_macro;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Number of keys recorded so far
+    pub fn recorded(&self) -> Option<usize> {
+        self.recording.as_ref().map(Vec::len)
+    }
+
+    pub fn start(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Stop recording, ignoring the keys after the `len` first ones (i.e.
+    /// the keys bound to `end-kbd-macro`).
+    pub fn end(&mut self, len: usize) {
+        if let Some(mut keys) = self.recording.take() {
+            keys.truncate(len);
+            self.last = Some(Macro::new(keys));
+        }
+    }
+
+    /// Record a key read from the terminal.
+    pub fn record(&mut self, key: KeyEvent) {
+        if let Some(ref mut keys) = self.recording {
+            keys.push(key);
+        }
+    }
+
+    /// Replay the last macro `n` times, before the keys already replayed.
+    pub fn replay(&mut self, n: usize) {
+        if let Some(ref kbd_macro) = self.last {
+            for _ in 0..n {
+                for key in kbd_macro.keys.iter().rev() {
+                    self.playback.push_front(*key);
+                }
+            }
+        }
+    }
+
+    /// Next replayed key
+    pub fn next_key(&mut self) -> Option<KeyEvent> {
+        self.playback.pop_front()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.playback.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Macro, MacroRecorder};
+    use crate::keys::KeyEvent as E;
+
+    #[test]
+    fn record_replay() {
+        let mut recorder = MacroRecorder::default();
+        recorder.record(E::from('x'));
+        assert_eq!(None, recorder.recorded());
+        recorder.start();
+        recorder.record(E::from('a'));
+        recorder.record(E::ctrl('X'));
+        recorder.record(E::from(')'));
+        recorder.end(1);
+        assert!(!recorder.is_recording());
+        assert_eq!(Some(&Macro::new(vec![E::from('a')])), recorder.last());
+
+        recorder.replay(2);
+        assert_eq!(Some(E::from('a')), recorder.next_key());
+        assert!(recorder.is_playing());
+        assert_eq!(Some(E::from('a')), recorder.next_key());
+        assert_eq!(None, recorder.next_key());
+    }
+
+    #[test]
+    fn display() {
+        let kbd_macro = Macro::new(vec![E::from('l'), E::from('s'), E::ENTER]);
+        assert_eq!("\"ls\\r\"", kbd_macro.to_string());
+    }
+}
@@ -1,13 +1,20 @@
 //! Bindings from keys to command for Emacs and Vi modes
-use std::sync::{Arc, RwLock};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex, RwLock};
 
 use log::debug;
-use radix_trie::Trie;
 
 use super::Result;
+use crate::binding::Bindings;
+use crate::custom_mode::CustomModes;
+use crate::history::Direction;
+use crate::kbd_macro::MacroRecorder;
 use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
+use crate::keys::{MouseButton, MouseEvent, MouseEventKind};
+use crate::prompter::{CustomCommand, Prompter};
 use crate::tty::{RawReader, Term, Terminal};
-use crate::{Config, EditMode, Event, EventContext, EventHandler};
+use crate::{Config, EditMode, Event, EventContext, EventHandler, Keymap};
 
 /// The number of times one command should be repeated.
 pub type RepeatCount = usize;
@@ -24,8 +31,16 @@ pub enum Cmd {
     AcceptLine,
     /// beginning-of-history
     BeginningOfHistory,
+    /// call-last-kbd-macro
+    CallLastKbdMacro(RepeatCount),
     /// capitalize-word
-    CapitalizeWord,
+    CapitalizeWord(RepeatCount),
+    /// character-search: move to the next occurrence of the next typed
+    /// character
+    CharacterSearch(RepeatCount),
+    /// character-search-backward: move to the previous occurrence of the next
+    /// typed character
+    CharacterSearchBackward(RepeatCount),
     /// clear-screen
     ClearScreen,
     /// Paste from the clipboard
@@ -35,26 +50,68 @@ pub enum Cmd {
     Complete,
     /// complete-backward
     CompleteBackward,
-    /// complete-hint
+    /// accept-hint: insert the whole hint
     CompleteHint,
+    /// accept-hint-word: insert the first word of the hint
+    CompleteHintWord,
+    /// Application closure (see [`Cmd::custom`])
+    Custom(CustomCommand),
+    /// dabbrev-expand: complete the word before the cursor with a word of
+    /// the line or of the history, the next one when repeated
+    DabbrevExpand,
     /// Dedent current line
     Dedent(Movement),
+    /// delete-char-or-list: delete the character under the cursor, or list
+    /// the possible completions at the end of the line
+    DeleteCharOrList,
     /// downcase-word
-    DowncaseWord,
+    DowncaseWord(RepeatCount),
+    /// dump-functions: print the custom key bindings in inputrc format
+    DumpFunctions,
+    /// dump-macros: print the custom macros in inputrc format
+    DumpMacros,
+    /// dump-variables: print the readline variables in inputrc format
+    DumpVariables,
+    /// edit-and-execute-command (when `accept`) / edit-command-line: edit the
+    /// line with `$VISUAL` or `$EDITOR`
+    EditCommandLine {
+        /// Whether the edited line is accepted
+        accept: bool,
+    },
+    /// emacs-editing-mode
+    EmacsEditingMode,
+    /// end-kbd-macro
+    EndKbdMacro,
     /// vi-eof-maybe
     EndOfFile,
     /// end-of-history
     EndOfHistory,
+    /// exchange-point-and-mark
+    ExchangePointAndMark,
     /// forward-search-history
     ForwardSearchHistory,
+    /// history-expand-line: perform the history expansion of the line
+    HistoryExpandLine,
     /// history-search-backward
     HistorySearchBackward,
     /// history-search-forward
     HistorySearchForward,
+    /// history-substring-search-backward
+    HistorySubstringSearchBackward,
+    /// history-substring-search-forward
+    HistorySubstringSearchForward,
     /// Indent current line
     Indent(Movement),
     /// Insert text
     Insert(RepeatCount, String),
+    /// insert-comment: comment out the line and accept it
+    InsertComment {
+        /// Uncomment the line instead if it is already commented out (when
+        /// a numeric argument is given)
+        toggle: bool,
+    },
+    /// insert-completions: insert all the possible completions of the word
+    InsertCompletions,
     /// Interrupt signal (Ctrl-C)
     Interrupt,
     /// backward-delete-char, backward-kill-line, backward-kill-word
@@ -65,42 +122,101 @@ pub enum Cmd {
     /// forward-char, forward-word, vi-char-search, vi-end-word, vi-next-word,
     /// vi-prev-word
     Move(Movement),
+    /// magic-space: perform the history expansion of the line (when enabled)
+    /// and insert a space
+    MagicSpace,
+    /// menu-complete: replace the word with the next possible completion
+    MenuComplete,
+    /// menu-complete-backward: replace the word with the previous possible
+    /// completion
+    MenuCompleteBackward,
+    /// Move the cursor to the character displayed at the given screen
+    /// position (0-based), e.g. where the mouse was clicked
+    MoveToScreenPosition {
+        /// Screen row
+        row: u16,
+        /// Screen column
+        column: u16,
+    },
     /// next-history
     NextHistory,
     /// No action
     Noop,
+    /// operate-and-get-next: accept the line and edit the next history
+    /// entry on the following `readline`
+    OperateAndGetNext,
     /// vi-replace
     Overwrite(char),
+    /// overwrite-mode: toggle between insert and overwrite modes
+    OverwriteMode,
+    /// possible-completions: list the possible completions of the word
+    /// without inserting anything
+    PossibleCompletions,
     /// previous-history
     PreviousHistory,
-    /// quoted-insert
-    QuotedInsert,
+    /// quoted-insert: insert the next character typed verbatim
+    QuotedInsert(RepeatCount),
+    /// redo: redo the last undone changes (`Ctrl-R` in vi command mode)
+    Redo(RepeatCount),
     /// vi-change-char
     ReplaceChar(RepeatCount, char),
     /// vi-change-to, vi-substitute
     Replace(Movement, Option<String>),
     /// reverse-search-history
     ReverseSearchHistory,
+    /// revert-line: undo all changes made to the line (restoring the
+    /// original history entry)
+    RevertLine,
+    /// Extend the selection (the region started with a shift + movement key)
+    /// by moving the cursor
+    Select(Movement),
     /// self-insert
     SelfInsert(RepeatCount, char),
+    /// Switch to another editing mode, e.g. a custom one (see
+    /// [`CustomMode`](crate::CustomMode))
+    SetEditMode(EditMode),
+    /// set-mark: set the mark at the cursor position and activate the region
+    SetMark,
+    /// start-kbd-macro
+    StartKbdMacro,
     /// Suspend signal (Ctrl-Z on unix platform)
     Suspend,
     /// transpose-chars
-    TransposeChars,
+    TransposeChars(RepeatCount),
     /// transpose-words
     TransposeWords(RepeatCount),
     /// undo
     Undo(RepeatCount),
+    /// universal-argument (emacs mode only)
+    UniversalArgument,
     /// Unsupported / unexpected
     Unknown,
     /// upcase-word
-    UpcaseWord,
+    UpcaseWord(RepeatCount),
+    /// vi-editing-mode
+    ViEditingMode,
+    /// Open a new line below (`o`) or above (`O`) the current one in vi
+    /// command mode, and switch to insert mode
+    ViOpenLine(Anchor),
+    /// vi-search: search the history for a pattern typed in a `/` (backward)
+    /// or `?` (forward) mini-prompt
+    ViSearch(Direction),
+    /// vi-search-again: repeat the last history search (`n`, or `N` in the
+    /// opposite direction)
+    ViSearchAgain(RepeatCount, Direction),
     /// vi-yank-to
     ViYankTo(Movement),
     /// yank, vi-put
     Yank(RepeatCount, Anchor),
     /// yank-pop
     YankPop,
+    /// yank-last-arg: insert the last word of the previous history entry (or
+    /// word `n`, counted from the end if negative), then the same word of
+    /// older entries when repeated
+    YankLastArg(isize),
+    /// yank-nth-arg: insert word `n` of the previous history entry (counted
+    /// from the end if negative, `0` being the first word)
+    YankNthArg(isize),
     /// moves cursor to the line above or switches to prev history entry if
     /// the cursor is already on the first line
     LineUpOrPreviousHistory(RepeatCount),
@@ -127,16 +243,34 @@ pub enum Cmd {
 }
 
 impl Cmd {
+    /// Command running `f`, e.g. for a one-off binding which does not need
+    /// to be registered under a name in a [`FunctionRegistry`](crate::FunctionRegistry).
+    pub fn custom<F>(f: F) -> Self
+    where
+        F: FnMut(&mut Prompter<'_>) -> io::Result<()> + Send + 'static,
+    {
+        Cmd::Custom(CustomCommand::new(f))
+    }
+
     /// Tells if current command should reset kill ring.
     pub fn should_reset_kill_ring(&self) -> bool {
         #[allow(clippy::match_same_arms)]
         match *self {
             Cmd::Kill(Movement::BackwardChar(_)) | Cmd::Kill(Movement::ForwardChar(_)) => true,
-            Cmd::ClearScreen
+            Cmd::CallLastKbdMacro(_)
+            | Cmd::ClearScreen
+            | Cmd::DumpFunctions
+            | Cmd::DumpMacros
+            | Cmd::DumpVariables
+            | Cmd::EndKbdMacro
             | Cmd::Kill(_)
+            | Cmd::MoveToScreenPosition { .. }
             | Cmd::Replace(..)
             | Cmd::Noop
+            | Cmd::PossibleCompletions
+            | Cmd::StartKbdMacro
             | Cmd::Suspend
+            | Cmd::UniversalArgument
             | Cmd::Yank(..)
             | Cmd::YankPop => false,
             _ => true,
@@ -160,7 +294,15 @@ impl Cmd {
 
     fn is_repeatable(&self) -> bool {
         match *self {
-            Cmd::Move(_) => true,
+            Cmd::CapitalizeWord(_)
+            | Cmd::CharacterSearch(_)
+            | Cmd::CharacterSearchBackward(_)
+            | Cmd::DowncaseWord(_)
+            | Cmd::Move(_)
+            | Cmd::QuotedInsert(_)
+            | Cmd::TransposeChars(_)
+            | Cmd::TransposeWords(_)
+            | Cmd::UpcaseWord(_) => true,
             _ => self.is_repeatable_change(),
         }
     }
@@ -168,13 +310,20 @@ impl Cmd {
     // Replay this command with a possible different `RepeatCount`.
     fn redo(&self, new: Option<RepeatCount>, wrt: &dyn Refresher) -> Self {
         match *self {
+            Cmd::CapitalizeWord(previous) => Cmd::CapitalizeWord(repeat_count(previous, new)),
+            Cmd::CharacterSearch(previous) => Cmd::CharacterSearch(repeat_count(previous, new)),
+            Cmd::CharacterSearchBackward(previous) => {
+                Cmd::CharacterSearchBackward(repeat_count(previous, new))
+            }
             Cmd::Dedent(ref mvt) => Cmd::Dedent(mvt.redo(new)),
+            Cmd::DowncaseWord(previous) => Cmd::DowncaseWord(repeat_count(previous, new)),
             Cmd::Indent(ref mvt) => Cmd::Indent(mvt.redo(new)),
             Cmd::Insert(previous, ref text) => {
                 Cmd::Insert(repeat_count(previous, new), text.clone())
             }
             Cmd::Kill(ref mvt) => Cmd::Kill(mvt.redo(new)),
             Cmd::Move(ref mvt) => Cmd::Move(mvt.redo(new)),
+            Cmd::QuotedInsert(previous) => Cmd::QuotedInsert(repeat_count(previous, new)),
             Cmd::ReplaceChar(previous, c) => Cmd::ReplaceChar(repeat_count(previous, new), c),
             Cmd::Replace(ref mvt, ref text) => {
                 if text.is_none() {
@@ -199,7 +348,9 @@ impl Cmd {
                     Cmd::SelfInsert(repeat_count(previous, new), c)
                 }
             }
-            // Cmd::TransposeChars => Cmd::TransposeChars,
+            Cmd::TransposeChars(previous) => Cmd::TransposeChars(repeat_count(previous, new)),
+            Cmd::TransposeWords(previous) => Cmd::TransposeWords(repeat_count(previous, new)),
+            Cmd::UpcaseWord(previous) => Cmd::UpcaseWord(repeat_count(previous, new)),
             Cmd::ViYankTo(ref mvt) => Cmd::ViYankTo(mvt.redo(new)),
             Cmd::Yank(previous, anchor) => Cmd::Yank(repeat_count(previous, new), anchor),
             _ => unreachable!(),
@@ -269,6 +420,24 @@ impl CharSearch {
     }
 }
 
+/// Vi text object, selected after an operator (`ciw`, `da(`)
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub enum TextObject {
+    /// `iw`, `iW`: the word (or the whitespace) under the cursor
+    InnerWord(Word),
+    /// `aw`, `aW`: the word under the cursor and the whitespace around it
+    AWord(Word),
+    /// `i"`, `i'`, `` i` ``: the text between the quotes on the current line
+    InnerQuote(char),
+    /// `a"`, `a'`, `` a` ``: the quoted text with its quotes and trailing
+    /// whitespace
+    AQuote(char),
+    /// `i(`, `i[`, `i{`, `i<`: the text between the enclosing brackets
+    InnerBlock(char, char),
+    /// `a(`, `a[`, `a{`, `a<`: the enclosing block with its brackets
+    ABlock(char, char),
+}
+
 /// Where to move
 #[derive(Debug, Clone, PartialEq)]
 #[non_exhaustive]
@@ -287,6 +456,8 @@ pub enum Movement {
     ViCharSearch(RepeatCount, CharSearch),
     /// vi-first-print
     ViFirstPrint,
+    /// vi text object (`iw`, `a(`, ...), only after an operator
+    ViTextObject(RepeatCount, TextObject),
     /// backward-char
     BackwardChar(RepeatCount),
     /// forward-char
@@ -301,6 +472,9 @@ pub enum Movement {
     BeginningOfBuffer,
     /// end-of-buffer
     EndOfBuffer,
+    /// Text between the mark and the cursor (not really a movement but a
+    /// range), e.g. for kill-region
+    Region,
 }
 
 impl Movement {
@@ -308,8 +482,12 @@ impl Movement {
     fn redo(&self, new: Option<RepeatCount>) -> Self {
         match *self {
             Movement::WholeLine => Movement::WholeLine,
+            Movement::Region => Movement::Region,
             Movement::BeginningOfLine => Movement::BeginningOfLine,
             Movement::ViFirstPrint => Movement::ViFirstPrint,
+            Movement::ViTextObject(previous, obj) => {
+                Movement::ViTextObject(repeat_count(previous, new), obj)
+            }
             Movement::EndOfLine => Movement::EndOfLine,
             Movement::BackwardWord(previous, word) => {
                 Movement::BackwardWord(repeat_count(previous, new), word)
@@ -332,7 +510,7 @@ impl Movement {
 }
 
 /// Vi input modes
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum InputMode {
     /// Vi Command/Alternate
     Command,
@@ -342,15 +520,63 @@ pub enum InputMode {
     Replace,
 }
 
+/// Editing mode and pending vi command, e.g. to render a mode indicator in
+/// the prompt (see
+/// [`Highlighter::mode_prompt`](crate::highlight::Highlighter::mode_prompt))
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ModeState {
+    /// Emacs or vi mode
+    pub edit_mode: EditMode,
+    /// Vi input mode (`Insert` or `Replace` in emacs mode)
+    pub input_mode: InputMode,
+    /// Vi operator waiting for a motion (`c`, `d`, `y`, `<` or `>`)
+    pub operator: Option<char>,
+    /// Vi count typed so far
+    pub count: Option<RepeatCount>,
+}
+
+/// Number of commands remembered for
+/// [`EventContext::command_history`](crate::EventContext::command_history)
+const RECENT_CMDS_LEN: usize = 16;
+
 /// Transform key(s) to commands based on current input mode
 pub struct InputState {
     pub(crate) mode: EditMode,
-    custom_bindings: Arc<RwLock<Trie<Event, EventHandler>>>,
+    custom_bindings: Arc<RwLock<Bindings>>,
     pub(crate) input_mode: InputMode, // vi only ?
     // numeric arguments: http://web.mit.edu/gnu/doc/html/rlman_1.html#SEC7
     num_args: i16,
     last_cmd: Cmd,                        // vi only
     last_char_search: Option<CharSearch>, // vi only
+    last_search_direction: Direction,     // vi only
+    queued_cmds: VecDeque<Cmd>,           // macro
+    keyseq_timeout: i32,
+    /// Completion candidates are being displayed
+    pub(crate) completing: bool,
+    /// Keys read while resolving a key sequence but not part of it
+    pub(crate) pending_keys: VecDeque<KeyEvent>,
+    /// Commands read since the start of the line, most recent first
+    pub(crate) recent_cmds: VecDeque<Cmd>,
+    /// Keyboard macros
+    pub(crate) kbd_macros: Arc<Mutex<MacroRecorder>>,
+    /// Modes registered by the application
+    pub(crate) custom_modes: Arc<Mutex<CustomModes>>,
+    /// Register selected with `"x` for the current command (vi only)
+    pub(crate) register: Option<char>,
+    /// Text inserted before the current switch to command mode (vi only)
+    pub(crate) inserted: Option<String>,
+    /// Command which switched to insert mode (`a`, `A`, `I`, `o`, `O`), and
+    /// replayed with the inserted text by `.` (vi only)
+    insert_entry: Option<Cmd>,
+    last_insert_entry: Option<Cmd>,
+    /// Mode strings of `show-mode-in-prompt`: emacs, vi insert and vi command
+    /// mode
+    mode_strings: Option<[String; 3]>,
+    /// Mode shown in the prompt
+    mode_state: ModeState,
+    /// Vi operator waiting for a motion
+    operator: Option<char>,
 }
 
 /// Provide indirect mutation to user input.
@@ -369,12 +595,19 @@ pub trait Refresher {
     fn refresh_line_with_msg(&mut self, msg: Option<&str>) -> Result<()>;
     /// Same as `refresh_line` but with a dynamic prompt.
     fn refresh_prompt_and_line(&mut self, prompt: &str) -> Result<()>;
+    /// Same as `refresh_line` but with the prompt displayed while waiting
+    /// for the rest of a key sequence (see
+    /// [`Highlighter::pending_prefix_prompt`](crate::highlight::Highlighter::pending_prefix_prompt)).
+    fn refresh_pending_prefix(&mut self, prefix: &[KeyEvent]) -> Result<()>;
     /// Vi only, switch to insert mode.
     fn doing_insert(&mut self);
     /// Vi only, switch to command mode.
     fn done_inserting(&mut self);
     /// Vi only, last text inserted.
     fn last_insert(&self) -> Option<String>;
+    /// Show the editing `mode` in the prompt, prefixed by `indicator` (the
+    /// mode string of `show-mode-in-prompt`).
+    fn set_mode(&mut self, mode: ModeState, indicator: &str) -> Result<()>;
     /// Returns `true` if the cursor is currently at the end of the line.
     fn is_cursor_at_end(&self) -> bool;
     /// Returns `true` if there is a hint displayed.
@@ -388,7 +621,7 @@ pub trait Refresher {
 }
 
 impl InputState {
-    pub fn new(config: &Config, custom_bindings: Arc<RwLock<Trie<Event, EventHandler>>>) -> Self {
+    pub fn new(config: &Config, custom_bindings: Arc<RwLock<Bindings>>) -> Self {
         Self {
             mode: config.edit_mode(),
             custom_bindings,
@@ -396,11 +629,106 @@ impl InputState {
             num_args: 0,
             last_cmd: Cmd::Noop,
             last_char_search: None,
+            last_search_direction: Direction::Reverse,
+            queued_cmds: VecDeque::new(),
+            keyseq_timeout: config.keyseq_timeout(),
+            completing: false,
+            pending_keys: VecDeque::new(),
+            recent_cmds: VecDeque::with_capacity(RECENT_CMDS_LEN),
+            kbd_macros: Arc::default(),
+            custom_modes: Arc::default(),
+            register: None,
+            inserted: None,
+            insert_entry: None,
+            last_insert_entry: None,
+            mode_strings: if config.show_mode_in_prompt() {
+                Some([
+                    config.emacs_mode_string().to_owned(),
+                    config.vi_ins_mode_string().to_owned(),
+                    config.vi_cmd_mode_string().to_owned(),
+                ])
+            } else {
+                None
+            },
+            mode_state: ModeState {
+                edit_mode: config.edit_mode(),
+                input_mode: InputMode::Insert,
+                operator: None,
+                count: None,
+            },
+            operator: None,
+        }
+    }
+
+    /// Mode shown in the prompt
+    pub(crate) fn mode_state(&self) -> ModeState {
+        self.mode_state
+    }
+
+    /// Mode string of `show-mode-in-prompt` for the current mode
+    pub(crate) fn mode_string(&self) -> &str {
+        match self.mode_strings {
+            None => "",
+            Some([ref emacs, _, _]) if self.mode != EditMode::Vi => emacs,
+            Some([_, _, ref vi_cmd]) if self.input_mode == InputMode::Command => vi_cmd,
+            Some([_, ref vi_ins, _]) => vi_ins,
         }
     }
 
+    /// Update the mode shown in the prompt if it changed.
+    fn update_mode(&mut self, wrt: &mut dyn Refresher, count: Option<RepeatCount>) -> Result<()> {
+        let mode = ModeState {
+            edit_mode: self.mode,
+            input_mode: self.input_mode,
+            operator: self.operator,
+            count,
+        };
+        if mode != self.mode_state {
+            self.mode_state = mode;
+            wrt.set_mode(mode, self.mode_string())?;
+        }
+        Ok(())
+    }
+
+    /// Emacs or custom mode
     pub fn is_emacs_mode(&self) -> bool {
-        self.mode == EditMode::Emacs
+        self.mode != EditMode::Vi
+    }
+
+    /// Switch to emacs, vi (insert) or a custom mode
+    pub(crate) fn set_edit_mode(&mut self, mode: EditMode) {
+        self.mode = mode;
+        self.input_mode = InputMode::Insert;
+        self.num_args = 0;
+        self.reset_custom_mode();
+    }
+
+    /// Switch between insert and overwrite (vi replace) modes
+    fn toggle_overwrite_mode(&mut self) {
+        self.input_mode = match self.input_mode {
+            InputMode::Insert => InputMode::Replace,
+            InputMode::Replace => InputMode::Insert,
+            InputMode::Command => InputMode::Command,
+        };
+    }
+
+    /// Reset the state of the current custom mode
+    pub(crate) fn reset_custom_mode(&self) {
+        if let EditMode::Custom(name) = self.mode {
+            if let Some(mode) = self.custom_modes.lock().unwrap().get_mut(name) {
+                mode.reset();
+            }
+        }
+    }
+
+    /// Keymap matching the current editing mode
+    pub fn keymap(&self) -> Keymap {
+        match (self.mode, self.input_mode) {
+            (EditMode::Emacs, _) => Keymap::Emacs,
+            (EditMode::Custom(name), _) => Keymap::Custom(name),
+            (EditMode::Vi, InputMode::Command) => Keymap::ViCommand,
+            (EditMode::Vi, _) => Keymap::ViInsert,
+        }
     }
 
     /// Parse user input into one command
@@ -412,75 +740,357 @@ impl InputState {
         wrt: &mut dyn Refresher,
         single_esc_abort: bool,
     ) -> Result<Cmd> {
-        match self.mode {
-            EditMode::Emacs => {
-                let key = rdr.next_key(single_esc_abort)?;
-                self.emacs(rdr, wrt, key)
-            }
-            EditMode::Vi if self.input_mode != InputMode::Command => {
-                let key = rdr.next_key(false)?;
-                self.vi_insert(rdr, wrt, key)
+        self.register = None;
+        self.inserted = None;
+        self.update_mode(wrt, None)?;
+        let recorded = self.kbd_macros.lock().unwrap().recorded();
+        let cmd = self.read_cmd(rdr, wrt, single_esc_abort)?;
+        if cmd == Cmd::OverwriteMode {
+            self.toggle_overwrite_mode();
+        }
+        let mut kbd_macros = self.kbd_macros.lock().unwrap();
+        match cmd {
+            Cmd::StartKbdMacro => kbd_macros.start(),
+            Cmd::EndKbdMacro => kbd_macros.end(recorded.unwrap_or_default()),
+            // like readline, a macro cannot replay itself
+            Cmd::CallLastKbdMacro(n) if !kbd_macros.is_recording() => kbd_macros.replay(n),
+            _ => {}
+        }
+        if self.recent_cmds.len() == RECENT_CMDS_LEN {
+            self.recent_cmds.pop_back();
+        }
+        self.recent_cmds.push_front(cmd.clone());
+        Ok(cmd)
+    }
+
+    fn read_cmd(
+        &mut self,
+        rdr: &mut <Terminal as Term>::Reader,
+        wrt: &mut dyn Refresher,
+        single_esc_abort: bool,
+    ) -> Result<Cmd> {
+        if let Some(cmd) = self.queued_cmds.pop_front() {
+            return Ok(cmd);
+        }
+        let single_esc_abort = single_esc_abort && self.mode == EditMode::Emacs;
+        let key = self.next_raw_key(rdr, single_esc_abort)?;
+        if key == E(K::BracketedPasteStart, M::NONE) {
+            return Self::paste(rdr);
+        }
+        if let E(K::Mouse(mouse), _) = key {
+            return Ok(self.mouse(wrt, mouse));
+        }
+        if let E(K::FocusGained, _) | E(K::FocusLost, _) = key {
+            return Ok(self.focus(wrt, key));
+        }
+        let key = self.keypad_fallback(key);
+        let cmd = match self.mode {
+            EditMode::Emacs => self.emacs(rdr, wrt, key),
+            EditMode::Vi if self.input_mode != InputMode::Command => self.vi_insert(rdr, wrt, key),
+            EditMode::Vi => self.vi_command(rdr, wrt, key),
+            EditMode::Custom(name) => self.custom_mode(rdr, wrt, key, name),
+        }?;
+        match cmd {
+            Cmd::CharacterSearch(n) => self.character_search(rdr, n, false),
+            Cmd::CharacterSearchBackward(n) => self.character_search(rdr, n, true),
+            Cmd::QuotedInsert(n) => self.quoted_insert(rdr, n),
+            cmd => Ok(cmd),
+        }
+    }
+
+    /// Keys typed in a custom mode: custom bindings first, then the mode (until
+    /// it returns a command). Without such mode, the emacs keymap is used.
+    fn custom_mode<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        wrt: &mut dyn Refresher,
+        mut key: KeyEvent,
+        name: &'static str,
+    ) -> Result<Cmd> {
+        let modes = Arc::clone(&self.custom_modes);
+        if !modes.lock().unwrap().contains_key(name) {
+            return self.emacs(rdr, wrt, key);
+        }
+        loop {
+            let mut evt = key.into();
+            if let Some(cmd) = self.custom_binding(rdr, wrt, &mut evt, 1, true)? {
+                return Ok(cmd);
             }
-            EditMode::Vi => {
-                let key = rdr.next_key(false)?;
-                self.vi_command(rdr, wrt, key)
+            let cmd = match modes.lock().unwrap().get_mut(name) {
+                Some(mode) => mode.handle(key, &EventContext::new(self, wrt)),
+                None => Some(Cmd::Unknown),
+            };
+            debug!(target: "rustyline", "Custom mode {}: {:?}", name, cmd);
+            match cmd {
+                Some(cmd) => return Ok(cmd),
+                None => key = self.next_key(rdr, false)?,
             }
         }
     }
 
-    fn custom_binding(
-        &self,
-        wrt: &mut dyn Refresher,
-        evt: &Event,
+    /// `character-search` and `character-search-backward`: the searched
+    /// character is the next one typed (any other key is handled as usual).
+    fn character_search<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
         n: RepeatCount,
-        positive: bool,
-    ) -> Option<Cmd> {
-        let bindings = self.custom_bindings.read().unwrap();
-        let handler = bindings.get(&evt).or_else(|| bindings.get(&Event::Any));
-        if let Some(handler) = handler {
-            match handler {
-                EventHandler::Simple(cmd) => Some(cmd.clone()),
-                EventHandler::Conditional(handler) => {
-                    let ctx = EventContext::new(self, wrt);
-                    handler.handle(&evt, n, positive, &ctx)
-                }
+        backward: bool,
+    ) -> Result<Cmd> {
+        let key = self.next_key(rdr, false)?;
+        Ok(match key {
+            E(K::Char(c), M::NONE) | E(K::Char(c), M::SHIFT) => {
+                let cs = if backward {
+                    CharSearch::Backward(c)
+                } else {
+                    CharSearch::Forward(c)
+                };
+                Cmd::Move(Movement::ViCharSearch(n, cs))
             }
-        } else {
-            None
+            _ => {
+                self.pending_keys.push_front(key);
+                Cmd::Noop
+            }
+        })
+    }
+
+    /// `quoted-insert`: the next character typed is inserted verbatim, even a
+    /// control character or the first one of an escape sequence (the others
+    /// follow as regular characters).
+    fn quoted_insert<R: RawReader>(&mut self, rdr: &mut R, n: RepeatCount) -> Result<Cmd> {
+        #[cfg(unix)]
+        if self.pending_keys.is_empty() && !self.kbd_macros.lock().unwrap().is_playing() {
+            let c = rdr.next_char()?;
+            self.kbd_macros.lock().unwrap().record(KeyEvent::from(c));
+            return Ok(Cmd::SelfInsert(n, c));
+        }
+        // keys already decoded (or Windows console)
+        let E(code, mods) = self.next_key(rdr, false)?;
+        if mods.contains(M::ALT) {
+            // like the escape sequence sent by the terminal
+            self.pending_keys.push_front(E(code, mods - M::ALT));
+            return Ok(Cmd::SelfInsert(n, '\x1b'));
+        }
+        Ok(E(code, mods)
+            .to_char()
+            .map_or(Cmd::Noop, |c| Cmd::SelfInsert(n, c)))
+    }
+
+    /// Next key, keys sent in application keypad mode being handled like the
+    /// normal keys
+    pub(crate) fn next_key<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        single_esc_abort: bool,
+    ) -> Result<KeyEvent> {
+        let E(code, mods) = self.next_raw_key(rdr, single_esc_abort)?;
+        Ok(E(code, mods - M::KEYPAD))
+    }
+
+    /// Next key, with its [`M::KEYPAD`] flag if any
+    fn next_raw_key<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        single_esc_abort: bool,
+    ) -> Result<KeyEvent> {
+        if let Some(key) = self.pending_keys.pop_front() {
+            return Ok(key);
+        }
+        let mut kbd_macros = self.kbd_macros.lock().unwrap();
+        if let Some(key) = kbd_macros.next_key() {
+            return Ok(key);
+        }
+        let key = rdr.next_key(single_esc_abort)?;
+        kbd_macros.record(key);
+        Ok(key)
+    }
+
+    /// Wait at most `timeout_ms` (or indefinitely if negative) for a key
+    fn wait_for_key<R: RawReader>(&mut self, rdr: &mut R, timeout_ms: i32) -> Result<bool> {
+        Ok(!self.pending_keys.is_empty()
+            || self.kbd_macros.lock().unwrap().is_playing()
+            || rdr.wait_for_input(timeout_ms)?)
+    }
+
+    /// `key` keeps its [`M::KEYPAD`] flag only if a custom binding starts
+    /// with it (see [`Bindings::keypad_fallback`]).
+    fn keypad_fallback(&self, key: KeyEvent) -> KeyEvent {
+        if !key.1.contains(M::KEYPAD) {
+            return key;
+        }
+        let mut evt = Event::from(key);
+        self.custom_bindings
+            .read()
+            .unwrap()
+            .keypad_fallback(self.keymap(), &mut evt);
+        evt.get(0).copied().unwrap_or(key)
+    }
+
+    /// Push back the keys of `evt` after the `len` first ones: they will be
+    /// handled next.
+    fn unread(&mut self, evt: &mut Event, len: usize) {
+        let mut keys = Vec::new();
+        while evt.get(len).is_some() {
+            keys.extend(evt.pop());
+        }
+        for key in keys {
+            self.pending_keys.push_front(key);
+        }
+    }
+
+    /// Pasted text is inserted verbatim, whatever the mode and the bindings
+    /// are.
+    fn paste<R: RawReader>(rdr: &mut R) -> Result<Cmd> {
+        let paste = rdr.read_pasted_text()?;
+        Ok(Cmd::Insert(1, paste))
+    }
+
+    /// Command for `key` when the built-in bindings (`Layer::Defaults`)
+    /// are disabled: characters are inserted, other keys are ignored.
+    fn without_defaults(&self, key: KeyEvent, n: RepeatCount) -> Option<Cmd> {
+        if self.custom_bindings.read().unwrap().has_defaults() {
+            return None;
+        }
+        Some(match key {
+            E(K::Char(c), M::NONE) => match self.input_mode {
+                _ if self.mode == EditMode::Emacs => Cmd::SelfInsert(n, c),
+                InputMode::Insert => Cmd::SelfInsert(n, c),
+                InputMode::Replace => Cmd::Overwrite(c),
+                InputMode::Command => Cmd::Unknown,
+            },
+            _ => Cmd::Unknown,
+        })
+    }
+
+    /// Mouse events are looked up by kind and modifiers, the handler receives
+    /// the event with its position.
+    /// By default, a left click moves the cursor and the wheel browses the
+    /// history (or the completion candidates).
+    fn mouse(&mut self, wrt: &mut dyn Refresher, mouse: MouseEvent) -> Cmd {
+        let evt = Event::Mouse(mouse);
+        let bindings = Arc::clone(&self.custom_bindings);
+        let bindings = bindings.read().unwrap();
+        if let Some(handler) = bindings.get(self.keymap(), &evt.clone().normalize()) {
+            if let Some(cmd) = self.invoke(handler, wrt, &evt, 1, true) {
+                return cmd;
+            }
+        }
+        if !bindings.has_defaults() {
+            return Cmd::Noop;
+        }
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => Cmd::MoveToScreenPosition {
+                row: mouse.row,
+                column: mouse.column,
+            },
+            MouseEventKind::ScrollUp if self.completing => Cmd::CompleteBackward,
+            MouseEventKind::ScrollUp => Cmd::PreviousHistory,
+            MouseEventKind::ScrollDown if self.completing => Cmd::Complete,
+            MouseEventKind::ScrollDown => Cmd::NextHistory,
+            _ => Cmd::Noop,
         }
     }
 
-    fn custom_seq_binding<R: RawReader>(
-        &self,
+    /// Focus reports only trigger their own binding: they are ignored by
+    /// default.
+    fn focus(&mut self, wrt: &mut dyn Refresher, key: KeyEvent) -> Cmd {
+        let evt = Event::from(key);
+        let bindings = Arc::clone(&self.custom_bindings);
+        let bindings = bindings.read().unwrap();
+        bindings
+            .get(self.keymap(), &evt)
+            .and_then(|handler| self.invoke(handler, wrt, &evt, 1, true))
+            .unwrap_or(Cmd::Noop)
+    }
+
+    /// Return the command bound to the longest sequence starting with `evt`:
+    /// keys are read while `evt` is the prefix of a longer bound sequence
+    /// (e.g. `C-x C-s` or `g g`), keys read but not part of the sequence are
+    /// handled next.
+    /// When no command is returned, `evt` is left with its first key only.
+    fn custom_binding<R: RawReader>(
+        &mut self,
         rdr: &mut R,
         wrt: &mut dyn Refresher,
         evt: &mut Event,
         n: RepeatCount,
         positive: bool,
     ) -> Result<Option<Cmd>> {
-        let bindings = self.custom_bindings.read().unwrap();
-        while let Some(subtrie) = bindings.get_raw_descendant(&evt) {
-            let snd_key = rdr.next_key(true)?;
-            if let Event::KeySeq(ref mut key_seq) = evt {
-                key_seq.push(snd_key);
-            } else {
-                break;
-            }
-            let handler = subtrie.get(&evt).unwrap();
-            if let Some(handler) = handler {
-                let cmd = match handler {
-                    EventHandler::Simple(cmd) => Some(cmd.clone()),
-                    EventHandler::Conditional(handler) => {
-                        let ctx = EventContext::new(self, wrt);
-                        handler.handle(&evt, n, positive, &ctx)
-                    }
+        let bindings = Arc::clone(&self.custom_bindings);
+        let bindings = bindings.read().unwrap();
+        let keymap = self.keymap();
+        let mut matched = bindings.get(keymap, evt).map(|handler| (handler, 1));
+        if bindings.is_prefix(keymap, evt) {
+            let mut len = 1;
+            let mut displayed = false;
+            loop {
+                // a single key or a bound sequence may also be complete:
+                // the next key must be typed before `keyseq-timeout`.
+                let timeout = if len == 1 || matched.is_some() {
+                    self.keyseq_timeout
+                } else {
+                    -1
                 };
-                if cmd.is_some() {
-                    return Ok(cmd);
+                if !displayed && !self.wait_for_key(rdr, 0)? {
+                    if let Event::KeySeq(ref keys) = evt {
+                        wrt.refresh_pending_prefix(keys)?;
+                    }
+                    displayed = true;
+                }
+                if !self.wait_for_key(rdr, timeout)? {
+                    break;
+                }
+                evt.push(self.next_raw_key(rdr, true)?);
+                bindings.keypad_fallback(keymap, evt);
+                len += 1;
+                if let Some(handler) = bindings.get(keymap, evt) {
+                    matched = Some((handler, len));
+                }
+                if !bindings.is_prefix(keymap, evt) {
+                    break;
                 }
             }
+            if displayed {
+                wrt.refresh_line()?;
+            }
+            self.unread(evt, matched.map_or(1, |(_, len)| len));
+        }
+        let handler = match matched {
+            Some((handler, _)) => handler,
+            None => match bindings.get(keymap, &Event::Any) {
+                Some(handler) => handler,
+                None => return Ok(None),
+            },
+        };
+        let cmd = self.invoke(handler, wrt, evt, n, positive);
+        if cmd.is_none() {
+            self.unread(evt, 1);
+        }
+        Ok(cmd)
+    }
+
+    /// Return the command to be performed by `handler`: other commands of a
+    /// macro are queued and returned by the next calls to `next_cmd`.
+    fn invoke(
+        &mut self,
+        handler: &EventHandler,
+        wrt: &mut dyn Refresher,
+        evt: &Event,
+        n: RepeatCount,
+        positive: bool,
+    ) -> Option<Cmd> {
+        match handler {
+            EventHandler::Simple(cmd) => Some(cmd.clone()),
+            EventHandler::Conditional(handler) => {
+                let ctx = EventContext::new(self, wrt);
+                handler.handle(evt, n, positive, &ctx)
+            }
+            EventHandler::Macro(cmds) => {
+                let mut cmds = cmds.iter().cloned();
+                let cmd = cmds.next();
+                self.queued_cmds.extend(cmds);
+                cmd
+            }
         }
-        Ok(None)
     }
 
     fn emacs_digit_argument<R: RawReader>(
@@ -501,7 +1111,7 @@ impl InputState {
         }
         loop {
             wrt.refresh_prompt_and_line(&format!("(arg: {}) ", self.num_args))?;
-            let key = rdr.next_key(true)?;
+            let key = self.next_key(rdr, true)?;
             #[allow(clippy::cast_possible_truncation)]
             match key {
                 E(K::Char(digit @ '0'..='9'), m) if m == M::NONE || m == M::ALT => {
@@ -524,6 +1134,47 @@ impl InputState {
         }
     }
 
+    /// `universal-argument`: multiply the current numeric argument by four
+    /// (or start with four) unless digits or `-` follow.
+    fn emacs_universal_argument<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        wrt: &mut dyn Refresher,
+        num_args: i16,
+    ) -> Result<KeyEvent> {
+        self.num_args = if num_args == 0 {
+            4
+        } else {
+            num_args.saturating_mul(4)
+        };
+        let mut digits = false;
+        loop {
+            wrt.refresh_prompt_and_line(&format!("(arg: {}) ", self.num_args))?;
+            let key = self.next_key(rdr, true)?;
+            #[allow(clippy::cast_possible_truncation)]
+            match key {
+                E(K::Char(digit @ '0'..='9'), M::NONE) => {
+                    let digit = digit.to_digit(10).unwrap() as i16;
+                    if !digits {
+                        self.num_args = if self.num_args == -1 { -digit } else { digit };
+                        digits = true;
+                    } else if self.num_args.abs() < 1000 {
+                        // shouldn't ever need more than 4 digits
+                        let digit = if self.num_args < 0 { -digit } else { digit };
+                        self.num_args = self.num_args.saturating_mul(10).saturating_add(digit);
+                    }
+                }
+                E(K::Char('-'), M::NONE) if !digits => {
+                    self.num_args = -1;
+                }
+                _ => {
+                    wrt.refresh_line()?;
+                    return Ok(key);
+                }
+            };
+        }
+    }
+
     fn emacs<R: RawReader>(
         &mut self,
         rdr: &mut R,
@@ -535,19 +1186,29 @@ impl InputState {
         } else if let E(K::Char(digit @ '0'..='9'), M::ALT) = key {
             key = self.emacs_digit_argument(rdr, wrt, digit)?;
         }
+        let num_args = self.num_args;
         let (n, positive) = self.emacs_num_args(); // consume them in all cases
 
         let mut evt = key.into();
-        if let Some(cmd) = self.custom_binding(wrt, &evt, n, positive) {
+        if let Some(cmd) = self.custom_binding(rdr, wrt, &mut evt, n, positive)? {
+            if cmd == Cmd::UniversalArgument {
+                let key = self.emacs_universal_argument(rdr, wrt, num_args)?;
+                return self.emacs(rdr, wrt, key);
+            }
             return Ok(if cmd.is_repeatable() {
                 cmd.redo(Some(n), wrt)
             } else {
                 cmd
             });
         }
+        if let Some(cmd) = self.without_defaults(key, n) {
+            return Ok(cmd);
+        }
         let cmd = match key {
             E(K::Char(c), M::NONE) => {
-                if positive {
+                if positive && n == 1 && self.input_mode == InputMode::Replace {
+                    Cmd::Overwrite(c)
+                } else if positive {
                     Cmd::SelfInsert(n, c)
                 } else {
                     Cmd::Unknown
@@ -585,8 +1246,15 @@ impl InputState {
                     Cmd::CompleteBackward
                 }
             }
+            // tab-insert
+            E(K::Tab, M::ALT) => Cmd::SelfInsert(n, '\t'),
             // Don't complete hints when the cursor is not at the end of a line
             E(K::Right, M::NONE) if wrt.has_hint() && wrt.is_cursor_at_end() => Cmd::CompleteHint,
+            E(K::Right, M::CTRL) | E(K::Right, M::ALT) | E(K::Char('f'), M::ALT)
+                if wrt.has_hint() && wrt.is_cursor_at_end() =>
+            {
+                Cmd::CompleteHintWord
+            }
             E(K::Char('K'), M::CTRL) => {
                 if positive {
                     Cmd::Kill(Movement::EndOfLine)
@@ -595,22 +1263,35 @@ impl InputState {
                 }
             }
             E(K::Char('L'), M::CTRL) => Cmd::ClearScreen,
+            E(K::Char(' '), M::CTRL) => Cmd::SetMark,
             E(K::Char('N'), M::CTRL) => Cmd::NextHistory,
+            E(K::Char('O'), M::CTRL) => Cmd::OperateAndGetNext,
             E(K::Char('P'), M::CTRL) => Cmd::PreviousHistory,
-            E(K::Char('X'), M::CTRL) => {
-                if let Some(cmd) = self.custom_seq_binding(rdr, wrt, &mut evt, n, positive)? {
-                    cmd
+            E(K::Char(']'), M::CTRL) => {
+                if positive {
+                    Cmd::CharacterSearch(n)
                 } else {
-                    let snd_key = match evt {
-                        // we may have already read the second key in custom_seq_binding
-                        Event::KeySeq(ref key_seq) if key_seq.len() > 1 => key_seq[1],
-                        _ => rdr.next_key(true)?,
-                    };
-                    match snd_key {
-                        E(K::Char('G'), M::CTRL) | E::ESC => Cmd::Abort,
-                        E(K::Char('U'), M::CTRL) => Cmd::Undo(n),
-                        _ => Cmd::Unknown,
-                    }
+                    Cmd::CharacterSearchBackward(n)
+                }
+            }
+            E(K::Char(']'), M::CTRL_ALT) => {
+                if positive {
+                    Cmd::CharacterSearchBackward(n)
+                } else {
+                    Cmd::CharacterSearch(n)
+                }
+            }
+            E(K::Char('X'), M::CTRL) => {
+                let snd_key = self.next_key(rdr, true)?;
+                match snd_key {
+                    E(K::Char('G'), M::CTRL) | E::ESC => Cmd::Abort,
+                    E(K::Char('U'), M::CTRL) => Cmd::Undo(n),
+                    E(K::Char('X'), M::CTRL) => Cmd::ExchangePointAndMark,
+                    E(K::Char('E'), M::CTRL) => Cmd::EditCommandLine { accept: true },
+                    E(K::Char('('), M::NONE) => Cmd::StartKbdMacro,
+                    E(K::Char(')'), M::NONE) => Cmd::EndKbdMacro,
+                    E(K::Char('e'), M::NONE) => Cmd::CallLastKbdMacro(n),
+                    _ => Cmd::Unknown,
                 }
             }
             E(K::Backspace, M::ALT) => {
@@ -620,8 +1301,14 @@ impl InputState {
                     Cmd::Kill(Movement::ForwardWord(n, At::AfterEnd, Word::Emacs))
                 }
             }
+            E(K::Char('#'), M::ALT) => Cmd::InsertComment {
+                toggle: num_args != 0,
+            },
             E(K::Char('<'), M::ALT) => Cmd::BeginningOfHistory,
             E(K::Char('>'), M::ALT) => Cmd::EndOfHistory,
+            E(K::Char('?'), M::ALT) | E(K::Char('='), M::ALT) => Cmd::PossibleCompletions,
+            E(K::Char('*'), M::ALT) => Cmd::InsertCompletions,
+            E(K::Char('/'), M::ALT) => Cmd::DabbrevExpand,
             E(K::Char('B'), M::ALT)
             | E(K::Char('b'), M::ALT)
             | E(K::Left, M::CTRL)
@@ -632,7 +1319,18 @@ impl InputState {
                     Cmd::Move(Movement::ForwardWord(n, At::AfterEnd, Word::Emacs))
                 }
             }
-            E(K::Char('C'), M::ALT) | E(K::Char('c'), M::ALT) => Cmd::CapitalizeWord,
+            // negative arguments have no effect on case-change commands
+            E(K::Char('C'), M::ALT)
+            | E(K::Char('c'), M::ALT)
+            | E(K::Char('L'), M::ALT)
+            | E(K::Char('l'), M::ALT)
+            | E(K::Char('U'), M::ALT)
+            | E(K::Char('u'), M::ALT)
+                if !positive =>
+            {
+                Cmd::Noop
+            }
+            E(K::Char('C'), M::ALT) | E(K::Char('c'), M::ALT) => Cmd::CapitalizeWord(n),
             E(K::Char('D'), M::ALT) | E(K::Char('d'), M::ALT) => {
                 if positive {
                     Cmd::Kill(Movement::ForwardWord(n, At::AfterEnd, Word::Emacs))
@@ -650,12 +1348,28 @@ impl InputState {
                     Cmd::Move(Movement::BackwardWord(n, Word::Emacs))
                 }
             }
-            E(K::Char('L'), M::ALT) | E(K::Char('l'), M::ALT) => Cmd::DowncaseWord,
+            E(K::Char('L'), M::ALT) | E(K::Char('l'), M::ALT) => Cmd::DowncaseWord(n),
+            // like readline, negative arguments have no effect
+            E(K::Char('T'), M::ALT) | E(K::Char('t'), M::ALT) if !positive => Cmd::Noop,
             E(K::Char('T'), M::ALT) | E(K::Char('t'), M::ALT) => Cmd::TransposeWords(n),
-            // TODO ESC-R (r): Undo all changes made to this line.
-            E(K::Char('U'), M::ALT) | E(K::Char('u'), M::ALT) => Cmd::UpcaseWord,
+            E(K::Char('R'), M::ALT) | E(K::Char('r'), M::ALT) => Cmd::RevertLine,
+            E(K::Char('U'), M::ALT) | E(K::Char('u'), M::ALT) => Cmd::UpcaseWord(n),
+            E(K::Char('W'), M::ALT) | E(K::Char('w'), M::ALT) => Cmd::ViYankTo(Movement::Region),
             E(K::Char('Y'), M::ALT) | E(K::Char('y'), M::ALT) => Cmd::YankPop,
-            _ => self.common(rdr, wrt, evt, key, n, positive)?,
+            E(K::Char('.'), M::ALT) | E(K::Char('_'), M::ALT) => {
+                Cmd::YankLastArg(if num_args == 0 {
+                    -1
+                } else {
+                    isize::from(num_args)
+                })
+            }
+            E(K::Char('^'), M::ALT) => Cmd::HistoryExpandLine,
+            E(K::Char('Y'), M::CTRL_ALT) => Cmd::YankNthArg(if num_args == 0 {
+                1
+            } else {
+                isize::from(num_args)
+            }),
+            _ => self.common(key, n, positive)?,
         };
         debug!(target: "rustyline", "Emacs command: {:?}", cmd);
         Ok(cmd)
@@ -670,8 +1384,9 @@ impl InputState {
     ) -> Result<KeyEvent> {
         self.num_args = digit.to_digit(10).unwrap() as i16;
         loop {
+            self.update_mode(wrt, Some(self.num_args.unsigned_abs() as RepeatCount))?;
             wrt.refresh_prompt_and_line(&format!("(arg: {}) ", self.num_args))?;
-            let key = rdr.next_key(false)?;
+            let key = self.next_key(rdr, false)?;
             if let E(K::Char(digit @ '0'..='9'), M::NONE) = key {
                 if self.num_args.abs() < 1000 {
                     // shouldn't ever need more than 4 digits
@@ -693,13 +1408,27 @@ impl InputState {
         wrt: &mut dyn Refresher,
         mut key: KeyEvent,
     ) -> Result<Cmd> {
-        if let E(K::Char(digit @ '1'..='9'), M::NONE) = key {
-            key = self.vi_arg_digit(rdr, wrt, digit)?;
+        self.insert_entry = None;
+        // the count and the register can be given in any order (`2"ayy`)
+        loop {
+            match key {
+                E(K::Char(digit @ '1'..='9'), M::NONE) if self.num_args == 0 => {
+                    key = self.vi_arg_digit(rdr, wrt, digit)?;
+                }
+                E(K::Char('"'), M::NONE) if self.register.is_none() => {
+                    if let Some(next) = self.vi_register(rdr)? {
+                        key = next;
+                    } else {
+                        return Ok(Cmd::Unknown);
+                    }
+                }
+                _ => break,
+            }
         }
         let no_num_args = self.num_args == 0;
         let n = self.vi_num_args(); // consume them in all cases
-        let evt = key.into();
-        if let Some(cmd) = self.custom_binding(wrt, &evt, n, true) {
+        let mut evt = key.into();
+        if let Some(cmd) = self.custom_binding(rdr, wrt, &mut evt, n, true)? {
             return Ok(if cmd.is_repeatable() {
                 if no_num_args {
                     cmd.redo(None, wrt)
@@ -710,43 +1439,68 @@ impl InputState {
                 cmd
             });
         }
+        if let Some(cmd) = self.without_defaults(key, n) {
+            return Ok(cmd);
+        }
+        if let Some(mvt) = self.vi_motion(rdr, key, n, None)? {
+            let cmd = match mvt {
+                Some(Movement::LineDown(n)) => Cmd::LineDownOrNextHistory(n),
+                Some(Movement::LineUp(n)) => Cmd::LineUpOrPreviousHistory(n),
+                Some(mvt) => Cmd::Move(mvt),
+                None => Cmd::Unknown,
+            };
+            debug!(target: "rustyline", "Vi command: {:?}", cmd);
+            return Ok(cmd);
+        }
         let cmd = match key {
-            E(K::Char('$'), M::NONE) | E(K::End, M::NONE) => Cmd::Move(Movement::EndOfLine),
+            E(K::Char('#'), M::NONE) => Cmd::InsertComment {
+                toggle: !no_num_args,
+            },
             E(K::Char('.'), M::NONE) => {
                 // vi-redo (repeat last command)
-                if no_num_args {
+                let cmd = if no_num_args {
                     self.last_cmd.redo(None, wrt)
                 } else {
                     self.last_cmd.redo(Some(n), wrt)
+                };
+                match (&self.last_insert_entry, cmd) {
+                    // replay the command entering insert mode, then the insertion
+                    (Some(entry), cmd @ Cmd::Insert(..)) => {
+                        self.queued_cmds.push_back(cmd);
+                        entry.clone()
+                    }
+                    (_, cmd) => cmd,
                 }
             }
             // TODO E(K::Char('%'), M::NONE) => Cmd::???, Move to the corresponding opening/closing
             // bracket
-            E(K::Char('0'), M::NONE) => Cmd::Move(Movement::BeginningOfLine),
-            E(K::Char('^'), M::NONE) => Cmd::Move(Movement::ViFirstPrint),
             E(K::Char('a'), M::NONE) => {
                 // vi-append-mode
                 self.input_mode = InputMode::Insert;
                 wrt.doing_insert();
+                self.insert_entry = Some(Cmd::Move(Movement::ForwardChar(n)));
                 Cmd::Move(Movement::ForwardChar(n))
             }
             E(K::Char('A'), M::NONE) => {
                 // vi-append-eol
                 self.input_mode = InputMode::Insert;
                 wrt.doing_insert();
+                self.insert_entry = Some(Cmd::Move(Movement::EndOfLine));
                 Cmd::Move(Movement::EndOfLine)
             }
-            E(K::Char('b'), M::NONE) => Cmd::Move(Movement::BackwardWord(n, Word::Vi)), /* vi-prev-word */
-            E(K::Char('B'), M::NONE) => Cmd::Move(Movement::BackwardWord(n, Word::Big)),
             E(K::Char('c'), M::NONE) => {
                 self.input_mode = InputMode::Insert;
                 match self.vi_cmd_motion(rdr, wrt, key, n)? {
-                    Some(mvt) => Cmd::Replace(mvt, None),
+                    Some(mvt) => {
+                        wrt.doing_insert();
+                        Cmd::Replace(mvt, None)
+                    }
                     None => Cmd::Unknown,
                 }
             }
             E(K::Char('C'), M::NONE) => {
                 self.input_mode = InputMode::Insert;
+                wrt.doing_insert();
                 Cmd::Replace(Movement::EndOfLine, None)
             }
             E(K::Char('d'), M::NONE) => match self.vi_cmd_motion(rdr, wrt, key, n)? {
@@ -754,12 +1508,6 @@ impl InputState {
                 None => Cmd::Unknown,
             },
             E(K::Char('D'), M::NONE) | E(K::Char('K'), M::CTRL) => Cmd::Kill(Movement::EndOfLine),
-            E(K::Char('e'), M::NONE) => {
-                Cmd::Move(Movement::ForwardWord(n, At::BeforeEnd, Word::Vi))
-            }
-            E(K::Char('E'), M::NONE) => {
-                Cmd::Move(Movement::ForwardWord(n, At::BeforeEnd, Word::Big))
-            }
             E(K::Char('i'), M::NONE) => {
                 // vi-insertion-mode
                 self.input_mode = InputMode::Insert;
@@ -770,30 +1518,39 @@ impl InputState {
                 // vi-insert-beg
                 self.input_mode = InputMode::Insert;
                 wrt.doing_insert();
+                self.insert_entry = Some(Cmd::Move(Movement::BeginningOfLine));
                 Cmd::Move(Movement::BeginningOfLine)
             }
-            E(K::Char(c), M::NONE) if c == 'f' || c == 'F' || c == 't' || c == 'T' => {
-                // vi-char-search
-                let cs = self.vi_char_search(rdr, c)?;
-                match cs {
-                    Some(cs) => Cmd::Move(Movement::ViCharSearch(n, cs)),
-                    None => Cmd::Unknown,
-                }
+            E(K::Char('o'), M::NONE) => {
+                self.input_mode = InputMode::Insert;
+                wrt.doing_insert();
+                self.insert_entry = Some(Cmd::ViOpenLine(Anchor::After));
+                Cmd::ViOpenLine(Anchor::After)
+            }
+            E(K::Char('O'), M::NONE) => {
+                self.input_mode = InputMode::Insert;
+                wrt.doing_insert();
+                self.insert_entry = Some(Cmd::ViOpenLine(Anchor::Before));
+                Cmd::ViOpenLine(Anchor::Before)
+            }
+            E(K::Char('/'), M::NONE) => {
+                self.last_search_direction = Direction::Reverse;
+                Cmd::ViSearch(Direction::Reverse)
+            }
+            E(K::Char('?'), M::NONE) => {
+                self.last_search_direction = Direction::Forward;
+                Cmd::ViSearch(Direction::Forward)
+            }
+            E(K::Char('n'), M::NONE) => Cmd::ViSearchAgain(n, self.last_search_direction),
+            E(K::Char('N'), M::NONE) => {
+                Cmd::ViSearchAgain(n, self.last_search_direction.opposite())
             }
-            E(K::Char(';'), M::NONE) => match self.last_char_search {
-                Some(cs) => Cmd::Move(Movement::ViCharSearch(n, cs)),
-                None => Cmd::Noop,
-            },
-            E(K::Char(','), M::NONE) => match self.last_char_search {
-                Some(ref cs) => Cmd::Move(Movement::ViCharSearch(n, cs.opposite())),
-                None => Cmd::Noop,
-            },
             // TODO E(K::Char('G'), M::NONE) => Cmd::???, Move to the history line n
             E(K::Char('p'), M::NONE) => Cmd::Yank(n, Anchor::After), // vi-put
             E(K::Char('P'), M::NONE) => Cmd::Yank(n, Anchor::Before), // vi-put
             E(K::Char('r'), M::NONE) => {
                 // vi-replace-char:
-                let ch = rdr.next_key(false)?;
+                let ch = self.next_key(rdr, false)?;
                 match ch {
                     E(K::Char(c), M::NONE) => Cmd::ReplaceChar(n, c),
                     E::ESC => Cmd::Noop,
@@ -803,22 +1560,23 @@ impl InputState {
             E(K::Char('R'), M::NONE) => {
                 //  vi-replace-mode (overwrite-mode)
                 self.input_mode = InputMode::Replace;
+                wrt.doing_insert();
                 Cmd::Replace(Movement::ForwardChar(0), None)
             }
             E(K::Char('s'), M::NONE) => {
                 // vi-substitute-char:
                 self.input_mode = InputMode::Insert;
+                wrt.doing_insert();
                 Cmd::Replace(Movement::ForwardChar(n), None)
             }
             E(K::Char('S'), M::NONE) => {
                 // vi-substitute-line:
                 self.input_mode = InputMode::Insert;
+                wrt.doing_insert();
                 Cmd::Replace(Movement::WholeLine, None)
             }
             E(K::Char('u'), M::NONE) => Cmd::Undo(n),
-            // E(K::Char('U'), M::NONE) => Cmd::???, // revert-line
-            E(K::Char('w'), M::NONE) => Cmd::Move(Movement::ForwardWord(n, At::Start, Word::Vi)), /* vi-next-word */
-            E(K::Char('W'), M::NONE) => Cmd::Move(Movement::ForwardWord(n, At::Start, Word::Big)), /* vi-next-word */
+            E(K::Char('U'), M::NONE) => Cmd::RevertLine,
             // TODO move backward if eol
             E(K::Char('x'), M::NONE) => Cmd::Kill(Movement::ForwardChar(n)), // vi-delete
             E(K::Char('X'), M::NONE) => Cmd::Kill(Movement::BackwardChar(n)), // vi-rubout
@@ -826,25 +1584,14 @@ impl InputState {
                 Some(mvt) => Cmd::ViYankTo(mvt),
                 None => Cmd::Unknown,
             },
-            // E(K::Char('Y'), M::NONE) => Cmd::???, // vi-yank-to
-            E(K::Char('h'), M::NONE) | E(K::Char('H'), M::CTRL) | E::BACKSPACE => {
-                Cmd::Move(Movement::BackwardChar(n))
-            }
+            E(K::Char('Y'), M::NONE) => Cmd::ViYankTo(Movement::EndOfLine), // y$
             E(K::Char('G'), M::CTRL) => Cmd::Abort,
-            E(K::Char('l'), M::NONE) | E(K::Char(' '), M::NONE) => {
-                Cmd::Move(Movement::ForwardChar(n))
-            }
             E(K::Char('L'), M::CTRL) => Cmd::ClearScreen,
-            E(K::Char('+'), M::NONE) | E(K::Char('j'), M::NONE) => Cmd::LineDownOrNextHistory(n),
             // TODO: move to the start of the line.
             E(K::Char('N'), M::CTRL) => Cmd::NextHistory,
-            E(K::Char('-'), M::NONE) | E(K::Char('k'), M::NONE) => Cmd::LineUpOrPreviousHistory(n),
             // TODO: move to the start of the line.
             E(K::Char('P'), M::CTRL) => Cmd::PreviousHistory,
-            E(K::Char('R'), M::CTRL) => {
-                self.input_mode = InputMode::Insert; // TODO Validate
-                Cmd::ReverseSearchHistory
-            }
+            E(K::Char('R'), M::CTRL) => Cmd::Redo(n),
             E(K::Char('S'), M::CTRL) => {
                 self.input_mode = InputMode::Insert; // TODO Validate
                 Cmd::ForwardSearchHistory
@@ -858,11 +1605,12 @@ impl InputState {
                 None => Cmd::Unknown,
             },
             E::ESC => Cmd::Noop,
-            _ => self.common(rdr, wrt, evt, key, n, true)?,
+            _ => self.common(key, n, true)?,
         };
         debug!(target: "rustyline", "Vi command: {:?}", cmd);
         if cmd.is_repeatable_change() {
             self.last_cmd = cmd.clone();
+            self.last_insert_entry = None;
         }
         Ok(cmd)
     }
@@ -873,14 +1621,17 @@ impl InputState {
         wrt: &mut dyn Refresher,
         key: KeyEvent,
     ) -> Result<Cmd> {
-        let evt = key.into();
-        if let Some(cmd) = self.custom_binding(wrt, &evt, 0, true) {
+        let mut evt = key.into();
+        if let Some(cmd) = self.custom_binding(rdr, wrt, &mut evt, 0, true)? {
             return Ok(if cmd.is_repeatable() {
                 cmd.redo(None, wrt)
             } else {
                 cmd
             });
         }
+        if let Some(cmd) = self.without_defaults(key, 1) {
+            return Ok(cmd);
+        }
         let cmd = match key {
             E(K::Char(c), M::NONE) => {
                 if self.input_mode == InputMode::Replace {
@@ -890,10 +1641,14 @@ impl InputState {
                 }
             }
             E(K::Char('H'), M::CTRL) | E::BACKSPACE => Cmd::Kill(Movement::BackwardChar(1)),
+            E(K::Char('G'), M::CTRL) => Cmd::Abort,
             E(K::BackTab, M::NONE) => Cmd::CompleteBackward,
             E(K::Tab, M::NONE) => Cmd::Complete,
             // Don't complete hints when the cursor is not at the end of a line
             E(K::Right, M::NONE) if wrt.has_hint() && wrt.is_cursor_at_end() => Cmd::CompleteHint,
+            E(K::Right, M::CTRL) if wrt.has_hint() && wrt.is_cursor_at_end() => {
+                Cmd::CompleteHintWord
+            }
             E(K::Char(k), M::ALT) => {
                 debug!(target: "rustyline", "Vi fast command mode: {}", k);
                 self.input_mode = InputMode::Command;
@@ -904,10 +1659,11 @@ impl InputState {
             E::ESC => {
                 // vi-movement-mode/vi-command-mode
                 self.input_mode = InputMode::Command;
+                self.inserted = wrt.last_insert();
                 wrt.done_inserting();
                 Cmd::Move(Movement::BackwardChar(1))
             }
-            _ => self.common(rdr, wrt, evt, key, 1, true)?,
+            _ => self.common(key, 1, true)?,
         };
         debug!(target: "rustyline", "Vi insert: {:?}", cmd);
         if cmd.is_repeatable_change() {
@@ -917,6 +1673,7 @@ impl InputState {
                 // inserting...
             } else {
                 self.last_cmd = cmd.clone();
+                self.last_insert_entry = self.insert_entry.take();
             }
         }
         Ok(cmd)
@@ -929,8 +1686,13 @@ impl InputState {
         key: KeyEvent,
         n: RepeatCount,
     ) -> Result<Option<Movement>> {
-        let mut mvt = rdr.next_key(false)?;
+        if let E(K::Char(operator), _) = key {
+            self.operator = Some(operator);
+            self.update_mode(wrt, Some(n).filter(|&n| n > 1))?;
+        }
+        let mut mvt = self.next_key(rdr, false)?;
         if mvt == key {
+            self.operator = None;
             return Ok(Some(Movement::WholeLine));
         }
         let mut n = n;
@@ -939,15 +1701,44 @@ impl InputState {
             mvt = self.vi_arg_digit(rdr, wrt, digit)?;
             n = self.vi_num_args().saturating_mul(n);
         }
-        Ok(match mvt {
-            E(K::Char('$'), M::NONE) => Some(Movement::EndOfLine),
-            E(K::Char('0'), M::NONE) => Some(Movement::BeginningOfLine),
+        self.operator = None;
+        Ok(self.vi_motion(rdr, mvt, n, Some(key))?.flatten())
+    }
+
+    /// Movement of the motion `key` repeated `n` times, shared by the cursor
+    /// movements and the operators (`c`, `d`, `y`, `<`, `>`): `None` if `key`
+    /// is not a motion, `Some(None)` if it is incomplete (e.g. `f` followed by
+    /// `Esc`).
+    ///
+    /// For an `operator`, the end of a word is included and `cw` is `ce`.
+    fn vi_motion<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        key: KeyEvent,
+        n: RepeatCount,
+        operator: Option<KeyEvent>,
+    ) -> Result<Option<Option<Movement>>> {
+        let end = if operator.is_some() {
+            At::AfterEnd
+        } else {
+            At::BeforeEnd
+        };
+        // 'cw' is 'ce'
+        let start = if operator == Some(E(K::Char('c'), M::NONE)) {
+            At::AfterEnd
+        } else {
+            At::Start
+        };
+        Ok(Some(match key {
+            E(K::Char('$'), M::NONE) | E(K::End, M::NONE) => Some(Movement::EndOfLine),
+            E(K::Char('0'), M::NONE) | E(K::Home, M::NONE) => Some(Movement::BeginningOfLine),
             E(K::Char('^'), M::NONE) => Some(Movement::ViFirstPrint),
-            E(K::Char('b'), M::NONE) => Some(Movement::BackwardWord(n, Word::Vi)),
+            E(K::Char('b'), M::NONE) => Some(Movement::BackwardWord(n, Word::Vi)), /* vi-prev-word */
             E(K::Char('B'), M::NONE) => Some(Movement::BackwardWord(n, Word::Big)),
-            E(K::Char('e'), M::NONE) => Some(Movement::ForwardWord(n, At::AfterEnd, Word::Vi)),
-            E(K::Char('E'), M::NONE) => Some(Movement::ForwardWord(n, At::AfterEnd, Word::Big)),
+            E(K::Char('e'), M::NONE) => Some(Movement::ForwardWord(n, end, Word::Vi)),
+            E(K::Char('E'), M::NONE) => Some(Movement::ForwardWord(n, end, Word::Big)),
             E(K::Char(c), M::NONE) if c == 'f' || c == 'F' || c == 't' || c == 'T' => {
+                // vi-char-search
                 let cs = self.vi_char_search(rdr, c)?;
                 cs.map(|cs| Movement::ViCharSearch(n, cs))
             }
@@ -957,30 +1748,71 @@ impl InputState {
             E(K::Char(','), M::NONE) => self
                 .last_char_search
                 .map(|cs| Movement::ViCharSearch(n, cs.opposite())),
-            E(K::Char('h'), M::NONE) | E(K::Char('H'), M::CTRL) | E::BACKSPACE => {
-                Some(Movement::BackwardChar(n))
+            E(K::Char('h'), M::NONE)
+            | E(K::Char('H'), M::CTRL)
+            | E::BACKSPACE
+            | E(K::Left, M::NONE) => Some(Movement::BackwardChar(n)),
+            E(K::Char('l'), M::NONE) | E(K::Char(' '), M::NONE) | E(K::Right, M::NONE) => {
+                Some(Movement::ForwardChar(n))
             }
-            E(K::Char('l'), M::NONE) | E(K::Char(' '), M::NONE) => Some(Movement::ForwardChar(n)),
             E(K::Char('j'), M::NONE) | E(K::Char('+'), M::NONE) => Some(Movement::LineDown(n)),
             E(K::Char('k'), M::NONE) | E(K::Char('-'), M::NONE) => Some(Movement::LineUp(n)),
-            E(K::Char('w'), M::NONE) => {
-                // 'cw' is 'ce'
-                if key == E(K::Char('c'), M::NONE) {
-                    Some(Movement::ForwardWord(n, At::AfterEnd, Word::Vi))
+            E(K::Char('w'), M::NONE) => Some(Movement::ForwardWord(n, start, Word::Vi)), /* vi-next-word */
+            E(K::Char('W'), M::NONE) => Some(Movement::ForwardWord(n, start, Word::Big)),
+            E(K::Char(c @ ('i' | 'a')), M::NONE) if operator.is_some() => self
+                .vi_text_object(rdr, c == 'i')?
+                .map(|obj| Movement::ViTextObject(n, obj)),
+            _ => return Ok(None),
+        }))
+    }
+
+    /// Select the register named by the next key and return the key after
+    /// it, `None` if the name is not valid.
+    fn vi_register<R: RawReader>(&mut self, rdr: &mut R) -> Result<Option<KeyEvent>> {
+        match self.next_key(rdr, false)? {
+            E(K::Char(name), M::NONE)
+                if name.is_ascii_alphabetic() || name == '"' || name == '.' =>
+            {
+                self.register = Some(name);
+                Ok(Some(self.next_key(rdr, false)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn vi_text_object<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        inner: bool,
+    ) -> Result<Option<TextObject>> {
+        let key = self.next_key(rdr, false)?;
+        let (open, close) = match key {
+            E(K::Char(c @ ('w' | 'W')), M::NONE) => {
+                let word_def = if c == 'w' { Word::Vi } else { Word::Big };
+                return Ok(Some(if inner {
+                    TextObject::InnerWord(word_def)
                 } else {
-                    Some(Movement::ForwardWord(n, At::Start, Word::Vi))
-                }
+                    TextObject::AWord(word_def)
+                }));
             }
-            E(K::Char('W'), M::NONE) => {
-                // 'cW' is 'cE'
-                if key == E(K::Char('c'), M::NONE) {
-                    Some(Movement::ForwardWord(n, At::AfterEnd, Word::Big))
+            E(K::Char(c @ ('"' | '\'' | '`')), M::NONE) => {
+                return Ok(Some(if inner {
+                    TextObject::InnerQuote(c)
                 } else {
-                    Some(Movement::ForwardWord(n, At::Start, Word::Big))
-                }
+                    TextObject::AQuote(c)
+                }));
             }
-            _ => None,
-        })
+            E(K::Char('(' | ')' | 'b'), M::NONE) => ('(', ')'),
+            E(K::Char('[' | ']'), M::NONE) => ('[', ']'),
+            E(K::Char('{' | '}' | 'B'), M::NONE) => ('{', '}'),
+            E(K::Char('<' | '>'), M::NONE) => ('<', '>'),
+            _ => return Ok(None),
+        };
+        Ok(Some(if inner {
+            TextObject::InnerBlock(open, close)
+        } else {
+            TextObject::ABlock(open, close)
+        }))
     }
 
     fn vi_char_search<R: RawReader>(
@@ -988,7 +1820,7 @@ impl InputState {
         rdr: &mut R,
         cmd: char,
     ) -> Result<Option<CharSearch>> {
-        let ch = rdr.next_key(false)?;
+        let ch = self.next_key(rdr, false)?;
         Ok(match ch {
             E(K::Char(ch), M::NONE) => {
                 let cs = match cmd {
@@ -1005,15 +1837,7 @@ impl InputState {
         })
     }
 
-    fn common<R: RawReader>(
-        &mut self,
-        rdr: &mut R,
-        wrt: &mut dyn Refresher,
-        mut evt: Event,
-        key: KeyEvent,
-        n: RepeatCount,
-        positive: bool,
-    ) -> Result<Cmd> {
+    fn common(&mut self, key: KeyEvent, n: RepeatCount, positive: bool) -> Result<Cmd> {
         Ok(match key {
             E(K::Home, M::NONE) => Cmd::Move(Movement::BeginningOfLine),
             E(K::Left, M::NONE) => {
@@ -1023,7 +1847,29 @@ impl InputState {
                     Cmd::Move(Movement::ForwardChar(n))
                 }
             }
+            // GUI-style selection
+            E(K::Home, M::SHIFT) => Cmd::Select(Movement::BeginningOfLine),
+            E(K::End, M::SHIFT) => Cmd::Select(Movement::EndOfLine),
+            E(K::Left, M::SHIFT) => Cmd::Select(Movement::BackwardChar(n)),
+            E(K::Right, M::SHIFT) => Cmd::Select(Movement::ForwardChar(n)),
+            E(K::Up, M::SHIFT) => Cmd::Select(Movement::LineUp(n)),
+            E(K::Down, M::SHIFT) => Cmd::Select(Movement::LineDown(n)),
+            E(K::Left, M::CTRL_SHIFT) => Cmd::Select(Movement::BackwardWord(n, Word::Emacs)),
+            E(K::Right, M::CTRL_SHIFT) => {
+                Cmd::Select(Movement::ForwardWord(n, At::AfterEnd, Word::Emacs))
+            }
+            E(K::Insert, M::CTRL) => Cmd::ViYankTo(Movement::Region),
+            E(K::Insert, M::NONE) => Cmd::OverwriteMode,
+            E(K::Delete, M::SHIFT) => Cmd::Kill(Movement::Region),
             E(K::Char('C'), M::CTRL) => Cmd::Interrupt,
+            // with a numeric argument, delete chars instead
+            E(K::Char('D'), M::CTRL) if n > 1 || !positive => {
+                if positive {
+                    Cmd::Kill(Movement::ForwardChar(n))
+                } else {
+                    Cmd::Kill(Movement::BackwardChar(n))
+                }
+            }
             E(K::Char('D'), M::CTRL) => Cmd::EndOfFile,
             E(K::Delete, M::NONE) => {
                 if positive {
@@ -1047,7 +1893,13 @@ impl InputState {
             E(K::Up, M::NONE) => Cmd::LineUpOrPreviousHistory(1),
             E(K::Char('R'), M::CTRL) => Cmd::ReverseSearchHistory,
             E(K::Char('S'), M::CTRL) => Cmd::ForwardSearchHistory, // most terminals override Ctrl+S to suspend execution
-            E(K::Char('T'), M::CTRL) => Cmd::TransposeChars,
+            E(K::Char('T'), M::CTRL) => {
+                if positive {
+                    Cmd::TransposeChars(n)
+                } else {
+                    Cmd::Noop
+                }
+            }
             E(K::Char('U'), M::CTRL) => {
                 if positive {
                     Cmd::Kill(Movement::BeginningOfLine)
@@ -1056,9 +1908,9 @@ impl InputState {
                 }
             }
             // most terminals override Ctrl+Q to resume execution
-            E(K::Char('Q'), M::CTRL) => Cmd::QuotedInsert,
+            E(K::Char('Q'), M::CTRL) => Cmd::QuotedInsert(n),
             #[cfg(not(windows))]
-            E(K::Char('V'), M::CTRL) => Cmd::QuotedInsert,
+            E(K::Char('V'), M::CTRL) => Cmd::QuotedInsert(n),
             #[cfg(windows)]
             E(K::Char('V'), M::CTRL) => Cmd::PasteFromClipboard,
             E(K::Char('W'), M::CTRL) => {
@@ -1078,13 +1930,7 @@ impl InputState {
             E(K::Char('Z'), M::CTRL) => Cmd::Suspend,
             E(K::Char('_'), M::CTRL) => Cmd::Undo(n),
             E(K::UnknownEscSeq, M::NONE) => Cmd::Noop,
-            E(K::BracketedPasteStart, M::NONE) => {
-                let paste = rdr.read_pasted_text()?;
-                Cmd::Insert(1, paste)
-            }
-            _ => self
-                .custom_seq_binding(rdr, wrt, &mut evt, n, positive)?
-                .unwrap_or(Cmd::Unknown),
+            _ => Cmd::Unknown,
         })
     }
 
@@ -71,6 +71,24 @@ impl KeyEvent {
         }
     }
 
+    /// Character sent by a terminal for this key, if any (e.g. `\x01` for
+    /// Ctrl-A): inverse of [`KeyEvent::new`] without Alt modifier.
+    pub(crate) fn to_char(self) -> Option<char> {
+        use {KeyCode as K, KeyEvent as E, Modifiers as M};
+
+        Some(match Self::normalize(self) {
+            E(K::Char(c), M::NONE) | E(K::Char(c), M::SHIFT) => c,
+            E(K::Char(' '), M::CTRL) => '\0',
+            E(K::Char('?'), M::CTRL) => '\x7f',
+            E(K::Char(c @ '@'..='_'), M::CTRL) => char::from(c as u8 & 0x1f),
+            E(K::Backspace, M::NONE) => '\x7f',
+            E(K::Enter, M::NONE) => '\r',
+            E(K::Esc, M::NONE) => '\x1b',
+            E(K::Tab, M::NONE) => '\t',
+            _ => return None,
+        })
+    }
+
     /// Constructor from `char` with Ctrl modifier
     pub fn ctrl(c: char) -> Self {
         Self::new(c, Modifiers::CTRL)
@@ -135,12 +153,21 @@ pub enum KeyCode {
     Esc,
     /// Function key
     F(u8),
+    /// Terminal window gained focus (on unix platform, when focus reporting
+    /// is enabled by the application)
+    FocusGained,
+    /// Terminal window lost focus (on unix platform, when focus reporting is
+    /// enabled by the application)
+    FocusLost,
     /// ⇱
     Home,
     /// Insert key
     Insert,
     /// ← arrow key
     Left,
+    /// Mouse event (on unix platform, when mouse tracking is enabled by the
+    /// application)
+    Mouse(MouseEvent),
     /// \0
     Null,
     /// ⇟
@@ -155,6 +182,65 @@ pub enum KeyCode {
     Up,
 }
 
+/// Mouse button
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MouseButton {
+    /// Left button
+    Left,
+    /// Middle button
+    Middle,
+    /// Right button
+    Right,
+}
+
+/// Kind of mouse event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MouseEventKind {
+    /// Button pressed
+    Down(MouseButton),
+    /// Button released
+    Up,
+    /// Mouse moved with a button pressed
+    Drag(MouseButton),
+    /// Wheel scrolled up
+    ScrollUp,
+    /// Wheel scrolled down
+    ScrollDown,
+}
+
+/// Mouse event reported by the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MouseEvent {
+    /// What happened
+    pub kind: MouseEventKind,
+    /// Modifier keys pressed
+    pub modifiers: Modifiers,
+    /// Screen row (0-based)
+    pub row: u16,
+    /// Screen column (0-based)
+    pub column: u16,
+}
+
+impl MouseEvent {
+    /// Constructor, without position
+    pub fn new(kind: MouseEventKind, modifiers: Modifiers) -> Self {
+        Self {
+            kind,
+            modifiers,
+            row: 0,
+            column: 0,
+        }
+    }
+}
+
+impl From<MouseEventKind> for MouseEvent {
+    fn from(kind: MouseEventKind) -> Self {
+        Self::new(kind, Modifiers::NONE)
+    }
+}
+
 bitflags::bitflags! {
     /// The set of modifier keys that were triggered along with a key press.
     pub struct Modifiers: u8 {
@@ -164,6 +250,10 @@ bitflags::bitflags! {
         const ALT  = 1<<2;
         /// Shift modifier
         const SHIFT = 1<<1;
+        /// Key sent in application keypad mode (`ESC O` prefixed sequence,
+        /// on unix platform): it triggers its own binding if any, the one of
+        /// the key without this flag otherwise.
+        const KEYPAD = 1<<4;
 
         /// No modifier
         const NONE = 0;
@@ -199,4 +289,19 @@ mod tests {
         assert_eq!(E::from('A'), E::normalize(E(K::Char('A'), M::SHIFT)));
         assert_eq!(E(K::BackTab, M::NONE), E::normalize(E(K::Tab, M::SHIFT)));
     }
+
+    #[test]
+    fn to_char() {
+        for c in (0..0x20u8).chain(0x20..0x7f).map(char::from) {
+            if c == '\x08' {
+                continue; // Backspace
+            }
+            assert_eq!(Some(c), E::from(c).to_char(), "{:?}", c);
+        }
+        assert_eq!(Some('\x7f'), E::BACKSPACE.to_char());
+        assert_eq!(Some('\x01'), E::ctrl('a').to_char());
+        assert_eq!(Some('é'), E::from('é').to_char());
+        assert_eq!(None, E::alt('a').to_char());
+        assert_eq!(None, E(K::Up, M::NONE).to_char());
+    }
 }
@@ -1,4 +1,6 @@
 //! Kill Ring management
+use std::collections::HashMap;
+
 use crate::line_buffer::{DeleteListener, Direction};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -21,6 +23,12 @@ pub struct KillRing {
     // whether or not the last command was a kill or a yank
     last_action: Action,
     killing: bool,
+    // vi named registers (`"a` to `"z`)
+    registers: HashMap<char, String>,
+    // register selected for the current command
+    register: Option<char>,
+    // text inserted before the last switch to vi command mode (`".`)
+    last_insert: String,
 }
 
 impl KillRing {
@@ -31,6 +39,9 @@ impl KillRing {
             index: 0,
             last_action: Action::Other,
             killing: false,
+            registers: HashMap::new(),
+            register: None,
+            last_insert: String::new(),
         }
     }
 
@@ -39,8 +50,56 @@ impl KillRing {
         self.last_action = Action::Other;
     }
 
+    /// Select the vi register of the next kills and yanks, the kill-ring
+    /// alone when `None`.
+    pub fn select_register(&mut self, name: Option<char>) {
+        self.register = name;
+    }
+
+    /// Content of the vi register `name`: `a` to `z` (case-insensitive), `"`
+    /// for the last killed text or `.` for the last inserted text.
+    pub fn register(&self, name: char) -> Option<&str> {
+        match name {
+            '"' => self.slots.get(self.index).map(String::as_str),
+            '.' => Some(self.last_insert.as_str()).filter(|text| !text.is_empty()),
+            _ => self
+                .registers
+                .get(&name.to_ascii_lowercase())
+                .map(String::as_str),
+        }
+    }
+
+    /// Set the vi register `name`, appending to it when `name` is uppercase.
+    /// Other registers than `a` to `z` are read-only.
+    pub fn set_register(&mut self, name: char, text: &str) {
+        if name.is_ascii_lowercase() {
+            self.registers.insert(name, text.to_owned());
+        } else if name.is_ascii_uppercase() {
+            self.registers
+                .entry(name.to_ascii_lowercase())
+                .or_default()
+                .push_str(text);
+        }
+    }
+
+    /// Set the `.` register.
+    pub fn set_last_insert(&mut self, text: &str) {
+        self.last_insert = text.to_owned();
+    }
+
     /// Add `text` to the kill-ring.
     pub fn kill(&mut self, text: &str, dir: Mode) {
+        if let Some(name) = self.register.filter(char::is_ascii_alphabetic) {
+            if let Action::Kill = self.last_action {
+                let register = self.registers.entry(name.to_ascii_lowercase()).or_default();
+                match dir {
+                    Mode::Append => register.push_str(text),
+                    Mode::Prepend => register.insert_str(0, text),
+                };
+            } else {
+                self.set_register(name, text);
+            }
+        }
         if let Action::Kill = self.last_action {
             if self.slots.capacity() == 0 {
                 // disabled
@@ -73,6 +132,14 @@ impl KillRing {
     /// Yank previously killed text.
     /// Return `None` when kill-ring is empty.
     pub fn yank(&mut self) -> Option<&String> {
+        if let Some(name) = self.register.filter(|&name| name != '"') {
+            // vi only, no yank-pop
+            return if name == '.' {
+                Some(&self.last_insert).filter(|text| !text.is_empty())
+            } else {
+                self.registers.get(&name.to_ascii_lowercase())
+            };
+        }
         if self.slots.is_empty() {
             None
         } else {
@@ -139,6 +206,33 @@ mod tests {
         assert_eq!(Action::Kill, kill_ring.last_action);
     }
 
+    #[test]
+    fn registers() {
+        let mut kill_ring = KillRing::new(2);
+        kill_ring.select_register(Some('a'));
+        kill_ring.kill("word1", Mode::Append);
+        kill_ring.kill(" word2", Mode::Append);
+        kill_ring.reset();
+        kill_ring.select_register(Some('A'));
+        kill_ring.kill("word3 ", Mode::Prepend);
+        kill_ring.reset();
+        kill_ring.select_register(None);
+        kill_ring.kill("word4", Mode::Append);
+        assert_eq!(Some("word1 word2word3 "), kill_ring.register('a'));
+        assert_eq!(Some("word4"), kill_ring.register('"'));
+        assert_eq!(None, kill_ring.register('b'));
+
+        kill_ring.set_register('.', "read-only");
+        assert_eq!(None, kill_ring.register('.'));
+        kill_ring.set_last_insert("inserted");
+        kill_ring.select_register(Some('.'));
+        assert_eq!(Some(&"inserted".to_owned()), kill_ring.yank());
+        kill_ring.select_register(Some('A'));
+        assert_eq!(Some(&"word1 word2word3 ".to_owned()), kill_ring.yank());
+        kill_ring.select_register(Some('"'));
+        assert_eq!(Some(&"word4".to_owned()), kill_ring.yank());
+    }
+
     #[test]
     fn one_kill() {
         let mut kill_ring = KillRing::new(2);
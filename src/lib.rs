@@ -17,61 +17,128 @@
 //! ```
 #![warn(missing_docs)]
 
+pub mod abbreviation;
+pub mod async_history;
 mod binding;
 mod command;
 pub mod completion;
 pub mod config;
+mod custom_mode;
 mod edit;
+#[cfg(feature = "history-encrypted")]
+pub mod encrypted_history;
 pub mod error;
+pub mod expansion;
+mod external_editor;
 pub mod highlight;
 pub mod hint;
 pub mod history;
+pub mod inputrc;
+mod kbd_macro;
 mod keymap;
 mod keys;
 mod kill_ring;
 mod layout;
 pub mod line_buffer;
+mod prompter;
+pub mod session_history;
+#[cfg(feature = "history-sqlite")]
+pub mod sqlite_history;
+pub mod terminal;
 mod tty;
 mod undo;
 pub mod validate;
 
 use std::fmt;
 use std::io::{self, Write};
-use std::path::Path;
+use std::marker::PhantomData;
+use std::mem;
+use std::path::{Path, PathBuf};
 use std::result;
 use std::sync::{Arc, Mutex, RwLock};
 
 use log::debug;
-use radix_trie::Trie;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use crate::tty::{RawMode, Renderer, Term, Terminal};
+use crate::tty::{RawMode, RawReader, Renderer, Term, Terminal};
 
-pub use crate::binding::{ConditionalEventHandler, Event, EventContext, EventHandler};
-use crate::completion::{longest_common_prefix, Candidate, Completer};
+use crate::abbreviation::{Abbreviations, Abbreviator};
+use crate::binding::Bindings;
+pub use crate::binding::{
+    Binding, BindingConflict, BindingSource, ConditionalEventHandler, Event, EventContext,
+    EventHandler, FunctionRegistry, Keymap, Layer,
+};
+use crate::completion::{
+    longest_common_prefix, longest_common_prefix_ignore_case, Candidate, Completer, Correction,
+};
 pub use crate::config::{
-    ColorMode, CompletionType, Config, EditMode, HistoryDuplicates, OutputStreamType,
+    BellStyle, ColorMode, CompletionType, Config, EditMode, HistChars, HistoryDuplicates,
+    HistoryEviction, HistoryFormat, HistorySearchMode, OutputStreamType, WordBoundary,
 };
+pub use crate::custom_mode::CustomMode;
+use crate::custom_mode::CustomModes;
 use crate::edit::State;
 use crate::highlight::Highlighter;
 use crate::hint::Hinter;
-use crate::history::{Direction, History};
-pub use crate::keymap::{Anchor, At, CharSearch, Cmd, InputMode, Movement, RepeatCount, Word};
+use crate::history::{Direction, History, HistoryFilter, HistoryStore};
+pub use crate::kbd_macro::Macro;
+use crate::kbd_macro::MacroRecorder;
+pub use crate::keymap::{
+    Anchor, At, CharSearch, Cmd, InputMode, ModeState, Movement, RepeatCount, TextObject, Word,
+};
 use crate::keymap::{InputState, Refresher};
-pub use crate::keys::{KeyCode, KeyEvent, Modifiers};
+pub use crate::keys::{KeyCode, KeyEvent, Modifiers, MouseButton, MouseEvent, MouseEventKind};
 use crate::kill_ring::KillRing;
+use crate::layout::Position;
+use crate::line_buffer::{LineBuffer, MAX_LINE};
+pub use crate::prompter::{CustomCommand, Prompter};
 
 use crate::validate::Validator;
 
 /// The error type for I/O and Linux Syscalls (Errno)
 pub type Result<T> = result::Result<T, error::ReadlineError>;
 
+fn common_prefix<'c, C: Candidate>(candidates: &'c [C], config: &Config) -> Option<&'c str> {
+    if config.completion_ignore_case() {
+        longest_common_prefix_ignore_case(candidates)
+    } else {
+        longest_common_prefix(candidates)
+    }
+}
+
 /// Completes the line/word
 fn complete_line<H: Helper>(
     rdr: &mut <Terminal as Term>::Reader,
     s: &mut State<'_, '_, H>,
     input_state: &mut InputState,
     config: &Config,
+) -> Result<Option<Cmd>> {
+    // get a list of completions
+    let (start, candidates) = match complete(rdr, s, config)? {
+        Some(completions) => completions,
+        None => return Ok(None), // cancelled
+    };
+    if !candidates.is_empty() {
+        return complete_word(rdr, s, input_state, config, start, &candidates);
+    }
+    // if no completions, maybe offer corrections
+    let corrections = corrections(s, config, start)?;
+    if corrections.is_empty() {
+        s.out.beep()?;
+        Ok(None)
+    } else {
+        complete_word(rdr, s, input_state, config, start, &corrections)
+    }
+}
+
+/// Completes the word starting at `start` with the `candidates`
+fn complete_word<C: Candidate, H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    config: &Config,
+    start: usize,
+    candidates: &[C],
 ) -> Result<Option<Cmd>> {
     #[cfg(all(unix, feature = "with-fuzzy"))]
     use skim::prelude::{
@@ -79,115 +146,42 @@ fn complete_line<H: Helper>(
     };
 
     let completer = s.helper.unwrap();
-    // get a list of completions
-    let (start, candidates) = completer.complete(&s.line, s.line.pos(), &s.ctx)?;
-    // if no completions, we are done
-    if candidates.is_empty() {
-        s.out.beep()?;
-        Ok(None)
-    } else if CompletionType::Circular == config.completion_type() {
-        let mark = s.changes.borrow_mut().begin();
-        // Save the current edited line before overwriting it
-        let backup = s.line.as_str().to_owned();
-        let backup_pos = s.line.pos();
-        let mut cmd;
-        let mut i = 0;
-        loop {
-            // Show completion or original buffer
-            if i < candidates.len() {
-                let candidate = candidates[i].replacement();
-                // TODO we can't highlight the line buffer directly
-                /*let candidate = if let Some(highlighter) = s.highlighter {
-                    highlighter.highlight_candidate(candidate, CompletionType::Circular)
-                } else {
-                    Borrowed(candidate)
-                };*/
-                completer.update(&mut s.line, start, candidate);
-            } else {
-                // Restore current edited line
-                s.line.update(&backup, backup_pos);
-            }
-            s.refresh_line()?;
-
-            cmd = s.next_cmd(input_state, rdr, true)?;
-            match cmd {
-                Cmd::Complete => {
-                    i = (i + 1) % (candidates.len() + 1); // Circular
-                    if i == candidates.len() {
-                        s.out.beep()?;
-                    }
-                }
-                Cmd::CompleteBackward => {
-                    if i == 0 {
-                        i = candidates.len(); // Circular
-                        s.out.beep()?;
-                    } else {
-                        i = (i - 1) % (candidates.len() + 1); // Circular
-                    }
-                }
-                Cmd::Abort => {
-                    // Re-show original buffer
-                    if i < candidates.len() {
-                        s.line.update(&backup, backup_pos);
-                        s.refresh_line()?;
-                    }
-                    s.changes.borrow_mut().truncate(mark);
-                    return Ok(None);
-                }
-                _ => {
-                    s.changes.borrow_mut().end();
-                    break;
-                }
+    if CompletionType::Circular == config.completion_type() {
+        menu_complete(rdr, s, input_state, config, start, candidates, false)
+    } else if CompletionType::List == config.completion_type() {
+        if candidates.len() == 1 {
+            let elected = completion::elected(&candidates[0]);
+            if elected != s.line[start..s.line.pos()] {
+                completer.update(&mut s.line, start, &elected);
+                s.refresh_line()?;
             }
+            return Ok(None);
         }
-        Ok(Some(cmd))
-    } else if CompletionType::List == config.completion_type() {
-        if let Some(lcp) = longest_common_prefix(&candidates) {
+        let mut modified = false;
+        if let Some(lcp) = common_prefix(candidates, config) {
             // if we can extend the item, extend it
             if lcp.len() > s.line.pos() - start {
                 completer.update(&mut s.line, start, lcp);
                 s.refresh_line()?;
+                modified = true;
             }
         }
-        // beep if ambiguous
-        if candidates.len() > 1 {
-            s.out.beep()?;
-        } else {
-            return Ok(None);
-        }
-        // we can't complete any further, wait for second tab
-        let mut cmd = s.next_cmd(input_state, rdr, true)?;
-        // if any character other than tab, pass it to the main loop
-        if cmd != Cmd::Complete {
-            return Ok(Some(cmd));
-        }
-        // move cursor to EOL to avoid overwriting the command line
-        let save_pos = s.line.pos();
-        s.edit_move_end()?;
-        s.line.set_pos(save_pos);
-        // we got a second tab, maybe show list of possible completions
-        let show_completions = if candidates.len() > config.completion_prompt_limit() {
-            let msg = format!("\nDisplay all {} possibilities? (y or n)", candidates.len());
-            s.out.write_and_flush(msg.as_bytes())?;
-            s.layout.end.row += 1;
-            while cmd != Cmd::SelfInsert(1, 'y')
-                && cmd != Cmd::SelfInsert(1, 'Y')
-                && cmd != Cmd::SelfInsert(1, 'n')
-                && cmd != Cmd::SelfInsert(1, 'N')
-                && cmd != Cmd::Kill(Movement::BackwardChar(1))
-            {
-                cmd = s.next_cmd(input_state, rdr, false)?;
+        if !(config.completion_show_all_if_ambiguous()
+            || (config.completion_show_all_if_unmodified() && !modified))
+        {
+            // beep if ambiguous (like readline, not in vi mode)
+            if config.edit_mode() != EditMode::Vi {
+                s.out.beep()?;
+            }
+            // we can't complete any further, wait for second tab
+            let cmd = s.next_cmd(input_state, rdr, true)?;
+            // if any character other than tab, pass it to the main loop
+            if cmd != Cmd::Complete {
+                return Ok(Some(cmd));
             }
-            matches!(cmd, Cmd::SelfInsert(1, 'y') | Cmd::SelfInsert(1, 'Y'))
-        } else {
-            true
-        };
-        if show_completions {
-            page_completions(rdr, s, input_state, &candidates)
-        } else {
-            s.refresh_line()?;
-            Ok(None)
         }
+        // we got a second tab, maybe show list of possible completions
+        list_completions(rdr, s, input_state, config, start, candidates)
     } else {
         // if fuzzy feature is enabled and on unix based systems check for the
         // corresponding completion_type
@@ -241,7 +235,7 @@ fn complete_line<H: Helper>(
                         .downcast_ref::<Candidate>() // downcast to concrete type
                         .expect("something wrong with downcast");
                     if let Some(candidate) = candidates.get(item.index) {
-                        completer.update(&mut s.line, start, candidate.replacement());
+                        completer.update(&mut s.line, start, &completion::elected(candidate));
                     }
                 }
                 s.refresh_line()?;
@@ -251,50 +245,536 @@ fn complete_line<H: Helper>(
     }
 }
 
-/// Completes the current hint
-fn complete_hint_line<H: Helper>(s: &mut State<'_, '_, H>) -> Result<()> {
+/// Spelling corrections of the word starting at `start` (none unless
+/// `completion_correction_distance` is set)
+fn corrections<H: Helper>(
+    s: &State<'_, '_, H>,
+    config: &Config,
+    start: usize,
+) -> Result<Vec<Correction>> {
+    let distance = config.completion_correction_distance();
+    if distance == 0 {
+        return Ok(Vec::new());
+    }
+    let pos = s.line.pos();
+    let vocabulary = s.helper.unwrap().vocabulary(&s.line, pos, &s.ctx)?;
+    Ok(completion::corrections(
+        &s.line[start..pos],
+        vocabulary,
+        distance,
+    ))
+}
+
+/// Lists the possible completions (`possible-completions`) without changing
+/// the line
+fn possible_completions_line<H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    config: &Config,
+) -> Result<Option<Cmd>> {
+    let (start, candidates) = match complete(rdr, s, config)? {
+        Some(completions) => completions,
+        None => return Ok(None), // cancelled
+    };
+    if !candidates.is_empty() {
+        return list_completions(rdr, s, input_state, config, start, &candidates);
+    }
+    let corrections = corrections(s, config, start)?;
+    if corrections.is_empty() {
+        s.out.beep()?;
+        Ok(None)
+    } else {
+        list_completions(rdr, s, input_state, config, start, &corrections)
+    }
+}
+
+/// Replaces the word with all the possible completions, separated by spaces
+/// (`insert-completions`)
+fn insert_completions_line<H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    config: &Config,
+) -> Result<()> {
+    let (start, candidates) = match complete(rdr, s, config)? {
+        Some(completions) => completions,
+        None => return Ok(()), // cancelled
+    };
+    if candidates.is_empty() {
+        return s.out.beep();
+    }
+    let mut text = String::new();
+    for candidate in &candidates {
+        text.push_str(candidate.replacement());
+        text.push(' ');
+    }
+    s.line.replace(start..s.line.pos(), &text);
+    s.refresh_line()
+}
+
+/// Lists the possible completions below the line, asking first when there
+/// are more than `completion-query-items`
+fn list_completions<C: Candidate, H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    config: &Config,
+    start: usize,
+    candidates: &[C],
+) -> Result<Option<Cmd>> {
+    // move cursor to EOL to avoid overwriting the command line
+    let save_pos = s.line.pos();
+    s.edit_move_end()?;
+    s.line.set_pos(save_pos);
+    let mut cmd = Cmd::Noop;
+    let show_completions = if candidates.len() > config.completion_prompt_limit() {
+        let msg = format!("\nDisplay all {} possibilities? (y or n)", candidates.len());
+        s.out.write_and_flush(msg.as_bytes())?;
+        s.layout.end.row += 1;
+        while cmd != Cmd::SelfInsert(1, 'y')
+            && cmd != Cmd::SelfInsert(1, 'Y')
+            && cmd != Cmd::SelfInsert(1, 'n')
+            && cmd != Cmd::SelfInsert(1, 'N')
+            && cmd != Cmd::Kill(Movement::BackwardChar(1))
+        {
+            cmd = s.next_cmd(input_state, rdr, false)?;
+        }
+        matches!(cmd, Cmd::SelfInsert(1, 'y') | Cmd::SelfInsert(1, 'Y'))
+    } else {
+        true
+    };
+    if show_completions {
+        page_completions(rdr, s, input_state, config, start, candidates)
+    } else {
+        s.refresh_line()?;
+        Ok(None)
+    }
+}
+
+/// Returns the start position and the completion candidates, waiting for the
+/// ones of an asynchronous completer unless the user goes on typing (`None`).
+/// The candidates of a group are gathered.
+///
+/// Only a key press cancels the completion: `wait_for_input` ignores the
+/// other input, like the release of the key which started the completion.
+fn complete<H: Helper, R: RawReader>(
+    rdr: &mut R,
+    s: &mut State<'_, '_, H>,
+    config: &Config,
+) -> Result<Option<(usize, Vec<H::Candidate>)>> {
+    use std::task::{self, Poll, Wake, Waker};
+
+    /// The future is polled periodically while waiting for input
+    struct NoopWaker;
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn grouped<C: Candidate>((start, mut candidates): (usize, Vec<C>)) -> Option<(usize, Vec<C>)> {
+        completion::sort_by_group(&mut candidates);
+        Some((start, candidates))
+    }
+
+    let completer = s.helper.unwrap();
+    let mut future = match completer.complete_async(&s.line, s.line.pos(), &s.ctx) {
+        Some(future) => future,
+        None => {
+            return completer
+                .complete(&s.line, s.line.pos(), &s.ctx)
+                .map(grouped)
+        }
+    };
+    let waker = Waker::from(Arc::new(NoopWaker));
+    let mut cx = task::Context::from_waker(&waker);
+    let mut pending = false;
+    loop {
+        if let Poll::Ready(completions) = future.as_mut().poll(&mut cx) {
+            if pending {
+                s.refresh_line()?; // hide the indicator
+            }
+            return completions.map(grouped);
+        }
+        if !pending {
+            pending = true;
+            let indicator = config.completion_pending_indicator();
+            if !indicator.is_empty() {
+                s.refresh_line_with_msg(Some(indicator))?;
+            }
+        }
+        if rdr.wait_for_input(COMPLETION_POLL_MS)? {
+            // the key is processed as usual
+            s.refresh_line()?;
+            return Ok(None);
+        }
+    }
+}
+
+/// Period (milliseconds) of the polling of asynchronous completions
+const COMPLETION_POLL_MS: i32 = 10;
+
+/// Cycles through the possible completions (`menu-complete` and
+/// `menu-complete-backward`)
+fn menu_complete_line<H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    config: &Config,
+    backward: bool,
+) -> Result<Option<Cmd>> {
+    let (start, candidates) = match complete(rdr, s, config)? {
+        Some(completions) => completions,
+        None => return Ok(None), // cancelled
+    };
+    if !candidates.is_empty() {
+        return menu_complete(rdr, s, input_state, config, start, &candidates, backward);
+    }
+    let corrections = corrections(s, config, start)?;
+    if corrections.is_empty() {
+        s.out.beep()?;
+        Ok(None)
+    } else {
+        menu_complete(rdr, s, input_state, config, start, &corrections, backward)
+    }
+}
+
+/// Replaces the word starting at `start` with each candidate in turn, the
+/// original word being shown after the last one
+fn menu_complete<C: Candidate, H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    config: &Config,
+    start: usize,
+    candidates: &[C],
+    backward: bool,
+) -> Result<Option<Cmd>> {
+    let completer = s.helper.unwrap();
+    let mark = s.changes.borrow_mut().begin();
+    // Save the current edited line before overwriting it
+    let backup = s.line.as_str().to_owned();
+    let backup_pos = s.line.pos();
+    let mut cmd;
+    let mut i = 0;
+    if config.menu_complete_display_prefix() && candidates.len() > 1 {
+        // Show the common prefix first, as if it were the original buffer
+        if let Some(lcp) = common_prefix(candidates, config) {
+            if lcp.len() > s.line.pos() - start {
+                completer.update(&mut s.line, start, lcp);
+                i = candidates.len();
+            }
+        }
+    }
+    if backward && i == 0 {
+        i = candidates.len() - 1;
+    }
+    let prefix = s.line.as_str().to_owned();
+    let prefix_pos = s.line.pos();
+    let show_menu = config.completion_show_menu();
+    loop {
+        // Show completion or original buffer
+        if i < candidates.len() {
+            let candidate = completion::elected(&candidates[i]);
+            // TODO we can't highlight the line buffer directly
+            /*let candidate = if let Some(highlighter) = s.highlighter {
+                highlighter.highlight_candidate(candidate, CompletionType::Circular)
+            } else {
+                Borrowed(candidate)
+            };*/
+            completer.update(&mut s.line, start, &candidate);
+        } else {
+            // Restore current edited line
+            s.line.update(&prefix, prefix_pos);
+        }
+        if show_menu {
+            let menu = completion_menu(
+                candidates,
+                i,
+                Columns::display_width(config, s.out.get_columns()),
+                s.out.get_rows(),
+                config.print_completions_horizontally(),
+                s.highlighter(),
+            );
+            s.refresh_line_with_msg(Some(&menu))?;
+        } else {
+            s.refresh_line()?;
+        }
+
+        cmd = s.next_cmd(input_state, rdr, true)?;
+        match cmd {
+            Cmd::Complete | Cmd::MenuComplete => {
+                i = (i + 1) % (candidates.len() + 1); // Circular
+                if i == candidates.len() {
+                    s.out.beep()?;
+                }
+            }
+            Cmd::CompleteBackward | Cmd::MenuCompleteBackward => {
+                if i == 0 {
+                    i = candidates.len(); // Circular
+                    s.out.beep()?;
+                } else {
+                    i = (i - 1) % (candidates.len() + 1); // Circular
+                }
+            }
+            Cmd::Abort => {
+                // Re-show original buffer
+                if s.line.as_str() != backup || show_menu {
+                    s.line.update(&backup, backup_pos);
+                    s.refresh_line()?;
+                }
+                s.changes.borrow_mut().truncate(mark);
+                return Ok(None);
+            }
+            _ => {
+                s.changes.borrow_mut().end();
+                if show_menu {
+                    s.refresh_line()?; // hide the menu
+                }
+                break;
+            }
+        }
+    }
+    Ok(Some(cmd))
+}
+
+/// Candidates laid out in columns below the line (on at most half the
+/// screen), the `selected` one highlighted in reverse video
+fn completion_menu<C: Candidate>(
+    candidates: &[C],
+    selected: usize,
+    cols: usize,
+    rows: usize,
+    horizontal: bool,
+    highlighter: Option<&dyn Highlighter>,
+) -> String {
+    use std::cmp;
+
+    let list = ListRow::all(candidates, cols, horizontal);
+    let visible = cmp::min(list.len(), cmp::max(1, rows / 2));
+    // scroll to show the selected candidate
+    let first_row = list
+        .iter()
+        .position(|row| row.contains(selected))
+        .map_or(0, |row| row.saturating_sub(visible - 1));
+    let mut menu = String::new();
+    for row in &list[first_row..first_row + visible] {
+        menu.push('\n');
+        let (start, columns, row) = match *row {
+            ListRow::Header(group) => {
+                match highlighter {
+                    Some(highlighter) => {
+                        menu.push_str(&highlighter.highlight_candidate_group(group))
+                    }
+                    None => menu.push_str(group),
+                }
+                continue;
+            }
+            ListRow::Candidates {
+                start,
+                columns,
+                row,
+            } => (start, columns, row),
+        };
+        for col in 0..columns.num_cols {
+            let i = match columns.index(row, col) {
+                Some(i) => start + i,
+                None => break,
+            };
+            let display = candidates[i].display();
+            match highlighter {
+                Some(highlighter) => {
+                    if i == selected {
+                        menu.push_str("\x1b[7m");
+                    }
+                    let matches = candidates[i].matches();
+                    menu.push_str(&highlighter.highlight_candidate_matches(display, matches));
+                    if i == selected {
+                        menu.push_str("\x1b[0m");
+                    }
+                }
+                None => menu.push_str(display),
+            }
+            if columns.index(row, col + 1).is_some() {
+                for _ in display.width()..columns.width {
+                    menu.push(' ');
+                }
+            }
+        }
+    }
+    menu
+}
+
+/// Layout of the completion candidates in columns
+#[derive(Clone, Copy)]
+struct Columns {
+    len: usize,
+    /// Width of the columns (padding included)
+    width: usize,
+    num_cols: usize,
+    num_rows: usize,
+    /// Candidates sorted in rows rather than in columns
+    horizontal: bool,
+}
+
+impl Columns {
+    /// Lay out `candidates` on `cols` screen columns (one per row if 0)
+    fn new<C: Candidate>(candidates: &[C], cols: usize, horizontal: bool) -> Self {
+        use std::cmp;
+
+        let min_col_pad = 2;
+        let width = candidates
+            .iter()
+            .map(|c| c.display().width())
+            .max()
+            .unwrap_or(0)
+            + min_col_pad;
+        let (width, num_cols) = if cols == 0 {
+            (width, 1)
+        } else {
+            let width = cmp::min(cols, width);
+            (width, cmp::max(1, cols / width))
+        };
+        Self {
+            len: candidates.len(),
+            width,
+            num_cols,
+            num_rows: candidates.len().div_ceil(num_cols),
+            horizontal,
+        }
+    }
+
+    /// Screen columns used to list candidates (`completion-display-width`)
+    fn display_width(config: &Config, screen_cols: usize) -> usize {
+        match config.completion_display_width() {
+            Some(width) if width <= screen_cols => width,
+            _ => screen_cols,
+        }
+    }
+
+    /// Index of the candidate displayed at `row` and `col`
+    fn index(&self, row: usize, col: usize) -> Option<usize> {
+        if col >= self.num_cols {
+            return None;
+        }
+        let i = if self.horizontal {
+            row * self.num_cols + col
+        } else {
+            col * self.num_rows + row
+        };
+        Some(i).filter(|&i| i < self.len)
+    }
+
+    /// Row of the candidate at `index`
+    fn row(&self, index: usize) -> usize {
+        if self.horizontal {
+            index / self.num_cols
+        } else {
+            index % self.num_rows
+        }
+    }
+}
+
+/// Row of the listing of the completion candidates
+#[derive(Clone, Copy)]
+enum ListRow<'c> {
+    /// Header of a group of candidates (see [`Candidate::group`])
+    Header(&'c str),
+    /// `row` of the `columns` of the group starting at index `start`
+    Candidates {
+        start: usize,
+        columns: Columns,
+        row: usize,
+    },
+}
+
+impl<'c> ListRow<'c> {
+    /// Rows listing `candidates`, the columns of each group under its header
+    fn all<C: Candidate>(candidates: &'c [C], cols: usize, horizontal: bool) -> Vec<Self> {
+        let mut rows = Vec::new();
+        for (group, range) in completion::groups(candidates) {
+            if let Some(group) = group {
+                rows.push(ListRow::Header(group));
+            }
+            let start = range.start;
+            let columns = Columns::new(&candidates[range], cols, horizontal);
+            rows.extend((0..columns.num_rows).map(|row| ListRow::Candidates {
+                start,
+                columns,
+                row,
+            }));
+        }
+        rows
+    }
+
+    /// Whether the candidate at `index` is displayed on this row
+    fn contains(&self, index: usize) -> bool {
+        match *self {
+            ListRow::Header(_) => false,
+            ListRow::Candidates {
+                start,
+                columns,
+                row,
+            } => index >= start && index - start < columns.len && columns.row(index - start) == row,
+        }
+    }
+}
+
+/// Completes the current hint (only its first word if `word`)
+fn complete_hint_line<H: Helper>(s: &mut State<'_, '_, H>, word: bool) -> Result<()> {
     let hint = match s.hint.as_ref() {
         Some(hint) => hint,
         None => return Ok(()),
     };
     s.line.move_end();
     if let Some(text) = hint.completion() {
+        let text = if word { hint::first_word(text) } else { text };
         if s.line.yank(text, 1).is_none() {
             s.out.beep()?;
         }
     } else {
         s.out.beep()?;
     }
-    s.refresh_line_with_msg(None)?;
+    if word {
+        s.refresh_line()?; // show the rest of the hint
+    } else {
+        s.refresh_line_with_msg(None)?;
+    }
     Ok(())
 }
 
+/// Lists the possible completions, one screen at a time (`--More--` prompt)
+/// unless `page-completions` is off: space shows the next screen, return the
+/// next line, tab switches to menu completion and any other key quits.
 fn page_completions<C: Candidate, H: Helper>(
     rdr: &mut <Terminal as Term>::Reader,
     s: &mut State<'_, '_, H>,
     input_state: &mut InputState,
+    config: &Config,
+    start: usize,
     candidates: &[C],
 ) -> Result<Option<Cmd>> {
-    use std::cmp;
-
-    let min_col_pad = 2;
     let cols = s.out.get_columns();
-    let max_width = cmp::min(
-        cols,
-        candidates
-            .iter()
-            .map(|s| s.display().width())
-            .max()
-            .unwrap()
-            + min_col_pad,
+    // candidates with descriptions are listed one per row
+    let described = candidates.iter().any(|c| c.description().is_some());
+    let display_width = if described {
+        0
+    } else {
+        Columns::display_width(config, cols)
+    };
+    let list = ListRow::all(
+        candidates,
+        display_width,
+        config.print_completions_horizontally(),
     );
-    let num_cols = cols / max_width;
 
-    let mut pause_row = s.out.get_rows() - 1;
-    let num_rows = (candidates.len() + num_cols - 1) / num_cols;
+    let mut pause_row = if config.page_completions() {
+        s.out.get_rows() - 1
+    } else {
+        usize::MAX
+    };
+    let mut menu = None;
     let mut ab = String::new();
-    for row in 0..num_rows {
-        if row == pause_row {
+    for (n, row) in list.iter().enumerate() {
+        if n == pause_row {
             s.out.write_and_flush(b"\n--More--")?;
             let mut cmd = Cmd::Noop;
             while cmd != Cmd::SelfInsert(1, 'y')
@@ -308,6 +788,13 @@ fn page_completions<C: Candidate, H: Helper>(
                 && cmd != Cmd::AcceptLine
                 && cmd != Cmd::Newline
                 && !matches!(cmd, Cmd::AcceptOrInsertLine { .. })
+                && !matches!(
+                    cmd,
+                    Cmd::Complete
+                        | Cmd::CompleteBackward
+                        | Cmd::MenuComplete
+                        | Cmd::MenuCompleteBackward
+                )
             {
                 cmd = s.next_cmd(input_state, rdr, false)?;
             }
@@ -318,22 +805,65 @@ fn page_completions<C: Candidate, H: Helper>(
                 Cmd::AcceptLine | Cmd::Newline | Cmd::AcceptOrInsertLine { .. } => {
                     pause_row += 1;
                 }
+                Cmd::Complete | Cmd::MenuComplete => {
+                    menu = Some(false);
+                    break;
+                }
+                Cmd::CompleteBackward | Cmd::MenuCompleteBackward => {
+                    menu = Some(true);
+                    break;
+                }
                 _ => break,
             }
         }
         s.out.write_and_flush(b"\n")?;
         ab.clear();
-        for col in 0..num_cols {
-            let i = (col * num_rows) + row;
-            if i < candidates.len() {
+        let (first, columns, row) = match *row {
+            ListRow::Header(group) => {
+                match s.highlighter() {
+                    Some(highlighter) => ab.push_str(&highlighter.highlight_candidate_group(group)),
+                    None => ab.push_str(group),
+                }
+                s.out.write_and_flush(ab.as_bytes())?;
+                continue;
+            }
+            ListRow::Candidates {
+                start,
+                columns,
+                row,
+            } => (start, columns, row),
+        };
+        let max_width = columns.width.min(cols);
+        for col in 0..columns.num_cols {
+            if let Some(i) = columns.index(row, col).map(|i| first + i) {
                 let candidate = &candidates[i].display();
                 let width = candidate.width();
+                let matches = candidates[i].matches();
                 if let Some(highlighter) = s.highlighter() {
-                    ab.push_str(&highlighter.highlight_candidate(candidate, CompletionType::List));
+                    if matches.is_empty() {
+                        ab.push_str(
+                            &highlighter.highlight_candidate(candidate, CompletionType::List),
+                        );
+                    } else {
+                        ab.push_str(&highlighter.highlight_candidate_matches(candidate, matches));
+                    }
                 } else {
                     ab.push_str(candidate);
                 }
-                if ((col + 1) * num_rows) + row < candidates.len() {
+                if let Some(description) = candidates[i].description() {
+                    for _ in width..max_width {
+                        ab.push(' ');
+                    }
+                    ab.push_str("-- ");
+                    let mut width = max_width + 3;
+                    for c in description.chars() {
+                        width += c.width().unwrap_or(0);
+                        if width > cols {
+                            break;
+                        }
+                        ab.push(c);
+                    }
+                } else if columns.index(row, col + 1).is_some() {
                     for _ in width..max_width {
                         ab.push(' ');
                     }
@@ -345,16 +875,37 @@ fn page_completions<C: Candidate, H: Helper>(
     s.out.write_and_flush(b"\n")?;
     s.layout.end.row = 0; // dirty way to make clear_old_rows do nothing
     s.layout.cursor.row = 0;
+    if let Some(backward) = menu {
+        return menu_complete(rdr, s, input_state, config, start, candidates, backward);
+    }
     s.refresh_line()?;
     Ok(None)
 }
 
-/// Incremental search
+/// Print `text` below the current line then redisplay the prompt and line
+fn print_above<H: Helper>(s: &mut State<'_, '_, H>, text: &str) -> Result<()> {
+    s.out.move_cursor(s.layout.cursor, s.layout.end)?;
+    s.out.write_and_flush(b"\n")?;
+    s.out.write_and_flush(text.as_bytes())?;
+    s.layout.end.row = 0; // dirty way to make clear_old_rows do nothing
+    s.layout.cursor.row = 0;
+    s.refresh_line()
+}
+
+/// Incremental search (`reverse-search-history` / `forward-search-history`)
+///
+/// Typed characters extend the query and Backspace shortens it. `C-r` / `C-s`
+/// skip to the previous / next match (reusing the last query when the current
+/// one is empty) and `C-g` restores the line as it was before the search.
+/// Any other command ends the search: the line found becomes the current
+/// history entry and the command is applied to it.
 fn reverse_incremental_search<H: Helper>(
     rdr: &mut <Terminal as Term>::Reader,
     s: &mut State<'_, '_, H>,
     input_state: &mut InputState,
-    history: &History,
+    history: &dyn HistoryStore,
+    last_search: &mut String,
+    mut direction: Direction,
 ) -> Result<Option<Cmd>> {
     if history.is_empty() {
         return Ok(None);
@@ -363,101 +914,225 @@ fn reverse_incremental_search<H: Helper>(
     // Save the current edited line (and cursor position) before overwriting it
     let backup = s.line.as_str().to_owned();
     let backup_pos = s.line.pos();
+    if s.ctx.history_index == history.len() {
+        s.backup();
+    }
 
     let mut search_buf = String::new();
-    let mut history_idx = history.len() - 1;
-    let mut direction = Direction::Reverse;
+    let mut history_idx = s.ctx.history_index.min(history.len() - 1);
     let mut success = true;
+    // whether the line has been replaced by a matching entry
+    let mut found = false;
+
+    let find = |query: &str, start: usize, dir: Direction| {
+        let idx = history.search(query, start, dir)?;
+        let entry = history.entry(idx)?;
+        let pos = match dir {
+            Direction::Reverse => entry.rfind(query),
+            Direction::Forward => entry.find(query),
+        }?;
+        Some((idx, entry, pos))
+    };
 
     let mut cmd;
-    // Display the reverse-i-search prompt and process chars
+    // Display the i-search prompt and process chars
     loop {
-        let prompt = if success {
-            format!("(reverse-i-search)`{}': ", search_buf)
-        } else {
-            format!("(failed reverse-i-search)`{}': ", search_buf)
-        };
+        let prompt = format!(
+            "({}{}i-search)`{}': ",
+            if success { "" } else { "failed " },
+            if direction == Direction::Reverse {
+                "reverse-"
+            } else {
+                ""
+            },
+            search_buf
+        );
         s.refresh_prompt_and_line(&prompt)?;
 
         cmd = s.next_cmd(input_state, rdr, true)?;
-        if let Cmd::SelfInsert(_, c) = cmd {
-            search_buf.push(c);
-        } else {
-            match cmd {
-                Cmd::Kill(Movement::BackwardChar(_)) => {
-                    search_buf.pop();
+        let result = match cmd {
+            Cmd::SelfInsert(_, c) => {
+                search_buf.push(c);
+                find(&search_buf, history_idx, direction)
+            }
+            Cmd::Kill(Movement::BackwardChar(_)) => {
+                search_buf.pop();
+                if search_buf.is_empty() {
+                    s.line.update(&backup, backup_pos);
+                    history_idx = s.ctx.history_index.min(history.len() - 1);
+                    success = true;
+                    found = false;
                     continue;
                 }
-                Cmd::ReverseSearchHistory => {
-                    direction = Direction::Reverse;
-                    if history_idx > 0 {
-                        history_idx -= 1;
-                    } else {
-                        success = false;
+                find(&search_buf, history_idx, direction)
+            }
+            Cmd::ReverseSearchHistory | Cmd::ForwardSearchHistory => {
+                direction = if cmd == Cmd::ReverseSearchHistory {
+                    Direction::Reverse
+                } else {
+                    Direction::Forward
+                };
+                if search_buf.is_empty() {
+                    if last_search.is_empty() {
                         continue;
                     }
-                }
-                Cmd::ForwardSearchHistory => {
-                    direction = Direction::Forward;
-                    if history_idx < history.len() - 1 {
-                        history_idx += 1;
+                    search_buf = last_search.clone();
+                    find(&search_buf, history_idx, direction)
+                } else {
+                    // another occurrence in the current entry
+                    let pos = s.line.pos();
+                    let mut occurrences = s.line.match_indices(search_buf.as_str()).map(|(i, _)| i);
+                    let other = if !found {
+                        None
+                    } else if direction == Direction::Reverse {
+                        occurrences.filter(|&i| i < pos).last()
                     } else {
-                        success = false;
+                        occurrences.find(|&i| i > pos)
+                    };
+                    if let Some(other) = other {
+                        s.line.set_pos(other);
+                        success = true;
                         continue;
                     }
+                    // or in the next entries
+                    match direction {
+                        Direction::Reverse if history_idx > 0 => {
+                            find(&search_buf, history_idx - 1, direction)
+                        }
+                        Direction::Forward if history_idx + 1 < history.len() => {
+                            find(&search_buf, history_idx + 1, direction)
+                        }
+                        _ => None,
+                    }
                 }
-                Cmd::Abort => {
-                    // Restore current edited line (before search)
-                    s.line.update(&backup, backup_pos);
-                    s.refresh_line()?;
-                    s.changes.borrow_mut().truncate(mark);
-                    return Ok(None);
-                }
-                Cmd::Move(_) => {
-                    s.refresh_line()?; // restore prompt
-                    break;
+            }
+            Cmd::Abort => {
+                if !search_buf.is_empty() {
+                    *last_search = search_buf;
                 }
-                _ => break,
+                // Restore current edited line (before search)
+                s.line.update(&backup, backup_pos);
+                s.refresh_line()?;
+                s.changes.borrow_mut().truncate(mark);
+                return Ok(None);
             }
-        }
-        success = match history.search(&search_buf, history_idx, direction) {
-            Some(idx) => {
+            Cmd::Move(_) => {
+                s.refresh_line()?; // restore prompt
+                break;
+            }
+            _ => break,
+        };
+        success = match result {
+            Some((idx, entry, pos)) => {
                 history_idx = idx;
-                let entry = history.get(idx).unwrap();
-                let pos = entry.find(&search_buf).unwrap();
-                s.line.update(entry, pos);
+                s.line.update(&entry, pos);
+                found = true;
                 true
             }
-            _ => false,
+            None => false,
         };
     }
+    if !search_buf.is_empty() {
+        *last_search = search_buf;
+    }
+    if found {
+        // the line found can be edited and navigated from like any other entry
+        s.ctx.history_index = history_idx;
+    }
     s.changes.borrow_mut().end();
     Ok(Some(cmd))
 }
 
+/// Vi history search (`/` / `?`)
+///
+/// The pattern is typed in a `/` (or `?`) mini-prompt in place of the line:
+/// Enter searches it (or the last pattern, shared with `C-r`, when empty)
+/// and Backspace on an empty pattern, Escape or any other key cancels the
+/// search.
+fn vi_search<H: Helper>(
+    rdr: &mut <Terminal as Term>::Reader,
+    s: &mut State<'_, '_, H>,
+    input_state: &mut InputState,
+    last_search: &mut String,
+    direction: Direction,
+) -> Result<()> {
+    let prompt = match direction {
+        Direction::Reverse => "/",
+        Direction::Forward => "?",
+    };
+    // the pattern is edited in its own buffer, without undo
+    let line = mem::replace(
+        &mut s.line,
+        LineBuffer::with_capacity(MAX_LINE).can_growth(true),
+    );
+    let accepted = loop {
+        s.refresh_prompt_and_line(prompt)?;
+        match input_state.next_key(rdr, false)? {
+            KeyEvent(KeyCode::Char(c), Modifiers::NONE) => {
+                s.line.insert(c, 1);
+            }
+            KeyEvent::BACKSPACE | KeyEvent(KeyCode::Char('H'), Modifiers::CTRL) => {
+                if !s.line.backspace(1) {
+                    break false;
+                }
+            }
+            KeyEvent::ENTER
+            | KeyEvent(KeyCode::Char('J'), Modifiers::CTRL)
+            | KeyEvent(KeyCode::Char('M'), Modifiers::CTRL) => break true,
+            _ => break false,
+        }
+    };
+    let pattern = mem::replace(&mut s.line, line).as_str().to_owned();
+    s.refresh_line()?; // restore prompt
+    if !accepted {
+        return Ok(());
+    }
+    if !pattern.is_empty() {
+        *last_search = pattern;
+    } else if last_search.is_empty() {
+        return s.out.beep();
+    }
+    s.edit_vi_search(last_search, direction, 1)
+}
+
 /// Handles reading and editing the readline buffer.
 /// It will also handle special inputs in an appropriate fashion
 /// (e.g., C-c will exit readline)
-fn readline_edit<H: Helper>(
+fn readline_edit<H: Helper, S: HistoryStore>(
     prompt: &str,
     initial: Option<(&str, &str)>,
-    editor: &mut Editor<H>,
+    editor: &mut Editor<H, S>,
     original_mode: &tty::Mode,
 ) -> Result<String> {
-    let mut stdout = editor.term.create_writer();
+    let mut stdout = editor.term.create_writer(&editor.config);
 
     editor.reset_kill_ring(); // TODO recreate a new kill ring vs Arc<Mutex<KillRing>>
-    let ctx = Context::new(&editor.history);
+    let ctx = Context::with_config(&editor.history, editor.config.clone());
     let mut s = State::new(&mut stdout, prompt, editor.helper.as_ref(), ctx);
 
     let mut input_state = InputState::new(&editor.config, Arc::clone(&editor.custom_bindings));
+    input_state.kbd_macros = Arc::clone(&editor.kbd_macros);
+    input_state.custom_modes = Arc::clone(&editor.custom_modes);
+    input_state.reset_custom_mode();
 
+    s.line.set_word_boundary(editor.config.word_boundary());
     s.line.set_delete_listener(editor.kill_ring.clone());
     s.line.set_change_listener(s.changes.clone());
 
+    let next_history = editor.next_history.take();
     if let Some((left, right)) = initial {
         s.line
             .update((left.to_owned() + right).as_ref(), left.len());
+    } else if let Some((idx, entry)) = next_history {
+        // the oldest entry may have been evicted when the line was added
+        let idx = [idx, idx.wrapping_sub(1)]
+            .iter()
+            .copied()
+            .find(|&i| editor.history.entry(i).as_deref() == Some(entry.as_str()));
+        if let Some(idx) = idx {
+            s.ctx.history_index = idx;
+            s.line.update(&entry, entry.len());
+        }
     }
 
     let mut rdr = editor.term.create_reader(&editor.config)?;
@@ -470,11 +1145,68 @@ fn readline_edit<H: Helper>(
             }
         }
     }
+    s.update_mode_prompt(input_state.mode_state(), input_state.mode_string());
+    s.update_cursor_shape(input_state.mode_state())?;
     s.refresh_line()?;
 
+    // raw mode enabled again after having left it (`original_mode` is still
+    // the one to restore once the line is read)
+    let mut reenabled: Option<RawModeGuard> = None;
+
     loop {
         let mut cmd = s.next_cmd(&mut input_state, &mut rdr, false)?;
 
+        // the text matched by a substring history search is highlighted until
+        // another command
+        if !matches!(
+            cmd,
+            Cmd::HistorySubstringSearchBackward
+                | Cmd::HistorySubstringSearchForward
+                | Cmd::LineUpOrPreviousHistory(_)
+                | Cmd::LineDownOrNextHistory(_)
+        ) {
+            s.clear_history_match()?;
+        }
+        // the selection is replaced by the text typed and deleted (not killed)
+        // by backspace or delete
+        if s.has_selection() {
+            match cmd {
+                Cmd::SelfInsert(..) | Cmd::Insert(..) | Cmd::Yank(..) => s.delete_selection(),
+                Cmd::ViYankTo(Movement::Region) | Cmd::Kill(Movement::Region) => {
+                    s.copy_selection_to_clipboard()
+                }
+                #[cfg(windows)]
+                Cmd::PasteFromClipboard => s.delete_selection(),
+                Cmd::Kill(Movement::BackwardChar(_)) | Cmd::Kill(Movement::ForwardChar(_)) => {
+                    s.delete_selection();
+                    cmd = Cmd::Noop;
+                }
+                _ => {}
+            }
+        }
+        // the region stays highlighted while the cursor moves (but moving
+        // without shift ends the selection)
+        let keep_region = match cmd {
+            Cmd::Select(_) | Cmd::SetMark | Cmd::ExchangePointAndMark => true,
+            Cmd::Move(_) => !s.has_selection(),
+            _ => false,
+        };
+        if !keep_region {
+            s.deactivate_region()?;
+        }
+        if let Cmd::Select(mvt) = cmd {
+            s.start_selection();
+            cmd = Cmd::Move(mvt);
+        }
+        // `yank-last-arg` only walks back through the history when repeated
+        if !matches!(cmd, Cmd::YankLastArg(_)) {
+            s.yanked_arg = None;
+        }
+        // and `dabbrev-expand` through the expansions
+        if cmd != Cmd::DabbrevExpand {
+            s.dabbrev = None;
+        }
+
         if cmd.should_reset_kill_ring() {
             editor.reset_kill_ring();
         }
@@ -482,7 +1214,30 @@ fn readline_edit<H: Helper>(
         // First trigger commands that need extra input
 
         if cmd == Cmd::Complete && s.helper.is_some() {
-            let next = complete_line(&mut rdr, &mut s, &mut input_state, &editor.config)?;
+            input_state.completing = true;
+            let next = complete_line(&mut rdr, &mut s, &mut input_state, &editor.config);
+            input_state.completing = false;
+            let next = next?;
+            if let Some(next) = next {
+                cmd = next;
+            } else {
+                continue;
+            }
+        }
+
+        // `delete-char-or-list` (and `C-d` in emacs mode) lists the possible
+        // completions at the end of the line
+        let list = cmd == Cmd::PossibleCompletions
+            || ((cmd == Cmd::DeleteCharOrList
+                || (cmd == Cmd::EndOfFile && input_state.is_emacs_mode()))
+                && !s.line.is_empty()
+                && s.line.pos() == s.line.len());
+        if list && s.helper.is_some() {
+            input_state.completing = true;
+            let next =
+                possible_completions_line(&mut rdr, &mut s, &mut input_state, &editor.config);
+            input_state.completing = false;
+            let next = next?;
             if let Some(next) = next {
                 cmd = next;
             } else {
@@ -490,10 +1245,40 @@ fn readline_edit<H: Helper>(
             }
         }
 
-        if cmd == Cmd::ReverseSearchHistory {
-            // Search history backward
+        if cmd == Cmd::InsertCompletions && s.helper.is_some() {
+            insert_completions_line(&mut rdr, &mut s, &editor.config)?;
+            continue;
+        }
+
+        if matches!(cmd, Cmd::MenuComplete | Cmd::MenuCompleteBackward) && s.helper.is_some() {
+            input_state.completing = true;
+            let backward = cmd == Cmd::MenuCompleteBackward;
             let next =
-                reverse_incremental_search(&mut rdr, &mut s, &mut input_state, &editor.history)?;
+                menu_complete_line(&mut rdr, &mut s, &mut input_state, &editor.config, backward);
+            input_state.completing = false;
+            let next = next?;
+            if let Some(next) = next {
+                cmd = next;
+            } else {
+                continue;
+            }
+        }
+
+        if cmd == Cmd::ReverseSearchHistory || cmd == Cmd::ForwardSearchHistory {
+            // Search history incrementally
+            let direction = if cmd == Cmd::ReverseSearchHistory {
+                Direction::Reverse
+            } else {
+                Direction::Forward
+            };
+            let next = reverse_incremental_search(
+                &mut rdr,
+                &mut s,
+                &mut input_state,
+                &editor.history,
+                &mut editor.last_search,
+                direction,
+            )?;
             if let Some(next) = next {
                 cmd = next;
             } else {
@@ -501,21 +1286,112 @@ fn readline_edit<H: Helper>(
             }
         }
 
+        if let Cmd::ViSearch(direction) = cmd {
+            vi_search(
+                &mut rdr,
+                &mut s,
+                &mut input_state,
+                &mut editor.last_search,
+                direction,
+            )?;
+            continue;
+        }
+        if let Cmd::ViSearchAgain(n, direction) = cmd {
+            if editor.last_search.is_empty() {
+                s.out.beep()?;
+            } else {
+                s.edit_vi_search(&editor.last_search, direction, n)?;
+            }
+            continue;
+        }
+
+        if matches!(
+            cmd,
+            Cmd::DumpFunctions | Cmd::DumpMacros | Cmd::DumpVariables
+        ) {
+            let dump = {
+                let bindings = editor.custom_bindings.read().unwrap();
+                match cmd {
+                    Cmd::DumpFunctions => inputrc::dump_functions(&bindings, input_state.keymap()),
+                    Cmd::DumpMacros => inputrc::dump_macros(&bindings, input_state.keymap()),
+                    _ => inputrc::dump_variables(&editor.config),
+                }
+            };
+            print_above(&mut s, &dump)?;
+            continue;
+        }
+
+        if let Cmd::EmacsEditingMode | Cmd::ViEditingMode | Cmd::SetEditMode(_) = cmd {
+            let mode = match cmd {
+                Cmd::ViEditingMode => EditMode::Vi,
+                Cmd::SetEditMode(mode) => mode,
+                _ => EditMode::Emacs,
+            };
+            // the new mode is kept for the following lines
+            editor.config.set_edit_mode(mode);
+            input_state.set_edit_mode(mode);
+            continue;
+        }
+
         #[cfg(unix)]
         if cmd == Cmd::Suspend {
+            s.suspend()?;
+            s.reset_cursor_shape()?;
+            drop(reenabled.take());
             original_mode.disable_raw_mode()?;
             tty::suspend()?;
-            let _ = editor.term.enable_raw_mode()?; // TODO original_mode may have changed
-            s.refresh_line()?;
+            // resumed (SIGCONT): handled here, not by the next read
+            s.out.sigcont();
+            reenabled = Some(RawModeGuard(Some(editor.term.enable_raw_mode()?)));
+            s.update_cursor_shape(input_state.mode_state())?;
+            s.resume()?;
             continue;
         }
 
-        #[cfg(unix)]
-        if cmd == Cmd::QuotedInsert {
-            // Quoted insert
-            use crate::tty::RawReader;
-            let c = rdr.next_char()?;
-            s.edit_insert(c, 1)?;
+        if let Cmd::EditCommandLine { accept } = cmd {
+            // the editor needs the terminal in cooked mode
+            s.reset_cursor_shape()?;
+            drop(reenabled.take());
+            original_mode.disable_raw_mode()?;
+            let edited = external_editor::edit(&external_editor::editor(), s.line.as_str());
+            reenabled = Some(RawModeGuard(Some(editor.term.enable_raw_mode()?)));
+            s.update_cursor_shape(input_state.mode_state())?;
+            let edited = edited?;
+            let failed = edited.is_none();
+            s.edit_externally_edited(edited)?;
+            if failed || !accept {
+                continue;
+            }
+            cmd = Cmd::AcceptLine;
+        }
+
+        if let Cmd::Custom(ref custom) = cmd {
+            s.changes.borrow_mut().begin();
+            let completer = s.helper.map(|h| h as &dyn completion::AnyCompleter);
+            let mut prompter = Prompter::new(&mut s.line, completer, &s.ctx);
+            let result = custom.call(&mut prompter);
+            let (messages, accept) = prompter.into_parts();
+            s.changes.borrow_mut().end();
+            result?;
+            for msg in messages {
+                print_above(&mut s, &msg)?;
+            }
+            if !accept {
+                s.refresh_line()?;
+                continue;
+            }
+            cmd = Cmd::AcceptLine;
+        }
+
+        if let Cmd::MoveToScreenPosition { row, column } = cmd {
+            // the edited line is located from the actual cursor position
+            if let Some(cursor) = s.out.cursor_position(&mut rdr)? {
+                let target = Position {
+                    col: usize::from(column),
+                    row: usize::from(row),
+                };
+                s.edit_move_to_screen_position(target, cursor)?;
+            }
             continue;
         }
 
@@ -526,6 +1402,20 @@ fn readline_edit<H: Helper>(
             s.edit_yank(&input_state, &clipboard[..], Anchor::Before, 1)?;
         }
 
+        // an abbreviation before the cursor is expanded when the line is
+        // accepted
+        if matches!(
+            cmd,
+            Cmd::AcceptLine | Cmd::AcceptOrInsertLine { .. } | Cmd::OperateAndGetNext
+        ) {
+            s.edit_expand_abbreviation(&editor.abbreviations, s.line.pos())?;
+        }
+        // or when a space is typed after it (see below)
+        let spaces = match cmd {
+            Cmd::SelfInsert(n, ' ') => Some(n),
+            _ => None,
+        };
+
         // Tiny test quirk
         #[cfg(test)]
         if matches!(
@@ -535,10 +1425,40 @@ fn readline_edit<H: Helper>(
             editor.term.cursor = s.layout.cursor.col;
         }
 
+        let get_next = cmd == Cmd::OperateAndGetNext;
+        // in vi command mode, the changes of a command are undone as a whole
+        let undo_group = !input_state.is_emacs_mode()
+            && input_state.input_mode == InputMode::Command
+            && !matches!(cmd, Cmd::Undo(_) | Cmd::Redo(_) | Cmd::RevertLine);
+        if undo_group {
+            s.changes.borrow_mut().begin();
+        }
         // Execute things can be done solely on a state object
-        match command::execute(cmd, &mut s, &input_state, &editor.kill_ring, &editor.config)? {
+        let status = command::execute(cmd, &mut s, &input_state, &editor.kill_ring, &editor.config);
+        if undo_group {
+            s.changes.borrow_mut().end();
+        }
+        if let (Some(n), Ok(_)) = (spaces, &status) {
+            // expanded after the space insertion so that undo restores the
+            // abbreviation followed by the space
+            let end = s.line.pos().saturating_sub(n);
+            s.edit_expand_abbreviation(&editor.abbreviations, end)?;
+        }
+        match status? {
             command::Status::Proceed => continue,
-            command::Status::Submit => break,
+            command::Status::Submit => {
+                if get_next && s.ctx.history_index < editor.history.len() {
+                    // the accepted line is the next entry once added to
+                    // the history
+                    let idx = s.ctx.history_index + 1;
+                    let entry = match editor.history.entry(idx) {
+                        Some(entry) => entry.into_owned(),
+                        None => s.line.as_str().to_owned(),
+                    };
+                    editor.next_history = Some((idx, entry));
+                }
+                break;
+            }
         }
     }
 
@@ -562,35 +1482,157 @@ impl Drop for Guard<'_> {
     }
 }
 
+/// Raw mode of the terminal (see [`Editor::raw_mode`]).
+///
+/// The original mode is restored when it is dropped.
+#[must_use = "the original mode is restored when the guard is dropped"]
+pub struct RawModeGuard(Option<tty::Mode>);
+
+impl RawModeGuard {
+    /// Restore the original mode now, reporting any error.
+    pub fn restore(mut self) -> Result<()> {
+        match self.0.take() {
+            Some(mode) => mode.disable_raw_mode(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        if let Some(mode) = self.0.take() {
+            let _ = mode.disable_raw_mode();
+        }
+    }
+}
+
+/// Keys pressed by the user (see [`Editor::key_events`]).
+///
+/// The terminal is in raw mode until it is dropped. Iteration stops at the
+/// end of input.
+pub struct KeyEvents<'e> {
+    reader: <Terminal as Term>::Reader,
+    mode: tty::Mode,
+    editor: PhantomData<&'e mut ()>,
+}
+
+impl KeyEvents<'_> {
+    /// Blocking read of the next key pressed
+    pub fn next_key(&mut self) -> Result<KeyEvent> {
+        self.reader.next_key(false)
+    }
+
+    /// Wait at most `timeout_ms` (indefinitely if negative) for a key and
+    /// tell if there is one to read.
+    pub fn poll(&mut self, timeout_ms: i32) -> Result<bool> {
+        self.reader.wait_for_input(timeout_ms)
+    }
+}
+
+impl Iterator for KeyEvents<'_> {
+    type Item = Result<KeyEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.next_key() {
+            Err(error::ReadlineError::Eof) => None,
+            key => Some(key),
+        }
+    }
+}
+
+#[allow(unused_must_use)]
+impl Drop for KeyEvents<'_> {
+    fn drop(&mut self) {
+        let mode = &self.mode;
+        mode.disable_raw_mode();
+    }
+}
+
 /// Readline method that will enable RAW mode, call the `readline_edit()`
 /// method and disable raw mode
-fn readline_raw<H: Helper>(
+fn readline_raw<H: Helper, S: HistoryStore>(
     prompt: &str,
     initial: Option<(&str, &str)>,
-    editor: &mut Editor<H>,
+    editor: &mut Editor<H, S>,
 ) -> Result<String> {
     let original_mode = editor.term.enable_raw_mode()?;
     let guard = Guard(&original_mode);
     let user_input = readline_edit(prompt, initial, editor, &original_mode);
+    if editor.config.cursor_shape() {
+        editor
+            .term
+            .create_writer(&editor.config)
+            .set_cursor_shape(tty::CursorShape::Default)?;
+    }
+    let user_input = accept_line(editor, user_input);
+    drop(guard); // disable_raw_mode(original_mode)?;
+    editor
+        .term
+        .create_writer(&editor.config)
+        .write_and_flush(b"\n")?;
+    user_input
+}
+
+/// History expansion and addition of the line read
+fn accept_line<H: Helper, S: HistoryStore>(
+    editor: &mut Editor<H, S>,
+    mut user_input: Result<String>,
+) -> Result<String> {
+    if editor.config.history_expansion() {
+        if let Ok(ref line) = user_input {
+            match expansion::expand(&editor.history, line, editor.config.hist_chars()) {
+                Ok(Some(expanded)) => user_input = Ok(expanded),
+                Ok(None) => {}
+                Err(err) => user_input = Err(err.into()),
+            }
+        }
+    }
     if editor.config.auto_add_history() {
         if let Ok(ref line) = user_input {
             editor.add_history_entry(line.as_str());
         }
     }
-    drop(guard); // disable_raw_mode(original_mode)?;
-    match editor.config.output_stream() {
-        OutputStreamType::Stdout => writeln!(io::stdout())?,
-        OutputStreamType::Stderr => writeln!(io::stderr())?,
-    };
     user_input
 }
 
-fn readline_direct() -> Result<String> {
+/// Line-buffered fallback for dumb terminals and when stdin is not a tty:
+/// the line is edited by the terminal driver, if any, and no escape sequence
+/// is written.
+fn readline_direct<H: Helper, S: HistoryStore>(
+    prompt: Option<&str>,
+    editor: &mut Editor<H, S>,
+) -> Result<String> {
+    if let Some(prompt) = prompt {
+        match editor.config.output_stream() {
+            OutputStreamType::Stdout => {
+                let mut stdout = io::stdout();
+                stdout.write_all(prompt.as_bytes())?;
+                stdout.flush()?;
+            }
+            OutputStreamType::Stderr => {
+                let mut stderr = io::stderr();
+                stderr.write_all(prompt.as_bytes())?;
+                stderr.flush()?;
+            }
+        }
+    }
     let mut line = String::new();
-    if io::stdin().read_line(&mut line)? > 0 {
-        Ok(line)
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Err(error::ReadlineError::Eof);
+    }
+    Ok(line)
+}
+
+/// Remove and return the trailing `\n` or `\r\n` of a line read from stdin
+fn strip_line_ending(line: &mut String) -> &'static str {
+    if line.ends_with("\r\n") {
+        line.truncate(line.len() - 2);
+        "\r\n"
+    } else if line.ends_with('\n') {
+        line.pop();
+        "\n"
     } else {
-        Err(error::ReadlineError::Eof)
+        ""
     }
 }
 
@@ -610,38 +1652,67 @@ impl<'h, H: ?Sized + Helper> Helper for &'h H {}
 
 /// Completion/suggestion context
 pub struct Context<'h> {
-    history: &'h History,
+    history: &'h dyn HistoryStore,
     history_index: usize,
+    config: Config,
 }
 
 impl<'h> Context<'h> {
     /// Constructor. Visible for testing.
-    pub fn new(history: &'h History) -> Self {
+    pub fn new(history: &'h dyn HistoryStore) -> Self {
+        Self::with_config(history, Config::default())
+    }
+
+    pub(crate) fn with_config(history: &'h dyn HistoryStore, config: Config) -> Self {
         Context {
             history,
             history_index: history.len(),
+            config,
         }
     }
 
     /// Return an immutable reference to the history object.
-    pub fn history(&self) -> &History {
-        &self.history
+    pub fn history(&self) -> &dyn HistoryStore {
+        self.history
     }
 
     /// The history index we are currently editing
     pub fn history_index(&self) -> usize {
         self.history_index
     }
+
+    /// The editor configuration (e.g. completion preferences)
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
 }
 
 /// Line editor
-pub struct Editor<H: Helper> {
+///
+/// The history is stored in a [`History`] unless another [`HistoryStore`] is
+/// given to [`Editor::with_history`].
+pub struct Editor<H: Helper, S: HistoryStore = History> {
     term: Terminal,
-    history: History,
+    history: S,
     helper: Option<H>,
     kill_ring: Arc<Mutex<KillRing>>,
     config: Config,
-    custom_bindings: Arc<RwLock<Trie<Event, EventHandler>>>,
+    custom_bindings: Arc<RwLock<Bindings>>,
+    functions: FunctionRegistry,
+    kbd_macros: Arc<Mutex<MacroRecorder>>,
+    custom_modes: Arc<Mutex<CustomModes>>,
+    application_name: Option<String>,
+    inputrc: inputrc::Loaded,
+    /// `operate-and-get-next`: index and content of the history entry to
+    /// edit on the next `readline`
+    next_history: Option<(usize, String)>,
+    /// query of the last incremental search, reused by `C-r` / `C-s` with an
+    /// empty query
+    last_search: String,
+    /// Lines rejected by one of these are not added to the history
+    history_filters: Vec<Box<dyn HistoryFilter + Send + Sync>>,
+    /// Expanded when a space is typed after them or when the line is accepted
+    abbreviations: Abbreviations,
 }
 
 #[allow(clippy::new_without_default)]
@@ -653,6 +1724,23 @@ impl<H: Helper> Editor<H> {
 
     /// Create an editor with a specific configuration.
     pub fn with_config(config: Config) -> Self {
+        let history = History::with_config(config.clone());
+        Self::with_history(config, history)
+    }
+}
+
+impl<H: Helper, S: HistoryStore> Editor<H, S> {
+    /// Create an editor with a specific configuration and history storage.
+    ///
+    /// ```
+    /// use rustyline::history::History;
+    /// use rustyline::{Config, Editor};
+    ///
+    /// let rl = Editor::<(), History>::with_history(Config::default(), History::new());
+    /// assert!(rl.history().is_empty());
+    /// ```
+    pub fn with_history(config: Config, mut history: S) -> Self {
+        history.configure(&config);
         let term = Terminal::new(
             config.color_mode(),
             config.output_stream(),
@@ -662,11 +1750,20 @@ impl<H: Helper> Editor<H> {
         );
         Self {
             term,
-            history: History::with_config(config),
+            history,
             helper: None,
             kill_ring: Arc::new(Mutex::new(KillRing::new(60))),
             config,
-            custom_bindings: Arc::new(RwLock::new(Trie::new())),
+            custom_bindings: Arc::new(RwLock::new(Bindings::default())),
+            functions: FunctionRegistry::new(),
+            kbd_macros: Arc::default(),
+            custom_modes: Arc::default(),
+            application_name: None,
+            inputrc: inputrc::Loaded::default(),
+            next_history: None,
+            last_search: String::new(),
+            history_filters: Vec::new(),
+            abbreviations: Abbreviations::default(),
         }
     }
 
@@ -691,56 +1788,307 @@ impl<H: Helper> Editor<H> {
         self.readline_with(prompt, Some(initial))
     }
 
+    /// Read one key pressed by the user, without editing a line.
+    ///
+    /// See [`key_events`](Editor::key_events).
+    pub fn read_key_event(&mut self) -> Result<KeyEvent> {
+        self.key_events()?.next_key()
+    }
+
+    /// Switch the terminal to raw mode and return the keys pressed by the
+    /// user, decoded like `readline` does, until the returned value is
+    /// dropped.
+    ///
+    /// Useful to build full-screen modes or pagers on top of the same
+    /// terminal handling. Fails if `stdin` is not a supported terminal.
+    /// ```no_run
+    /// use rustyline::{Editor, KeyCode, KeyEvent, Modifiers};
+    ///
+    /// let mut rl = Editor::<()>::new();
+    /// for key in rl.key_events()? {
+    ///     match key? {
+    ///         KeyEvent(KeyCode::Esc, Modifiers::NONE) => break,
+    ///         key => println!("{:?}\r", key),
+    ///     }
+    /// }
+    /// # Ok::<(), rustyline::error::ReadlineError>(())
+    /// ```
+    pub fn key_events(&mut self) -> Result<KeyEvents<'_>> {
+        if self.term.is_unsupported() || !self.term.is_stdin_tty() {
+            return Err(error::ReadlineError::Io(io::Error::other(
+                "stdin is not a supported terminal",
+            )));
+        }
+        #[allow(clippy::let_unit_value)] // `tty::Mode` is `()` in tests
+        let mode = self.term.enable_raw_mode()?;
+        let reader = self.term.create_reader(&self.config)?;
+        Ok(KeyEvents {
+            reader,
+            mode,
+            editor: PhantomData,
+        })
+    }
+
+    /// Switch the terminal to raw mode until the returned guard is dropped.
+    ///
+    /// Keys can then be read from stdin one at a time, without echo. Fails if
+    /// `stdin` is not a supported terminal. Like while `readline` is running,
+    /// the mode of the terminal is also restored if the process panics or
+    /// exits before the guard is dropped.
+    pub fn raw_mode(&mut self) -> Result<RawModeGuard> {
+        if self.term.is_unsupported() || !self.term.is_stdin_tty() {
+            return Err(error::ReadlineError::Io(io::Error::other(
+                "stdin is not a supported terminal",
+            )));
+        }
+        #[allow(clippy::let_unit_value)] // `tty::Mode` is `()` in tests
+        let mode = self.term.enable_raw_mode()?;
+        Ok(RawModeGuard(Some(mode)))
+    }
+
     fn readline_with(&mut self, prompt: &str, initial: Option<(&str, &str)>) -> Result<String> {
+        if self.inputrc.auto_reload && self.inputrc.is_modified() {
+            if let Err(err) = self.reload_inputrc() {
+                debug!(target: "rustyline", "cannot reload inputrc: {}", err);
+            }
+        }
         if self.term.is_unsupported() {
             debug!(target: "rustyline", "unsupported terminal");
-            // Write prompt and flush it to stdout
-            let mut stdout = io::stdout();
-            stdout.write_all(prompt.as_bytes())?;
-            stdout.flush()?;
-
-            readline_direct()
+            // interactive: the line is added to the history like in raw mode,
+            // without its line ending, which is still returned
+            let mut ending = "";
+            let user_input = readline_direct(Some(prompt), self).map(|mut line| {
+                ending = strip_line_ending(&mut line);
+                line
+            });
+            accept_line(self, user_input).map(|line| line + ending)
         } else if self.term.is_stdin_tty() {
             readline_raw(prompt, initial, self)
         } else {
             debug!(target: "rustyline", "stdin is not a tty");
-            // Not a tty: read from file / pipe.
-            readline_direct()
+            // Not a tty: read from file / pipe, prompting only a user who
+            // sees the output.
+            let prompt = if self.term.is_output_tty() {
+                Some(prompt)
+            } else {
+                None
+            };
+            readline_direct(prompt, self)
         }
     }
 
     /// Load the history from the specified file.
     pub fn load_history<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
-        self.history.load(path)
+        self.history.load(path.as_ref())
     }
 
     /// Save the history in the specified file.
     pub fn save_history<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
-        self.history.save(path)
+        self.history.save(path.as_ref())
     }
 
     /// Append new entries in the specified file.
     pub fn append_history<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
-        self.history.append(path)
+        self.history.append(path.as_ref())
+    }
+
+    /// Append new entries in the specified file and load the ones appended
+    /// by other processes (see [`History::sync`]).
+    pub fn sync_history<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
+        self.history.sync(path.as_ref())
+    }
+
+    /// Load and apply a readline init file (inputrc).
+    ///
+    /// Unsupported or malformed lines are ignored.
+    pub fn load_inputrc<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
+        inputrc::load(self, path.as_ref(), &mut inputrc::log_error)
+    }
+
+    /// Load and apply a readline init file (inputrc), reporting unsupported
+    /// or malformed lines to `on_error`.
+    pub fn load_inputrc_with<P, F>(&mut self, path: &P, mut on_error: F) -> Result<()>
+    where
+        P: AsRef<Path> + ?Sized,
+        F: FnMut(&inputrc::InputrcError),
+    {
+        inputrc::load(self, path.as_ref(), &mut on_error)
+    }
+
+    /// Load and apply the first existing inputrc file of `source`.
+    ///
+    /// Returns the path of the loaded file, if any.
+    pub fn load_inputrc_from(
+        &mut self,
+        source: &inputrc::InputrcSource,
+    ) -> Result<Option<PathBuf>> {
+        match source.resolve() {
+            Some(path) => {
+                self.load_inputrc(&path)?;
+                Ok(Some(path))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Reload the last file loaded with [`Editor::load_inputrc`]: the key
+    /// bindings it made are removed before it is applied again.
+    pub fn reload_inputrc(&mut self) -> Result<()> {
+        inputrc::reload(self, &mut inputrc::log_error)
+    }
+
+    /// Reload the last loaded inputrc file, reporting unsupported or
+    /// malformed lines to `on_error`.
+    pub fn reload_inputrc_with<F>(&mut self, mut on_error: F) -> Result<()>
+    where
+        F: FnMut(&inputrc::InputrcError),
+    {
+        inputrc::reload(self, &mut on_error)
+    }
+
+    /// Reload the last loaded inputrc file automatically when it, or one of
+    /// the files it includes, is modified.
+    ///
+    /// The modification times are checked each time a line is read. By
+    /// default, the file is not reloaded.
+    pub fn set_inputrc_auto_reload(&mut self, yes: bool) {
+        self.inputrc.auto_reload = yes;
+    }
+
+    /// Apply directives returned by [`inputrc::parse`].
+    ///
+    /// Relative `$include` paths are resolved from the current directory.
+    pub fn apply_inputrc(&mut self, directives: &[inputrc::Directive]) {
+        inputrc::apply(self, directives, &mut inputrc::log_error)
+    }
+
+    /// Set a readline variable, like `set name value` in an inputrc file.
+    ///
+    /// ```
+    /// let mut rl = rustyline::Editor::<()>::new();
+    /// rl.set_variable("editing-mode", "vi").unwrap();
+    /// assert_eq!(Some("vi".to_owned()), rl.get_variable("editing-mode"));
+    /// ```
+    pub fn set_variable(
+        &mut self,
+        name: &str,
+        value: &str,
+    ) -> result::Result<(), inputrc::VariableError> {
+        match inputrc::Variable::from_name(name) {
+            Some(variable) => variable.set(self, value),
+            None => Err(inputrc::VariableError::UnknownName),
+        }
+    }
+
+    /// Current value of a readline variable, formatted like readline does.
+    pub fn get_variable(&self, name: &str) -> Option<String> {
+        inputrc::Variable::from_name(name).map(|variable| variable.get(&self.config))
     }
 
-    /// Add a new entry in the history.
-    pub fn add_history_entry<S: AsRef<str> + Into<String>>(&mut self, line: S) -> bool {
+    /// All known readline variables with their current values, sorted by
+    /// name.
+    pub fn variables(&self) -> impl Iterator<Item = (&'static str, String)> + '_ {
+        inputrc::Variable::ALL
+            .iter()
+            .map(move |variable| (variable.name(), variable.get(&self.config)))
+    }
+
+    /// Export readline variables and custom key bindings.
+    ///
+    /// Only bindings to readline functions and macros are exported.
+    pub fn settings(&self) -> inputrc::Settings {
+        inputrc::settings::export(self)
+    }
+
+    /// Import readline variables and custom key bindings exported by
+    /// [`Editor::settings`].
+    ///
+    /// Invalid entries are ignored.
+    pub fn apply_settings(&mut self, settings: &inputrc::Settings) {
+        inputrc::settings::import(self, settings)
+    }
+
+    /// Apply directives returned by [`inputrc::parse`], reporting unsupported
+    /// ones to `on_error`.
+    pub fn apply_inputrc_with<F>(&mut self, directives: &[inputrc::Directive], mut on_error: F)
+    where
+        F: FnMut(&inputrc::InputrcError),
+    {
+        inputrc::apply(self, directives, &mut on_error)
+    }
+
+    /// Set the application name tested by inputrc `$if` directives
+    /// (like readline's `rl_readline_name`).
+    pub fn set_application_name<N: Into<String>>(&mut self, name: N) {
+        self.application_name = Some(name.into());
+    }
+
+    /// Add a new entry in the history, unless it is rejected by one of the
+    /// history filters.
+    pub fn add_history_entry<L: AsRef<str> + Into<String>>(&mut self, line: L) -> bool {
+        let line = line.as_ref();
+        if !self
+            .history_filters
+            .iter()
+            .all(|filter| filter.accept(line))
+        {
+            return false;
+        }
         self.history.add(line)
     }
 
+    /// Register a filter deciding whether lines are added to the history (by
+    /// [`add_history_entry`](Self::add_history_entry), including with
+    /// `auto_add_history`): lines must be accepted by all the filters.
+    pub fn add_history_filter<F: HistoryFilter + Send + Sync + 'static>(&mut self, filter: F) {
+        self.history_filters.push(Box::new(filter));
+    }
+
+    /// Remove all the history filters.
+    pub fn clear_history_filters(&mut self) {
+        self.history_filters.clear();
+    }
+
+    /// Register an `abbreviation` replaced by `expansion` when a space is
+    /// typed after it or when the line is accepted with the cursor after it
+    /// (one undo restores the abbreviation).
+    ///
+    /// Returns the previous expansion of `abbreviation`, if any.
+    pub fn add_abbreviation(&mut self, abbreviation: &str, expansion: &str) -> Option<String> {
+        self.abbreviations.insert(abbreviation, expansion)
+    }
+
+    /// Remove an abbreviation, returning its expansion.
+    pub fn remove_abbreviation(&mut self, abbreviation: &str) -> Option<String> {
+        self.abbreviations.remove(abbreviation)
+    }
+
+    /// Register an `expander` supplying the expansion of the words which are
+    /// not registered abbreviations (the expanders are tried in order).
+    pub fn add_abbreviation_expander<A: Abbreviator + Send + Sync + 'static>(
+        &mut self,
+        expander: A,
+    ) {
+        self.abbreviations.add_expander(Box::new(expander));
+    }
+
+    /// Remove all the abbreviation expanders.
+    pub fn clear_abbreviation_expanders(&mut self) {
+        self.abbreviations.clear_expanders();
+    }
+
     /// Clear history.
     pub fn clear_history(&mut self) {
         self.history.clear()
     }
 
     /// Return a mutable reference to the history object.
-    pub fn history_mut(&mut self) -> &mut History {
+    pub fn history_mut(&mut self) -> &mut S {
         &mut self.history
     }
 
     /// Return an immutable reference to the history object.
-    pub fn history(&self) -> &History {
+    pub fn history(&self) -> &S {
         &self.history
     }
 
@@ -761,22 +2109,229 @@ impl<H: Helper> Editor<H> {
     }
 
     /// Bind a sequence to a command.
+    ///
+    /// The binding is active whatever the keymap is.
+    /// It is made in the [`Layer::Application`] layer: a binding read from an
+    /// inputrc file for the same sequence is kept and becomes active again
+    /// when this one is removed.
     pub fn bind_sequence<E: Into<Event>, R: Into<EventHandler>>(
         &mut self,
         key_seq: E,
         handler: R,
+    ) -> Option<EventHandler> {
+        self.bind(Layer::Application, None, key_seq.into(), handler.into())
+    }
+
+    /// Remove a binding for the given sequence.
+    pub fn unbind_sequence<E: Into<Event>>(&mut self, key_seq: E) -> Option<EventHandler> {
+        self.unbind(Layer::Application, None, key_seq.into())
+    }
+
+    /// Bind a sequence to a command in the specified `keymap` only.
+    ///
+    /// Such a binding takes precedence over the ones bound with
+    /// [`Editor::bind_sequence`].
+    pub fn bind_sequence_in<E: Into<Event>, R: Into<EventHandler>>(
+        &mut self,
+        keymap: Keymap,
+        key_seq: E,
+        handler: R,
+    ) -> Option<EventHandler> {
+        self.bind(
+            Layer::Application,
+            Some(keymap),
+            key_seq.into(),
+            handler.into(),
+        )
+    }
+
+    /// Remove a binding for the given sequence from the specified `keymap`.
+    pub fn unbind_sequence_in<E: Into<Event>>(
+        &mut self,
+        keymap: Keymap,
+        key_seq: E,
+    ) -> Option<EventHandler> {
+        self.unbind(Layer::Application, Some(keymap), key_seq.into())
+    }
+
+    /// Bind a sequence to a command in the specified `layer`, for `keymap` or
+    /// globally (`None`).
+    ///
+    /// The [`Layer::Defaults`] layer is where an application can register
+    /// its own defaults, which users can override from their inputrc file.
+    pub fn bind_sequence_in_layer<E: Into<Event>, R: Into<EventHandler>>(
+        &mut self,
+        layer: Layer,
+        keymap: Option<Keymap>,
+        key_seq: E,
+        handler: R,
+    ) -> Option<EventHandler> {
+        self.bind(layer, keymap, key_seq.into(), handler.into())
+    }
+
+    /// Remove a binding for the given sequence from the specified `layer`
+    /// only.
+    pub fn unbind_sequence_in_layer<E: Into<Event>>(
+        &mut self,
+        layer: Layer,
+        keymap: Option<Keymap>,
+        key_seq: E,
+    ) -> Option<EventHandler> {
+        self.unbind(layer, keymap, key_seq.into())
+    }
+
+    /// Remove all the custom bindings of `layer` (the built-in bindings of
+    /// [`Layer::Defaults`] are kept).
+    pub fn clear_layer(&mut self, layer: Layer) {
+        if let Ok(mut bindings) = self.custom_bindings.write() {
+            bindings.clear(layer);
+        }
+    }
+
+    /// Return the enabled layers, highest precedence first.
+    ///
+    /// By default: application, user then defaults.
+    pub fn layer_order(&self) -> Vec<Layer> {
+        self.custom_bindings.read().unwrap().order().to_vec()
+    }
+
+    /// Set the precedence of the binding layers, highest first. Layers which
+    /// are not listed are disabled (but their bindings are kept).
+    ///
+    /// Custom bindings are always looked up before the built-in bindings,
+    /// which are active only if [`Layer::Defaults`] is enabled: otherwise,
+    /// only characters are inserted.
+    pub fn set_layer_order(&mut self, layers: &[Layer]) {
+        if let Ok(mut bindings) = self.custom_bindings.write() {
+            bindings.set_order(layers);
+        }
+    }
+
+    /// Return the custom bindings active in `keymap`: the keymap specific
+    /// ones and the global ones which are not overridden.
+    ///
+    /// [`Cmd::DumpFunctions`] prints them in inputrc format (like `bind -p`).
+    pub fn bindings(&self, keymap: Keymap) -> impl Iterator<Item = (Event, Binding)> {
+        let bindings = self.custom_bindings.read().unwrap();
+        let bindings: Vec<_> = bindings
+            .iter(keymap)
+            .into_iter()
+            .map(|(evt, handler)| (evt.clone(), Binding::from(handler)))
+            .collect();
+        bindings.into_iter()
+    }
+
+    /// Analyze the custom bindings active in `keymap` and report the ones
+    /// which shadow others and the bound sequences which are also the prefix
+    /// of longer bound sequences (like `C-x` and `C-x C-f`).
+    ///
+    /// Built-in emacs / vi bindings are not considered.
+    ///
+    /// ```
+    /// use rustyline::{BindingConflict, Cmd, Editor, Event, KeyEvent, Keymap};
+    ///
+    /// let mut rl = Editor::<()>::new();
+    /// rl.bind_sequence(KeyEvent::ctrl('X'), Cmd::Noop);
+    /// rl.bind_sequence(
+    ///     Event::KeySeq(vec![KeyEvent::ctrl('X'), KeyEvent::ctrl('F')].into()),
+    ///     Cmd::Noop,
+    /// );
+    /// for conflict in rl.binding_conflicts(Keymap::Emacs) {
+    ///     if let BindingConflict::Prefix { prefix, sequences } = conflict {
+    ///         println!("{:?} is a prefix of {:?}", prefix, sequences);
+    ///     }
+    /// }
+    /// ```
+    pub fn binding_conflicts(&self, keymap: Keymap) -> Vec<BindingConflict> {
+        self.custom_bindings.read().unwrap().conflicts(keymap)
+    }
+
+    /// Return the custom binding of the given sequence in `keymap` (or
+    /// globally).
+    pub fn lookup<E: Into<Event>>(&self, keymap: Keymap, key_seq: E) -> Option<Binding> {
+        let bindings = self.custom_bindings.read().unwrap();
+        bindings
+            .get(keymap, &Event::normalize(key_seq.into()))
+            .map(Binding::from)
+    }
+
+    /// Return an immutable reference to the application specific functions.
+    pub fn functions(&self) -> &FunctionRegistry {
+        &self.functions
+    }
+
+    /// Return a mutable reference to the application specific functions,
+    /// which can be bound from inputrc files.
+    pub fn functions_mut(&mut self) -> &mut FunctionRegistry {
+        &mut self.functions
+    }
+
+    /// Register `mode` under `name`, replacing any previous mode with the
+    /// same name: it is used while the editing mode is
+    /// `EditMode::Custom(name)`.
+    pub fn register_mode<M: CustomMode + 'static>(&mut self, name: &'static str, mode: M) {
+        self.custom_modes
+            .lock()
+            .unwrap()
+            .insert(name, Box::new(mode));
+    }
+
+    /// Remove the mode registered under `name`.
+    ///
+    /// Returns `true` if there was such a mode.
+    pub fn unregister_mode(&mut self, name: &str) -> bool {
+        self.custom_modes.lock().unwrap().remove(name).is_some()
+    }
+
+    /// Return the last recorded keyboard macro (`C-x (` ... `C-x )`), e.g.
+    /// to persist it.
+    pub fn last_macro(&self) -> Option<Macro> {
+        self.kbd_macros.lock().unwrap().last().cloned()
+    }
+
+    /// Set the keyboard macro replayed by `C-x e`.
+    pub fn set_last_macro(&mut self, kbd_macro: Option<Macro>) {
+        self.kbd_macros.lock().unwrap().set_last(kbd_macro);
+    }
+
+    /// Return the content of the vi register `name`: `a` to `z`, `"` for the
+    /// last killed text or `.` for the last inserted text.
+    pub fn register(&self, name: char) -> Option<String> {
+        self.kill_ring
+            .lock()
+            .unwrap()
+            .register(name)
+            .map(str::to_owned)
+    }
+
+    /// Set the vi register `name` (`a` to `z`, or `A` to `Z` to append to
+    /// it); the other registers are read-only.
+    pub fn set_register(&mut self, name: char, text: &str) {
+        self.kill_ring.lock().unwrap().set_register(name, text);
+    }
+
+    fn bind(
+        &mut self,
+        layer: Layer,
+        keymap: Option<Keymap>,
+        key_seq: Event,
+        handler: EventHandler,
     ) -> Option<EventHandler> {
         if let Ok(mut bindings) = self.custom_bindings.write() {
-            bindings.insert(Event::normalize(key_seq.into()), handler.into())
+            bindings.insert(layer, keymap, Event::normalize(key_seq), handler)
         } else {
             None
         }
     }
 
-    /// Remove a binding for the given sequence.
-    pub fn unbind_sequence<E: Into<Event>>(&mut self, key_seq: E) -> Option<EventHandler> {
+    fn unbind(
+        &mut self,
+        layer: Layer,
+        keymap: Option<Keymap>,
+        key_seq: Event,
+    ) -> Option<EventHandler> {
         if let Ok(mut bindings) = self.custom_bindings.write() {
-            bindings.remove(&Event::normalize(key_seq.into()))
+            bindings.remove(layer, keymap, &Event::normalize(key_seq))
         } else {
             None
         }
@@ -809,11 +2364,18 @@ impl<H: Helper> Editor<H> {
         kill_ring.reset();
     }
 
+    /// Read keys from and render the line to `terminal` instead of the
+    /// terminal of the platform (see [`terminal::Terminal`]).
+    #[cfg(not(test))]
+    pub fn set_terminal<T: terminal::Terminal + 'static>(&mut self, terminal: T) {
+        self.term = Terminal::Custom(tty::CustomTerminal::new(terminal, &self.config));
+    }
+
     /// If output stream is a tty, this function returns its width and height as
     /// a number of characters.
     pub fn dimensions(&mut self) -> Option<(usize, usize)> {
         if self.term.is_output_tty() {
-            let out = self.term.create_writer();
+            let out = self.term.create_writer(&self.config);
             Some((out.get_columns(), out.get_rows()))
         } else {
             None
@@ -821,33 +2383,68 @@ impl<H: Helper> Editor<H> {
     }
 }
 
-impl<H: Helper> config::Configurer for Editor<H> {
+impl<H: Helper, S: HistoryStore> config::Configurer for Editor<H, S> {
     fn config_mut(&mut self) -> &mut Config {
         &mut self.config
     }
 
     fn set_max_history_size(&mut self, max_size: usize) {
         self.config_mut().set_max_history_size(max_size);
-        self.history.set_max_len(max_size);
+        self.history.configure(&self.config);
+    }
+
+    fn set_history_max_bytes(&mut self, max_bytes: usize) {
+        self.config_mut().set_history_max_bytes(max_bytes);
+        self.history.configure(&self.config);
+    }
+
+    fn set_history_eviction(&mut self, eviction: HistoryEviction) {
+        self.config_mut().set_history_eviction(eviction);
+        self.history.configure(&self.config);
     }
 
     fn set_history_ignore_dups(&mut self, yes: bool) {
         self.config_mut().set_history_ignore_dups(yes);
-        self.history.ignore_dups = yes;
+        self.history.configure(&self.config);
+    }
+
+    fn set_history_duplicates(&mut self, duplicates: HistoryDuplicates) {
+        self.config_mut().set_history_duplicates(duplicates);
+        self.history.configure(&self.config);
+    }
+
+    fn set_history_control(&mut self, value: &str) {
+        self.config_mut().set_history_control(value);
+        self.history.configure(&self.config);
     }
 
     fn set_history_ignore_space(&mut self, yes: bool) {
         self.config_mut().set_history_ignore_space(yes);
-        self.history.ignore_space = yes;
+        self.history.configure(&self.config);
+    }
+
+    fn set_history_format(&mut self, format: HistoryFormat) {
+        self.config_mut().set_history_format(format);
+        self.history.configure(&self.config);
+    }
+
+    fn set_bell_style(&mut self, bell_style: BellStyle) {
+        self.config_mut().set_bell_style(bell_style);
+        self.term.set_bell_style(bell_style);
     }
 
     fn set_color_mode(&mut self, color_mode: ColorMode) {
         self.config_mut().set_color_mode(color_mode);
-        self.term.color_mode = color_mode;
+        self.term.set_color_mode(color_mode);
+    }
+
+    fn enable_bracketed_paste(&mut self, enabled: bool) {
+        self.config_mut().set_enable_bracketed_paste(enabled);
+        self.term.set_enable_bracketed_paste(enabled);
     }
 }
 
-impl<H: Helper> fmt::Debug for Editor<H> {
+impl<H: Helper, S: HistoryStore> fmt::Debug for Editor<H, S> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Editor")
             .field("term", &self.term)
@@ -856,12 +2453,12 @@ impl<H: Helper> fmt::Debug for Editor<H> {
     }
 }
 
-struct Iter<'a, H: Helper> {
-    editor: &'a mut Editor<H>,
+struct Iter<'a, H: Helper, S: HistoryStore> {
+    editor: &'a mut Editor<H, S>,
     prompt: &'a str,
 }
 
-impl<'a, H: Helper> Iterator for Iter<'a, H> {
+impl<'a, H: Helper, S: HistoryStore> Iterator for Iter<'a, H, S> {
     type Item = Result<String>;
 
     fn next(&mut self) -> Option<Result<String>> {
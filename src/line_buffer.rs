@@ -1,7 +1,8 @@
 //! Line buffer with current cursor position
-use crate::keymap::{At, CharSearch, Movement, RepeatCount, Word};
+use crate::config::WordBoundary;
+use crate::keymap::{Anchor, At, CharSearch, Movement, RepeatCount, TextObject, Word};
 use std::cell::RefCell;
-use std::cmp::min;
+use std::cmp::{min, Ordering};
 use std::fmt;
 use std::iter;
 use std::ops::{Deref, Index, Range};
@@ -56,9 +57,11 @@ pub(crate) trait ChangeListener: DeleteListener {
 ///
 /// The methods do text manipulations or/and cursor movements.
 pub struct LineBuffer {
-    buf: String,      // Edited line buffer (rl_line_buffer)
-    pos: usize,       // Current cursor position (byte position) (rl_point)
-    can_growth: bool, // Whether to allow dynamic growth
+    buf: String,                 // Edited line buffer (rl_line_buffer)
+    pos: usize,                  // Current cursor position (byte position) (rl_point)
+    can_growth: bool,            // Whether to allow dynamic growth
+    word_boundary: WordBoundary, // Emacs words
+    mark: Option<usize>,         // Emacs mark (byte position) (rl_mark)
     dl: Option<Arc<Mutex<dyn DeleteListener>>>,
     cl: Option<Rc<RefCell<dyn ChangeListener>>>,
 }
@@ -79,6 +82,8 @@ impl LineBuffer {
             buf: String::with_capacity(capacity),
             pos: 0,
             can_growth: false,
+            word_boundary: WordBoundary::Alphanumeric,
+            mark: None,
             dl: None,
             cl: None,
         }
@@ -107,6 +112,11 @@ impl LineBuffer {
         lb
     }
 
+    /// Set the characters which make up emacs words
+    pub(crate) fn set_word_boundary(&mut self, word_boundary: WordBoundary) {
+        self.word_boundary = word_boundary;
+    }
+
     pub(crate) fn set_delete_listener(&mut self, dl: Arc<Mutex<dyn DeleteListener>>) {
         self.dl = Some(dl);
     }
@@ -136,6 +146,46 @@ impl LineBuffer {
         self.pos = pos;
     }
 
+    /// Mark position (byte position), if set
+    pub fn mark(&self) -> Option<usize> {
+        self.mark
+    }
+
+    /// Set the mark at the cursor position.
+    pub fn set_mark(&mut self) {
+        self.mark = Some(self.pos);
+    }
+
+    /// Swap the cursor and the mark positions.
+    /// Return `false` if the mark is not set.
+    pub fn exchange_point_and_mark(&mut self) -> bool {
+        match self.mark {
+            Some(mark) => {
+                self.mark = Some(self.pos);
+                self.pos = mark;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Text between the mark and the cursor, if the mark is set.
+    pub fn region(&self) -> Option<Range<usize>> {
+        self.mark
+            .map(|mark| min(mark, self.pos)..mark.max(self.pos))
+    }
+
+    /// Delete the text between the mark and the cursor, without killing it
+    pub fn delete_region(&mut self) -> bool {
+        match self.region() {
+            Some(region) if !region.is_empty() => {
+                self.delete_range(region);
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Returns the length of this buffer, in bytes.
     pub fn len(&self) -> usize {
         self.buf.len()
@@ -163,6 +213,7 @@ impl LineBuffer {
             self.insert_str(0, buf);
             self.pos = pos;
         }
+        self.mark = None;
     }
 
     fn end_of_line(&self) -> usize {
@@ -182,6 +233,20 @@ impl LineBuffer {
         }
     }
 
+    /// Range between the first non-blank character of the line and the
+    /// cursor
+    fn first_print_range(&self) -> Option<Range<usize>> {
+        let start = self.start_of_line();
+        let first = self.buf[start..]
+            .find(|c: char| c == '\n' || !c.is_whitespace())
+            .map_or(self.buf.len(), |i| start + i);
+        match first.cmp(&self.pos) {
+            Ordering::Less => Some(first..self.pos),
+            Ordering::Greater => Some(self.pos..first),
+            Ordering::Equal => None,
+        }
+    }
+
     /// Returns the character at current cursor position.
     pub(crate) fn grapheme_at_cursor(&self) -> Option<&str> {
         if self.pos == self.buf.len() {
@@ -230,6 +295,7 @@ impl LineBuffer {
         let push = self.pos == self.buf.len();
         if n == 1 {
             self.buf.insert(self.pos, ch);
+            self.mark_inserted(self.pos, ch.len_utf8());
             for cl in &self.cl {
                 if let Ok(mut cl) = cl.try_borrow_mut() {
                     cl.insert_char(self.pos, ch);
@@ -265,6 +331,24 @@ impl LineBuffer {
         Some(push)
     }
 
+    /// Open a new line below (`Anchor::After`) or above (`Anchor::Before`)
+    /// the current one and move the cursor to it.
+    pub fn open_line(&mut self, anchor: Anchor) -> bool {
+        if self.must_truncate(self.buf.len() + 1) {
+            return false;
+        }
+        let idx = match anchor {
+            Anchor::After => self.end_of_line(),
+            Anchor::Before => self.start_of_line(),
+        };
+        self.insert_str(idx, "\n");
+        self.pos = match anchor {
+            Anchor::After => idx + 1,
+            Anchor::Before => idx,
+        };
+        true
+    }
+
     /// Delete previously yanked text and yank/paste `text` at current position.
     pub fn yank_pop(&mut self, yank_size: usize, text: &str) -> Option<bool> {
         let end = self.pos;
@@ -374,6 +458,25 @@ impl LineBuffer {
         }
     }
 
+    /// Replace the characters at the left of the cursor by spaces and move
+    /// the cursor before them ("Backspace" in overwrite mode).
+    /// At the end of the line, they are just deleted.
+    pub fn overwrite_backspace(&mut self, n: RepeatCount) -> bool {
+        if self.pos == self.buf.len() {
+            return self.backspace(n);
+        }
+        match self.prev_pos(n) {
+            Some(pos) => {
+                let end = self.pos;
+                let spaces = " ".repeat(self.buf[pos..end].graphemes(true).count());
+                self.replace(pos..end, &spaces);
+                self.pos = pos;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Kill the text from point to the end of the line.
     pub fn kill_line(&mut self) -> bool {
         if !self.buf.is_empty() && self.pos < self.buf.len() {
@@ -431,18 +534,23 @@ impl LineBuffer {
         }
     }
 
-    /// Exchange the char before cursor with the character at cursor.
-    pub fn transpose_chars(&mut self) -> bool {
-        if self.pos == 0 || self.buf.graphemes(true).count() < 2 {
+    /// Drag the char before cursor forward over the `n` characters at cursor,
+    /// moving the cursor forward as well.
+    /// At the end of the line, exchange the last two characters.
+    pub fn transpose_chars(&mut self, n: RepeatCount) -> bool {
+        if n == 0 || self.pos == 0 || self.buf.graphemes(true).count() < 2 {
             return false;
         }
-        if self.pos == self.buf.len() {
+        let n = if self.pos == self.buf.len() {
             self.move_backward(1);
-        }
-        let chars = self.delete(1).unwrap();
+            1
+        } else {
+            n
+        };
         self.move_backward(1);
+        let chars = self.delete(1).unwrap();
+        self.move_forward(n);
         self.yank(&chars, 1);
-        self.move_forward(1);
         true
     }
 
@@ -460,7 +568,7 @@ impl LineBuffer {
                 if let Some((j, y)) = gj {
                     let gi = gis.next();
                     if let Some((_, x)) = gi {
-                        if is_start_of_word(word_def, x, y) {
+                        if is_start_of_word(word_def, self.word_boundary, x, y) {
                             sow = j;
                             break 'inner;
                         }
@@ -499,6 +607,32 @@ impl LineBuffer {
         }
     }
 
+    /// End of `n` words forward from the cursor for an operator: unlike the
+    /// cursor movement, it spans the last word of the buffer (`dw`).
+    fn next_word_end(&self, at: At, word_def: Word, n: RepeatCount) -> Option<usize> {
+        let pos = match self.next_word_pos(self.pos, at, word_def, n) {
+            Some(pos) => pos,
+            // the cursor is on the last character
+            None if at == At::Start && word_def != Word::Emacs && self.pos < self.buf.len() => {
+                return Some(self.buf.len());
+            }
+            None => return None,
+        };
+        if at != At::Start || word_def == Word::Emacs {
+            return Some(pos);
+        }
+        let mut graphemes = self.buf[..pos].graphemes(true).rev();
+        match (graphemes.next(), self.buf[pos..].graphemes(true).next()) {
+            (Some(previous), Some(last))
+                if pos + last.len() == self.buf.len()
+                    && !is_start_of_word(word_def, self.word_boundary, previous, last) =>
+            {
+                Some(self.buf.len())
+            }
+            _ => Some(pos),
+        }
+    }
+
     fn next_word_pos(&self, pos: usize, at: At, word_def: Word, n: RepeatCount) -> Option<usize> {
         if pos == self.buf.len() {
             return None;
@@ -518,10 +652,12 @@ impl LineBuffer {
                 if let Some((i, x)) = gi {
                     let gj = gis.next();
                     if let Some((j, y)) = gj {
-                        if at == At::Start && is_start_of_word(word_def, x, y) {
+                        if at == At::Start && is_start_of_word(word_def, self.word_boundary, x, y) {
                             wp = j;
                             break 'inner;
-                        } else if at != At::Start && is_end_of_word(word_def, x, y) {
+                        } else if at != At::Start
+                            && is_end_of_word(word_def, self.word_boundary, x, y)
+                        {
                             if word_def == Word::Emacs || at == At::AfterEnd {
                                 wp = j;
                             } else {
@@ -723,7 +859,7 @@ impl LineBuffer {
     /// Kill from the cursor to the end of the current word,
     /// or, if between words, to the end of the next word.
     pub fn delete_word(&mut self, at: At, word_def: Word, n: RepeatCount) -> bool {
-        if let Some(pos) = self.next_word_pos(self.pos, at, word_def, n) {
+        if let Some(pos) = self.next_word_end(at, word_def, n) {
             let start = self.pos;
             self.drain(start..pos, Direction::Forward);
             true
@@ -760,6 +896,148 @@ impl LineBuffer {
         }
     }
 
+    /// Range of the text object `obj` around the cursor, `n` words or
+    /// nesting levels.
+    fn text_object_range(&self, obj: TextObject, n: RepeatCount) -> Option<(usize, usize)> {
+        match obj {
+            TextObject::InnerWord(word_def) => self.word_object(word_def, n, false),
+            TextObject::AWord(word_def) => self.word_object(word_def, n, true),
+            TextObject::InnerQuote(quote) => self
+                .quote_object(quote)
+                .map(|(open, close)| (open + quote.len_utf8(), close)),
+            TextObject::AQuote(quote) => self.quote_object(quote).map(|(open, close)| {
+                let end = close + quote.len_utf8();
+                let blanks = self.buf[end..]
+                    .find(|c: char| c != ' ' && c != '\t')
+                    .unwrap_or(self.buf.len() - end);
+                (open, end + blanks)
+            }),
+            TextObject::InnerBlock(open, close) => self
+                .block_object(open, close, n)
+                .map(|(start, end)| (start + open.len_utf8(), end)),
+            TextObject::ABlock(open, close) => self
+                .block_object(open, close, n)
+                .map(|(start, end)| (start, end + close.len_utf8())),
+        }
+    }
+
+    /// `n` words (or runs of whitespace) of the current line from the one
+    /// under the cursor, with the whitespace following them when `around`
+    /// (or preceding them at the end of the line).
+    fn word_object(&self, word_def: Word, n: RepeatCount, around: bool) -> Option<(usize, usize)> {
+        let (start, end) = (self.start_of_line(), self.end_of_line());
+        // (start, end, whitespace)
+        let mut runs: Vec<(usize, usize, bool)> = Vec::new();
+        let mut class = None;
+        for (i, g) in self.buf[start..end].grapheme_indices(true) {
+            let c = if g.chars().any(char::is_whitespace) {
+                0
+            } else if is_word_char(word_def, self.word_boundary, g) {
+                1
+            } else {
+                2
+            };
+            let (i, j) = (start + i, start + i + g.len());
+            match runs.last_mut() {
+                Some(run) if class == Some(c) => run.1 = j,
+                _ => runs.push((i, j, c == 0)),
+            }
+            class = Some(c);
+        }
+        let r = runs
+            .iter()
+            .position(|run| self.pos < run.1)
+            .or_else(|| runs.len().checked_sub(1))?;
+        if !around {
+            let last = (r + n - 1).min(runs.len() - 1);
+            return Some((runs[r].0, runs[last].1));
+        }
+        let mut next = r;
+        let mut trailing = false;
+        for _ in 0..n {
+            if next == runs.len() {
+                break;
+            }
+            if runs[next].2 {
+                // whitespace and the following word
+                next = (next + 2).min(runs.len());
+            } else if runs.get(next + 1).is_some_and(|run| run.2) {
+                // word and the following whitespace
+                next += 2;
+                trailing = true;
+            } else {
+                next += 1;
+                trailing = false;
+            }
+        }
+        let start = if !trailing && !runs[r].2 && r > 0 && runs[r - 1].2 {
+            runs[r - 1].0
+        } else {
+            runs[r].0
+        };
+        Some((start, runs[next - 1].1))
+    }
+
+    /// Positions of the quotes around (or after) the cursor on the current
+    /// line, quotes being paired from the start of the line.
+    fn quote_object(&self, quote: char) -> Option<(usize, usize)> {
+        let start = self.start_of_line();
+        let mut escaped = false;
+        let quotes: Vec<usize> = self.buf[start..self.end_of_line()]
+            .char_indices()
+            .filter_map(|(i, c)| {
+                let found = !escaped && c == quote;
+                escaped = !escaped && c == '\\';
+                if found {
+                    Some(start + i)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        quotes
+            .chunks_exact(2)
+            .map(|pair| (pair[0], pair[1]))
+            .find(|&(_, close)| self.pos <= close)
+    }
+
+    /// Positions of the `n`th pair of brackets enclosing the cursor.
+    fn block_object(&self, open: char, close: char, n: RepeatCount) -> Option<(usize, usize)> {
+        // an opening bracket under the cursor belongs to the block
+        let end = match self.buf[self.pos..].chars().next() {
+            Some(c) if c == open => self.pos + c.len_utf8(),
+            _ => self.pos,
+        };
+        let mut level = n;
+        let mut depth = 0usize;
+        let (start, _) = self.buf[..end].char_indices().rev().find(|&(_, c)| {
+            if c == close {
+                depth += 1;
+            } else if c == open {
+                if depth == 0 {
+                    level -= 1;
+                    return level == 0;
+                }
+                depth -= 1;
+            }
+            false
+        })?;
+        let inner = start + open.len_utf8();
+        depth = 0;
+        let (end, _) = self.buf[inner..].char_indices().find(|&(_, c)| {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            false
+        })?;
+        Some((start, inner + end))
+    }
+
     fn skip_whitespace(&self) -> Option<usize> {
         if self.pos == self.buf.len() {
             return None;
@@ -777,35 +1055,40 @@ impl LineBuffer {
             .map(|i| i + self.pos)
     }
 
-    /// Alter the next word.
-    pub fn edit_word(&mut self, a: WordAction) -> bool {
-        if let Some(start) = self.skip_whitespace() {
-            if let Some(end) = self.next_word_pos(start, At::AfterEnd, Word::Emacs, 1) {
-                if start == end {
-                    return false;
+    /// Alter the next `n` words.
+    pub fn edit_word(&mut self, a: WordAction, n: RepeatCount) -> bool {
+        let mut succeed = false;
+        for _ in 0..n {
+            let start = match self.skip_whitespace() {
+                Some(start) => start,
+                None => break,
+            };
+            let end = match self.next_word_pos(start, At::AfterEnd, Word::Emacs, 1) {
+                Some(end) if end > start => end,
+                _ => break,
+            };
+            let word = self
+                .drain(start..end, Direction::default())
+                .collect::<String>();
+            let result = match a {
+                WordAction::Capitalize => {
+                    let ch = word.graphemes(true).next().unwrap();
+                    let cap = to_titlecase(ch);
+                    cap + &word[ch.len()..].to_lowercase()
                 }
-                let word = self
-                    .drain(start..end, Direction::default())
-                    .collect::<String>();
-                let result = match a {
-                    WordAction::Capitalize => {
-                        let ch = (&word).graphemes(true).next().unwrap();
-                        let cap = ch.to_uppercase();
-                        cap + &word[ch.len()..].to_lowercase()
-                    }
-                    WordAction::Lowercase => word.to_lowercase(),
-                    WordAction::Uppercase => word.to_uppercase(),
-                };
-                self.insert_str(start, &result);
-                self.pos = start + result.len();
-                return true;
-            }
+                WordAction::Lowercase => word.to_lowercase(),
+                WordAction::Uppercase => word.to_uppercase(),
+            };
+            self.insert_str(start, &result);
+            self.pos = start + result.len();
+            succeed = true;
         }
-        false
+        succeed
     }
 
     /// Transpose two words
     pub fn transpose_words(&mut self, n: RepeatCount) -> bool {
+        let pos = self.pos;
         let word_def = Word::Emacs;
         self.move_to_next_word(At::AfterEnd, word_def, n);
         let w2_end = self.pos;
@@ -816,6 +1099,7 @@ impl LineBuffer {
         self.move_to_next_word(At::AfterEnd, word_def, 1);
         let w1_end = self.pos;
         if w1_beg == w2_beg || w2_beg < w1_end {
+            self.pos = pos;
             return false;
         }
 
@@ -843,12 +1127,14 @@ impl LineBuffer {
             } // Ok: while undoing, cl is borrowed. And we want to ignore
               // changes while undoing.
         }
+        self.mark_deleted(&range);
         self.buf.drain(range);
         if start == self.buf.len() {
             self.buf.push_str(text);
         } else {
             self.buf.insert_str(start, text);
         }
+        self.mark_inserted(start, text.len());
         self.pos = start + text.len();
     }
 
@@ -861,6 +1147,7 @@ impl LineBuffer {
             } // Ok: while undoing, cl is borrowed. And we want to ignore
               // changes while undoing.
         }
+        self.mark_inserted(idx, s.len());
         if idx == self.buf.len() {
             self.buf.push_str(s);
             true
@@ -889,9 +1176,30 @@ impl LineBuffer {
             } // Ok: while undoing, cl is borrowed. And we want to ignore
               // changes while undoing.
         }
+        self.mark_deleted(&range);
         self.buf.drain(range)
     }
 
+    /// Keep the mark on the same text when `len` bytes are inserted at `idx`
+    fn mark_inserted(&mut self, idx: usize, len: usize) {
+        if let Some(ref mut mark) = self.mark {
+            if *mark > idx {
+                *mark += len;
+            }
+        }
+    }
+
+    /// Keep the mark on the same text when `range` is deleted
+    fn mark_deleted(&mut self, range: &Range<usize>) {
+        if let Some(ref mut mark) = self.mark {
+            if *mark >= range.end {
+                *mark -= range.end - range.start;
+            } else if *mark > range.start {
+                *mark = range.start;
+            }
+        }
+    }
+
     /// Return the content between current cursor position and `mvt` position.
     /// Return `None` when the buffer is empty or when the movement fails.
     pub fn copy(&self, mvt: &Movement) -> Option<String> {
@@ -916,14 +1224,9 @@ impl LineBuffer {
                     Some(self.buf[start..self.pos].to_owned())
                 }
             }
-            Movement::ViFirstPrint => {
-                if self.pos == 0 {
-                    None
-                } else {
-                    self.next_word_pos(0, At::Start, Word::Big, 1)
-                        .map(|pos| self.buf[pos..self.pos].to_owned())
-                }
-            }
+            Movement::ViFirstPrint => self
+                .first_print_range()
+                .map(|range| self.buf[range].to_owned()),
             Movement::EndOfLine => {
                 let end = self.end_of_line();
                 if self.pos == end {
@@ -957,7 +1260,7 @@ impl LineBuffer {
                 .prev_word_pos(self.pos, word_def, n)
                 .map(|pos| self.buf[pos..self.pos].to_owned()),
             Movement::ForwardWord(n, at, word_def) => self
-                .next_word_pos(self.pos, at, word_def, n)
+                .next_word_end(at, word_def, n)
                 .map(|pos| self.buf[self.pos..pos].to_owned()),
             Movement::ViCharSearch(n, cs) => {
                 let search_result = match cs {
@@ -972,6 +1275,13 @@ impl LineBuffer {
                     CharSearch::Forward(c) => self.buf[self.pos..pos + c.len_utf8()].to_owned(),
                 })
             }
+            Movement::ViTextObject(n, obj) => self
+                .text_object_range(obj, n)
+                .map(|(start, end)| self.buf[start..end].to_owned()),
+            Movement::Region => self
+                .region()
+                .filter(|region| !region.is_empty())
+                .map(|region| self.buf[region].to_owned()),
             Movement::BackwardChar(n) => self
                 .prev_pos(n)
                 .map(|pos| self.buf[pos..self.pos].to_owned()),
@@ -1051,8 +1361,28 @@ impl LineBuffer {
                 }
             }
             Movement::ViFirstPrint => {
-                false // TODO
+                if let Some(range) = self.first_print_range() {
+                    self.delete_range(range);
+                    true
+                } else {
+                    false
+                }
+            }
+            Movement::ViTextObject(n, obj) => {
+                if let Some((start, end)) = self.text_object_range(obj, n) {
+                    self.delete_range(start..end);
+                    true
+                } else {
+                    false
+                }
             }
+            Movement::Region => match self.region() {
+                Some(region) if !region.is_empty() => {
+                    self.delete_range(region);
+                    true
+                }
+                _ => false,
+            },
             Movement::EndOfBuffer => {
                 // Kill the text from point to the end of the buffer.
                 self.kill_buffer()
@@ -1093,8 +1423,10 @@ impl LineBuffer {
                 .prev_word_pos(self.pos, word_def, n)
                 .map(|pos| (pos, self.pos)),
             Movement::ForwardWord(n, at, word_def) => self
-                .next_word_pos(self.pos, at, word_def, n)
+                .next_word_end(at, word_def, n)
                 .map(|pos| (self.pos, pos)),
+            Movement::ViTextObject(n, obj) => self.text_object_range(obj, n),
+            Movement::Region => self.region().map(|region| (region.start, region.end)),
             Movement::LineUp(n) => self.n_lines_up(n),
             Movement::LineDown(n) => self.n_lines_down(n),
         };
@@ -1136,6 +1468,27 @@ impl LineBuffer {
         }
         true
     }
+
+    /// Insert `prefix` at the beginning of each line (`insert-comment`).
+    /// With `toggle`, remove it instead when all lines already start with it.
+    pub fn insert_comment(&mut self, prefix: &str, toggle: bool) -> bool {
+        if prefix.is_empty() {
+            return false;
+        }
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(self.buf.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let uncomment = toggle && starts.iter().all(|&i| self.buf[i..].starts_with(prefix));
+        for &start in starts.iter().rev() {
+            if uncomment {
+                self.drain(start..start + prefix.len(), Direction::default());
+            } else {
+                self.insert_str(start, prefix);
+            }
+        }
+        self.pos = self.buf.len();
+        true
+    }
 }
 
 impl Deref for LineBuffer {
@@ -1146,18 +1499,70 @@ impl Deref for LineBuffer {
     }
 }
 
-fn is_start_of_word(word_def: Word, previous: &str, grapheme: &str) -> bool {
-    (!is_word_char(word_def, previous) && is_word_char(word_def, grapheme))
+/// Titlecase mapping of the first char of `grapheme`: it differs from the
+/// uppercase mapping for digraphs, ligatures and some Greek letters (e.g.
+/// `ǆ` becomes `ǅ` and `ß` becomes `Ss`).
+fn to_titlecase(grapheme: &str) -> String {
+    let mut chars = grapheme.chars();
+    let first = match chars.next() {
+        Some(c) => c,
+        None => return String::new(),
+    };
+    let mut title = match first {
+        '\u{1c4}'..='\u{1c6}' => "\u{1c5}".to_owned(),
+        '\u{1c7}'..='\u{1c9}' => "\u{1c8}".to_owned(),
+        '\u{1ca}'..='\u{1cc}' => "\u{1cb}".to_owned(),
+        '\u{1f1}'..='\u{1f3}' => "\u{1f2}".to_owned(),
+        'ß' => "Ss".to_owned(),
+        'ŉ' => "\u{2bc}N".to_owned(),
+        'և' => "Եւ".to_owned(),
+        'ﬀ' => "Ff".to_owned(),
+        'ﬁ' => "Fi".to_owned(),
+        'ﬂ' => "Fl".to_owned(),
+        'ﬃ' => "Ffi".to_owned(),
+        'ﬄ' => "Ffl".to_owned(),
+        'ﬅ' | 'ﬆ' => "St".to_owned(),
+        'ﬓ' => "Մն".to_owned(),
+        'ﬔ' => "Մե".to_owned(),
+        'ﬕ' => "Մի".to_owned(),
+        'ﬖ' => "Վն".to_owned(),
+        'ﬗ' => "Մխ".to_owned(),
+        // Greek with ypogegrammeni: titlecase with prosgegrammeni
+        '\u{1f80}'..='\u{1f87}' | '\u{1f90}'..='\u{1f97}' | '\u{1fa0}'..='\u{1fa7}' => {
+            char::from_u32(first as u32 + 8).unwrap().to_string()
+        }
+        '\u{1f88}'..='\u{1f8f}'
+        | '\u{1f98}'..='\u{1f9f}'
+        | '\u{1fa8}'..='\u{1faf}'
+        | '\u{1fbc}'
+        | '\u{1fcc}'
+        | '\u{1ffc}' => first.to_string(),
+        '\u{1fb3}' => "\u{1fbc}".to_owned(),
+        '\u{1fc3}' => "\u{1fcc}".to_owned(),
+        '\u{1ff3}' => "\u{1ffc}".to_owned(),
+        c => c.to_uppercase().collect(),
+    };
+    title.extend(chars.flat_map(char::to_uppercase));
+    title
+}
+
+fn is_start_of_word(
+    word_def: Word,
+    boundary: WordBoundary,
+    previous: &str,
+    grapheme: &str,
+) -> bool {
+    (!is_word_char(word_def, boundary, previous) && is_word_char(word_def, boundary, grapheme))
         || (word_def == Word::Vi && !is_other_char(previous) && is_other_char(grapheme))
 }
-fn is_end_of_word(word_def: Word, grapheme: &str, next: &str) -> bool {
-    (!is_word_char(word_def, next) && is_word_char(word_def, grapheme))
+fn is_end_of_word(word_def: Word, boundary: WordBoundary, grapheme: &str, next: &str) -> bool {
+    (!is_word_char(word_def, boundary, next) && is_word_char(word_def, boundary, grapheme))
         || (word_def == Word::Vi && !is_other_char(next) && is_other_char(grapheme))
 }
 
-fn is_word_char(word_def: Word, grapheme: &str) -> bool {
+fn is_word_char(word_def: Word, boundary: WordBoundary, grapheme: &str) -> bool {
     match word_def {
-        Word::Emacs => grapheme.chars().all(char::is_alphanumeric),
+        Word::Emacs => grapheme.chars().all(|c| boundary.is_word_char(c)),
         Word::Vi => is_vi_word_char(grapheme),
         Word::Big => !grapheme.chars().any(char::is_whitespace),
     }
@@ -1375,6 +1780,24 @@ mod test {
         cl.borrow().assert_deleted_str_eq("α");
     }
 
+    #[test]
+    fn overwrite_backspace() {
+        let mut s = LineBuffer::init("αßγ", 4, None);
+        let ok = s.overwrite_backspace(1);
+        assert_eq!("α γ", s.buf);
+        assert_eq!(2, s.pos);
+        assert!(ok);
+
+        s.move_end();
+        let ok = s.overwrite_backspace(1);
+        assert_eq!("α ", s.buf);
+        assert_eq!(3, s.pos);
+        assert!(ok);
+
+        s.set_pos(0);
+        assert!(!s.overwrite_backspace(1));
+    }
+
     #[test]
     fn kill() {
         let cl = Listener::new();
@@ -1393,6 +1816,116 @@ mod test {
         cl.borrow().assert_deleted_str_eq("αß");
     }
 
+    #[test]
+    fn region() {
+        use crate::keymap::Movement;
+        let cl = Listener::new();
+        let mut s = LineBuffer::init("hello world", 6, Some(cl.clone()));
+        assert_eq!(None, s.region());
+        assert_eq!(None, s.copy(&Movement::Region));
+        s.set_mark();
+        s.pos = 11;
+        assert_eq!(Some(6..11), s.region());
+        assert_eq!(Some("world".to_owned()), s.copy(&Movement::Region));
+        // the mark follows the text
+        s.insert_str(0, ">> ");
+        assert_eq!(Some(9), s.mark());
+        s.delete_range(0..3);
+        assert_eq!(Some(6), s.mark());
+        s.delete_range(4..8);
+        assert_eq!(Some(4), s.mark());
+        assert_eq!("hellrld", s.buf);
+
+        s.pos = 7;
+        assert!(s.exchange_point_and_mark());
+        assert_eq!(4, s.pos);
+        assert_eq!(Some(7), s.mark());
+        assert!(s.kill(&Movement::Region));
+        assert_eq!("hell", s.buf);
+        assert_eq!(4, s.pos);
+        cl.borrow().assert_deleted_str_eq("rld");
+        assert!(!s.kill(&Movement::Region));
+
+        s.update("new", 3);
+        assert_eq!(None, s.mark());
+        assert!(!s.exchange_point_and_mark());
+    }
+
+    #[test]
+    fn text_object() {
+        use crate::keymap::{Movement, TextObject};
+        let copy = |line: &str, pos: usize, n: usize, obj: TextObject| {
+            LineBuffer::init(line, pos, None).copy(&Movement::ViTextObject(n, obj))
+        };
+        let s = "let foo = bar(x, (y + 1)) ;";
+        assert_eq!(
+            Some("foo"),
+            copy(s, 5, 1, TextObject::InnerWord(Word::Vi)).as_deref()
+        );
+        assert_eq!(
+            Some("foo "),
+            copy(s, 5, 1, TextObject::AWord(Word::Vi)).as_deref()
+        );
+        assert_eq!(
+            Some("foo ="),
+            copy(s, 5, 3, TextObject::InnerWord(Word::Vi)).as_deref()
+        );
+        assert_eq!(
+            Some(" ="),
+            copy(s, 7, 1, TextObject::AWord(Word::Vi)).as_deref()
+        );
+        assert_eq!(
+            Some("bar(x,"),
+            copy(s, 10, 1, TextObject::InnerWord(Word::Big)).as_deref()
+        );
+        // leading whitespace at the end of the line
+        assert_eq!(
+            Some(" ;"),
+            copy(s, 26, 1, TextObject::AWord(Word::Vi)).as_deref()
+        );
+
+        let block = TextObject::InnerBlock('(', ')');
+        assert_eq!(Some("y + 1"), copy(s, 19, 1, block).as_deref());
+        assert_eq!(Some("x, (y + 1)"), copy(s, 19, 2, block).as_deref());
+        assert_eq!(Some("y + 1"), copy(s, 18, 1, block).as_deref());
+        assert_eq!(Some("y + 1"), copy(s, 23, 1, block).as_deref());
+        assert_eq!(
+            Some("(x, (y + 1))"),
+            copy(s, 14, 1, TextObject::ABlock('(', ')')).as_deref()
+        );
+        assert_eq!(None, copy(s, 2, 1, block));
+        assert_eq!(None, copy(s, 19, 3, block));
+
+        let s = r#"echo "a \"b\"" 'c' "d""#;
+        assert_eq!(
+            Some(r#"a \"b\""#),
+            copy(s, 7, 1, TextObject::InnerQuote('"')).as_deref()
+        );
+        assert_eq!(
+            Some(r#""a \"b\"" "#),
+            copy(s, 5, 1, TextObject::AQuote('"')).as_deref()
+        );
+        // the next quoted text
+        assert_eq!(
+            Some("a \\\"b\\\""),
+            copy(s, 0, 1, TextObject::InnerQuote('"')).as_deref()
+        );
+        assert_eq!(
+            Some("d"),
+            copy(s, 17, 1, TextObject::InnerQuote('"')).as_deref()
+        );
+        assert_eq!(
+            Some("c"),
+            copy(s, 15, 1, TextObject::InnerQuote('\'')).as_deref()
+        );
+        assert_eq!(None, copy(s, 21, 1, TextObject::InnerQuote('\'')));
+
+        let mut s = LineBuffer::init("f(a, b)", 3, None);
+        assert!(s.kill(&Movement::ViTextObject(1, TextObject::InnerBlock('(', ')'))));
+        assert_eq!("f()", s.buf);
+        assert_eq!(2, s.pos);
+    }
+
     #[test]
     fn kill_multiline() {
         let cl = Listener::new();
@@ -1452,24 +1985,44 @@ mod test {
     #[test]
     fn transpose() {
         let mut s = LineBuffer::init("aßc", 1, None);
-        let ok = s.transpose_chars();
+        let ok = s.transpose_chars(1);
         assert_eq!("ßac", s.buf);
         assert_eq!(3, s.pos);
         assert_eq!(true, ok);
 
         s.buf = String::from("aßc");
         s.pos = 3;
-        let ok = s.transpose_chars();
+        let ok = s.transpose_chars(1);
         assert_eq!("acß", s.buf);
         assert_eq!(4, s.pos);
         assert_eq!(true, ok);
 
         s.buf = String::from("aßc");
         s.pos = 4;
-        let ok = s.transpose_chars();
+        let ok = s.transpose_chars(1);
         assert_eq!("acß", s.buf);
         assert_eq!(4, s.pos);
         assert_eq!(true, ok);
+
+        // drag 'a' over two characters
+        let mut s = LineBuffer::init("abcd", 1, None);
+        assert!(s.transpose_chars(2));
+        assert_eq!("bcad", s.buf);
+        assert_eq!(3, s.pos);
+        // count is ignored at the end of the line
+        s.pos = 4;
+        assert!(s.transpose_chars(3));
+        assert_eq!("bcda", s.buf);
+        assert_eq!(4, s.pos);
+        // not enough characters
+        s.pos = 1;
+        assert!(s.transpose_chars(9));
+        assert_eq!("cdab", s.buf);
+        assert_eq!(4, s.pos);
+        s.pos = 0;
+        assert!(!s.transpose_chars(1));
+        let mut s = LineBuffer::init("a", 1, None);
+        assert!(!s.transpose_chars(1));
     }
 
     #[test]
@@ -1491,6 +2044,29 @@ mod test {
         assert_eq!(0, s.pos);
     }
 
+    #[test]
+    fn word_boundary() {
+        use crate::config::WordBoundary;
+        let line = "ls foo.bar::baz|grep x";
+        let mut s = LineBuffer::init(line, 3, None);
+        s.set_word_boundary(WordBoundary::Whitespace);
+        assert!(s.move_to_next_word(At::AfterEnd, Word::Emacs, 1));
+        assert_eq!(20, s.pos); // after "foo.bar::baz|grep"
+        assert!(s.move_to_prev_word(Word::Emacs, 1));
+        assert_eq!(3, s.pos);
+
+        s.set_word_boundary(WordBoundary::ShellToken);
+        assert!(s.move_to_next_word(At::AfterEnd, Word::Emacs, 2));
+        assert_eq!(20, s.pos); // after "grep"
+        assert!(s.delete_prev_word(Word::Emacs, 1));
+        assert_eq!("ls foo.bar::baz| x", s.buf);
+
+        s.set_word_boundary(WordBoundary::Custom(|c| c.is_alphanumeric() || c == '.'));
+        s.set_pos(3);
+        assert!(s.delete_word(At::AfterEnd, Word::Emacs, 1));
+        assert_eq!("ls ::baz| x", s.buf);
+    }
+
     #[test]
     fn move_to_prev_vi_word() {
         let mut s = LineBuffer::init("alpha ,beta/rho; mu", 19, None);
@@ -1732,6 +2308,18 @@ mod test {
         assert_eq!(2, s.pos);
         assert_eq!(true, ok);
         cl.borrow().assert_deleted_str_eq("ß  ");
+
+        // a one-character last word
+        let mut s = LineBuffer::init("a b c", 4, Some(cl.clone()));
+        let ok = s.delete_word(At::Start, Word::Vi, 1);
+        assert_eq!("a b ", s.buf);
+        assert_eq!(4, s.pos);
+        assert!(ok);
+        cl.borrow().assert_deleted_str_eq("c");
+
+        let mut s = LineBuffer::init("c", 0, Some(cl.clone()));
+        assert!(s.delete_word(At::Start, Word::Vi, 1));
+        assert_eq!("", s.buf);
     }
 
     #[test]
@@ -1773,24 +2361,37 @@ mod test {
     #[test]
     fn edit_word() {
         let mut s = LineBuffer::init("a ßeta  c", 1, None);
-        assert!(s.edit_word(WordAction::Uppercase));
+        assert!(s.edit_word(WordAction::Uppercase, 1));
         assert_eq!("a SSETA  c", s.buf);
         assert_eq!(7, s.pos);
 
         let mut s = LineBuffer::init("a ßetA  c", 1, None);
-        assert!(s.edit_word(WordAction::Lowercase));
+        assert!(s.edit_word(WordAction::Lowercase, 1));
         assert_eq!("a ßeta  c", s.buf);
         assert_eq!(7, s.pos);
 
         let mut s = LineBuffer::init("a ßETA  c", 1, None);
-        assert!(s.edit_word(WordAction::Capitalize));
-        assert_eq!("a SSeta  c", s.buf);
+        assert!(s.edit_word(WordAction::Capitalize, 1));
+        assert_eq!("a Sseta  c", s.buf);
         assert_eq!(7, s.pos);
 
+        let mut s = LineBuffer::init("ǆungla ΣΟΦΟΣ", 0, None);
+        assert!(s.edit_word(WordAction::Capitalize, 1));
+        assert_eq!("ǅungla ΣΟΦΟΣ", s.buf);
+        assert!(s.edit_word(WordAction::Capitalize, 1));
+        assert_eq!("ǅungla Σοφος", s.buf);
+        assert_eq!(s.buf.len(), s.pos);
+
         let mut s = LineBuffer::init("test", 1, None);
-        assert!(s.edit_word(WordAction::Capitalize));
+        assert!(s.edit_word(WordAction::Capitalize, 1));
         assert_eq!("tEst", s.buf);
         assert_eq!(4, s.pos);
+
+        let mut s = LineBuffer::init("one two  three", 0, None);
+        assert!(s.edit_word(WordAction::Uppercase, 5));
+        assert_eq!("ONE TWO  THREE", s.buf);
+        assert_eq!(14, s.pos);
+        assert!(!s.edit_word(WordAction::Uppercase, 1));
     }
 
     #[test]
@@ -1807,6 +2408,18 @@ mod test {
 
         let mut s = LineBuffer::init(" / δelta", 8, None);
         assert!(!s.transpose_words(1));
+        assert_eq!(8, s.pos);
+
+        // at the end of the line, the last two words are transposed
+        let mut s = LineBuffer::init("a bc d", 6, None);
+        assert!(s.transpose_words(1));
+        assert_eq!("a d bc", s.buf);
+        assert_eq!(6, s.pos);
+        // like readline, the first and last words are exchanged
+        let mut s = LineBuffer::init("a bc d", 1, None);
+        assert!(s.transpose_words(2));
+        assert_eq!("d bc a", s.buf);
+        assert_eq!(6, s.pos);
 
         let mut s = LineBuffer::init("ßeta / __", 9, None);
         assert!(!s.transpose_words(1));
@@ -1851,4 +2464,36 @@ mod test {
         assert_eq!(14, s.pos);
         assert!(ok);
     }
+
+    #[test]
+    fn insert_comment() {
+        let mut s = LineBuffer::init("ls\necho", 1, None);
+        assert!(s.insert_comment("# ", false));
+        assert_eq!("# ls\n# echo", s.buf);
+        assert_eq!(s.buf.len(), s.pos);
+        assert!(s.insert_comment("# ", true));
+        assert_eq!("ls\necho", s.buf);
+
+        let mut s = LineBuffer::init("#ls", 0, None);
+        assert!(s.insert_comment("#", false));
+        assert_eq!("##ls", s.buf);
+        let mut s = LineBuffer::init("#ls\necho", 0, None);
+        assert!(s.insert_comment("#", true));
+        assert_eq!("##ls\n#echo", s.buf);
+        assert!(!s.insert_comment("", true));
+    }
+
+    #[test]
+    fn open_line() {
+        use crate::keymap::Anchor;
+
+        let mut s = LineBuffer::init("ab\ncd", 1, None);
+        assert!(s.open_line(Anchor::After));
+        assert_eq!("ab\n\ncd", s.buf);
+        assert_eq!(3, s.pos);
+        let mut s = LineBuffer::init("ab\ncd", 4, None);
+        assert!(s.open_line(Anchor::Before));
+        assert_eq!("ab\n\ncd", s.buf);
+        assert_eq!(3, s.pos);
+    }
 }
@@ -0,0 +1,136 @@
+//! Closures bound to key sequences (see [`Cmd::Custom`](crate::Cmd::Custom))
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::completion::{AnyCompleter, CompletionState};
+use crate::error::ReadlineError;
+use crate::line_buffer::LineBuffer;
+use crate::Context;
+
+/// Access to the line being edited, given to [`CustomCommand`] closures.
+///
+/// The line is refreshed once the closure returns.
+pub struct Prompter<'l> {
+    line: &'l mut LineBuffer,
+    completer: Option<&'l dyn AnyCompleter>,
+    ctx: &'l Context<'l>,
+    messages: Vec<String>,
+    accept: bool,
+}
+
+impl<'l> Prompter<'l> {
+    pub(crate) fn new(
+        line: &'l mut LineBuffer,
+        completer: Option<&'l dyn AnyCompleter>,
+        ctx: &'l Context<'l>,
+    ) -> Self {
+        Self {
+            line,
+            completer,
+            ctx,
+            messages: Vec::new(),
+            accept: false,
+        }
+    }
+
+    /// Line being edited
+    pub fn line(&self) -> &LineBuffer {
+        self.line
+    }
+
+    /// Line being edited: changes can be undone as a whole
+    pub fn line_mut(&mut self) -> &mut LineBuffer {
+        self.line
+    }
+
+    /// Print `text` (as one or more lines) above the prompt
+    pub fn print<S: Into<String>>(&mut self, text: S) {
+        let mut text = text.into();
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        self.messages.push(text);
+    }
+
+    /// Candidates of the helper for the word at the cursor, for frontends
+    /// rendering them themselves (`None` without helper)
+    pub fn completion(&self) -> io::Result<Option<CompletionState>> {
+        let completer = match self.completer {
+            Some(completer) => completer,
+            None => return Ok(None),
+        };
+        match CompletionState::from_any(completer, self.line, self.ctx) {
+            Ok(state) => Ok(Some(state)),
+            Err(ReadlineError::Io(err)) => Err(err),
+            Err(err) => Err(io::Error::other(err)),
+        }
+    }
+
+    /// Inserts the candidate selected in `state` with the helper `update`
+    /// (nothing if none is selected or if the word has been edited since)
+    pub fn accept_completion(&mut self, state: &CompletionState) -> bool {
+        match self.completer {
+            Some(completer) => state.accept_any(completer, self.line),
+            None => false,
+        }
+    }
+
+    /// Accept the line (like `accept-line`) once the closure returns
+    pub fn accept(&mut self) {
+        self.accept = true;
+    }
+
+    /// Messages to print and whether the line is accepted
+    pub(crate) fn into_parts(self) -> (Vec<String>, bool) {
+        (self.messages, self.accept)
+    }
+}
+
+type CustomFn = dyn FnMut(&mut Prompter<'_>) -> io::Result<()> + Send;
+
+/// Closure bound to a key sequence without being registered under a name.
+///
+/// ```
+/// use rustyline::{Cmd, Editor, KeyEvent};
+///
+/// let mut rl = Editor::<()>::new();
+/// rl.bind_sequence(
+///     KeyEvent::alt('d'),
+///     Cmd::custom(|p| {
+///         let pos = p.line().pos();
+///         p.line_mut().insert_str(pos, "2021-01-01");
+///         Ok(())
+///     }),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct CustomCommand(Arc<Mutex<CustomFn>>);
+
+impl CustomCommand {
+    /// Wrap `f`
+    pub fn new<F>(f: F) -> Self
+    where
+        F: FnMut(&mut Prompter<'_>) -> io::Result<()> + Send + 'static,
+    {
+        Self(Arc::new(Mutex::new(f)))
+    }
+
+    pub(crate) fn call(&self, prompter: &mut Prompter<'_>) -> io::Result<()> {
+        let mut f = self.0.lock().unwrap();
+        (*f)(prompter)
+    }
+}
+
+impl fmt::Debug for CustomCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomCommand")
+    }
+}
+
+/// Two commands are equal only if they share the same closure.
+impl PartialEq for CustomCommand {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
@@ -0,0 +1,236 @@
+//! History private to a session, layered over a shared history
+//!
+//! A [`SessionHistory`] shows the entries of the shared history (as of its
+//! creation or last [`import`](SessionHistory::import)) followed by the
+//! entries added during the session. The latter are kept apart until they are
+//! [merged](SessionHistory::merge) into the shared history: saving only writes
+//! the shared entries, so that an experimental session does not end up in the
+//! history file unless asked to.
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//!
+//! use rustyline::history::{History, HistoryStore};
+//! use rustyline::session_history::SessionHistory;
+//! use rustyline::{Config, Editor};
+//!
+//! let shared = Arc::new(Mutex::new(History::new()));
+//! let session = SessionHistory::new(Arc::clone(&shared));
+//! let mut rl = Editor::<(), SessionHistory>::with_history(Config::default(), session);
+//! rl.add_history_entry("rm -rf build");
+//! assert!(shared.lock().unwrap().is_empty());
+//! rl.history_mut().merge();
+//! assert_eq!(1, shared.lock().unwrap().len());
+//! ```
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use super::Result;
+use crate::config::{Config, HistoryDuplicates};
+use crate::history::{EntryMetadata, History, HistoryStore};
+
+/// Entries added during a session over a shared history
+pub struct SessionHistory<S: HistoryStore = History> {
+    shared: Arc<Mutex<S>>,
+    /// Entries (and timestamps) of the shared history as of the last `import`
+    base: Vec<(String, Option<SystemTime>)>,
+    /// Entries added during the session
+    session: History,
+    duplicates: HistoryDuplicates,
+}
+
+impl<S: HistoryStore> SessionHistory<S> {
+    /// Start a session over the `shared` history.
+    pub fn new(shared: Arc<Mutex<S>>) -> Self {
+        let mut history = Self {
+            shared,
+            base: Vec::new(),
+            session: History::new(),
+            duplicates: HistoryDuplicates::default(),
+        };
+        history.import();
+        history
+    }
+
+    /// The history shared by the sessions
+    pub fn shared(&self) -> &Arc<Mutex<S>> {
+        &self.shared
+    }
+
+    /// Return the number of entries added during the session (and not merged
+    /// yet).
+    pub fn session_len(&self) -> usize {
+        self.session.len()
+    }
+
+    /// Refresh the view of the shared history (e.g. with the entries merged
+    /// by other sessions), keeping the entries of the session after them.
+    pub fn import(&mut self) {
+        let shared = self.shared.lock().unwrap();
+        self.base = (0..shared.len())
+            .filter_map(|index| {
+                let entry = shared.entry(index)?.into_owned();
+                Some((entry, shared.timestamp(index)))
+            })
+            .collect();
+    }
+
+    /// Add the entries of the session to the shared history and return how
+    /// many have been added (the shared history may ignore duplicates).
+    pub fn merge(&mut self) -> usize {
+        let mut added = 0;
+        {
+            let mut shared = self.shared.lock().unwrap();
+            for entry in self.session.iter() {
+                if shared.add(entry) {
+                    added += 1;
+                }
+            }
+        }
+        self.session.clear();
+        self.import();
+        added
+    }
+
+    /// Drop the entries added during the session.
+    pub fn discard(&mut self) {
+        self.session.clear();
+    }
+}
+
+impl<S: HistoryStore> HistoryStore for SessionHistory<S> {
+    fn len(&self) -> usize {
+        self.base.len() + self.session.len()
+    }
+
+    fn entry(&self, index: usize) -> Option<Cow<'_, str>> {
+        match self.base.get(index) {
+            Some((entry, _)) => Some(Cow::Borrowed(entry)),
+            None => self.session.get(index - self.base.len()).map(Cow::from),
+        }
+    }
+
+    fn timestamp(&self, index: usize) -> Option<SystemTime> {
+        match self.base.get(index) {
+            Some((_, timestamp)) => *timestamp,
+            None => self.session.timestamp(index - self.base.len()),
+        }
+    }
+
+    /// Metadata of the entries added during the session
+    fn metadata(&self, index: usize) -> Option<Cow<'_, EntryMetadata>> {
+        let index = index.checked_sub(self.base.len())?;
+        self.session.metadata(index).map(Cow::Borrowed)
+    }
+
+    fn set_metadata(&mut self, index: usize, key: &str, value: &str) -> bool {
+        match index.checked_sub(self.base.len()) {
+            Some(index) => HistoryStore::set_metadata(&mut self.session, index, key, value),
+            None => false,
+        }
+    }
+
+    /// Add an entry to the session only.
+    fn add(&mut self, line: &str) -> bool {
+        if self.session.is_empty()
+            && self.duplicates != HistoryDuplicates::AlwaysAdd
+            && self.base.last().is_some_and(|(entry, _)| entry == line)
+        {
+            return false;
+        }
+        self.session.add(line)
+    }
+
+    /// Remove all the entries from the session view: the shared history is
+    /// left untouched.
+    fn clear(&mut self) {
+        self.base.clear();
+        self.session.clear();
+    }
+
+    /// Load the entries persisted at `path` into the shared history.
+    fn load(&mut self, path: &Path) -> Result<()> {
+        self.shared.lock().unwrap().load(path)?;
+        self.import();
+        Ok(())
+    }
+
+    /// Persist the entries of the shared history at `path`: those of the
+    /// session are not, until they are merged.
+    fn save(&mut self, path: &Path) -> Result<()> {
+        self.shared.lock().unwrap().save(path)
+    }
+
+    fn append(&mut self, path: &Path) -> Result<()> {
+        self.shared.lock().unwrap().append(path)
+    }
+
+    fn sync(&mut self, path: &Path) -> Result<()> {
+        self.shared.lock().unwrap().sync(path)?;
+        self.import();
+        Ok(())
+    }
+
+    /// Apply the settings to the entries of the session, the shared history
+    /// being configured by its owner.
+    fn configure(&mut self, config: &Config) {
+        self.duplicates = config.history_duplicates();
+        HistoryStore::configure(&mut self.session, config);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::SessionHistory;
+    use crate::history::{History, HistoryStore};
+
+    fn entries<S: HistoryStore>(history: &S) -> Vec<String> {
+        history.entries().map(|entry| entry.into_owned()).collect()
+    }
+
+    #[test]
+    fn isolation() {
+        let shared = Arc::new(Mutex::new(History::new()));
+        shared.lock().unwrap().add("ls");
+        let mut one = SessionHistory::new(Arc::clone(&shared));
+        let mut two = SessionHistory::new(Arc::clone(&shared));
+        assert!(!one.add("ls")); // consecutive duplicate
+        assert!(one.add("make"));
+        assert!(two.add("cargo build"));
+        assert_eq!(vec!["ls", "make"], entries(&one));
+        assert_eq!(vec!["ls", "cargo build"], entries(&two));
+        assert_eq!(vec!["ls"], entries(&*shared.lock().unwrap()));
+
+        assert_eq!(1, one.merge());
+        assert_eq!(0, one.session_len());
+        assert_eq!(vec!["ls", "make"], entries(&*shared.lock().unwrap()));
+        // other sessions see the merged entries once they import them
+        assert_eq!(vec!["ls", "cargo build"], entries(&two));
+        two.import();
+        assert_eq!(vec!["ls", "make", "cargo build"], entries(&two));
+        two.discard();
+        assert_eq!(vec!["ls", "make"], entries(&two));
+    }
+
+    #[test]
+    fn save() -> crate::Result<()> {
+        let tf = tempfile::NamedTempFile::new()?;
+        let shared = Arc::new(Mutex::new(History::new()));
+        let mut session = SessionHistory::new(Arc::clone(&shared));
+        session.add("rm -rf build");
+        session.save(tf.path())?;
+        let mut history = History::new();
+        history.load(tf.path())?;
+        assert!(history.is_empty());
+
+        session.merge();
+        session.save(tf.path())?;
+        history.load(tf.path())?;
+        assert_eq!(vec!["rm -rf build"], entries(&history));
+        Ok(())
+    }
+}
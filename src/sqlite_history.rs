@@ -0,0 +1,770 @@
+//! History stored in a SQLite database (`history-sqlite` feature)
+//!
+//! Each entry is a row of the `history` table, inserted as soon as the entry is
+//! added: there is nothing to save, and sessions sharing the same database
+//! see each other's entries once they [`sync`](HistoryStore::sync).
+//!
+//! Besides its timestamp, an entry carries a [`CommandInfo`]: the working
+//! directory it was entered in, and (once the command has run) its exit status
+//! and duration. Its [metadata](HistoryStore::metadata) are persisted too, the
+//! working directory being the `cwd` metadata.
+//!
+//! ```no_run
+//! use rustyline::sqlite_history::SqliteHistory;
+//! use rustyline::{Config, Editor};
+//!
+//! let config = Config::builder().max_history_size(usize::MAX).build();
+//! let history = SqliteHistory::open("history.sqlite3")?;
+//! let mut rl = Editor::<(), SqliteHistory>::with_history(config, history);
+//! let line = rl.readline(">> ")?;
+//! rl.add_history_entry(line);
+//! # Ok::<(), rustyline::error::ReadlineError>(())
+//! ```
+//!
+//! `max_history_size`, `history_max_bytes` and `history_eviction` are applied
+//! to the database: rows of the evicted entries are deleted, so a large
+//! `max_history_size` should be configured for a long-lived database.
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use log::warn;
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+
+use super::Result;
+use crate::config::{Config, HistoryDuplicates, HistoryEviction};
+use crate::history::{Direction, EntryMetadata, HistoryStore};
+
+/// Metadata key of the working directory
+const CWD: &str = "cwd";
+
+/// Statements creating the `history` table in the `db` database
+fn schema(db: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS {0}.history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entry TEXT NOT NULL,
+            timestamp INTEGER,
+            cwd TEXT,
+            exit_status INTEGER,
+            duration INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS {0}.history_entry ON history (entry);
+        CREATE TABLE IF NOT EXISTS {0}.history_metadata (
+            entry_id INTEGER NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            PRIMARY KEY (entry_id, key)
+        );",
+        db
+    )
+}
+
+/// Information recorded along with a history entry
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommandInfo {
+    /// Working directory the entry was entered in
+    pub cwd: Option<PathBuf>,
+    /// Exit status of the command
+    pub exit_status: Option<i32>,
+    /// How long the command took to run
+    pub duration: Option<Duration>,
+}
+
+/// History entries stored in a SQLite database
+pub struct SqliteHistory {
+    conn: Connection,
+    /// Database file (`None` when in memory)
+    path: Option<PathBuf>,
+    /// Row ids of the entries, oldest first
+    ids: VecDeque<i64>,
+    /// Row id of the last entry when the entries were last read or appended
+    /// to another database
+    last_id: i64,
+    bytes: usize,
+    max_len: usize,
+    max_bytes: usize,
+    eviction: HistoryEviction,
+    ignore_space: bool,
+    duplicates: HistoryDuplicates,
+}
+
+impl SqliteHistory {
+    /// Open (or create) the database at `path`.
+    pub fn open<P: AsRef<Path> + ?Sized>(path: &P) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open(path)?;
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        Self::with_connection(conn, Some(path))
+    }
+
+    /// Create a database in memory: entries are only persisted with
+    /// [`save`](HistoryStore::save) or [`append`](HistoryStore::append).
+    pub fn in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?, None)
+    }
+
+    fn with_connection(conn: Connection, path: Option<PathBuf>) -> Result<Self> {
+        conn.execute_batch(&schema("main"))?;
+        let config = Config::default();
+        let mut history = Self {
+            conn,
+            path,
+            ids: VecDeque::new(),
+            last_id: 0,
+            bytes: 0,
+            max_len: config.max_history_size(),
+            max_bytes: config.history_max_bytes(),
+            eviction: config.history_eviction(),
+            ignore_space: config.history_ignore_space(),
+            duplicates: config.history_duplicates(),
+        };
+        history.reload()?;
+        Ok(history)
+    }
+
+    /// Read the row ids of all the entries (including the ones added by other
+    /// sessions).
+    fn reload(&mut self) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, length(CAST(entry AS BLOB)) FROM history ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+        self.ids.clear();
+        self.bytes = 0;
+        for row in rows {
+            let (id, len) = row?;
+            self.ids.push_back(id);
+            self.bytes += usize::try_from(len).unwrap_or(0);
+        }
+        drop(stmt);
+        self.last_id = self.ids.back().copied().unwrap_or(0);
+        Ok(())
+    }
+
+    /// Whether `path` is the database file
+    fn is_database(&self, path: &Path) -> bool {
+        match self.path {
+            Some(ref own) => fs::canonicalize(path).is_ok_and(|path| path == *own),
+            None => false,
+        }
+    }
+
+    /// Add a new entry with some `info`: return `true` if it has been added
+    /// (see [`History::add`](crate::history::History::add)).
+    pub fn add_with_info(&mut self, line: &str, info: &CommandInfo) -> Result<bool> {
+        if self.max_len == 0 || line.len() > self.max_bytes {
+            return Ok(false);
+        }
+        if line.is_empty() || (self.ignore_space && line.starts_with(char::is_whitespace)) {
+            return Ok(false);
+        }
+        match self.duplicates {
+            HistoryDuplicates::IgnoreConsecutive => {
+                if self.entry(self.len().wrapping_sub(1)).as_deref() == Some(line) {
+                    return Ok(false);
+                }
+            }
+            HistoryDuplicates::EraseAll => {
+                while let Some(index) = self.find(line)? {
+                    self.remove(index)?;
+                }
+            }
+            HistoryDuplicates::AlwaysAdd => {}
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .ok();
+        self.conn.execute(
+            "INSERT INTO history (entry, timestamp, cwd, exit_status, duration) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                line,
+                timestamp,
+                info.cwd.as_ref().map(|cwd| cwd.to_string_lossy()),
+                info.exit_status,
+                info.duration.map(|d| d.as_millis() as i64),
+            ],
+        )?;
+        self.ids.push_back(self.conn.last_insert_rowid());
+        self.bytes += line.len();
+        self.evict()?;
+        Ok(true)
+    }
+
+    /// Return the information recorded with the entry at position `index`.
+    pub fn command_info(&self, index: usize) -> Result<Option<CommandInfo>> {
+        let id = match self.ids.get(index) {
+            Some(id) => *id,
+            None => return Ok(None),
+        };
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT cwd, exit_status, duration FROM history WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok(CommandInfo {
+                        cwd: row.get::<_, Option<String>>(0)?.map(PathBuf::from),
+                        exit_status: row.get(1)?,
+                        duration: row
+                            .get::<_, Option<i64>>(2)?
+                            .map(|ms| Duration::from_millis(ms as u64)),
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Replace the information recorded with the entry at position `index`
+    /// (e.g. to record the exit status of the last command once it has run).
+    pub fn set_command_info(&mut self, index: usize, info: &CommandInfo) -> Result<()> {
+        if let Some(id) = self.ids.get(index) {
+            self.conn.execute(
+                "UPDATE history SET cwd = ?1, exit_status = ?2, duration = ?3 WHERE id = ?4",
+                params![
+                    info.cwd.as_ref().map(|cwd| cwd.to_string_lossy()),
+                    info.exit_status,
+                    info.duration.map(|d| d.as_millis() as i64),
+                    id
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Index of the most recent entry equal to `line`
+    fn find(&self, line: &str) -> Result<Option<usize>> {
+        let id = self.conn.query_row(
+            "SELECT max(id) FROM history WHERE entry = ?1",
+            params![line],
+            |row| row.get::<_, Option<i64>>(0),
+        )?;
+        Ok(id.and_then(|id| self.ids.binary_search(&id).ok()))
+    }
+
+    /// Drop entries while there are more than `max_len` or they take more than
+    /// `max_bytes`
+    fn evict(&mut self) -> Result<()> {
+        while self.ids.len() > self.max_len || self.bytes > self.max_bytes {
+            let index = match self.eviction {
+                HistoryEviction::DropOldestDuplicateFirst => self.oldest_duplicate()?.unwrap_or(0),
+                _ => 0,
+            };
+            self.remove(index)?;
+        }
+        Ok(())
+    }
+
+    /// Index of the oldest entry which is repeated later
+    fn oldest_duplicate(&self) -> Result<Option<usize>> {
+        let first = match self.ids.front() {
+            Some(id) => *id,
+            None => return Ok(None),
+        };
+        let id = self
+            .conn
+            .query_row(
+                "SELECT h.id FROM history h WHERE h.id >= ?1 AND EXISTS \
+                 (SELECT 1 FROM history g WHERE g.entry = h.entry AND g.id > h.id) \
+                 ORDER BY h.id LIMIT 1",
+                params![first],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()?;
+        Ok(id.and_then(|id| self.ids.binary_search(&id).ok()))
+    }
+
+    /// Delete the entry at position `index`
+    fn remove(&mut self, index: usize) -> Result<()> {
+        if let Some(id) = self.ids.remove(index) {
+            let len = self
+                .conn
+                .query_row(
+                    "SELECT length(CAST(entry AS BLOB)) FROM history WHERE id = ?1",
+                    params![id],
+                    |row| row.get::<_, i64>(0),
+                )
+                .optional()?;
+            self.bytes = self
+                .bytes
+                .saturating_sub(len.map_or(0, |len| usize::try_from(len).unwrap_or(0)));
+            self.conn
+                .execute("DELETE FROM history WHERE id = ?1", params![id])?;
+            self.conn.execute(
+                "DELETE FROM history_metadata WHERE entry_id = ?1",
+                params![id],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Index of the nearest entry matching the `condition` (with `args` as
+    /// `?2`, `?3`...), from `start` in the `dir` direction
+    fn search_match(
+        &self,
+        condition: &str,
+        args: &[&str],
+        start: usize,
+        dir: Direction,
+    ) -> Result<Option<usize>> {
+        if start >= self.len() {
+            return Ok(None);
+        }
+        let sql = match dir {
+            Direction::Reverse => format!(
+                "SELECT id FROM history WHERE id <= ?1 AND {} ORDER BY id DESC LIMIT 1",
+                condition
+            ),
+            Direction::Forward => format!(
+                "SELECT id FROM history WHERE id >= ?1 AND {} ORDER BY id LIMIT 1",
+                condition
+            ),
+        };
+        let mut stmt = self.conn.prepare_cached(&sql)?;
+        let mut id = self.ids[start];
+        loop {
+            let mut values: Vec<&dyn ToSql> = vec![&id];
+            values.extend(args.iter().map(|arg| arg as &dyn ToSql));
+            let found = stmt
+                .query_row(&*values, |row| row.get::<_, i64>(0))
+                .optional()?;
+            match found {
+                // entries added by other sessions are skipped until synced
+                Some(found) => match self.ids.binary_search(&found) {
+                    Ok(index) => return Ok(Some(index)),
+                    Err(_) if dir == Direction::Reverse => id = found - 1,
+                    Err(_) => id = found + 1,
+                },
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Copy the entries (with row id greater than `after`) and their metadata
+    /// of the `from` database into the `to` database
+    fn copy(&self, from: &str, to: &str, after: i64) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut select = tx.prepare(&format!(
+                "SELECT id, entry, timestamp, cwd, exit_status, duration FROM {}.history \
+                 WHERE id > ?1 ORDER BY id",
+                from
+            ))?;
+            let mut insert = tx.prepare(&format!(
+                "INSERT INTO {}.history (entry, timestamp, cwd, exit_status, duration) \
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                to
+            ))?;
+            let mut insert_metadata = tx.prepare(&format!(
+                "INSERT INTO {}.history_metadata (entry_id, key, value) \
+                 SELECT ?1, key, value FROM {}.history_metadata WHERE entry_id = ?2",
+                to, from
+            ))?;
+            let mut rows = select.query(params![after])?;
+            while let Some(row) = rows.next()? {
+                insert.execute(params![
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<i64>>(2)?,
+                    row.get::<_, Option<String>>(3)?,
+                    row.get::<_, Option<i32>>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                ])?;
+                insert_metadata.execute(params![tx.last_insert_rowid(), row.get::<_, i64>(0)?])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Run `f` with the database at `path` attached as `name`
+    fn with_attached<F>(&self, path: &Path, name: &str, f: F) -> Result<()>
+    where
+        F: FnOnce() -> Result<()>,
+    {
+        self.conn.execute(
+            "ATTACH DATABASE ?1 AS ?2",
+            params![path.to_string_lossy(), name],
+        )?;
+        let result = self
+            .conn
+            .execute_batch(&schema(name))
+            .map_err(From::from)
+            .and_then(|_| f());
+        self.conn.execute("DETACH DATABASE ?1", params![name])?;
+        result
+    }
+}
+
+impl HistoryStore for SqliteHistory {
+    fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    fn entry(&self, index: usize) -> Option<Cow<'_, str>> {
+        let id = *self.ids.get(index)?;
+        self.conn
+            .query_row(
+                "SELECT entry FROM history WHERE id = ?1",
+                params![id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .unwrap_or_else(|err| {
+                warn!(target: "rustyline", "cannot read history entry: {}", err);
+                None
+            })
+            .map(Cow::Owned)
+    }
+
+    fn timestamp(&self, index: usize) -> Option<SystemTime> {
+        let id = *self.ids.get(index)?;
+        let secs = self
+            .conn
+            .query_row(
+                "SELECT timestamp FROM history WHERE id = ?1",
+                params![id],
+                |row| row.get::<_, Option<i64>>(0),
+            )
+            .ok()??;
+        Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(secs).ok()?))
+    }
+
+    fn metadata(&self, index: usize) -> Option<Cow<'_, EntryMetadata>> {
+        let id = *self.ids.get(index)?;
+        let read = || -> Result<EntryMetadata> {
+            let mut metadata = EntryMetadata::new();
+            let cwd = self.conn.query_row(
+                "SELECT cwd FROM history WHERE id = ?1",
+                params![id],
+                |row| row.get::<_, Option<String>>(0),
+            )?;
+            if let Some(cwd) = cwd {
+                metadata.insert(CWD.to_owned(), cwd);
+            }
+            let mut stmt = self
+                .conn
+                .prepare_cached("SELECT key, value FROM history_metadata WHERE entry_id = ?1")?;
+            for row in stmt.query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))? {
+                let (key, value) = row?;
+                metadata.insert(key, value);
+            }
+            Ok(metadata)
+        };
+        match read() {
+            Ok(metadata) => Some(Cow::Owned(metadata)),
+            Err(err) => {
+                warn!(target: "rustyline", "cannot read history metadata: {}", err);
+                None
+            }
+        }
+    }
+
+    fn set_metadata(&mut self, index: usize, key: &str, value: &str) -> bool {
+        let id = match self.ids.get(index) {
+            Some(id) => *id,
+            None => return false,
+        };
+        let result = if key == CWD {
+            self.conn.execute(
+                "UPDATE history SET cwd = ?1 WHERE id = ?2",
+                params![value, id],
+            )
+        } else {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO history_metadata (entry_id, key, value) \
+                 VALUES (?1, ?2, ?3)",
+                params![id, key, value],
+            )
+        };
+        result.map(|_| true).unwrap_or_else(|err| {
+            warn!(target: "rustyline", "cannot set history metadata: {}", err);
+            false
+        })
+    }
+
+    fn search_metadata(
+        &self,
+        key: &str,
+        value: &str,
+        start: usize,
+        dir: Direction,
+    ) -> Option<usize> {
+        let result = if key == CWD {
+            self.search_match("cwd = ?2", &[value], start, dir)
+        } else {
+            self.search_match(
+                "EXISTS (SELECT 1 FROM history_metadata m \
+                 WHERE m.entry_id = history.id AND m.key = ?2 AND m.value = ?3)",
+                &[key, value],
+                start,
+                dir,
+            )
+        };
+        result.unwrap_or_else(|err| {
+            warn!(target: "rustyline", "cannot search history: {}", err);
+            None
+        })
+    }
+
+    fn add(&mut self, line: &str) -> bool {
+        let info = CommandInfo {
+            cwd: env::current_dir().ok(),
+            ..CommandInfo::default()
+        };
+        self.add_with_info(line, &info).unwrap_or_else(|err| {
+            warn!(target: "rustyline", "cannot add history entry: {}", err);
+            false
+        })
+    }
+
+    fn clear(&mut self) {
+        let result = match (self.ids.front(), self.ids.back()) {
+            (Some(first), Some(last)) => self.conn.execute_batch(&format!(
+                "DELETE FROM history WHERE id BETWEEN {0} AND {1};
+                 DELETE FROM history_metadata WHERE entry_id BETWEEN {0} AND {1};",
+                first, last
+            )),
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            warn!(target: "rustyline", "cannot clear history: {}", err);
+        }
+        self.ids.clear();
+        self.bytes = 0;
+    }
+
+    fn search(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
+        if term.is_empty() {
+            return None;
+        }
+        self.search_match("instr(entry, ?2) > 0", &[term], start, dir)
+            .unwrap_or_else(|err| {
+                warn!(target: "rustyline", "cannot search history: {}", err);
+                None
+            })
+    }
+
+    fn starts_with(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
+        if term.is_empty() {
+            return None;
+        }
+        self.search_match("substr(entry, 1, length(?2)) = ?2", &[term], start, dir)
+            .unwrap_or_else(|err| {
+                warn!(target: "rustyline", "cannot search history: {}", err);
+                None
+            })
+    }
+
+    /// Read the entries of the database at `path` (of the current database,
+    /// the ones added by other sessions).
+    fn load(&mut self, path: &Path) -> Result<()> {
+        if !self.is_database(path) {
+            self.with_attached(path, "source", || self.copy("source", "main", 0))?;
+        }
+        self.reload()?;
+        self.evict()
+    }
+
+    /// Copy all the entries into the database at `path` (nothing to do for the
+    /// current database).
+    fn save(&mut self, path: &Path) -> Result<()> {
+        if !self.is_database(path) {
+            self.with_attached(path, "target", || {
+                self.conn.execute_batch(
+                    "DELETE FROM target.history; DELETE FROM target.history_metadata;",
+                )?;
+                self.copy("main", "target", 0)
+            })?;
+            self.last_id = self.ids.back().copied().unwrap_or(0);
+        }
+        Ok(())
+    }
+
+    /// Copy the entries added by this session into the database at `path`
+    /// (nothing to do for the current database).
+    fn append(&mut self, path: &Path) -> Result<()> {
+        if !self.is_database(path) {
+            self.with_attached(path, "target", || self.copy("main", "target", self.last_id))?;
+            self.last_id = self.ids.back().copied().unwrap_or(0);
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self, path: &Path) -> Result<()> {
+        if self.is_database(path) {
+            self.reload()?;
+            self.evict()
+        } else {
+            self.append(path)
+        }
+    }
+
+    fn configure(&mut self, config: &Config) {
+        self.max_len = config.max_history_size();
+        self.max_bytes = config.history_max_bytes();
+        self.eviction = config.history_eviction();
+        self.ignore_space = config.history_ignore_space();
+        self.duplicates = config.history_duplicates();
+        if let Err(err) = self.evict() {
+            warn!(target: "rustyline", "cannot evict history entries: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use super::{CommandInfo, SqliteHistory};
+    use crate::config::{Config, HistoryDuplicates};
+    use crate::history::{Direction, HistoryStore};
+    use crate::Result;
+
+    fn init() -> SqliteHistory {
+        let mut history = SqliteHistory::in_memory().unwrap();
+        assert!(history.add("line1"));
+        assert!(history.add("line2"));
+        assert!(history.add("line3"));
+        history
+    }
+
+    fn entries(history: &SqliteHistory) -> Vec<String> {
+        history.entries().map(|entry| entry.into_owned()).collect()
+    }
+
+    #[test]
+    fn add() {
+        let mut history = init();
+        assert!(!history.add("line3"));
+        assert!(!history.add(""));
+        assert_eq!(vec!["line1", "line2", "line3"], entries(&history));
+        assert!(history.timestamp(0).is_some());
+        assert_eq!(
+            std::env::current_dir().ok(),
+            history.command_info(2).unwrap().unwrap().cwd
+        );
+        history.clear();
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn search() {
+        let history = init();
+        assert_eq!(None, history.search("", 0, Direction::Forward));
+        assert_eq!(None, history.search("none", 0, Direction::Forward));
+        assert_eq!(None, history.search("line", 3, Direction::Forward));
+        assert_eq!(Some(0), history.search("line", 0, Direction::Forward));
+        assert_eq!(Some(1), history.search("ne2", 0, Direction::Forward));
+        assert_eq!(Some(2), history.search("line", 2, Direction::Reverse));
+        assert_eq!(Some(1), history.search("e2", 2, Direction::Reverse));
+        assert_eq!(None, history.search("e3", 1, Direction::Reverse));
+        assert_eq!(Some(1), history.starts_with("line2", 2, Direction::Reverse));
+        assert_eq!(None, history.starts_with("ine", 2, Direction::Reverse));
+    }
+
+    #[test]
+    fn command_info() {
+        let mut history = init();
+        let info = CommandInfo {
+            cwd: None,
+            exit_status: Some(1),
+            duration: Some(Duration::from_millis(1500)),
+        };
+        history.set_command_info(1, &info).unwrap();
+        assert_eq!(Some(info), history.command_info(1).unwrap());
+        assert_eq!(None, history.command_info(3).unwrap());
+    }
+
+    #[test]
+    fn metadata() {
+        let mut history = init();
+        assert!(history.set_metadata(0, "cwd", "/tmp"));
+        assert!(history.set_metadata(1, "session", "1"));
+        assert!(history.set_metadata(2, "session", "2"));
+        assert!(history.set_metadata(2, "session", "1"));
+        assert!(!history.set_metadata(3, "session", "1"));
+        let metadata = history.metadata(2).unwrap();
+        assert_eq!(Some("1"), metadata.get("session").map(String::as_str));
+        assert_eq!(
+            Some(0),
+            history.search_metadata("cwd", "/tmp", 2, Direction::Reverse)
+        );
+        assert_eq!(
+            Some(2),
+            history.search_metadata("session", "1", 2, Direction::Reverse)
+        );
+        assert_eq!(
+            None,
+            history.search_metadata("session", "2", 0, Direction::Forward)
+        );
+        history.clear();
+        assert!(history.add("line"));
+        assert_eq!(
+            None,
+            history
+                .metadata(0)
+                .unwrap()
+                .get("session")
+                .map(String::as_str)
+        );
+    }
+
+    #[test]
+    fn configure() {
+        let mut history = init();
+        let config = Config::builder()
+            .max_history_size(2)
+            .history_duplicates(HistoryDuplicates::EraseAll)
+            .build();
+        history.configure(&config);
+        assert_eq!(vec!["line2", "line3"], entries(&history));
+        assert!(history.add("line2"));
+        assert_eq!(vec!["line3", "line2"], entries(&history));
+    }
+
+    #[test]
+    fn save_and_load() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let path = td.path().join("history.sqlite3");
+        let mut history = init();
+        assert!(history.set_metadata(1, "session", "1"));
+        history.save(&path)?;
+        assert!(history.add("line4"));
+        history.append(&path)?;
+        history.append(&path)?;
+
+        let mut history = SqliteHistory::open(&path)?;
+        assert_eq!(vec!["line1", "line2", "line3", "line4"], entries(&history));
+        assert_eq!(
+            Some(1),
+            history.search_metadata("session", "1", 0, Direction::Forward)
+        );
+        history.load(Path::new(&path))?; // already read
+        assert_eq!(4, history.len());
+        Ok(())
+    }
+
+    #[test]
+    fn sync() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let path = td.path().join("history.sqlite3");
+        let mut first = SqliteHistory::open(&path)?;
+        let mut second = SqliteHistory::open(&path)?;
+        assert!(first.add("first"));
+        assert!(second.add("second"));
+        assert_eq!(vec!["first"], entries(&first));
+        // entries of the other session are ignored until synced
+        assert_eq!(None, first.search("second", 0, Direction::Forward));
+        first.sync(&path)?;
+        assert_eq!(vec!["first", "second"], entries(&first));
+        assert_eq!(Some(1), first.search("second", 0, Direction::Forward));
+        Ok(())
+    }
+}
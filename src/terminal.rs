@@ -0,0 +1,74 @@
+//! Terminal backend
+//!
+//! By default, an [`Editor`](crate::Editor) uses the terminal of the platform
+//! (termios on unix, the console API on windows). An application can plug
+//! its own backend instead (crossterm, termion, an embedded serial console,
+//! ...) with [`Editor::set_terminal`](crate::Editor::set_terminal): keys are
+//! read from it and the line is rendered with ANSI escape sequences written
+//! to it.
+use crate::keys::KeyEvent;
+use crate::Result;
+
+/// Terminal I/O of an editor
+///
+/// ```
+/// use rustyline::terminal::Terminal;
+/// use rustyline::{KeyCode, KeyEvent, Modifiers, Result};
+///
+/// /// Replay keys, discard the output
+/// struct Replay(std::vec::IntoIter<KeyEvent>);
+///
+/// impl Terminal for Replay {
+///     fn enable_raw_mode(&mut self) -> Result<()> {
+///         Ok(())
+///     }
+///     fn disable_raw_mode(&mut self) -> Result<()> {
+///         Ok(())
+///     }
+///     fn size(&self) -> (usize, usize) {
+///         (80, 24)
+///     }
+///     fn read_key(&mut self) -> Result<KeyEvent> {
+///         self.0.next().ok_or(rustyline::error::ReadlineError::Eof)
+///     }
+///     fn poll(&mut self, _timeout_ms: i32) -> Result<bool> {
+///         Ok(self.0.len() > 0)
+///     }
+///     fn write(&mut self, _buf: &[u8]) -> Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let mut rl = rustyline::Editor::<()>::new();
+/// let enter = KeyEvent(KeyCode::Enter, Modifiers::NONE);
+/// let keys = vec![KeyEvent::from('l'), KeyEvent::from('s'), enter];
+/// rl.set_terminal(Replay(keys.into_iter()));
+/// assert_eq!("ls", rl.readline("> ")?);
+/// # Ok::<(), rustyline::error::ReadlineError>(())
+/// ```
+pub trait Terminal: Send {
+    /// Enable raw mode: keys are read one at a time, without echo nor line
+    /// editing by the terminal driver.
+    fn enable_raw_mode(&mut self) -> Result<()>;
+    /// Restore the mode saved by
+    /// [`enable_raw_mode`](Terminal::enable_raw_mode).
+    fn disable_raw_mode(&mut self) -> Result<()>;
+    /// Number of columns and rows
+    fn size(&self) -> (usize, usize);
+    /// Check if the size has changed since the last call
+    fn size_changed(&mut self) -> bool {
+        false
+    }
+    /// Blocking read of a key
+    fn read_key(&mut self) -> Result<KeyEvent>;
+    /// Wait at most `timeout_ms` (indefinitely if negative) for a key and
+    /// tell if there is one.
+    fn poll(&mut self, timeout_ms: i32) -> Result<bool>;
+    /// Write `buf` (text and ANSI escape sequences) and flush it.
+    fn write(&mut self, buf: &[u8]) -> Result<()>;
+    /// Check if text attributes (colors, reverse video, ...) are supported.
+    /// Only consulted with [`ColorMode::Enabled`](crate::ColorMode::Enabled).
+    fn colors_enabled(&self) -> bool {
+        true
+    }
+}
@@ -279,6 +279,29 @@ fn ctrl_v() {
     }
 }
 
+#[cfg(unix)]
+#[test]
+fn ctrl_v_control_char() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_line(
+            *mode,
+            &[E::ctrl('V'), E::ctrl('A'), E::ctrl('V'), E::ESC, E::ENTER],
+            "\x01\x1b",
+        );
+        assert_line(*mode, &[E::ctrl('V'), E::ENTER, E::ENTER], "\r");
+    }
+    // displayed in caret notation
+    let mut editor = init_editor(EditMode::Emacs, &[E::ctrl('V'), E::ctrl('A'), E::ENTER]);
+    assert_eq!("\x01", editor.readline("").unwrap());
+    assert_eq!(2, editor.term.cursor);
+    // with a repeat count
+    assert_line(
+        EditMode::Emacs,
+        &[E::alt('3'), E::ctrl('V'), E::from('x'), E::ENTER],
+        "xxx",
+    );
+}
+
 #[test]
 fn ctrl_w() {
     for mode in &[EditMode::Emacs, EditMode::Vi] {
@@ -338,3 +361,86 @@ fn ctrl__() {
         }
     }
 }
+
+#[test]
+fn shift_selection() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        // typing replaces the selection
+        assert_cursor(
+            *mode,
+            ("Hello, world", ""),
+            &[
+                E(K::Left, M::SHIFT),
+                E(K::Left, M::SHIFT),
+                E::from('!'),
+                E::ENTER,
+            ],
+            ("Hello, wor!", ""),
+        );
+        // backspace deletes it
+        assert_cursor(
+            *mode,
+            ("Hello", ", world"),
+            &[E(K::End, M::SHIFT), E::BACKSPACE, E::ENTER],
+            ("Hello", ""),
+        );
+        // a move without shift ends it
+        assert_cursor(
+            *mode,
+            ("Hello", ", world"),
+            &[
+                E(K::Home, M::SHIFT),
+                E(K::Right, M::NONE),
+                E::from('x'),
+                E::ENTER,
+            ],
+            ("Hx", "ello, world"),
+        );
+    }
+    // cut and paste
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hello", ", world"),
+        &[
+            E(K::Right, M::CTRL_SHIFT),
+            E(K::Delete, M::SHIFT),
+            E(K::Home, M::NONE),
+            E::ctrl('Y'),
+            E::ENTER,
+        ],
+        (", world", "Hello"),
+    );
+}
+
+#[test]
+fn selection_clipboard() {
+    use crate::config::Config;
+    use crate::Editor;
+    // the clipboard failure is ignored
+    let config = Config::builder().selection_clipboard(true).build();
+    let mut editor = Editor::<()>::with_config(config);
+    editor.term.keys = vec![
+        E::from('a'),
+        E::from('b'),
+        E(K::Home, M::SHIFT),
+        E(K::Delete, M::SHIFT),
+        E::from('c'),
+        E::ctrl('Y'),
+        E::ENTER,
+    ];
+    assert_eq!("cab", editor.readline(">>").unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_z() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        // the line and the cursor are restored once resumed
+        assert_cursor(
+            *mode,
+            ("Hel", "lo"),
+            &[E::ctrl('Z'), E::from('p'), E::ENTER],
+            ("Help", "lo"),
+        );
+    }
+}
@@ -1,5 +1,5 @@
 //! Emacs specific key bindings
-use super::{assert_cursor, assert_history};
+use super::{assert_cursor, assert_history, assert_line_with_initial};
 use crate::config::EditMode;
 use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
 
@@ -123,6 +123,36 @@ fn backspace() {
     );
 }
 
+#[test]
+fn overwrite_mode() {
+    let insert = E(K::Insert, M::NONE);
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "Hi"),
+        &[insert, E::from('a'), E::from('b'), E::from('c'), E::ENTER],
+        ("abc", ""),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("H", "ello"),
+        &[insert, E::from('a'), E::BACKSPACE, E::BACKSPACE, E::ENTER],
+        ("", "  llo"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hi", ""),
+        &[insert, E::BACKSPACE, E::ENTER],
+        ("H", ""),
+    );
+    // toggled back to insert mode
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "Hi"),
+        &[insert, E::from('a'), insert, E::from('b'), E::ENTER],
+        ("ab", "i"),
+    );
+}
+
 #[test]
 fn ctrl_k() {
     assert_cursor(
@@ -226,12 +256,19 @@ fn ctrl_p() {
 
 #[test]
 fn ctrl_t() {
-    /* FIXME
     assert_cursor(
+        EditMode::Emacs,
         ("ab", "cd"),
         &[E::alt('2'), E::ctrl('T'), E::ENTER],
         ("acdb", ""),
-    );*/
+    );
+    // negative arguments have no effect
+    assert_cursor(
+        EditMode::Emacs,
+        ("ab", "cd"),
+        &[E::alt('-'), E::ctrl('T'), E::ENTER],
+        ("ab", "cd"),
+    );
 }
 
 #[test]
@@ -302,12 +339,24 @@ fn meta_c() {
         &[E::alt('C'), E::ENTER],
         ("Hi", ""),
     );
-    /* FIXME
     assert_cursor(
+        EditMode::Emacs,
         ("", "hi test"),
         &[E::alt('2'), E::alt('C'), E::ENTER],
         ("Hi Test", ""),
-    );*/
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "ﬁne ǳ"),
+        &[E::alt('2'), E::alt('C'), E::ENTER],
+        ("Fine ǲ", ""),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("hi", " test"),
+        &[E::alt('-'), E::alt('C'), E::ENTER],
+        ("hi", " test"),
+    );
 }
 
 #[test]
@@ -324,12 +373,12 @@ fn meta_l() {
         &[E::alt('L'), E::ENTER],
         ("hi", ""),
     );
-    /* FIXME
     assert_cursor(
+        EditMode::Emacs,
         ("", "HI TEST"),
         &[E::alt('2'), E::alt('L'), E::ENTER],
         ("hi test", ""),
-    );*/
+    );
 }
 
 #[test]
@@ -346,12 +395,18 @@ fn meta_u() {
         &[E::alt('U'), E::ENTER],
         ("HI", ""),
     );
-    /* FIXME
     assert_cursor(
+        EditMode::Emacs,
         ("", "hi test"),
         &[E::alt('2'), E::alt('U'), E::ENTER],
         ("HI TEST", ""),
-    );*/
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "straße"),
+        &[E::alt('U'), E::ENTER],
+        ("STRASSE", ""),
+    );
 }
 
 #[test]
@@ -378,6 +433,19 @@ fn meta_t() {
         &[E::alt('T'), E::ENTER],
         ("world, Hello", "!"),
     );
+    // at the end of the line
+    assert_cursor(
+        EditMode::Emacs,
+        ("One Two Three", ""),
+        &[E::alt('T'), E::ENTER],
+        ("One Three Two", ""),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("One", " Two Three"),
+        &[E::alt('2'), E::alt('T'), E::ENTER],
+        ("Three Two One", ""),
+    );
     /* FIXME
     assert_cursor(
         ("One Two", " Three Four"),
@@ -386,6 +454,34 @@ fn meta_t() {
     );*/
 }
 
+#[test]
+fn meta_hash() {
+    assert_line_with_initial(EditMode::Emacs, ("ls", " -l"), &[E::alt('#')], "#ls -l");
+    // with an argument, toggle the comment
+    assert_line_with_initial(
+        EditMode::Emacs,
+        ("#ls", ""),
+        &[E::alt('1'), E::alt('#')],
+        "ls",
+    );
+    assert_line_with_initial(
+        EditMode::Emacs,
+        ("ls", ""),
+        &[E::alt('1'), E::alt('#')],
+        "#ls",
+    );
+}
+
+#[test]
+fn meta_tab() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("a", "b"),
+        &[E(K::Tab, M::ALT), E::ENTER],
+        ("a\t", "b"),
+    );
+}
+
 #[test]
 fn meta_y() {
     assert_cursor(
@@ -422,3 +518,203 @@ fn meta_digit() {
         ("hhh", ""),
     );
 }
+
+#[test]
+fn meta_digit_ctrl_d() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("a", "bcde"),
+        &[E::alt('3'), E::ctrl('D'), E::ENTER],
+        ("a", "e"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("abc", "d"),
+        &[E::alt('-'), E::alt('2'), E::ctrl('D'), E::ENTER],
+        ("a", "d"),
+    );
+}
+
+#[test]
+fn universal_argument() {
+    use super::init_editor;
+    use crate::Cmd;
+    let readline = |keys: &[E]| {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.bind_sequence(E::ctrl('U'), Cmd::UniversalArgument);
+        editor.readline(">>").unwrap()
+    };
+    let u = E::ctrl('U');
+    assert_eq!("ffff", readline(&[u, E::from('f'), E::ENTER]));
+    assert_eq!("f".repeat(16), readline(&[u, u, E::from('f'), E::ENTER]));
+    assert_eq!(
+        "f".repeat(12),
+        readline(&[u, E::from('1'), E::from('2'), E::from('f'), E::ENTER])
+    );
+    // digits replace the default argument
+    assert_eq!("--", readline(&[u, E::from('2'), E::from('-'), E::ENTER]));
+    assert_eq!(
+        "ab",
+        readline(&[
+            E::from('a'),
+            E::from('b'),
+            E::from('c'),
+            u,
+            E::from('-'),
+            E::ctrl('D'),
+            E::ENTER
+        ])
+    );
+}
+
+#[test]
+fn character_search() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "abcabc"),
+        &[E::ctrl(']'), E::from('c'), E::ENTER],
+        ("ab", "cabc"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("", "abcabc"),
+        &[E::alt('2'), E::ctrl(']'), E::from('c'), E::ENTER],
+        ("abcab", "c"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("abcabc", ""),
+        &[E(K::Char(']'), M::CTRL_ALT), E::from('a'), E::ENTER],
+        ("abc", "abc"),
+    );
+    // negative argument: backward
+    assert_cursor(
+        EditMode::Emacs,
+        ("abcabc", ""),
+        &[E::alt('-'), E::ctrl(']'), E::from('b'), E::ENTER],
+        ("abca", "bc"),
+    );
+    // not a character: the key is handled as usual
+    assert_cursor(
+        EditMode::Emacs,
+        ("ab", "c"),
+        &[E::ctrl(']'), E::ctrl('A'), E::ENTER],
+        ("", "abc"),
+    );
+}
+
+#[test]
+fn word_boundary() {
+    use super::init_editor;
+    use crate::config::{Configurer, WordBoundary};
+    let keys = [E::alt('2'), E::alt('b'), E::alt('d'), E::ENTER];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.set_word_boundary(WordBoundary::Whitespace);
+    assert_eq!(
+        "x  baz",
+        editor
+            .readline_with_initial(">>", ("x foo.bar::baz baz", ""))
+            .unwrap()
+    );
+    // default
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!(
+        "x foo.bar:: baz",
+        editor
+            .readline_with_initial(">>", ("x foo.bar::baz baz", ""))
+            .unwrap()
+    );
+}
+
+#[test]
+fn mark_and_region() {
+    // C-@ M-b M-w C-e C-y
+    assert_cursor(
+        EditMode::Emacs,
+        ("hello world", ""),
+        &[
+            E::ctrl(' '),
+            E::alt('b'),
+            E::alt('w'),
+            E::ctrl('E'),
+            E::ctrl('Y'),
+            E::ENTER,
+        ],
+        ("hello worldworld", ""),
+    );
+    // C-x C-x
+    assert_cursor(
+        EditMode::Emacs,
+        ("hello world", ""),
+        &[
+            E::ctrl(' '),
+            E::ctrl('A'),
+            E::ctrl('X'),
+            E::ctrl('X'),
+            E::ENTER,
+        ],
+        ("hello world", ""),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("hello", " world"),
+        &[
+            E::ctrl(' '),
+            E::ctrl('E'),
+            E::ctrl('X'),
+            E::ctrl('X'),
+            E::ENTER,
+        ],
+        ("hello", " world"),
+    );
+}
+
+#[test]
+fn kbd_macro() {
+    use super::init_editor;
+    use crate::Macro;
+    let c_x = E::ctrl('X');
+    let keys = [
+        c_x,
+        E::from('('),
+        E::from('a'),
+        E::from('b'),
+        c_x,
+        E::from(')'),
+        c_x,
+        E::from('e'),
+        E::alt('2'),
+        c_x,
+        E::from('e'),
+        E::ENTER,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!("abababab", editor.readline(">>").unwrap());
+    let kbd_macro = Macro::new(vec![E::from('a'), E::from('b')]);
+    assert_eq!(Some(kbd_macro), editor.last_macro());
+
+    let mut editor = init_editor(EditMode::Emacs, &[c_x, E::from('e'), E::ENTER]);
+    editor.set_last_macro(Macro::from_sequence("\\C-als "));
+    assert_eq!(
+        "ls x",
+        editor.readline_with_initial(">>", ("x", "")).unwrap()
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn edit_and_execute_command() {
+    use super::init_editor;
+    use std::{env, fs};
+    let src = env::temp_dir().join("rustyline-edit-and-execute-command.txt");
+    fs::write(&src, "ls -l\n").unwrap();
+    env::set_var("VISUAL", format!("cp {}", src.display()));
+    let keys = [E::ctrl('X'), E::ctrl('E')];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!(
+        "ls -l",
+        editor.readline_with_initial(">>", ("ls", "")).unwrap()
+    );
+    env::remove_var("VISUAL");
+    fs::remove_file(src).unwrap();
+}
@@ -1,5 +1,5 @@
 //! History related commands tests
-use super::assert_history;
+use super::{assert_history, init_editor};
 use crate::config::EditMode;
 use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
 
@@ -146,13 +146,13 @@ fn ctrl_r() {
             ("car", "go"),
         );
         assert_history(
-            EditMode::Emacs,
+            *mode,
             &["rustc", "cargo"],
             &[
                 E::from('a'),
                 E::ctrl('R'),
                 E::from('r'),
-                E::ctrl('G'), // abort (FIXME: doesn't work with vi mode)
+                E::ctrl('G'), // abort
                 E::ENTER,
             ],
             "",
@@ -161,6 +161,74 @@ fn ctrl_r() {
     }
 }
 
+#[test]
+fn ctrl_r_same_entry() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        // older occurrence in the same entry first
+        assert_history(
+            *mode,
+            &["cargo", "git log; git status"],
+            &[
+                E::ctrl('R'),
+                E::from('g'),
+                E::from('i'),
+                E::ctrl('R'),
+                E(K::Right, M::NONE), // just to assert cursor pos
+                E::ENTER,
+            ],
+            "",
+            ("g", "it log; git status"),
+        );
+        // Backspace searches again with the shorter query
+        assert_history(
+            *mode,
+            &["rustc", "cargo"],
+            &[
+                E::ctrl('R'),
+                E::from('r'),
+                E::from('u'),
+                E::BACKSPACE,
+                E(K::Right, M::NONE), // just to assert cursor pos
+                E::ENTER,
+            ],
+            "",
+            ("r", "ustc"),
+        );
+    }
+}
+
+#[test]
+fn ctrl_r_edit() {
+    let mut editor = init_editor(
+        EditMode::Emacs,
+        &[
+            E::from('x'),
+            E::ctrl('R'),
+            E::from('r'),
+            E::from('u'),
+            E(K::End, M::NONE),
+            E::from('!'),
+            E::ENTER,
+        ],
+    );
+    editor.add_history_entry("rustc");
+    editor.add_history_entry("cargo");
+    assert_eq!("rustc!", editor.readline("").unwrap());
+    // the found entry becomes the current one
+    editor.term.keys = vec![
+        E::ctrl('R'),
+        E::from('c'),
+        E::from('a'),
+        E(K::Down, M::NONE),
+        E::ENTER,
+    ];
+    assert_eq!("", editor.readline("").unwrap());
+    // an empty query reuses the last one
+    editor.term.keys = vec![E::ctrl('R'), E::ctrl('R'), E(K::Right, M::NONE), E::ENTER];
+    assert_eq!("cargo", editor.readline("").unwrap());
+    assert_eq!(1, editor.term.cursor);
+}
+
 #[test]
 fn ctrl_r_with_long_prompt() {
     for mode in &[EditMode::Emacs, EditMode::Vi] {
@@ -194,6 +262,56 @@ fn ctrl_s() {
     }
 }
 
+#[test]
+fn vi_search() {
+    let history = ["rustc", "cargo", "rustdoc"];
+    let search = |pattern: &str| {
+        let mut keys = vec![E::ESC, E::from('/')];
+        keys.extend(pattern.chars().map(E::from));
+        keys.push(E::ENTER);
+        keys
+    };
+    // `/` searches backward
+    let mut keys = search("ru");
+    keys.push(E::ENTER);
+    assert_history(EditMode::Vi, &history, &keys, "", ("", "rustdoc"));
+    // `n` repeats the search, `N` in the opposite direction
+    let mut keys = search("ru");
+    keys.extend(&[E::from('n'), E::ENTER]);
+    assert_history(EditMode::Vi, &history, &keys, "", ("", "rustc"));
+    let mut keys = search("ru");
+    keys.extend(&[E::from('n'), E::from('N'), E::ENTER]);
+    assert_history(EditMode::Vi, &history, &keys, "", ("", "rustdoc"));
+    // `?` searches forward
+    let mut keys = search("c");
+    keys.extend(&[E::from('2'), E::from('n'), E::from('?')]);
+    keys.extend(&[E::from('d'), E::ENTER, E::ENTER]);
+    assert_history(EditMode::Vi, &history, &keys, "", ("", "rustdoc"));
+    // anchored pattern
+    let mut keys = search("^c");
+    keys.push(E::ENTER);
+    assert_history(EditMode::Vi, &history, &keys, "", ("", "cargo"));
+    // an empty pattern reuses the last one
+    let mut keys = search("ru");
+    keys.extend(&[E::from('/'), E::ENTER, E::ENTER]);
+    assert_history(EditMode::Vi, &history, &keys, "", ("", "rustc"));
+    // Escape cancels the search
+    assert_history(
+        EditMode::Vi,
+        &history,
+        &[
+            E::from('a'),
+            E::ESC,
+            E::from('/'),
+            E::from('r'),
+            E::ESC,
+            E::ENTER,
+        ],
+        "",
+        ("", "a"),
+    );
+}
+
 #[test]
 fn meta_lt() {
     assert_history(
@@ -241,3 +359,478 @@ fn meta_gt() {
         ("a", ""),
     );
 }
+
+#[test]
+fn operate_and_get_next() {
+    let mut editor = super::init_editor(EditMode::Emacs, &[]);
+    for entry in &["one", "two", "three"] {
+        editor.history.add(*entry);
+    }
+    editor.term.keys = vec![E(K::Up, M::NONE), E(K::Up, M::NONE), E::ctrl('O')];
+    assert_eq!("two", editor.readline(">>").unwrap());
+    editor.history.add("two");
+    editor.term.keys = vec![E::ctrl('O')];
+    assert_eq!("three", editor.readline(">>").unwrap());
+    editor.history.add("three");
+    editor.term.keys = vec![E::ENTER];
+    assert_eq!("two", editor.readline(">>").unwrap());
+    // the line just added follows the last entry
+    editor.term.keys = vec![E(K::Up, M::NONE), E::ctrl('O')];
+    assert_eq!("three", editor.readline(">>").unwrap());
+    editor.history.add("three");
+    editor.term.keys = vec![E::ENTER];
+    assert_eq!("three", editor.readline(">>").unwrap());
+    // nothing to get after a new line
+    editor.term.keys = vec![E::from('a'), E::ctrl('O')];
+    assert_eq!("a", editor.readline(">>").unwrap());
+    editor.term.keys = vec![E::ENTER];
+    assert_eq!("", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn operate_and_get_next_with_eviction() {
+    use crate::config::Config;
+    use crate::Editor;
+
+    let config = Config::builder().max_history_size(3).build();
+    let mut editor = Editor::<()>::with_config(config);
+    for entry in &["one", "two", "three"] {
+        editor.history.add(*entry);
+    }
+    editor.term.keys = vec![E(K::Up, M::NONE), E(K::Up, M::NONE), E::ctrl('O')];
+    assert_eq!("two", editor.readline(">>").unwrap());
+    editor.history.add("two"); // "one" is evicted
+    editor.term.keys = vec![E(K::Up, M::NONE), E::ENTER];
+    assert_eq!("two", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn yank_last_arg() {
+    let entries = &["vim src/lib.rs", "cargo test --all"];
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::from('x'), E::alt('.'), E::ENTER],
+        "",
+        ("xall", ""),
+    );
+    // older entries when repeated
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[
+            E::alt('.'),
+            E::alt('.'),
+            E::from(' '),
+            E::alt('.'),
+            E::ENTER,
+        ],
+        "",
+        ("rs all", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('.'), E::alt('.'), E::alt('.'), E::ENTER],
+        "",
+        ("rs", ""),
+    );
+    // with an argument
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('1'), E::alt('.'), E::alt('.'), E::ENTER],
+        "",
+        ("src", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('-'), E::alt('2'), E::alt('_'), E::ENTER],
+        "",
+        ("test", ""),
+    );
+    assert_history(EditMode::Emacs, &[], &[E::alt('.'), E::ENTER], "", ("", ""));
+}
+
+#[test]
+fn dabbrev_expand() {
+    // the words of the line (the nearest first), then the ones of the history
+    // (the newest first), then the original word
+    let expansions = ["carrot", "carry", "cargo", "cat", "ca"];
+    for (n, expansion) in expansions.iter().enumerate() {
+        let mut keys = vec![E::alt('/'); n + 1];
+        keys.push(E::ENTER);
+        let mut editor = init_editor(EditMode::Emacs, &keys);
+        editor.history.add("cargo test --all");
+        editor.history.add("cat cargo.toml");
+        let line = editor
+            .readline_with_initial("", ("carrot ca", " carry"))
+            .unwrap();
+        assert_eq!(format!("carrot {} carry", expansion), line);
+        assert_eq!(7 + expansion.len(), editor.term.cursor);
+    }
+    // no word before the cursor
+    assert_history(
+        EditMode::Emacs,
+        &["cat"],
+        &[E::alt('/'), E::ENTER],
+        "",
+        ("", ""),
+    );
+}
+
+#[test]
+fn yank_nth_arg() {
+    use crate::config::{Configurer, WordBoundary};
+
+    let entries = &["vim src/lib.rs", "cargo test --all"];
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E(K::Char('Y'), M::CTRL_ALT), E::ENTER],
+        "",
+        ("test", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        entries,
+        &[E::alt('2'), E(K::Char('Y'), M::CTRL_ALT), E::ENTER],
+        "",
+        ("all", ""),
+    );
+    // words are split according to `word_boundary`
+    let mut editor = init_editor(
+        EditMode::Emacs,
+        &[
+            E(K::Char('Y'), M::CTRL_ALT),
+            E::from(' '),
+            E::alt('.'),
+            E::ENTER,
+        ],
+    );
+    editor.set_word_boundary(WordBoundary::Whitespace);
+    editor.history.add("vim src/lib.rs");
+    assert_eq!("src/lib.rs src/lib.rs", editor.readline("").unwrap());
+}
+
+#[test]
+fn history_expansion() {
+    use crate::config::Config;
+    use crate::error::ReadlineError;
+    use crate::expansion::ExpansionError;
+    use crate::Editor;
+
+    let config = Config::builder()
+        .history_expansion(true)
+        .auto_add_history(true)
+        .build();
+    let mut editor = Editor::<()>::with_config(config);
+    editor.history.add("cargo build");
+    editor.term.keys = "echo !$"
+        .chars()
+        .map(E::from)
+        .chain(Some(E::ENTER))
+        .collect();
+    assert_eq!("echo build", editor.readline(">>").unwrap());
+    // the expanded line is added to the history
+    editor.term.keys = "^echo^ls"
+        .chars()
+        .map(E::from)
+        .chain(Some(E::ENTER))
+        .collect();
+    assert_eq!("ls build", editor.readline(">>").unwrap());
+    editor.term.keys = "!rustc"
+        .chars()
+        .map(E::from)
+        .chain(Some(E::ENTER))
+        .collect();
+    match editor.readline(">>") {
+        Err(ReadlineError::HistoryExpansion(ExpansionError::EventNotFound(event))) => {
+            assert_eq!("!rustc", event)
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn history_expand_line() {
+    let mut keys: Vec<E> = "echo !$ x".chars().map(E::from).collect();
+    keys.extend_from_slice(&[
+        E(K::Left, M::NONE),
+        E(K::Left, M::NONE),
+        E::alt('^'),
+        E::ENTER,
+    ]);
+    assert_history(
+        EditMode::Emacs,
+        &["cargo build"],
+        &keys,
+        "",
+        ("echo build", " x"),
+    );
+    // unknown event
+    assert_history(
+        EditMode::Emacs,
+        &["cargo build"],
+        &[E::from('!'), E::from('x'), E::alt('^'), E::ENTER],
+        "",
+        ("!x", ""),
+    );
+}
+
+#[test]
+fn magic_space() {
+    use crate::config::Config;
+    use crate::keymap::Cmd;
+    use crate::Editor;
+
+    let config = Config::builder().history_expansion(true).build();
+    let mut editor = Editor::<()>::with_config(config);
+    editor.history.add("cargo build");
+    editor.bind_sequence(E::from(' '), Cmd::MagicSpace);
+    editor.term.keys = "!!&&".chars().map(E::from).collect();
+    editor.term.keys.insert(2, E::from(' '));
+    editor.term.keys.push(E::ENTER);
+    assert_eq!("cargo build &&", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn history_filter() {
+    use crate::config::Configurer;
+
+    let mut editor = init_editor(EditMode::Emacs, &[]);
+    editor.set_auto_add_history(true);
+    editor.add_history_filter(|line: &str| !line.contains("password"));
+    editor.add_history_filter(|line: &str| !line.starts_with("token"));
+    for line in &["ls", "mysql --password=secret", "token abc", "make"] {
+        editor.term.keys = line.chars().map(E::from).chain(Some(E::ENTER)).collect();
+        assert_eq!(*line, editor.readline(">>").unwrap());
+    }
+    assert_eq!(
+        vec!["ls", "make"],
+        editor.history.iter().collect::<Vec<_>>()
+    );
+    editor.clear_history_filters();
+    assert!(editor.add_history_entry("token abc"));
+}
+
+#[test]
+fn history_edits() {
+    let mut editor = init_editor(EditMode::Emacs, &[]);
+    editor.history.add("line1");
+    editor.history.add("line2");
+    // edits are kept while browsing the history
+    editor.term.keys = vec![
+        E(K::Up, M::NONE),
+        E::from('x'),
+        E(K::Up, M::NONE),
+        E(K::Down, M::NONE),
+        E::ENTER,
+    ];
+    assert_eq!("line2x", editor.readline(">>").unwrap());
+    // but the history is left untouched
+    assert_eq!(
+        vec!["line1", "line2"],
+        editor.history.iter().collect::<Vec<_>>()
+    );
+    editor.term.keys = vec![E(K::Up, M::NONE), E::ENTER];
+    assert_eq!("line2", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn revert_line() {
+    let keys = [
+        E(K::Up, M::NONE),
+        E::from('x'),
+        E(K::Up, M::NONE),
+        E(K::Down, M::NONE),
+        E::alt('r'),
+        E::ENTER,
+    ];
+    assert_history(
+        EditMode::Emacs,
+        &["line1", "line2"],
+        &keys,
+        "",
+        ("line2", ""),
+    );
+    assert_history(
+        EditMode::Emacs,
+        &["line1"],
+        &[
+            E::from('a'),
+            E::from('b'),
+            E(K::Left, M::NONE),
+            E::alt('r'),
+            E::ENTER,
+        ],
+        "",
+        ("", ""),
+    );
+    assert_history(
+        EditMode::Vi,
+        &["line1"],
+        &[E::ESC, E::from('k'), E::from('x'), E::from('U'), E::ENTER],
+        "",
+        ("line1", ""),
+    );
+}
+
+#[test]
+fn custom_store() {
+    use std::borrow::Cow;
+    use std::path::Path;
+
+    use crate::config::{Config, Configurer};
+    use crate::history::HistoryStore;
+    use crate::keymap::Cmd;
+    use crate::{Editor, Result};
+
+    /// Keeps every entry, upper-cased
+    #[derive(Default)]
+    struct Shouting(Vec<String>);
+
+    impl HistoryStore for Shouting {
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn entry(&self, index: usize) -> Option<Cow<'_, str>> {
+            self.0.get(index).map(|entry| Cow::Borrowed(entry.as_str()))
+        }
+
+        fn add(&mut self, line: &str) -> bool {
+            self.0.push(line.to_uppercase());
+            true
+        }
+
+        fn clear(&mut self) {
+            self.0.clear()
+        }
+
+        fn load(&mut self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+
+        fn save(&mut self, _path: &Path) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut editor = Editor::<(), Shouting>::with_history(Config::default(), Shouting::default());
+    editor.set_auto_add_history(true);
+    editor.add_history_entry("line1");
+    editor.term.keys = vec![E::from('a'), E::ENTER];
+    assert_eq!("a", editor.readline(">>").unwrap());
+    assert_eq!(vec!["LINE1", "A"], editor.history().0);
+    // navigation and searches go through the store
+    editor.term.keys = vec![E(K::Up, M::NONE), E(K::Up, M::NONE), E::ENTER];
+    assert_eq!("LINE1", editor.readline(">>").unwrap());
+    editor.term.keys = vec![E::ctrl('R'), E::from('N'), E::ENTER];
+    assert_eq!("LINE1", editor.readline(">>").unwrap());
+    editor.bind_sequence(E::alt('p'), Cmd::HistorySearchBackward);
+    editor.term.keys = vec![E::from('L'), E::alt('p'), E::ENTER];
+    assert_eq!("LINE1", editor.readline(">>").unwrap());
+    assert_eq!(
+        vec!["LINE1", "A", "LINE1", "LINE1", "LINE1"],
+        editor.history().entries().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn history_search_prefix() {
+    use super::init_editor;
+    use crate::config::HistoryDuplicates;
+    use crate::keymap::Cmd;
+
+    let up = E(K::Up, M::NONE);
+    let down = E(K::Down, M::NONE);
+    for (keys, line, cursor) in &[
+        // the text before the cursor is kept, duplicates are skipped
+        (vec![E::from('l'), up, E::ENTER], "ls /home", 1),
+        (vec![E::from('l'), up, up, E::ENTER], "ls /tmp", 1),
+        // no older match
+        (vec![E::from('l'), up, up, up, E::ENTER], "ls /tmp", 1),
+        (vec![E::from('l'), up, up, down, E::ENTER], "ls /home", 1),
+        // past the last match
+        (vec![E::from('l'), up, down, E::ENTER], "l", 1),
+        (vec![E::from('x'), up, E::ENTER], "x", 1),
+        // without prefix, like previous-history
+        (vec![up, up, E::ENTER], "cd /src", 0),
+    ] {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.bind_sequence(up, Cmd::HistorySearchBackward);
+        editor.bind_sequence(down, Cmd::HistorySearchForward);
+        editor.history.duplicates = HistoryDuplicates::AlwaysAdd;
+        for entry in &["ls /tmp", "cd /src", "ls /home", "ls /home"] {
+            editor.history.add_with_timestamp(*entry, None);
+        }
+        assert_eq!(*line, editor.readline("").unwrap());
+        assert_eq!(*cursor, editor.term.cursor);
+    }
+}
+
+#[test]
+fn history_search_mode() {
+    use crate::config::{Config, HistorySearchMode};
+    use crate::Editor;
+
+    let up = E(K::Up, M::NONE);
+    let down = E(K::Down, M::NONE);
+    for (mode, keys, line) in &[
+        (
+            HistorySearchMode::Substring,
+            vec![E::from('t'), up],
+            "cargo test",
+        ),
+        // the line as typed is searched while cycling
+        (
+            HistorySearchMode::Substring,
+            vec![E::from('t'), up, up],
+            "git status",
+        ),
+        // no older match
+        (
+            HistorySearchMode::Substring,
+            vec![E::from('t'), up, up, up],
+            "git status",
+        ),
+        (
+            HistorySearchMode::Substring,
+            vec![E::from('t'), up, up, down],
+            "cargo test",
+        ),
+        (
+            HistorySearchMode::Substring,
+            vec![E::from('t'), up, down],
+            "t",
+        ),
+        (HistorySearchMode::Substring, vec![up, up], "cargo test"),
+        (
+            HistorySearchMode::Prefix,
+            vec![E::from('c'), up, up],
+            "cargo build",
+        ),
+        (
+            HistorySearchMode::Navigate,
+            vec![E::from('c'), up, up],
+            "cargo test",
+        ),
+    ] {
+        let config = Config::builder().history_search_mode(*mode).build();
+        let mut editor = Editor::<()>::with_config(config);
+        for entry in &["cargo build", "git status", "cargo test", "ls"] {
+            editor.history.add(*entry);
+        }
+        editor.term.keys = keys.clone();
+        editor.term.keys.push(E::ENTER);
+        assert_eq!(
+            *line,
+            editor.readline(">>").unwrap(),
+            "{:?} {:?}",
+            mode,
+            keys
+        );
+    }
+}
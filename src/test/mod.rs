@@ -1,9 +1,8 @@
 use std::sync::{Arc, RwLock};
 use std::vec::IntoIter;
 
-use radix_trie::Trie;
-
-use crate::completion::Completer;
+use crate::binding::Bindings;
+use crate::completion::{Completer, Completion};
 use crate::config::{Config, EditMode};
 use crate::edit::init_state;
 use crate::highlight::Highlighter;
@@ -12,7 +11,7 @@ use crate::keymap::{Cmd, InputState};
 use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
 use crate::tty::Sink;
 use crate::validate::Validator;
-use crate::{Context, Editor, Helper, Result};
+use crate::{Context, Editor, Event, Helper, Result};
 
 mod common;
 mod emacs;
@@ -59,7 +58,7 @@ fn complete_line() {
     let helper = Some(SimpleCompleter);
     let mut s = init_state(&mut out, "rus", 3, helper.as_ref(), &history);
     let config = Config::default();
-    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(Trie::new())));
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(Bindings::default())));
     let keys = vec![E::ENTER];
     let mut rdr: IntoIter<KeyEvent> = keys.into_iter();
     let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &Config::default()).unwrap();
@@ -73,6 +72,514 @@ fn complete_line() {
     assert_eq!(4, s.line.pos());
 }
 
+#[test]
+fn conditional_completion() {
+    use crate::{ConditionalEventHandler, EventContext, EventHandler, RepeatCount};
+
+    struct TabHandler;
+    impl ConditionalEventHandler for TabHandler {
+        fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+            if ctx.is_completing() {
+                Some(Cmd::Insert(1, "!".to_owned()))
+            } else {
+                None
+            }
+        }
+    }
+
+    let mut editor = Editor::<SimpleCompleter>::new();
+    editor.set_helper(Some(SimpleCompleter));
+    editor.term.keys.extend(
+        [E(K::Tab, M::NONE), E(K::Tab, M::NONE), E::ENTER]
+            .iter()
+            .cloned(),
+    );
+    editor.bind_sequence(
+        E(K::Tab, M::NONE),
+        EventHandler::Conditional(Box::new(TabHandler)),
+    );
+    assert_eq!(
+        "rust!",
+        editor.readline_with_initial(">>", ("rus", "")).unwrap()
+    );
+}
+
+#[test]
+fn last_command() {
+    use crate::{ConditionalEventHandler, EventContext, EventHandler, RepeatCount};
+
+    /// Insert the name of the previous command
+    struct LastCommand;
+    impl ConditionalEventHandler for LastCommand {
+        fn handle(&self, _: &Event, _: RepeatCount, _: bool, ctx: &EventContext) -> Option<Cmd> {
+            let text = match ctx.last_command() {
+                Some(Cmd::SelfInsert(..)) => "[insert]",
+                Some(Cmd::Kill(..)) => "[kill]",
+                Some(_) => "[other]",
+                None => "[none]",
+            };
+            assert_eq!(ctx.last_command(), ctx.command_history().next());
+            assert_eq!(0, ctx.pending_keys().count());
+            Some(Cmd::Insert(1, text.to_owned()))
+        }
+    }
+
+    let keys = [
+        E::ctrl('X'),
+        E::from('a'),
+        E::ctrl('W'),
+        E::ctrl('X'),
+        E::ENTER,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(
+        E::ctrl('X'),
+        EventHandler::Conditional(Box::new(LastCommand)),
+    );
+    assert_eq!("[kill]", editor.readline(">>").unwrap());
+}
+
+struct AmbiguousCompleter;
+impl Completer for AmbiguousCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        _line: &str,
+        _pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<String>)> {
+        Ok((0, vec!["rustc".to_owned(), "rustup".to_owned()]))
+    }
+}
+impl Hinter for AmbiguousCompleter {
+    type Hint = String;
+}
+
+impl Helper for AmbiguousCompleter {}
+impl Highlighter for AmbiguousCompleter {}
+impl Validator for AmbiguousCompleter {}
+
+#[test]
+fn menu_complete_display_prefix() {
+    let mut out = Sink::new();
+    let history = crate::history::History::new();
+    let helper = Some(AmbiguousCompleter);
+    let mut s = init_state(&mut out, "ru", 2, helper.as_ref(), &history);
+    let config = Config::builder().menu_complete_display_prefix(true).build();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(Bindings::default())));
+    let keys = vec![E::from('\t'), E::from('\t'), E::ENTER];
+    let mut rdr: IntoIter<KeyEvent> = keys.into_iter();
+    let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+    assert_eq!(
+        Some(Cmd::AcceptOrInsertLine {
+            accept_in_the_middle: true
+        }),
+        cmd
+    );
+    // prefix, then "rustc", then "rustup"
+    assert_eq!("rustup", s.line.as_str());
+}
+
+/// Completer whose candidates are ready after `polls` polls (never if
+/// `None`)
+struct AsyncCompleter {
+    polls: Option<usize>,
+}
+
+struct Slow(Option<usize>);
+
+impl std::future::Future for Slow {
+    type Output = Result<(usize, Vec<String>)>;
+
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        match self.0 {
+            Some(0) => std::task::Poll::Ready(Ok((0, vec!["rustc".to_owned()]))),
+            Some(ref mut polls) => {
+                *polls -= 1;
+                std::task::Poll::Pending
+            }
+            None => std::task::Poll::Pending,
+        }
+    }
+}
+
+impl Completer for AsyncCompleter {
+    type Candidate = String;
+
+    fn complete_async(
+        &self,
+        _line: &str,
+        _pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Option<crate::completion::CompletionFuture<String>> {
+        Some(Box::pin(Slow(self.polls)))
+    }
+}
+impl Hinter for AsyncCompleter {
+    type Hint = String;
+}
+
+impl Helper for AsyncCompleter {}
+impl Highlighter for AsyncCompleter {}
+impl Validator for AsyncCompleter {}
+
+#[test]
+fn async_completion() {
+    let mut out = Sink::new();
+    let history = crate::history::History::new();
+    let helper = Some(AsyncCompleter { polls: Some(3) });
+    let mut s = init_state(&mut out, "ru", 2, helper.as_ref(), &history);
+    let config = Config::builder()
+        .completion_type(crate::config::CompletionType::List)
+        .build();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(Bindings::default())));
+    let mut rdr: IntoIter<KeyEvent> = Vec::new().into_iter();
+    let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+    assert_eq!(None, cmd);
+    assert_eq!("rustc", s.line.as_str());
+
+    // cancelled by typing
+    let mut editor = Editor::<AsyncCompleter>::new();
+    editor.set_helper(Some(AsyncCompleter { polls: None }));
+    editor.term.keys = vec![E::from('r'), E(K::Tab, M::NONE), E::from('s'), E::ENTER];
+    assert_eq!("rs", editor.readline(">>").unwrap());
+}
+
+/// Reader with only input ignored by `next_key` (release of a key, focus,
+/// ...)
+struct NonKeyInput {
+    polls: usize,
+}
+
+impl crate::tty::RawReader for NonKeyInput {
+    fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
+        unreachable!("no key pressed")
+    }
+
+    fn wait_for_input(&mut self, _: i32) -> Result<bool> {
+        self.polls += 1;
+        Ok(false)
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        unimplemented!()
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        unimplemented!()
+    }
+}
+
+#[test]
+fn async_completion_not_cancelled_by_non_key_input() {
+    let mut out = Sink::new();
+    let history = crate::history::History::new();
+    let helper = Some(AsyncCompleter { polls: Some(3) });
+    let mut s = init_state(&mut out, "ru", 2, helper.as_ref(), &history);
+    let config = Config::default();
+    let mut rdr = NonKeyInput { polls: 0 };
+    let (start, candidates) = super::complete(&mut rdr, &mut s, &config).unwrap().unwrap();
+    assert_eq!(0, start);
+    assert_eq!(vec!["rustc".to_owned()], candidates);
+    assert_eq!(3, rdr.polls);
+}
+
+#[test]
+fn menu_complete() {
+    for &(show_menu, backward) in &[(false, false), (true, false), (true, true)] {
+        let config = Config::builder()
+            .completion_type(crate::config::CompletionType::List)
+            .completion_show_menu(show_menu)
+            .build();
+        let mut editor = Editor::<AmbiguousCompleter>::with_config(config);
+        editor.set_helper(Some(AmbiguousCompleter));
+        editor.bind_sequence(E(K::Tab, M::NONE), Cmd::MenuComplete);
+        editor.bind_sequence(E(K::BackTab, M::NONE), Cmd::MenuCompleteBackward);
+        let first = if backward { K::BackTab } else { K::Tab };
+        editor.term.keys = vec![E(first, M::NONE), E(K::Tab, M::NONE), E::ENTER];
+        let expected = if backward { "" } else { "rustup" };
+        assert_eq!(expected, editor.readline(">>").unwrap());
+    }
+}
+
+#[test]
+fn possible_completions() {
+    let mut editor = Editor::<AmbiguousCompleter>::new();
+    editor.set_helper(Some(AmbiguousCompleter));
+    // listed without changing the line
+    editor.term.keys = vec![E::from('r'), E::from('u'), E::alt('?'), E::ENTER];
+    assert_eq!("ru", editor.readline(">>").unwrap());
+    // all inserted
+    editor.term.keys = vec![E::from('r'), E::from('u'), E::alt('*'), E::ENTER];
+    assert_eq!("rustc rustup ", editor.readline(">>").unwrap());
+    // `C-d` lists at the end of the line and deletes elsewhere
+    editor.term.keys = vec![E::from('r'), E::from('u'), E::ctrl('D'), E::ENTER];
+    assert_eq!("ru", editor.readline(">>").unwrap());
+    editor.term.keys = vec![
+        E::from('r'),
+        E::from('u'),
+        E(K::Left, M::NONE),
+        E::ctrl('D'),
+        E::ENTER,
+    ];
+    assert_eq!("r", editor.readline(">>").unwrap());
+}
+
+struct SpellingCompleter;
+impl Completer for SpellingCompleter {
+    type Candidate = String;
+
+    fn vocabulary(&self, _line: &str, _pos: usize, _ctx: &Context<'_>) -> Result<Vec<String>> {
+        Ok(vec!["cargo".to_owned(), "rustc".to_owned()])
+    }
+}
+impl Hinter for SpellingCompleter {
+    type Hint = String;
+}
+impl Helper for SpellingCompleter {}
+impl Highlighter for SpellingCompleter {}
+impl Validator for SpellingCompleter {}
+
+#[test]
+fn corrections() {
+    for &(distance, expected) in &[(0, "cagro"), (1, "cargo")] {
+        let config = Config::builder()
+            .completion_type(crate::config::CompletionType::List)
+            .completion_correction_distance(distance)
+            .build();
+        let mut editor = Editor::<SpellingCompleter>::with_config(config);
+        editor.set_helper(Some(SpellingCompleter));
+        editor.term.keys = vec![E(K::Tab, M::NONE), E::ENTER];
+        assert_eq!(
+            expected,
+            editor.readline_with_initial(">>", ("cagro", "")).unwrap()
+        );
+    }
+}
+
+#[test]
+fn completion_menu() {
+    let candidates = ["a", "bb", "ccc", "dddd", "e"];
+    // 2 columns of 3 rows, on 2 rows at most, scrolled to the selection
+    assert_eq!(
+        "\na     dddd\nbb    e",
+        super::completion_menu(&candidates, 0, 12, 4, false, None)
+    );
+    assert_eq!(
+        "\nbb    e\n\x1b[7mccc\x1b[0m",
+        super::completion_menu(&candidates, 2, 12, 4, false, Some(&()))
+    );
+    // sorted horizontally
+    assert_eq!(
+        "\na     bb\nccc   dddd",
+        super::completion_menu(&candidates, 0, 12, 4, true, None)
+    );
+    assert_eq!(
+        "\nccc   dddd\ne",
+        super::completion_menu(&candidates, 4, 12, 4, true, None)
+    );
+    // one per row
+    assert_eq!(
+        "\nbb\nccc",
+        super::completion_menu(&candidates, 2, 0, 4, false, None)
+    );
+    // East Asian wide characters take two columns
+    let candidates = ["日本", "a", "b"];
+    assert_eq!(
+        "\n日本  b\na",
+        super::completion_menu(&candidates, 0, 12, 4, false, None)
+    );
+    // a header above each group
+    let candidates: Vec<Completion> = [("a", "files"), ("bb", "files"), ("c", "branches")]
+        .iter()
+        .map(|(name, group)| Completion {
+            group: Some((*group).to_owned()),
+            ..Completion::new(*name)
+        })
+        .collect();
+    assert_eq!(
+        "\nfiles\na   bb\nbranches\nc",
+        super::completion_menu(&candidates, 0, 12, 8, false, None)
+    );
+    assert_eq!(
+        "\na   bb\n\x1b[1mbranches\x1b[22m\n\x1b[7mc\x1b[0m",
+        super::completion_menu(&candidates, 2, 12, 6, false, Some(&()))
+    );
+}
+
+#[test]
+fn show_all_if_ambiguous() {
+    let mut out = Sink::new();
+    let history = crate::history::History::new();
+    let helper = Some(AmbiguousCompleter);
+    let mut s = init_state(&mut out, "ru", 2, helper.as_ref(), &history);
+    let config = Config::builder()
+        .completion_type(crate::config::CompletionType::List)
+        .completion_show_all_if_ambiguous(true)
+        .build();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(Bindings::default())));
+    let keys = vec![E::ENTER];
+    let mut rdr: IntoIter<KeyEvent> = keys.into_iter();
+    let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+    // the list is shown without waiting for a second tab
+    assert_eq!(None, cmd);
+    assert_eq!("rust", s.line.as_str());
+}
+
+#[test]
+fn show_all_if_unmodified() {
+    let mut out = Sink::new();
+    let history = crate::history::History::new();
+    let helper = Some(AmbiguousCompleter);
+    let config = Config::builder()
+        .completion_type(crate::config::CompletionType::List)
+        .completion_show_all_if_unmodified(true)
+        .build();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(Bindings::default())));
+    // the common prefix is inserted: a second tab is needed
+    let mut s = init_state(&mut out, "ru", 2, helper.as_ref(), &history);
+    let mut rdr: IntoIter<KeyEvent> = vec![E::ENTER].into_iter();
+    let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+    assert_eq!(
+        Some(Cmd::AcceptOrInsertLine {
+            accept_in_the_middle: true
+        }),
+        cmd
+    );
+    assert_eq!("rust", s.line.as_str());
+    // nothing to insert: the list is shown
+    let mut s = init_state(&mut out, "rust", 4, helper.as_ref(), &history);
+    let mut rdr: IntoIter<KeyEvent> = vec![E::ENTER].into_iter();
+    let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+    assert_eq!(None, cmd);
+    assert_eq!(1, rdr.len());
+}
+
+/// More candidates than rows
+struct ManyCompleter;
+impl Completer for ManyCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        _line: &str,
+        _pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<String>)> {
+        let long = "x".repeat(50); // one column
+        Ok((
+            0,
+            (0..30).map(|i| format!("rust{:02}{}", i, long)).collect(),
+        ))
+    }
+}
+impl Hinter for ManyCompleter {
+    type Hint = String;
+}
+
+impl Helper for ManyCompleter {}
+impl Highlighter for ManyCompleter {}
+impl Validator for ManyCompleter {}
+
+#[test]
+fn page_completions() {
+    let mut out = Sink::new();
+    let history = crate::history::History::new();
+    let helper = Some(ManyCompleter);
+    let mut s = init_state(&mut out, "ru", 2, helper.as_ref(), &history);
+    let config = Config::builder()
+        .completion_type(crate::config::CompletionType::List)
+        .completion_show_all_if_ambiguous(true)
+        .build();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(Bindings::default())));
+    // tab at the `--More--` prompt switches to menu completion
+    let keys = vec![E(K::Tab, M::NONE), E(K::Tab, M::NONE), E::ENTER];
+    let mut rdr: IntoIter<KeyEvent> = keys.into_iter();
+    let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+    assert_eq!(
+        Some(Cmd::AcceptOrInsertLine {
+            accept_in_the_middle: true
+        }),
+        cmd
+    );
+    assert!(s.line.as_str().starts_with("rust01"));
+
+    // without paging, the whole list is shown at once
+    let mut s = init_state(&mut out, "ru", 2, helper.as_ref(), &history);
+    let config = Config::builder()
+        .completion_type(crate::config::CompletionType::List)
+        .completion_show_all_if_ambiguous(true)
+        .page_completions(false)
+        .build();
+    let keys = vec![E::ENTER];
+    let mut rdr: IntoIter<KeyEvent> = keys.into_iter();
+    let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+    assert_eq!(None, cmd);
+    assert_eq!(1, rdr.len());
+}
+
+/// Cargo subcommands with descriptions
+struct DescribedCompleter;
+impl Completer for DescribedCompleter {
+    type Candidate = Completion;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> Result<(usize, Vec<Completion>)> {
+        let start = line[..pos].rfind(' ').map_or(0, |i| i + 1);
+        let candidates = [("build", "Compile"), ("bench", "Run the benchmarks")]
+            .iter()
+            .filter(|(name, _)| name.starts_with(&line[start..pos]))
+            .map(|(name, description)| Completion {
+                display: format!("{} (cmd)", name),
+                description: Some((*description).to_owned()),
+                suffix: Some(" ".to_owned()),
+                ..Completion::new(*name)
+            })
+            .collect();
+        Ok((start, candidates))
+    }
+}
+impl Hinter for DescribedCompleter {
+    type Hint = String;
+}
+
+impl Helper for DescribedCompleter {}
+impl Highlighter for DescribedCompleter {}
+impl Validator for DescribedCompleter {}
+
+#[test]
+fn completion_description_and_suffix() {
+    let mut out = Sink::new();
+    let history = crate::history::History::new();
+    let helper = Some(DescribedCompleter);
+    let config = Config::builder()
+        .completion_type(crate::config::CompletionType::List)
+        .completion_show_all_if_ambiguous(true)
+        .build();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(Bindings::default())));
+    // the suffix is inserted after a unique candidate
+    let mut s = init_state(&mut out, "cargo bu", 8, helper.as_ref(), &history);
+    let mut rdr: IntoIter<KeyEvent> = vec![].into_iter();
+    let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+    assert_eq!(None, cmd);
+    assert_eq!("cargo build ", s.line.as_str());
+    // but not after the common prefix of ambiguous ones, which are listed
+    // with their descriptions
+    let mut s = init_state(&mut out, "cargo ", 6, helper.as_ref(), &history);
+    let cmd = super::complete_line(&mut rdr, &mut s, &mut input_state, &config).unwrap();
+    assert_eq!(None, cmd);
+    assert_eq!("cargo b", s.line.as_str());
+}
+
 // `keys`: keys to press
 // `expected_line`: line after enter key
 fn assert_line(mode: EditMode, keys: &[KeyEvent], expected_line: &str) {
@@ -133,6 +640,702 @@ fn unknown_esc_key() {
     }
 }
 
+#[test]
+fn keymap_bindings() {
+    use crate::{Keymap, Movement};
+    let keys = [E::ctrl('A'), E::ESC, E::ctrl('A'), E::ENTER];
+    let mut editor = init_editor(EditMode::Vi, &keys);
+    editor.bind_sequence_in(
+        Keymap::ViInsert,
+        E::ctrl('A'),
+        Cmd::Move(Movement::EndOfLine),
+    );
+    editor.bind_sequence_in(
+        Keymap::ViCommand,
+        E::ctrl('A'),
+        Cmd::Move(Movement::BackwardChar(1)),
+    );
+    editor.bind_sequence_in(
+        Keymap::Emacs,
+        E::ctrl('A'),
+        Cmd::Move(Movement::BeginningOfLine),
+    );
+    let line = editor.readline_with_initial("", ("", "bcd")).unwrap();
+    assert_eq!("bcd", line);
+    // end of line, then back to the last char, then one char backward
+    assert_eq!(1, editor.term.cursor);
+}
+
+#[test]
+fn key_chords() {
+    use crate::{Keymap, Movement};
+    let readline = |mode, keys: &[KeyEvent]| {
+        let mut editor = init_editor(mode, keys);
+        editor.bind_sequence_in(
+            Keymap::ViCommand,
+            Event::KeySeq(vec![E::from('g'), E::from('g')].into()),
+            Cmd::Move(Movement::BeginningOfLine),
+        );
+        editor.bind_sequence_in(
+            Keymap::Emacs,
+            Event::KeySeq(vec![E::ctrl('A'), E::ctrl('B')].into()),
+            Cmd::Insert(1, "!".to_owned()),
+        );
+        editor.readline_with_initial("", ("abc", "")).unwrap()
+    };
+    let g = E::from('g');
+    let keys = [E::ESC, g, g, E::from('i'), E::from('X'), E::ENTER];
+    assert_eq!("Xabc", readline(EditMode::Vi, &keys));
+    // `g` alone is not bound and `x` is handled next
+    let keys = [E::ESC, g, E::from('x'), E::ENTER];
+    assert_eq!("ab", readline(EditMode::Vi, &keys));
+    // `C-a` is also bound by default
+    let keys = [E::ctrl('A'), E::ctrl('B'), E::ENTER];
+    assert_eq!("abc!", readline(EditMode::Emacs, &keys));
+    let keys = [E::ctrl('A'), E::from('x'), E::ENTER];
+    assert_eq!("xabc", readline(EditMode::Emacs, &keys));
+}
+
+#[test]
+fn pending_prefix_prompt() {
+    use std::borrow::Cow;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct PrefixHelper(Mutex<Vec<Vec<KeyEvent>>>);
+    impl Completer for PrefixHelper {
+        type Candidate = String;
+    }
+    impl Hinter for PrefixHelper {
+        type Hint = String;
+    }
+    impl Highlighter for PrefixHelper {
+        fn pending_prefix_prompt<'p>(&self, prompt: &'p str, prefix: &[KeyEvent]) -> Cow<'p, str> {
+            self.0.lock().unwrap().push(prefix.to_vec());
+            Cow::Owned(format!("{}(pending) ", prompt))
+        }
+    }
+    impl Validator for PrefixHelper {}
+    impl Helper for PrefixHelper {}
+
+    // no more input while `C-x` is pending
+    let mut editor = Editor::<PrefixHelper>::new();
+    editor.term.keys.push(E::ctrl('X'));
+    editor.set_helper(Some(PrefixHelper::default()));
+    editor.bind_sequence(
+        Event::KeySeq(vec![E::ctrl('X'), E::ctrl('S')].into()),
+        Cmd::AcceptLine,
+    );
+    assert!(editor.readline(">>").is_err());
+    let helper = editor.helper().unwrap();
+    assert_eq!(vec![vec![E::ctrl('X')]], *helper.0.lock().unwrap());
+}
+
+#[test]
+fn mode_prompt() {
+    use crate::keymap::{InputMode, ModeState};
+    use std::borrow::Cow;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct ModeHelper(Mutex<Vec<(String, ModeState)>>);
+    impl Completer for ModeHelper {
+        type Candidate = String;
+    }
+    impl Hinter for ModeHelper {
+        type Hint = String;
+    }
+    impl Highlighter for ModeHelper {
+        fn mode_prompt<'p>(&self, prompt: &'p str, mode: &ModeState) -> Cow<'p, str> {
+            self.0.lock().unwrap().push((prompt.to_owned(), *mode));
+            Cow::Borrowed(prompt)
+        }
+    }
+    impl Validator for ModeHelper {}
+    impl Helper for ModeHelper {}
+
+    let config = Config::builder()
+        .edit_mode(EditMode::Vi)
+        .show_mode_in_prompt(true)
+        .build();
+    let mut editor = Editor::<ModeHelper>::with_config(config);
+    editor.term.keys.extend(
+        [
+            E::ESC,
+            E::from('d'),
+            E::from('2'),
+            E::from('w'),
+            E::from('i'),
+            E::ENTER,
+        ]
+        .iter()
+        .cloned(),
+    );
+    editor.set_helper(Some(ModeHelper::default()));
+    let line = editor.readline_with_initial(">> ", ("a b c", "")).unwrap();
+    assert_eq!("a b ", line);
+    let helper = editor.helper().unwrap();
+    let modes = helper.0.lock().unwrap();
+    let modes: Vec<_> = modes
+        .iter()
+        .map(|(prompt, mode)| (prompt.as_str(), mode.input_mode, mode.operator, mode.count))
+        .collect();
+    assert_eq!(
+        vec![
+            ("(ins)>> ", InputMode::Insert, None, None),
+            ("(cmd)>> ", InputMode::Command, None, None),
+            ("(cmd)>> ", InputMode::Command, Some('d'), None),
+            ("(cmd)>> ", InputMode::Command, Some('d'), Some(2)),
+            ("(cmd)>> ", InputMode::Command, None, None),
+            ("(ins)>> ", InputMode::Insert, None, None),
+        ],
+        modes
+    );
+}
+
+#[test]
+fn custom_mode() {
+    use crate::{CustomMode, EventContext, Keymap, Movement};
+
+    /// Letters are commands until `i`, `dd` kills the line
+    #[derive(Default)]
+    struct Modal {
+        inserting: bool,
+        operator: bool,
+    }
+    impl CustomMode for Modal {
+        fn reset(&mut self) {
+            self.inserting = true;
+            self.operator = false;
+        }
+
+        fn handle(&mut self, key: KeyEvent, _: &EventContext) -> Option<Cmd> {
+            Some(match key {
+                E::ENTER => Cmd::AcceptLine,
+                E::ESC => {
+                    self.inserting = false;
+                    Cmd::Noop
+                }
+                E(K::Char(c), M::NONE) if self.inserting => Cmd::SelfInsert(1, c),
+                E(K::Char('d'), M::NONE) if !self.operator => {
+                    self.operator = true;
+                    return None;
+                }
+                E(K::Char('d'), M::NONE) => {
+                    self.operator = false;
+                    Cmd::Kill(Movement::WholeLine)
+                }
+                E(K::Char('e'), M::NONE) => Cmd::EmacsEditingMode,
+                E(K::Char('h'), M::NONE) => Cmd::Move(Movement::BackwardChar(1)),
+                E(K::Char('i'), M::NONE) => {
+                    self.inserting = true;
+                    Cmd::Noop
+                }
+                _ => Cmd::Unknown,
+            })
+        }
+    }
+
+    let readline = |keys: &[KeyEvent]| {
+        let mut editor = init_editor(EditMode::Custom("modal"), keys);
+        editor.register_mode("modal", Modal::default());
+        editor.bind_sequence_in(
+            Keymap::Custom("modal"),
+            Event::from(E::ctrl('A')),
+            Cmd::Move(Movement::BeginningOfLine),
+        );
+        editor.bind_sequence_in(
+            Keymap::Emacs,
+            Event::from(E::ctrl('X')),
+            Cmd::SetEditMode(EditMode::Custom("modal")),
+        );
+        let line = editor.readline(">>").unwrap();
+        (line, editor.config.edit_mode())
+    };
+    let modal = EditMode::Custom("modal");
+    let keys = [
+        E::from('a'),
+        E::from('b'),
+        E::ESC,
+        E::from('h'),
+        E::from('i'),
+    ];
+    let mut line = keys.to_vec();
+    line.extend(&[E::from('X'), E::ENTER]);
+    assert_eq!(("aXb".to_owned(), modal), readline(&line));
+    // custom bindings
+    let mut line = keys.to_vec();
+    line.extend(&[E::ctrl('A'), E::from('X'), E::ENTER]);
+    assert_eq!(("Xab".to_owned(), modal), readline(&line));
+    // multi-keys command
+    let mut line = keys.to_vec();
+    line.extend(&[E::ESC, E::from('d'), E::from('d'), E::ENTER]);
+    assert_eq!(("".to_owned(), modal), readline(&line));
+    // switching modes
+    let keys = [
+        E::ESC,
+        E::from('e'),
+        E::from('a'),
+        E::ctrl('X'),
+        E::from('b'),
+        E::ENTER,
+    ];
+    assert_eq!(("ab".to_owned(), modal), readline(&keys));
+    // without such mode, the emacs keymap is used
+    let mut editor = init_editor(modal, &[E::from('a'), E::ctrl('A'), E::from('b'), E::ENTER]);
+    assert_eq!("ba", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn accept_hint_word() {
+    use crate::hint::HistoryHinter;
+
+    struct HintHelper(HistoryHinter);
+    impl Completer for HintHelper {
+        type Candidate = String;
+    }
+    impl Hinter for HintHelper {
+        type Hint = String;
+
+        fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+            self.0.hint(line, pos, ctx)
+        }
+    }
+    impl Highlighter for HintHelper {}
+    impl Validator for HintHelper {}
+    impl Helper for HintHelper {}
+
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        let config = Config::builder().edit_mode(*mode).build();
+        let mut editor = Editor::<HintHelper>::with_config(config);
+        editor.set_helper(Some(HintHelper(HistoryHinter {})));
+        editor.add_history_entry("cargo build --release");
+        editor.term.keys = vec![
+            E::from('c'),
+            E(K::Right, M::CTRL),
+            E(K::Right, M::CTRL),
+            E::ENTER,
+        ];
+        assert_eq!("cargo build", editor.readline(">>").unwrap());
+        editor.term.keys = vec![E::from('c'), E(K::Right, M::NONE), E::ENTER];
+        assert_eq!("cargo build --release", editor.readline(">>").unwrap());
+    }
+}
+
+#[test]
+fn binding_introspection() {
+    use crate::{Binding, EventHandler, Keymap};
+    let mut editor = Editor::<()>::new();
+    editor.bind_sequence(E::ctrl('X'), Cmd::Noop);
+    editor.bind_sequence_in(Keymap::ViInsert, E::ctrl('X'), Cmd::Abort);
+    editor.bind_sequence(
+        E::alt('x'),
+        EventHandler::Macro(vec![Cmd::Insert(1, "x".to_owned())]),
+    );
+    assert_eq!(
+        Some(Binding::Command(Cmd::Abort)),
+        editor.lookup(Keymap::ViInsert, E::ctrl('X'))
+    );
+    assert_eq!(
+        Some(Binding::Command(Cmd::Noop)),
+        editor.lookup(Keymap::Emacs, E(K::Char('x'), M::CTRL))
+    );
+    assert_eq!(None, editor.lookup(Keymap::Emacs, E::ctrl('Y')));
+    let bindings: Vec<_> = editor.bindings(Keymap::ViInsert).collect();
+    assert_eq!(2, bindings.len());
+    assert!(bindings.contains(&(
+        Event::from(E::alt('x')),
+        Binding::Macro(vec![Cmd::Insert(1, "x".to_owned())])
+    )));
+
+    assert!(editor
+        .unbind_sequence_in(Keymap::ViInsert, E::ctrl('X'))
+        .is_some());
+    assert_eq!(
+        Some(Binding::Command(Cmd::Noop)),
+        editor.lookup(Keymap::ViInsert, E::ctrl('X'))
+    );
+}
+
+#[test]
+fn binding_layers() {
+    use crate::{Keymap, Layer};
+    let insert = |text: &str| Cmd::Insert(1, text.to_owned());
+    let readline = |order: &[Layer], app: bool| {
+        let mut editor = init_editor(EditMode::Emacs, &[E::ctrl('T'), E::ctrl('H'), E::ENTER]);
+        editor.bind_sequence_in_layer(Layer::User, None, E::ctrl('T'), insert("user"));
+        editor.bind_sequence_in_layer(
+            Layer::Defaults,
+            Some(Keymap::Emacs),
+            E::ctrl('T'),
+            insert("default"),
+        );
+        if app {
+            editor.bind_sequence(E::ctrl('T'), insert("app"));
+        }
+        editor.set_layer_order(order);
+        editor.readline(">>").unwrap()
+    };
+    let all = [Layer::Application, Layer::User, Layer::Defaults];
+    assert_eq!("ap", readline(&all, true));
+    // the user binding is not replaced
+    assert_eq!("use", readline(&all, false));
+    assert_eq!(
+        "use",
+        readline(&[Layer::User, Layer::Application, Layer::Defaults], true)
+    );
+    assert_eq!("defaul", readline(&[Layer::Defaults], true));
+
+    // built-in bindings disabled: C-H and Enter are ignored
+    let mut editor = init_editor(
+        EditMode::Emacs,
+        &[E::from('a'), E::ctrl('H'), E::from('b'), E::ctrl('J')],
+    );
+    editor.bind_sequence(E::ctrl('J'), Cmd::AcceptLine);
+    editor.set_layer_order(&[Layer::Application, Layer::User]);
+    assert_eq!(vec![Layer::Application, Layer::User], editor.layer_order());
+    assert_eq!("ab", editor.readline(">>").unwrap());
+
+    let mut editor = init_editor(EditMode::Emacs, &[]);
+    editor.bind_sequence_in_layer(Layer::User, None, E::ctrl('T'), insert("user"));
+    editor.clear_layer(Layer::User);
+    assert_eq!(None, editor.lookup(Keymap::Emacs, E::ctrl('T')));
+}
+
+#[test]
+fn custom_command() {
+    let mut editor = init_editor(
+        EditMode::Emacs,
+        &[E::ctrl('T'), E::ctrl('T'), E::from('!'), E::ENTER],
+    );
+    let mut count = 0;
+    editor.bind_sequence(
+        E::ctrl('T'),
+        Cmd::custom(move |p| {
+            count += 1;
+            let pos = p.line().pos();
+            p.line_mut().insert_str(pos, &count.to_string());
+            p.line_mut().set_pos(pos + 1);
+            Ok(())
+        }),
+    );
+    assert_eq!("12!", editor.readline(">>").unwrap());
+
+    // accept the line
+    let mut editor = init_editor(EditMode::Emacs, &[E::from('a'), E::ctrl('O')]);
+    editor.bind_sequence(
+        E::ctrl('O'),
+        Cmd::custom(|p| {
+            p.print("accepted");
+            p.accept();
+            Ok(())
+        }),
+    );
+    assert_eq!("a", editor.readline(">>").unwrap());
+
+    // changes are undone at once
+    let mut editor = init_editor(EditMode::Emacs, &[E::ctrl('T'), E::ctrl('_'), E::ENTER]);
+    editor.bind_sequence(
+        E::ctrl('T'),
+        Cmd::custom(|p| {
+            p.line_mut().update("a", 1);
+            p.line_mut().insert_str(1, "b");
+            Ok(())
+        }),
+    );
+    assert_eq!("", editor.readline(">>").unwrap());
+
+    // errors are reported
+    let mut editor = init_editor(EditMode::Emacs, &[E::ctrl('T'), E::ENTER]);
+    editor.bind_sequence(
+        E::ctrl('T'),
+        Cmd::custom(|_| Err(std::io::Error::other("failure"))),
+    );
+    assert!(editor.readline(">>").is_err());
+}
+
+#[test]
+fn custom_completion_menu() {
+    let mut editor = Editor::<AmbiguousCompleter>::new();
+    editor.set_helper(Some(AmbiguousCompleter));
+    editor.bind_sequence(
+        E(K::Tab, M::NONE),
+        Cmd::custom(|p| {
+            let mut state = p.completion()?.unwrap();
+            assert_eq!(2, state.candidates().len());
+            assert_eq!("ru", state.word());
+            state.select_previous();
+            assert_eq!(Some("rustup"), state.accepted_text().as_deref());
+            assert!(p.accept_completion(&state));
+            Ok(())
+        }),
+    );
+    editor.term.keys = vec![E::from('r'), E::from('u'), E(K::Tab, M::NONE), E::ENTER];
+    assert_eq!("rustup", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn macro_binding() {
+    use crate::EventHandler;
+    let keys = [E::from('l'), E::ctrl('X'), E::ctrl('R')];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(
+        Event::KeySeq(vec![E::ctrl('X'), E::ctrl('R')].into()),
+        EventHandler::Macro(vec![Cmd::Insert(1, "s -l".to_owned()), Cmd::AcceptLine]),
+    );
+    let line = editor.readline(">>").unwrap();
+    assert_eq!("ls -l", line);
+}
+
+#[test]
+fn switch_edit_mode() {
+    let keys = [
+        E::from('a'),
+        E::ctrl('X'),
+        E::ctrl('V'),
+        E::ESC,
+        E::from('0'),
+        E::from('i'),
+        E::from('b'),
+        E::ENTER,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(
+        Event::KeySeq(vec![E::ctrl('X'), E::ctrl('V')].into()),
+        Cmd::ViEditingMode,
+    );
+    assert_eq!("ba", editor.readline(">>").unwrap());
+    assert_eq!(EditMode::Vi, editor.config.edit_mode());
+
+    let keys = [
+        E::from('a'),
+        E::ESC,
+        E::ctrl('E'),
+        E::ctrl('A'),
+        E::from('b'),
+        E::ENTER,
+    ];
+    let mut editor = init_editor(EditMode::Vi, &keys);
+    editor.bind_sequence_in(
+        crate::Keymap::ViCommand,
+        E::ctrl('E'),
+        Cmd::EmacsEditingMode,
+    );
+    assert_eq!("ba", editor.readline(">>").unwrap());
+    assert_eq!(EditMode::Emacs, editor.config.edit_mode());
+}
+
+struct ExpandAlias;
+impl crate::ConditionalEventHandler for ExpandAlias {
+    fn handle(
+        &self,
+        _: &Event,
+        _: crate::RepeatCount,
+        _: bool,
+        ctx: &crate::EventContext,
+    ) -> Option<Cmd> {
+        if ctx.line() == "ll" {
+            Some(Cmd::Replace(
+                crate::Movement::WholeLine,
+                Some("ls -l".to_owned()),
+            ))
+        } else {
+            Some(Cmd::Noop)
+        }
+    }
+}
+
+#[test]
+fn registered_function() {
+    let keys = [
+        E::from('l'),
+        E::from('l'),
+        E::ctrl('X'),
+        E::from('a'),
+        E::ENTER,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor
+        .functions_mut()
+        .register("My-App-Expand-Alias", ExpandAlias);
+    assert!(editor.functions().contains("my-app-expand-alias"));
+    let mut errors = 0;
+    editor.apply_inputrc_with(
+        &crate::inputrc::parse("\"\\C-xa\": my-app-expand-alias\n"),
+        |_| errors += 1,
+    );
+    assert_eq!(0, errors);
+    assert_eq!("ls -l", editor.readline(">>").unwrap());
+    assert!(editor.functions_mut().unregister("my-app-expand-alias"));
+}
+
+#[test]
+fn ambiguous_sequence() {
+    fn readline(keys: &[KeyEvent]) -> String {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.bind_sequence(
+            Event::KeySeq(vec![E::ctrl('X'), E::from('a')].into()),
+            Cmd::Insert(1, "1".to_owned()),
+        );
+        editor.bind_sequence(
+            Event::KeySeq(vec![E::ctrl('X'), E::from('a'), E::from('b')].into()),
+            Cmd::Insert(1, "2".to_owned()),
+        );
+        editor.readline(">>").unwrap()
+    }
+    let c_x = E::ctrl('X');
+    let a = E::from('a');
+    assert_eq!("2", readline(&[c_x, a, E::from('b'), E::ENTER]));
+    // the key following the shorter sequence is not lost
+    assert_eq!("1c", readline(&[c_x, a, E::from('c'), E::ENTER]));
+    assert_eq!("1", readline(&[c_x, a, E::ENTER]));
+}
+
+#[test]
+fn dump_binding() {
+    let keys = [E::from('a'), E::ctrl('X'), E::from('v'), E::ENTER];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(
+        Event::KeySeq(vec![E::ctrl('X'), E::from('v')].into()),
+        Cmd::DumpVariables,
+    );
+    let line = editor.readline(">>").unwrap();
+    assert_eq!("a", line);
+}
+
+#[test]
+fn bracketed_paste() {
+    let paste = |text: &str| {
+        let mut keys = vec![E(K::BracketedPasteStart, M::NONE)];
+        keys.extend(text.chars().map(|c| match c {
+            '\n' => E::ENTER,
+            c => E::from(c),
+        }));
+        keys.push(E(K::BracketedPasteEnd, M::NONE));
+        keys
+    };
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        let mut keys = paste("ls\nx");
+        keys.push(E::ENTER);
+        assert_eq!("ls\nx", init_editor(*mode, &keys).readline(">>").unwrap());
+    }
+    // vi command mode
+    let mut keys = vec![E::ESC];
+    keys.extend(paste("dd"));
+    keys.push(E::ENTER);
+    assert_eq!(
+        "dd",
+        init_editor(EditMode::Vi, &keys).readline(">>").unwrap()
+    );
+    // pasted text does not trigger bindings
+    let mut keys = paste("ab");
+    keys.push(E::ENTER);
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(E::from('a'), Cmd::Insert(1, "z".to_owned()));
+    assert_eq!("ab", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn mouse_bindings() {
+    use crate::{MouseButton, MouseEvent, MouseEventKind};
+    let mouse = |kind| E(K::Mouse(MouseEvent::from(kind)), M::NONE);
+    // wheel browses the history
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_history(
+            *mode,
+            &["line1", "line2"],
+            &[
+                mouse(MouseEventKind::ScrollUp),
+                mouse(MouseEventKind::ScrollUp),
+                mouse(MouseEventKind::ScrollDown),
+                E::ENTER,
+            ],
+            "",
+            ("line2", ""),
+        );
+    }
+    // custom binding, whatever the position is
+    let click = MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Right),
+        modifiers: M::NONE,
+        row: 3,
+        column: 7,
+    };
+    let keys = [E(K::Mouse(click), M::NONE), E::ENTER];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(
+        MouseEventKind::Down(MouseButton::Right),
+        Cmd::Insert(1, "paste".to_owned()),
+    );
+    assert_eq!("paste", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn focus_bindings() {
+    let focus_lost = E(K::FocusLost, M::NONE);
+    let focus_gained = E(K::FocusGained, M::NONE);
+    // ignored by default
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_line(
+            *mode,
+            &[
+                E::from('a'),
+                focus_lost,
+                focus_gained,
+                E::from('b'),
+                E::ENTER,
+            ],
+            "ab",
+        );
+    }
+    let keys = [E::from('a'), focus_lost, focus_gained, E::ENTER];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(focus_lost, Cmd::Insert(1, "-".to_owned()));
+    editor.bind_sequence(focus_gained, Cmd::Insert(1, "+".to_owned()));
+    assert_eq!("a-+", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn keypad_bindings() {
+    let kp_left = E(K::Left, M::KEYPAD);
+    let kp_enter = E(K::Enter, M::KEYPAD);
+    // handled like the normal keys by default
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_line(
+            *mode,
+            &[E::from('b'), kp_left, E::from('a'), kp_enter],
+            "ab",
+        );
+    }
+    let keys = [E::from('a'), E(K::Left, M::NONE), kp_left, E::ENTER];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(kp_left, Cmd::Insert(1, "<".to_owned()));
+    assert_eq!("<a", editor.readline(">>").unwrap());
+    // as the last key of a sequence
+    let keys = [
+        E::ctrl('X'),
+        kp_left,
+        E::ctrl('X'),
+        E(K::Left, M::NONE),
+        E::ENTER,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(
+        Event::KeySeq(vec![E::ctrl('X'), E(K::Left, M::NONE)].into()),
+        Cmd::Insert(1, "x".to_owned()),
+    );
+    assert_eq!("xx", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn key_events() {
+    let keys = [E::from('a'), E::ctrl('X'), E(K::Up, M::NONE)];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!(E::from('a'), editor.read_key_event().unwrap());
+    let events: Vec<_> = editor.key_events().unwrap().map(Result::unwrap).collect();
+    assert_eq!(keys.to_vec(), events);
+}
+
 #[test]
 fn test_send() {
     fn assert_send<T: Send>() {}
@@ -144,3 +1347,100 @@ fn test_sync() {
     fn assert_sync<T: Sync>() {}
     assert_sync::<Editor<()>>();
 }
+
+#[test]
+fn abbreviations() {
+    let keys = |s: &str| {
+        let mut keys: Vec<_> = s.chars().map(E::from).collect();
+        keys.push(E::ENTER);
+        keys
+    };
+    let init = |keys: &[E]| {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.add_abbreviation("gco", "git checkout");
+        editor
+            .add_abbreviation_expander(|word: &str| word.strip_prefix('@').map(str::to_uppercase));
+        editor
+    };
+    let mut editor = init(&keys("gco main"));
+    assert_eq!("git checkout main", editor.readline(">>").unwrap());
+    // when the line is accepted
+    let mut editor = init(&keys("echo @home"));
+    assert_eq!("echo HOME", editor.readline(">>").unwrap());
+    // not in the middle of a word
+    let mut editor = init(&keys("xgco gcox"));
+    assert_eq!("xgco gcox", editor.readline(">>").unwrap());
+    // undo restores the abbreviation
+    let mut editor = init(&[
+        E::from('g'),
+        E::from('c'),
+        E::from('o'),
+        E::from(' '),
+        E::ctrl('_'),
+        E::from('x'),
+        E::ENTER,
+    ]);
+    assert_eq!("gco x", editor.readline(">>").unwrap());
+    assert_eq!(
+        Some("git checkout".to_owned()),
+        editor.remove_abbreviation("gco")
+    );
+    editor.clear_abbreviation_expanders();
+}
+
+#[test]
+fn auto_pairs() {
+    use crate::config::{Configurer, AUTO_PAIRS};
+    let readline = |keys: &[E], in_strings: bool| {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.set_auto_pairs(AUTO_PAIRS);
+        editor.set_auto_pairs_in_strings(in_strings);
+        editor.readline(">>").unwrap()
+    };
+    let typed = |s: &str| {
+        let mut keys: Vec<_> = s.chars().map(E::from).collect();
+        keys.push(E::ENTER);
+        keys
+    };
+    // the closing character is inserted, then skipped over
+    assert_eq!("f(x)", readline(&typed("f(x"), false));
+    assert_eq!("f(x)", readline(&typed("f(x)"), false));
+    assert_eq!("[{}]", readline(&typed("[{}]"), false));
+    // not before a word
+    let mut keys = vec![E::from('a'), E::ctrl('A'), E::from('(')];
+    keys.push(E::ENTER);
+    assert_eq!("(a", readline(&keys, false));
+    // backspace deletes both
+    assert_eq!(
+        "x",
+        readline(&[E::from('('), E::BACKSPACE, E::from('x'), E::ENTER], false)
+    );
+    // quotes
+    assert_eq!("echo \"a\"", readline(&typed("echo \"a"), false));
+    assert_eq!("don't", readline(&typed("don't"), false));
+    // brackets in strings
+    assert_eq!("echo \"a(\"", readline(&typed("echo \"a("), false));
+    assert_eq!("echo \"a()\"", readline(&typed("echo \"a("), true));
+}
+
+#[test]
+fn strip_line_ending() {
+    let mut line = "ls\r\n".to_owned();
+    assert_eq!("\r\n", crate::strip_line_ending(&mut line));
+    assert_eq!("ls", line);
+    let mut line = "ls\n".to_owned();
+    assert_eq!("\n", crate::strip_line_ending(&mut line));
+    assert_eq!("ls", line);
+    // last line without line ending
+    let mut line = "ls".to_owned();
+    assert_eq!("", crate::strip_line_ending(&mut line));
+    assert_eq!("ls", line);
+}
+
+#[test]
+fn raw_mode() {
+    let mut editor = Editor::<()>::new();
+    let guard = editor.raw_mode().unwrap();
+    guard.restore().unwrap();
+    let _guard = editor.raw_mode().unwrap();
+}
@@ -1,5 +1,5 @@
 //! Vi command mode specific key bindings
-use super::{assert_cursor, assert_history};
+use super::{assert_cursor, assert_history, assert_line_with_initial, init_editor};
 use crate::config::EditMode;
 use crate::keys::KeyEvent as E;
 
@@ -13,10 +13,52 @@ fn dollar() {
     );
 }
 
-/*#[test]
+#[test]
 fn dot() {
-    // TODO
-}*/
+    assert_cursor(
+        EditMode::Vi,
+        ("", "one two three"),
+        &[
+            E::ESC,
+            E::from('d'),
+            E::from('w'),
+            E::from('2'),
+            E::from('.'),
+            E::ENTER,
+        ],
+        ("", ""),
+    );
+    // the command entering insert mode is replayed with the insertion
+    assert_cursor(
+        EditMode::Vi,
+        ("a", "b"),
+        &[
+            E::ESC,
+            E::from('A'),
+            E::from('!'),
+            E::ESC,
+            E::from('0'),
+            E::from('.'),
+            E::ENTER,
+        ],
+        ("ab!", "!"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "one two"),
+        &[
+            E::ESC,
+            E::from('c'),
+            E::from('w'),
+            E::from('x'),
+            E::ESC,
+            E::from('w'),
+            E::from('.'),
+            E::ENTER,
+        ],
+        ("x ", "x"),
+    );
+}
 
 #[test]
 fn semi_colon() {
@@ -144,6 +186,195 @@ fn ctrl_k() {
     }
 }
 
+#[test]
+fn d() {
+    // with counts, multiplied
+    assert_cursor(
+        EditMode::Vi,
+        ("", "one two three four"),
+        &[E::ESC, E::from('d'), E::from('2'), E::from('w'), E::ENTER],
+        ("", "three four"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "one two three four"),
+        &[
+            E::ESC,
+            E::from('3'),
+            E::from('d'),
+            E::from('1'),
+            E::from('w'),
+            E::ENTER,
+        ],
+        ("", "four"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("one ", "two"),
+        &[E::ESC, E::from('d'), E::from('d'), E::ENTER],
+        ("", ""),
+    );
+    // the end of the word is included
+    assert_cursor(
+        EditMode::Vi,
+        ("", "one two"),
+        &[E::ESC, E::from('d'), E::from('e'), E::ENTER],
+        ("", " two"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "f(a, b)"),
+        &[E::ESC, E::from('d'), E::from('f'), E::from('('), E::ENTER],
+        ("", "a, b)"),
+    );
+    // up to the first non-blank character
+    assert_cursor(
+        EditMode::Vi,
+        ("  abc", "def"),
+        &[E::ESC, E::from('d'), E::from('^'), E::ENTER],
+        ("  ", "cdef"),
+    );
+}
+
+#[test]
+fn c() {
+    assert_cursor(
+        EditMode::Vi,
+        ("(", "a, b)"),
+        &[
+            E::ESC,
+            E::from('l'),
+            E::from('c'),
+            E::from('t'),
+            E::from(')'),
+            E::from('x'),
+            E::ENTER,
+        ],
+        ("(x", ")"),
+    );
+    // `cw` is `ce`
+    assert_cursor(
+        EditMode::Vi,
+        ("", "one two"),
+        &[E::ESC, E::from('c'), E::from('w'), E::from('x'), E::ENTER],
+        ("x", " two"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("  abc", "def"),
+        &[E::ESC, E::from('c'), E::from('^'), E::from('X'), E::ENTER],
+        ("  X", "cdef"),
+    );
+}
+
+#[test]
+fn text_objects() {
+    assert_cursor(
+        EditMode::Vi,
+        ("echo \"hel", "lo\" world"),
+        &[
+            E::ESC,
+            E::from('c'),
+            E::from('i'),
+            E::from('"'),
+            E::from('x'),
+            E::ENTER,
+        ],
+        ("echo \"x", "\" world"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("f(a, (b", "))"),
+        &[
+            E::ESC,
+            E::from('d'),
+            E::from('2'),
+            E::from('a'),
+            E::from('('),
+            E::ENTER,
+        ],
+        ("f", ""),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("one tw", "o three"),
+        &[E::ESC, E::from('d'), E::from('a'), E::from('w'), E::ENTER],
+        ("one ", "three"),
+    );
+    // not a text object
+    assert_cursor(
+        EditMode::Vi,
+        ("one tw", "o three"),
+        &[E::ESC, E::from('d'), E::from('i'), E::from('z'), E::ENTER],
+        ("one t", "wo three"),
+    );
+}
+
+#[test]
+fn registers() {
+    let mut editor = init_editor(
+        EditMode::Vi,
+        &[
+            E::ESC,
+            E::from('"'),
+            E::from('a'),
+            E::from('y'),
+            E::from('w'),
+            E::from('w'),
+            E::from('"'),
+            E::from('b'),
+            E::from('d'),
+            E::from('w'),
+            E::from('2'),
+            E::from('"'),
+            E::from('a'),
+            E::from('P'),
+            E::ENTER,
+        ],
+    );
+    let line = editor
+        .readline_with_initial("", ("", "one two three"))
+        .unwrap();
+    assert_eq!("one one one three", line);
+    assert_eq!(Some("one ".to_owned()), editor.register('a'));
+    assert_eq!(Some("two ".to_owned()), editor.register('b'));
+    assert_eq!(Some("two ".to_owned()), editor.register('"'));
+
+    // last insert, paste from the API
+    editor.set_register('c', "!");
+    editor.term.keys = vec![
+        E::from('x'),
+        E::from('y'),
+        E::ESC,
+        E::from('"'),
+        E::from('.'),
+        E::from('p'),
+        E::from('"'),
+        E::from('c'),
+        E::from('p'),
+        E::ENTER,
+    ];
+    let line = editor.readline("").unwrap();
+    assert_eq!("xyxy!", line);
+    assert_eq!(Some("xy".to_owned()), editor.register('.'));
+}
+
+#[test]
+fn y() {
+    assert_cursor(
+        EditMode::Vi,
+        ("", "ab cd"),
+        &[E::ESC, E::from('y'), E::from('$'), E::from('P'), E::ENTER],
+        ("ab c", "dab cd"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "ab cd"),
+        &[E::ESC, E::from('Y'), E::from('P'), E::ENTER],
+        ("ab c", "dab cd"),
+    );
+}
+
 #[test]
 fn e() {
     assert_cursor(
@@ -228,6 +459,26 @@ fn uppercase_i() {
     );
 }
 
+#[test]
+fn o() {
+    assert_cursor(
+        EditMode::Vi,
+        ("ab", "c\nd"),
+        &[E::ESC, E::from('o'), E::from('x'), E::ENTER],
+        ("abc\nx", "\nd"),
+    );
+}
+
+#[test]
+fn uppercase_o() {
+    assert_cursor(
+        EditMode::Vi,
+        ("ab\ncd", ""),
+        &[E::ESC, E::from('O'), E::from('x'), E::ENTER],
+        ("ab\nx", "\ncd"),
+    );
+}
+
 #[test]
 fn u() {
     assert_cursor(
@@ -238,6 +489,78 @@ fn u() {
     );
 }
 
+#[test]
+fn undo_groups() {
+    // a change and its insertion are undone together
+    assert_cursor(
+        EditMode::Vi,
+        ("", "one two"),
+        &[
+            E::ESC,
+            E::from('c'),
+            E::from('w'),
+            E::from('a'),
+            E::from(' '),
+            E::from('b'),
+            E::ESC,
+            E::from('u'),
+            E::ENTER,
+        ],
+        ("one", " two"),
+    );
+    // as well as a repeated one
+    assert_cursor(
+        EditMode::Vi,
+        ("", "one two"),
+        &[
+            E::ESC,
+            E::from('c'),
+            E::from('w'),
+            E::from('x'),
+            E::ESC,
+            E::from('w'),
+            E::from('.'),
+            E::from('u'),
+            E::ENTER,
+        ],
+        ("x two", ""),
+    );
+}
+
+#[test]
+fn ctrl_r() {
+    assert_cursor(
+        EditMode::Vi,
+        ("", "abc"),
+        &[
+            E::ESC,
+            E::from('x'),
+            E::from('x'),
+            E::from('2'),
+            E::from('u'),
+            E::from('l'),
+            E::ctrl('R'),
+            E::ENTER,
+        ],
+        ("", "bc"),
+    );
+    assert_cursor(
+        EditMode::Vi,
+        ("", "abc"),
+        &[
+            E::ESC,
+            E::from('x'),
+            E::from('x'),
+            E::from('2'),
+            E::from('u'),
+            E::from('2'),
+            E::ctrl('R'),
+            E::ENTER,
+        ],
+        ("", "c"),
+    );
+}
+
 #[test]
 fn w() {
     assert_cursor(
@@ -505,6 +828,22 @@ fn uppercase_t() {
     );
 }
 
+#[test]
+fn hash() {
+    assert_line_with_initial(
+        EditMode::Vi,
+        ("ls", " -l"),
+        &[E::ESC, E::from('#')],
+        "#ls -l",
+    );
+    assert_line_with_initial(
+        EditMode::Vi,
+        ("#ls", ""),
+        &[E::ESC, E::from('2'), E::from('#')],
+        "ls",
+    );
+}
+
 #[test]
 fn indent() {
     assert_cursor(
@@ -0,0 +1,426 @@
+//! ANSI (VT100) rendering, shared by the unix backend and the backends
+//! provided by applications
+use std::cmp;
+use std::thread;
+use std::time::Duration;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{display_line, width, Capabilities, CursorShape, RawReader, Renderer};
+use crate::config::BellStyle;
+use crate::highlight::Highlighter;
+use crate::layout::{Layout, Position};
+use crate::line_buffer::LineBuffer;
+use crate::Result;
+
+// Reverse video (DECSCNM), toggled to flash the screen
+const REVERSE_VIDEO_ON: &[u8] = b"\x1b[?5h";
+const REVERSE_VIDEO_OFF: &[u8] = b"\x1b[?5l";
+const FLASH_DURATION: Duration = Duration::from_millis(100);
+
+/// Output stream of an [`AnsiRenderer`]
+pub trait AnsiOutput {
+    /// Reader of the terminal responses
+    type Reader: RawReader;
+
+    /// Write `buf` and flush it
+    fn write_and_flush(&self, buf: &[u8]) -> Result<()>;
+    /// Number of columns and rows
+    fn get_win_size(&self) -> (usize, usize);
+    /// Check if the size of the terminal has changed
+    fn sigwinch(&self) -> bool;
+    /// Check if the process has been resumed after being stopped
+    fn sigcont(&self) -> bool {
+        false
+    }
+    /// Ring the bell (`BellStyle::Audible`)
+    fn bell(&self) -> Result<()> {
+        self.write_and_flush(b"\x07")
+    }
+    /// Absolute cursor position on the screen (0-based), if it can be
+    /// queried.
+    fn cursor_position(&self, _rdr: &mut Self::Reader) -> Result<Option<Position>> {
+        Ok(None)
+    }
+}
+
+/// Console output writer using ANSI escape sequences
+pub struct AnsiRenderer<O> {
+    out: O,
+    cols: usize, // Number of columns in terminal
+    buffer: String,
+    tab_stop: usize,
+    colors_enabled: bool,
+    bell_style: BellStyle,
+    output_meta: bool,
+    caps: Capabilities,
+}
+
+impl<O: AnsiOutput> AnsiRenderer<O> {
+    pub fn new(
+        out: O,
+        tab_stop: usize,
+        colors_enabled: bool,
+        bell_style: BellStyle,
+        output_meta: bool,
+    ) -> Self {
+        let (cols, _) = out.get_win_size();
+        Self {
+            out,
+            cols,
+            buffer: String::with_capacity(1024),
+            tab_stop,
+            colors_enabled,
+            bell_style,
+            output_meta,
+            caps: Capabilities::default(),
+        }
+    }
+
+    /// Use the control sequences of `caps` instead of the ANSI ones
+    pub fn with_capabilities(mut self, caps: Capabilities) -> Self {
+        self.caps = caps;
+        self
+    }
+
+    fn clear_old_rows(&mut self, layout: &Layout) {
+        let current_row = layout.cursor.row;
+        let old_rows = layout.end.row;
+        // old_rows < cursor_row if the prompt spans multiple lines and if
+        // this is the default State.
+        let cursor_row_movement = old_rows.saturating_sub(current_row);
+        // move the cursor down as required
+        if cursor_row_movement > 0 {
+            self.caps.cursor_down(&mut self.buffer, cursor_row_movement);
+        }
+        // clear old rows
+        for _ in 0..old_rows {
+            self.buffer.push('\r');
+            self.caps.clear_to_eol(&mut self.buffer);
+            self.caps.cursor_up(&mut self.buffer, 1);
+        }
+        // clear the line
+        self.buffer.push('\r');
+        self.caps.clear_to_eol(&mut self.buffer);
+    }
+}
+
+impl<O: AnsiOutput> Renderer for AnsiRenderer<O> {
+    type Reader = O::Reader;
+
+    fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
+        self.buffer.clear();
+        let row_ordering = new.row.cmp(&old.row);
+        if row_ordering == cmp::Ordering::Greater {
+            // move down
+            self.caps.cursor_down(&mut self.buffer, new.row - old.row);
+        } else if row_ordering == cmp::Ordering::Less {
+            // move up
+            self.caps.cursor_up(&mut self.buffer, old.row - new.row);
+        }
+        let col_ordering = new.col.cmp(&old.col);
+        if col_ordering == cmp::Ordering::Greater {
+            // move right
+            self.caps.cursor_right(&mut self.buffer, new.col - old.col);
+        } else if col_ordering == cmp::Ordering::Less {
+            // move left
+            self.caps.cursor_left(&mut self.buffer, old.col - new.col);
+        }
+        self.write_and_flush(self.buffer.as_bytes())
+    }
+
+    fn refresh_line(
+        &mut self,
+        prompt: &str,
+        line: &LineBuffer,
+        hint: Option<&str>,
+        old_layout: &Layout,
+        new_layout: &Layout,
+        highlighter: Option<&dyn Highlighter>,
+    ) -> Result<()> {
+        self.buffer.clear();
+
+        let default_prompt = new_layout.default_prompt;
+        let cursor = new_layout.cursor;
+        let end_pos = new_layout.end;
+
+        self.clear_old_rows(old_layout);
+
+        if let Some(highlighter) = highlighter {
+            // display the prompt
+            self.buffer
+                .push_str(&highlighter.highlight_prompt(prompt, default_prompt));
+            // display the input line
+            self.buffer.push_str(&display_line(line, Some(highlighter)));
+        } else {
+            // display the prompt
+            self.buffer.push_str(prompt);
+            // display the input line
+            self.buffer.push_str(&display_line(line, None));
+        }
+        // display hint
+        if let Some(hint) = hint {
+            if let Some(highlighter) = highlighter {
+                self.buffer.push_str(&highlighter.highlight_hint(hint));
+            } else {
+                self.buffer.push_str(hint);
+            }
+        }
+        if !self.output_meta && !self.buffer.is_ascii() {
+            self.buffer = escape_meta(&self.buffer);
+        }
+        // we have to generate our own newline on line wrap
+        if end_pos.col == 0 && end_pos.row > 0 && !self.buffer.ends_with('\n') {
+            self.buffer.push('\n');
+        }
+        // position the cursor
+        let new_cursor_row_movement = end_pos.row - cursor.row;
+        // move the cursor up as required
+        if new_cursor_row_movement > 0 {
+            self.caps
+                .cursor_up(&mut self.buffer, new_cursor_row_movement);
+        }
+        // position the cursor within the line
+        self.buffer.push('\r');
+        if cursor.col > 0 {
+            self.caps.cursor_right(&mut self.buffer, cursor.col);
+        }
+
+        self.write_and_flush(self.buffer.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn write_and_flush(&self, buf: &[u8]) -> Result<()> {
+        self.out.write_and_flush(buf)
+    }
+
+    /// Control characters are treated as having zero width.
+    /// Characters with 2 column width are correctly handled (not split).
+    fn calculate_position(&self, s: &str, orig: Position) -> Position {
+        let mut pos = orig;
+        let mut esc_seq = 0;
+        for c in s.graphemes(true) {
+            if c == "\n" {
+                pos.row += 1;
+                pos.col = 0;
+                continue;
+            }
+            let cw = if c == "\t" {
+                self.tab_stop - (pos.col % self.tab_stop)
+            } else if !self.output_meta && !c.is_ascii() {
+                c.chars()
+                    .map(|c| if c.is_ascii() { 1 } else { 4 * c.len_utf8() })
+                    .sum()
+            } else {
+                width(c, &mut esc_seq)
+            };
+            pos.col += cw;
+            if pos.col > self.cols {
+                pos.row += 1;
+                pos.col = cw;
+            }
+        }
+        if pos.col == self.cols {
+            pos.col = 0;
+            pos.row += 1;
+        }
+        pos
+    }
+
+    fn beep(&mut self) -> Result<()> {
+        match self.bell_style {
+            BellStyle::Audible => self.out.bell(),
+            BellStyle::Visible => {
+                // like the `flash` capability of xterm-like terminals
+                self.write_and_flush(REVERSE_VIDEO_ON)?;
+                thread::sleep(FLASH_DURATION);
+                self.write_and_flush(REVERSE_VIDEO_OFF)
+            }
+            BellStyle::None => Ok(()),
+        }
+    }
+
+    /// Clear the screen. Used to handle ctrl+l
+    fn clear_screen(&mut self) -> Result<()> {
+        self.buffer.clear();
+        self.caps.clear_screen(&mut self.buffer);
+        self.write_and_flush(self.buffer.as_bytes())
+    }
+
+    /// With the OSC 52 escape sequence, ignored by the terminals which don't
+    /// support it
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        let seq = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+        self.write_and_flush(seq.as_bytes())
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        let seq = format!("\x1b[{} q", shape as u8);
+        self.write_and_flush(seq.as_bytes())
+    }
+
+    /// Check if a SIGWINCH signal has been received
+    fn sigwinch(&self) -> bool {
+        self.out.sigwinch()
+    }
+
+    /// Check if a SIGCONT signal has been received
+    fn sigcont(&self) -> bool {
+        self.out.sigcont()
+    }
+
+    /// Try to update the number of columns in the current terminal,
+    fn update_size(&mut self) {
+        let (cols, _) = self.out.get_win_size();
+        self.cols = cols;
+    }
+
+    fn get_columns(&self) -> usize {
+        self.cols
+    }
+
+    /// Try to get the number of rows in the current terminal,
+    /// or assume 24 if it fails.
+    fn get_rows(&self) -> usize {
+        let (_, rows) = self.out.get_win_size();
+        rows
+    }
+
+    fn colors_enabled(&self) -> bool {
+        self.colors_enabled
+    }
+
+    fn move_cursor_at_leftmost(&mut self, rdr: &mut O::Reader) -> Result<()> {
+        match self.out.cursor_position(rdr)? {
+            Some(pos) if pos.col != 0 => self.write_and_flush(b"\n"),
+            _ => Ok(()),
+        }
+    }
+
+    fn cursor_position(&mut self, rdr: &mut O::Reader) -> Result<Option<Position>> {
+        self.out.cursor_position(rdr)
+    }
+}
+
+/// Display the bytes of non-ASCII characters as octal escapes (`output-meta
+/// off`)
+fn escape_meta(s: &str) -> String {
+    use std::fmt::Write;
+    let mut escaped = String::with_capacity(s.len() * 4);
+    for c in s.chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+        } else {
+            let mut buf = [0; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                write!(escaped, "\\{:03o}", b).unwrap();
+            }
+        }
+    }
+    escaped
+}
+
+/// Standard base64 encoding (with padding), as expected by OSC 52
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use std::vec::IntoIter;
+
+    use super::{AnsiOutput, AnsiRenderer, Position, Renderer};
+    use crate::config::BellStyle;
+    use crate::keys::KeyEvent;
+    use crate::line_buffer::LineBuffer;
+    use crate::Result;
+
+    struct Output;
+
+    impl AnsiOutput for Output {
+        type Reader = IntoIter<KeyEvent>;
+
+        fn write_and_flush(&self, _: &[u8]) -> Result<()> {
+            Ok(())
+        }
+
+        fn get_win_size(&self) -> (usize, usize) {
+            (80, 24)
+        }
+
+        fn sigwinch(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn base64() {
+        use super::base64;
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("Zm9vYmFy", base64(b"foobar"));
+    }
+
+    #[test]
+    #[ignore]
+    fn prompt_with_ansi_escape_codes() {
+        let out = AnsiRenderer::new(Output, 4, true, BellStyle::default(), true);
+        let pos = out.calculate_position("\x1b[1;32m>>\x1b[0m ", Position::default());
+        assert_eq!(3, pos.col);
+        assert_eq!(0, pos.row);
+    }
+
+    #[test]
+    fn output_meta() {
+        assert_eq!("a\\303\\251", super::escape_meta("a\u{e9}"));
+        let out = AnsiRenderer::new(Output, 4, true, BellStyle::default(), false);
+        let pos = out.calculate_position("a\u{e9}", Position::default());
+        assert_eq!(9, pos.col);
+        assert_eq!(0, pos.row);
+    }
+
+    #[test]
+    fn test_line_wrap() {
+        let mut out = AnsiRenderer::new(Output, 4, true, BellStyle::default(), true);
+        let prompt = "> ";
+        let default_prompt = true;
+        let prompt_size = out.calculate_position(prompt, Position::default());
+
+        let mut line = LineBuffer::init("", 0, None);
+        let old_layout = out.compute_layout(prompt_size, default_prompt, &line, None);
+        assert_eq!(Position { col: 2, row: 0 }, old_layout.cursor);
+        assert_eq!(old_layout.cursor, old_layout.end);
+
+        assert_eq!(Some(true), line.insert('a', out.cols - prompt_size.col + 1));
+        let new_layout = out.compute_layout(prompt_size, default_prompt, &line, None);
+        assert_eq!(Position { col: 1, row: 1 }, new_layout.cursor);
+        assert_eq!(new_layout.cursor, new_layout.end);
+        out.refresh_line(prompt, &line, None, &old_layout, &new_layout, None)
+            .unwrap();
+        #[rustfmt::skip]
+        assert_eq!(
+            "\r\u{1b}[0K> aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\u{1b}[C",
+            out.buffer
+        );
+    }
+}
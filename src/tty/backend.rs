@@ -0,0 +1,244 @@
+//! Terminal of the platform or backend provided by the application
+use super::{
+    CursorShape, CustomMode, CustomReader, CustomRenderer, CustomTerminal, NativeTerminal, RawMode,
+    RawReader, Renderer, Term,
+};
+use crate::config::{BellStyle, ColorMode, Config, OutputStreamType};
+use crate::highlight::Highlighter;
+use crate::keys::KeyEvent;
+use crate::layout::{Layout, Position};
+use crate::line_buffer::LineBuffer;
+use crate::Result;
+
+type NativeMode = <NativeTerminal as Term>::Mode;
+type NativeReader = <NativeTerminal as Term>::Reader;
+type NativeWriter = <NativeTerminal as Term>::Writer;
+
+/// Forward a call to the native or custom variant
+macro_rules! dispatch {
+    ($value:expr, $inner:ident => $call:expr) => {
+        match $value {
+            Self::Native($inner) => $call,
+            Self::Custom($inner) => $call,
+        }
+    };
+}
+
+pub enum Mode {
+    Native(NativeMode),
+    Custom(CustomMode),
+}
+
+impl RawMode for Mode {
+    fn disable_raw_mode(&self) -> Result<()> {
+        dispatch!(self, mode => mode.disable_raw_mode())
+    }
+}
+
+pub enum Reader {
+    Native(NativeReader),
+    Custom(CustomReader),
+}
+
+impl RawReader for Reader {
+    fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyEvent> {
+        dispatch!(self, rdr => rdr.next_key(single_esc_abort))
+    }
+
+    fn wait_for_input(&mut self, timeout_ms: i32) -> Result<bool> {
+        dispatch!(self, rdr => rdr.wait_for_input(timeout_ms))
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        dispatch!(self, rdr => rdr.next_char())
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        dispatch!(self, rdr => rdr.read_pasted_text())
+    }
+}
+
+pub enum Writer {
+    Native(NativeWriter),
+    Custom(CustomRenderer),
+}
+
+impl Renderer for Writer {
+    type Reader = Reader;
+
+    fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
+        dispatch!(self, out => out.move_cursor(old, new))
+    }
+
+    fn refresh_line(
+        &mut self,
+        prompt: &str,
+        line: &LineBuffer,
+        hint: Option<&str>,
+        old_layout: &Layout,
+        new_layout: &Layout,
+        highlighter: Option<&dyn Highlighter>,
+    ) -> Result<()> {
+        dispatch!(self, out => out.refresh_line(prompt, line, hint, old_layout, new_layout, highlighter))
+    }
+
+    fn compute_layout(
+        &self,
+        prompt_size: Position,
+        default_prompt: bool,
+        line: &LineBuffer,
+        info: Option<&str>,
+    ) -> Layout {
+        dispatch!(self, out => out.compute_layout(prompt_size, default_prompt, line, info))
+    }
+
+    fn calculate_position(&self, s: &str, orig: Position) -> Position {
+        dispatch!(self, out => out.calculate_position(s, orig))
+    }
+
+    fn write_and_flush(&self, buf: &[u8]) -> Result<()> {
+        dispatch!(self, out => out.write_and_flush(buf))
+    }
+
+    fn beep(&mut self) -> Result<()> {
+        dispatch!(self, out => out.beep())
+    }
+
+    fn clear_screen(&mut self) -> Result<()> {
+        dispatch!(self, out => out.clear_screen())
+    }
+
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        dispatch!(self, out => out.copy_to_clipboard(text))
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        dispatch!(self, out => out.set_cursor_shape(shape))
+    }
+
+    fn sigwinch(&self) -> bool {
+        dispatch!(self, out => out.sigwinch())
+    }
+
+    fn sigcont(&self) -> bool {
+        dispatch!(self, out => out.sigcont())
+    }
+
+    fn update_size(&mut self) {
+        dispatch!(self, out => out.update_size())
+    }
+
+    fn get_columns(&self) -> usize {
+        dispatch!(self, out => out.get_columns())
+    }
+
+    fn get_rows(&self) -> usize {
+        dispatch!(self, out => out.get_rows())
+    }
+
+    fn colors_enabled(&self) -> bool {
+        dispatch!(self, out => out.colors_enabled())
+    }
+
+    fn move_cursor_at_leftmost(&mut self, rdr: &mut Reader) -> Result<()> {
+        match (self, rdr) {
+            (Self::Native(out), Reader::Native(rdr)) => out.move_cursor_at_leftmost(rdr),
+            (Self::Custom(out), Reader::Custom(rdr)) => out.move_cursor_at_leftmost(rdr),
+            _ => unreachable!("reader and writer of different terminals"),
+        }
+    }
+
+    fn cursor_position(&mut self, rdr: &mut Reader) -> Result<Option<Position>> {
+        match (self, rdr) {
+            (Self::Native(out), Reader::Native(rdr)) => out.cursor_position(rdr),
+            (Self::Custom(out), Reader::Custom(rdr)) => out.cursor_position(rdr),
+            _ => unreachable!("reader and writer of different terminals"),
+        }
+    }
+}
+
+/// Terminal of the platform or provided by the application
+#[derive(Debug)]
+pub enum Terminal {
+    Native(NativeTerminal),
+    Custom(CustomTerminal),
+}
+
+impl Terminal {
+    pub(crate) fn set_color_mode(&mut self, color_mode: ColorMode) {
+        dispatch!(self, term => term.color_mode = color_mode)
+    }
+
+    pub(crate) fn set_bell_style(&mut self, bell_style: BellStyle) {
+        dispatch!(self, term => term.bell_style = bell_style)
+    }
+
+    pub(crate) fn set_enable_bracketed_paste(&mut self, enabled: bool) {
+        dispatch!(self, term => term.enable_bracketed_paste = enabled)
+    }
+}
+
+impl Term for Terminal {
+    type Mode = Mode;
+    type Reader = Reader;
+    type Writer = Writer;
+
+    fn new(
+        color_mode: ColorMode,
+        stream: OutputStreamType,
+        tab_stop: usize,
+        bell_style: BellStyle,
+        enable_bracketed_paste: bool,
+    ) -> Self {
+        Self::Native(NativeTerminal::new(
+            color_mode,
+            stream,
+            tab_stop,
+            bell_style,
+            enable_bracketed_paste,
+        ))
+    }
+
+    fn is_unsupported(&self) -> bool {
+        match self {
+            Self::Native(term) => term.is_unsupported(),
+            Self::Custom(_) => false,
+        }
+    }
+
+    fn is_stdin_tty(&self) -> bool {
+        match self {
+            Self::Native(term) => term.is_stdin_tty(),
+            Self::Custom(_) => true,
+        }
+    }
+
+    fn is_output_tty(&self) -> bool {
+        match self {
+            Self::Native(term) => term.is_output_tty(),
+            Self::Custom(_) => true,
+        }
+    }
+
+    fn enable_raw_mode(&mut self) -> Result<Mode> {
+        match self {
+            Self::Native(term) => term.enable_raw_mode().map(Mode::Native),
+            Self::Custom(term) => term.enable_raw_mode().map(Mode::Custom),
+        }
+    }
+
+    fn create_reader(&self, config: &Config) -> Result<Reader> {
+        match self {
+            Self::Native(term) => term.create_reader(config).map(Reader::Native),
+            Self::Custom(term) => Ok(Reader::Custom(term.create_reader())),
+        }
+    }
+
+    fn create_writer(&self, config: &Config) -> Writer {
+        match self {
+            Self::Native(term) => Writer::Native(term.create_writer(config)),
+            Self::Custom(term) => Writer::Custom(term.create_writer(config)),
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Backend provided by the application (see [`crate::terminal::Terminal`])
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use log::debug;
+
+use super::{restore, AnsiOutput, AnsiRenderer, RawMode, RawReader};
+use crate::config::{BellStyle, ColorMode, Config};
+use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E};
+use crate::terminal::Terminal;
+use crate::Result;
+
+const BRACKETED_PASTE_ON: &[u8] = b"\x1b[?2004h";
+const BRACKETED_PASTE_OFF: &[u8] = b"\x1b[?2004l";
+
+type Shared = Arc<Mutex<dyn Terminal>>;
+
+pub struct CustomMode {
+    term: Shared,
+    bracketed_paste: bool,
+    id: restore::RawModeId,
+}
+
+fn restore_mode(term: &mut dyn Terminal, bracketed_paste: bool) -> Result<()> {
+    if bracketed_paste {
+        term.write(BRACKETED_PASTE_OFF)?;
+    }
+    term.disable_raw_mode()
+}
+
+impl RawMode for CustomMode {
+    fn disable_raw_mode(&self) -> Result<()> {
+        restore::raw_mode_disabled(self.id);
+        restore_mode(&mut *self.term.lock().unwrap(), self.bracketed_paste)
+    }
+}
+
+pub struct CustomReader(Shared);
+
+impl RawReader for CustomReader {
+    fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
+        self.0.lock().unwrap().read_key()
+    }
+
+    fn wait_for_input(&mut self, timeout_ms: i32) -> Result<bool> {
+        self.0.lock().unwrap().poll(timeout_ms)
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        loop {
+            if let Some(c) = self.next_key(false)?.to_char() {
+                return Ok(c);
+            }
+        }
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        let mut buffer = String::new();
+        loop {
+            match self.next_key(false)? {
+                E(K::BracketedPasteEnd, _) => break,
+                E(K::Enter, _) => buffer.push('\n'),
+                key => buffer.extend(key.to_char()),
+            }
+        }
+        Ok(buffer)
+    }
+}
+
+pub struct CustomOutput(Shared);
+
+impl AnsiOutput for CustomOutput {
+    type Reader = CustomReader;
+
+    fn write_and_flush(&self, buf: &[u8]) -> Result<()> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn get_win_size(&self) -> (usize, usize) {
+        self.0.lock().unwrap().size()
+    }
+
+    fn sigwinch(&self) -> bool {
+        self.0.lock().unwrap().size_changed()
+    }
+}
+
+/// Console output writer of a custom backend
+pub type CustomRenderer = AnsiRenderer<CustomOutput>;
+
+/// Terminal provided by the application
+pub struct CustomTerminal {
+    term: Shared,
+    pub(crate) color_mode: ColorMode,
+    tab_stop: usize,
+    pub(crate) bell_style: BellStyle,
+    pub(crate) enable_bracketed_paste: bool,
+}
+
+impl CustomTerminal {
+    pub fn new<T: Terminal + 'static>(term: T, config: &Config) -> Self {
+        Self {
+            term: Arc::new(Mutex::new(term)),
+            color_mode: config.color_mode(),
+            tab_stop: config.tab_stop(),
+            bell_style: config.bell_style(),
+            enable_bracketed_paste: config.enable_bracketed_paste(),
+        }
+    }
+
+    fn colors_enabled(&self) -> bool {
+        match self.color_mode {
+            ColorMode::Enabled => self.term.lock().unwrap().colors_enabled(),
+            ColorMode::Forced => true,
+            ColorMode::Disabled => false,
+        }
+    }
+
+    pub fn enable_raw_mode(&mut self) -> Result<CustomMode> {
+        let mut term = self.term.lock().unwrap();
+        term.enable_raw_mode()?;
+        let bracketed_paste = if !self.enable_bracketed_paste {
+            false
+        } else if let Err(e) = term.write(BRACKETED_PASTE_ON) {
+            debug!(target: "rustyline", "Cannot enable bracketed paste: {}", e);
+            false
+        } else {
+            true
+        };
+        drop(term);
+        let id = restore::RawModeId::new();
+        let saved = self.term.clone();
+        restore::raw_mode_enabled(
+            id,
+            Box::new(move || {
+                // the panic may have occurred while the terminal was locked
+                if let Ok(mut term) = saved.try_lock() {
+                    let _ = restore_mode(&mut *term, bracketed_paste);
+                }
+            }),
+        );
+        Ok(CustomMode {
+            term: self.term.clone(),
+            bracketed_paste,
+            id,
+        })
+    }
+
+    pub fn create_reader(&self) -> CustomReader {
+        CustomReader(self.term.clone())
+    }
+
+    pub fn create_writer(&self, config: &Config) -> CustomRenderer {
+        AnsiRenderer::new(
+            CustomOutput(self.term.clone()),
+            self.tab_stop,
+            self.colors_enabled(),
+            self.bell_style,
+            config.output_meta(),
+        )
+    }
+}
+
+impl fmt::Debug for CustomTerminal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomTerminal")
+            .field("color_mode", &self.color_mode)
+            .field("tab_stop", &self.tab_stop)
+            .field("bell_style", &self.bell_style)
+            .field("enable_bracketed_paste", &self.enable_bracketed_paste)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::vec::IntoIter;
+
+    use super::CustomTerminal;
+    use crate::config::Config;
+    use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
+    use crate::layout::Position;
+    use crate::line_buffer::LineBuffer;
+    use crate::terminal::Terminal;
+    use crate::tty::{RawMode, RawReader, Renderer};
+    use crate::Result;
+
+    #[derive(Clone)]
+    struct Replay {
+        keys: Arc<Mutex<IntoIter<KeyEvent>>>,
+        raw: Arc<Mutex<bool>>,
+        output: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Terminal for Replay {
+        fn enable_raw_mode(&mut self) -> Result<()> {
+            *self.raw.lock().unwrap() = true;
+            Ok(())
+        }
+
+        fn disable_raw_mode(&mut self) -> Result<()> {
+            *self.raw.lock().unwrap() = false;
+            Ok(())
+        }
+
+        fn size(&self) -> (usize, usize) {
+            (10, 5)
+        }
+
+        fn read_key(&mut self) -> Result<KeyEvent> {
+            Ok(self.keys.lock().unwrap().next().unwrap())
+        }
+
+        fn poll(&mut self, _: i32) -> Result<bool> {
+            Ok(self.keys.lock().unwrap().len() > 0)
+        }
+
+        fn write(&mut self, buf: &[u8]) -> Result<()> {
+            self.output.lock().unwrap().extend_from_slice(buf);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn custom_terminal() {
+        let keys = vec![
+            E::from('a'),
+            E(K::BracketedPasteStart, M::NONE),
+            E::from('b'),
+            E(K::Enter, M::NONE),
+            E(K::BracketedPasteEnd, M::NONE),
+        ];
+        let replay = Replay {
+            keys: Arc::new(Mutex::new(keys.into_iter())),
+            raw: Arc::default(),
+            output: Arc::default(),
+        };
+        let config = Config::builder().bracketed_paste(true).build();
+        let mut term = CustomTerminal::new(replay.clone(), &config);
+
+        let mode = term.enable_raw_mode().unwrap();
+        assert!(*replay.raw.lock().unwrap());
+        let mut rdr = term.create_reader();
+        assert!(rdr.wait_for_input(0).unwrap());
+        assert_eq!(E::from('a'), rdr.next_key(false).unwrap());
+        rdr.next_key(false).unwrap();
+        assert_eq!("b\n", rdr.read_pasted_text().unwrap());
+        assert!(!rdr.wait_for_input(0).unwrap());
+
+        let mut out = term.create_writer(&config);
+        assert_eq!(10, out.get_columns());
+        let line = LineBuffer::init("0123456789ab", 12, None);
+        let layout = out.compute_layout(Position::default(), true, &line, None);
+        assert_eq!(Position { col: 2, row: 1 }, layout.end);
+        replay.output.lock().unwrap().clear();
+        out.clear_screen().unwrap();
+        assert_eq!(b"\x1b[H\x1b[2J", &replay.output.lock().unwrap()[..]);
+
+        mode.disable_raw_mode().unwrap();
+        assert!(!*replay.raw.lock().unwrap());
+        assert!(replay.output.lock().unwrap().ends_with(b"\x1b[?2004l"));
+    }
+}
@@ -1,5 +1,7 @@
 //! This module implements and describes common TTY methods & traits
 
+use std::borrow::Cow;
+
 use unicode_width::UnicodeWidthStr;
 
 use crate::config::{BellStyle, ColorMode, Config, OutputStreamType};
@@ -19,6 +21,10 @@ pub trait RawMode: Sized {
 pub trait RawReader {
     /// Blocking read of key pressed.
     fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyEvent>;
+    /// Wait at most `timeout_ms` (indefinitely if negative) for input and tell
+    /// if there is some: only a key press counts, not the input ignored by
+    /// `next_key` (key release, focus, mouse, ...).
+    fn wait_for_input(&mut self, timeout_ms: i32) -> Result<bool>;
     /// For CTRL-V support
     #[cfg(unix)]
     fn next_char(&mut self) -> Result<char>;
@@ -26,6 +32,19 @@ pub trait RawReader {
     fn read_pasted_text(&mut self) -> Result<String>;
 }
 
+/// Cursor shape, set with the DECSCUSR escape sequence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CursorShape {
+    /// Terminal default
+    Default = 0,
+    /// Steady block
+    Block = 2,
+    /// Steady underline
+    Underline = 4,
+    /// Steady bar
+    Bar = 6,
+}
+
 /// Display prompt, line and cursor in terminal output
 pub trait Renderer {
     type Reader: RawReader;
@@ -56,12 +75,12 @@ pub trait Renderer {
     ) -> Layout {
         // calculate the desired position of the cursor
         let pos = line.pos();
-        let cursor = self.calculate_position(&line[..pos], prompt_size);
+        let cursor = self.calculate_position(&escape_control(&line[..pos]), prompt_size);
         // calculate the position of the end of the input line
         let mut end = if pos == line.len() {
             cursor
         } else {
-            self.calculate_position(&line[pos..], cursor)
+            self.calculate_position(&escape_control(&line[pos..]), cursor)
         };
         if let Some(info) = info {
             end = self.calculate_position(&info, end);
@@ -91,8 +110,17 @@ pub trait Renderer {
     /// Clear the screen. Used to handle ctrl+l
     fn clear_screen(&mut self) -> Result<()>;
 
+    /// Copy `text` to the system clipboard (best effort)
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()>;
+
+    /// Change the cursor shape
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()>;
+
     /// Check if a SIGWINCH signal has been received
     fn sigwinch(&self) -> bool;
+    /// Check if a SIGCONT signal has been received, raw mode being enabled
+    /// again
+    fn sigcont(&self) -> bool;
     /// Update the number of columns/rows in the current terminal.
     fn update_size(&mut self);
     /// Get the number of columns in the current terminal.
@@ -104,6 +132,10 @@ pub trait Renderer {
 
     /// Make sure prompt is at the leftmost edge of the screen
     fn move_cursor_at_leftmost(&mut self, rdr: &mut Self::Reader) -> Result<()>;
+
+    /// Absolute cursor position on the screen (0-based), if it can be
+    /// queried.
+    fn cursor_position(&mut self, rdr: &mut Self::Reader) -> Result<Option<Position>>;
 }
 
 impl<'a, R: Renderer + ?Sized> Renderer for &'a mut R {
@@ -141,10 +173,22 @@ impl<'a, R: Renderer + ?Sized> Renderer for &'a mut R {
         (**self).clear_screen()
     }
 
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        (**self).copy_to_clipboard(text)
+    }
+
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        (**self).set_cursor_shape(shape)
+    }
+
     fn sigwinch(&self) -> bool {
         (**self).sigwinch()
     }
 
+    fn sigcont(&self) -> bool {
+        (**self).sigcont()
+    }
+
     fn update_size(&mut self) {
         (**self).update_size()
     }
@@ -164,6 +208,10 @@ impl<'a, R: Renderer + ?Sized> Renderer for &'a mut R {
     fn move_cursor_at_leftmost(&mut self, rdr: &mut R::Reader) -> Result<()> {
         (**self).move_cursor_at_leftmost(rdr)
     }
+
+    fn cursor_position(&mut self, rdr: &mut R::Reader) -> Result<Option<Position>> {
+        (**self).cursor_position(rdr)
+    }
 }
 
 // ignore ANSI escape sequence
@@ -197,6 +245,42 @@ fn width(s: &str, esc_seq: &mut u8) -> usize {
     }
 }
 
+/// Display control characters (except newline and tab) in caret notation
+/// (e.g. `^[` for escape), like readline does.
+pub(crate) fn escape_control(s: &str) -> Cow<'_, str> {
+    let is_escaped = |c: char| c.is_ascii_control() && c != '\n' && c != '\t';
+    if !s.chars().any(is_escaped) {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len() + 4);
+    for c in s.chars() {
+        if is_escaped(c) {
+            escaped.push('^');
+            escaped.push(char::from(c as u8 ^ 0x40));
+        } else {
+            escaped.push(c);
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Input line as displayed: control characters are escaped before
+/// highlighting.
+pub(crate) fn display_line<'l>(
+    line: &'l LineBuffer,
+    highlighter: Option<&dyn Highlighter>,
+) -> Cow<'l, str> {
+    let escaped = escape_control(line);
+    match (highlighter, escaped) {
+        (Some(highlighter), Cow::Borrowed(_)) => highlighter.highlight(line, line.pos()),
+        (Some(highlighter), Cow::Owned(escaped)) => {
+            let pos = escape_control(&line[..line.pos()]).len();
+            Cow::Owned(highlighter.highlight(&escaped, pos).into_owned())
+        }
+        (None, escaped) => escaped,
+    }
+}
+
 /// Terminal contract
 pub trait Term {
     type Reader: RawReader; // rl_instream
@@ -222,9 +306,25 @@ pub trait Term {
     /// Create a RAW reader
     fn create_reader(&self, config: &Config) -> Result<Self::Reader>;
     /// Create a writer
-    fn create_writer(&self) -> Self::Writer;
+    fn create_writer(&self, config: &Config) -> Self::Writer;
 }
 
+mod ansi;
+pub use self::ansi::*;
+mod terminfo;
+pub use self::terminfo::Capabilities;
+mod restore;
+
+// Backend provided by the application and dispatch between it and the
+// platform one
+mod custom;
+#[cfg(not(test))]
+pub use self::custom::*;
+#[cfg(not(test))]
+mod backend;
+#[cfg(not(test))]
+pub use self::backend::*;
+
 // If on Windows platform import Windows TTY module
 // and re-export into mod.rs scope
 #[cfg(all(windows, not(target_arch = "wasm32")))]
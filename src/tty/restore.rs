@@ -0,0 +1,145 @@
+//! Restoration of the terminal when the process panics or exits while it is
+//! in raw mode
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, Once, TryLockError};
+use std::thread::{self, ThreadId};
+
+type Restore = Box<dyn Fn() + Send>;
+
+/// Identify a raw mode enabled, to unregister it once disabled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct RawModeId(usize);
+
+impl RawModeId {
+    pub(crate) fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+struct Entry {
+    id: RawModeId,
+    /// Thread which enabled the raw mode
+    thread: ThreadId,
+    restore: Restore,
+}
+
+/// How to restore each raw mode currently enabled, from the outermost one
+static RAW_MODES: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+
+fn raw_modes() -> Option<MutexGuard<'static, Vec<Entry>>> {
+    // the panic may have occurred while the lock was held
+    match RAW_MODES.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Register how to restore the mode saved by the raw mode `id` just enabled
+/// by the current thread
+pub(crate) fn raw_mode_enabled(id: RawModeId, restore: Restore) {
+    install_hooks();
+    if let Some(mut modes) = raw_modes() {
+        modes.push(Entry {
+            id,
+            thread: thread::current().id(),
+            restore,
+        });
+    }
+}
+
+/// Unregister the raw mode `id`, just disabled
+pub(crate) fn raw_mode_disabled(id: RawModeId) {
+    if let Some(mut modes) = raw_modes() {
+        modes.retain(|entry| entry.id != id);
+    }
+}
+
+/// Restore the mode saved by the outermost raw mode enabled by the current
+/// thread, if any: raw modes of other threads are left untouched.
+fn restore_thread_modes() {
+    let thread = thread::current().id();
+    if let Some(mut modes) = raw_modes() {
+        if let Some(entry) = modes.iter().find(|entry| entry.thread == thread) {
+            (entry.restore)();
+        }
+        modes.retain(|entry| entry.thread != thread);
+    }
+}
+
+/// Restore the mode saved by the outermost raw mode, if any
+#[cfg(unix)]
+fn restore_cooked_mode() {
+    if let Some(mut modes) = raw_modes() {
+        if let Some(entry) = modes.first() {
+            (entry.restore)();
+        }
+        modes.clear();
+    }
+}
+
+fn install_hooks() {
+    static HOOKS: Once = Once::new();
+    HOOKS.call_once(|| {
+        // before the panic message is printed
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_thread_modes();
+            hook(info)
+        }));
+        #[cfg(unix)]
+        unsafe {
+            libc::atexit(restore_at_exit);
+        }
+    });
+}
+
+#[cfg(unix)]
+extern "C" fn restore_at_exit() {
+    restore_cooked_mode()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::RawModeId;
+
+    fn enable(restored: &Arc<AtomicUsize>, depth: usize) -> RawModeId {
+        let restored = restored.clone();
+        let id = RawModeId::new();
+        super::raw_mode_enabled(
+            id,
+            Box::new(move || {
+                restored.store(depth, Ordering::SeqCst);
+            }),
+        );
+        id
+    }
+
+    #[test]
+    fn restore_thread_modes() {
+        let restored = Arc::new(AtomicUsize::new(0));
+        let outer = enable(&restored, 1);
+        let inner = enable(&restored, 2);
+        let innermost = enable(&restored, 3);
+        // not the last one
+        super::raw_mode_disabled(inner);
+        // raw modes of other threads
+        thread::spawn(super::restore_thread_modes).join().unwrap();
+        assert_eq!(0, restored.load(Ordering::SeqCst));
+        super::restore_thread_modes();
+        // the outermost one
+        assert_eq!(1, restored.load(Ordering::SeqCst));
+        restored.store(0, Ordering::SeqCst);
+        super::restore_thread_modes();
+        assert_eq!(0, restored.load(Ordering::SeqCst));
+        // already unregistered
+        super::raw_mode_disabled(outer);
+        super::raw_mode_disabled(innermost);
+    }
+}
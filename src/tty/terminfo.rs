@@ -0,0 +1,226 @@
+//! Control sequences of the terminal, looked up in its terminfo entry or
+//! ANSI (VT100) ones when it has no entry
+use std::fmt::Write;
+#[cfg(all(unix, feature = "with-terminfo"))]
+use std::sync::Arc;
+
+#[cfg(all(unix, feature = "with-terminfo"))]
+use log::debug;
+#[cfg(all(unix, feature = "with-terminfo"))]
+use term::terminfo::parm::{expand, Param, Variables};
+#[cfg(all(unix, feature = "with-terminfo"))]
+use term::terminfo::TermInfo;
+
+/// Cursor movement, clear and attribute sequences of a terminal
+///
+/// The default capabilities are the ANSI ones.
+#[derive(Clone, Debug, Default)]
+pub struct Capabilities {
+    #[cfg(all(unix, feature = "with-terminfo"))]
+    terminfo: Option<Arc<TermInfo>>,
+}
+
+impl Capabilities {
+    /// Capabilities of the terminal named by the `TERM` environment
+    /// variable
+    pub fn from_env() -> Self {
+        #[cfg(all(unix, feature = "with-terminfo"))]
+        {
+            match TermInfo::from_env() {
+                Ok(terminfo) => return Self::from_terminfo(terminfo),
+                Err(e) => debug!(target: "rustyline", "no terminfo entry: {}", e),
+            }
+        }
+        Self::default()
+    }
+
+    #[cfg(all(unix, feature = "with-terminfo"))]
+    fn from_terminfo(terminfo: TermInfo) -> Self {
+        Self {
+            terminfo: Some(Arc::new(terminfo)),
+        }
+    }
+
+    /// Expand the capability `name` with the numeric `params`, or `None` if
+    /// the terminal doesn't have it
+    #[cfg(all(unix, feature = "with-terminfo"))]
+    fn expand(&self, name: &str, params: &[usize]) -> Option<String> {
+        let cap = self.terminfo.as_ref()?.strings.get(name)?;
+        let params: Vec<Param> = params.iter().map(|&n| Param::Number(n as i32)).collect();
+        let seq = expand(cap, &params, &mut Variables::new()).ok()?;
+        String::from_utf8(seq).ok()
+    }
+
+    #[cfg(not(all(unix, feature = "with-terminfo")))]
+    fn expand(&self, _name: &str, _params: &[usize]) -> Option<String> {
+        None
+    }
+
+    #[cfg(all(unix, feature = "with-terminfo"))]
+    fn has_terminfo(&self) -> bool {
+        self.terminfo.is_some()
+    }
+
+    #[cfg(not(all(unix, feature = "with-terminfo")))]
+    fn has_terminfo(&self) -> bool {
+        false
+    }
+
+    /// Append the first capability found among `names`, or the `ansi`
+    /// sequence if the terminal has no terminfo entry: a capability missing
+    /// from its entry is unsupported and nothing is appended.
+    fn push(&self, buf: &mut String, names: &[(&str, &[usize])], ansi: std::fmt::Arguments<'_>) {
+        match names
+            .iter()
+            .find_map(|(name, params)| self.expand(name, params))
+        {
+            Some(seq) => buf.push_str(&seq),
+            None if !self.has_terminfo() => buf.write_fmt(ansi).unwrap(),
+            None => {}
+        }
+    }
+
+    /// Move the cursor `n` rows up
+    pub fn cursor_up(&self, buf: &mut String, n: usize) {
+        if n == 1 {
+            self.push(buf, &[("cuu1", &[]), ("cuu", &[1])], format_args!("\x1b[A"));
+        } else {
+            self.push(buf, &[("cuu", &[n])], format_args!("\x1b[{}A", n));
+        }
+    }
+
+    /// Move the cursor `n` rows down
+    pub fn cursor_down(&self, buf: &mut String, n: usize) {
+        // `cud1` is usually a line feed, which would also move the cursor to
+        // the first column.
+        if n == 1 {
+            self.push(buf, &[("cud", &[1])], format_args!("\x1b[B"));
+        } else {
+            self.push(buf, &[("cud", &[n])], format_args!("\x1b[{}B", n));
+        }
+    }
+
+    /// Move the cursor `n` columns right
+    pub fn cursor_right(&self, buf: &mut String, n: usize) {
+        if n == 1 {
+            self.push(buf, &[("cuf1", &[]), ("cuf", &[1])], format_args!("\x1b[C"));
+        } else {
+            self.push(buf, &[("cuf", &[n])], format_args!("\x1b[{}C", n));
+        }
+    }
+
+    /// Move the cursor `n` columns left
+    pub fn cursor_left(&self, buf: &mut String, n: usize) {
+        if n == 1 {
+            self.push(buf, &[("cub1", &[]), ("cub", &[1])], format_args!("\x1b[D"));
+        } else {
+            self.push(buf, &[("cub", &[n])], format_args!("\x1b[{}D", n));
+        }
+    }
+
+    /// Clear from the cursor to the end of the line
+    pub fn clear_to_eol(&self, buf: &mut String) {
+        self.push(buf, &[("el", &[])], format_args!("\x1b[0K"));
+    }
+
+    /// Clear the screen and move the cursor to its top left corner
+    pub fn clear_screen(&self, buf: &mut String) {
+        self.push(buf, &[("clear", &[])], format_args!("\x1b[H\x1b[2J"));
+    }
+
+    /// Check if the cursor can be moved up: only a terminfo entry without
+    /// `cuu1` nor `cuu` (like `dumb`) tells it can't.
+    pub fn cursor_movement(&self) -> bool {
+        #[cfg(all(unix, feature = "with-terminfo"))]
+        {
+            if let Some(ref terminfo) = self.terminfo {
+                return ["cuu1", "cuu"]
+                    .iter()
+                    .any(|name| terminfo.strings.contains_key(name));
+            }
+        }
+        true
+    }
+
+    /// Check if text attributes (colors, bold, reverse video, ...) are
+    /// supported: only a terminfo entry without any of them tells they
+    /// aren't.
+    pub fn attributes(&self) -> bool {
+        #[cfg(all(unix, feature = "with-terminfo"))]
+        {
+            if let Some(ref terminfo) = self.terminfo {
+                return ["sgr0", "sgr", "setaf", "bold", "rev", "smul"]
+                    .iter()
+                    .any(|name| terminfo.strings.contains_key(name));
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Capabilities;
+
+    #[test]
+    fn ansi() {
+        let caps = Capabilities::default();
+        let mut buf = String::new();
+        caps.cursor_up(&mut buf, 1);
+        caps.cursor_up(&mut buf, 2);
+        caps.cursor_down(&mut buf, 3);
+        caps.cursor_right(&mut buf, 4);
+        caps.cursor_left(&mut buf, 1);
+        caps.clear_to_eol(&mut buf);
+        caps.clear_screen(&mut buf);
+        assert_eq!("\x1b[A\x1b[2A\x1b[3B\x1b[4C\x1b[D\x1b[0K\x1b[H\x1b[2J", buf);
+        assert!(caps.cursor_movement());
+        assert!(caps.attributes());
+    }
+
+    #[cfg(all(unix, feature = "with-terminfo"))]
+    #[test]
+    fn terminfo() {
+        use std::collections::HashMap;
+        use term::terminfo::TermInfo;
+
+        let mut strings = HashMap::new();
+        strings.insert("cuu1", b"\x1bA".to_vec());
+        strings.insert("cuu", b"\x1b[%p1%dA".to_vec());
+        strings.insert("cuf", b"\x1b[%p1%dC".to_vec());
+        strings.insert("cub1", b"\x08".to_vec());
+        strings.insert("el", b"\x1bK".to_vec());
+        strings.insert("clear", b"\x1bH\x1bJ$<50>".to_vec());
+        let caps = Capabilities::from_terminfo(TermInfo {
+            names: vec!["vt52ish".to_owned()],
+            bools: HashMap::new(),
+            numbers: HashMap::new(),
+            strings,
+        });
+        let mut buf = String::new();
+        caps.cursor_up(&mut buf, 1);
+        caps.cursor_up(&mut buf, 2);
+        // no `cud`: unsupported
+        caps.cursor_down(&mut buf, 1);
+        // no `cuf1`: `cuf`
+        caps.cursor_right(&mut buf, 1);
+        caps.cursor_left(&mut buf, 1);
+        caps.clear_to_eol(&mut buf);
+        caps.clear_screen(&mut buf);
+        assert_eq!("\x1bA\x1b[2A\x1b[1C\x08\x1bK\x1bH\x1bJ", buf);
+        assert!(caps.cursor_movement());
+        assert!(!caps.attributes());
+
+        let dumb = Capabilities::from_terminfo(TermInfo {
+            names: vec!["dumb".to_owned()],
+            bools: HashMap::new(),
+            numbers: HashMap::new(),
+            strings: HashMap::new(),
+        });
+        assert!(!dumb.cursor_movement());
+        let mut buf = String::new();
+        dumb.cursor_up(&mut buf, 1);
+        dumb.clear_screen(&mut buf);
+        assert_eq!("", buf);
+    }
+}
@@ -3,7 +3,7 @@ use std::iter::IntoIterator;
 use std::slice::Iter;
 use std::vec::IntoIter;
 
-use super::{RawMode, RawReader, Renderer, Term};
+use super::{CursorShape, RawMode, RawReader, Renderer, Term};
 use crate::config::{BellStyle, ColorMode, Config, OutputStreamType};
 use crate::error::ReadlineError;
 use crate::highlight::Highlighter;
@@ -12,9 +12,10 @@ use crate::layout::{Layout, Position};
 use crate::line_buffer::LineBuffer;
 use crate::Result;
 
+#[cfg(test)]
 pub type Mode = ();
 
-impl RawMode for Mode {
+impl RawMode for () {
     fn disable_raw_mode(&self) -> Result<()> {
         Ok(())
     }
@@ -28,6 +29,10 @@ impl<'a> RawReader for Iter<'a, KeyEvent> {
         }
     }
 
+    fn wait_for_input(&mut self, _: i32) -> Result<bool> {
+        Ok(!self.as_slice().is_empty())
+    }
+
     #[cfg(unix)]
     fn next_char(&mut self) -> Result<char> {
         unimplemented!();
@@ -46,18 +51,31 @@ impl RawReader for IntoIter<KeyEvent> {
         }
     }
 
+    fn wait_for_input(&mut self, _: i32) -> Result<bool> {
+        Ok(!self.as_slice().is_empty())
+    }
+
     #[cfg(unix)]
     fn next_char(&mut self) -> Result<char> {
-        use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
         match self.next() {
-            Some(E(K::Char(c), M::NONE)) => Ok(c),
+            Some(key) => Ok(key.to_char().unwrap()),
             None => Err(ReadlineError::Eof),
-            _ => unimplemented!(),
         }
     }
 
     fn read_pasted_text(&mut self) -> Result<String> {
-        unimplemented!()
+        use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+        let mut buffer = String::new();
+        loop {
+            match self.next() {
+                Some(E(K::BracketedPasteEnd, _)) => return Ok(buffer),
+                Some(E(K::Char(c), M::NONE)) | Some(E(K::Char(c), M::SHIFT)) => buffer.push(c),
+                Some(E(K::Enter, _)) => buffer.push('\n'),
+                Some(E(K::Tab, _)) => buffer.push('\t'),
+                None => return Err(ReadlineError::Eof),
+                _ => unimplemented!(),
+            }
+        }
     }
 }
 
@@ -106,10 +124,23 @@ impl Renderer for Sink {
         Ok(())
     }
 
+    fn copy_to_clipboard(&mut self, _: &str) -> Result<()> {
+        // like a busy clipboard
+        Err(std::io::Error::other("clipboard busy").into())
+    }
+
+    fn set_cursor_shape(&mut self, _: CursorShape) -> Result<()> {
+        Ok(())
+    }
+
     fn sigwinch(&self) -> bool {
         false
     }
 
+    fn sigcont(&self) -> bool {
+        false
+    }
+
     fn update_size(&mut self) {}
 
     fn get_columns(&self) -> usize {
@@ -127,9 +158,16 @@ impl Renderer for Sink {
     fn move_cursor_at_leftmost(&mut self, _: &mut IntoIter<KeyEvent>) -> Result<()> {
         Ok(())
     }
+
+    fn cursor_position(&mut self, _: &mut IntoIter<KeyEvent>) -> Result<Option<Position>> {
+        Ok(None)
+    }
 }
 
+#[cfg(test)]
 pub type Terminal = DummyTerminal;
+#[cfg(not(test))]
+pub type NativeTerminal = DummyTerminal;
 
 #[derive(Clone, Debug)]
 pub struct DummyTerminal {
@@ -137,10 +175,26 @@ pub struct DummyTerminal {
     pub cursor: usize, // cursor position before last command
     pub color_mode: ColorMode,
     pub bell_style: BellStyle,
+    pub enable_bracketed_paste: bool,
+}
+
+#[cfg(test)]
+impl DummyTerminal {
+    pub(crate) fn set_color_mode(&mut self, color_mode: ColorMode) {
+        self.color_mode = color_mode;
+    }
+
+    pub(crate) fn set_bell_style(&mut self, bell_style: BellStyle) {
+        self.bell_style = bell_style;
+    }
+
+    pub(crate) fn set_enable_bracketed_paste(&mut self, enabled: bool) {
+        self.enable_bracketed_paste = enabled;
+    }
 }
 
 impl Term for DummyTerminal {
-    type Mode = Mode;
+    type Mode = ();
     type Reader = IntoIter<KeyEvent>;
     type Writer = Sink;
 
@@ -149,13 +203,14 @@ impl Term for DummyTerminal {
         _stream: OutputStreamType,
         _tab_stop: usize,
         bell_style: BellStyle,
-        _enable_bracketed_paste: bool,
+        enable_bracketed_paste: bool,
     ) -> DummyTerminal {
         DummyTerminal {
             keys: Vec::new(),
             cursor: 0,
             color_mode,
             bell_style,
+            enable_bracketed_paste,
         }
     }
 
@@ -181,7 +236,7 @@ impl Term for DummyTerminal {
 
     // Interactive loop:
 
-    fn enable_raw_mode(&mut self) -> Result<Mode> {
+    fn enable_raw_mode(&mut self) -> Result<()> {
         Ok(())
     }
 
@@ -189,7 +244,7 @@ impl Term for DummyTerminal {
         Ok(self.keys.clone().into_iter())
     }
 
-    fn create_writer(&self) -> Sink {
+    fn create_writer(&self, _: &Config) -> Sink {
         Sink::new()
     }
 }
@@ -1,5 +1,4 @@
 //! Unix specific definitions
-use std::cmp;
 use std::io::{self, ErrorKind, Read, Write};
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::sync;
@@ -10,16 +9,14 @@ use nix::poll::{self, PollFlags};
 use nix::sys::signal;
 use nix::sys::termios;
 use nix::sys::termios::SetArg;
-use unicode_segmentation::UnicodeSegmentation;
 use utf8parse::{Parser, Receiver};
 
-use super::{width, RawMode, RawReader, Renderer, Term};
+use super::{restore, AnsiOutput, AnsiRenderer, Capabilities, RawMode, RawReader, Term};
 use crate::config::{BellStyle, ColorMode, Config, OutputStreamType};
 use crate::error;
-use crate::highlight::Highlighter;
 use crate::keys::{KeyCode as K, KeyEvent, KeyEvent as E, Modifiers as M};
-use crate::layout::{Layout, Position};
-use crate::line_buffer::LineBuffer;
+use crate::keys::{MouseButton, MouseEvent, MouseEventKind};
+use crate::layout::Position;
 use crate::Result;
 
 const STDIN_FILENO: RawFd = libc::STDIN_FILENO;
@@ -96,17 +93,15 @@ fn is_a_tty(fd: RawFd) -> bool {
 }
 
 #[must_use = "You must restore default mode (disable_raw_mode)"]
+#[derive(Clone)]
 pub struct PosixMode {
     termios: termios::Termios,
     out: Option<OutputStreamType>,
+    id: restore::RawModeId,
 }
 
-#[cfg(not(test))]
-pub type Mode = PosixMode;
-
-impl RawMode for PosixMode {
-    /// Disable RAW mode for the terminal.
-    fn disable_raw_mode(&self) -> Result<()> {
+impl PosixMode {
+    fn restore(&self) -> Result<()> {
         termios::tcsetattr(STDIN_FILENO, SetArg::TCSADRAIN, &self.termios)?;
         // disable bracketed paste
         if let Some(out) = self.out {
@@ -116,6 +111,14 @@ impl RawMode for PosixMode {
     }
 }
 
+impl RawMode for PosixMode {
+    /// Disable RAW mode for the terminal.
+    fn disable_raw_mode(&self) -> Result<()> {
+        restore::raw_mode_disabled(self.id);
+        self.restore()
+    }
+}
+
 // Rust std::io::Stdin is buffered with no way to know if bytes are available.
 // So we use low-level stuff instead...
 struct StdinRaw {}
@@ -132,6 +135,10 @@ impl Read for StdinRaw {
             };
             if res == -1 {
                 let error = io::Error::last_os_error();
+                if error.kind() == ErrorKind::Interrupted && SIGCONT.load(Ordering::Relaxed) {
+                    resume_raw_mode();
+                    return Err(error);
+                }
                 if error.kind() != ErrorKind::Interrupted || SIGWINCH.load(Ordering::Relaxed) {
                     return Err(error);
                 }
@@ -150,6 +157,10 @@ pub struct PosixRawReader {
     buf: [u8; 1],
     parser: Parser,
     receiver: Utf8,
+    convert_meta: bool,
+    input_meta: bool,
+    /// Character following the ESC of a converted meta byte
+    meta: Option<char>,
 }
 
 struct Utf8 {
@@ -194,6 +205,9 @@ impl PosixRawReader {
                 c: None,
                 valid: true,
             },
+            convert_meta: config.convert_meta(),
+            input_meta: config.input_meta(),
+            meta: None,
         }
     }
 
@@ -268,6 +282,29 @@ impl PosixRawReader {
                     self.extended_escape(seq2)
                 }
             }
+        } else if seq2 == 'M' {
+            // X10 mouse report: \E[M <button> <column> <row>, offset by 32
+            let cb = u32::from(self.next_char()?);
+            let cx = u32::from(self.next_char()?);
+            let cy = u32::from(self.next_char()?);
+            Ok(mouse_event(
+                cb.saturating_sub(32),
+                cx.saturating_sub(33),
+                cy.saturating_sub(33),
+                false,
+            ))
+        } else if seq2 == '<' {
+            // SGR mouse report: \E[< <button> ; <column> ; <row> (M | m)
+            let (cb, cx, cy, release) = match self.sgr_mouse_report()? {
+                Some(report) => report,
+                None => return Ok(E(K::UnknownEscSeq, M::NONE)),
+            };
+            Ok(mouse_event(
+                cb,
+                cx.saturating_sub(1),
+                cy.saturating_sub(1),
+                release,
+            ))
         } else if seq2 == '[' {
             let seq3 = self.next_char()?;
             // Linux console
@@ -293,10 +330,12 @@ impl PosixRawReader {
                 END => E(K::End, M::NONE),
                 //'G' => E(K::, M::), // Ignore
                 HOME => E(K::Home, M::NONE), // khome
+                'I' => E(K::FocusGained, M::NONE),
                 //'J' => E(K::, M::), // clr_eos
                 //'K' => E(K::, M::), // clr_eol
                 //'L' => E(K::, M::), // il1
                 //'M' => E(K::, M::), // kmous
+                'O' => E(K::FocusLost, M::NONE),
                 //'P' => E(K::Delete, M::NONE), // dch1
                 'Z' => E(K::BackTab, M::NONE),
                 'a' => E(K::Up, M::SHIFT),    // rxvt: kind or kUP
@@ -311,6 +350,29 @@ impl PosixRawReader {
         }
     }
 
+    /// Read the parameters of a SGR mouse report
+    fn sgr_mouse_report(&mut self) -> Result<Option<(u32, u32, u32, bool)>> {
+        let mut params = [0u32; 3];
+        let mut i = 0;
+        loop {
+            match self.next_char()? {
+                digit @ '0'..='9' => {
+                    params[i] = params[i]
+                        .saturating_mul(10)
+                        .saturating_add(digit.to_digit(10).unwrap());
+                }
+                ';' if i < 2 => i += 1,
+                c @ 'M' | c @ 'm' if i == 2 => {
+                    return Ok(Some((params[0], params[1], params[2], c == 'm')));
+                }
+                c => {
+                    debug!(target: "rustyline", "unsupported mouse report: \\E[<...{:?}", c);
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
     /// Handle \E[ <seq2:digit> escape sequences
     #[allow(clippy::cognitive_complexity)]
     fn extended_escape(&mut self, seq2: char) -> Result<KeyEvent> {
@@ -608,18 +670,18 @@ impl PosixRawReader {
     fn escape_o(&mut self) -> Result<KeyEvent> {
         let seq2 = self.next_char()?;
         Ok(match seq2 {
-            UP => E(K::Up, M::NONE),
-            DOWN => E(K::Down, M::NONE),
-            RIGHT => E(K::Right, M::NONE),
-            LEFT => E(K::Left, M::NONE),
+            UP => E(K::Up, M::KEYPAD),
+            DOWN => E(K::Down, M::KEYPAD),
+            RIGHT => E(K::Right, M::KEYPAD),
+            LEFT => E(K::Left, M::KEYPAD),
             //'E' => E(K::, M::),// key_b2, kb2
-            END => E(K::End, M::NONE),   // kend
-            HOME => E(K::Home, M::NONE), // khome
-            'M' => E::ENTER,             // kent
-            'P' => E(K::F(1), M::NONE),  // kf1
-            'Q' => E(K::F(2), M::NONE),  // kf2
-            'R' => E(K::F(3), M::NONE),  // kf3
-            'S' => E(K::F(4), M::NONE),  // kf4
+            END => E(K::End, M::KEYPAD),   // kend
+            HOME => E(K::Home, M::KEYPAD), // khome
+            'M' => E(K::Enter, M::KEYPAD), // kent
+            'P' => E(K::F(1), M::NONE),    // kf1
+            'Q' => E(K::F(2), M::NONE),    // kf2
+            'R' => E(K::F(3), M::NONE),    // kf3
+            'S' => E(K::F(4), M::NONE),    // kf4
             'a' => E(K::Up, M::CTRL),
             'b' => E(K::Down, M::CTRL),
             'c' => E(K::Right, M::CTRL), // rxvt
@@ -643,7 +705,10 @@ impl PosixRawReader {
         match r {
             Ok(_) => r,
             Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => {
-                if SIGWINCH.load(Ordering::Relaxed) {
+                if SIGCONT.load(Ordering::Relaxed) {
+                    resume_raw_mode();
+                    r
+                } else if SIGWINCH.load(Ordering::Relaxed) {
                     r
                 } else {
                     Ok(0) // Ignore EINTR while polling
@@ -665,7 +730,12 @@ impl RawReader for PosixRawReader {
             } else {
                 self.timeout_ms
             };
-            match self.poll(timeout_ms) {
+            let ready = if self.meta.is_some() {
+                Ok(1)
+            } else {
+                self.poll(timeout_ms)
+            };
+            match ready {
                 Ok(n) if n == 0 => {
                     // single escape
                 }
@@ -681,13 +751,35 @@ impl RawReader for PosixRawReader {
         Ok(key)
     }
 
+    fn wait_for_input(&mut self, timeout_ms: i32) -> Result<bool> {
+        if self.meta.is_some() {
+            return Ok(true);
+        }
+        match self.poll(timeout_ms) {
+            Ok(n) => Ok(n > 0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     fn next_char(&mut self) -> Result<char> {
+        if let Some(c) = self.meta.take() {
+            return Ok(c);
+        }
         loop {
             let n = self.stdin.read(&mut self.buf)?;
             if n == 0 {
                 return Err(error::ReadlineError::Eof);
             }
             let b = self.buf[0];
+            if b & 0x80 != 0 {
+                if self.convert_meta {
+                    // like readline: ESC followed by the byte without its eighth bit
+                    self.meta = Some(char::from(b & 0x7f));
+                    return Ok('\x1b');
+                } else if !self.input_meta {
+                    return Ok(char::from(b & 0x7f));
+                }
+            }
             self.parser.advance(&mut self.receiver, b);
             if !self.receiver.valid {
                 return Err(error::ReadlineError::Utf8Error);
@@ -733,206 +825,17 @@ impl Receiver for Utf8 {
 }
 
 /// Console output writer
-pub struct PosixRenderer {
-    out: OutputStreamType,
-    cols: usize, // Number of columns in terminal
-    buffer: String,
-    tab_stop: usize,
-    colors_enabled: bool,
-    bell_style: BellStyle,
-}
+pub type PosixRenderer = AnsiRenderer<OutputStreamType>;
 
-impl PosixRenderer {
-    fn new(
-        out: OutputStreamType,
-        tab_stop: usize,
-        colors_enabled: bool,
-        bell_style: BellStyle,
-    ) -> Self {
-        let (cols, _) = get_win_size(&out);
-        Self {
-            out,
-            cols,
-            buffer: String::with_capacity(1024),
-            tab_stop,
-            colors_enabled,
-            bell_style,
-        }
-    }
-
-    fn clear_old_rows(&mut self, layout: &Layout) {
-        use std::fmt::Write;
-        let current_row = layout.cursor.row;
-        let old_rows = layout.end.row;
-        // old_rows < cursor_row if the prompt spans multiple lines and if
-        // this is the default State.
-        let cursor_row_movement = old_rows.saturating_sub(current_row);
-        // move the cursor down as required
-        if cursor_row_movement > 0 {
-            write!(self.buffer, "\x1b[{}B", cursor_row_movement).unwrap();
-        }
-        // clear old rows
-        for _ in 0..old_rows {
-            self.buffer.push_str("\r\x1b[0K\x1b[A");
-        }
-        // clear the line
-        self.buffer.push_str("\r\x1b[0K");
-    }
-}
-
-impl Renderer for PosixRenderer {
+impl AnsiOutput for OutputStreamType {
     type Reader = PosixRawReader;
 
-    fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
-        use std::fmt::Write;
-        self.buffer.clear();
-        let row_ordering = new.row.cmp(&old.row);
-        if row_ordering == cmp::Ordering::Greater {
-            // move down
-            let row_shift = new.row - old.row;
-            if row_shift == 1 {
-                self.buffer.push_str("\x1b[B");
-            } else {
-                write!(self.buffer, "\x1b[{}B", row_shift).unwrap();
-            }
-        } else if row_ordering == cmp::Ordering::Less {
-            // move up
-            let row_shift = old.row - new.row;
-            if row_shift == 1 {
-                self.buffer.push_str("\x1b[A");
-            } else {
-                write!(self.buffer, "\x1b[{}A", row_shift).unwrap();
-            }
-        }
-        let col_ordering = new.col.cmp(&old.col);
-        if col_ordering == cmp::Ordering::Greater {
-            // move right
-            let col_shift = new.col - old.col;
-            if col_shift == 1 {
-                self.buffer.push_str("\x1b[C");
-            } else {
-                write!(self.buffer, "\x1b[{}C", col_shift).unwrap();
-            }
-        } else if col_ordering == cmp::Ordering::Less {
-            // move left
-            let col_shift = old.col - new.col;
-            if col_shift == 1 {
-                self.buffer.push_str("\x1b[D");
-            } else {
-                write!(self.buffer, "\x1b[{}D", col_shift).unwrap();
-            }
-        }
-        self.write_and_flush(self.buffer.as_bytes())
-    }
-
-    fn refresh_line(
-        &mut self,
-        prompt: &str,
-        line: &LineBuffer,
-        hint: Option<&str>,
-        old_layout: &Layout,
-        new_layout: &Layout,
-        highlighter: Option<&dyn Highlighter>,
-    ) -> Result<()> {
-        use std::fmt::Write;
-        self.buffer.clear();
-
-        let default_prompt = new_layout.default_prompt;
-        let cursor = new_layout.cursor;
-        let end_pos = new_layout.end;
-
-        self.clear_old_rows(old_layout);
-
-        if let Some(highlighter) = highlighter {
-            // display the prompt
-            self.buffer
-                .push_str(&highlighter.highlight_prompt(prompt, default_prompt));
-            // display the input line
-            self.buffer
-                .push_str(&highlighter.highlight(line, line.pos()));
-        } else {
-            // display the prompt
-            self.buffer.push_str(prompt);
-            // display the input line
-            self.buffer.push_str(line);
-        }
-        // display hint
-        if let Some(hint) = hint {
-            if let Some(highlighter) = highlighter {
-                self.buffer.push_str(&highlighter.highlight_hint(hint));
-            } else {
-                self.buffer.push_str(hint);
-            }
-        }
-        // we have to generate our own newline on line wrap
-        if end_pos.col == 0 && end_pos.row > 0 && !self.buffer.ends_with('\n') {
-            self.buffer.push('\n');
-        }
-        // position the cursor
-        let new_cursor_row_movement = end_pos.row - cursor.row;
-        // move the cursor up as required
-        if new_cursor_row_movement > 0 {
-            write!(self.buffer, "\x1b[{}A", new_cursor_row_movement).unwrap();
-        }
-        // position the cursor within the line
-        if cursor.col > 0 {
-            write!(self.buffer, "\r\x1b[{}C", cursor.col).unwrap();
-        } else {
-            self.buffer.push('\r');
-        }
-
-        self.write_and_flush(self.buffer.as_bytes())?;
-
-        Ok(())
-    }
-
     fn write_and_flush(&self, buf: &[u8]) -> Result<()> {
-        write_and_flush(self.out, buf)
+        write_and_flush(*self, buf)
     }
 
-    /// Control characters are treated as having zero width.
-    /// Characters with 2 column width are correctly handled (not split).
-    fn calculate_position(&self, s: &str, orig: Position) -> Position {
-        let mut pos = orig;
-        let mut esc_seq = 0;
-        for c in s.graphemes(true) {
-            if c == "\n" {
-                pos.row += 1;
-                pos.col = 0;
-                continue;
-            }
-            let cw = if c == "\t" {
-                self.tab_stop - (pos.col % self.tab_stop)
-            } else {
-                width(c, &mut esc_seq)
-            };
-            pos.col += cw;
-            if pos.col > self.cols {
-                pos.row += 1;
-                pos.col = cw;
-            }
-        }
-        if pos.col == self.cols {
-            pos.col = 0;
-            pos.row += 1;
-        }
-        pos
-    }
-
-    fn beep(&mut self) -> Result<()> {
-        match self.bell_style {
-            BellStyle::Audible => {
-                io::stderr().write_all(b"\x07")?;
-                io::stderr().flush()?;
-                Ok(())
-            }
-            _ => Ok(()),
-        }
-    }
-
-    /// Clear the screen. Used to handle ctrl+l
-    fn clear_screen(&mut self) -> Result<()> {
-        self.write_and_flush(b"\x1b[H\x1b[2J")
+    fn get_win_size(&self) -> (usize, usize) {
+        get_win_size(self)
     }
 
     /// Check if a SIGWINCH signal has been received
@@ -942,52 +845,87 @@ impl Renderer for PosixRenderer {
             .unwrap_or(false)
     }
 
-    /// Try to update the number of columns in the current terminal,
-    fn update_size(&mut self) {
-        let (cols, _) = get_win_size(&self.out);
-        self.cols = cols;
-    }
-
-    fn get_columns(&self) -> usize {
-        self.cols
-    }
-
-    /// Try to get the number of rows in the current terminal,
-    /// or assume 24 if it fails.
-    fn get_rows(&self) -> usize {
-        let (_, rows) = get_win_size(&self.out);
-        rows
+    /// Check if a SIGCONT signal has been received
+    fn sigcont(&self) -> bool {
+        SIGCONT
+            .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
+            .unwrap_or(false)
     }
 
-    fn colors_enabled(&self) -> bool {
-        self.colors_enabled
+    fn bell(&self) -> Result<()> {
+        io::stderr().write_all(b"\x07")?;
+        io::stderr().flush()?;
+        Ok(())
     }
 
-    fn move_cursor_at_leftmost(&mut self, rdr: &mut PosixRawReader) -> Result<()> {
+    fn cursor_position(&self, rdr: &mut PosixRawReader) -> Result<Option<Position>> {
         if rdr.poll(0)? != 0 {
             debug!(target: "rustyline", "cannot request cursor location");
-            return Ok(());
+            return Ok(None);
         }
         /* Report cursor location */
         self.write_and_flush(b"\x1b[6n")?;
         /* Read the response: ESC [ rows ; cols R */
-        if rdr.poll(100)? == 0
-            || rdr.next_char()? != '\x1b'
-            || rdr.next_char()? != '['
-            || read_digits_until(rdr, ';')?.is_none()
-        {
-            warn!(target: "rustyline", "cannot read initial cursor location");
-            return Ok(());
+        if rdr.poll(100)? == 0 || rdr.next_char()? != '\x1b' || rdr.next_char()? != '[' {
+            warn!(target: "rustyline", "cannot read cursor location");
+            return Ok(None);
         }
-        let col = read_digits_until(rdr, 'R')?;
-        debug!(target: "rustyline", "initial cursor location: {:?}", col);
-        if col.is_some() && col != Some(1) {
-            self.write_and_flush(b"\n")?;
-        }
-        Ok(())
+        let row = read_digits_until(rdr, ';')?;
+        let col = row.map_or(Ok(None), |_| read_digits_until(rdr, 'R'))?;
+        Ok(match (row, col) {
+            (Some(row), Some(col)) => Some(Position {
+                col: col.saturating_sub(1) as usize,
+                row: row.saturating_sub(1) as usize,
+            }),
+            _ => None,
+        })
     }
 }
 
+/// Decode a mouse report: `cb` encodes the button and modifiers, `cx` and
+/// `cy` are 0-based.
+#[allow(clippy::cast_possible_truncation)]
+fn mouse_event(cb: u32, cx: u32, cy: u32, release: bool) -> KeyEvent {
+    let mut mods = M::NONE;
+    if cb & 4 != 0 {
+        mods |= M::SHIFT;
+    }
+    if cb & 8 != 0 {
+        mods |= M::ALT;
+    }
+    if cb & 16 != 0 {
+        mods |= M::CTRL;
+    }
+    let button = match cb & 3 {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        _ => None,
+    };
+    let kind = if cb & 64 != 0 {
+        if cb & 1 == 0 {
+            MouseEventKind::ScrollUp
+        } else {
+            MouseEventKind::ScrollDown
+        }
+    } else {
+        match button {
+            // X10 reports a release without the button
+            _ if release => MouseEventKind::Up,
+            None => MouseEventKind::Up,
+            Some(button) if cb & 32 != 0 => MouseEventKind::Drag(button),
+            Some(button) => MouseEventKind::Down(button),
+        }
+    };
+    let mouse = MouseEvent {
+        kind,
+        modifiers: mods,
+        row: cy.min(u32::from(u16::MAX)) as u16,
+        column: cx.min(u32::from(u16::MAX)) as u16,
+    };
+    E(K::Mouse(mouse), mods)
+}
+
 fn read_digits_until(rdr: &mut PosixRawReader, sep: char) -> Result<Option<u32>> {
     let mut num: u32 = 0;
     loop {
@@ -1007,6 +945,7 @@ fn read_digits_until(rdr: &mut PosixRawReader, sep: char) -> Result<Option<u32>>
 
 static SIGWINCH_ONCE: sync::Once = sync::Once::new();
 static SIGWINCH: AtomicBool = AtomicBool::new(false);
+static SIGCONT: AtomicBool = AtomicBool::new(false);
 
 fn install_sigwinch_handler() {
     SIGWINCH_ONCE.call_once(|| unsafe {
@@ -1016,6 +955,12 @@ fn install_sigwinch_handler() {
             signal::SigSet::empty(),
         );
         let _ = signal::sigaction(signal::SIGWINCH, &sigwinch);
+        let sigcont = signal::SigAction::new(
+            signal::SigHandler::Handler(sigcont_handler),
+            signal::SaFlags::empty(),
+            signal::SigSet::empty(),
+        );
+        let _ = signal::sigaction(signal::SIGCONT, &sigcont);
     });
 }
 
@@ -1024,8 +969,48 @@ extern "C" fn sigwinch_handler(_: libc::c_int) {
     debug!(target: "rustyline", "SIGWINCH");
 }
 
+extern "C" fn sigcont_handler(_: libc::c_int) {
+    SIGCONT.store(true, Ordering::SeqCst);
+    debug!(target: "rustyline", "SIGCONT");
+}
+
+/// Raw mode derived from the `original` one
+fn raw_mode(original: &termios::Termios) -> termios::Termios {
+    use nix::sys::termios::{ControlFlags, InputFlags, LocalFlags, SpecialCharacterIndices};
+    let mut raw = original.clone();
+    // disable BREAK interrupt, CR to NL conversion on input,
+    // input parity check, strip high bit (bit 8), output flow control
+    raw.input_flags &= !(InputFlags::BRKINT
+        | InputFlags::ICRNL
+        | InputFlags::INPCK
+        | InputFlags::ISTRIP
+        | InputFlags::IXON);
+    // we don't want raw output, it turns newlines into straight line feeds
+    // disable all output processing
+    // raw.c_oflag = raw.c_oflag & !(OutputFlags::OPOST);
+
+    // character-size mark (8 bits)
+    raw.control_flags |= ControlFlags::CS8;
+    // disable echoing, canonical mode, extended input processing and signals
+    raw.local_flags &=
+        !(LocalFlags::ECHO | LocalFlags::ICANON | LocalFlags::IEXTEN | LocalFlags::ISIG);
+    raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1; // One character-at-a-time input
+    raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0; // with blocking read
+    raw
+}
+
+/// Enable raw mode again once the process is resumed: the shell may have
+/// restored the cooked mode while it was stopped.
+fn resume_raw_mode() {
+    if let Err(e) = termios::tcgetattr(STDIN_FILENO)
+        .and_then(|mode| termios::tcsetattr(STDIN_FILENO, SetArg::TCSADRAIN, &raw_mode(&mode)))
+    {
+        warn!(target: "rustyline", "cannot enable raw mode again: {}", e);
+    }
+}
+
 #[cfg(not(test))]
-pub type Terminal = PosixTerminal;
+pub type NativeTerminal = PosixTerminal;
 
 #[derive(Clone, Debug)]
 pub struct PosixTerminal {
@@ -1035,14 +1020,15 @@ pub struct PosixTerminal {
     pub(crate) color_mode: ColorMode,
     stream_type: OutputStreamType,
     tab_stop: usize,
-    bell_style: BellStyle,
-    enable_bracketed_paste: bool,
+    pub(crate) bell_style: BellStyle,
+    pub(crate) enable_bracketed_paste: bool,
+    caps: Capabilities,
 }
 
 impl PosixTerminal {
     fn colors_enabled(&self) -> bool {
         match self.color_mode {
-            ColorMode::Enabled => self.stdstream_isatty,
+            ColorMode::Enabled => self.stdstream_isatty && self.caps.attributes(),
             ColorMode::Forced => true,
             ColorMode::Disabled => false,
         }
@@ -1061,8 +1047,9 @@ impl Term for PosixTerminal {
         bell_style: BellStyle,
         enable_bracketed_paste: bool,
     ) -> Self {
+        let caps = Capabilities::from_env();
         let term = Self {
-            unsupported: is_unsupported_term(),
+            unsupported: is_unsupported_term() || !caps.cursor_movement(),
             stdin_isatty: is_a_tty(STDIN_FILENO),
             stdstream_isatty: is_a_tty(stream_type.as_raw_fd()),
             color_mode,
@@ -1070,6 +1057,7 @@ impl Term for PosixTerminal {
             tab_stop,
             bell_style,
             enable_bracketed_paste,
+            caps,
         };
         if !term.unsupported && term.stdin_isatty && term.stdstream_isatty {
             install_sigwinch_handler();
@@ -1098,31 +1086,11 @@ impl Term for PosixTerminal {
 
     fn enable_raw_mode(&mut self) -> Result<Self::Mode> {
         use nix::errno::Errno::ENOTTY;
-        use nix::sys::termios::{ControlFlags, InputFlags, LocalFlags, SpecialCharacterIndices};
         if !self.stdin_isatty {
             return Err(nix::Error::from_errno(ENOTTY).into());
         }
         let original_mode = termios::tcgetattr(STDIN_FILENO)?;
-        let mut raw = original_mode.clone();
-        // disable BREAK interrupt, CR to NL conversion on input,
-        // input parity check, strip high bit (bit 8), output flow control
-        raw.input_flags &= !(InputFlags::BRKINT
-            | InputFlags::ICRNL
-            | InputFlags::INPCK
-            | InputFlags::ISTRIP
-            | InputFlags::IXON);
-        // we don't want raw output, it turns newlines into straight line feeds
-        // disable all output processing
-        // raw.c_oflag = raw.c_oflag & !(OutputFlags::OPOST);
-
-        // character-size mark (8 bits)
-        raw.control_flags |= ControlFlags::CS8;
-        // disable echoing, canonical mode, extended input processing and signals
-        raw.local_flags &=
-            !(LocalFlags::ECHO | LocalFlags::ICANON | LocalFlags::IEXTEN | LocalFlags::ISIG);
-        raw.control_chars[SpecialCharacterIndices::VMIN as usize] = 1; // One character-at-a-time input
-        raw.control_chars[SpecialCharacterIndices::VTIME as usize] = 0; // with blocking read
-        termios::tcsetattr(STDIN_FILENO, SetArg::TCSADRAIN, &raw)?;
+        termios::tcsetattr(STDIN_FILENO, SetArg::TCSADRAIN, &raw_mode(&original_mode))?;
 
         // enable bracketed paste
         let out = if !self.enable_bracketed_paste {
@@ -1133,10 +1101,19 @@ impl Term for PosixTerminal {
         } else {
             Some(self.stream_type)
         };
-        Ok(PosixMode {
+        let mode = PosixMode {
             termios: original_mode,
             out,
-        })
+            id: restore::RawModeId::new(),
+        };
+        let saved = mode.clone();
+        restore::raw_mode_enabled(
+            mode.id,
+            Box::new(move || {
+                let _ = saved.restore();
+            }),
+        );
+        Ok(mode)
     }
 
     /// Create a RAW reader
@@ -1144,13 +1121,15 @@ impl Term for PosixTerminal {
         Ok(PosixRawReader::new(config))
     }
 
-    fn create_writer(&self) -> PosixRenderer {
+    fn create_writer(&self, config: &Config) -> PosixRenderer {
         PosixRenderer::new(
             self.stream_type,
             self.tab_stop,
             self.colors_enabled(),
             self.bell_style,
+            config.output_meta(),
         )
+        .with_capabilities(self.caps.clone())
     }
 }
 
@@ -1178,17 +1157,49 @@ fn write_and_flush(out: OutputStreamType, buf: &[u8]) -> Result<()> {
 
 #[cfg(test)]
 mod test {
-    use super::{Position, PosixRenderer, PosixTerminal, Renderer};
-    use crate::config::{BellStyle, OutputStreamType};
-    use crate::line_buffer::LineBuffer;
+    use super::PosixTerminal;
+
+    use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+    use crate::keys::{MouseButton, MouseEvent, MouseEventKind};
 
     #[test]
-    #[ignore]
-    fn prompt_with_ansi_escape_codes() {
-        let out = PosixRenderer::new(OutputStreamType::Stdout, 4, true, BellStyle::default());
-        let pos = out.calculate_position("\x1b[1;32m>>\x1b[0m ", Position::default());
-        assert_eq!(3, pos.col);
-        assert_eq!(0, pos.row);
+    fn mouse_event() {
+        let mouse = |kind, modifiers, column, row| {
+            E(
+                K::Mouse(MouseEvent {
+                    kind,
+                    modifiers,
+                    row,
+                    column,
+                }),
+                modifiers,
+            )
+        };
+        assert_eq!(
+            mouse(MouseEventKind::Down(MouseButton::Left), M::NONE, 9, 2),
+            super::mouse_event(0, 9, 2, false)
+        );
+        assert_eq!(
+            mouse(MouseEventKind::Up, M::NONE, 9, 2),
+            super::mouse_event(0, 9, 2, true)
+        );
+        // X10 release
+        assert_eq!(
+            mouse(MouseEventKind::Up, M::NONE, 0, 0),
+            super::mouse_event(3, 0, 0, false)
+        );
+        assert_eq!(
+            mouse(MouseEventKind::Drag(MouseButton::Right), M::CTRL, 1, 1),
+            super::mouse_event(2 | 16 | 32, 1, 1, false)
+        );
+        assert_eq!(
+            mouse(MouseEventKind::ScrollUp, M::SHIFT, 0, 0),
+            super::mouse_event(64 | 4, 0, 0, false)
+        );
+        assert_eq!(
+            mouse(MouseEventKind::ScrollDown, M::ALT, 0, 0),
+            super::mouse_event(65 | 8, 0, 0, false)
+        );
     }
 
     #[test]
@@ -1213,27 +1224,17 @@ mod test {
     }
 
     #[test]
-    fn test_line_wrap() {
-        let mut out = PosixRenderer::new(OutputStreamType::Stdout, 4, true, BellStyle::default());
-        let prompt = "> ";
-        let default_prompt = true;
-        let prompt_size = out.calculate_position(prompt, Position::default());
-
-        let mut line = LineBuffer::init("", 0, None);
-        let old_layout = out.compute_layout(prompt_size, default_prompt, &line, None);
-        assert_eq!(Position { col: 2, row: 0 }, old_layout.cursor);
-        assert_eq!(old_layout.cursor, old_layout.end);
-
-        assert_eq!(Some(true), line.insert('a', out.cols - prompt_size.col + 1));
-        let new_layout = out.compute_layout(prompt_size, default_prompt, &line, None);
-        assert_eq!(Position { col: 1, row: 1 }, new_layout.cursor);
-        assert_eq!(new_layout.cursor, new_layout.end);
-        out.refresh_line(prompt, &line, None, &old_layout, &new_layout, None)
-            .unwrap();
-        #[rustfmt::skip]
-        assert_eq!(
-            "\r\u{1b}[0K> aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\u{1b}[1C",
-            out.buffer
-        );
+    fn sigcont() {
+        use super::AnsiOutput;
+        use crate::config::OutputStreamType;
+        use nix::sys::signal;
+
+        super::install_sigwinch_handler();
+        let out = OutputStreamType::Stdout;
+        assert!(!out.sigcont());
+        signal::raise(signal::SIGCONT).unwrap();
+        assert!(out.sigcont());
+        // only once
+        assert!(!out.sigcont());
     }
 }
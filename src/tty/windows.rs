@@ -4,6 +4,8 @@
 use std::io::{self, Write};
 use std::mem;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use log::{debug, warn};
 use unicode_segmentation::UnicodeSegmentation;
@@ -13,9 +15,9 @@ use winapi::shared::winerror;
 use winapi::um::handleapi::INVALID_HANDLE_VALUE;
 use winapi::um::wincon::{self, CONSOLE_SCREEN_BUFFER_INFO, COORD};
 use winapi::um::winnt::{CHAR, HANDLE};
-use winapi::um::{consoleapi, processenv, winbase, winuser};
+use winapi::um::{consoleapi, processenv, synchapi, winbase, winuser};
 
-use super::{width, RawMode, RawReader, Renderer, Term};
+use super::{display_line, restore, width, CursorShape, RawMode, RawReader, Renderer, Term};
 use crate::config::{BellStyle, ColorMode, Config, OutputStreamType};
 use crate::error;
 use crate::highlight::Highlighter;
@@ -27,6 +29,10 @@ use crate::Result;
 const STDIN_FILENO: DWORD = winbase::STD_INPUT_HANDLE;
 const STDOUT_FILENO: DWORD = winbase::STD_OUTPUT_HANDLE;
 const STDERR_FILENO: DWORD = winbase::STD_ERROR_HANDLE;
+// Reverse video (DECSCNM), toggled to flash the screen
+const REVERSE_VIDEO_ON: &[u8] = b"\x1b[?5h";
+const REVERSE_VIDEO_OFF: &[u8] = b"\x1b[?5l";
+const FLASH_DURATION: Duration = Duration::from_millis(100);
 
 fn get_std_handle(fd: DWORD) -> Result<HANDLE> {
     let handle = unsafe { processenv::GetStdHandle(fd) };
@@ -67,20 +73,17 @@ fn get_console_mode(handle: HANDLE) -> Result<DWORD> {
 }
 
 #[must_use = "You must restore default mode (disable_raw_mode)"]
-#[cfg(not(test))]
-pub type Mode = ConsoleMode;
-
 #[derive(Clone, Copy, Debug)]
 pub struct ConsoleMode {
     original_stdin_mode: DWORD,
     stdin_handle: HANDLE,
     original_stdstream_mode: Option<DWORD>,
     stdstream_handle: HANDLE,
+    id: restore::RawModeId,
 }
 
-impl RawMode for ConsoleMode {
-    /// Disable RAW mode for the terminal.
-    fn disable_raw_mode(&self) -> Result<()> {
+impl ConsoleMode {
+    fn restore(&self) -> Result<()> {
         check(unsafe { consoleapi::SetConsoleMode(self.stdin_handle, self.original_stdin_mode) })?;
         if let Some(original_stdstream_mode) = self.original_stdstream_mode {
             check(unsafe {
@@ -91,29 +94,144 @@ impl RawMode for ConsoleMode {
     }
 }
 
+impl RawMode for ConsoleMode {
+    /// Disable RAW mode for the terminal.
+    fn disable_raw_mode(&self) -> Result<()> {
+        restore::raw_mode_disabled(self.id);
+        self.restore()
+    }
+}
+
+// the standard handles can be used from any thread
+unsafe impl Send for ConsoleMode {}
+
 /// Console input reader
 pub struct ConsoleRawReader {
     handle: HANDLE,
+    // high surrogate of a character split over two records
+    surrogate: u16,
+    // key repeated by the console (`wRepeatCount` > 1) and remaining count
+    repeat: Option<(KeyEvent, WORD)>,
 }
 
 impl ConsoleRawReader {
     pub fn create() -> Result<ConsoleRawReader> {
         let handle = get_std_handle(STDIN_FILENO)?;
-        Ok(ConsoleRawReader { handle })
+        Ok(ConsoleRawReader {
+            handle,
+            surrogate: 0,
+            repeat: None,
+        })
     }
 }
 
-impl RawReader for ConsoleRawReader {
-    fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
-        use std::char::decode_utf16;
-        use winapi::um::wincon::{
-            LEFT_ALT_PRESSED, LEFT_CTRL_PRESSED, RIGHT_ALT_PRESSED, RIGHT_CTRL_PRESSED,
-            SHIFT_PRESSED,
+/// Translate a console key event to a key, or to `None` when it must be
+/// ignored (release of a key, modifier key alone, high surrogate, ...)
+fn key_event(
+    key_event: &wincon::KEY_EVENT_RECORD,
+    surrogate: &mut u16,
+) -> Result<Option<KeyEvent>> {
+    use std::char::decode_utf16;
+    use winapi::um::wincon::{
+        LEFT_ALT_PRESSED, LEFT_CTRL_PRESSED, RIGHT_ALT_PRESSED, RIGHT_CTRL_PRESSED, SHIFT_PRESSED,
+    };
+
+    // the character composed with Alt and the numeric keypad is reported
+    // when Alt is released
+    let alt_numpad =
+        key_event.bKeyDown == 0 && key_event.wVirtualKeyCode == winuser::VK_MENU as WORD;
+    if key_event.bKeyDown == 0 && !alt_numpad {
+        return Ok(None);
+    }
+
+    let alt_gr = key_event.dwControlKeyState & (LEFT_CTRL_PRESSED | RIGHT_ALT_PRESSED)
+        == (LEFT_CTRL_PRESSED | RIGHT_ALT_PRESSED);
+    let alt = key_event.dwControlKeyState & (LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED) != 0;
+    let mut mods = M::NONE;
+    if !alt_gr && key_event.dwControlKeyState & (LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED) != 0 {
+        mods |= M::CTRL;
+    }
+    if alt && !alt_gr && !alt_numpad {
+        mods |= M::ALT;
+    }
+    if key_event.dwControlKeyState & SHIFT_PRESSED != 0 {
+        mods |= M::SHIFT;
+    }
+
+    let utf16 = unsafe { *key_event.uChar.UnicodeChar() };
+    if utf16 == 0 {
+        let code = match i32::from(key_event.wVirtualKeyCode) {
+            winuser::VK_LEFT => K::Left,
+            winuser::VK_RIGHT => K::Right,
+            winuser::VK_UP => K::Up,
+            winuser::VK_DOWN => K::Down,
+            winuser::VK_DELETE => K::Delete,
+            winuser::VK_HOME => K::Home,
+            winuser::VK_END => K::End,
+            winuser::VK_PRIOR => K::PageUp,
+            winuser::VK_NEXT => K::PageDown,
+            winuser::VK_INSERT => K::Insert,
+            winuser::VK_F1 => K::F(1),
+            winuser::VK_F2 => K::F(2),
+            winuser::VK_F3 => K::F(3),
+            winuser::VK_F4 => K::F(4),
+            winuser::VK_F5 => K::F(5),
+            winuser::VK_F6 => K::F(6),
+            winuser::VK_F7 => K::F(7),
+            winuser::VK_F8 => K::F(8),
+            winuser::VK_F9 => K::F(9),
+            winuser::VK_F10 => K::F(10),
+            winuser::VK_F11 => K::F(11),
+            winuser::VK_F12 => K::F(12),
+            // Ctrl-Space
+            winuser::VK_SPACE if !mods.is_empty() => K::Char(' '),
+            // Ctrl or Alt combinations for which there is no character
+            // (e.g. Ctrl-Alt-A)
+            vk @ 0x30..=0x39 | vk @ 0x41..=0x5A if mods.intersects(M::CTRL | M::ALT) => {
+                let c = char::from(vk as u8);
+                // like Ctrl-A, Ctrl-Alt-A is reported in uppercase
+                let c = if mods.intersects(M::CTRL | M::SHIFT) {
+                    c
+                } else {
+                    c.to_ascii_lowercase()
+                };
+                return Ok(Some(KeyEvent::new(c, mods)));
+            }
+            // winuser::VK_BACK is correctly handled because the key_event.UnicodeChar
+            // is also set.
+            _ => return Ok(None),
         };
+        return Ok(Some(KeyEvent(code, mods)));
+    } else if utf16 == 27 {
+        return Ok(Some(KeyEvent(K::Esc, mods)));
+    } else if (0xD800..0xDC00).contains(&utf16) {
+        *surrogate = utf16;
+        return Ok(None);
+    }
+    let orc = if *surrogate == 0 {
+        decode_utf16(Some(utf16)).next()
+    } else {
+        decode_utf16([mem::take(surrogate), utf16].iter().cloned()).next()
+    };
+    let rc = if let Some(rc) = orc {
+        rc
+    } else {
+        return Err(error::ReadlineError::Eof);
+    };
+    let c = rc?;
+    Ok(Some(KeyEvent::new(c, mods)))
+}
 
+impl RawReader for ConsoleRawReader {
+    fn next_key(&mut self, _: bool) -> Result<KeyEvent> {
+        if let Some((key, n)) = self.repeat.take() {
+            if n > 1 {
+                self.repeat = Some((key, n - 1));
+            }
+            return Ok(key);
+        }
         let mut rec: wincon::INPUT_RECORD = unsafe { mem::zeroed() };
         let mut count = 0;
-        let mut surrogate = 0;
         loop {
             // TODO GetNumberOfConsoleInputEvents
             check(unsafe { consoleapi::ReadConsoleInputW(self.handle, &mut rec, 1, &mut count) })?;
@@ -127,84 +245,61 @@ impl RawReader for ConsoleRawReader {
             } else if rec.EventType != wincon::KEY_EVENT {
                 continue;
             }
-            let key_event = unsafe { rec.Event.KeyEvent() };
-            // writeln!(io::stderr(), "key_event: {:?}", key_event).unwrap();
-            if key_event.bKeyDown == 0 && key_event.wVirtualKeyCode != winuser::VK_MENU as WORD {
-                continue;
-            }
-            // key_event.wRepeatCount seems to be always set to 1 (maybe because we only
-            // read one character at a time)
-
-            let alt_gr = key_event.dwControlKeyState & (LEFT_CTRL_PRESSED | RIGHT_ALT_PRESSED)
-                == (LEFT_CTRL_PRESSED | RIGHT_ALT_PRESSED);
-            let alt = key_event.dwControlKeyState & (LEFT_ALT_PRESSED | RIGHT_ALT_PRESSED) != 0;
-            let mut mods = M::NONE;
-            if !alt_gr
-                && key_event.dwControlKeyState & (LEFT_CTRL_PRESSED | RIGHT_CTRL_PRESSED) != 0
-            {
-                mods |= M::CTRL;
+            let record = unsafe { rec.Event.KeyEvent() };
+            let key = match key_event(record, &mut self.surrogate)? {
+                Some(key) => key,
+                None => continue,
+            };
+            if record.wRepeatCount > 1 {
+                self.repeat = Some((key, record.wRepeatCount - 1));
             }
-            if alt && !alt_gr {
-                mods |= M::ALT;
+            debug!(target: "rustyline", "key: {:?}", key);
+            return Ok(key);
+        }
+    }
+
+    /// The console input handle is also signaled by the records ignored by
+    /// `next_key` (release of a key, focus, menu, mouse): they are dropped
+    /// until a key press is pending or the timeout expires.
+    fn wait_for_input(&mut self, timeout_ms: i32) -> Result<bool> {
+        if self.repeat.is_some() {
+            return Ok(true);
+        }
+        let deadline = if timeout_ms < 0 {
+            None
+        } else {
+            Some(Instant::now() + Duration::from_millis(timeout_ms as u64))
+        };
+        let mut rec: wincon::INPUT_RECORD = unsafe { mem::zeroed() };
+        let mut count = 0;
+        loop {
+            let timeout = match deadline {
+                None => winbase::INFINITE,
+                Some(deadline) => deadline
+                    .saturating_duration_since(Instant::now())
+                    .as_millis() as DWORD,
+            };
+            match unsafe { synchapi::WaitForSingleObject(self.handle, timeout) } {
+                winbase::WAIT_OBJECT_0 => {}
+                winerror::WAIT_TIMEOUT => return Ok(false),
+                _ => return Err(io::Error::last_os_error().into()),
             }
-            if key_event.dwControlKeyState & SHIFT_PRESSED != 0 {
-                mods |= M::SHIFT;
+            check(unsafe { consoleapi::PeekConsoleInputW(self.handle, &mut rec, 1, &mut count) })?;
+            if count == 0 {
+                continue;
             }
-
-            let utf16 = unsafe { *key_event.uChar.UnicodeChar() };
-            let key = if utf16 == 0 {
-                KeyEvent(
-                    match i32::from(key_event.wVirtualKeyCode) {
-                        winuser::VK_LEFT => K::Left,
-                        winuser::VK_RIGHT => K::Right,
-                        winuser::VK_UP => K::Up,
-                        winuser::VK_DOWN => K::Down,
-                        winuser::VK_DELETE => K::Delete,
-                        winuser::VK_HOME => K::Home,
-                        winuser::VK_END => K::End,
-                        winuser::VK_PRIOR => K::PageUp,
-                        winuser::VK_NEXT => K::PageDown,
-                        winuser::VK_INSERT => K::Insert,
-                        winuser::VK_F1 => K::F(1),
-                        winuser::VK_F2 => K::F(2),
-                        winuser::VK_F3 => K::F(3),
-                        winuser::VK_F4 => K::F(4),
-                        winuser::VK_F5 => K::F(5),
-                        winuser::VK_F6 => K::F(6),
-                        winuser::VK_F7 => K::F(7),
-                        winuser::VK_F8 => K::F(8),
-                        winuser::VK_F9 => K::F(9),
-                        winuser::VK_F10 => K::F(10),
-                        winuser::VK_F11 => K::F(11),
-                        winuser::VK_F12 => K::F(12),
-                        // winuser::VK_BACK is correctly handled because the key_event.UnicodeChar
-                        // is also set.
-                        _ => continue,
-                    },
-                    mods,
-                )
-            } else if utf16 == 27 {
-                KeyEvent(K::Esc, mods)
-            } else {
-                if (0xD800..0xDC00).contains(&utf16) {
-                    surrogate = utf16;
-                    continue;
+            if rec.EventType == wincon::WINDOW_BUFFER_SIZE_EVENT {
+                // reported by `next_key`
+                return Ok(true);
+            } else if rec.EventType == wincon::KEY_EVENT {
+                let mut surrogate = self.surrogate;
+                match key_event(unsafe { rec.Event.KeyEvent() }, &mut surrogate) {
+                    Ok(Some(_)) | Err(_) => return Ok(true),
+                    // a high surrogate is kept like `next_key` does
+                    Ok(None) => self.surrogate = surrogate,
                 }
-                let orc = if surrogate == 0 {
-                    decode_utf16(Some(utf16)).next()
-                } else {
-                    decode_utf16([surrogate, utf16].iter().cloned()).next()
-                };
-                let rc = if let Some(rc) = orc {
-                    rc
-                } else {
-                    return Err(error::ReadlineError::Eof);
-                };
-                let c = rc?;
-                KeyEvent::new(c, mods)
-            };
-            debug!(target: "rustyline", "key: {:?}", key);
-            return Ok(key);
+            }
+            check(unsafe { consoleapi::ReadConsoleInputW(self.handle, &mut rec, 1, &mut count) })?;
         }
     }
 
@@ -354,12 +449,12 @@ impl Renderer for ConsoleRenderer {
             // append the prompt
             col = self.wrap_at_eol(&highlighter.highlight_prompt(prompt, default_prompt), col);
             // append the input line
-            col = self.wrap_at_eol(&highlighter.highlight(line, line.pos()), col);
+            col = self.wrap_at_eol(&display_line(line, Some(highlighter)), col);
         } else {
             // append the prompt
             self.buffer.push_str(prompt);
             // append the input line
-            self.buffer.push_str(line);
+            self.buffer.push_str(&display_line(line, None));
         }
         // append hint
         if let Some(hint) = hint {
@@ -433,6 +528,12 @@ impl Renderer for ConsoleRenderer {
                 io::stderr().flush()?;
                 Ok(())
             }
+            // reverse video needs virtual terminal processing
+            BellStyle::Visible if self.colors_enabled => {
+                self.write_and_flush(REVERSE_VIDEO_ON)?;
+                thread::sleep(FLASH_DURATION);
+                self.write_and_flush(REVERSE_VIDEO_OFF)
+            }
             _ => Ok(()),
         }
     }
@@ -446,12 +547,30 @@ impl Renderer for ConsoleRenderer {
         self.clear(n, coord, info.wAttributes)
     }
 
+    fn copy_to_clipboard(&mut self, text: &str) -> Result<()> {
+        Ok(clipboard_win::set_clipboard_string(text)?)
+    }
+
+    /// DECSCUSR needs virtual terminal processing
+    fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<()> {
+        if self.colors_enabled {
+            let seq = format!("\x1b[{} q", shape as u8);
+            self.write_and_flush(seq.as_bytes())
+        } else {
+            Ok(())
+        }
+    }
+
     fn sigwinch(&self) -> bool {
         SIGWINCH
             .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
             .unwrap_or(false)
     }
 
+    fn sigcont(&self) -> bool {
+        false
+    }
+
     /// Try to get the number of columns in the current terminal,
     /// or assume 80 if it fails.
     fn update_size(&mut self) {
@@ -493,12 +612,20 @@ impl Renderer for ConsoleRenderer {
         }
         res
     }
+
+    fn cursor_position(&mut self, _: &mut ConsoleRawReader) -> Result<Option<Position>> {
+        let info = self.get_console_screen_buffer_info()?;
+        Ok(Some(Position {
+            col: info.dwCursorPosition.X as usize,
+            row: (info.dwCursorPosition.Y - info.srWindow.Top) as usize,
+        }))
+    }
 }
 
 static SIGWINCH: AtomicBool = AtomicBool::new(false);
 
 #[cfg(not(test))]
-pub type Terminal = Console;
+pub type NativeTerminal = Console;
 
 #[derive(Clone, Debug)]
 pub struct Console {
@@ -509,7 +636,9 @@ pub struct Console {
     pub(crate) color_mode: ColorMode,
     ansi_colors_supported: bool,
     stream_type: OutputStreamType,
-    bell_style: BellStyle,
+    pub(crate) bell_style: BellStyle,
+    #[allow(dead_code)] // bracketed paste is not supported
+    pub(crate) enable_bracketed_paste: bool,
 }
 
 impl Console {
@@ -533,7 +662,7 @@ impl Term for Console {
         stream_type: OutputStreamType,
         _tab_stop: usize,
         bell_style: BellStyle,
-        _enable_bracketed_paste: bool,
+        enable_bracketed_paste: bool,
     ) -> Console {
         use std::ptr;
         let stdin_handle = get_std_handle(STDIN_FILENO);
@@ -567,6 +696,7 @@ impl Term for Console {
             ansi_colors_supported: false,
             stream_type,
             bell_style,
+            enable_bracketed_paste,
         }
     }
 
@@ -613,10 +743,12 @@ impl Term for Console {
 
             let mut mode = original_stdstream_mode;
             if mode & wincon::ENABLE_WRAP_AT_EOL_OUTPUT == 0 {
-                mode |= wincon::ENABLE_WRAP_AT_EOL_OUTPUT;
                 debug!(target: "rustyline", "activate ENABLE_WRAP_AT_EOL_OUTPUT");
-                unsafe {
-                    assert!(consoleapi::SetConsoleMode(self.stdstream_handle, mode) != 0);
+                let wrap = mode | wincon::ENABLE_WRAP_AT_EOL_OUTPUT;
+                if unsafe { consoleapi::SetConsoleMode(self.stdstream_handle, wrap) } == FALSE {
+                    warn!(target: "rustyline", "cannot activate ENABLE_WRAP_AT_EOL_OUTPUT");
+                } else {
+                    mode = wrap;
                 }
             }
             // To enable ANSI colors (Windows 10 only):
@@ -624,10 +756,11 @@ impl Term for Console {
             self.ansi_colors_supported = mode & wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0;
             if self.ansi_colors_supported {
                 if self.color_mode == ColorMode::Disabled {
-                    mode &= !wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING;
                     debug!(target: "rustyline", "deactivate ENABLE_VIRTUAL_TERMINAL_PROCESSING");
-                    unsafe {
-                        assert!(consoleapi::SetConsoleMode(self.stdstream_handle, mode) != 0);
+                    let no_vt = mode & !wincon::ENABLE_VIRTUAL_TERMINAL_PROCESSING;
+                    if unsafe { consoleapi::SetConsoleMode(self.stdstream_handle, no_vt) } == FALSE
+                    {
+                        warn!(target: "rustyline", "cannot deactivate ENABLE_VIRTUAL_TERMINAL_PROCESSING");
                     }
                 } else {
                     debug!(target: "rustyline", "ANSI colors already enabled");
@@ -643,19 +776,27 @@ impl Term for Console {
             None
         };
 
-        Ok(ConsoleMode {
+        let mode = ConsoleMode {
             original_stdin_mode,
             stdin_handle: self.stdin_handle,
             original_stdstream_mode,
             stdstream_handle: self.stdstream_handle,
-        })
+            id: restore::RawModeId::new(),
+        };
+        restore::raw_mode_enabled(
+            mode.id,
+            Box::new(move || {
+                let _ = mode.restore();
+            }),
+        );
+        Ok(mode)
     }
 
     fn create_reader(&self, _: &Config) -> Result<ConsoleRawReader> {
         ConsoleRawReader::create()
     }
 
-    fn create_writer(&self) -> ConsoleRenderer {
+    fn create_writer(&self, _: &Config) -> ConsoleRenderer {
         ConsoleRenderer::new(
             self.stdstream_handle,
             self.stream_type,
@@ -670,7 +811,97 @@ unsafe impl Sync for Console {}
 
 #[cfg(test)]
 mod test {
+    use std::mem;
+
+    use winapi::shared::minwindef::{BOOL, DWORD, WORD};
+    use winapi::um::wincon::{self, KEY_EVENT_RECORD};
+    use winapi::um::winuser;
+
     use super::Console;
+    use crate::keys::{KeyCode as K, KeyEvent as E, Modifiers as M};
+
+    fn record(down: bool, vk: i32, utf16: u16, state: DWORD) -> KEY_EVENT_RECORD {
+        let mut rec: KEY_EVENT_RECORD = unsafe { mem::zeroed() };
+        rec.bKeyDown = BOOL::from(down);
+        rec.wRepeatCount = 1;
+        rec.wVirtualKeyCode = vk as WORD;
+        unsafe {
+            *rec.uChar.UnicodeChar_mut() = utf16;
+        }
+        rec.dwControlKeyState = state;
+        rec
+    }
+
+    fn key_event(rec: KEY_EVENT_RECORD) -> Option<E> {
+        super::key_event(&rec, &mut 0).unwrap()
+    }
+
+    #[test]
+    fn key_events() {
+        assert_eq!(Some(E::from('a')), key_event(record(true, 0x41, 0x61, 0)));
+        assert_eq!(None, key_event(record(false, 0x41, 0x61, 0)));
+        assert_eq!(
+            Some(E(K::Left, M::NONE)),
+            key_event(record(true, winuser::VK_LEFT, 0, 0))
+        );
+        // Alt
+        assert_eq!(
+            Some(E::alt('b')),
+            key_event(record(true, 0x42, 0x62, wincon::LEFT_ALT_PRESSED))
+        );
+        assert_eq!(
+            Some(E(K::Char('C'), M::CTRL_ALT)),
+            key_event(record(
+                true,
+                0x43,
+                0,
+                wincon::RIGHT_CTRL_PRESSED | wincon::LEFT_ALT_PRESSED
+            ))
+        );
+        // AltGr
+        assert_eq!(
+            Some(E::from('@')),
+            key_event(record(
+                true,
+                0x51,
+                0x40,
+                wincon::LEFT_CTRL_PRESSED | wincon::RIGHT_ALT_PRESSED
+            ))
+        );
+        // Alt + numeric keypad
+        assert_eq!(
+            Some(E::from('\u{e9}')),
+            key_event(record(
+                false,
+                winuser::VK_MENU,
+                0xe9,
+                wincon::LEFT_ALT_PRESSED
+            ))
+        );
+        // Ctrl-Space
+        assert_eq!(
+            Some(E(K::Char(' '), M::CTRL)),
+            key_event(record(
+                true,
+                winuser::VK_SPACE,
+                0,
+                wincon::LEFT_CTRL_PRESSED
+            ))
+        );
+    }
+
+    #[test]
+    fn surrogate_pair() {
+        let mut surrogate = 0;
+        let high = record(true, 0, 0xD83D, 0);
+        assert_eq!(None, super::key_event(&high, &mut surrogate).unwrap());
+        let low = record(true, 0, 0xDE00, 0);
+        assert_eq!(
+            Some(E::from('\u{1f600}')),
+            super::key_event(&low, &mut surrogate).unwrap()
+        );
+        assert_eq!(0, surrogate);
+    }
 
     #[test]
     fn test_send() {
@@ -50,7 +50,6 @@ impl Change {
         }
     }
 
-    #[cfg(test)]
     fn redo(&self, line: &mut LineBuffer) {
         match *self {
             Change::Begin | Change::End => {
@@ -58,6 +57,7 @@ impl Change {
             }
             Change::Insert { idx, ref text } => {
                 line.insert_str(idx, text);
+                line.set_pos(idx);
             }
             Change::Delete { idx, ref text } => {
                 line.delete_range(idx..idx + text.len());
@@ -68,6 +68,7 @@ impl Change {
                 ref new,
             } => {
                 line.replace(idx..idx + old.len(), new);
+                line.set_pos(idx);
             }
         }
     }
@@ -115,7 +116,6 @@ impl Changeset {
 
     pub fn begin(&mut self) -> usize {
         debug!(target: "rustyline", "Changeset::begin");
-        self.redos.clear();
         let mark = self.undos.len();
         self.undos.push(Change::Begin);
         self.undo_group_level += 1;
@@ -126,7 +126,6 @@ impl Changeset {
     /// this `end`.
     pub fn end(&mut self) -> bool {
         debug!(target: "rustyline", "Changeset::end");
-        self.redos.clear();
         let mut touched = false;
         while self.undo_group_level > 0 {
             self.undo_group_level -= 1;
@@ -288,8 +287,10 @@ impl Changeset {
         self.undos.truncate(len);
     }
 
-    #[cfg(test)]
-    pub fn redo(&mut self, line: &mut LineBuffer) -> bool {
+    /// Redo the last `n` undone changes (or groups of changes).
+    pub fn redo(&mut self, line: &mut LineBuffer, n: RepeatCount) -> bool {
+        debug!(target: "rustyline", "Changeset::redo");
+        let mut count = 0;
         let mut waiting_for_end = 0;
         let mut redone = false;
         loop {
@@ -311,7 +312,10 @@ impl Changeset {
                 break;
             }
             if waiting_for_end <= 0 {
-                break;
+                count += 1;
+                if count >= n {
+                    break;
+                }
             }
         }
         redone
@@ -397,7 +401,7 @@ mod tests {
         assert_eq!(1, cs.redos.len());
         assert_eq!(buf.as_str(), "Hello");
 
-        cs.redo(&mut buf);
+        cs.redo(&mut buf, 1);
         assert_eq!(1, cs.undos.len());
         assert_eq!(0, cs.redos.len());
         assert_eq!(buf.as_str(), "Hello, world!");
@@ -415,7 +419,7 @@ mod tests {
         cs.undo(&mut buf, 1);
         assert_eq!(buf.as_str(), "Hello, world!");
 
-        cs.redo(&mut buf);
+        cs.redo(&mut buf, 1);
         assert_eq!(buf.as_str(), "Hello");
     }
 
@@ -461,7 +465,7 @@ mod tests {
         cs.undo(&mut buf, 1);
         assert_eq!(buf.as_str(), "Hello, world!");
 
-        cs.redo(&mut buf);
+        cs.redo(&mut buf, 1);
         assert_eq!(buf.as_str(), "Hi, world!");
     }
 
@@ -476,6 +480,29 @@ mod tests {
         assert_eq!(Some("Bye".to_owned()), insert);
     }
 
+    #[test]
+    fn test_redo_groups() {
+        let mut buf = LineBuffer::init("", 0, None);
+        buf.insert_str(0, "Hello, world!");
+        let mut cs = Changeset::new();
+        cs.insert_str(0, "Hello");
+        cs.begin();
+        cs.insert_str(5, ",");
+        cs.insert_str(6, " world!");
+        cs.end();
+
+        assert!(cs.undo(&mut buf, 2));
+        assert_eq!(buf.as_str(), "");
+        assert!(cs.redo(&mut buf, 1));
+        assert_eq!(buf.as_str(), "Hello");
+        // an empty group does not drop the changes to redo
+        cs.begin();
+        cs.end();
+        assert!(cs.redo(&mut buf, 2));
+        assert_eq!(buf.as_str(), "Hello, world!");
+        assert!(!cs.redo(&mut buf, 1));
+    }
+
     #[test]
     fn test_end() {
         let mut cs = Changeset::new();